@@ -0,0 +1,38 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Fuzz target for parsing [PGPKeyring]s.
+//!
+//! This feeds arbitrary bytes to [PGPKeyring::parse], both as binary
+//! and wrapped in ASCII armor, and checks that it does not panic.
+#![no_main]
+
+use constellation_common::config::signing::pgp::PGPKeyring;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PGPKeyring::parse(data, &[]);
+
+    let mut armored =
+        Vec::from(&b"-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n"[..]);
+
+    armored.extend_from_slice(data);
+    armored.extend_from_slice(b"\n-----END PGP PUBLIC KEY BLOCK-----\n");
+
+    let _ = PGPKeyring::parse(&armored, &[]);
+});
@@ -0,0 +1,49 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Fuzz target for verifying detached OpenPGP signatures.
+//!
+//! This feeds arbitrary bytes as a detached signature over a fixed
+//! message to a keyring loaded from the test data, and checks that
+//! verification rejects them without panicking.
+#![no_main]
+
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use constellation_common::config::signing::pgp::PGPKeyring;
+use constellation_common::config::signing::PGPTrustLevel;
+use libfuzzer_sys::fuzz_target;
+
+const KEYRING: &[u8] = include_bytes!("../../test/data/pgp/keyring.gpg");
+const MSG: &[u8] = include_bytes!("../../test/data/pgp/msg.txt");
+
+static TEST_KEYRING: OnceLock<PGPKeyring> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    let keyring = TEST_KEYRING.get_or_init(|| {
+        PGPKeyring::parse(KEYRING, &[String::from("73311A2CB3B58601")]).unwrap()
+    });
+
+    let _ = keyring.verify_detached_at(
+        MSG,
+        data,
+        PGPTrustLevel::Marginal,
+        SystemTime::now()
+    );
+});
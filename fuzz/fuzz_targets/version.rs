@@ -0,0 +1,42 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Fuzz target for decoding [Version]s.
+//!
+//! This feeds arbitrary bytes to the PER, aligned PER, and DER codecs
+//! for [Version], and checks that each either rejects them or decodes a
+//! value that survives a round trip.
+#![no_main]
+
+use constellation_common::codec::aper::APERCodec;
+use constellation_common::codec::der::DERCodec;
+use constellation_common::codec::per::PERCodec;
+use constellation_common::codec::testing::check_decode_bytes;
+use constellation_common::codec::DatagramCodec;
+use constellation_common::version::Version;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut per = PERCodec::<Version, 32>::create(()).unwrap();
+    let mut aper = APERCodec::<Version, 48>::create(()).unwrap();
+    let mut der = DERCodec::<Version, 128>::create(()).unwrap();
+
+    check_decode_bytes(&mut per, data).unwrap();
+    check_decode_bytes(&mut aper, data).unwrap();
+    check_decode_bytes(&mut der, data).unwrap();
+});
@@ -0,0 +1,1010 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Authenticated-encryption wrapper codec.
+//!
+//! This module provides [AEADCodec], which wraps an inner codec and
+//! seals its output using an AEAD cipher (AES-GCM or
+//! ChaCha20-Poly1305).  This provides confidentiality and integrity
+//! for datagram channels without the need for DTLS, provided keys
+//! are distributed by some other means.
+//!
+//! # Frame Format
+//!
+//! Each frame consists of the following, in order:
+//!
+//! - A 32-bit key identifier, selecting the key used to seal the frame
+//! - A 128-bit sender salt, chosen at random when the codec is created
+//! - A 64-bit sequence number
+//! - A 16-bit length of the ciphertext
+//! - The ciphertext
+//! - A 128-bit authentication tag
+//!
+//! Frames are not sealed with the key itself, but with a subkey
+//! derived from the key and the sender salt using HKDF-SHA256.  Each
+//! codec instance therefore seals with its own subkey, and the
+//! sequence number alone is used as the nonce.  This avoids reusing a
+//! (key, nonce) pair when many senders share a key, or when a sender
+//! restarts and begins again from sequence number 0.
+//!
+//! The key identifier, salt, sequence number, and length are all
+//! authenticated as additional data.
+//!
+//! # Replay Protection
+//!
+//! When decoding, a [ReplayWindow] is kept for each combination of
+//! key identifier and sender salt.  Frames whose sequence numbers
+//! have already been seen, or which are too old to be tracked by the
+//! window, are rejected.  Windows are never discarded, as a sender
+//! whose window was forgotten could have its frames replayed; once
+//! [MAX_REPLAY_WINDOWS] senders have been seen, frames from any new
+//! sender are rejected.  Keys should therefore be retired well before
+//! the number of senders using them reaches this, and the codec
+//! re-created with the new keys.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+use openssl::error::ErrorStack;
+use openssl::md::Md;
+use openssl::pkey::Id;
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rand::rand_bytes;
+use openssl::symm::decrypt_aead;
+use openssl::symm::encrypt_aead;
+use openssl::symm::Cipher;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Size of the key identifier.
+const KEY_ID_BYTES: usize = 4;
+
+/// Size of the sender salt.
+const SALT_BYTES: usize = 16;
+
+/// Size of the sequence number.
+const SEQ_BYTES: usize = 8;
+
+/// Size of the nonce passed to the cipher.
+const NONCE_BYTES: usize = 12;
+
+/// Size of the ciphertext length.
+const LEN_BYTES: usize = 2;
+
+/// Offset of the sequence number in the frame header.
+const SEQ_OFFSET: usize = KEY_ID_BYTES + SALT_BYTES;
+
+/// Offset of the ciphertext length in the frame header.
+const LEN_OFFSET: usize = SEQ_OFFSET + SEQ_BYTES;
+
+/// Size of the frame header.
+const HEADER_BYTES: usize = LEN_OFFSET + LEN_BYTES;
+
+/// HKDF info string used to derive subkeys.
+const SUBKEY_INFO: &[u8] = b"constellation aead subkey";
+
+/// Size of the authentication tag.
+const TAG_BYTES: usize = 16;
+
+/// Total size overhead added by [AEADCodec].
+pub const AEAD_OVERHEAD_BYTES: usize = HEADER_BYTES + TAG_BYTES;
+
+/// Maximum number of replay windows tracked by an [AEADCodec].
+pub const MAX_REPLAY_WINDOWS: usize = 1024;
+
+/// Size of a [ReplayWindow], in sequence numbers.
+const WINDOW_SIZE: u64 = 64;
+
+/// AEAD algorithms supported by [AEADCodec].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AEADAlgo {
+    /// AES in Galois/Counter Mode, with a 128-bit key.
+    AES128GCM,
+    /// AES in Galois/Counter Mode, with a 256-bit key.
+    AES256GCM,
+    /// ChaCha20-Poly1305 (RFC 8439), with a 256-bit key.
+    ChaCha20Poly1305
+}
+
+/// A key for use with [AEADCodec].
+#[derive(Clone)]
+pub struct AEADKey {
+    /// Key identifier.
+    id: u32,
+    /// Algorithm to use with this key.
+    algo: AEADAlgo,
+    /// Key material.
+    key: Vec<u8>
+}
+
+/// Parameters for creating an [AEADCodec].
+#[derive(Clone)]
+pub struct AEADParam<P> {
+    /// Keys that will be accepted when decoding.
+    keys: Vec<AEADKey>,
+    /// Identifier of the key to use when encoding.
+    send_key: u32,
+    /// Parameter for creating the inner codec.
+    inner: P
+}
+
+/// Sliding window for detecting replayed sequence numbers.
+///
+/// This tracks the highest sequence number seen, along with which of
+/// the 64 sequence numbers preceding it have been seen.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReplayWindow {
+    /// Highest sequence number seen, plus one.
+    next: u64,
+    /// Bitmap of seen sequence numbers, by distance below the highest.
+    seen: u64
+}
+
+/// State kept for each sender seen by an [AEADCodec].
+struct AEADSender {
+    /// Subkey derived from the key and the sender's salt.
+    subkey: Vec<u8>,
+    /// Replay window for the sender's sequence numbers.
+    window: ReplayWindow
+}
+
+/// Codec that seals the output of an inner codec using an AEAD cipher.
+///
+/// See the [module documentation](crate::codec::aead) for details.
+pub struct AEADCodec<T, Inner: DatagramCodec<T>> {
+    inner: Inner,
+    keys: HashMap<u32, AEADKey>,
+    send_key: u32,
+    salt: [u8; SALT_BYTES],
+    subkey: Vec<u8>,
+    seq: u64,
+    senders: HashMap<(u32, [u8; SALT_BYTES]), AEADSender>,
+    msg: PhantomData<T>
+}
+
+/// Errors that can occur when creating an [AEADCodec].
+#[derive(Debug)]
+pub enum AEADCreateError<Inner> {
+    /// Error creating the inner codec.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error from OpenSSL.
+    OpenSSL {
+        /// The OpenSSL error.
+        error: ErrorStack
+    },
+    /// The sending key was not among the keys.
+    NoSendKey {
+        /// Identifier of the sending key.
+        id: u32
+    },
+    /// A key had the wrong length for its algorithm.
+    BadKeyLen {
+        /// Identifier of the key.
+        id: u32,
+        /// Required key length.
+        expected: usize,
+        /// Actual key length.
+        actual: usize
+    }
+}
+
+/// Errors that can occur when encoding with an [AEADCodec].
+#[derive(Debug)]
+pub enum AEADEncodeError<Inner> {
+    /// Error encoding the message.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error from OpenSSL.
+    OpenSSL {
+        /// The OpenSSL error.
+        error: ErrorStack
+    },
+    /// All sequence numbers have been used.
+    ///
+    /// The codec must be re-created (or re-keyed) to send further
+    /// messages.
+    SeqExhausted,
+    /// The encoded message is too long for the frame's length field.
+    TooLong {
+        /// Length of the encoded message.
+        len: usize
+    }
+}
+
+/// Errors that can occur when decoding with an [AEADCodec].
+#[derive(Debug)]
+pub enum AEADDecodeError<Inner> {
+    /// Error decoding the message.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// The frame was too short.
+    Truncated,
+    /// The key identifier was not recognized.
+    UnknownKey {
+        /// The key identifier.
+        id: u32
+    },
+    /// Error from OpenSSL.
+    OpenSSL {
+        /// The OpenSSL error.
+        error: ErrorStack
+    },
+    /// The frame failed authentication.
+    AuthFailed,
+    /// The frame's sequence number was already seen, or is too old.
+    Replay {
+        /// The sequence number.
+        seq: u64
+    },
+    /// The frame was from a new sender, but no more replay windows
+    /// can be tracked.
+    TooManySenders
+}
+
+impl AEADAlgo {
+    /// Get the required key length for this algorithm.
+    #[inline]
+    pub fn key_len(&self) -> usize {
+        match self {
+            AEADAlgo::AES128GCM => 16,
+            AEADAlgo::AES256GCM => 32,
+            AEADAlgo::ChaCha20Poly1305 => 32
+        }
+    }
+
+    #[inline]
+    fn cipher(&self) -> Cipher {
+        match self {
+            AEADAlgo::AES128GCM => Cipher::aes_128_gcm(),
+            AEADAlgo::AES256GCM => Cipher::aes_256_gcm(),
+            AEADAlgo::ChaCha20Poly1305 => Cipher::chacha20_poly1305()
+        }
+    }
+}
+
+impl AEADKey {
+    /// Derive the subkey used by the sender with salt `salt`.
+    fn subkey(
+        &self,
+        salt: &[u8]
+    ) -> Result<Vec<u8>, ErrorStack> {
+        let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+        let mut subkey = vec![0; self.algo.key_len()];
+
+        ctx.derive_init()?;
+        ctx.set_hkdf_md(Md::sha256())?;
+        ctx.set_hkdf_key(&self.key)?;
+        ctx.set_hkdf_salt(salt)?;
+        ctx.add_hkdf_info(SUBKEY_INFO)?;
+        ctx.derive(Some(&mut subkey))?;
+
+        Ok(subkey)
+    }
+
+    /// Create a new `AEADKey` from its components.
+    #[inline]
+    pub fn new(
+        id: u32,
+        algo: AEADAlgo,
+        key: Vec<u8>
+    ) -> Self {
+        AEADKey {
+            id: id,
+            algo: algo,
+            key: key
+        }
+    }
+
+    /// Get the key identifier.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Get the algorithm to use with this key.
+    #[inline]
+    pub fn algo(&self) -> AEADAlgo {
+        self.algo
+    }
+}
+
+impl<P> AEADParam<P> {
+    /// Create a new `AEADParam` from its components.
+    ///
+    /// Messages will be sealed with the key identified by
+    /// `send_key`, which must be present in `keys`.  Messages sealed
+    /// with any key in `keys` will be accepted.
+    #[inline]
+    pub fn new(
+        keys: Vec<AEADKey>,
+        send_key: u32,
+        inner: P
+    ) -> Self {
+        AEADParam {
+            keys: keys,
+            send_key: send_key,
+            inner: inner
+        }
+    }
+
+    /// Get the keys.
+    #[inline]
+    pub fn keys(&self) -> &[AEADKey] {
+        &self.keys
+    }
+
+    /// Get the identifier of the key to use when encoding.
+    #[inline]
+    pub fn send_key(&self) -> u32 {
+        self.send_key
+    }
+
+    /// Get the parameter for the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (Vec<AEADKey>, u32, P) {
+        (self.keys, self.send_key, self.inner)
+    }
+}
+
+impl ReplayWindow {
+    /// Check whether `seq` is acceptable.
+    ///
+    /// This returns `false` if `seq` has already been seen, or is too
+    /// old to be tracked by the window.  This does not update the
+    /// window.
+    pub fn check(
+        &self,
+        seq: u64
+    ) -> bool {
+        if seq >= self.next {
+            true
+        } else {
+            let age = self.next - seq - 1;
+
+            age < WINDOW_SIZE && self.seen & (1 << age) == 0
+        }
+    }
+
+    /// Record `seq` as having been seen.
+    ///
+    /// This should only be called once `seq` has passed
+    /// [check](ReplayWindow::check), and the message has been
+    /// authenticated.
+    pub fn update(
+        &mut self,
+        seq: u64
+    ) {
+        if seq >= self.next {
+            let shift = seq - self.next + 1;
+
+            self.seen = if shift >= WINDOW_SIZE {
+                1
+            } else {
+                (self.seen << shift) | 1
+            };
+            self.next = seq + 1;
+        } else {
+            self.seen |= 1 << (self.next - seq - 1)
+        }
+    }
+}
+
+impl<T, Inner> AEADCodec<T, Inner>
+where
+    Inner: DatagramCodec<T>
+{
+    /// Get the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Get the identifier of the key used when encoding.
+    #[inline]
+    pub fn send_key(&self) -> u32 {
+        self.send_key
+    }
+}
+
+impl<T, Inner> DatagramCodec<T> for AEADCodec<T, Inner>
+where
+    Inner: DatagramCodec<T>
+{
+    type CreateError = AEADCreateError<Inner::CreateError>;
+    type DecodeError = AEADDecodeError<Inner::DecodeError>;
+    type EncodeError = AEADEncodeError<Inner::EncodeError>;
+    type Param = AEADParam<Inner::Param>;
+
+    const MAX_BYTES: usize = Inner::MAX_BYTES + AEAD_OVERHEAD_BYTES;
+
+    fn create(param: Self::Param) -> Result<Self, Self::CreateError> {
+        let (keys, send_key, inner) = param.take();
+        let mut keymap = HashMap::with_capacity(keys.len());
+
+        for key in keys {
+            if key.key.len() != key.algo.key_len() {
+                return Err(AEADCreateError::BadKeyLen {
+                    id: key.id,
+                    expected: key.algo.key_len(),
+                    actual: key.key.len()
+                });
+            }
+
+            keymap.insert(key.id, key);
+        }
+
+        if !keymap.contains_key(&send_key) {
+            return Err(AEADCreateError::NoSendKey { id: send_key });
+        }
+
+        let mut salt = [0; SALT_BYTES];
+
+        rand_bytes(&mut salt)
+            .map_err(|err| AEADCreateError::OpenSSL { error: err })?;
+
+        let subkey = keymap[&send_key]
+            .subkey(&salt)
+            .map_err(|err| AEADCreateError::OpenSSL { error: err })?;
+
+        let inner = Inner::create(inner)
+            .map_err(|err| AEADCreateError::Inner { error: err })?;
+
+        Ok(AEADCodec {
+            inner: inner,
+            keys: keymap,
+            send_key: send_key,
+            salt: salt,
+            subkey: subkey,
+            seq: 0,
+            senders: HashMap::new(),
+            msg: PhantomData
+        })
+    }
+
+    fn encode(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let seq = self.seq;
+
+        self.seq = seq.checked_add(1).ok_or(AEADEncodeError::SeqExhausted)?;
+
+        let plain = self
+            .inner
+            .encode_to_vec(val)
+            .map_err(|err| AEADEncodeError::Inner { error: err })?;
+        let len = u16::try_from(plain.len())
+            .map_err(|_| AEADEncodeError::TooLong { len: plain.len() })?;
+        let key = &self.keys[&self.send_key];
+        let header = &mut buf[..HEADER_BYTES];
+
+        header[..KEY_ID_BYTES].copy_from_slice(&key.id.to_be_bytes());
+        header[KEY_ID_BYTES..SEQ_OFFSET].copy_from_slice(&self.salt);
+        header[SEQ_OFFSET..LEN_OFFSET].copy_from_slice(&seq.to_be_bytes());
+        header[LEN_OFFSET..].copy_from_slice(&len.to_be_bytes());
+
+        let mut tag = [0; TAG_BYTES];
+        let cipher = encrypt_aead(
+            key.algo.cipher(),
+            &self.subkey,
+            Some(&nonce(seq)),
+            header,
+            &plain,
+            &mut tag
+        )
+        .map_err(|err| AEADEncodeError::OpenSSL { error: err })?;
+        let end = HEADER_BYTES + cipher.len();
+
+        buf[HEADER_BYTES..end].copy_from_slice(&cipher);
+        buf[end..end + TAG_BYTES].copy_from_slice(&tag);
+
+        Ok(end + TAG_BYTES)
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(T, usize), Self::DecodeError> {
+        if buf.len() < AEAD_OVERHEAD_BYTES {
+            return Err(AEADDecodeError::Truncated);
+        }
+
+        let header = &buf[..HEADER_BYTES];
+        let mut id = [0; KEY_ID_BYTES];
+        let mut salt = [0; SALT_BYTES];
+        let mut seq = [0; SEQ_BYTES];
+        let mut len = [0; LEN_BYTES];
+
+        id.copy_from_slice(&header[..KEY_ID_BYTES]);
+        salt.copy_from_slice(&header[KEY_ID_BYTES..SEQ_OFFSET]);
+        seq.copy_from_slice(&header[SEQ_OFFSET..LEN_OFFSET]);
+        len.copy_from_slice(&header[LEN_OFFSET..]);
+
+        let id = u32::from_be_bytes(id);
+        let seq = u64::from_be_bytes(seq);
+        let len = u16::from_be_bytes(len) as usize;
+        let end = HEADER_BYTES + len;
+
+        if buf.len() < end + TAG_BYTES {
+            return Err(AEADDecodeError::Truncated);
+        }
+
+        let key = self
+            .keys
+            .get(&id)
+            .ok_or(AEADDecodeError::UnknownKey { id: id })?;
+
+        let (subkey, known) = match self.senders.get(&(id, salt)) {
+            Some(sender) if !sender.window.check(seq) => {
+                return Err(AEADDecodeError::Replay { seq: seq })
+            }
+            Some(sender) => (sender.subkey.clone(), true),
+            None => (
+                key.subkey(&salt)
+                    .map_err(|err| AEADDecodeError::OpenSSL { error: err })?,
+                false
+            )
+        };
+        if !known && self.senders.len() >= MAX_REPLAY_WINDOWS {
+            return Err(AEADDecodeError::TooManySenders);
+        }
+
+        let plain = decrypt_aead(
+            key.algo.cipher(),
+            &subkey,
+            Some(&nonce(seq)),
+            header,
+            &buf[HEADER_BYTES..end],
+            &buf[end..end + TAG_BYTES]
+        )
+        .map_err(|_| AEADDecodeError::AuthFailed)?;
+
+        // Only record the sender once authenticated.
+        self.senders
+            .entry((id, salt))
+            .or_insert_with(|| AEADSender {
+                subkey: subkey,
+                window: ReplayWindow::default()
+            })
+            .window
+            .update(seq);
+
+        let (val, _) = self
+            .inner
+            .decode(&plain)
+            .map_err(|err| AEADDecodeError::Inner { error: err })?;
+
+        Ok((val, end + TAG_BYTES))
+    }
+}
+
+/// Get the cipher nonce for sequence number `seq`.
+///
+/// Each sender seals with its own subkey, so the sequence number is
+/// sufficient to make the nonce unique.
+#[inline]
+fn nonce(seq: u64) -> [u8; NONCE_BYTES] {
+    let mut nonce = [0; NONCE_BYTES];
+
+    nonce[NONCE_BYTES - SEQ_BYTES..].copy_from_slice(&seq.to_be_bytes());
+
+    nonce
+}
+
+impl<Inner> ScopedError for AEADCreateError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            AEADCreateError::Inner { error } => error.scope(),
+            AEADCreateError::OpenSSL { .. } => ErrorScope::System,
+            _ => ErrorScope::Unrecoverable
+        }
+    }
+}
+
+impl<Inner> ScopedError for AEADEncodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            AEADEncodeError::Inner { error } => error.scope(),
+            AEADEncodeError::OpenSSL { .. } => ErrorScope::Msg,
+            AEADEncodeError::SeqExhausted => ErrorScope::Session,
+            AEADEncodeError::TooLong { .. } => ErrorScope::Msg
+        }
+    }
+}
+
+impl<Inner> ScopedError for AEADDecodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            AEADDecodeError::Inner { error } => error.scope(),
+            _ => ErrorScope::Msg
+        }
+    }
+}
+
+impl Display for AEADAlgo {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            AEADAlgo::AES128GCM => write!(f, "AES-128-GCM"),
+            AEADAlgo::AES256GCM => write!(f, "AES-256-GCM"),
+            AEADAlgo::ChaCha20Poly1305 => write!(f, "ChaCha20-Poly1305")
+        }
+    }
+}
+
+impl<Inner> Display for AEADCreateError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            AEADCreateError::Inner { error } => error.fmt(f),
+            AEADCreateError::OpenSSL { error } => error.fmt(f),
+            AEADCreateError::NoSendKey { id } => {
+                write!(f, "no key with ID {:08x}", id)
+            }
+            AEADCreateError::BadKeyLen {
+                id,
+                expected,
+                actual
+            } => write!(
+                f,
+                "key {:08x} has length {} (expected {})",
+                id, actual, expected
+            )
+        }
+    }
+}
+
+impl<Inner> Display for AEADEncodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            AEADEncodeError::Inner { error } => error.fmt(f),
+            AEADEncodeError::OpenSSL { error } => error.fmt(f),
+            AEADEncodeError::SeqExhausted => {
+                write!(f, "sequence numbers exhausted")
+            }
+            AEADEncodeError::TooLong { len } => {
+                write!(f, "message of {} bytes is too long to seal", len)
+            }
+        }
+    }
+}
+
+impl<Inner> Display for AEADDecodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            AEADDecodeError::Inner { error } => error.fmt(f),
+            AEADDecodeError::Truncated => write!(f, "frame truncated"),
+            AEADDecodeError::UnknownKey { id } => {
+                write!(f, "unknown key ID {:08x}", id)
+            }
+            AEADDecodeError::OpenSSL { error } => error.fmt(f),
+            AEADDecodeError::AuthFailed => {
+                write!(f, "message authentication failed")
+            }
+            AEADDecodeError::Replay { seq } => {
+                write!(f, "replayed sequence number {}", seq)
+            }
+            AEADDecodeError::TooManySenders => {
+                write!(
+                    f,
+                    "too many senders to track (maximum {})",
+                    MAX_REPLAY_WINDOWS
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::collections::HashSet;
+#[cfg(test)]
+use std::convert::Infallible;
+
+#[cfg(test)]
+use crate::version::Version;
+#[cfg(test)]
+use crate::version::VersionPERCodec;
+
+#[cfg(test)]
+fn test_keys() -> Vec<AEADKey> {
+    vec![
+        AEADKey::new(1, AEADAlgo::AES128GCM, vec![0x11; 16]),
+        AEADKey::new(2, AEADAlgo::AES256GCM, vec![0x22; 32]),
+        AEADKey::new(3, AEADAlgo::ChaCha20Poly1305, vec![0x33; 32]),
+    ]
+}
+
+/// Codec that passes bytes through unchanged.
+#[cfg(test)]
+struct RawCodec;
+
+#[cfg(test)]
+impl DatagramCodec<Vec<u8>> for RawCodec {
+    type CreateError = Infallible;
+    type DecodeError = Infallible;
+    type EncodeError = Infallible;
+    type Param = ();
+
+    const MAX_BYTES: usize = 1 << 17;
+
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(RawCodec)
+    }
+
+    fn encode(
+        &mut self,
+        val: &Vec<u8>,
+        buf: &mut [u8]
+    ) -> Result<usize, Infallible> {
+        buf[..val.len()].copy_from_slice(val);
+
+        Ok(val.len())
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(Vec<u8>, usize), Infallible> {
+        Ok((buf.to_vec(), buf.len()))
+    }
+}
+
+#[test]
+fn test_aead_round_trip() {
+    for id in 1..=3 {
+        let param = AEADParam::new(test_keys(), id, ());
+        let mut sender =
+            AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+        let param = AEADParam::new(test_keys(), 1, ());
+        let mut receiver =
+            AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+        let version = Version::new(1, 2, 3);
+        let encoded = sender.encode_to_vec(&version).unwrap();
+        let (actual, nbytes) = receiver.decode(&encoded).unwrap();
+
+        assert_eq!(&encoded[..4], &id.to_be_bytes());
+        assert_eq!(version, actual);
+        assert_eq!(encoded.len(), nbytes);
+    }
+}
+
+#[test]
+fn test_aead_tampered() {
+    let param = AEADParam::new(test_keys(), 2, ());
+    let mut codec =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let version = Version::new(1, 2, 3);
+    let encoded = codec.encode_to_vec(&version).unwrap();
+
+    for i in 0..encoded.len() {
+        let mut tampered = encoded.clone();
+
+        tampered[i] ^= 0x01;
+
+        assert!(codec.decode(&tampered).is_err());
+    }
+}
+
+#[test]
+fn test_aead_unknown_key() {
+    let param = AEADParam::new(test_keys(), 3, ());
+    let mut sender =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let keys = vec![AEADKey::new(1, AEADAlgo::AES128GCM, vec![0x11; 16])];
+    let param = AEADParam::new(keys, 1, ());
+    let mut receiver =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let encoded = sender.encode_to_vec(&Version::new(1, 2, 3)).unwrap();
+
+    assert!(matches!(
+        receiver.decode(&encoded),
+        Err(AEADDecodeError::UnknownKey { id: 3 })
+    ));
+}
+
+#[test]
+fn test_aead_replay() {
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut sender =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut receiver =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let msgs: Vec<Vec<u8>> = (0..100)
+        .map(|i| sender.encode_to_vec(&Version::new(i, 0, 0)).unwrap())
+        .collect();
+
+    // Out-of-order delivery within the window is fine.
+    receiver.decode(&msgs[90]).unwrap();
+    receiver.decode(&msgs[80]).unwrap();
+    receiver.decode(&msgs[99]).unwrap();
+
+    assert!(matches!(
+        receiver.decode(&msgs[80]),
+        Err(AEADDecodeError::Replay { seq: 80 })
+    ));
+    assert!(matches!(
+        receiver.decode(&msgs[99]),
+        Err(AEADDecodeError::Replay { seq: 99 })
+    ));
+    // Too old for the window.
+    assert!(matches!(
+        receiver.decode(&msgs[10]),
+        Err(AEADDecodeError::Replay { seq: 10 })
+    ));
+
+    receiver.decode(&msgs[95]).unwrap();
+}
+
+#[test]
+fn test_aead_too_many_senders() {
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut receiver =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut first =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let old = first.encode_to_vec(&Version::new(1, 0, 0)).unwrap();
+
+    receiver.decode(&old).unwrap();
+
+    for i in 1..MAX_REPLAY_WINDOWS {
+        let param = AEADParam::new(test_keys(), 1, ());
+        let mut sender =
+            AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+        let msg = sender.encode_to_vec(&Version::new(i as u16, 0, 0)).unwrap();
+
+        receiver.decode(&msg).unwrap();
+    }
+
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut last =
+        AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+    let msg = last.encode_to_vec(&Version::new(0, 1, 0)).unwrap();
+
+    assert!(matches!(
+        receiver.decode(&msg),
+        Err(AEADDecodeError::TooManySenders)
+    ));
+    // The first sender is still tracked, so its frames cannot be
+    // replayed.
+    assert!(matches!(
+        receiver.decode(&old),
+        Err(AEADDecodeError::Replay { seq: 0 })
+    ));
+    receiver
+        .decode(&first.encode_to_vec(&Version::new(2, 0, 0)).unwrap())
+        .unwrap();
+}
+
+#[test]
+fn test_aead_unique_nonces() {
+    let mut nonces = HashSet::new();
+    let mut sealed = HashSet::new();
+
+    for _ in 0..256 {
+        let param = AEADParam::new(test_keys(), 1, ());
+        let mut codec =
+            AEADCodec::<Version, VersionPERCodec>::create(param).unwrap();
+
+        for _ in 0..4 {
+            let encoded = codec.encode_to_vec(&Version::new(1, 2, 3)).unwrap();
+
+            // Every instance restarts at sequence number 0, but the
+            // salt, and so the subkey, differs between them.
+            assert!(nonces.insert(encoded[KEY_ID_BYTES..LEN_OFFSET].to_vec()));
+            assert!(sealed.insert(encoded[HEADER_BYTES..].to_vec()));
+        }
+    }
+}
+
+#[test]
+fn test_aead_too_long() {
+    let param = AEADParam::new(test_keys(), 1, ());
+    let mut codec = AEADCodec::<Vec<u8>, RawCodec>::create(param).unwrap();
+    let max = vec![0x55; u16::MAX as usize];
+    let encoded = codec.encode_to_vec(&max).unwrap();
+    let (actual, _) = codec.decode(&encoded).unwrap();
+
+    assert_eq!(max, actual);
+    assert!(matches!(
+        codec.encode_to_vec(&vec![0x55; u16::MAX as usize + 1]),
+        Err(AEADEncodeError::TooLong { len: 65536 })
+    ));
+}
+
+#[test]
+fn test_aead_bad_key_len() {
+    let keys = vec![AEADKey::new(1, AEADAlgo::AES256GCM, vec![0x11; 16])];
+    let param = AEADParam::new(keys, 1, ());
+
+    assert!(matches!(
+        AEADCodec::<Version, VersionPERCodec>::create(param),
+        Err(AEADCreateError::BadKeyLen { id: 1, .. })
+    ));
+}
+
+#[test]
+fn test_replay_window() {
+    let mut window = ReplayWindow::default();
+
+    assert!(window.check(0));
+    window.update(0);
+    assert!(!window.check(0));
+    assert!(window.check(5));
+    window.update(5);
+    assert!(window.check(3));
+    window.update(3);
+    assert!(!window.check(3));
+    assert!(!window.check(5));
+    assert!(!window.check(0));
+    assert!(window.check(1));
+    window.update(200);
+    assert!(!window.check(5));
+    assert!(window.check(199));
+    assert!(!window.check(200));
+}
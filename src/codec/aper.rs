@@ -0,0 +1,2100 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Codec implementations using ASN.1 aligned packed encoding rules (APER).
+//!
+//! This module provides a [DatagramCodec] implementation for any type
+//! implementing the [Readable] and [Writable] traits from the
+//! [asn1rs] package.  This codec encodes and decodes the type using
+//! the ALIGNED variant of the ASN.1 packed encoding rules (ITU-T
+//! X.691).
+//!
+//! The [asn1rs] package itself only provides the UNALIGNED variant
+//! (see [per](crate::codec::per)).  The aligned variant is somewhat
+//! less dense, but is the variant used by most external ASN.1
+//! tooling, and is thus preferred for interoperability.
+//!
+//! Fragmented length determinants (lengths of 16384 units or more)
+//! are not supported, and will result in an
+//! [Unsupported](APERError::Unsupported) error.
+use std::cmp::min;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+use asn1rs::syn::bitstring;
+use asn1rs::syn::boolean;
+use asn1rs::syn::choice;
+use asn1rs::syn::default;
+use asn1rs::syn::enumerated;
+use asn1rs::syn::ia5string;
+use asn1rs::syn::null;
+use asn1rs::syn::numbers;
+use asn1rs::syn::numericstring;
+use asn1rs::syn::octetstring;
+use asn1rs::syn::printablestring;
+use asn1rs::syn::sequence;
+use asn1rs::syn::sequenceof;
+use asn1rs::syn::set;
+use asn1rs::syn::setof;
+use asn1rs::syn::utf8string;
+use asn1rs::syn::visiblestring;
+use asn1rs::syn::Null;
+use asn1rs::syn::Readable;
+use asn1rs::syn::ReadableType;
+use asn1rs::syn::Reader;
+use asn1rs::syn::Writable;
+use asn1rs::syn::WritableType;
+use asn1rs::syn::Writer;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Largest length that can be encoded without fragmentation.
+const MAX_UNFRAGMENTED_LEN: u64 = 16384;
+
+/// Bound below which constrained lengths are encoded as constrained
+/// whole numbers.
+const SIXTY_FOUR_K: u64 = 65536;
+
+/// Sub-trait of [DatagramCodec] for things that can be encoded using
+/// the ASN.1 aligned packed encoding rules (APER).
+pub trait DatagramAPERCodec<T>: DatagramCodec<T>
+where
+    T: Readable + Writable {
+    /// Encode `val` into the [APERWriter].
+    #[inline]
+    fn encode_to_writer(
+        &mut self,
+        val: &T,
+        writer: &mut APERWriter
+    ) -> Result<(), APERError> {
+        writer.write(val)
+    }
+
+    /// Decode a value of type `T` from the [APERReader].
+    #[inline]
+    fn decode_from_reader(
+        &mut self,
+        reader: &mut APERReader<'_>
+    ) -> Result<T, APERError> {
+        reader.read::<T>()
+    }
+}
+
+/// Errors that can occur encoding or decoding using the ASN.1
+/// aligned packed encoding rules.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum APERError {
+    /// Input ended before the value was completely decoded.
+    EndOfInput,
+    /// An integer value was outside its permitted range.
+    ValueOutOfRange {
+        /// The value.
+        value: i64,
+        /// The lower bound, if one exists.
+        min: Option<i64>,
+        /// The upper bound, if one exists.
+        max: Option<i64>
+    },
+    /// A size was outside its permitted range.
+    SizeOutOfRange {
+        /// The size.
+        size: u64,
+        /// The lower bound, if one exists.
+        min: Option<u64>,
+        /// The upper bound, if one exists.
+        max: Option<u64>
+    },
+    /// A `CHOICE` or `ENUMERATED` index was not recognized.
+    BadIndex {
+        /// Name of the type.
+        name: &'static str,
+        /// The index.
+        index: u64
+    },
+    /// A character was not permitted in a restricted string type.
+    BadChar {
+        /// The character.
+        c: char
+    },
+    /// A `UTF8String` contained invalid UTF-8.
+    BadUTF8,
+    /// A mandatory extension addition was absent.
+    MissingExtension {
+        /// Name of the type.
+        name: &'static str
+    },
+    /// The encoding used a feature that is not supported.
+    Unsupported {
+        /// Description of the feature.
+        what: &'static str
+    },
+    /// The encoded message exceeds the maximum message size.
+    TooLong {
+        /// The length of the encoded message.
+        len: usize,
+        /// The maximum message size.
+        max: usize
+    }
+}
+
+/// Codec for encoding/decoding using ASN.1 aligned packed encoding
+/// rules (APER).
+///
+/// This type provides a [DatagramCodec] implementation for any type
+/// implementing the [Readable] and [Writable] traits from the
+/// [asn1rs] packages.  This implementation encodes and decodes the
+/// type using the ALIGNED variant of the ASN.1 packed encoding rules.
+pub struct APERCodec<T: Readable + Writable, const MAX_BITS: usize>(
+    PhantomData<T>
+);
+
+/// [Writer] implementation for the ASN.1 aligned packed encoding
+/// rules.
+#[derive(Default)]
+pub struct APERWriter {
+    bits: BitBuf,
+    scope: Option<WriteScope>
+}
+
+/// [Reader] implementation for the ASN.1 aligned packed encoding
+/// rules.
+pub struct APERReader<'a> {
+    bits: BitSlice<'a>,
+    scope: Option<ReadScope>
+}
+
+#[derive(Default)]
+struct BitBuf {
+    bytes: Vec<u8>,
+    len: usize
+}
+
+struct BitSlice<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    len: usize
+}
+
+/// Field-tracking state for the body of a `SEQUENCE` or `SET`.
+struct WriteScope {
+    /// Position of the next optional field presence bit.
+    opt_pos: usize,
+    /// Index of the next field.
+    field: u64,
+    /// Index of the last root field, if the type is extensible.
+    ext_after: Option<u64>,
+    /// Complete encodings of extension additions.
+    additions: Vec<Option<Vec<u8>>>
+}
+
+/// Field-tracking state for the body of a `SEQUENCE` or `SET`.
+struct ReadScope {
+    name: &'static str,
+    /// Position of the next optional field presence bit.
+    opt_pos: usize,
+    /// Index of the next field.
+    field: u64,
+    /// Index of the last root field, if the type is extensible.
+    ext_after: Option<u64>,
+    /// Whether the extension bit was set.
+    ext_present: bool,
+    /// Presence bits for extension additions, once read.
+    additions: Option<Vec<bool>>
+}
+
+/// Position of a field within a `SEQUENCE` or `SET`.
+enum FieldKind {
+    Root,
+    Addition(usize)
+}
+
+#[inline]
+fn bits_for(val: u128) -> usize {
+    (128 - val.leading_zeros()) as usize
+}
+
+#[inline]
+fn octets_for(val: u128) -> usize {
+    ((bits_for(val).max(1) - 1) >> 3) + 1
+}
+
+#[inline]
+fn signed_octets_for(val: i64) -> usize {
+    let mut n = 1;
+
+    while n < 8 && (val < -(1 << (8 * n - 1)) || val >= (1 << (8 * n - 1))) {
+        n += 1
+    }
+
+    n
+}
+
+#[inline]
+fn check_size(
+    extensible: bool,
+    min: Option<u64>,
+    max: Option<u64>,
+    size: u64
+) -> Result<bool, APERError> {
+    let in_range =
+        size >= min.unwrap_or(0) && max.is_none_or(|max| size <= max);
+
+    if !in_range && !extensible {
+        Err(APERError::SizeOutOfRange {
+            size: size,
+            min: min,
+            max: max
+        })
+    } else {
+        Ok(in_range)
+    }
+}
+
+#[inline]
+fn numeric_index(c: char) -> Result<u64, APERError> {
+    match c {
+        ' ' => Ok(0),
+        '0'..='9' => Ok(c as u64 - '0' as u64 + 1),
+        _ => Err(APERError::BadChar { c: c })
+    }
+}
+
+#[inline]
+fn check_printable(c: char) -> Result<(), APERError> {
+    match c {
+        'A'..='Z' |
+        'a'..='z' |
+        '0'..='9' |
+        ' ' |
+        '\'' |
+        '(' |
+        ')' |
+        '+' |
+        ',' |
+        '-' |
+        '.' |
+        '/' |
+        ':' |
+        '=' |
+        '?' => Ok(()),
+        _ => Err(APERError::BadChar { c: c })
+    }
+}
+
+impl BitBuf {
+    #[inline]
+    fn write_bit(
+        &mut self,
+        bit: bool
+    ) {
+        if self.len % 8 == 0 {
+            self.bytes.push(0);
+        }
+
+        if bit {
+            self.bytes[self.len / 8] |= 0x80 >> (self.len % 8);
+        }
+
+        self.len += 1;
+    }
+
+    #[inline]
+    fn set_bit(
+        &mut self,
+        pos: usize,
+        bit: bool
+    ) {
+        if bit {
+            self.bytes[pos / 8] |= 0x80 >> (pos % 8);
+        } else {
+            self.bytes[pos / 8] &= !(0x80 >> (pos % 8));
+        }
+    }
+
+    #[inline]
+    fn write_bits(
+        &mut self,
+        val: u128,
+        nbits: usize
+    ) {
+        for i in (0..nbits).rev() {
+            self.write_bit((val >> i) & 1 == 1)
+        }
+    }
+
+    #[inline]
+    fn align(&mut self) {
+        self.len = self.bytes.len() * 8;
+    }
+
+    #[inline]
+    fn write_octets(
+        &mut self,
+        octets: &[u8]
+    ) {
+        self.align();
+        self.bytes.extend_from_slice(octets);
+        self.len = self.bytes.len() * 8;
+    }
+
+    /// Encode a constrained whole number (X.691 10.5).
+    fn write_constrained(
+        &mut self,
+        lb: i64,
+        ub: i64,
+        val: i64
+    ) {
+        let range = (ub as i128 - lb as i128 + 1) as u128;
+        let offset = (val as i128 - lb as i128) as u128;
+
+        if range == 1 {
+        } else if range <= 255 {
+            self.write_bits(offset, bits_for(range - 1))
+        } else if range == 256 {
+            self.align();
+            self.write_bits(offset, 8)
+        } else if range <= SIXTY_FOUR_K as u128 {
+            self.align();
+            self.write_bits(offset, 16)
+        } else {
+            let nbytes = octets_for(offset);
+            let max_bytes = octets_for(range - 1);
+
+            self.write_constrained(1, max_bytes as i64, nbytes as i64);
+            self.align();
+            self.write_bits(offset, nbytes * 8)
+        }
+    }
+
+    /// Encode a length determinant (X.691 10.9).
+    fn write_length(
+        &mut self,
+        lb: Option<u64>,
+        ub: Option<u64>,
+        len: u64
+    ) -> Result<(), APERError> {
+        match ub {
+            Some(ub) if ub < SIXTY_FOUR_K => {
+                self.write_constrained(
+                    lb.unwrap_or(0) as i64,
+                    ub as i64,
+                    len as i64
+                );
+
+                Ok(())
+            }
+            _ => {
+                self.align();
+
+                if len < 128 {
+                    self.write_bits(len as u128, 8);
+
+                    Ok(())
+                } else if len < MAX_UNFRAGMENTED_LEN {
+                    self.write_bits(0x8000 | len as u128, 16);
+
+                    Ok(())
+                } else {
+                    Err(APERError::Unsupported {
+                        what: "fragmented length determinant"
+                    })
+                }
+            }
+        }
+    }
+
+    /// Encode a semi-constrained whole number (X.691 10.7).
+    fn write_semi_constrained(
+        &mut self,
+        lb: i64,
+        val: i64
+    ) -> Result<(), APERError> {
+        let offset = (val as i128 - lb as i128) as u128;
+        let nbytes = octets_for(offset);
+
+        self.write_length(None, None, nbytes as u64)?;
+        self.write_bits(offset, nbytes * 8);
+
+        Ok(())
+    }
+
+    /// Encode an unconstrained whole number (X.691 10.8).
+    fn write_unconstrained(
+        &mut self,
+        val: i64
+    ) -> Result<(), APERError> {
+        let nbytes = signed_octets_for(val);
+
+        self.write_length(None, None, nbytes as u64)?;
+        self.write_bits(
+            val as u128 & (u128::MAX >> (128 - nbytes * 8)),
+            nbytes * 8
+        );
+
+        Ok(())
+    }
+
+    /// Encode a normally small non-negative whole number (X.691 10.6).
+    fn write_normally_small(
+        &mut self,
+        val: u64
+    ) -> Result<(), APERError> {
+        if val <= 63 {
+            self.write_bit(false);
+            self.write_bits(val as u128, 6);
+
+            Ok(())
+        } else {
+            self.write_bit(true);
+            self.write_semi_constrained(0, val as i64)
+        }
+    }
+
+    /// Encode the extension bit and length for a sized type, and
+    /// align for the contents as necessary.
+    fn write_size(
+        &mut self,
+        extensible: bool,
+        min: Option<u64>,
+        max: Option<u64>,
+        size: u64,
+        unit_bits: u64
+    ) -> Result<(), APERError> {
+        let in_range = check_size(extensible, min, max, size)?;
+        let lb = min.unwrap_or(0);
+
+        if extensible {
+            self.write_bit(!in_range)
+        }
+
+        match max {
+            Some(ub) if in_range && ub == lb => {
+                if ub * unit_bits > 16 {
+                    if ub < SIXTY_FOUR_K {
+                        self.align()
+                    } else {
+                        self.write_length(None, None, size)?;
+                    }
+                }
+            }
+            Some(ub) if in_range => {
+                self.write_length(Some(lb), Some(ub), size)?;
+
+                if size != 0 {
+                    self.align()
+                }
+            }
+            _ => {
+                self.write_length(None, None, size)?;
+
+                if size != 0 {
+                    self.align()
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the complete encoding (X.691 11.1).
+    #[inline]
+    fn into_bytes(self) -> Vec<u8> {
+        if self.bytes.is_empty() {
+            vec![0]
+        } else {
+            self.bytes
+        }
+    }
+}
+
+impl<'a> BitSlice<'a> {
+    #[inline]
+    fn new(bytes: &'a [u8]) -> Self {
+        BitSlice {
+            bytes: bytes,
+            pos: 0,
+            len: bytes.len() * 8
+        }
+    }
+
+    #[inline]
+    fn bit_at(
+        &self,
+        pos: usize
+    ) -> Result<bool, APERError> {
+        if pos < self.len {
+            Ok(self.bytes[pos / 8] & (0x80 >> (pos % 8)) != 0)
+        } else {
+            Err(APERError::EndOfInput)
+        }
+    }
+
+    #[inline]
+    fn read_bit(&mut self) -> Result<bool, APERError> {
+        let out = self.bit_at(self.pos)?;
+
+        self.pos += 1;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_bits(
+        &mut self,
+        nbits: usize
+    ) -> Result<u128, APERError> {
+        let mut out = 0;
+
+        for _ in 0..nbits {
+            out = (out << 1) | self.read_bit()? as u128
+        }
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn skip(
+        &mut self,
+        nbits: usize
+    ) -> Result<(), APERError> {
+        if self.pos + nbits <= self.len {
+            self.pos += nbits;
+
+            Ok(())
+        } else {
+            Err(APERError::EndOfInput)
+        }
+    }
+
+    #[inline]
+    fn align(&mut self) {
+        self.pos = min(self.pos.div_ceil(8) * 8, self.len.max(self.pos))
+    }
+
+    #[inline]
+    fn read_octets(
+        &mut self,
+        nbytes: usize
+    ) -> Result<&'a [u8], APERError> {
+        self.align();
+
+        let start = self.pos / 8;
+
+        if start + nbytes <= self.bytes.len() {
+            self.pos += nbytes * 8;
+
+            Ok(&self.bytes[start..start + nbytes])
+        } else {
+            Err(APERError::EndOfInput)
+        }
+    }
+
+    /// Decode a constrained whole number (X.691 10.5).
+    fn read_constrained(
+        &mut self,
+        lb: i64,
+        ub: i64
+    ) -> Result<i64, APERError> {
+        let range = (ub as i128 - lb as i128 + 1) as u128;
+        let offset = if range == 1 {
+            0
+        } else if range <= 255 {
+            self.read_bits(bits_for(range - 1))?
+        } else if range == 256 {
+            self.align();
+            self.read_bits(8)?
+        } else if range <= SIXTY_FOUR_K as u128 {
+            self.align();
+            self.read_bits(16)?
+        } else {
+            let max_bytes = octets_for(range - 1);
+            let nbytes = self.read_constrained(1, max_bytes as i64)?;
+
+            self.align();
+            self.read_bits(nbytes as usize * 8)?
+        };
+
+        if offset < range {
+            Ok((lb as i128 + offset as i128) as i64)
+        } else {
+            Err(APERError::ValueOutOfRange {
+                value: (lb as i128 + offset as i128) as i64,
+                min: Some(lb),
+                max: Some(ub)
+            })
+        }
+    }
+
+    /// Decode a length determinant (X.691 10.9).
+    fn read_length(
+        &mut self,
+        lb: Option<u64>,
+        ub: Option<u64>
+    ) -> Result<u64, APERError> {
+        match ub {
+            Some(ub) if ub < SIXTY_FOUR_K => self
+                .read_constrained(lb.unwrap_or(0) as i64, ub as i64)
+                .map(|len| len as u64),
+            _ => {
+                self.align();
+
+                let first = self.read_bits(8)? as u64;
+
+                if first & 0x80 == 0 {
+                    Ok(first)
+                } else if first & 0x40 == 0 {
+                    let second = self.read_bits(8)? as u64;
+
+                    Ok(((first & 0x3f) << 8) | second)
+                } else {
+                    Err(APERError::Unsupported {
+                        what: "fragmented length determinant"
+                    })
+                }
+            }
+        }
+    }
+
+    /// Decode a semi-constrained whole number (X.691 10.7).
+    fn read_semi_constrained(
+        &mut self,
+        lb: i64
+    ) -> Result<i64, APERError> {
+        let nbytes = self.read_length(None, None)? as usize;
+
+        if nbytes == 0 || nbytes > 8 {
+            return Err(APERError::Unsupported {
+                what: "integer encoding longer than 64 bits"
+            });
+        }
+
+        let offset = self.read_bits(nbytes * 8)?;
+        let val = lb as i128 + offset as i128;
+
+        if val <= i64::MAX as i128 {
+            Ok(val as i64)
+        } else {
+            Err(APERError::Unsupported {
+                what: "integer value larger than 64 bits"
+            })
+        }
+    }
+
+    /// Decode an unconstrained whole number (X.691 10.8).
+    fn read_unconstrained(&mut self) -> Result<i64, APERError> {
+        let nbytes = self.read_length(None, None)? as usize;
+
+        if nbytes == 0 || nbytes > 8 {
+            return Err(APERError::Unsupported {
+                what: "integer encoding longer than 64 bits"
+            });
+        }
+
+        let raw = self.read_bits(nbytes * 8)? as u64;
+        let shift = 64 - nbytes * 8;
+
+        Ok(((raw << shift) as i64) >> shift)
+    }
+
+    /// Decode a normally small non-negative whole number (X.691 10.6).
+    fn read_normally_small(&mut self) -> Result<u64, APERError> {
+        if self.read_bit()? {
+            self.read_semi_constrained(0).map(|val| val as u64)
+        } else {
+            self.read_bits(6).map(|val| val as u64)
+        }
+    }
+
+    /// Decode the extension bit and length for a sized type, and
+    /// align for the contents as necessary.
+    fn read_size(
+        &mut self,
+        extensible: bool,
+        min: Option<u64>,
+        max: Option<u64>,
+        unit_bits: u64
+    ) -> Result<u64, APERError> {
+        let extended = extensible && self.read_bit()?;
+        let lb = min.unwrap_or(0);
+
+        let size = match max {
+            Some(ub) if !extended && ub == lb => {
+                if ub * unit_bits > 16 {
+                    if ub < SIXTY_FOUR_K {
+                        self.align();
+
+                        ub
+                    } else {
+                        self.read_length(None, None)?
+                    }
+                } else {
+                    ub
+                }
+            }
+            Some(ub) if !extended => {
+                let size = self.read_length(Some(lb), Some(ub))?;
+
+                if size != 0 {
+                    self.align()
+                }
+
+                size
+            }
+            _ => {
+                let size = self.read_length(None, None)?;
+
+                if size != 0 {
+                    self.align()
+                }
+
+                size
+            }
+        };
+
+        if !extended {
+            check_size(false, min, max, size)?;
+        }
+
+        Ok(size)
+    }
+}
+
+impl APERWriter {
+    /// Create a new `APERWriter` with a buffer of capacity `capacity`
+    /// bytes.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        APERWriter {
+            bits: BitBuf {
+                bytes: Vec::with_capacity(capacity),
+                len: 0
+            },
+            scope: None
+        }
+    }
+
+    /// Get the number of bits written so far.
+    #[inline]
+    pub fn bit_len(&self) -> usize {
+        self.bits.len
+    }
+
+    /// Get the complete encoding as a [Vec].
+    ///
+    /// Per X.691, an empty encoding is replaced by a single zero
+    /// byte.
+    #[inline]
+    pub fn into_bytes_vec(self) -> Vec<u8> {
+        self.bits.into_bytes()
+    }
+
+    /// Record an entry in the enclosing `SEQUENCE`, if there is one,
+    /// then write the field contents using `f` if it is present.
+    fn with_field<F>(
+        &mut self,
+        is_opt: bool,
+        present: bool,
+        f: F
+    ) -> Result<(), APERError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), APERError> {
+        match &mut self.scope {
+            Some(scope) => {
+                let field = scope.field;
+
+                scope.field += 1;
+
+                match scope.ext_after {
+                    Some(after) if field > after => {
+                        if present {
+                            let mut sub = APERWriter::default();
+
+                            f(&mut sub)?;
+                            scope.additions.push(Some(sub.into_bytes_vec()));
+                        } else {
+                            scope.additions.push(None)
+                        }
+
+                        return Ok(());
+                    }
+                    _ => {
+                        if is_opt {
+                            self.bits.set_bit(scope.opt_pos, present);
+                            scope.opt_pos += 1;
+                        }
+                    }
+                }
+            }
+            None => {
+                if is_opt {
+                    self.bits.write_bit(present)
+                }
+            }
+        }
+
+        if present {
+            let scope = self.scope.take();
+            let out = f(self);
+
+            self.scope = scope;
+
+            out
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write the body of a `SEQUENCE` or `SET`.
+    fn write_fields<F>(
+        &mut self,
+        nopts: u64,
+        ext_after: Option<u64>,
+        f: F
+    ) -> Result<(), APERError>
+    where
+        F: Fn(&mut Self) -> Result<(), APERError> {
+        let ext_pos = ext_after.map(|_| {
+            let pos = self.bits.len;
+
+            self.bits.write_bit(false);
+
+            pos
+        });
+
+        if nopts >= SIXTY_FOUR_K {
+            return Err(APERError::Unsupported {
+                what: "more than 64K optional fields"
+            });
+        }
+
+        let opt_pos = self.bits.len;
+
+        for _ in 0..nopts {
+            self.bits.write_bit(false)
+        }
+
+        let outer = self.scope.replace(WriteScope {
+            opt_pos: opt_pos,
+            field: 0,
+            ext_after: ext_after,
+            additions: Vec::new()
+        });
+        let out = f(self);
+        let scope = std::mem::replace(&mut self.scope, outer);
+
+        out?;
+
+        if let (Some(ext_pos), Some(scope)) = (ext_pos, scope) {
+            if scope.additions.iter().any(Option::is_some) {
+                self.bits.set_bit(ext_pos, true);
+                self.bits
+                    .write_normally_small(scope.additions.len() as u64 - 1)?;
+
+                for addition in &scope.additions {
+                    self.bits.write_bit(addition.is_some())
+                }
+
+                for addition in scope.additions.iter().flatten() {
+                    self.bits.write_length(
+                        None,
+                        None,
+                        addition.len() as u64
+                    )?;
+                    self.bits.write_octets(addition);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_chars<I>(
+        &mut self,
+        extensible: bool,
+        min: Option<u64>,
+        max: Option<u64>,
+        chars: I,
+        char_bits: usize
+    ) -> Result<(), APERError>
+    where
+        I: Iterator<Item = u64> + Clone {
+        let nchars = chars.clone().count() as u64;
+
+        self.bits
+            .write_size(extensible, min, max, nchars, char_bits as u64)?;
+
+        for c in chars {
+            self.bits.write_bits(c as u128, char_bits)
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> APERReader<'a> {
+    /// Create a new `APERReader` over `bytes`.
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        APERReader {
+            bits: BitSlice::new(bytes),
+            scope: None
+        }
+    }
+
+    /// Get the number of bits consumed so far.
+    #[inline]
+    pub fn bits_consumed(&self) -> usize {
+        self.bits.pos
+    }
+
+    /// Get the number of bits remaining.
+    #[inline]
+    pub fn bits_remaining(&self) -> usize {
+        self.bits.len - self.bits.pos
+    }
+
+    /// Read the extension addition header, if it has not already
+    /// been read.
+    fn addition_bits(&mut self) -> Result<&[bool], APERError> {
+        let scope = match &mut self.scope {
+            Some(scope) => scope,
+            None => return Ok(&[])
+        };
+
+        if scope.additions.is_none() {
+            let mut bits = Vec::new();
+
+            if scope.ext_present {
+                let count = self.bits.read_normally_small()? + 1;
+
+                for _ in 0..count {
+                    bits.push(self.bits.read_bit()?)
+                }
+            }
+
+            scope.additions = Some(bits)
+        }
+
+        Ok(scope.additions.as_deref().unwrap_or(&[]))
+    }
+
+    /// Read an entry in the enclosing `SEQUENCE`, if there is one,
+    /// then read the field contents using `f` if it is present.
+    fn with_field<T, F>(
+        &mut self,
+        is_opt: bool,
+        f: F
+    ) -> Result<Option<T>, APERError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, APERError> {
+        let kind = match &mut self.scope {
+            Some(scope) => {
+                let field = scope.field;
+
+                scope.field += 1;
+
+                match scope.ext_after {
+                    Some(after) if field > after => {
+                        FieldKind::Addition((field - after - 1) as usize)
+                    }
+                    _ => {
+                        if is_opt {
+                            let present = self.bits.bit_at(scope.opt_pos)?;
+
+                            scope.opt_pos += 1;
+
+                            if !present {
+                                return Ok(None);
+                            }
+                        }
+
+                        FieldKind::Root
+                    }
+                }
+            }
+            None => {
+                if is_opt && !self.bits.read_bit()? {
+                    return Ok(None);
+                }
+
+                FieldKind::Root
+            }
+        };
+
+        match kind {
+            FieldKind::Root => {
+                let scope = self.scope.take();
+                let out = f(self);
+
+                self.scope = scope;
+
+                out.map(Some)
+            }
+            FieldKind::Addition(idx) => {
+                let present =
+                    self.addition_bits()?.get(idx).copied().unwrap_or(false);
+
+                if present {
+                    let len = self.bits.read_length(None, None)? as usize;
+                    let octets = self.bits.read_octets(len)?;
+                    let mut sub = APERReader::new(octets);
+
+                    f(&mut sub).map(Some)
+                } else if is_opt {
+                    Ok(None)
+                } else {
+                    let name = self.scope.as_ref().map_or("", |s| s.name);
+
+                    Err(APERError::MissingExtension { name: name })
+                }
+            }
+        }
+    }
+
+    /// Read the body of a `SEQUENCE` or `SET`.
+    fn read_fields<S, F>(
+        &mut self,
+        name: &'static str,
+        nopts: u64,
+        ext_after: Option<u64>,
+        f: F
+    ) -> Result<S, APERError>
+    where
+        F: Fn(&mut Self) -> Result<S, APERError> {
+        let ext_present = match ext_after {
+            Some(_) => self.bits.read_bit()?,
+            None => false
+        };
+        let opt_pos = self.bits.pos;
+
+        self.bits.skip(nopts as usize)?;
+
+        let outer = self.scope.replace(ReadScope {
+            name: name,
+            opt_pos: opt_pos,
+            field: 0,
+            ext_after: ext_after,
+            ext_present: ext_present,
+            additions: None
+        });
+        let out = f(self).and_then(|out| {
+            // Skip any extension additions we don't know about.
+            let known = match &self.scope {
+                Some(ReadScope {
+                    field,
+                    ext_after: Some(after),
+                    ..
+                }) if field > after => (field - after - 1) as usize,
+                _ => 0
+            };
+            let bits = self.addition_bits()?.to_vec();
+
+            for present in bits.into_iter().skip(known) {
+                if present {
+                    let len = self.bits.read_length(None, None)? as usize;
+
+                    self.bits.read_octets(len)?;
+                }
+            }
+
+            Ok(out)
+        });
+
+        self.scope = outer;
+
+        out
+    }
+
+    fn read_chars<F>(
+        &mut self,
+        extensible: bool,
+        min: Option<u64>,
+        max: Option<u64>,
+        char_bits: usize,
+        convert: F
+    ) -> Result<String, APERError>
+    where
+        F: Fn(u64) -> Result<char, APERError> {
+        let nchars =
+            self.bits
+                .read_size(extensible, min, max, char_bits as u64)?;
+        let mut out = String::with_capacity(nchars as usize);
+
+        for _ in 0..nchars {
+            out.push(convert(self.bits.read_bits(char_bits)? as u64)?)
+        }
+
+        Ok(out)
+    }
+}
+
+impl Writer for APERWriter {
+    type Error = APERError;
+
+    fn write_sequence<C, F>(
+        &mut self,
+        f: F
+    ) -> Result<(), APERError>
+    where
+        C: sequence::Constraint,
+        F: Fn(&mut Self) -> Result<(), APERError> {
+        self.with_field(false, true, |w| {
+            w.write_fields(C::STD_OPTIONAL_FIELDS, C::EXTENDED_AFTER_FIELD, f)
+        })
+    }
+
+    fn write_sequence_of<C, T>(
+        &mut self,
+        slice: &[T::Type]
+    ) -> Result<(), APERError>
+    where
+        C: sequenceof::Constraint,
+        T: WritableType {
+        self.with_field(false, true, |w| {
+            let len = slice.len() as u64;
+            let in_range = check_size(C::EXTENSIBLE, C::MIN, C::MAX, len)?;
+
+            if C::EXTENSIBLE {
+                w.bits.write_bit(!in_range)
+            }
+
+            match C::MAX {
+                Some(ub) if in_range && ub == C::MIN.unwrap_or(0) => {
+                    if ub >= SIXTY_FOUR_K {
+                        w.bits.write_length(None, None, len)?
+                    }
+                }
+                Some(ub) if in_range => {
+                    w.bits.write_length(C::MIN, Some(ub), len)?
+                }
+                _ => w.bits.write_length(None, None, len)?
+            }
+
+            for val in slice {
+                T::write_value(w, val)?
+            }
+
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn write_set<C, F>(
+        &mut self,
+        f: F
+    ) -> Result<(), APERError>
+    where
+        C: set::Constraint,
+        F: Fn(&mut Self) -> Result<(), APERError> {
+        self.with_field(false, true, |w| {
+            w.write_fields(C::STD_OPTIONAL_FIELDS, C::EXTENDED_AFTER_FIELD, f)
+        })
+    }
+
+    fn write_set_of<C, T>(
+        &mut self,
+        slice: &[T::Type]
+    ) -> Result<(), APERError>
+    where
+        C: setof::Constraint,
+        T: WritableType {
+        self.with_field(false, true, |w| {
+            w.bits.write_length(None, None, slice.len() as u64)?;
+
+            for val in slice {
+                T::write_value(w, val)?
+            }
+
+            Ok(())
+        })
+    }
+
+    fn write_enumerated<C>(
+        &mut self,
+        enumerated: &C
+    ) -> Result<(), APERError>
+    where
+        C: enumerated::Constraint {
+        self.with_field(false, true, |w| {
+            let idx = enumerated.to_choice_index();
+
+            if idx < C::STD_VARIANT_COUNT {
+                if C::EXTENSIBLE {
+                    w.bits.write_bit(false)
+                }
+
+                w.bits.write_constrained(
+                    0,
+                    C::STD_VARIANT_COUNT as i64 - 1,
+                    idx as i64
+                );
+
+                Ok(())
+            } else if C::EXTENSIBLE {
+                w.bits.write_bit(true);
+                w.bits.write_normally_small(idx - C::STD_VARIANT_COUNT)
+            } else {
+                Err(APERError::BadIndex {
+                    name: C::NAME,
+                    index: idx
+                })
+            }
+        })
+    }
+
+    fn write_choice<C>(
+        &mut self,
+        choice: &C
+    ) -> Result<(), APERError>
+    where
+        C: choice::Constraint {
+        self.with_field(false, true, |w| {
+            let idx = choice.to_choice_index();
+
+            if idx < C::STD_VARIANT_COUNT {
+                if C::EXTENSIBLE {
+                    w.bits.write_bit(false)
+                }
+
+                w.bits.write_constrained(
+                    0,
+                    C::STD_VARIANT_COUNT as i64 - 1,
+                    idx as i64
+                );
+                choice.write_content(w)
+            } else if C::EXTENSIBLE {
+                let mut sub = APERWriter::default();
+
+                choice.write_content(&mut sub)?;
+
+                let bytes = sub.into_bytes_vec();
+
+                w.bits.write_bit(true);
+                w.bits.write_normally_small(idx - C::STD_VARIANT_COUNT)?;
+                w.bits.write_length(None, None, bytes.len() as u64)?;
+                w.bits.write_octets(&bytes);
+
+                Ok(())
+            } else {
+                Err(APERError::BadIndex {
+                    name: C::NAME,
+                    index: idx
+                })
+            }
+        })
+    }
+
+    #[inline]
+    fn write_opt<T>(
+        &mut self,
+        value: Option<&T::Type>
+    ) -> Result<(), APERError>
+    where
+        T: WritableType {
+        match value {
+            Some(value) => {
+                self.with_field(true, true, |w| T::write_value(w, value))
+            }
+            None => self.with_field(true, false, |_| Ok(()))
+        }
+    }
+
+    #[inline]
+    fn write_default<C, T>(
+        &mut self,
+        value: &T::Type
+    ) -> Result<(), APERError>
+    where
+        C: default::Constraint<Owned = T::Type>,
+        T: WritableType {
+        let present = C::DEFAULT_VALUE.ne(value);
+
+        self.with_field(true, present, |w| T::write_value(w, value))
+    }
+
+    fn write_number<T, C>(
+        &mut self,
+        value: T
+    ) -> Result<(), APERError>
+    where
+        T: numbers::Number,
+        C: numbers::Constraint<T> {
+        self.with_field(false, true, |w| {
+            let val = value.to_i64();
+            let in_range = C::MIN.is_none_or(|min| val >= min) &&
+                C::MAX.is_none_or(|max| val <= max);
+
+            if C::EXTENSIBLE {
+                w.bits.write_bit(!in_range)
+            } else if !in_range {
+                return Err(APERError::ValueOutOfRange {
+                    value: val,
+                    min: C::MIN,
+                    max: C::MAX
+                });
+            }
+
+            match (C::MIN, C::MAX) {
+                (Some(lb), Some(ub)) if in_range => {
+                    w.bits.write_constrained(lb, ub, val);
+
+                    Ok(())
+                }
+                (Some(lb), None) if in_range => {
+                    w.bits.write_semi_constrained(lb, val)
+                }
+                _ => w.bits.write_unconstrained(val)
+            }
+        })
+    }
+
+    fn write_utf8string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), APERError>
+    where
+        C: utf8string::Constraint {
+        self.with_field(false, true, |w| {
+            check_size(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.chars().count() as u64
+            )?;
+            w.bits.write_length(None, None, value.len() as u64)?;
+            w.bits.write_octets(value.as_bytes());
+
+            Ok(())
+        })
+    }
+
+    fn write_ia5string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), APERError>
+    where
+        C: ia5string::Constraint {
+        self.with_field(false, true, |w| {
+            if let Some(c) = value.chars().find(|c| !c.is_ascii()) {
+                return Err(APERError::BadChar { c: c });
+            }
+
+            w.write_chars(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.chars().map(|c| c as u64),
+                8
+            )
+        })
+    }
+
+    fn write_numeric_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), APERError>
+    where
+        C: numericstring::Constraint {
+        self.with_field(false, true, |w| {
+            for c in value.chars() {
+                numeric_index(c)?;
+            }
+
+            w.write_chars(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.chars().map(|c| numeric_index(c).unwrap_or(0)),
+                4
+            )
+        })
+    }
+
+    fn write_visible_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), APERError>
+    where
+        C: visiblestring::Constraint {
+        self.with_field(false, true, |w| {
+            if let Some(c) = value.chars().find(|c| !(' '..='~').contains(c)) {
+                return Err(APERError::BadChar { c: c });
+            }
+
+            w.write_chars(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.chars().map(|c| c as u64),
+                8
+            )
+        })
+    }
+
+    fn write_printable_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), APERError>
+    where
+        C: printablestring::Constraint {
+        self.with_field(false, true, |w| {
+            for c in value.chars() {
+                check_printable(c)?;
+            }
+
+            w.write_chars(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.chars().map(|c| c as u64),
+                8
+            )
+        })
+    }
+
+    fn write_octet_string<C>(
+        &mut self,
+        value: &[u8]
+    ) -> Result<(), APERError>
+    where
+        C: octetstring::Constraint {
+        self.with_field(false, true, |w| {
+            w.bits.write_size(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                value.len() as u64,
+                8
+            )?;
+
+            for byte in value {
+                w.bits.write_bits(*byte as u128, 8)
+            }
+
+            Ok(())
+        })
+    }
+
+    fn write_bit_string<C>(
+        &mut self,
+        value: &[u8],
+        bit_len: u64
+    ) -> Result<(), APERError>
+    where
+        C: bitstring::Constraint {
+        self.with_field(false, true, |w| {
+            w.bits
+                .write_size(C::EXTENSIBLE, C::MIN, C::MAX, bit_len, 1)?;
+
+            for i in 0..bit_len as usize {
+                let byte = value.get(i / 8).copied().unwrap_or(0);
+
+                w.bits.write_bit(byte & (0x80 >> (i % 8)) != 0)
+            }
+
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn write_boolean<C>(
+        &mut self,
+        value: bool
+    ) -> Result<(), APERError>
+    where
+        C: boolean::Constraint {
+        self.with_field(false, true, |w| {
+            w.bits.write_bit(value);
+
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn write_null<C>(
+        &mut self,
+        _value: &Null
+    ) -> Result<(), APERError>
+    where
+        C: null::Constraint {
+        self.with_field(false, true, |_| Ok(()))
+    }
+}
+
+impl Reader for APERReader<'_> {
+    type Error = APERError;
+
+    fn read_sequence<C, S, F>(
+        &mut self,
+        f: F
+    ) -> Result<S, APERError>
+    where
+        C: sequence::Constraint,
+        F: Fn(&mut Self) -> Result<S, APERError> {
+        self.with_field(false, |r| {
+            r.read_fields(
+                C::NAME,
+                C::STD_OPTIONAL_FIELDS,
+                C::EXTENDED_AFTER_FIELD,
+                f
+            )
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_sequence_of<C, T>(&mut self) -> Result<Vec<T::Type>, APERError>
+    where
+        C: sequenceof::Constraint,
+        T: ReadableType {
+        self.with_field(false, |r| {
+            let extended = C::EXTENSIBLE && r.bits.read_bit()?;
+            let len = match C::MAX {
+                Some(ub) if !extended && ub == C::MIN.unwrap_or(0) => {
+                    if ub >= SIXTY_FOUR_K {
+                        r.bits.read_length(None, None)?
+                    } else {
+                        ub
+                    }
+                }
+                Some(ub) if !extended => {
+                    r.bits.read_length(C::MIN, Some(ub))?
+                }
+                _ => r.bits.read_length(None, None)?
+            };
+
+            if !extended {
+                check_size(false, C::MIN, C::MAX, len)?;
+            }
+
+            // Each element takes at least one bit, except for empty types.
+            let mut out = Vec::with_capacity(min(len as usize, 1024));
+
+            for _ in 0..len {
+                out.push(T::read_value(r)?)
+            }
+
+            Ok(out)
+        })
+        .map(Option::unwrap)
+    }
+
+    #[inline]
+    fn read_set<C, S, F>(
+        &mut self,
+        f: F
+    ) -> Result<S, APERError>
+    where
+        C: set::Constraint,
+        F: Fn(&mut Self) -> Result<S, APERError> {
+        self.with_field(false, |r| {
+            r.read_fields(
+                C::NAME,
+                C::STD_OPTIONAL_FIELDS,
+                C::EXTENDED_AFTER_FIELD,
+                f
+            )
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_set_of<C, T>(&mut self) -> Result<Vec<T::Type>, APERError>
+    where
+        C: setof::Constraint,
+        T: ReadableType {
+        self.with_field(false, |r| {
+            let len = r.bits.read_length(None, None)?;
+            let mut out = Vec::with_capacity(min(len as usize, 1024));
+
+            for _ in 0..len {
+                out.push(T::read_value(r)?)
+            }
+
+            Ok(out)
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_enumerated<C>(&mut self) -> Result<C, APERError>
+    where
+        C: enumerated::Constraint {
+        self.with_field(false, |r| {
+            let idx = if C::EXTENSIBLE && r.bits.read_bit()? {
+                r.bits.read_normally_small()? + C::STD_VARIANT_COUNT
+            } else {
+                r.bits
+                    .read_constrained(0, C::STD_VARIANT_COUNT as i64 - 1)?
+                    as u64
+            };
+
+            C::from_choice_index(idx).ok_or(APERError::BadIndex {
+                name: C::NAME,
+                index: idx
+            })
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_choice<C>(&mut self) -> Result<C, APERError>
+    where
+        C: choice::Constraint {
+        self.with_field(false, |r| {
+            if C::EXTENSIBLE && r.bits.read_bit()? {
+                let idx = r.bits.read_normally_small()? + C::STD_VARIANT_COUNT;
+                let len = r.bits.read_length(None, None)? as usize;
+                let octets = r.bits.read_octets(len)?;
+                let mut sub = APERReader::new(octets);
+
+                C::read_content(idx, &mut sub)?.ok_or(APERError::BadIndex {
+                    name: C::NAME,
+                    index: idx
+                })
+            } else {
+                let idx = r
+                    .bits
+                    .read_constrained(0, C::STD_VARIANT_COUNT as i64 - 1)?
+                    as u64;
+
+                C::read_content(idx, r)?.ok_or(APERError::BadIndex {
+                    name: C::NAME,
+                    index: idx
+                })
+            }
+        })
+        .map(Option::unwrap)
+    }
+
+    #[inline]
+    fn read_opt<T>(&mut self) -> Result<Option<T::Type>, APERError>
+    where
+        T: ReadableType {
+        self.with_field(true, |r| T::read_value(r))
+    }
+
+    #[inline]
+    fn read_default<C, T>(&mut self) -> Result<T::Type, APERError>
+    where
+        C: default::Constraint<Owned = T::Type>,
+        T: ReadableType {
+        self.with_field(true, |r| T::read_value(r))
+            .map(|val| val.unwrap_or_else(|| C::DEFAULT_VALUE.to_owned()))
+    }
+
+    fn read_number<T, C>(&mut self) -> Result<T, APERError>
+    where
+        T: numbers::Number,
+        C: numbers::Constraint<T> {
+        self.with_field(false, |r| {
+            let extended = C::EXTENSIBLE && r.bits.read_bit()?;
+            let val = match (C::MIN, C::MAX) {
+                (Some(lb), Some(ub)) if !extended => {
+                    r.bits.read_constrained(lb, ub)?
+                }
+                (Some(lb), None) if !extended => {
+                    r.bits.read_semi_constrained(lb)?
+                }
+                _ => r.bits.read_unconstrained()?
+            };
+
+            Ok(T::from_i64(val))
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_utf8string<C>(&mut self) -> Result<String, APERError>
+    where
+        C: utf8string::Constraint {
+        self.with_field(false, |r| {
+            let len = r.bits.read_length(None, None)? as usize;
+            let octets = r.bits.read_octets(len)?;
+            let out = String::from_utf8(octets.to_vec())
+                .map_err(|_| APERError::BadUTF8)?;
+
+            check_size(
+                C::EXTENSIBLE,
+                C::MIN,
+                C::MAX,
+                out.chars().count() as u64
+            )?;
+
+            Ok(out)
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_ia5string<C>(&mut self) -> Result<String, APERError>
+    where
+        C: ia5string::Constraint {
+        self.with_field(false, |r| {
+            r.read_chars(C::EXTENSIBLE, C::MIN, C::MAX, 8, |c| {
+                if c < 128 {
+                    Ok(c as u8 as char)
+                } else {
+                    Err(APERError::BadChar { c: c as u8 as char })
+                }
+            })
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_numeric_string<C>(&mut self) -> Result<String, APERError>
+    where
+        C: numericstring::Constraint {
+        self.with_field(false, |r| {
+            r.read_chars(C::EXTENSIBLE, C::MIN, C::MAX, 4, |c| match c {
+                0 => Ok(' '),
+                1..=10 => Ok((b'0' + c as u8 - 1) as char),
+                _ => Err(APERError::BadChar { c: c as u8 as char })
+            })
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_visible_string<C>(&mut self) -> Result<String, APERError>
+    where
+        C: visiblestring::Constraint {
+        self.with_field(false, |r| {
+            r.read_chars(C::EXTENSIBLE, C::MIN, C::MAX, 8, |c| {
+                let c = c as u8 as char;
+
+                if (' '..='~').contains(&c) {
+                    Ok(c)
+                } else {
+                    Err(APERError::BadChar { c: c })
+                }
+            })
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_printable_string<C>(&mut self) -> Result<String, APERError>
+    where
+        C: printablestring::Constraint {
+        self.with_field(false, |r| {
+            r.read_chars(C::EXTENSIBLE, C::MIN, C::MAX, 8, |c| {
+                let c = c as u8 as char;
+
+                check_printable(c).map(|_| c)
+            })
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_octet_string<C>(&mut self) -> Result<Vec<u8>, APERError>
+    where
+        C: octetstring::Constraint {
+        self.with_field(false, |r| {
+            let len = r.bits.read_size(C::EXTENSIBLE, C::MIN, C::MAX, 8)?;
+            let mut out = Vec::with_capacity(min(len as usize, r.bits.len / 8));
+
+            for _ in 0..len {
+                out.push(r.bits.read_bits(8)? as u8)
+            }
+
+            Ok(out)
+        })
+        .map(Option::unwrap)
+    }
+
+    fn read_bit_string<C>(&mut self) -> Result<(Vec<u8>, u64), APERError>
+    where
+        C: bitstring::Constraint {
+        self.with_field(false, |r| {
+            let len = r.bits.read_size(C::EXTENSIBLE, C::MIN, C::MAX, 1)?;
+            let mut out = Vec::with_capacity(min(len as usize, r.bits.len) / 8);
+
+            for i in 0..len as usize {
+                if i % 8 == 0 {
+                    out.push(0)
+                }
+
+                if r.bits.read_bit()? {
+                    out[i / 8] |= 0x80 >> (i % 8)
+                }
+            }
+
+            Ok((out, len))
+        })
+        .map(Option::unwrap)
+    }
+
+    #[inline]
+    fn read_boolean<C>(&mut self) -> Result<bool, APERError>
+    where
+        C: boolean::Constraint {
+        self.with_field(false, |r| r.bits.read_bit())
+            .map(Option::unwrap)
+    }
+
+    #[inline]
+    fn read_null<C>(&mut self) -> Result<Null, APERError>
+    where
+        C: null::Constraint {
+        self.with_field(false, |_| Ok(Null)).map(Option::unwrap)
+    }
+}
+
+impl<T, const MAX_BITS: usize> Clone for APERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        APERCodec(self.0)
+    }
+}
+
+impl<T, const MAX_BITS: usize> DatagramCodec<T> for APERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    type CreateError = Infallible;
+    type DecodeError = APERError;
+    type EncodeError = APERError;
+    type Param = ();
+
+    const MAX_BYTES: usize = ((MAX_BITS - 1) >> 3) + 1;
+
+    #[inline]
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(APERCodec(PhantomData))
+    }
+
+    #[inline]
+    fn encode(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let vec = self.encode_to_vec(val)?;
+        let len = vec.len();
+
+        buf[..len].copy_from_slice(&vec);
+
+        Ok(len)
+    }
+
+    #[inline]
+    fn encode_to_vec(
+        &mut self,
+        val: &T
+    ) -> Result<Vec<u8>, Self::EncodeError> {
+        let mut writer = APERWriter::with_capacity(Self::MAX_BYTES);
+
+        self.encode_to_writer(val, &mut writer)?;
+
+        let out = writer.into_bytes_vec();
+
+        if out.len() <= Self::MAX_BYTES {
+            Ok(out)
+        } else {
+            Err(APERError::TooLong {
+                len: out.len(),
+                max: Self::MAX_BYTES
+            })
+        }
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(T, usize), Self::DecodeError> {
+        let buf = &buf[..min(buf.len(), Self::MAX_BYTES)];
+        let mut reader = APERReader::new(buf);
+        let out = self.decode_from_reader(&mut reader)?;
+        let nbits = reader.bits_consumed();
+
+        // An empty encoding is represented by a single zero byte.
+        let nbytes = if nbits != 0 {
+            ((nbits - 1) >> 3) + 1
+        } else {
+            min(buf.len(), 1)
+        };
+
+        Ok((out, nbytes))
+    }
+}
+
+impl<T, const MAX_BITS: usize> DatagramAPERCodec<T> for APERCodec<T, MAX_BITS> where
+    T: Readable + Writable
+{
+}
+
+impl<T, const MAX_BITS: usize> Default for APERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    #[inline]
+    fn default() -> Self {
+        APERCodec(PhantomData)
+    }
+}
+
+impl ScopedError for APERError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl Display for APERError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            APERError::EndOfInput => write!(f, "unexpected end of input"),
+            APERError::ValueOutOfRange { value, min, max } => {
+                write!(f, "value {} out of range (", value)?;
+
+                match min {
+                    Some(min) => write!(f, "{}..", min)?,
+                    None => write!(f, "MIN..")?
+                }
+
+                match max {
+                    Some(max) => write!(f, "{})", max),
+                    None => write!(f, "MAX)")
+                }
+            }
+            APERError::SizeOutOfRange { size, min, max } => {
+                write!(f, "size {} out of range (", size)?;
+
+                match min {
+                    Some(min) => write!(f, "{}..", min)?,
+                    None => write!(f, "0..")?
+                }
+
+                match max {
+                    Some(max) => write!(f, "{})", max),
+                    None => write!(f, "MAX)")
+                }
+            }
+            APERError::BadIndex { name, index } => {
+                write!(f, "bad index {} for type {}", index, name)
+            }
+            APERError::BadChar { c } => {
+                write!(f, "character {:?} not permitted", c)
+            }
+            APERError::BadUTF8 => write!(f, "invalid UTF-8 string"),
+            APERError::MissingExtension { name } => {
+                write!(f, "mandatory extension missing from type {}", name)
+            }
+            APERError::Unsupported { what } => {
+                write!(f, "unsupported encoding feature: {}", what)
+            }
+            APERError::TooLong { len, max } => {
+                write!(f, "encoded length {} exceeds maximum {}", len, max)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::version::Version;
+#[cfg(test)]
+use crate::version::VersionRange;
+#[cfg(test)]
+use crate::version::VersionRangeElem;
+
+#[test]
+fn test_version_aper_encode() {
+    let version = Version::new(1, 2, 10);
+    let mut codec = APERCodec::<Version, 64>::create(()).unwrap();
+    let encoded = codec.encode_to_vec(&version).unwrap();
+
+    assert_eq!(encoded, [0x00, 0x01, 0x00, 0x02, 0x00, 0x0a]);
+}
+
+#[test]
+fn test_version_aper_round_trip() {
+    let version = Version::new(1023, 17, 4095);
+    let mut codec = APERCodec::<Version, 64>::create(()).unwrap();
+    let mut buf = [0; APERCodec::<Version, 64>::MAX_BYTES];
+    let nencoded = codec.encode(&version, &mut buf[..]).unwrap();
+    let (actual, nbytes) = codec.decode(&buf[..]).unwrap();
+
+    assert_eq!(version, actual);
+    assert_eq!(nencoded, nbytes);
+}
+
+#[test]
+fn test_version_range_aper_encode() {
+    let range = VersionRange {
+        lower: Some(VersionRangeElem::minor(1, 2)),
+        upper: None
+    };
+    let mut codec = APERCodec::<VersionRange, 128>::create(()).unwrap();
+    let encoded = codec.encode_to_vec(&range).unwrap();
+
+    // Presence bits 10, choice index 01, then two aligned octet pairs.
+    assert_eq!(encoded, [0x90, 0x00, 0x01, 0x00, 0x02]);
+}
+
+#[test]
+fn test_version_range_aper_round_trip() {
+    let ranges = [
+        VersionRange {
+            lower: None,
+            upper: None
+        },
+        VersionRange {
+            lower: Some(VersionRangeElem::major(3)),
+            upper: None
+        },
+        VersionRange {
+            lower: None,
+            upper: Some(VersionRangeElem::sub(7, 8, 9))
+        },
+        VersionRange {
+            lower: Some(VersionRangeElem::minor(1, 2)),
+            upper: Some(VersionRangeElem::sub(1, 5, 4000))
+        }
+    ];
+    let mut codec = APERCodec::<VersionRange, 128>::create(()).unwrap();
+
+    for range in ranges {
+        let encoded = codec.encode_to_vec(&range).unwrap();
+        let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+        assert_eq!(range, actual);
+        assert_eq!(encoded.len(), nbytes);
+    }
+}
+
+#[test]
+fn test_version_aper_truncated() {
+    let mut codec = APERCodec::<Version, 64>::create(()).unwrap();
+
+    assert_eq!(
+        codec.decode(&[0x00, 0x01, 0x00]),
+        Err(APERError::EndOfInput)
+    );
+}
+
+#[test]
+fn test_version_aper_out_of_range() {
+    let version = Version::new(1024, 0, 0);
+    let mut codec = APERCodec::<Version, 64>::create(()).unwrap();
+
+    assert!(codec.encode_to_vec(&version).is_err());
+    assert!(codec.decode(&[0x04, 0x00, 0x00, 0x00, 0x00, 0x00]).is_err());
+}
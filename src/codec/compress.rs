@@ -0,0 +1,529 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Compression wrapper codec.
+//!
+//! This module provides [CompressCodec], which wraps an inner codec
+//! and compresses its output using DEFLATE (RFC 1951).  Each frame is
+//! prefixed by a single flag byte indicating whether the remainder is
+//! compressed.  Compression is only applied when it actually reduces
+//! the size of the frame, so the overhead for incompressible data is
+//! limited to the flag byte.
+//!
+//! The maximum size of a frame is given separately from the inner
+//! codec's [MAX_BYTES](DatagramCodec::MAX_BYTES), which allows the
+//! inner codec to produce messages larger than the channel can
+//! carry, so long as they compress to fit.  This limit is enforced
+//! after compression.
+//!
+//! When decoding, decompression will never produce more than the
+//! inner codec's [MAX_BYTES](DatagramCodec::MAX_BYTES), which guards
+//! against decompression bombs.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+use flate2::Compress;
+use flate2::CompressError;
+use flate2::Compression;
+use flate2::Decompress;
+use flate2::DecompressError;
+use flate2::FlushCompress;
+use flate2::FlushDecompress;
+use flate2::Status;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Flag byte indicating an uncompressed frame.
+const FLAG_UNCOMPRESSED: u8 = 0x00;
+
+/// Flag byte indicating a DEFLATE-compressed frame.
+const FLAG_DEFLATE: u8 = 0x01;
+
+/// Default compression level.
+const DEFAULT_LEVEL: u32 = 6;
+
+/// Parameters for creating a [CompressCodec].
+#[derive(Clone, Debug)]
+pub struct CompressParam<P> {
+    /// Compression level, from 0 (fastest) to 9 (best).
+    level: u32,
+    /// Parameter for creating the inner codec.
+    inner: P
+}
+
+/// Codec that compresses the output of an inner codec.
+///
+/// Frames produced by this codec will never exceed `MAX_BYTES`
+/// bytes; see the [module documentation](crate::codec::compress) for
+/// details.
+pub struct CompressCodec<T, Inner: DatagramCodec<T>, const MAX_BYTES: usize> {
+    inner: Inner,
+    compress: Compress,
+    decompress: Decompress,
+    msg: PhantomData<T>
+}
+
+/// Errors that can occur when encoding with a [CompressCodec].
+#[derive(Debug)]
+pub enum CompressEncodeError<Inner> {
+    /// Error encoding the message.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error compressing the message.
+    Compress {
+        /// Error from the compressor.
+        error: CompressError
+    },
+    /// Encoded frame exceeds the maximum size, even after compression.
+    TooLong {
+        /// Size of the smallest encoded frame.
+        len: usize,
+        /// Maximum frame size.
+        max: usize
+    }
+}
+
+/// Errors that can occur when decoding with a [CompressCodec].
+#[derive(Debug)]
+pub enum CompressDecodeError<Inner> {
+    /// Error decoding the message.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error decompressing the message.
+    Decompress {
+        /// Error from the decompressor.
+        error: DecompressError
+    },
+    /// The frame was empty.
+    Empty,
+    /// The flag byte was not recognized.
+    BadFlag {
+        /// The flag byte.
+        flag: u8
+    },
+    /// The compressed data ended prematurely.
+    Truncated,
+    /// The decompressed data exceeds the inner codec's maximum size.
+    TooLarge {
+        /// Maximum decompressed size.
+        max: usize
+    }
+}
+
+impl<P> CompressParam<P> {
+    /// Create a new `CompressParam` from its components.
+    ///
+    /// The `level` ranges from 0 (fastest) to 9 (best), and will be
+    /// clamped to that range.
+    #[inline]
+    pub fn new(
+        level: u32,
+        inner: P
+    ) -> Self {
+        CompressParam {
+            level: level.min(9),
+            inner: inner
+        }
+    }
+
+    /// Get the compression level.
+    #[inline]
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Get the parameter for the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (u32, P) {
+        (self.level, self.inner)
+    }
+}
+
+impl<P> From<P> for CompressParam<P> {
+    #[inline]
+    fn from(inner: P) -> Self {
+        CompressParam::new(DEFAULT_LEVEL, inner)
+    }
+}
+
+impl<T, Inner, const MAX_BYTES: usize> CompressCodec<T, Inner, MAX_BYTES>
+where
+    Inner: DatagramCodec<T>
+{
+    /// Get the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Attempt to compress `raw` into `buf`.
+    ///
+    /// This returns the size of the compressed data, or `None` if it
+    /// would not fit within `buf`.
+    fn try_compress(
+        &mut self,
+        raw: &[u8],
+        buf: &mut [u8]
+    ) -> Result<Option<usize>, CompressError> {
+        self.compress.reset();
+
+        match self.compress.compress(raw, buf, FlushCompress::Finish)? {
+            Status::StreamEnd => Ok(Some(self.compress.total_out() as usize)),
+            _ => Ok(None)
+        }
+    }
+
+    /// Decompress `buf`, returning the decompressed data and the
+    /// number of bytes consumed.
+    fn decompress(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(Vec<u8>, usize), CompressDecodeError<Inner::DecodeError>> {
+        // Allow one extra byte, to detect overruns.
+        let mut out = vec![0; Inner::MAX_BYTES + 1];
+
+        self.decompress.reset(false);
+
+        let status = self
+            .decompress
+            .decompress(buf, &mut out, FlushDecompress::Finish)
+            .map_err(|err| CompressDecodeError::Decompress { error: err })?;
+        let len = self.decompress.total_out() as usize;
+
+        match status {
+            Status::StreamEnd if len <= Inner::MAX_BYTES => {
+                out.truncate(len);
+
+                Ok((out, self.decompress.total_in() as usize))
+            }
+            _ if len > Inner::MAX_BYTES => Err(CompressDecodeError::TooLarge {
+                max: Inner::MAX_BYTES
+            }),
+            _ => Err(CompressDecodeError::Truncated)
+        }
+    }
+}
+
+impl<T, Inner, const MAX_BYTES: usize> DatagramCodec<T>
+    for CompressCodec<T, Inner, MAX_BYTES>
+where
+    Inner: DatagramCodec<T>
+{
+    type CreateError = Inner::CreateError;
+    type DecodeError = CompressDecodeError<Inner::DecodeError>;
+    type EncodeError = CompressEncodeError<Inner::EncodeError>;
+    type Param = CompressParam<Inner::Param>;
+
+    const MAX_BYTES: usize = MAX_BYTES;
+
+    fn create(param: Self::Param) -> Result<Self, Self::CreateError> {
+        let (level, inner) = param.take();
+        let inner = Inner::create(inner)?;
+
+        Ok(CompressCodec {
+            inner: inner,
+            compress: Compress::new(Compression::new(level), false),
+            decompress: Decompress::new(false),
+            msg: PhantomData
+        })
+    }
+
+    fn encode(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let raw = self
+            .inner
+            .encode_to_vec(val)
+            .map_err(|err| CompressEncodeError::Inner { error: err })?;
+        // Only accept compressed output strictly smaller than the
+        // raw output, and no larger than the maximum frame size.
+        let limit =
+            raw.len().saturating_sub(1).min(MAX_BYTES.saturating_sub(1));
+        let compressed = if limit != 0 {
+            self.try_compress(&raw, &mut buf[1..limit + 1])
+                .map_err(|err| CompressEncodeError::Compress { error: err })?
+        } else {
+            None
+        };
+
+        match compressed {
+            Some(len) => {
+                buf[0] = FLAG_DEFLATE;
+
+                Ok(len + 1)
+            }
+            None if raw.len() < MAX_BYTES => {
+                buf[0] = FLAG_UNCOMPRESSED;
+                buf[1..raw.len() + 1].copy_from_slice(&raw);
+
+                Ok(raw.len() + 1)
+            }
+            None => Err(CompressEncodeError::TooLong {
+                len: raw.len() + 1,
+                max: MAX_BYTES
+            })
+        }
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(T, usize), Self::DecodeError> {
+        match buf.first() {
+            Some(&FLAG_UNCOMPRESSED) => {
+                let (val, nbytes) = self
+                    .inner
+                    .decode(&buf[1..])
+                    .map_err(|err| CompressDecodeError::Inner { error: err })?;
+
+                Ok((val, nbytes + 1))
+            }
+            Some(&FLAG_DEFLATE) => {
+                let (raw, nbytes) = self.decompress(&buf[1..])?;
+                let (val, _) = self
+                    .inner
+                    .decode(&raw)
+                    .map_err(|err| CompressDecodeError::Inner { error: err })?;
+
+                Ok((val, nbytes + 1))
+            }
+            Some(flag) => Err(CompressDecodeError::BadFlag { flag: *flag }),
+            None => Err(CompressDecodeError::Empty)
+        }
+    }
+}
+
+impl<Inner> ScopedError for CompressEncodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            CompressEncodeError::Inner { error } => error.scope(),
+            CompressEncodeError::Compress { .. } => ErrorScope::Unrecoverable,
+            CompressEncodeError::TooLong { .. } => ErrorScope::Msg
+        }
+    }
+}
+
+impl<Inner> ScopedError for CompressDecodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            CompressDecodeError::Inner { error } => error.scope(),
+            _ => ErrorScope::Msg
+        }
+    }
+}
+
+impl<Inner> Display for CompressEncodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            CompressEncodeError::Inner { error } => error.fmt(f),
+            CompressEncodeError::Compress { error } => {
+                write!(f, "error compressing message: {}", error)
+            }
+            CompressEncodeError::TooLong { len, max } => write!(
+                f,
+                "encoded length {} exceeds maximum {} after compression",
+                len, max
+            )
+        }
+    }
+}
+
+impl<Inner> Display for CompressDecodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            CompressDecodeError::Inner { error } => error.fmt(f),
+            CompressDecodeError::Decompress { error } => {
+                write!(f, "error decompressing message: {}", error)
+            }
+            CompressDecodeError::Empty => write!(f, "empty frame"),
+            CompressDecodeError::BadFlag { flag } => {
+                write!(f, "unrecognized compression flag {:02x}", flag)
+            }
+            CompressDecodeError::Truncated => {
+                write!(f, "compressed data truncated")
+            }
+            CompressDecodeError::TooLarge { max } => {
+                write!(f, "decompressed data exceeds maximum size {}", max)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::convert::Infallible;
+
+#[cfg(test)]
+use crate::codec::per::PERCodec;
+#[cfg(test)]
+use crate::version::Version;
+#[cfg(test)]
+use crate::version::VersionPERCodec;
+
+/// Trivial codec for byte strings, for testing.
+#[cfg(test)]
+struct BytesCodec<const MAX: usize>;
+
+#[cfg(test)]
+impl<const MAX: usize> DatagramCodec<Vec<u8>> for BytesCodec<MAX> {
+    type CreateError = Infallible;
+    type DecodeError = Infallible;
+    type EncodeError = Infallible;
+    type Param = ();
+
+    const MAX_BYTES: usize = MAX;
+
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(BytesCodec)
+    }
+
+    fn encode(
+        &mut self,
+        val: &Vec<u8>,
+        buf: &mut [u8]
+    ) -> Result<usize, Infallible> {
+        buf[..val.len()].copy_from_slice(val);
+
+        Ok(val.len())
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(Vec<u8>, usize), Infallible> {
+        Ok((buf.to_vec(), buf.len()))
+    }
+}
+
+#[test]
+fn test_compress_incompressible() {
+    let mut codec =
+        CompressCodec::<Version, VersionPERCodec, 5>::create(().into())
+            .unwrap();
+    let version = Version::new(1, 2, 3);
+    let encoded = codec.encode_to_vec(&version).unwrap();
+    let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+    assert_eq!(encoded[0], FLAG_UNCOMPRESSED);
+    assert_eq!(version, actual);
+    assert_eq!(encoded.len(), nbytes);
+}
+
+#[test]
+fn test_compress_compressible() {
+    let mut codec =
+        CompressCodec::<Vec<u8>, BytesCodec<4096>, 256>::create(().into())
+            .unwrap();
+    let msg = vec![0x5a; 4096];
+    let encoded = codec.encode_to_vec(&msg).unwrap();
+    let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+    assert_eq!(encoded[0], FLAG_DEFLATE);
+    assert!(encoded.len() <= 256);
+    assert_eq!(msg, actual);
+    assert_eq!(encoded.len(), nbytes);
+}
+
+#[test]
+fn test_compress_too_long() {
+    let mut codec =
+        CompressCodec::<Vec<u8>, BytesCodec<4096>, 64>::create(().into())
+            .unwrap();
+    let msg: Vec<u8> = (0..1024).map(|i| (i * 7919 % 251) as u8).collect();
+
+    assert!(matches!(
+        codec.encode_to_vec(&msg),
+        Err(CompressEncodeError::TooLong { .. })
+    ));
+
+    // No frame fits at all.
+    let mut codec =
+        CompressCodec::<Vec<u8>, BytesCodec<4096>, 0>::create(().into())
+            .unwrap();
+
+    assert!(matches!(
+        codec.encode_to_vec(&msg),
+        Err(CompressEncodeError::TooLong { .. })
+    ));
+}
+
+#[test]
+fn test_compress_bomb() {
+    let mut big =
+        CompressCodec::<Vec<u8>, BytesCodec<65536>, 1024>::create(().into())
+            .unwrap();
+    let mut small =
+        CompressCodec::<Vec<u8>, BytesCodec<1024>, 1024>::create(().into())
+            .unwrap();
+    let msg = vec![0; 65536];
+    let encoded = big.encode_to_vec(&msg).unwrap();
+
+    assert_eq!(encoded[0], FLAG_DEFLATE);
+    assert!(matches!(
+        small.decode(&encoded),
+        Err(CompressDecodeError::TooLarge { max: 1024 })
+    ));
+}
+
+#[test]
+fn test_compress_bad_frames() {
+    let mut codec =
+        CompressCodec::<Version, PERCodec<Version, 32>, 16>::create(().into())
+            .unwrap();
+
+    assert!(matches!(codec.decode(&[]), Err(CompressDecodeError::Empty)));
+    assert!(matches!(
+        codec.decode(&[0x7f, 0x00]),
+        Err(CompressDecodeError::BadFlag { flag: 0x7f })
+    ));
+    assert!(codec.decode(&[FLAG_DEFLATE, 0xff, 0xff]).is_err());
+}
@@ -0,0 +1,1881 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Codec implementations using ASN.1 distinguished encoding rules (DER).
+//!
+//! This module provides a [DatagramCodec] implementation for any type
+//! implementing the [Readable] and [Writable] traits from the
+//! [asn1rs] package.  This codec encodes and decodes the type using
+//! the ASN.1 distinguished encoding rules (ITU-T X.690).
+//!
+//! The ASN.1 modules in this crate use `AUTOMATIC TAGS`, which
+//! [asn1rs] does not apply itself.  This implementation therefore
+//! assigns context-specific tags to the components of `SEQUENCE` and
+//! `SET` types and to the alternatives of `CHOICE` types, in the
+//! manner prescribed by X.680.  Explicit non-universal tags in the
+//! ASN.1 module are honored as implicit tags.
+//!
+//! Decoding is strict: non-minimal lengths and integers, indefinite
+//! lengths, non-canonical booleans, and trailing data in
+//! non-extensible types are all rejected.
+//!
+//! The [split_tlv] and [encode_tlv] functions provide low-level access
+//! to individual encodings, for structures such as X.509 certificates
+//! and OCSP messages that are handled outside of [asn1rs].  These
+//! apply the same length checks as [DERReader].
+use std::cmp::min;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+
+use asn1rs::model::Tag;
+use asn1rs::syn::bitstring;
+use asn1rs::syn::boolean;
+use asn1rs::syn::choice;
+use asn1rs::syn::default;
+use asn1rs::syn::enumerated;
+use asn1rs::syn::ia5string;
+use asn1rs::syn::null;
+use asn1rs::syn::numbers;
+use asn1rs::syn::numericstring;
+use asn1rs::syn::octetstring;
+use asn1rs::syn::printablestring;
+use asn1rs::syn::sequence;
+use asn1rs::syn::sequenceof;
+use asn1rs::syn::set;
+use asn1rs::syn::setof;
+use asn1rs::syn::utf8string;
+use asn1rs::syn::visiblestring;
+use asn1rs::syn::Null;
+use asn1rs::syn::Readable;
+use asn1rs::syn::ReadableType;
+use asn1rs::syn::Reader;
+use asn1rs::syn::Writable;
+use asn1rs::syn::WritableType;
+use asn1rs::syn::Writer;
+use time::OffsetDateTime;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Sub-trait of [DatagramCodec] for things that can be encoded using
+/// the ASN.1 distinguished encoding rules (DER).
+pub trait DatagramDERCodec<T>: DatagramCodec<T>
+where
+    T: Readable + Writable {
+    /// Encode `val` into the [DERWriter].
+    #[inline]
+    fn encode_to_writer(
+        &mut self,
+        val: &T,
+        writer: &mut DERWriter
+    ) -> Result<(), DERError> {
+        writer.write(val)
+    }
+
+    /// Decode a value of type `T` from the [DERReader].
+    #[inline]
+    fn decode_from_reader(
+        &mut self,
+        reader: &mut DERReader<'_>
+    ) -> Result<T, DERError> {
+        reader.read::<T>()
+    }
+}
+
+/// Errors that can occur encoding or decoding using the ASN.1
+/// distinguished encoding rules.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DERError {
+    /// Input ended before the value was completely decoded.
+    EndOfInput,
+    /// A tag other than the expected one was found.
+    UnexpectedTag {
+        /// The expected tag.
+        expected: Tag,
+        /// The tag that was found.
+        actual: Tag
+    },
+    /// A primitive encoding was found where a constructed one was
+    /// expected, or vice versa.
+    BadConstructed {
+        /// The tag.
+        tag: Tag
+    },
+    /// An encoding was valid BER, but not valid DER.
+    NonCanonical {
+        /// Description of the problem.
+        what: &'static str
+    },
+    /// Data remained after the end of a non-extensible type.
+    TrailingData {
+        /// Name of the type.
+        name: &'static str
+    },
+    /// An integer value was outside its permitted range.
+    ValueOutOfRange {
+        /// The value.
+        value: i64,
+        /// The lower bound, if one exists.
+        min: Option<i64>,
+        /// The upper bound, if one exists.
+        max: Option<i64>
+    },
+    /// A size was outside its permitted range.
+    SizeOutOfRange {
+        /// The size.
+        size: u64,
+        /// The lower bound, if one exists.
+        min: Option<u64>,
+        /// The upper bound, if one exists.
+        max: Option<u64>
+    },
+    /// A `CHOICE` or `ENUMERATED` index was not recognized.
+    BadIndex {
+        /// Name of the type.
+        name: &'static str,
+        /// The index.
+        index: u64
+    },
+    /// A character was not permitted in a restricted string type.
+    BadChar {
+        /// The character.
+        c: char
+    },
+    /// A `UTF8String` contained invalid UTF-8.
+    BadUTF8,
+    /// The encoding used a feature that is not supported.
+    Unsupported {
+        /// Description of the feature.
+        what: &'static str
+    },
+    /// The encoded message exceeds the maximum message size.
+    TooLong {
+        /// The length of the encoded message.
+        len: usize,
+        /// The maximum message size.
+        max: usize
+    }
+}
+
+/// Codec for encoding/decoding using ASN.1 distinguished encoding
+/// rules (DER).
+///
+/// This type provides a [DatagramCodec] implementation for any type
+/// implementing the [Readable] and [Writable] traits from the
+/// [asn1rs] packages.  This implementation encodes and decodes the
+/// type using the ASN.1 distinguished encoding rules.
+///
+/// As with [PERCodec](crate::codec::per::PERCodec), the maximum
+/// message size is given in bits.  DER is byte-oriented, so this is
+/// rounded up to a whole number of bytes.
+pub struct DERCodec<T: Readable + Writable, const MAX_BITS: usize>(
+    PhantomData<T>
+);
+
+/// [Writer] implementation for the ASN.1 distinguished encoding
+/// rules.
+pub struct DERWriter {
+    frames: Vec<WriteFrame>,
+    pending: Option<Tag>
+}
+
+/// [Reader] implementation for the ASN.1 distinguished encoding
+/// rules.
+pub struct DERReader<'a> {
+    frames: Vec<ReadFrame<'a>>,
+    pending: Option<Tag>
+}
+
+/// Contents of a constructed encoding being written.
+struct WriteFrame {
+    buf: Vec<u8>,
+    /// Index of the next component, for `SEQUENCE` and `SET` bodies.
+    fields: Option<u64>
+}
+
+/// Contents of a constructed encoding being read.
+struct ReadFrame<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Index of the next component, for `SEQUENCE` and `SET` bodies.
+    fields: Option<u64>
+}
+
+/// Determine the tag for a value with its own tag `own`.
+///
+/// Components of `SEQUENCE` and `SET` types, and alternatives of
+/// `CHOICE` types get automatic context-specific tags, unless they
+/// have a non-universal tag of their own.
+#[inline]
+fn resolve_tag(
+    fields: &mut Option<u64>,
+    pending: &mut Option<Tag>,
+    own: Tag
+) -> Tag {
+    let auto = fields.as_mut().map(|field| {
+        let out = *field;
+
+        *field += 1;
+
+        Tag::ContextSpecific(out as usize)
+    });
+    let pending = pending.take();
+
+    match own {
+        Tag::Universal(_) => auto.or(pending).unwrap_or(own),
+        _ => own
+    }
+}
+
+/// Determine the explicit outer tag for a `CHOICE`, if it has one.
+#[inline]
+fn resolve_choice_tag(
+    fields: &mut Option<u64>,
+    pending: &mut Option<Tag>,
+    own: Tag
+) -> Option<Tag> {
+    match resolve_tag(fields, pending, own) {
+        Tag::Universal(_) => None,
+        tag => Some(tag)
+    }
+}
+
+#[inline]
+fn write_header(
+    buf: &mut Vec<u8>,
+    tag: Tag,
+    constructed: bool,
+    len: usize
+) {
+    let (class, number) = match tag {
+        Tag::Universal(n) => (0x00, n),
+        Tag::Application(n) => (0x40, n),
+        Tag::ContextSpecific(n) => (0x80, n),
+        Tag::Private(n) => (0xc0, n)
+    };
+    let first = class | if constructed { 0x20 } else { 0x00 };
+
+    if number < 31 {
+        buf.push(first | number as u8)
+    } else {
+        let nbits = (usize::BITS - number.leading_zeros()) as usize;
+        let ngroups = nbits.div_ceil(7);
+
+        buf.push(first | 0x1f);
+
+        for i in (0..ngroups).rev() {
+            let cont = if i != 0 { 0x80 } else { 0x00 };
+
+            buf.push(cont | ((number >> (7 * i)) & 0x7f) as u8)
+        }
+    }
+
+    write_len(buf, len)
+}
+
+/// Write the length octets for `len`.
+fn write_len(
+    buf: &mut Vec<u8>,
+    len: usize
+) {
+    if len < 128 {
+        buf.push(len as u8)
+    } else {
+        let nbytes = ((usize::BITS - len.leading_zeros()) as usize).div_ceil(8);
+
+        buf.push(0x80 | nbytes as u8);
+
+        for i in (0..nbytes).rev() {
+            buf.push((len >> (8 * i)) as u8)
+        }
+    }
+}
+
+/// Parse the length octets at the start of `data`, returning the
+/// length and the number of octets.
+///
+/// Indefinite and non-minimal lengths are rejected, as is any length
+/// that exceeds the remaining data.
+fn read_len(data: &[u8]) -> Result<(usize, usize), DERError> {
+    let first = *data.first().ok_or(DERError::EndOfInput)?;
+    let (len, len_len) = if first < 0x80 {
+        (first as usize, 1)
+    } else if first == 0x80 {
+        return Err(DERError::NonCanonical {
+            what: "indefinite length"
+        });
+    } else {
+        let nbytes = (first & 0x7f) as usize;
+
+        if nbytes > (usize::BITS / 8) as usize {
+            return Err(DERError::Unsupported {
+                what: "length too large"
+            });
+        }
+
+        let bytes = data.get(1..nbytes + 1).ok_or(DERError::EndOfInput)?;
+
+        if bytes[0] == 0 {
+            return Err(DERError::NonCanonical {
+                what: "non-minimal length"
+            });
+        }
+
+        let len = bytes
+            .iter()
+            .fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+
+        if len < 128 {
+            return Err(DERError::NonCanonical {
+                what: "non-minimal length"
+            });
+        }
+
+        (len, nbytes + 1)
+    };
+
+    if data.len() - len_len < len {
+        return Err(DERError::EndOfInput);
+    }
+
+    Ok((len, len_len))
+}
+
+/// Split a single DER encoding off the front of `buf`.
+///
+/// This is intended for picking apart structures that have no
+/// [Readable] implementation, such as X.509 certificates and OCSP
+/// messages.  Returns the identifier octet, the contents, and the
+/// remainder of `buf`.
+///
+/// Only low tag numbers (less than 31) are supported.  Lengths are
+/// subject to the same checks as in [DERReader]: indefinite,
+/// non-minimal, and truncated lengths are all rejected.
+pub fn split_tlv(buf: &[u8]) -> Result<(u8, &[u8], &[u8]), DERError> {
+    let (&tag, rest) = buf.split_first().ok_or(DERError::EndOfInput)?;
+
+    if tag & 0x1f == 0x1f {
+        return Err(DERError::Unsupported {
+            what: "high tag number"
+        });
+    }
+
+    let (len, len_len) = read_len(rest)?;
+    let (content, rest) = rest[len_len..].split_at(len);
+
+    Ok((tag, content, rest))
+}
+
+/// DER-encode `content` with the identifier octet `tag`.
+///
+/// This is the inverse of [split_tlv].
+pub fn encode_tlv(
+    tag: u8,
+    content: &[u8]
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 10);
+
+    out.push(tag);
+    write_len(&mut out, content.len());
+    out.extend_from_slice(content);
+
+    out
+}
+
+/// DER-encode the non-negative big-endian integer `bytes` as an
+/// `INTEGER`.
+pub fn encode_unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let bytes = match bytes.iter().position(|byte| *byte != 0) {
+        Some(idx) => &bytes[idx..],
+        None => &[]
+    };
+    let mut content = Vec::with_capacity(bytes.len() + 1);
+
+    if bytes.first().is_none_or(|byte| *byte & 0x80 != 0) {
+        content.push(0);
+    }
+
+    content.extend_from_slice(bytes);
+
+    encode_tlv(0x02, &content)
+}
+
+/// DER-encode `time` as a `GeneralizedTime`, truncated to whole
+/// seconds.
+pub fn encode_generalized_time(time: SystemTime) -> Vec<u8> {
+    let time = OffsetDateTime::from(time);
+    let str = format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    );
+
+    encode_tlv(0x18, str.as_bytes())
+}
+
+#[inline]
+fn int_content(val: i64) -> Vec<u8> {
+    let bytes = val.to_be_bytes();
+    let mut start = 0;
+
+    while start < 7 &&
+        ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) ||
+            (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1
+    }
+
+    bytes[start..].to_vec()
+}
+
+fn parse_int(content: &[u8]) -> Result<i64, DERError> {
+    match content {
+        [] => Err(DERError::NonCanonical {
+            what: "empty integer"
+        }),
+        [0x00, next, ..] if next & 0x80 == 0 => Err(DERError::NonCanonical {
+            what: "non-minimal integer"
+        }),
+        [0xff, next, ..] if next & 0x80 != 0 => Err(DERError::NonCanonical {
+            what: "non-minimal integer"
+        }),
+        _ if content.len() > 8 => Err(DERError::Unsupported {
+            what: "integer value larger than 64 bits"
+        }),
+        _ => {
+            let init: i64 = if content[0] & 0x80 != 0 { -1 } else { 0 };
+
+            Ok(content
+                .iter()
+                .fold(init, |acc, byte| (acc << 8) | *byte as i64))
+        }
+    }
+}
+
+#[inline]
+fn check_size(
+    extensible: bool,
+    min: Option<u64>,
+    max: Option<u64>,
+    size: u64
+) -> Result<(), DERError> {
+    let in_range =
+        size >= min.unwrap_or(0) && max.is_none_or(|max| size <= max);
+
+    if !in_range && !extensible {
+        Err(DERError::SizeOutOfRange {
+            size: size,
+            min: min,
+            max: max
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn check_value(
+    extensible: bool,
+    min: Option<i64>,
+    max: Option<i64>,
+    value: i64
+) -> Result<(), DERError> {
+    let in_range = min.is_none_or(|min| value >= min) &&
+        max.is_none_or(|max| value <= max);
+
+    if !in_range && !extensible {
+        Err(DERError::ValueOutOfRange {
+            value: value,
+            min: min,
+            max: max
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that all characters in `value` satisfy `pred`.
+#[inline]
+fn check_chars<F>(
+    value: &str,
+    pred: F
+) -> Result<(), DERError>
+where
+    F: Fn(char) -> bool {
+    match value.chars().find(|c| !pred(*c)) {
+        Some(c) => Err(DERError::BadChar { c: c }),
+        None => Ok(())
+    }
+}
+
+#[inline]
+fn is_numeric(c: char) -> bool {
+    c == ' ' || c.is_ascii_digit()
+}
+
+#[inline]
+fn is_visible(c: char) -> bool {
+    (' '..='~').contains(&c)
+}
+
+#[inline]
+fn is_printable(c: char) -> bool {
+    c.is_ascii_alphanumeric() || " '()+,-./:=?".contains(c)
+}
+
+impl Default for DERWriter {
+    #[inline]
+    fn default() -> Self {
+        DERWriter::with_capacity(0)
+    }
+}
+
+impl DERWriter {
+    /// Create a new `DERWriter` with a buffer of capacity `capacity`
+    /// bytes.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        DERWriter {
+            frames: vec![WriteFrame {
+                buf: Vec::with_capacity(capacity),
+                fields: None
+            }],
+            pending: None
+        }
+    }
+
+    /// Get the number of bytes written so far.
+    #[inline]
+    pub fn byte_len(&self) -> usize {
+        self.frames[0].buf.len()
+    }
+
+    /// Get the complete encoding as a [Vec].
+    #[inline]
+    pub fn into_bytes_vec(mut self) -> Vec<u8> {
+        self.frames.truncate(1);
+
+        self.frames.pop().map(|frame| frame.buf).unwrap_or_default()
+    }
+
+    #[inline]
+    fn frame(&mut self) -> &mut WriteFrame {
+        self.frames.last_mut().expect("Writer frame stack is empty")
+    }
+
+    #[inline]
+    fn tag(
+        &mut self,
+        own: Tag
+    ) -> Tag {
+        let fields = &mut self.frames.last_mut().unwrap().fields;
+
+        resolve_tag(fields, &mut self.pending, own)
+    }
+
+    /// Skip an absent component in the current `SEQUENCE` or `SET`.
+    #[inline]
+    fn skip_field(&mut self) {
+        if let Some(field) = &mut self.frame().fields {
+            *field += 1
+        }
+    }
+
+    #[inline]
+    fn write_primitive(
+        &mut self,
+        tag: Tag,
+        content: &[u8]
+    ) {
+        let buf = &mut self.frame().buf;
+
+        write_header(buf, tag, false, content.len());
+        buf.extend_from_slice(content)
+    }
+
+    /// Write a constructed encoding whose contents are produced by `f`.
+    fn write_constructed<F>(
+        &mut self,
+        tag: Tag,
+        fields: Option<u64>,
+        f: F
+    ) -> Result<(), DERError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), DERError> {
+        self.frames.push(WriteFrame {
+            buf: Vec::new(),
+            fields: fields
+        });
+
+        let out = f(self);
+        let content = self.frames.pop().map(|frame| frame.buf);
+
+        out?;
+
+        let content = content.unwrap_or_default();
+        let buf = &mut self.frame().buf;
+
+        write_header(buf, tag, true, content.len());
+        buf.extend_from_slice(&content);
+
+        Ok(())
+    }
+
+    fn write_string(
+        &mut self,
+        own: Tag,
+        value: &str
+    ) -> Result<(), DERError> {
+        let tag = self.tag(own);
+
+        self.write_primitive(tag, value.as_bytes());
+
+        Ok(())
+    }
+}
+
+impl<'a> DERReader<'a> {
+    /// Create a new `DERReader` over `bytes`.
+    #[inline]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        DERReader {
+            frames: vec![ReadFrame {
+                data: bytes,
+                pos: 0,
+                fields: None
+            }],
+            pending: None
+        }
+    }
+
+    /// Get the number of bytes consumed so far.
+    #[inline]
+    pub fn bytes_consumed(&self) -> usize {
+        self.frames[0].pos
+    }
+
+    #[inline]
+    fn frame(&mut self) -> &mut ReadFrame<'a> {
+        self.frames.last_mut().expect("Reader frame stack is empty")
+    }
+
+    #[inline]
+    fn tag(
+        &mut self,
+        own: Tag
+    ) -> Tag {
+        let fields = &mut self.frames.last_mut().unwrap().fields;
+
+        resolve_tag(fields, &mut self.pending, own)
+    }
+
+    /// Skip an absent component in the current `SEQUENCE` or `SET`.
+    #[inline]
+    fn skip_field(&mut self) {
+        if let Some(field) = &mut self.frame().fields {
+            *field += 1
+        }
+    }
+
+    /// Parse the next identifier octets, returning the tag, whether
+    /// the encoding is constructed, and the number of octets.
+    fn peek_tag(&mut self) -> Result<Option<(Tag, bool, usize)>, DERError> {
+        let frame = self.frame();
+        let data = &frame.data[frame.pos..];
+        let first = match data.first() {
+            Some(first) => *first,
+            None => return Ok(None)
+        };
+        let constructed = first & 0x20 != 0;
+        let (number, nbytes) = if first & 0x1f != 0x1f {
+            ((first & 0x1f) as usize, 1)
+        } else {
+            let mut number: usize = 0;
+            let mut nbytes = 1;
+
+            loop {
+                let byte = *data.get(nbytes).ok_or(DERError::EndOfInput)?;
+
+                if nbytes == 1 && byte == 0x80 {
+                    return Err(DERError::NonCanonical {
+                        what: "non-minimal tag"
+                    });
+                }
+
+                if number.leading_zeros() < 7 {
+                    return Err(DERError::Unsupported {
+                        what: "tag number too large"
+                    });
+                }
+
+                number = (number << 7) | (byte & 0x7f) as usize;
+                nbytes += 1;
+
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            if number < 31 {
+                return Err(DERError::NonCanonical {
+                    what: "non-minimal tag"
+                });
+            }
+
+            (number, nbytes)
+        };
+        let tag = match first & 0xc0 {
+            0x00 => Tag::Universal(number),
+            0x40 => Tag::Application(number),
+            0x80 => Tag::ContextSpecific(number),
+            _ => Tag::Private(number)
+        };
+
+        Ok(Some((tag, constructed, nbytes)))
+    }
+
+    /// Read a complete encoding with tag `tag`, returning its contents.
+    fn read_tlv(
+        &mut self,
+        tag: Tag,
+        constructed: bool
+    ) -> Result<&'a [u8], DERError> {
+        let (actual, is_constructed, tag_len) =
+            self.peek_tag()?.ok_or(DERError::EndOfInput)?;
+
+        if actual != tag {
+            return Err(DERError::UnexpectedTag {
+                expected: tag,
+                actual: actual
+            });
+        }
+
+        if is_constructed != constructed {
+            return Err(DERError::BadConstructed { tag: tag });
+        }
+
+        let frame = self.frame();
+        let (len, len_len) = read_len(&frame.data[frame.pos + tag_len..])?;
+        let start = frame.pos + tag_len + len_len;
+
+        frame.pos = start + len;
+
+        Ok(&frame.data[start..start + len])
+    }
+
+    /// Read a constructed encoding, reading its contents with `f`.
+    fn read_constructed<S, F>(
+        &mut self,
+        tag: Tag,
+        fields: Option<u64>,
+        extensible: bool,
+        name: &'static str,
+        f: F
+    ) -> Result<S, DERError>
+    where
+        F: FnOnce(&mut Self) -> Result<S, DERError> {
+        let content = self.read_tlv(tag, true)?;
+
+        self.frames.push(ReadFrame {
+            data: content,
+            pos: 0,
+            fields: fields
+        });
+
+        let out = f(self);
+        let frame = self.frames.pop();
+
+        match frame {
+            Some(frame)
+                if out.is_ok() &&
+                    !extensible &&
+                    frame.pos < frame.data.len() =>
+            {
+                Err(DERError::TrailingData { name: name })
+            }
+            _ => out
+        }
+    }
+
+    /// Check whether a component with the next automatic tag is
+    /// present.
+    fn next_present(&mut self) -> Result<bool, DERError> {
+        let expected = self.frame().fields;
+
+        match (self.peek_tag()?, expected) {
+            (None, _) => Ok(false),
+            (Some((tag, ..)), Some(field)) => {
+                Ok(tag == Tag::ContextSpecific(field as usize))
+            }
+            (Some(_), None) => Ok(true)
+        }
+    }
+
+    fn read_string(
+        &mut self,
+        own: Tag
+    ) -> Result<String, DERError> {
+        let tag = self.tag(own);
+        let content = self.read_tlv(tag, false)?;
+
+        String::from_utf8(content.to_vec()).map_err(|_| DERError::BadUTF8)
+    }
+
+    fn read_sized_of<T>(&mut self) -> Result<Vec<T::Type>, DERError>
+    where
+        T: ReadableType {
+        let mut out = Vec::new();
+
+        while self.peek_tag()?.is_some() {
+            out.push(T::read_value(self)?)
+        }
+
+        Ok(out)
+    }
+}
+
+impl Writer for DERWriter {
+    type Error = DERError;
+
+    #[inline]
+    fn write_sequence<C, F>(
+        &mut self,
+        f: F
+    ) -> Result<(), DERError>
+    where
+        C: sequence::Constraint,
+        F: Fn(&mut Self) -> Result<(), DERError> {
+        let tag = self.tag(C::TAG);
+
+        self.write_constructed(tag, Some(0), f)
+    }
+
+    fn write_sequence_of<C, T>(
+        &mut self,
+        slice: &[T::Type]
+    ) -> Result<(), DERError>
+    where
+        C: sequenceof::Constraint,
+        T: WritableType {
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, slice.len() as u64)?;
+
+        let tag = self.tag(C::TAG);
+
+        self.write_constructed(tag, None, |w| {
+            for val in slice {
+                T::write_value(w, val)?
+            }
+
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn write_set<C, F>(
+        &mut self,
+        f: F
+    ) -> Result<(), DERError>
+    where
+        C: set::Constraint,
+        F: Fn(&mut Self) -> Result<(), DERError> {
+        // Automatic tags are already in canonical order.
+        let tag = self.tag(C::TAG);
+
+        self.write_constructed(tag, Some(0), f)
+    }
+
+    fn write_set_of<C, T>(
+        &mut self,
+        slice: &[T::Type]
+    ) -> Result<(), DERError>
+    where
+        C: setof::Constraint,
+        T: WritableType {
+        let tag = self.tag(C::TAG);
+        let mut encodings = Vec::with_capacity(slice.len());
+
+        for val in slice {
+            let mut sub = DERWriter::default();
+
+            T::write_value(&mut sub, val)?;
+            encodings.push(sub.into_bytes_vec())
+        }
+
+        // DER requires SET OF elements to be sorted by encoding.
+        encodings.sort();
+
+        self.write_constructed(tag, None, |w| {
+            for encoding in encodings {
+                w.frame().buf.extend_from_slice(&encoding)
+            }
+
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn write_enumerated<C>(
+        &mut self,
+        enumerated: &C
+    ) -> Result<(), DERError>
+    where
+        C: enumerated::Constraint {
+        let tag = self.tag(C::TAG);
+        let idx = enumerated.to_choice_index();
+
+        self.write_primitive(tag, &int_content(idx as i64));
+
+        Ok(())
+    }
+
+    fn write_choice<C>(
+        &mut self,
+        choice: &C
+    ) -> Result<(), DERError>
+    where
+        C: choice::Constraint {
+        let fields = &mut self.frames.last_mut().unwrap().fields;
+        let outer = resolve_choice_tag(fields, &mut self.pending, C::TAG);
+        let idx = choice.to_choice_index();
+
+        match outer {
+            // CHOICE types are always explicitly tagged.
+            Some(outer) => self.write_constructed(outer, None, |w| {
+                w.pending = Some(Tag::ContextSpecific(idx as usize));
+
+                choice.write_content(w)
+            }),
+            None => {
+                self.pending = Some(Tag::ContextSpecific(idx as usize));
+
+                choice.write_content(self)
+            }
+        }
+    }
+
+    #[inline]
+    fn write_opt<T>(
+        &mut self,
+        value: Option<&T::Type>
+    ) -> Result<(), DERError>
+    where
+        T: WritableType {
+        match value {
+            Some(value) => T::write_value(self, value),
+            None => {
+                self.skip_field();
+
+                Ok(())
+            }
+        }
+    }
+
+    #[inline]
+    fn write_default<C, T>(
+        &mut self,
+        value: &T::Type
+    ) -> Result<(), DERError>
+    where
+        C: default::Constraint<Owned = T::Type>,
+        T: WritableType {
+        // DER requires values equal to the default to be omitted.
+        if C::DEFAULT_VALUE.ne(value) {
+            T::write_value(self, value)
+        } else {
+            self.skip_field();
+
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn write_number<T, C>(
+        &mut self,
+        value: T
+    ) -> Result<(), DERError>
+    where
+        T: numbers::Number,
+        C: numbers::Constraint<T> {
+        let val = value.to_i64();
+
+        check_value(C::EXTENSIBLE, C::MIN, C::MAX, val)?;
+
+        let tag = self.tag(C::TAG);
+
+        self.write_primitive(tag, &int_content(val));
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_utf8string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), DERError>
+    where
+        C: utf8string::Constraint {
+        check_size(
+            C::EXTENSIBLE,
+            C::MIN,
+            C::MAX,
+            value.chars().count() as u64
+        )?;
+        self.write_string(C::TAG, value)
+    }
+
+    #[inline]
+    fn write_ia5string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), DERError>
+    where
+        C: ia5string::Constraint {
+        check_chars(value, |c| c.is_ascii())?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, value.len() as u64)?;
+        self.write_string(C::TAG, value)
+    }
+
+    #[inline]
+    fn write_numeric_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), DERError>
+    where
+        C: numericstring::Constraint {
+        check_chars(value, is_numeric)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, value.len() as u64)?;
+        self.write_string(C::TAG, value)
+    }
+
+    #[inline]
+    fn write_visible_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), DERError>
+    where
+        C: visiblestring::Constraint {
+        check_chars(value, is_visible)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, value.len() as u64)?;
+        self.write_string(C::TAG, value)
+    }
+
+    #[inline]
+    fn write_printable_string<C>(
+        &mut self,
+        value: &str
+    ) -> Result<(), DERError>
+    where
+        C: printablestring::Constraint {
+        check_chars(value, is_printable)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, value.len() as u64)?;
+        self.write_string(C::TAG, value)
+    }
+
+    #[inline]
+    fn write_octet_string<C>(
+        &mut self,
+        value: &[u8]
+    ) -> Result<(), DERError>
+    where
+        C: octetstring::Constraint {
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, value.len() as u64)?;
+
+        let tag = self.tag(C::TAG);
+
+        self.write_primitive(tag, value);
+
+        Ok(())
+    }
+
+    fn write_bit_string<C>(
+        &mut self,
+        value: &[u8],
+        bit_len: u64
+    ) -> Result<(), DERError>
+    where
+        C: bitstring::Constraint {
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, bit_len)?;
+
+        let tag = self.tag(C::TAG);
+        let nbytes = bit_len.div_ceil(8) as usize;
+        let unused = (nbytes * 8) as u64 - bit_len;
+        let mut content = Vec::with_capacity(nbytes + 1);
+
+        content.push(unused as u8);
+
+        for i in 0..nbytes {
+            content.push(value.get(i).copied().unwrap_or(0))
+        }
+
+        // DER requires the unused bits to be zero.
+        if let Some(last) = content.last_mut().filter(|_| nbytes != 0) {
+            *last &= 0xff << unused
+        }
+
+        self.write_primitive(tag, &content);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_boolean<C>(
+        &mut self,
+        value: bool
+    ) -> Result<(), DERError>
+    where
+        C: boolean::Constraint {
+        let tag = self.tag(C::TAG);
+
+        self.write_primitive(tag, &[if value { 0xff } else { 0x00 }]);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn write_null<C>(
+        &mut self,
+        _value: &Null
+    ) -> Result<(), DERError>
+    where
+        C: null::Constraint {
+        let tag = self.tag(C::TAG);
+
+        self.write_primitive(tag, &[]);
+
+        Ok(())
+    }
+}
+
+impl Reader for DERReader<'_> {
+    type Error = DERError;
+
+    #[inline]
+    fn read_sequence<C, S, F>(
+        &mut self,
+        f: F
+    ) -> Result<S, DERError>
+    where
+        C: sequence::Constraint,
+        F: Fn(&mut Self) -> Result<S, DERError> {
+        let tag = self.tag(C::TAG);
+        let extensible = C::EXTENDED_AFTER_FIELD.is_some();
+
+        self.read_constructed(tag, Some(0), extensible, C::NAME, f)
+    }
+
+    fn read_sequence_of<C, T>(&mut self) -> Result<Vec<T::Type>, DERError>
+    where
+        C: sequenceof::Constraint,
+        T: ReadableType {
+        let tag = self.tag(C::TAG);
+        let out =
+            self.read_constructed(tag, None, false, "SEQUENCE OF", |r| {
+                r.read_sized_of::<T>()
+            })?;
+
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_set<C, S, F>(
+        &mut self,
+        f: F
+    ) -> Result<S, DERError>
+    where
+        C: set::Constraint,
+        F: Fn(&mut Self) -> Result<S, DERError> {
+        let tag = self.tag(C::TAG);
+        let extensible = C::EXTENDED_AFTER_FIELD.is_some();
+
+        self.read_constructed(tag, Some(0), extensible, C::NAME, f)
+    }
+
+    fn read_set_of<C, T>(&mut self) -> Result<Vec<T::Type>, DERError>
+    where
+        C: setof::Constraint,
+        T: ReadableType {
+        let tag = self.tag(C::TAG);
+
+        self.read_constructed(tag, None, false, "SET OF", |r| {
+            r.read_sized_of::<T>()
+        })
+    }
+
+    fn read_enumerated<C>(&mut self) -> Result<C, DERError>
+    where
+        C: enumerated::Constraint {
+        let tag = self.tag(C::TAG);
+        let idx = parse_int(self.read_tlv(tag, false)?)?;
+
+        if idx < 0 {
+            return Err(DERError::BadIndex {
+                name: C::NAME,
+                index: idx as u64
+            });
+        }
+
+        C::from_choice_index(idx as u64).ok_or(DERError::BadIndex {
+            name: C::NAME,
+            index: idx as u64
+        })
+    }
+
+    fn read_choice<C>(&mut self) -> Result<C, DERError>
+    where
+        C: choice::Constraint {
+        let fields = &mut self.frames.last_mut().unwrap().fields;
+        let outer = resolve_choice_tag(fields, &mut self.pending, C::TAG);
+        let read = |r: &mut Self| {
+            let idx = match r.peek_tag()? {
+                Some((Tag::ContextSpecific(idx), ..)) => idx as u64,
+                Some((tag, ..)) => {
+                    return Err(DERError::UnexpectedTag {
+                        expected: Tag::ContextSpecific(0),
+                        actual: tag
+                    })
+                }
+                None => return Err(DERError::EndOfInput)
+            };
+
+            r.pending = Some(Tag::ContextSpecific(idx as usize));
+
+            let out = C::read_content(idx, r)?;
+
+            r.pending = None;
+
+            out.ok_or(DERError::BadIndex {
+                name: C::NAME,
+                index: idx
+            })
+        };
+
+        match outer {
+            Some(outer) => {
+                self.read_constructed(outer, None, false, C::NAME, read)
+            }
+            None => read(self)
+        }
+    }
+
+    #[inline]
+    fn read_opt<T>(&mut self) -> Result<Option<T::Type>, DERError>
+    where
+        T: ReadableType {
+        if self.next_present()? {
+            T::read_value(self).map(Some)
+        } else {
+            self.skip_field();
+
+            Ok(None)
+        }
+    }
+
+    #[inline]
+    fn read_default<C, T>(&mut self) -> Result<T::Type, DERError>
+    where
+        C: default::Constraint<Owned = T::Type>,
+        T: ReadableType {
+        if self.next_present()? {
+            let out = T::read_value(self)?;
+
+            // DER requires values equal to the default to be omitted.
+            if C::DEFAULT_VALUE.eq(&out) {
+                Err(DERError::NonCanonical {
+                    what: "default value encoded"
+                })
+            } else {
+                Ok(out)
+            }
+        } else {
+            self.skip_field();
+
+            Ok(C::DEFAULT_VALUE.to_owned())
+        }
+    }
+
+    #[inline]
+    fn read_number<T, C>(&mut self) -> Result<T, DERError>
+    where
+        T: numbers::Number,
+        C: numbers::Constraint<T> {
+        let tag = self.tag(C::TAG);
+        let val = parse_int(self.read_tlv(tag, false)?)?;
+
+        check_value(C::EXTENSIBLE, C::MIN, C::MAX, val)?;
+
+        Ok(T::from_i64(val))
+    }
+
+    #[inline]
+    fn read_utf8string<C>(&mut self) -> Result<String, DERError>
+    where
+        C: utf8string::Constraint {
+        let out = self.read_string(C::TAG)?;
+
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.chars().count() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_ia5string<C>(&mut self) -> Result<String, DERError>
+    where
+        C: ia5string::Constraint {
+        let out = self.read_string(C::TAG)?;
+
+        check_chars(&out, |c| c.is_ascii())?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_numeric_string<C>(&mut self) -> Result<String, DERError>
+    where
+        C: numericstring::Constraint {
+        let out = self.read_string(C::TAG)?;
+
+        check_chars(&out, is_numeric)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_visible_string<C>(&mut self) -> Result<String, DERError>
+    where
+        C: visiblestring::Constraint {
+        let out = self.read_string(C::TAG)?;
+
+        check_chars(&out, is_visible)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_printable_string<C>(&mut self) -> Result<String, DERError>
+    where
+        C: printablestring::Constraint {
+        let out = self.read_string(C::TAG)?;
+
+        check_chars(&out, is_printable)?;
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_octet_string<C>(&mut self) -> Result<Vec<u8>, DERError>
+    where
+        C: octetstring::Constraint {
+        let tag = self.tag(C::TAG);
+        let out = self.read_tlv(tag, false)?.to_vec();
+
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, out.len() as u64)?;
+
+        Ok(out)
+    }
+
+    fn read_bit_string<C>(&mut self) -> Result<(Vec<u8>, u64), DERError>
+    where
+        C: bitstring::Constraint {
+        let tag = self.tag(C::TAG);
+        let content = self.read_tlv(tag, false)?;
+        let (unused, bytes) = match content.split_first() {
+            Some((unused, bytes)) => (*unused, bytes),
+            None => {
+                return Err(DERError::NonCanonical {
+                    what: "empty bit string"
+                })
+            }
+        };
+
+        if unused > 7 || (bytes.is_empty() && unused != 0) {
+            return Err(DERError::NonCanonical {
+                what: "bad unused bit count"
+            });
+        }
+
+        if bytes
+            .last()
+            .is_some_and(|last| last & !(0xff << unused) != 0)
+        {
+            return Err(DERError::NonCanonical {
+                what: "nonzero unused bits"
+            });
+        }
+
+        let bit_len = (bytes.len() * 8) as u64 - unused as u64;
+
+        check_size(C::EXTENSIBLE, C::MIN, C::MAX, bit_len)?;
+
+        Ok((bytes.to_vec(), bit_len))
+    }
+
+    #[inline]
+    fn read_boolean<C>(&mut self) -> Result<bool, DERError>
+    where
+        C: boolean::Constraint {
+        let tag = self.tag(C::TAG);
+
+        match self.read_tlv(tag, false)? {
+            [0x00] => Ok(false),
+            [0xff] => Ok(true),
+            _ => Err(DERError::NonCanonical {
+                what: "bad boolean value"
+            })
+        }
+    }
+
+    #[inline]
+    fn read_null<C>(&mut self) -> Result<Null, DERError>
+    where
+        C: null::Constraint {
+        let tag = self.tag(C::TAG);
+
+        if self.read_tlv(tag, false)?.is_empty() {
+            Ok(Null)
+        } else {
+            Err(DERError::NonCanonical {
+                what: "non-empty null"
+            })
+        }
+    }
+}
+
+impl<T, const MAX_BITS: usize> Clone for DERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        DERCodec(self.0)
+    }
+}
+
+impl<T, const MAX_BITS: usize> DatagramCodec<T> for DERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    type CreateError = Infallible;
+    type DecodeError = DERError;
+    type EncodeError = DERError;
+    type Param = ();
+
+    const MAX_BYTES: usize = ((MAX_BITS - 1) >> 3) + 1;
+
+    #[inline]
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(DERCodec(PhantomData))
+    }
+
+    #[inline]
+    fn encode(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let vec = self.encode_to_vec(val)?;
+        let len = vec.len();
+
+        buf[..len].copy_from_slice(&vec);
+
+        Ok(len)
+    }
+
+    #[inline]
+    fn encode_to_vec(
+        &mut self,
+        val: &T
+    ) -> Result<Vec<u8>, Self::EncodeError> {
+        let mut writer = DERWriter::with_capacity(Self::MAX_BYTES);
+
+        self.encode_to_writer(val, &mut writer)?;
+
+        let out = writer.into_bytes_vec();
+
+        if out.len() <= Self::MAX_BYTES {
+            Ok(out)
+        } else {
+            Err(DERError::TooLong {
+                len: out.len(),
+                max: Self::MAX_BYTES
+            })
+        }
+    }
+
+    #[inline]
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(T, usize), Self::DecodeError> {
+        let buf = &buf[..min(buf.len(), Self::MAX_BYTES)];
+        let mut reader = DERReader::new(buf);
+        let out = self.decode_from_reader(&mut reader)?;
+
+        Ok((out, reader.bytes_consumed()))
+    }
+}
+
+impl<T, const MAX_BITS: usize> DatagramDERCodec<T> for DERCodec<T, MAX_BITS> where
+    T: Readable + Writable
+{
+}
+
+impl<T, const MAX_BITS: usize> Default for DERCodec<T, MAX_BITS>
+where
+    T: Readable + Writable
+{
+    #[inline]
+    fn default() -> Self {
+        DERCodec(PhantomData)
+    }
+}
+
+impl ScopedError for DERError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl Display for DERError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            DERError::EndOfInput => write!(f, "unexpected end of input"),
+            DERError::UnexpectedTag { expected, actual } => {
+                write!(f, "expected tag {:?}, found {:?}", expected, actual)
+            }
+            DERError::BadConstructed { tag } => {
+                write!(f, "wrong primitive/constructed form for tag {:?}", tag)
+            }
+            DERError::NonCanonical { what } => {
+                write!(f, "non-canonical encoding: {}", what)
+            }
+            DERError::TrailingData { name } => {
+                write!(f, "trailing data after value of type {}", name)
+            }
+            DERError::ValueOutOfRange { value, min, max } => {
+                write!(f, "value {} out of range (", value)?;
+
+                match min {
+                    Some(min) => write!(f, "{}..", min)?,
+                    None => write!(f, "MIN..")?
+                }
+
+                match max {
+                    Some(max) => write!(f, "{})", max),
+                    None => write!(f, "MAX)")
+                }
+            }
+            DERError::SizeOutOfRange { size, min, max } => {
+                write!(f, "size {} out of range (", size)?;
+
+                match min {
+                    Some(min) => write!(f, "{}..", min)?,
+                    None => write!(f, "0..")?
+                }
+
+                match max {
+                    Some(max) => write!(f, "{})", max),
+                    None => write!(f, "MAX)")
+                }
+            }
+            DERError::BadIndex { name, index } => {
+                write!(f, "bad index {} for type {}", index, name)
+            }
+            DERError::BadChar { c } => {
+                write!(f, "character {:?} not permitted", c)
+            }
+            DERError::BadUTF8 => write!(f, "invalid UTF-8 string"),
+            DERError::Unsupported { what } => {
+                write!(f, "unsupported encoding feature: {}", what)
+            }
+            DERError::TooLong { len, max } => {
+                write!(f, "encoded length {} exceeds maximum {}", len, max)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use crate::version::Version;
+#[cfg(test)]
+use crate::version::VersionRange;
+#[cfg(test)]
+use crate::version::VersionRangeElem;
+
+#[test]
+fn test_version_der_encode() {
+    let version = Version::new(1, 2, 10);
+    let mut codec = DERCodec::<Version, 256>::create(()).unwrap();
+    let encoded = codec.encode_to_vec(&version).unwrap();
+
+    assert_eq!(
+        encoded,
+        [0x30, 0x09, 0x80, 0x01, 0x01, 0x81, 0x01, 0x02, 0x82, 0x01, 0x0a]
+    );
+}
+
+#[test]
+fn test_version_der_round_trip() {
+    let version = Version::new(1023, 128, 4095);
+    let mut codec = DERCodec::<Version, 256>::create(()).unwrap();
+    let mut buf = [0; DERCodec::<Version, 256>::MAX_BYTES];
+    let nencoded = codec.encode(&version, &mut buf[..]).unwrap();
+    let (actual, nbytes) = codec.decode(&buf[..]).unwrap();
+
+    assert_eq!(version, actual);
+    assert_eq!(nencoded, nbytes);
+}
+
+#[test]
+fn test_version_range_der_encode() {
+    let range = VersionRange {
+        lower: None,
+        upper: Some(VersionRangeElem::major(3))
+    };
+    let mut codec = DERCodec::<VersionRange, 512>::create(()).unwrap();
+    let encoded = codec.encode_to_vec(&range).unwrap();
+
+    // Explicit [1] wrapping the implicitly-tagged [0] alternative.
+    assert_eq!(
+        encoded,
+        [0x30, 0x07, 0xa1, 0x05, 0xa0, 0x03, 0x80, 0x01, 0x03]
+    );
+}
+
+#[test]
+fn test_version_range_der_round_trip() {
+    let ranges = [
+        VersionRange {
+            lower: None,
+            upper: None
+        },
+        VersionRange {
+            lower: Some(VersionRangeElem::major(3)),
+            upper: None
+        },
+        VersionRange {
+            lower: None,
+            upper: Some(VersionRangeElem::sub(7, 8, 9))
+        },
+        VersionRange {
+            lower: Some(VersionRangeElem::minor(1, 2)),
+            upper: Some(VersionRangeElem::sub(1, 5, 4000))
+        }
+    ];
+    let mut codec = DERCodec::<VersionRange, 512>::create(()).unwrap();
+
+    for range in ranges {
+        let encoded = codec.encode_to_vec(&range).unwrap();
+        let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+        assert_eq!(range, actual);
+        assert_eq!(encoded.len(), nbytes);
+    }
+}
+
+#[test]
+fn test_version_range_elem_der_round_trip() {
+    let elem = VersionRangeElem::minor(4, 5);
+    let mut codec = DERCodec::<VersionRangeElem, 256>::create(()).unwrap();
+    let encoded = codec.encode_to_vec(&elem).unwrap();
+    let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+    assert_eq!(encoded, [0xa1, 0x06, 0x80, 0x01, 0x04, 0x81, 0x01, 0x05]);
+    assert_eq!(elem, actual);
+    assert_eq!(encoded.len(), nbytes);
+}
+
+#[test]
+fn test_version_der_non_canonical() {
+    let mut codec = DERCodec::<Version, 256>::create(()).unwrap();
+
+    // Non-minimal integer.
+    assert!(codec
+        .decode(&[
+            0x30, 0x0a, 0x80, 0x02, 0x00, 0x01, 0x81, 0x01, 0x02, 0x82, 0x01,
+            0x0a
+        ])
+        .is_err());
+    // Non-minimal length.
+    assert!(codec
+        .decode(&[
+            0x30, 0x81, 0x09, 0x80, 0x01, 0x01, 0x81, 0x01, 0x02, 0x82, 0x01,
+            0x0a
+        ])
+        .is_err());
+    // Trailing data.
+    assert!(codec
+        .decode(&[
+            0x30, 0x0c, 0x80, 0x01, 0x01, 0x81, 0x01, 0x02, 0x82, 0x01, 0x0a,
+            0x83, 0x01, 0x00
+        ])
+        .is_err());
+}
+
+#[test]
+fn test_version_der_out_of_range() {
+    let version = Version::new(1024, 0, 0);
+    let mut codec = DERCodec::<Version, 256>::create(()).unwrap();
+
+    assert!(codec.encode_to_vec(&version).is_err());
+}
+
+#[test]
+fn test_split_tlv() {
+    let buf = [0x04, 0x02, 0xaa, 0xbb, 0x05, 0x00];
+    let (tag, content, rest) = split_tlv(&buf).unwrap();
+
+    assert_eq!(tag, 0x04);
+    assert_eq!(content, [0xaa, 0xbb]);
+    assert_eq!(rest, [0x05, 0x00]);
+}
+
+#[test]
+fn test_split_tlv_long_length() {
+    let content = [0x5a; 200];
+    let encoded = encode_tlv(0x04, &content);
+
+    assert_eq!(encoded[..3], [0x04, 0x81, 200]);
+
+    let (tag, actual, rest) = split_tlv(&encoded).unwrap();
+
+    assert_eq!(tag, 0x04);
+    assert_eq!(actual, content);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn test_split_tlv_empty() {
+    assert_eq!(split_tlv(&[]), Err(DERError::EndOfInput));
+    assert_eq!(split_tlv(&[0x04]), Err(DERError::EndOfInput));
+}
+
+#[test]
+fn test_split_tlv_truncated() {
+    assert_eq!(
+        split_tlv(&[0x04, 0x03, 0x00, 0x00]),
+        Err(DERError::EndOfInput)
+    );
+    assert_eq!(split_tlv(&[0x04, 0x82, 0x01]), Err(DERError::EndOfInput));
+    assert_eq!(
+        split_tlv(&[0x04, 0x82, 0x01, 0x00, 0x00]),
+        Err(DERError::EndOfInput)
+    );
+}
+
+#[test]
+fn test_split_tlv_indefinite_length() {
+    assert_eq!(
+        split_tlv(&[0x30, 0x80, 0x00, 0x00]),
+        Err(DERError::NonCanonical {
+            what: "indefinite length"
+        })
+    );
+}
+
+#[test]
+fn test_split_tlv_non_minimal_length() {
+    // Short length in long form.
+    assert_eq!(
+        split_tlv(&[0x04, 0x81, 0x01, 0x00]),
+        Err(DERError::NonCanonical {
+            what: "non-minimal length"
+        })
+    );
+
+    // Leading zero length octet.
+    let mut buf = vec![0x04, 0x82, 0x00, 0xc8];
+
+    buf.extend_from_slice(&[0; 200]);
+
+    assert_eq!(
+        split_tlv(&buf),
+        Err(DERError::NonCanonical {
+            what: "non-minimal length"
+        })
+    );
+}
+
+#[test]
+fn test_split_tlv_overlong_length() {
+    let mut buf = vec![0x04, 0x80 | (usize::BITS / 8 + 1) as u8];
+
+    buf.extend_from_slice(&[0xff; 16]);
+
+    assert_eq!(
+        split_tlv(&buf),
+        Err(DERError::Unsupported {
+            what: "length too large"
+        })
+    );
+
+    let mut buf = vec![0x04, 0x80 | (usize::BITS / 8) as u8];
+
+    buf.extend_from_slice(&[0xff; 16]);
+
+    assert_eq!(split_tlv(&buf), Err(DERError::EndOfInput));
+}
+
+#[test]
+fn test_split_tlv_high_tag() {
+    assert_eq!(
+        split_tlv(&[0x1f, 0x1f, 0x00]),
+        Err(DERError::Unsupported {
+            what: "high tag number"
+        })
+    );
+}
+
+#[test]
+fn test_encode_unsigned_integer() {
+    assert_eq!(encode_unsigned_integer(&[]), [0x02, 0x01, 0x00]);
+    assert_eq!(encode_unsigned_integer(&[0x00, 0x00]), [0x02, 0x01, 0x00]);
+    assert_eq!(encode_unsigned_integer(&[0x7f]), [0x02, 0x01, 0x7f]);
+    assert_eq!(encode_unsigned_integer(&[0x80]), [0x02, 0x02, 0x00, 0x80]);
+    assert_eq!(
+        encode_unsigned_integer(&[0x00, 0x01, 0x02]),
+        [0x02, 0x02, 0x01, 0x02]
+    );
+}
+
+#[test]
+fn test_encode_generalized_time() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+
+    assert_eq!(encode_generalized_time(time), b"\x18\x0f20010909014640Z");
+}
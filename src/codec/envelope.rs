@@ -0,0 +1,663 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Self-describing envelope codec.
+//!
+//! Messages encoded with most codecs (such as
+//! [PERCodec](crate::codec::per::PERCodec)) carry no information
+//! about their type or the version of the protocol that produced
+//! them.  This module provides [EnvelopeCodec], which wraps an inner
+//! codec and prefixes each message with a compact header consisting
+//! of:
+//!
+//! - A 16-bit protocol identifier
+//! - The protocol [Version], encoded with [VersionPERCodec]
+//! - A 16-bit message type tag
+//!
+//! When decoding, the header is checked against the expected protocol
+//! identifier and the acceptable [VersionRange], and the message type
+//! tag is passed to the inner codec by way of
+//! [decode_msg_type](EnvelopeInnerCodec::decode_msg_type), which
+//! allows it to dispatch to the correct decoder.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+use asn1rs::io::per::err::Error;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::version::Version;
+use crate::version::VersionPERCodec;
+use crate::version::VersionRange;
+
+/// Size of the protocol identifier in the envelope header.
+const PROTOCOL_BYTES: usize = 2;
+
+/// Size of the message type tag in the envelope header.
+const MSG_TYPE_BYTES: usize = 2;
+
+/// Maximum size of the envelope header.
+pub const ENVELOPE_HEADER_MAX_BYTES: usize =
+    PROTOCOL_BYTES + VersionPERCodec::MAX_BYTES + MSG_TYPE_BYTES;
+
+/// Sub-trait of [DatagramCodec] for codecs that can be wrapped by an
+/// [EnvelopeCodec].
+///
+/// This provides the mapping between messages and their type tags.
+pub trait EnvelopeInnerCodec<T>: DatagramCodec<T> {
+    /// Get the message type tag for `val`.
+    fn msg_type(val: &T) -> u16;
+
+    /// Decode a message whose envelope header has the message type
+    /// tag `msg_type`.
+    ///
+    /// This returns `None` if `msg_type` is not a type tag recognized
+    /// by this codec.  The default implementation decodes using
+    /// [decode](DatagramCodec::decode), then checks that the result
+    /// has the type tag `msg_type`.  Codecs that need to select a
+    /// decoder based on the type tag should override this.
+    fn decode_msg_type(
+        &mut self,
+        msg_type: u16,
+        buf: &[u8]
+    ) -> Result<Option<(T, usize)>, Self::DecodeError> {
+        let (val, nbytes) = self.decode(buf)?;
+
+        if Self::msg_type(&val) == msg_type {
+            Ok(Some((val, nbytes)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Parameters for creating an [EnvelopeCodec].
+#[derive(Clone, Debug)]
+pub struct EnvelopeParam<P> {
+    /// Protocol identifier to use for all messages.
+    protocol: u16,
+    /// Local protocol version, attached to encoded messages.
+    version: Version,
+    /// Range of peer protocol versions that will be accepted.
+    accept: VersionRange,
+    /// Parameter for creating the inner codec.
+    inner: P
+}
+
+/// Decoded envelope header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvelopeHeader {
+    /// Protocol identifier.
+    protocol: u16,
+    /// Protocol version of the sender.
+    version: Version,
+    /// Message type tag.
+    msg_type: u16
+}
+
+/// Codec that wraps messages in a self-describing envelope.
+///
+/// This wraps an inner codec `Inner`, and prefixes all messages with
+/// a header identifying the protocol, its version, and the message
+/// type.  See the [module documentation](crate::codec::envelope) for
+/// details.
+pub struct EnvelopeCodec<T, Inner: EnvelopeInnerCodec<T>> {
+    inner: Inner,
+    version_codec: VersionPERCodec,
+    protocol: u16,
+    version: Version,
+    accept: VersionRange,
+    msg: PhantomData<T>
+}
+
+/// Errors that can occur when encoding with an [EnvelopeCodec].
+#[derive(Debug)]
+pub enum EnvelopeEncodeError<Inner> {
+    /// Error encoding the message body.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error encoding the version.
+    Version {
+        /// Error from the version codec.
+        error: Error
+    }
+}
+
+/// Errors that can occur when decoding with an [EnvelopeCodec].
+#[derive(Debug)]
+pub enum EnvelopeDecodeError<Inner> {
+    /// Error decoding the message body.
+    Inner {
+        /// Error from the inner codec.
+        error: Inner
+    },
+    /// Error decoding the version.
+    Version {
+        /// Error from the version codec.
+        error: Error
+    },
+    /// The message was too short to contain a header.
+    Truncated,
+    /// The protocol identifier did not match.
+    BadProtocol {
+        /// Expected protocol identifier.
+        expected: u16,
+        /// Protocol identifier in the header.
+        actual: u16
+    },
+    /// The peer's version is outside the acceptable range.
+    BadVersion {
+        /// The peer's version.
+        version: Version
+    },
+    /// The message type tag was not recognized.
+    UnknownType {
+        /// The message type tag.
+        msg_type: u16
+    }
+}
+
+impl<P> EnvelopeParam<P> {
+    /// Create a new `EnvelopeParam` from its components.
+    ///
+    /// The `protocol` and `version` will be attached to all encoded
+    /// messages.  Messages whose version does not fall into `accept`
+    /// will be rejected.  The `inner` parameter will be used to
+    /// create the inner codec.
+    #[inline]
+    pub fn new(
+        protocol: u16,
+        version: Version,
+        accept: VersionRange,
+        inner: P
+    ) -> Self {
+        EnvelopeParam {
+            protocol: protocol,
+            version: version,
+            accept: accept,
+            inner: inner
+        }
+    }
+
+    /// Get the protocol identifier.
+    #[inline]
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+
+    /// Get the local protocol version.
+    #[inline]
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Get the range of acceptable peer protocol versions.
+    #[inline]
+    pub fn accept(&self) -> &VersionRange {
+        &self.accept
+    }
+
+    /// Get the parameter for the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (u16, Version, VersionRange, P) {
+        (self.protocol, self.version, self.accept, self.inner)
+    }
+}
+
+impl EnvelopeHeader {
+    /// Get the protocol identifier.
+    #[inline]
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+
+    /// Get the protocol version of the sender.
+    #[inline]
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Get the message type tag.
+    #[inline]
+    pub fn msg_type(&self) -> u16 {
+        self.msg_type
+    }
+}
+
+impl<T, Inner> EnvelopeCodec<T, Inner>
+where
+    Inner: EnvelopeInnerCodec<T>
+{
+    /// Get the inner codec.
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Get the local protocol version.
+    #[inline]
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Get the range of acceptable peer protocol versions.
+    #[inline]
+    pub fn accept(&self) -> &VersionRange {
+        &self.accept
+    }
+
+    /// Decode and check only the envelope header from `buf`.
+    ///
+    /// This returns the header, and the number of bytes it occupies.
+    /// This will fail if the protocol identifier does not match, or
+    /// if the version is not in the acceptable range.
+    pub fn decode_header(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(EnvelopeHeader, usize), EnvelopeDecodeError<Inner::DecodeError>>
+    {
+        if buf.len() < PROTOCOL_BYTES {
+            return Err(EnvelopeDecodeError::Truncated);
+        }
+
+        let protocol = u16::from_be_bytes([buf[0], buf[1]]);
+
+        if protocol != self.protocol {
+            return Err(EnvelopeDecodeError::BadProtocol {
+                expected: self.protocol,
+                actual: protocol
+            });
+        }
+
+        let (version, nbytes) = self
+            .version_codec
+            .decode(&buf[PROTOCOL_BYTES..])
+            .map_err(|err| EnvelopeDecodeError::Version { error: err })?;
+
+        if !self.accept.contains(&version) {
+            return Err(EnvelopeDecodeError::BadVersion { version: version });
+        }
+
+        let pos = PROTOCOL_BYTES + nbytes;
+
+        if buf.len() < pos + MSG_TYPE_BYTES {
+            return Err(EnvelopeDecodeError::Truncated);
+        }
+
+        let msg_type = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
+        let header = EnvelopeHeader {
+            protocol: protocol,
+            version: version,
+            msg_type: msg_type
+        };
+
+        Ok((header, pos + MSG_TYPE_BYTES))
+    }
+
+    /// Encode the envelope header for `val` into `buf`.
+    fn encode_header(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, EnvelopeEncodeError<Inner::EncodeError>> {
+        buf[..PROTOCOL_BYTES].copy_from_slice(&self.protocol.to_be_bytes());
+
+        let nbytes = self
+            .version_codec
+            .encode(&self.version, &mut buf[PROTOCOL_BYTES..])
+            .map_err(|err| EnvelopeEncodeError::Version { error: err })?;
+        let pos = PROTOCOL_BYTES + nbytes;
+
+        buf[pos..pos + MSG_TYPE_BYTES]
+            .copy_from_slice(&Inner::msg_type(val).to_be_bytes());
+
+        Ok(pos + MSG_TYPE_BYTES)
+    }
+}
+
+impl<T, Inner> DatagramCodec<T> for EnvelopeCodec<T, Inner>
+where
+    Inner: EnvelopeInnerCodec<T>
+{
+    type CreateError = Inner::CreateError;
+    type DecodeError = EnvelopeDecodeError<Inner::DecodeError>;
+    type EncodeError = EnvelopeEncodeError<Inner::EncodeError>;
+    type Param = EnvelopeParam<Inner::Param>;
+
+    const MAX_BYTES: usize = ENVELOPE_HEADER_MAX_BYTES + Inner::MAX_BYTES;
+
+    fn create(param: Self::Param) -> Result<Self, Self::CreateError> {
+        let (protocol, version, accept, inner) = param.take();
+        let inner = Inner::create(inner)?;
+        let version_codec = match VersionPERCodec::create(()) {
+            Ok(codec) => codec,
+            Err(err) => match err {}
+        };
+
+        Ok(EnvelopeCodec {
+            inner: inner,
+            version_codec: version_codec,
+            protocol: protocol,
+            version: version,
+            accept: accept,
+            msg: PhantomData
+        })
+    }
+
+    fn encode(
+        &mut self,
+        val: &T,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let header_len = self.encode_header(val, buf)?;
+        let body_len = self
+            .inner
+            .encode(val, &mut buf[header_len..])
+            .map_err(|err| EnvelopeEncodeError::Inner { error: err })?;
+
+        Ok(header_len + body_len)
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(T, usize), Self::DecodeError> {
+        let (header, header_len) = self.decode_header(buf)?;
+
+        match self
+            .inner
+            .decode_msg_type(header.msg_type, &buf[header_len..])
+            .map_err(|err| EnvelopeDecodeError::Inner { error: err })?
+        {
+            Some((val, body_len)) => Ok((val, header_len + body_len)),
+            None => Err(EnvelopeDecodeError::UnknownType {
+                msg_type: header.msg_type
+            })
+        }
+    }
+}
+
+impl<Inner> ScopedError for EnvelopeEncodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            EnvelopeEncodeError::Inner { error } => error.scope(),
+            EnvelopeEncodeError::Version { .. } => ErrorScope::Unrecoverable
+        }
+    }
+}
+
+impl<Inner> ScopedError for EnvelopeDecodeError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            EnvelopeDecodeError::Inner { error } => error.scope(),
+            EnvelopeDecodeError::BadVersion { .. } => ErrorScope::Session,
+            _ => ErrorScope::Msg
+        }
+    }
+}
+
+impl<Inner> Display for EnvelopeEncodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            EnvelopeEncodeError::Inner { error } => error.fmt(f),
+            EnvelopeEncodeError::Version { error } => {
+                write!(f, "error encoding version: {}", error)
+            }
+        }
+    }
+}
+
+impl<Inner> Display for EnvelopeDecodeError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            EnvelopeDecodeError::Inner { error } => error.fmt(f),
+            EnvelopeDecodeError::Version { error } => {
+                write!(f, "error decoding version: {}", error)
+            }
+            EnvelopeDecodeError::Truncated => {
+                write!(f, "message too short for envelope header")
+            }
+            EnvelopeDecodeError::BadProtocol { expected, actual } => write!(
+                f,
+                "bad protocol identifier {:04x} (expected {:04x})",
+                actual, expected
+            ),
+            EnvelopeDecodeError::BadVersion { version } => {
+                write!(f, "unacceptable peer version {}", version)
+            }
+            EnvelopeDecodeError::UnknownType { msg_type } => {
+                write!(f, "unknown message type {}", msg_type)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::convert::Infallible;
+
+#[cfg(test)]
+use crate::codec::per::PERCodec;
+#[cfg(test)]
+use crate::version::VersionRangeElem;
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+enum TestMsg {
+    Version(Version),
+    Range(VersionRange)
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct TestCodec {
+    version: VersionPERCodec,
+    range: PERCodec<VersionRange, 128>
+}
+
+#[cfg(test)]
+impl DatagramCodec<TestMsg> for TestCodec {
+    type CreateError = Infallible;
+    type DecodeError = Error;
+    type EncodeError = Error;
+    type Param = ();
+
+    const MAX_BYTES: usize = PERCodec::<VersionRange, 128>::MAX_BYTES;
+
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(TestCodec::default())
+    }
+
+    fn encode(
+        &mut self,
+        val: &TestMsg,
+        buf: &mut [u8]
+    ) -> Result<usize, Error> {
+        match val {
+            TestMsg::Version(version) => self.version.encode(version, buf),
+            TestMsg::Range(range) => self.range.encode(range, buf)
+        }
+    }
+
+    fn decode(
+        &mut self,
+        _buf: &[u8]
+    ) -> Result<(TestMsg, usize), Error> {
+        panic!("Envelope should dispatch on message type")
+    }
+}
+
+#[cfg(test)]
+impl EnvelopeInnerCodec<TestMsg> for TestCodec {
+    fn msg_type(val: &TestMsg) -> u16 {
+        match val {
+            TestMsg::Version(_) => 1,
+            TestMsg::Range(_) => 2
+        }
+    }
+
+    fn decode_msg_type(
+        &mut self,
+        msg_type: u16,
+        buf: &[u8]
+    ) -> Result<Option<(TestMsg, usize)>, Error> {
+        match msg_type {
+            1 => self
+                .version
+                .decode(buf)
+                .map(|(val, n)| Some((TestMsg::Version(val), n))),
+            2 => self
+                .range
+                .decode(buf)
+                .map(|(val, n)| Some((TestMsg::Range(val), n))),
+            _ => Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_param(
+    protocol: u16,
+    version: Version
+) -> EnvelopeParam<()> {
+    let accept = VersionRange {
+        lower: Some(VersionRangeElem::minor(1, 2)),
+        upper: Some(VersionRangeElem::major(1))
+    };
+
+    EnvelopeParam::new(protocol, version, accept, ())
+}
+
+#[test]
+fn test_envelope_round_trip() {
+    let param = test_param(0xc0de, Version::new(1, 3, 0));
+    let mut codec = EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let msgs = [
+        TestMsg::Version(Version::new(4, 5, 6)),
+        TestMsg::Range(VersionRange {
+            lower: Some(VersionRangeElem::sub(1, 2, 3)),
+            upper: None
+        })
+    ];
+
+    for msg in msgs {
+        let encoded = codec.encode_to_vec(&msg).unwrap();
+        let (actual, nbytes) = codec.decode(&encoded).unwrap();
+
+        assert_eq!(msg, actual);
+        assert_eq!(encoded.len(), nbytes);
+    }
+}
+
+#[test]
+fn test_envelope_header() {
+    let param = test_param(0xc0de, Version::new(1, 3, 0));
+    let mut codec = EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let msg = TestMsg::Version(Version::new(4, 5, 6));
+    let encoded = codec.encode_to_vec(&msg).unwrap();
+    let (header, nbytes) = codec.decode_header(&encoded).unwrap();
+
+    assert_eq!(&encoded[..2], &[0xc0, 0xde]);
+    assert_eq!(header.protocol(), 0xc0de);
+    assert_eq!(header.version(), &Version::new(1, 3, 0));
+    assert_eq!(header.msg_type(), 1);
+    assert_eq!(nbytes, ENVELOPE_HEADER_MAX_BYTES);
+}
+
+#[test]
+fn test_envelope_bad_version() {
+    let param = test_param(0xc0de, Version::new(2, 0, 0));
+    let mut sender =
+        EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let param = test_param(0xc0de, Version::new(1, 3, 0));
+    let mut receiver =
+        EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let msg = TestMsg::Version(Version::new(4, 5, 6));
+    let encoded = sender.encode_to_vec(&msg).unwrap();
+
+    match receiver.decode(&encoded) {
+        Err(EnvelopeDecodeError::BadVersion { version }) => {
+            assert_eq!(version, Version::new(2, 0, 0))
+        }
+        _ => panic!("Expected bad version error")
+    }
+}
+
+#[test]
+fn test_envelope_bad_protocol() {
+    let param = test_param(0xc0de, Version::new(1, 3, 0));
+    let mut sender =
+        EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let param = test_param(0xbeef, Version::new(1, 3, 0));
+    let mut receiver =
+        EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let msg = TestMsg::Version(Version::new(4, 5, 6));
+    let encoded = sender.encode_to_vec(&msg).unwrap();
+
+    assert!(matches!(
+        receiver.decode(&encoded),
+        Err(EnvelopeDecodeError::BadProtocol {
+            expected: 0xbeef,
+            actual: 0xc0de
+        })
+    ));
+}
+
+#[test]
+fn test_envelope_unknown_type() {
+    let param = test_param(0xc0de, Version::new(1, 3, 0));
+    let mut codec = EnvelopeCodec::<TestMsg, TestCodec>::create(param).unwrap();
+    let msg = TestMsg::Version(Version::new(4, 5, 6));
+    let mut encoded = codec.encode_to_vec(&msg).unwrap();
+
+    encoded[ENVELOPE_HEADER_MAX_BYTES - 1] = 7;
+
+    assert!(matches!(
+        codec.decode(&encoded),
+        Err(EnvelopeDecodeError::UnknownType { msg_type: 7 })
+    ));
+}
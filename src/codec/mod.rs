@@ -30,7 +30,15 @@
 //! * It facilitates the use of encoding formats such as ASN.1 PER.
 use std::fmt::Display;
 
+#[cfg(feature = "openssl")]
+pub mod aead;
+pub mod aper;
+pub mod compress;
+pub mod der;
+pub mod envelope;
 pub mod per;
+#[cfg(feature = "proptest")]
+pub mod testing;
 
 /// Trait for encoding/decoding logic on types to datagrams.
 pub trait DatagramCodec<T>: Sized {
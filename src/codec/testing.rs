@@ -0,0 +1,443 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Property-testing harness for [DatagramCodec] implementations.
+//!
+//! This module provides generic checks that can be applied to any
+//! [DatagramCodec] implementation, either from unit tests (using
+//! [proptest]) or from fuzz targets.  The following properties are
+//! checked:
+//!
+//! * Encoding and then decoding a value produces the same value, and consumes
+//!   exactly the encoded bytes.
+//!
+//! * [encode](DatagramCodec::encode) and
+//!   [encode_to_vec](DatagramCodec::encode_to_vec) never produce more than
+//!   [MAX_BYTES](DatagramCodec::MAX_BYTES) bytes, and produce encodings of the
+//!   same length.
+//!
+//! * Decoding arbitrary bytes never panics, never reports consuming more bytes
+//!   than were given, and any value so decoded itself satisfies the round-trip
+//!   property.
+//!
+//! This module is only available with the `proptest` feature, and is
+//! intended to be used by downstream packages in their own tests:
+//!
+//! ```
+//! use constellation_common::codec::testing::arb_version;
+//! use constellation_common::codec::testing::check_codec;
+//! use constellation_common::version::VersionPERCodec;
+//!
+//! check_codec::<_, VersionPERCodec, _>((), arb_version());
+//! ```
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use proptest::collection::vec;
+use proptest::prelude::any;
+use proptest::prelude::Just;
+use proptest::prop_oneof;
+use proptest::strategy::Strategy;
+use proptest::test_runner::Config;
+use proptest::test_runner::TestCaseError;
+use proptest::test_runner::TestError;
+use proptest::test_runner::TestRunner;
+
+use crate::codec::DatagramCodec;
+use crate::version::Version;
+use crate::version::VersionRange;
+use crate::version::VersionRangeElem;
+use crate::version::VersionRangeElemMajor;
+use crate::version::VersionRangeElemMinor;
+use crate::version::VersionRangeElemSub;
+
+/// Byte used to fill encoding buffers, to detect codecs that report
+/// the wrong length.
+const FILL_BYTE: u8 = 0xa5;
+
+/// Violations of codec properties detected by the checks in this
+/// module.
+#[derive(Debug)]
+pub enum CodecCheckError<Encode, Decode> {
+    /// Encoding a value failed.
+    Encode {
+        /// Error from the codec.
+        error: Encode
+    },
+    /// Decoding an encoded value failed.
+    Decode {
+        /// Error from the codec.
+        error: Decode
+    },
+    /// Encoding produced more than `MAX_BYTES` bytes.
+    TooLong {
+        /// Length of the encoding.
+        len: usize,
+        /// Value of `MAX_BYTES`.
+        max: usize
+    },
+    /// `encode` and `encode_to_vec` produced different lengths.
+    VecMismatch,
+    /// Decoding produced a different value than was encoded.
+    Mismatch,
+    /// Decoding consumed a different number of bytes than were
+    /// produced by encoding.
+    BadLen {
+        /// Number of bytes produced by encoding.
+        encoded: usize,
+        /// Number of bytes consumed by decoding.
+        decoded: usize
+    },
+    /// Decoding reported consuming more bytes than were given.
+    Overrun {
+        /// Number of bytes consumed by decoding.
+        decoded: usize,
+        /// Number of bytes given.
+        len: usize
+    }
+}
+
+/// Check the round-trip property for `val` using `codec`.
+///
+/// This encodes `val` using both [encode](DatagramCodec::encode) and
+/// [encode_to_vec](DatagramCodec::encode_to_vec), checks that both
+/// produce encodings of the same length, no more than
+/// [MAX_BYTES](DatagramCodec::MAX_BYTES), then decodes each
+/// encoding and checks that the result is equal to `val` and
+/// consumes all the encoded bytes.
+///
+/// Note that this decodes each encoding exactly once, so it is
+/// suitable for use with stateful codecs such as
+/// [AEADCodec](crate::codec::aead::AEADCodec).
+pub fn check_round_trip<T, C>(
+    codec: &mut C,
+    val: &T
+) -> Result<(), CodecCheckError<C::EncodeError, C::DecodeError>>
+where
+    C: DatagramCodec<T>,
+    T: PartialEq {
+    let mut buf = vec![FILL_BYTE; C::MAX_BYTES];
+    let len = codec
+        .encode(val, &mut buf)
+        .map_err(|err| CodecCheckError::Encode { error: err })?;
+
+    if len > C::MAX_BYTES {
+        return Err(CodecCheckError::TooLong {
+            len: len,
+            max: C::MAX_BYTES
+        });
+    }
+
+    let vec = codec
+        .encode_to_vec(val)
+        .map_err(|err| CodecCheckError::Encode { error: err })?;
+
+    if vec.len() > C::MAX_BYTES {
+        return Err(CodecCheckError::TooLong {
+            len: vec.len(),
+            max: C::MAX_BYTES
+        });
+    }
+
+    // Stateful codecs (for example, ones with per-message nonces)
+    // may legitimately produce different bytes each time, so only
+    // insist on agreement in length.
+    if vec.len() != len {
+        return Err(CodecCheckError::VecMismatch);
+    }
+
+    for encoded in [&buf[..len], &vec[..]] {
+        let (decoded, nbytes) = codec
+            .decode(encoded)
+            .map_err(|err| CodecCheckError::Decode { error: err })?;
+
+        if nbytes != len {
+            return Err(CodecCheckError::BadLen {
+                encoded: len,
+                decoded: nbytes
+            });
+        }
+
+        if &decoded != val {
+            return Err(CodecCheckError::Mismatch);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that decoding `buf` with `codec` is well-behaved.
+///
+/// Decoding arbitrary bytes may fail, but must not panic, and must
+/// not report consuming more bytes than are present in `buf`.  If
+/// decoding succeeds, the decoded value must itself satisfy
+/// [check_round_trip].
+///
+/// This returns the decoded value, if decoding succeeded.
+pub fn check_decode_bytes<T, C>(
+    codec: &mut C,
+    buf: &[u8]
+) -> Result<Option<T>, CodecCheckError<C::EncodeError, C::DecodeError>>
+where
+    C: DatagramCodec<T>,
+    T: PartialEq {
+    match codec.decode(buf) {
+        Ok((val, nbytes)) => {
+            if nbytes > buf.len() {
+                return Err(CodecCheckError::Overrun {
+                    decoded: nbytes,
+                    len: buf.len()
+                });
+            }
+
+            check_round_trip(codec, &val)?;
+
+            Ok(Some(val))
+        }
+        Err(_) => Ok(None)
+    }
+}
+
+/// Property-test the round-trip property for `codec` over values
+/// generated by `strategy`.
+///
+/// See [check_round_trip] for the properties that are checked.
+pub fn check_codec_round_trip<T, C, S>(
+    param: C::Param,
+    strategy: S,
+    config: Config
+) -> Result<(), TestError<T>>
+where
+    C: DatagramCodec<T>,
+    C::CreateError: Debug,
+    C::EncodeError: Debug,
+    C::DecodeError: Debug,
+    S: Strategy<Value = T>,
+    T: Debug + PartialEq {
+    let codec = RefCell::new(C::create(param).expect("Failed to create codec"));
+    let mut runner = TestRunner::new(config);
+
+    runner.run(&strategy, |val| {
+        check_round_trip(&mut *codec.borrow_mut(), &val)
+            .map_err(|err| TestCaseError::fail(err.to_string()))
+    })
+}
+
+/// Property-test decoding random bytes with `codec`.
+///
+/// Byte strings up to twice [MAX_BYTES](DatagramCodec::MAX_BYTES)
+/// long are generated.  See [check_decode_bytes] for the properties
+/// that are checked.
+pub fn check_codec_decode_random<T, C>(
+    param: C::Param,
+    config: Config
+) -> Result<(), TestError<Vec<u8>>>
+where
+    C: DatagramCodec<T>,
+    C::CreateError: Debug,
+    C::EncodeError: Debug,
+    C::DecodeError: Debug,
+    T: PartialEq {
+    let codec = RefCell::new(C::create(param).expect("Failed to create codec"));
+    let mut runner = TestRunner::new(config);
+    let strategy = vec(any::<u8>(), 0..=C::MAX_BYTES * 2);
+
+    runner.run(&strategy, |buf| {
+        check_decode_bytes(&mut *codec.borrow_mut(), &buf)
+            .map(|_| ())
+            .map_err(|err| TestCaseError::fail(err.to_string()))
+    })
+}
+
+/// Run all property tests on `codec`, panicking on failure.
+///
+/// This runs both [check_codec_round_trip] and
+/// [check_codec_decode_random] with the default [Config], and is
+/// intended to be called directly from a `#[test]` function.
+pub fn check_codec<T, C, S>(
+    param: C::Param,
+    strategy: S
+) where
+    C: DatagramCodec<T>,
+    C::Param: Clone,
+    C::CreateError: Debug,
+    C::EncodeError: Debug,
+    C::DecodeError: Debug,
+    S: Strategy<Value = T>,
+    T: Debug + PartialEq {
+    if let Err(err) = check_codec_round_trip::<T, C, S>(
+        param.clone(),
+        strategy,
+        Config::default()
+    ) {
+        panic!("{}", err)
+    }
+
+    if let Err(err) =
+        check_codec_decode_random::<T, C>(param, Config::default())
+    {
+        panic!("{}", err)
+    }
+}
+
+/// Strategy generating valid [Version]s.
+pub fn arb_version() -> impl Strategy<Value = Version> {
+    (0..=1023u16, 0..=1023u16, 0..=4095u16)
+        .prop_map(|(major, minor, sub)| Version::new(major, minor, sub))
+}
+
+/// Strategy generating valid [VersionRangeElem]s.
+pub fn arb_version_range_elem() -> impl Strategy<Value = VersionRangeElem> {
+    prop_oneof![
+        (0..=1023u16).prop_map(|major| VersionRangeElem::Major(
+            VersionRangeElemMajor::new(major)
+        )),
+        (0..=1023u16, 0..=1023u16).prop_map(|(major, minor)| {
+            VersionRangeElem::Minor(VersionRangeElemMinor::new(major, minor))
+        }),
+        (0..=1023u16, 0..=1023u16, 0..=4095u16).prop_map(
+            |(major, minor, sub)| {
+                VersionRangeElem::Sub(VersionRangeElemSub::new(
+                    major, minor, sub
+                ))
+            }
+        )
+    ]
+}
+
+/// Strategy generating valid [VersionRange]s.
+///
+/// The generated ranges are structurally valid, but are not
+/// necessarily non-empty.
+pub fn arb_version_range() -> impl Strategy<Value = VersionRange> {
+    let bound =
+        prop_oneof![Just(None), arb_version_range_elem().prop_map(Some)];
+
+    (bound.clone(), bound).prop_map(|(lower, upper)| VersionRange {
+        lower: lower,
+        upper: upper
+    })
+}
+
+impl<Encode, Decode> Display for CodecCheckError<Encode, Decode>
+where
+    Encode: Debug,
+    Decode: Debug
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            CodecCheckError::Encode { error } => {
+                write!(f, "error encoding: {:?}", error)
+            }
+            CodecCheckError::Decode { error } => {
+                write!(f, "error decoding: {:?}", error)
+            }
+            CodecCheckError::TooLong { len, max } => {
+                write!(f, "encoding is {} bytes (maximum {})", len, max)
+            }
+            CodecCheckError::VecMismatch => {
+                write!(f, "encode and encode_to_vec produced different lengths")
+            }
+            CodecCheckError::Mismatch => {
+                write!(f, "decoded value differs from encoded value")
+            }
+            CodecCheckError::BadLen { encoded, decoded } => write!(
+                f,
+                "encoded {} bytes, but decoding consumed {}",
+                encoded, decoded
+            ),
+            CodecCheckError::Overrun { decoded, len } => write!(
+                f,
+                "decoding consumed {} bytes, but only {} were given",
+                decoded, len
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::codec::aper::APERCodec;
+#[cfg(test)]
+use crate::codec::compress::CompressCodec;
+#[cfg(test)]
+use crate::codec::compress::CompressParam;
+#[cfg(test)]
+use crate::codec::der::DERCodec;
+#[cfg(test)]
+use crate::codec::per::PERCodec;
+#[cfg(test)]
+use crate::version::VersionPERCodec;
+
+#[test]
+fn test_version_per_codec() {
+    check_codec::<_, VersionPERCodec, _>((), arb_version());
+}
+
+#[test]
+fn test_version_range_per_codec() {
+    check_codec::<_, PERCodec<VersionRange, 80>, _>((), arb_version_range());
+}
+
+#[test]
+fn test_version_aper_codec() {
+    check_codec::<_, APERCodec<Version, 48>, _>((), arb_version());
+}
+
+#[test]
+fn test_version_range_aper_codec() {
+    check_codec::<_, APERCodec<VersionRange, 128>, _>((), arb_version_range());
+}
+
+#[test]
+fn test_version_der_codec() {
+    check_codec::<_, DERCodec<Version, 128>, _>((), arb_version());
+}
+
+#[test]
+fn test_version_range_der_codec() {
+    check_codec::<_, DERCodec<VersionRange, 320>, _>((), arb_version_range());
+}
+
+#[test]
+fn test_version_range_compress_codec() {
+    check_codec::<_, CompressCodec<_, PERCodec<VersionRange, 80>, 16>, _>(
+        CompressParam::from(()),
+        arb_version_range()
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_version_aead_codec() {
+    use crate::codec::aead::AEADAlgo;
+    use crate::codec::aead::AEADCodec;
+    use crate::codec::aead::AEADKey;
+    use crate::codec::aead::AEADParam;
+
+    let keys = vec![AEADKey::new(1, AEADAlgo::AES256GCM, vec![0x5a; 32])];
+
+    check_codec::<_, AEADCodec<_, VersionPERCodec>, _>(
+        AEADParam::new(keys, 1, ()),
+        arb_version()
+    );
+}
@@ -25,4 +25,5 @@
 
 pub mod authn;
 pub mod pki;
-// pub mod signing;
+#[cfg(feature = "pgp")]
+pub mod signing;
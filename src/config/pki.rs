@@ -21,14 +21,26 @@
 //! This module contains configuration objects PKI trust roots.  This
 //! functionality is used for setting up DTLS/TLS sessions, as well as
 //! for signing and signature verification purposes.
-#[cfg(feature = "openssl")]
 use std::convert::TryFrom;
 use std::fmt::Display;
 use std::fmt::Formatter;
+#[cfg(feature = "openssl")]
+use std::fs::metadata;
+#[cfg(feature = "openssl")]
+use std::fs::read;
+#[cfg(feature = "openssl")]
+use std::fs::read_dir;
+use std::io::Error;
+#[cfg(feature = "openssl")]
+use std::path::Path;
 use std::path::PathBuf;
 #[cfg(feature = "openssl")]
+use std::time::Duration;
+#[cfg(feature = "openssl")]
 use std::time::SystemTime;
 
+#[cfg(feature = "rustls")]
+use ::rustls::client::VerifierBuilderError;
 #[cfg(feature = "openssl")]
 use log::debug;
 #[cfg(feature = "openssl")]
@@ -36,10 +48,38 @@ use log::info;
 #[cfg(feature = "openssl")]
 use log::trace;
 #[cfg(feature = "openssl")]
+use openssl::asn1::Asn1Time;
+#[cfg(feature = "openssl")]
+use openssl::asn1::Asn1TimeRef;
+#[cfg(feature = "openssl")]
 use openssl::error::ErrorStack;
 #[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
+#[cfg(feature = "openssl")]
+use openssl::pkey::Private;
+#[cfg(feature = "openssl")]
+use openssl::ssl::ConnectConfiguration;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslAcceptor;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslAcceptorBuilder;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslConnector;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslContext;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslContextBuilder;
+#[cfg(feature = "openssl")]
 use openssl::ssl::SslFiletype;
 #[cfg(feature = "openssl")]
+use openssl::ssl::SslMethod;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslVerifyMode;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslVersion;
+#[cfg(feature = "openssl")]
+use openssl::ssl::StatusType;
+#[cfg(feature = "openssl")]
 use openssl::x509::store::X509Lookup;
 #[cfg(feature = "openssl")]
 use openssl::x509::store::X509Store;
@@ -52,21 +92,68 @@ use openssl::x509::verify::X509VerifyFlags;
 #[cfg(feature = "openssl")]
 use openssl::x509::verify::X509VerifyParam;
 #[cfg(feature = "openssl")]
+use openssl::x509::verify::X509VerifyParamRef;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509Crl;
+#[cfg(feature = "openssl")]
 use openssl::x509::X509PurposeId;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509Ref;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509;
 use serde::Deserialize;
 use serde::Serialize;
-#[cfg(feature = "openssl")]
+#[cfg(any(feature = "openssl", feature = "rustls"))]
 use serde::Serializer;
 #[cfg(feature = "openssl")]
 use time::OffsetDateTime;
 
+#[cfg(feature = "openssl")]
+use crate::codec::der::split_tlv;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use crate::config::pki::constraints::PKIPeerConstraints;
+use crate::config::pki::keys::PKIFileKey;
+use crate::config::pki::keys::PKIKey;
+use crate::config::pki::keys::PKIKeyLoadError;
+#[cfg(feature = "openssl")]
+use crate::config::pki::keys::PKIKeyProvider;
+use crate::config::pki::keys::PKIPassphraseSource;
+#[cfg(feature = "openssl")]
+use crate::config::pki::ocsp::OCSPError;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use crate::config::pki::ocsp::PKIOCSPConfig;
+#[cfg(feature = "openssl")]
+use crate::dtls::set_cookie_callbacks;
 use crate::error::ErrorScope;
 use crate::error::ScopedError;
 #[cfg(feature = "openssl")]
+use crate::hashid::CompoundHashAlgo;
+#[cfg(feature = "openssl")]
+use crate::hashid::CompoundHashID;
+#[cfg(feature = "openssl")]
+use crate::hashid::HashAlgo;
+#[cfg(feature = "openssl")]
 use crate::net::IPEndpointAddr;
+#[cfg(feature = "openssl")]
+use crate::sign::cert_subject;
+#[cfg(feature = "openssl")]
+use crate::sign::name_oneline;
 
-/// Allowed flags for X509 hosts.
+pub mod constraints;
+#[cfg(feature = "openssl")]
+pub mod diagnostics;
+pub mod expiry;
+pub mod keys;
+pub mod ocsp;
 #[cfg(feature = "openssl")]
+pub mod reload;
+#[cfg(feature = "rustls")]
+pub mod rustls;
+#[cfg(all(feature = "openssl", any(test, feature = "test-pki")))]
+pub mod testing;
+
+/// Allowed flags for X509 hosts.
+#[cfg(any(feature = "openssl", feature = "rustls"))]
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[serde(untagged)]
 #[serde(try_from = "&'_ str")]
@@ -80,7 +167,7 @@ pub enum X509HostFlag {
 }
 
 /// Allowed verification flags.
-#[cfg(feature = "openssl")]
+#[cfg(any(feature = "openssl", feature = "rustls"))]
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[serde(untagged)]
 #[serde(try_from = "&'_ str")]
@@ -116,10 +203,118 @@ pub enum PKITrustRootLoadError {
         /// The bad [SystemTime].
         time: SystemTime
     },
+    #[cfg(feature = "rustls")]
+    /// A certificate or CRL file could not be read.
+    PEM {
+        /// Path to the file.
+        path: PathBuf,
+        /// The PEM error.
+        error: ::rustls::pki_types::pem::Error
+    },
+    #[cfg(feature = "rustls")]
+    /// A root certificate could not be used as a trust anchor.
+    BadRootCert {
+        /// Path to the file containing the certificate.
+        path: PathBuf,
+        /// The rustls error.
+        error: ::rustls::Error
+    },
+    #[cfg(feature = "rustls")]
+    /// The certificate verifier could not be built.
+    Verifier {
+        /// The verifier error.
+        error: VerifierBuilderError
+    },
+    #[cfg(feature = "rustls")]
+    /// A configuration option cannot be honored by the rustls
+    /// backend.
+    Unsupported {
+        /// Description of the option.
+        option: String
+    },
     /// No root certificates were found.
     NoRootCerts
 }
 
+/// Errors that can occur while loading a [PKIIdentity].
+#[derive(Debug)]
+pub enum PKIIdentityLoadError {
+    /// An IO error occurred reading a file.
+    IO {
+        /// Path to the file.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    },
+    #[cfg(feature = "openssl")]
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The certificate chain file contained no certificates.
+    NoCerts {
+        /// Path to the certificate chain file.
+        path: PathBuf
+    },
+    /// An error occurred loading the private key.
+    Key {
+        /// The key loading error.
+        error: PKIKeyLoadError
+    },
+    #[cfg(feature = "rustls")]
+    /// The certificate chain could not be read.
+    PEM {
+        /// Path to the certificate chain.
+        path: PathBuf,
+        /// The PEM error.
+        error: ::rustls::pki_types::pem::Error
+    },
+    #[cfg(feature = "rustls")]
+    /// An error occurred in the rustls library.
+    Rustls {
+        /// The rustls error.
+        error: ::rustls::Error
+    },
+    /// The private key does not match the leaf certificate.
+    KeyMismatch
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+/// Errors that can occur while creating a TLS context from a
+/// [PKITrustRoot] and [PKIIdentity].
+#[derive(Debug)]
+pub enum TLSContextLoadError {
+    /// An error occurred loading the trust root.
+    TrustRoot {
+        /// The trust root error.
+        error: PKITrustRootLoadError
+    },
+    /// An error occurred loading the identity.
+    Identity {
+        /// The identity error.
+        error: PKIIdentityLoadError
+    },
+    #[cfg(feature = "openssl")]
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    #[cfg(feature = "openssl")]
+    /// An error occurred loading the OCSP response to staple.
+    OCSP {
+        /// The OCSP error.
+        error: OCSPError
+    },
+    #[cfg(feature = "rustls")]
+    /// An error occurred in the rustls library.
+    Rustls {
+        /// The rustls error.
+        error: ::rustls::Error
+    }
+}
+
 /// Configurations for a PKI-based root-of-trust.
 ///
 /// This provides the configuration options for verifying signatures
@@ -127,8 +322,8 @@ pub enum PKITrustRootLoadError {
 ///
 /// # YAML Format
 ///
-/// The YAML format has seven fields, some of which are present only
-/// when the `openssl` feature is enabled:
+/// The YAML format has nine fields, some of which are present only
+/// when the `openssl` or `rustls` features are enabled:
 ///
 /// - `dirs`: A list of paths to CA directories, containing root certificates
 ///   and CRLs.
@@ -198,8 +393,43 @@ pub enum PKITrustRootLoadError {
 ///     RSA, DSA and DH keys shorter than 15360 bits and ECC keys shorter than
 ///     512 bits are prohibited.
 ///
-/// - `verify-depth`: Depth to which to verify certificate chains. This is only
-///   available on OpenSSL.
+/// - `verify-depth`: Depth to which to verify certificate chains.
+///
+/// - `ocsp`: OCSP revocation checking options, in the format given by
+///   [PKIOCSPConfig].  This is only supported with OpenSSL.
+///
+/// - `peer-constraints`: Additional constraints on peer certificates, in the
+///   format given by [PKIPeerConstraints].  This is only supported with
+///   OpenSSL.
+///
+/// ## rustls
+///
+/// With the `rustls` feature, the same configuration can be used to
+/// verify peers with rustls and webpki, without linking OpenSSL.
+/// This provides a trust root for statically linked and embedded
+/// builds, which is loaded only from the files in the configuration.
+/// The certificate chain and CRLs are PEM-encoded, as with OpenSSL;
+/// every file in `dirs` is read, and any certificates and CRLs in it
+/// are used.  The options are mapped as follows:
+///
+/// - `verify-flags`: `CRL_CHECK` and `CRL_CHECK_ALL` are honored, and as with
+///   OpenSSL, CRLs are only checked if one of them is given.  `NO_CHECK_TIME`
+///   and `ALLOW_PROXY_CERTS` are ignored with a warning, as webpki always
+///   checks validity periods and never accepts proxy certificates.  The
+///   remaining flags are rejected.
+///
+/// - `host-flags`: `NO_PARTIAL_WILDCARDS` and `NEVER_CHECK_SUBJECT` are always
+///   in effect with webpki.  `ALWAYS_CHECK_SUBJECT`, `MULTI_LABEL_WILDCARDS`,
+///   and `SINGLE_LABEL_SUBDOMAINS` are ignored with a warning.  `NO_WILDCARDS`
+///   is rejected.
+///
+/// - `auth-level`: Levels up to 2 are accepted, as rustls never negotiates
+///   anything weaker.  Higher levels are rejected.
+///
+/// - `verify-depth`: Depths of 6 or more are accepted, as webpki allows at most
+///   6 intermediate certificates.  Lower depths are rejected.
+///
+/// - `ocsp` and `peer-constraints` are rejected.
 ///
 /// ## Examples
 ///
@@ -220,6 +450,20 @@ pub enum PKITrustRootLoadError {
 ///   - NO_WILDCARDS
 /// auth-level: 4
 /// verify-depth: 16
+/// ocsp:
+///   responder-url: http://ocsp.example.com/
+///   nonce: true
+///   freshness-tolerance: 300
+///   responses:
+///     - /etc/ssl/ocsp/server-cert.der
+///   require-stapled: true
+/// peer-constraints:
+///   spki-pins:
+///     - SHA256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+///   alt-names:
+///     - "*.nodes.example.com"
+///   required-ekus:
+///     - serverAuth
 /// ```
 #[derive(
     Clone,
@@ -245,15 +489,15 @@ pub struct PKITrustRoot {
     /// A list of paths to files containing PEM-encoded CRLs.
     #[serde(default)]
     crls: Vec<PathBuf>,
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// OpenSSL verification flags.
     #[serde(default)]
     verify_flags: Vec<X509VerifyFlag>,
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// OpenSSL host flags.
     #[serde(default)]
     host_flags: Vec<X509HostFlag>,
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// OpenSSL authentication level.
     ///
     /// This can be used as a blanket method for setting a minimum
@@ -294,10 +538,124 @@ pub struct PKITrustRoot {
     ///   bits are prohibited.
     #[serde(default)]
     auth_level: Option<u8>,
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// Depth to which to verify certificate chains.
     #[serde(default)]
-    verify_depth: Option<u8>
+    verify_depth: Option<u8>,
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    /// OCSP revocation checking options.
+    #[serde(default)]
+    ocsp: Option<PKIOCSPConfig>,
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    /// Additional constraints on peer certificates.
+    #[serde(default)]
+    peer_constraints: Option<PKIPeerConstraints>
+}
+
+/// Configuration for a PKI identity.
+///
+/// This consists of a private key, together with a certificate chain
+/// binding its public key to an identity.  This is used for signing,
+/// as well as for authenticating to peers.
+///
+/// # YAML Format
+///
+/// The YAML format has the following fields:
+///
+/// - `cert-chain`: Path to a file containing a PEM-encoded certificate chain.
+///   The first certificate must be the one for the private key, followed by any
+///   intermediate certificates needed to link it to a root.
+///
+/// - `key`: Either a [PKIKey] describing where the private key is held, or a
+///   path to a file containing a PEM-encoded private key.
+///
+/// - `key-password-file` (optional): Path to a file containing the password for
+///   an encrypted private key.  Only the first line of the file is used.  This
+///   may only be given if `key` is a path, and is equivalent to a `file`
+///   [PKIPassphraseSource].
+///
+/// ## Examples
+///
+/// ```yaml
+/// cert-chain: /etc/ssl/certs/server-cert.pem
+/// key: /etc/ssl/private/server-key.pem
+/// key-password-file: /etc/ssl/private/server-key.pass
+/// ```
+///
+/// ```yaml
+/// cert-chain: /etc/ssl/certs/server-cert.pem
+/// key:
+///   pkcs11:
+///     uri: "pkcs11:token=constellation;object=server-key;type=private"
+///     pin:
+///       env: PKCS11_PIN
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-identity")]
+#[serde(try_from = "PKIIdentityRepr")]
+pub struct PKIIdentity {
+    /// Path to the PEM-encoded certificate chain.
+    cert_chain: PathBuf,
+    /// Location of the private key.
+    key: PKIKey
+}
+
+/// Deserialized form of [PKIIdentity], accepting bare key paths.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-identity")]
+struct PKIIdentityRepr {
+    cert_chain: PathBuf,
+    key: PKIKeyRepr,
+    #[serde(default)]
+    key_password_file: Option<PathBuf>
+}
+
+/// Deserialized form of [PKIKey], accepting bare key paths.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PKIKeyRepr {
+    Path(PathBuf),
+    Key(PKIKey)
+}
+
+#[cfg(feature = "openssl")]
+/// Information about a certificate, for diagnostic purposes.
+///
+/// This can be obtained for any certificate with
+/// [inspect](PKICertInfo::inspect), and is reported for each root
+/// certificate by [diagnose](PKITrustRoot::diagnose).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PKICertInfo {
+    /// Path to the file containing the certificate.
+    path: PathBuf,
+    /// Subject name of the certificate.
+    subject: String,
+    /// Issuer name of the certificate.
+    issuer: String,
+    /// Start of the certificate's validity period.
+    not_before: SystemTime,
+    /// Expiry time of the certificate.
+    not_after: SystemTime,
+    /// Hash of the DER-encoded certificate.
+    fingerprint: CompoundHashID
+}
+
+#[cfg(feature = "openssl")]
+/// Information about a CRL, for diagnostic purposes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PKICRLInfo {
+    /// Path to the file containing the CRL.
+    path: PathBuf,
+    /// Issuer name of the CRL.
+    issuer: String,
+    /// Time at which the CRL was issued.
+    last_update: SystemTime,
+    /// Time by which the next CRL will be issued, if given.
+    next_update: Option<SystemTime>
 }
 
 impl PKITrustRoot {
@@ -313,9 +671,9 @@ impl PKITrustRoot {
     ///
     /// ```
     /// # use constellation_common::config::pki::PKITrustRoot;
-    /// # #[cfg(feature = "openssl")]
+    /// # #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// # use constellation_common::config::pki::X509HostFlag;
-    /// # #[cfg(feature = "openssl")]
+    /// # #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// # use constellation_common::config::pki::X509VerifyFlag;
     /// # use std::path::PathBuf;
     /// #
@@ -340,16 +698,20 @@ impl PKITrustRoot {
     ///         vec![PathBuf::from("/etc/ssl/CA")],
     ///         vec![PathBuf::from("/etc/ssl/certs/server-ca-cert.pem")],
     ///         vec![PathBuf::from("/etc/ssl/crls/server-ca-crl.pem")],
-    ///         #[cfg(feature = "openssl")]
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
     ///         vec![X509VerifyFlag::ExplicitPolicy,
     ///              X509VerifyFlag::AllowProxyCerts],
-    ///         #[cfg(feature = "openssl")]
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
     ///         vec![X509HostFlag::AlwaysCheckSubject,
     ///              X509HostFlag::NoWildcards],
-    ///         #[cfg(feature = "openssl")]
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
     ///         Some(4),
-    ///         #[cfg(feature = "openssl")]
-    ///         Some(16)
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
+    ///         Some(16),
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
+    ///         None,
+    ///         #[cfg(any(feature = "openssl", feature = "rustls"))]
+    ///         None
     ///     ),
     ///     serde_yaml::from_str(yaml).unwrap()
     /// );
@@ -359,23 +721,39 @@ impl PKITrustRoot {
         dirs: Vec<PathBuf>,
         certs: Vec<PathBuf>,
         crls: Vec<PathBuf>,
-        #[cfg(feature = "openssl")] verify_flags: Vec<X509VerifyFlag>,
-        #[cfg(feature = "openssl")] host_flags: Vec<X509HostFlag>,
-        #[cfg(feature = "openssl")] auth_level: Option<u8>,
-        #[cfg(feature = "openssl")] verify_depth: Option<u8>
+        #[cfg(any(feature = "openssl", feature = "rustls"))] verify_flags: Vec<
+            X509VerifyFlag
+        >,
+        #[cfg(any(feature = "openssl", feature = "rustls"))] host_flags: Vec<
+            X509HostFlag
+        >,
+        #[cfg(any(feature = "openssl", feature = "rustls"))] auth_level: Option<
+            u8
+        >,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        verify_depth: Option<u8>,
+        #[cfg(any(feature = "openssl", feature = "rustls"))] ocsp: Option<
+            PKIOCSPConfig
+        >,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        peer_constraints: Option<PKIPeerConstraints>
     ) -> PKITrustRoot {
         PKITrustRoot {
             dirs: dirs,
             root_certs: certs,
             crls: crls,
-            #[cfg(feature = "openssl")]
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
             verify_flags: verify_flags,
-            #[cfg(feature = "openssl")]
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
             host_flags: host_flags,
-            #[cfg(feature = "openssl")]
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
             auth_level: auth_level,
-            #[cfg(feature = "openssl")]
-            verify_depth: verify_depth
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
+            verify_depth: verify_depth,
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
+            ocsp: ocsp,
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
+            peer_constraints: peer_constraints
         }
     }
 
@@ -397,34 +775,82 @@ impl PKITrustRoot {
         &self.crls
     }
 
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// Get the verification flags.
     #[inline]
     pub fn verify_flags(&self) -> &[X509VerifyFlag] {
         &self.verify_flags
     }
 
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// Get the host flags.
     #[inline]
     pub fn host_flags(&self) -> &[X509HostFlag] {
         &self.host_flags
     }
 
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// Get the OpenSSL authentication level.
     #[inline]
     pub fn auth_level(&self) -> Option<u8> {
         self.auth_level
     }
 
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     /// Get the maximum verification depth.
     #[inline]
     pub fn verify_depth(&self) -> Option<u8> {
         self.verify_depth
     }
 
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    /// Get the OCSP configuration.
+    #[inline]
+    pub fn ocsp(&self) -> Option<&PKIOCSPConfig> {
+        self.ocsp.as_ref()
+    }
+
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    /// Get the additional constraints on peer certificates.
+    #[inline]
+    pub fn peer_constraints(&self) -> Option<&PKIPeerConstraints> {
+        self.peer_constraints.as_ref()
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Get the modification times of all files that the trust store
+    /// would be built from.
+    ///
+    /// Files that cannot be accessed are recorded with no time.
+    fn file_stamps(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut stamps = Vec::new();
+
+        for path in self.root_certs.iter().chain(self.crls.iter()) {
+            stamps.push((path.clone(), modified(path)))
+        }
+
+        for dir in &self.dirs {
+            stamps.push((dir.clone(), modified(dir)));
+
+            if let Ok(entries) = read_dir(dir) {
+                let mut entries: Vec<(PathBuf, Option<SystemTime>)> = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| {
+                        let path = entry.path();
+                        let time = modified(&path);
+
+                        (path, time)
+                    })
+                    .collect();
+
+                entries.sort();
+                stamps.extend(entries);
+            }
+        }
+
+        stamps
+    }
+
     #[cfg(feature = "openssl")]
     /// Get the OpenSSL host flags.
     fn load_host_flags(&self) -> X509CheckFlags {
@@ -654,26 +1080,9 @@ impl PKITrustRoot {
             params.set_time(duration.as_secs() as i64)
         }
 
-        match endpoint {
-            Some(IPEndpointAddr::Addr(addr)) => {
-                info!(target: "pki-trust-root",
-                      "setting PKI verification target to {}",
-                      addr);
-
-                params
-                    .set_ip(*addr)
-                    .map_err(|e| PKITrustRootLoadError::OpenSSL { error: e })?
-            }
-            Some(IPEndpointAddr::Name(name)) => {
-                info!(target: "pki-trust-root",
-                      "setting PKI verification target to {}",
-                      name);
-
-                params
-                    .set_host(name)
-                    .map_err(|e| PKITrustRootLoadError::OpenSSL { error: e })?
-            }
-            None => {}
+        if let Some(endpoint) = endpoint {
+            set_verify_endpoint(&mut params, endpoint)
+                .map_err(|e| PKITrustRootLoadError::OpenSSL { error: e })?
         }
 
         if let Some(lvl) = self.auth_level {
@@ -701,10 +1110,10 @@ impl PKITrustRoot {
     /// certificates, if one exists.  The `purpose` parameter is a
     /// [X509PurposeId] giving the trust store's role.
     ///
-    /// Additionally, the [X509Store] will be configured in the
-    /// following ways:
-    ///
-    /// - The minimum protocol version will be set to TLS 1.3
+    /// This only creates the trust store; see
+    /// [load_connector](PKITrustRoot::load_connector) and
+    /// [load_acceptor](PKITrustRoot::load_acceptor) for fully-configured
+    /// TLS contexts.
     ///
     /// # Examples
     ///
@@ -715,25 +1124,34 @@ impl PKITrustRoot {
     /// # use constellation_common::config::pki::PKITrustRoot;
     /// # use constellation_common::net::IPEndpointAddr;
     /// # use openssl::x509::X509PurposeId;
+    /// # #[cfg(feature = "test-pki")]
+    /// # {
+    /// # use constellation_common::config::pki::testing::TestPKI;
+    /// # let pki = TestPKI::generate().unwrap();
+    /// # let ca_cert = pki.path("server/ca_cert.pem");
     /// #
-    /// let yaml = concat!(
-    ///     "root-certs:\n",
-    ///     "  - test/data/certs/server/ca_cert.pem\n",
-    ///     "crls: []\n",
-    ///     "verify-flags:\n",
-    ///     "  - EXPLICIT_POLICY\n",
-    ///     "  - ALLOW_PROXY_CERTS\n",
-    ///     "host-flags:\n",
-    ///     "  - ALWAYS_CHECK_SUBJECT\n",
-    ///     "  - NO_WILDCARDS\n",
-    ///     "auth-level: 4\n",
-    ///     "verify-depth: 16\n"
+    /// let yaml = format!(
+    ///     concat!(
+    ///         "root-certs:\n",
+    ///         "  - {}\n",
+    ///         "crls: []\n",
+    ///         "verify-flags:\n",
+    ///         "  - EXPLICIT_POLICY\n",
+    ///         "  - ALLOW_PROXY_CERTS\n",
+    ///         "host-flags:\n",
+    ///         "  - ALWAYS_CHECK_SUBJECT\n",
+    ///         "  - NO_WILDCARDS\n",
+    ///         "auth-level: 4\n",
+    ///         "verify-depth: 16\n"
+    ///     ),
+    ///     ca_cert.display()
     /// );
-    /// let conf: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    /// let conf: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     ///
     /// conf.load(None, Some(&IPEndpointAddr::name(String::from("test"))),
     ///           X509PurposeId::SSL_CLIENT)
     ///     .expect("Expected success");
+    /// # }
     /// ```
     pub fn load(
         &self,
@@ -811,10 +1229,10 @@ impl PKITrustRoot {
     /// The `verify_time` parameter optionally sets the time that will
     /// be checked against certificate validity and expiry times.
     ///
-    /// Additionally, the [X509Store] will be configured in the
-    /// following ways:
-    ///
-    /// - The minimum protocol version will be set to TLS 1.3
+    /// This only creates the trust store; see
+    /// [load_connector](PKITrustRoot::load_connector) and
+    /// [load_acceptor](PKITrustRoot::load_acceptor) for fully-configured
+    /// TLS contexts.
     #[inline]
     pub fn load_server(
         &self,
@@ -837,10 +1255,10 @@ impl PKITrustRoot {
     /// `endpoint` parameter supplied an [IPEndpointAddr] used to check
     /// certificates.
     ///
-    /// Additionally, the [X509Store] will be configured in the
-    /// following ways:
-    ///
-    /// - The minimum protocol version will be set to TLS 1.3
+    /// This only creates the trust store; see
+    /// [load_connector](PKITrustRoot::load_connector) and
+    /// [load_acceptor](PKITrustRoot::load_acceptor) for fully-configured
+    /// TLS contexts.
     #[inline]
     pub fn load_client(
         &self,
@@ -864,10 +1282,10 @@ impl PKITrustRoot {
     /// `endpoint` parameter supplied an [IPEndpointAddr] used to check
     /// certificates.
     ///
-    /// Additionally, the [X509Store] will be configured in the
-    /// following ways:
-    ///
-    /// - The minimum protocol version will be set to TLS 1.3
+    /// This only creates the trust store; see
+    /// [load_connector](PKITrustRoot::load_connector) and
+    /// [load_acceptor](PKITrustRoot::load_acceptor) for fully-configured
+    /// TLS contexts.
     #[inline]
     pub fn load_peer(
         &self,
@@ -876,112 +1294,973 @@ impl PKITrustRoot {
     ) -> Result<X509Store, PKITrustRootLoadError> {
         self.load(verify_time, Some(endpoint), X509PurposeId::ANY)
     }
-}
 
-impl ScopedError for PKITrustRootLoadError {
-    fn scope(&self) -> ErrorScope {
-        match self {
-            PKITrustRootLoadError::OpenSSL { .. } => ErrorScope::Unrecoverable,
-            PKITrustRootLoadError::BadTime { .. } => ErrorScope::Unrecoverable,
-            PKITrustRootLoadError::NoRootCerts => ErrorScope::System
+    #[cfg(feature = "openssl")]
+    /// Load the trust store used to check OCSP responses, if OCSP is
+    /// configured.
+    ///
+    /// OCSP responses are verified for a different purpose than the
+    /// peer's certificate, and without checking its name.
+    fn load_ocsp_store(
+        &self,
+        verify_time: Option<SystemTime>
+    ) -> Result<Option<X509Store>, PKITrustRootLoadError> {
+        match &self.ocsp {
+            Some(_) => self
+                .load(verify_time, None, X509PurposeId::OCSP_HELPER)
+                .map(Some),
+            None => Ok(None)
         }
     }
-}
 
-impl Display for PKITrustRootLoadError {
-    fn fmt(
+    #[cfg(feature = "openssl")]
+    /// Apply settings common to all TLS contexts.
+    fn configure_tls(
         &self,
-        f: &mut Formatter
-    ) -> Result<(), std::fmt::Error> {
-        match self {
-            #[cfg(feature = "openssl")]
-            PKITrustRootLoadError::OpenSSL { error } => error.fmt(f),
-            #[cfg(feature = "openssl")]
-            PKITrustRootLoadError::BadTime { time } => {
-                write!(
-                    f,
-                    "time {} is before epoch",
-                    OffsetDateTime::from(*time)
-                )
-            }
-            PKITrustRootLoadError::NoRootCerts => {
-                write!(f, "no CA dirs and no root certs in configuration")
-            }
+        builder: &mut SslContextBuilder,
+        store: X509Store,
+        identity: Option<&PKIIdentity>,
+        verify_time: Option<SystemTime>,
+        min_version: SslVersion
+    ) -> Result<(), TLSContextLoadError> {
+        builder
+            .set_min_proto_version(Some(min_version))
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+        builder.set_cert_store(store);
+
+        if let Some(depth) = self.verify_depth {
+            debug!(target: "pki-trust-root",
+                   "setting PKI verification depth to {}",
+                   depth);
+
+            builder.set_verify_depth(depth.into())
         }
-    }
-}
 
-#[cfg(feature = "openssl")]
-impl Serialize for X509HostFlag {
-    fn serialize<S>(
-        &self,
-        serializer: S
-    ) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer {
-        match self {
-            X509HostFlag::AlwaysCheckSubject => {
-                serializer.serialize_str("ALWAYS_CHECK_SUBJECT")
-            }
-            X509HostFlag::NoWildcards => {
-                serializer.serialize_str("NO_WILDCARDS")
-            }
-            X509HostFlag::NoPartialWildcards => {
-                serializer.serialize_str("NO_PARTIAL_WILDCARDS")
-            }
-            X509HostFlag::MultiLabelWildcards => {
-                serializer.serialize_str("MULTI_LABEL_WILDCARDS")
+        let mut stapled = false;
+        let mut identity_chain = Vec::new();
+
+        if let Some(identity) = identity {
+            let (key, chain) = identity
+                .load()
+                .map_err(|err| TLSContextLoadError::Identity { error: err })?;
+
+            if let (Some(ocsp), Some(cert)) = (&self.ocsp, chain.first()) {
+                if let Some(issuer) = ocsp.find_issuer(cert, &chain[1..]) {
+                    stapled = ocsp
+                        .find_response(cert, &issuer)
+                        .map_err(|err| TLSContextLoadError::OCSP {
+                            error: err
+                        })?
+                        .is_some();
+                }
             }
-            X509HostFlag::SingleLabelSubdomains => {
-                serializer.serialize_str("SINGLE_LABEL_SUBDOMAINS")
+
+            identity_chain = chain.clone();
+
+            let mut certs = chain.into_iter();
+
+            if let Some(cert) = certs.next() {
+                builder.set_certificate(&cert).map_err(|err| {
+                    TLSContextLoadError::OpenSSL { error: err }
+                })?;
             }
-            X509HostFlag::NeverCheckSubject => {
-                serializer.serialize_str("NEVER_CHECK_SUBJECT")
+
+            for cert in certs {
+                builder.add_extra_chain_cert(cert).map_err(|err| {
+                    TLSContextLoadError::OpenSSL { error: err }
+                })?;
             }
+
+            builder
+                .set_private_key(&key)
+                .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+        }
+
+        if let Some(ocsp) = &self.ocsp {
+            let ocsp = ocsp.clone();
+            // OCSP responses are verified for a different purpose than
+            // the peer's certificate.
+            let ocsp_store = self
+                .load(verify_time, None, X509PurposeId::OCSP_HELPER)
+                .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+
+            debug!(target: "pki-trust-root",
+                   "enabling OCSP status checks{}",
+                   if stapled { " and stapling" } else { "" });
+
+            builder
+                .set_status_callback(move |ssl| {
+                    ocsp.status_callback(ssl, &ocsp_store, &identity_chain)
+                })
+                .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
         }
+
+        Ok(())
     }
-}
 
-#[cfg(feature = "openssl")]
-impl Serialize for X509VerifyFlag {
-    fn serialize<S>(
+    #[cfg(feature = "openssl")]
+    /// Set the verification mode on `builder`.
+    ///
+    /// If peer constraints are configured, this also installs a
+    /// verification callback that enforces them.
+    fn set_verify(
         &self,
-        serializer: S
-    ) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer {
-        match self {
-            X509VerifyFlag::CRLCheck => serializer.serialize_str("CRL_CHECK"),
-            X509VerifyFlag::CRLCheckAll => {
-                serializer.serialize_str("CRL_CHECK_ALL")
-            }
-            X509VerifyFlag::AllowProxyCerts => {
-                serializer.serialize_str("ALLOW_PROXY_CERTS")
-            }
-            X509VerifyFlag::PolicyCheck => {
-                serializer.serialize_str("POLICY_CHECK")
-            }
-            X509VerifyFlag::ExplicitPolicy => {
-                serializer.serialize_str("EXPLICIT_POLICY")
-            }
-            X509VerifyFlag::InhibitAny => {
-                serializer.serialize_str("INHIBIT_ANY")
-            }
-            X509VerifyFlag::InhibitMap => {
-                serializer.serialize_str("INHIBIT_MAP")
-            }
-            X509VerifyFlag::CheckSSSignature => {
-                serializer.serialize_str("CHECK_SS_SIGNATURE")
-            }
-            X509VerifyFlag::NoCheckTime => {
-                serializer.serialize_str("NO_CHECK_TIME")
+        builder: &mut SslContextBuilder,
+        mode: SslVerifyMode
+    ) {
+        match &self.peer_constraints {
+            Some(constraints) => {
+                let constraints = constraints.clone();
+
+                debug!(target: "pki-trust-root",
+                       "enabling peer certificate constraints");
+
+                builder.set_verify_callback(mode, move |preverify, ctx| {
+                    constraints.verify_callback(preverify, ctx)
+                })
             }
+            None => builder.set_verify(mode)
         }
     }
-}
 
-#[cfg(feature = "openssl")]
-impl<'a> TryFrom<&'a str> for X509HostFlag {
+    #[cfg(feature = "openssl")]
+    /// Create a [ConnectConfiguration] from `connector`, which was
+    /// created by [load_connector](PKITrustRoot::load_connector).
+    ///
+    /// If OCSP is configured, this requests that the server staple an
+    /// OCSP response.  Stapled responses are checked during the
+    /// handshake; see [PKIOCSPConfig] for details.
+    pub fn connect_configuration(
+        &self,
+        connector: &SslConnector
+    ) -> Result<ConnectConfiguration, ErrorStack> {
+        let mut config = connector.configure()?;
+
+        if self.ocsp.is_some() {
+            config.set_status_type(StatusType::OCSP)?;
+        }
+
+        Ok(config)
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Generate an OpenSSL [SslConnector] for connecting to
+    /// `endpoint` from this configuration.
+    ///
+    /// The server's certificate will be verified against the trust
+    /// store created by [load](PKITrustRoot::load), and its name or
+    /// address will be checked against `endpoint`.  If `identity` is
+    /// provided, it will be presented as the client certificate.
+    ///
+    /// The `verify_time` parameter optionally sets the time that will
+    /// be checked against certificate validity and expiry times.
+    ///
+    /// Additionally, the [SslConnector] will be configured in the
+    /// following ways:
+    ///
+    /// - The minimum protocol version will be set to TLS 1.3
+    ///
+    /// - Peer verification will be required
+    ///
+    /// - The verification depth will be set from `verify-depth`, if present
+    ///
+    /// - If `ocsp` is present, stapled OCSP responses will be checked; use
+    ///   [connect_configuration](PKITrustRoot::connect_configuration) to
+    ///   request them
+    pub fn load_connector(
+        &self,
+        identity: Option<&PKIIdentity>,
+        verify_time: Option<SystemTime>,
+        endpoint: &IPEndpointAddr
+    ) -> Result<SslConnector, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing TLS connector from configuration");
+
+        let store = self
+            .load(verify_time, Some(endpoint), X509PurposeId::SSL_SERVER)
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let mut builder = SslConnector::builder(SslMethod::tls_client())
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        self.configure_tls(
+            &mut builder,
+            store,
+            identity,
+            verify_time,
+            SslVersion::TLS1_3
+        )?;
+        self.set_verify(&mut builder, SslVerifyMode::PEER);
+
+        let params = builder.verify_param_mut();
+
+        params.set_hostflags(self.load_host_flags());
+        set_verify_endpoint(params, endpoint)
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        Ok(builder.build())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Generate an OpenSSL [SslAcceptor] from this configuration.
+    ///
+    /// The server will present `identity`, and will require clients
+    /// to present certificates, which will be verified against the
+    /// trust store created by [load](PKITrustRoot::load).
+    ///
+    /// The `verify_time` parameter optionally sets the time that will
+    /// be checked against certificate validity and expiry times.
+    ///
+    /// Additionally, the [SslAcceptor] will be configured in the
+    /// following ways:
+    ///
+    /// - The minimum protocol version will be set to TLS 1.3
+    ///
+    /// - Client certificates will be required
+    ///
+    /// - The verification depth will be set from `verify-depth`, if present
+    ///
+    /// - If `ocsp` is present, a pre-fetched OCSP response for `identity` will
+    ///   be stapled, if one is available
+    #[inline]
+    pub fn load_acceptor(
+        &self,
+        identity: &PKIIdentity,
+        verify_time: Option<SystemTime>
+    ) -> Result<SslAcceptor, TLSContextLoadError> {
+        Ok(self.acceptor_builder(identity, verify_time)?.build())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Create the builder for [load_acceptor](PKITrustRoot::load_acceptor).
+    fn acceptor_builder(
+        &self,
+        identity: &PKIIdentity,
+        verify_time: Option<SystemTime>
+    ) -> Result<SslAcceptorBuilder, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing TLS acceptor from configuration");
+
+        let store = self
+            .load(verify_time, None, X509PurposeId::SSL_CLIENT)
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let mut builder =
+            SslAcceptor::mozilla_modern_v5(SslMethod::tls_server())
+                .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        self.configure_tls(
+            &mut builder,
+            store,
+            Some(identity),
+            verify_time,
+            SslVersion::TLS1_3
+        )?;
+        self.set_verify(
+            &mut builder,
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+        );
+
+        Ok(builder)
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Generate an OpenSSL [SslContext] for the client side of DTLS
+    /// sessions from this configuration.
+    ///
+    /// The peer's certificate will be verified against the trust
+    /// store created by [load](PKITrustRoot::load), and `identity`
+    /// will be presented as the client certificate.  No name or
+    /// address checks are performed, as DTLS peers are identified by
+    /// socket address; the peer's certificate is instead made
+    /// available to the caller.
+    ///
+    /// The `verify_time` parameter optionally sets the time that will
+    /// be checked against certificate validity and expiry times.
+    ///
+    /// Additionally, the [SslContext] will be configured in the
+    /// following ways:
+    ///
+    /// - The minimum protocol version will be set to DTLS 1.2
+    ///
+    /// - Peer verification will be required
+    ///
+    /// - The verification depth will be set from `verify-depth`, if present
+    pub fn load_dtls_client(
+        &self,
+        identity: &PKIIdentity,
+        verify_time: Option<SystemTime>
+    ) -> Result<SslContext, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing DTLS client context from configuration");
+
+        let store = self
+            .load(verify_time, None, X509PurposeId::SSL_SERVER)
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let mut builder = SslContextBuilder::new(SslMethod::dtls_client())
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        self.configure_tls(
+            &mut builder,
+            store,
+            Some(identity),
+            verify_time,
+            SslVersion::DTLS1_2
+        )?;
+        self.set_verify(&mut builder, SslVerifyMode::PEER);
+
+        Ok(builder.build())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Generate an OpenSSL [SslContext] for the server side of DTLS
+    /// sessions from this configuration.
+    ///
+    /// The server will present `identity`, and will require clients
+    /// to present certificates, which will be verified against the
+    /// trust store created by [load](PKITrustRoot::load).
+    ///
+    /// The `verify_time` parameter optionally sets the time that will
+    /// be checked against certificate validity and expiry times.
+    ///
+    /// Additionally, the [SslContext] will be configured in the
+    /// following ways:
+    ///
+    /// - The minimum protocol version will be set to DTLS 1.2
+    ///
+    /// - Client certificates will be required
+    ///
+    /// - Cookie exchange will be enabled, with the callbacks from
+    ///   [set_cookie_callbacks]
+    ///
+    /// - The verification depth will be set from `verify-depth`, if present
+    pub fn load_dtls_server(
+        &self,
+        identity: &PKIIdentity,
+        verify_time: Option<SystemTime>
+    ) -> Result<SslContext, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing DTLS server context from configuration");
+
+        let store = self
+            .load(verify_time, None, X509PurposeId::SSL_CLIENT)
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let mut builder = SslContextBuilder::new(SslMethod::dtls_server())
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        self.configure_tls(
+            &mut builder,
+            store,
+            Some(identity),
+            verify_time,
+            SslVersion::DTLS1_2
+        )?;
+        self.set_verify(
+            &mut builder,
+            SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+        );
+        set_cookie_callbacks(&mut builder)
+            .map_err(|err| TLSContextLoadError::OpenSSL { error: err })?;
+
+        Ok(builder.build())
+    }
+}
+
+#[cfg(feature = "openssl")]
+/// DER contents of the extended key usage extension OID (2.5.29.37).
+pub(crate) const EKU_OID: [u8; 3] = [0x55, 0x1d, 0x25];
+
+#[cfg(feature = "openssl")]
+/// Get the value of the extension with DER-encoded `oid` from the
+/// DER-encoded certificate `cert`, if it has one.
+pub(crate) fn cert_extension<'a>(
+    cert: &'a [u8],
+    oid: &[u8]
+) -> Option<&'a [u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate TBSCertificate, ... }
+    let (0x30, outer, _) = split_tlv(cert).ok()? else {
+        return None;
+    };
+    let (0x30, mut tbs, _) = split_tlv(outer).ok()? else {
+        return None;
+    };
+
+    // extensions [3] EXPLICIT Extensions, the last field
+    while !tbs.is_empty() {
+        let (tag, content, rest) = split_tlv(tbs).ok()?;
+
+        if tag == 0xa3 {
+            let (0x30, mut exts, _) = split_tlv(content).ok()? else {
+                return None;
+            };
+
+            // Extension ::= SEQUENCE { extnID, critical, extnValue }
+            while !exts.is_empty() {
+                let (0x30, ext, rest) = split_tlv(exts).ok()? else {
+                    return None;
+                };
+                let (0x06, id, mut ext) = split_tlv(ext).ok()? else {
+                    return None;
+                };
+
+                if let Ok((0x01, _, after)) = split_tlv(ext) {
+                    ext = after;
+                }
+
+                let (0x04, value, _) = split_tlv(ext).ok()? else {
+                    return None;
+                };
+
+                if id == oid {
+                    return Some(value);
+                }
+
+                exts = rest;
+            }
+
+            return None;
+        }
+
+        tbs = rest;
+    }
+
+    None
+}
+
+#[cfg(feature = "openssl")]
+/// Get the DER contents of the OIDs in an extended key usage
+/// extension value, or [None] if it is malformed.
+pub(crate) fn ext_key_usages(value: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut out = Vec::new();
+    let (0x30, mut oids, []) = split_tlv(value).ok()? else {
+        return None;
+    };
+
+    while !oids.is_empty() {
+        let (0x06, oid, rest) = split_tlv(oids).ok()? else {
+            return None;
+        };
+
+        out.push(oid);
+        oids = rest;
+    }
+
+    Some(out)
+}
+
+#[cfg(feature = "openssl")]
+/// Convert an [Asn1TimeRef] to a [SystemTime].
+fn asn1_system_time(time: &Asn1TimeRef) -> Result<SystemTime, ErrorStack> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    let secs = i64::from(diff.days) * 86400 + i64::from(diff.secs);
+
+    if secs >= 0 {
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64))
+    } else {
+        Ok(SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()))
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[inline]
+fn modified(path: &Path) -> Option<SystemTime> {
+    metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(feature = "openssl")]
+fn set_verify_endpoint(
+    params: &mut X509VerifyParamRef,
+    endpoint: &IPEndpointAddr
+) -> Result<(), ErrorStack> {
+    match endpoint {
+        IPEndpointAddr::Addr(addr) => {
+            info!(target: "pki-trust-root",
+                  "setting PKI verification target to {}",
+                  addr);
+
+            params.set_ip(*addr)
+        }
+        IPEndpointAddr::Name(name) => {
+            info!(target: "pki-trust-root",
+                  "setting PKI verification target to {}",
+                  name);
+
+            params.set_host(name)
+        }
+    }
+}
+
+impl TryFrom<PKIIdentityRepr> for PKIIdentity {
+    type Error = &'static str;
+
+    fn try_from(val: PKIIdentityRepr) -> Result<PKIIdentity, &'static str> {
+        let key = match (val.key, val.key_password_file) {
+            (PKIKeyRepr::Path(path), password) => PKIKey::File(
+                PKIFileKey::new(path, password.map(PKIPassphraseSource::File))
+            ),
+            (PKIKeyRepr::Key(key), None) => key,
+            (PKIKeyRepr::Key(_), Some(_)) => {
+                return Err(
+                    "key-password-file can only be used with a key path"
+                )
+            }
+        };
+
+        Ok(PKIIdentity::new(val.cert_chain, key))
+    }
+}
+
+impl PKIIdentity {
+    /// Create a new `PKIIdentity` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        cert_chain: PathBuf,
+        key: PKIKey
+    ) -> Self {
+        PKIIdentity {
+            cert_chain: cert_chain,
+            key: key
+        }
+    }
+
+    /// Get the path to the PEM-encoded certificate chain.
+    #[inline]
+    pub fn cert_chain(&self) -> &PathBuf {
+        &self.cert_chain
+    }
+
+    /// Get the location of the private key.
+    #[inline]
+    pub fn key(&self) -> &PKIKey {
+        &self.key
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Load the certificate chain.
+    ///
+    /// The first certificate in the result is the one for the private
+    /// key.
+    pub fn load_cert_chain(&self) -> Result<Vec<X509>, PKIIdentityLoadError> {
+        trace!(target: "pki-identity",
+               "loading certificate chain from {}",
+               self.cert_chain.display());
+
+        let pem =
+            read(&self.cert_chain).map_err(|err| PKIIdentityLoadError::IO {
+                path: self.cert_chain.clone(),
+                error: err
+            })?;
+        let chain = X509::stack_from_pem(&pem)
+            .map_err(|err| PKIIdentityLoadError::OpenSSL { error: err })?;
+
+        if chain.is_empty() {
+            Err(PKIIdentityLoadError::NoCerts {
+                path: self.cert_chain.clone()
+            })
+        } else {
+            Ok(chain)
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Load the private key.
+    ///
+    /// See [PKIKeyProvider::load_key].
+    #[inline]
+    pub fn load_key(&self) -> Result<PKey<Private>, PKIIdentityLoadError> {
+        self.key
+            .load_key()
+            .map_err(|err| PKIIdentityLoadError::Key { error: err })
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Load the private key and certificate chain.
+    ///
+    /// This also checks that the private key matches the first
+    /// certificate in the chain.
+    pub fn load(
+        &self
+    ) -> Result<(PKey<Private>, Vec<X509>), PKIIdentityLoadError> {
+        debug!(target: "pki-identity",
+               "loading PKI identity from configuration");
+
+        let key = self.load_key()?;
+        let chain = self.load_cert_chain()?;
+        let public = chain[0]
+            .public_key()
+            .map_err(|err| PKIIdentityLoadError::OpenSSL { error: err })?;
+
+        if public.public_eq(&key) {
+            Ok((key, chain))
+        } else {
+            Err(PKIIdentityLoadError::KeyMismatch)
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Get the modification times of the certificate chain file, and
+    /// of the key file if the key is held in one.
+    fn file_stamps(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut stamps =
+            vec![(self.cert_chain.clone(), modified(&self.cert_chain))];
+
+        if let PKIKey::File(key) = &self.key {
+            stamps.push((key.path().clone(), modified(key.path())))
+        }
+
+        stamps
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKICertInfo {
+    /// Get information about `cert`, which was loaded from `path`.
+    ///
+    /// The fingerprint is computed with `algo`.
+    pub fn inspect(
+        path: PathBuf,
+        cert: &X509Ref,
+        algo: &CompoundHashAlgo
+    ) -> Result<Self, ErrorStack> {
+        let der = cert.to_der()?;
+
+        Ok(PKICertInfo {
+            path: path,
+            subject: cert_subject(cert)?,
+            issuer: name_oneline(cert.issuer_name())?,
+            not_before: asn1_system_time(cert.not_before())?,
+            not_after: asn1_system_time(cert.not_after())?,
+            fingerprint: algo.hash_bytes(&der)
+        })
+    }
+
+    /// Get the path to the file containing the certificate.
+    #[inline]
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Get the subject name, in one-line form.
+    #[inline]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Get the issuer name, in one-line form.
+    #[inline]
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Get the start of the validity period.
+    #[inline]
+    pub fn not_before(&self) -> SystemTime {
+        self.not_before
+    }
+
+    /// Get the expiry time.
+    #[inline]
+    pub fn not_after(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Get the hash of the DER-encoded certificate.
+    #[inline]
+    pub fn fingerprint(&self) -> &CompoundHashID {
+        &self.fingerprint
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKICRLInfo {
+    /// Get information about the first CRL in `pem`, which was loaded
+    /// from `path`.
+    fn load(
+        path: PathBuf,
+        pem: &[u8]
+    ) -> Result<Self, ErrorStack> {
+        let crl = X509Crl::from_pem(pem)?;
+        let next_update = match crl.next_update() {
+            Some(time) => Some(asn1_system_time(time)?),
+            None => None
+        };
+
+        Ok(PKICRLInfo {
+            path: path,
+            issuer: name_oneline(crl.issuer_name())?,
+            last_update: asn1_system_time(crl.last_update())?,
+            next_update: next_update
+        })
+    }
+
+    /// Get the path to the file containing the CRL.
+    #[inline]
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Get the issuer name, in one-line form.
+    #[inline]
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Get the time at which the CRL was issued.
+    #[inline]
+    pub fn last_update(&self) -> SystemTime {
+        self.last_update
+    }
+
+    /// Get the time by which the next CRL will be issued, if given.
+    #[inline]
+    pub fn next_update(&self) -> Option<SystemTime> {
+        self.next_update
+    }
+}
+
+impl ScopedError for PKITrustRootLoadError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            #[cfg(feature = "openssl")]
+            PKITrustRootLoadError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            #[cfg(feature = "openssl")]
+            PKITrustRootLoadError::BadTime { .. } => ErrorScope::Unrecoverable,
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::PEM { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::BadRootCert { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::Verifier { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::Unsupported { .. } => ErrorScope::System,
+            PKITrustRootLoadError::NoRootCerts => ErrorScope::System
+        }
+    }
+}
+
+impl Display for PKITrustRootLoadError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            #[cfg(feature = "openssl")]
+            PKITrustRootLoadError::OpenSSL { error } => error.fmt(f),
+            #[cfg(feature = "openssl")]
+            PKITrustRootLoadError::BadTime { time } => {
+                write!(
+                    f,
+                    "time {} is before epoch",
+                    OffsetDateTime::from(*time)
+                )
+            }
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::PEM { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::BadRootCert { path, error } => {
+                write!(
+                    f,
+                    "bad root certificate in {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::Verifier { error } => error.fmt(f),
+            #[cfg(feature = "rustls")]
+            PKITrustRootLoadError::Unsupported { option } => {
+                write!(f, "{} is not supported with rustls", option)
+            }
+            PKITrustRootLoadError::NoRootCerts => {
+                write!(f, "no CA dirs and no root certs in configuration")
+            }
+        }
+    }
+}
+
+impl ScopedError for PKIIdentityLoadError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PKIIdentityLoadError::IO { error, .. } => error.scope(),
+            #[cfg(feature = "openssl")]
+            PKIIdentityLoadError::OpenSSL { .. } => ErrorScope::System,
+            PKIIdentityLoadError::NoCerts { .. } => ErrorScope::System,
+            PKIIdentityLoadError::Key { error } => error.scope(),
+            #[cfg(feature = "rustls")]
+            PKIIdentityLoadError::PEM { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKIIdentityLoadError::Rustls { .. } => ErrorScope::System,
+            PKIIdentityLoadError::KeyMismatch => ErrorScope::System
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl ScopedError for TLSContextLoadError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            TLSContextLoadError::TrustRoot { error } => error.scope(),
+            TLSContextLoadError::Identity { error } => error.scope(),
+            #[cfg(feature = "openssl")]
+            TLSContextLoadError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            #[cfg(feature = "openssl")]
+            TLSContextLoadError::OCSP { error } => error.scope(),
+            #[cfg(feature = "rustls")]
+            TLSContextLoadError::Rustls { .. } => ErrorScope::System
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl Display for TLSContextLoadError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            TLSContextLoadError::TrustRoot { error } => error.fmt(f),
+            TLSContextLoadError::Identity { error } => error.fmt(f),
+            #[cfg(feature = "openssl")]
+            TLSContextLoadError::OpenSSL { error } => error.fmt(f),
+            #[cfg(feature = "openssl")]
+            TLSContextLoadError::OCSP { error } => error.fmt(f),
+            #[cfg(feature = "rustls")]
+            TLSContextLoadError::Rustls { error } => error.fmt(f)
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKICertInfo {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} ({}), valid {} to {}, fingerprint {}",
+            self.subject,
+            self.path.display(),
+            OffsetDateTime::from(self.not_before),
+            OffsetDateTime::from(self.not_after),
+            self.fingerprint
+        )
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKICRLInfo {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} ({}), issued {}",
+            self.issuer,
+            self.path.display(),
+            OffsetDateTime::from(self.last_update)
+        )?;
+
+        match self.next_update {
+            Some(time) => {
+                write!(f, ", next update {}", OffsetDateTime::from(time))
+            }
+            None => Ok(())
+        }
+    }
+}
+
+impl Display for PKIIdentityLoadError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIIdentityLoadError::IO { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            #[cfg(feature = "openssl")]
+            PKIIdentityLoadError::OpenSSL { error } => error.fmt(f),
+            PKIIdentityLoadError::NoCerts { path } => {
+                write!(f, "no certificates in {}", path.display())
+            }
+            PKIIdentityLoadError::Key { error } => error.fmt(f),
+            #[cfg(feature = "rustls")]
+            PKIIdentityLoadError::PEM { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            #[cfg(feature = "rustls")]
+            PKIIdentityLoadError::Rustls { error } => error.fmt(f),
+            PKIIdentityLoadError::KeyMismatch => {
+                write!(f, "private key does not match certificate")
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl X509HostFlag {
+    /// Get the name of this flag, as used in the YAML format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            X509HostFlag::AlwaysCheckSubject => "ALWAYS_CHECK_SUBJECT",
+            X509HostFlag::NoWildcards => "NO_WILDCARDS",
+            X509HostFlag::NoPartialWildcards => "NO_PARTIAL_WILDCARDS",
+            X509HostFlag::MultiLabelWildcards => "MULTI_LABEL_WILDCARDS",
+            X509HostFlag::SingleLabelSubdomains => "SINGLE_LABEL_SUBDOMAINS",
+            X509HostFlag::NeverCheckSubject => "NEVER_CHECK_SUBJECT"
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl X509VerifyFlag {
+    /// Get the name of this flag, as used in the YAML format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            X509VerifyFlag::CRLCheck => "CRL_CHECK",
+            X509VerifyFlag::CRLCheckAll => "CRL_CHECK_ALL",
+            X509VerifyFlag::AllowProxyCerts => "ALLOW_PROXY_CERTS",
+            X509VerifyFlag::PolicyCheck => "POLICY_CHECK",
+            X509VerifyFlag::ExplicitPolicy => "EXPLICIT_POLICY",
+            X509VerifyFlag::InhibitAny => "INHIBIT_ANY",
+            X509VerifyFlag::InhibitMap => "INHIBIT_MAP",
+            X509VerifyFlag::CheckSSSignature => "CHECK_SS_SIGNATURE",
+            X509VerifyFlag::NoCheckTime => "NO_CHECK_TIME"
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl Serialize for X509HostFlag {
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl Serialize for X509VerifyFlag {
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl<'a> TryFrom<&'a str> for X509HostFlag {
     type Error = &'a str;
 
     #[inline]
@@ -1000,7 +2279,7 @@ impl<'a> TryFrom<&'a str> for X509HostFlag {
     }
 }
 
-#[cfg(feature = "openssl")]
+#[cfg(any(feature = "openssl", feature = "rustls"))]
 impl<'a> TryFrom<&'a str> for X509VerifyFlag {
     type Error = &'a str;
 
@@ -1021,6 +2300,24 @@ impl<'a> TryFrom<&'a str> for X509VerifyFlag {
     }
 }
 
+#[cfg(all(test, feature = "openssl"))]
+use std::fs::write;
+#[cfg(all(test, feature = "openssl"))]
+use std::net::TcpListener;
+#[cfg(all(test, feature = "openssl"))]
+use std::net::TcpStream;
+#[cfg(all(test, feature = "openssl"))]
+use std::thread::spawn;
+
+#[cfg(all(test, feature = "openssl"))]
+use openssl::asn1::Asn1Object;
+#[cfg(all(test, feature = "openssl"))]
+use openssl::symm::Cipher;
+
+#[cfg(test)]
+use crate::config::pki::keys::PKIPKCS11Key;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
 #[cfg(test)]
 use crate::init;
 
@@ -1033,14 +2330,18 @@ fn test_deserialize_cfg_dir() {
         dirs: vec![PathBuf::from("/usr/local/etc/test/certs/")],
         root_certs: Vec::default(),
         crls: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         verify_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         host_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         auth_level: None,
-        #[cfg(feature = "openssl")]
-        verify_depth: None
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        verify_depth: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        ocsp: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1062,14 +2363,18 @@ fn test_deserialize_cfg_dir_certs() {
         dirs: vec![PathBuf::from("/usr/local/etc/test/certs/")],
         root_certs: vec![PathBuf::from("/usr/local/etc/test/test.cert")],
         crls: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         verify_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         host_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         auth_level: None,
-        #[cfg(feature = "openssl")]
-        verify_depth: None
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        verify_depth: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        ocsp: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1091,14 +2396,18 @@ fn test_deserialize_cfg_dir_crls() {
         dirs: vec![PathBuf::from("/usr/local/etc/test/certs/")],
         root_certs: Vec::default(),
         crls: vec![PathBuf::from("/usr/local/etc/test/test.crl")],
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         verify_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         host_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         auth_level: None,
-        #[cfg(feature = "openssl")]
-        verify_depth: None
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        verify_depth: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        ocsp: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1119,14 +2428,18 @@ fn test_deserialize_cfg_certs_dir_empty_crls() {
         dirs: vec![PathBuf::from("/usr/local/etc/test/certs/")],
         root_certs: Vec::default(),
         crls: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         verify_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         host_flags: Vec::default(),
-        #[cfg(feature = "openssl")]
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
         auth_level: None,
-        #[cfg(feature = "openssl")]
-        verify_depth: None
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        verify_depth: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        ocsp: None,
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1153,7 +2466,9 @@ fn test_deserialize_cfg_dir_certs_auth_level() {
         verify_flags: Vec::default(),
         host_flags: Vec::default(),
         auth_level: Some(3),
-        verify_depth: None
+        verify_depth: None,
+        ocsp: None,
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1185,7 +2500,9 @@ fn test_deserialize_cfg_dir_certs_verify_flags() {
         ],
         host_flags: Vec::default(),
         auth_level: None,
-        verify_depth: None
+        verify_depth: None,
+        ocsp: None,
+        peer_constraints: None
     };
 
     let actual = serde_yaml::from_str(yaml).unwrap();
@@ -1198,12 +2515,12 @@ fn test_deserialize_cfg_dir_certs_verify_flags() {
 fn test_load_trust_root_single_no_crl() {
     init();
 
-    let yaml = concat!(
-        "root-certs:\n",
-        "  - test/data/certs/client/ca_cert.pem\n",
-        "crls: []\n"
+    let pki = TestPKI::generate().unwrap();
+    let yaml = format!(
+        "root-certs:\n  - {}\ncrls: []\n",
+        pki.path("client/ca_cert.pem").display()
     );
-    let root: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     let name = String::from("test-client.nowhere.com");
     let endpoint = IPEndpointAddr::name(name);
 
@@ -1215,13 +2532,13 @@ fn test_load_trust_root_single_no_crl() {
 fn test_load_trust_root_two_no_crl() {
     init();
 
-    let yaml = concat!(
-        "root-certs:\n",
-        "  - test/data/certs/client/ca_cert.pem\n",
-        "  - test/data/certs/server/ca_cert.pem\n",
-        "crls: []\n"
+    let pki = TestPKI::generate().unwrap();
+    let yaml = format!(
+        "root-certs:\n  - {}\n  - {}\ncrls: []\n",
+        pki.path("client/ca_cert.pem").display(),
+        pki.path("server/ca_cert.pem").display()
     );
-    let root: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     let name = String::from("test-client.nowhere.com");
     let endpoint = IPEndpointAddr::name(name);
 
@@ -1233,9 +2550,10 @@ fn test_load_trust_root_two_no_crl() {
 fn test_load_trust_root_dir_no_crl() {
     init();
 
+    let pki = TestPKI::generate().unwrap();
     let yaml =
-        concat!("dirs:\n", "  - test/data/certs/client/\n", "crls: []\n");
-    let root: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+        format!("dirs:\n  - {}\ncrls: []\n", pki.path("client").display());
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     let name = String::from("test-client.nowhere.com");
     let endpoint = IPEndpointAddr::name(name);
 
@@ -1247,14 +2565,13 @@ fn test_load_trust_root_dir_no_crl() {
 fn test_load_trust_root_dir_certs_auth_level() {
     init();
 
-    let yaml = concat!(
-        "root-certs:\n",
-        "  - test/data/certs/server/ca_cert.pem\n",
-        "dirs:\n",
-        "  - test/data/certs/client/\n",
-        "auth-level: 3\n"
+    let pki = TestPKI::generate().unwrap();
+    let yaml = format!(
+        "root-certs:\n  - {}\ndirs:\n  - {}\nauth-level: 3\n",
+        pki.path("server/ca_cert.pem").display(),
+        pki.path("client").display()
     );
-    let root: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     let name = String::from("test-server.nowhere.com");
     let endpoint = IPEndpointAddr::name(name);
 
@@ -1266,18 +2583,414 @@ fn test_load_trust_root_dir_certs_auth_level() {
 fn test_load_trust_root_dir_certs_verify_flags() {
     init();
 
-    let yaml = concat!(
-        "root-certs:\n",
-        "  - test/data/certs/server/ca_cert.pem\n",
-        "dirs:\n",
-        "  - test/data/certs/client/\n",
-        "verify-flags:\n",
-        "  - CRL_CHECK_ALL\n",
-        "  - EXPLICIT_POLICY\n"
+    let pki = TestPKI::generate().unwrap();
+    let yaml = format!(
+        concat!(
+            "root-certs:\n",
+            "  - {}\n",
+            "dirs:\n",
+            "  - {}\n",
+            "verify-flags:\n",
+            "  - CRL_CHECK_ALL\n",
+            "  - EXPLICIT_POLICY\n"
+        ),
+        pki.path("server/ca_cert.pem").display(),
+        pki.path("client").display()
     );
-    let root: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
     let name = String::from("test-server.nowhere.com");
     let endpoint = IPEndpointAddr::name(name);
 
     root.load_client(None, &endpoint).expect("Expected success");
 }
+
+#[test]
+fn test_deserialize_identity() {
+    init();
+
+    let yaml = concat!(
+        "cert-chain: /etc/ssl/certs/server-cert.pem\n",
+        "key: /etc/ssl/private/server-key.pem\n"
+    );
+    let expected = PKIIdentity::new(
+        PathBuf::from("/etc/ssl/certs/server-cert.pem"),
+        PKIKey::File(PKIFileKey::new(
+            PathBuf::from("/etc/ssl/private/server-key.pem"),
+            None
+        ))
+    );
+    let actual = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(expected, actual)
+}
+
+#[test]
+fn test_deserialize_identity_password() {
+    init();
+
+    let yaml = concat!(
+        "cert-chain: /etc/ssl/certs/server-cert.pem\n",
+        "key: /etc/ssl/private/server-key.pem\n",
+        "key-password-file: /etc/ssl/private/server-key.pass\n"
+    );
+    let expected = PKIIdentity::new(
+        PathBuf::from("/etc/ssl/certs/server-cert.pem"),
+        PKIKey::File(PKIFileKey::new(
+            PathBuf::from("/etc/ssl/private/server-key.pem"),
+            Some(PKIPassphraseSource::File(PathBuf::from(
+                "/etc/ssl/private/server-key.pass"
+            )))
+        ))
+    );
+    let actual = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(expected, actual)
+}
+
+#[test]
+fn test_deserialize_identity_file_key() {
+    init();
+
+    let yaml = concat!(
+        "cert-chain: /etc/ssl/certs/server-cert.pem\n",
+        "key:\n",
+        "  file:\n",
+        "    path: /etc/ssl/private/server-key.der\n",
+        "    passphrase:\n",
+        "      env: SERVER_KEY_PASSPHRASE\n"
+    );
+    let expected = PKIIdentity::new(
+        PathBuf::from("/etc/ssl/certs/server-cert.pem"),
+        PKIKey::File(PKIFileKey::new(
+            PathBuf::from("/etc/ssl/private/server-key.der"),
+            Some(PKIPassphraseSource::Env(String::from(
+                "SERVER_KEY_PASSPHRASE"
+            )))
+        ))
+    );
+    let actual = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(expected, actual)
+}
+
+#[test]
+fn test_deserialize_identity_pkcs11() {
+    init();
+
+    let yaml = concat!(
+        "cert-chain: /etc/ssl/certs/server-cert.pem\n",
+        "key:\n",
+        "  pkcs11:\n",
+        "    uri: \"pkcs11:token=test;object=server-key;type=private\"\n",
+        "    pin:\n",
+        "      file: /etc/ssl/private/token.pin\n"
+    );
+    let expected = PKIIdentity::new(
+        PathBuf::from("/etc/ssl/certs/server-cert.pem"),
+        PKIKey::PKCS11(PKIPKCS11Key::new(
+            String::from("pkcs11:token=test;object=server-key;type=private"),
+            Some(PKIPassphraseSource::File(PathBuf::from(
+                "/etc/ssl/private/token.pin"
+            ))),
+            None
+        ))
+    );
+    let actual: PKIIdentity = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(expected, actual);
+
+    match actual.key() {
+        PKIKey::PKCS11(key) => assert_eq!("pkcs11", key.provider()),
+        _ => panic!("Expected PKCS#11 key")
+    }
+}
+
+#[test]
+fn test_deserialize_identity_key_password_file_bad() {
+    init();
+
+    let yaml = concat!(
+        "cert-chain: /etc/ssl/certs/server-cert.pem\n",
+        "key:\n",
+        "  pkcs11:\n",
+        "    uri: \"pkcs11:object=server-key\"\n",
+        "key-password-file: /etc/ssl/private/server-key.pass\n"
+    );
+
+    assert!(serde_yaml::from_str::<PKIIdentity>(yaml).is_err())
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_load_identity_encrypted_key() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let key_path = pki.path("key.pem");
+    let pass_path = pki.path("key.pass");
+    let bad_pass_path = pki.path("bad.pass");
+    let cert_path = pki.path("server/certs/test_server_cert.pem");
+    let key = pki.server_identity().load_key().unwrap();
+    let pem = key
+        .private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secret")
+        .unwrap();
+
+    write(&key_path, pem).unwrap();
+    write(&pass_path, "secret\n").unwrap();
+    write(&bad_pass_path, "wrong\n").unwrap();
+
+    let file_key = |passphrase| {
+        PKIKey::File(PKIFileKey::new(key_path.clone(), passphrase))
+    };
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        file_key(Some(PKIPassphraseSource::File(pass_path)))
+    );
+
+    identity.load().expect("Expected success");
+
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        file_key(Some(PKIPassphraseSource::File(bad_pass_path)))
+    );
+
+    assert!(identity.load_key().is_err());
+
+    let identity = PKIIdentity::new(cert_path, file_key(None));
+
+    assert!(identity.load_key().is_err());
+}
+
+#[cfg(feature = "openssl")]
+#[cfg(test)]
+fn tls_handshake(name: &str) -> (Result<(), String>, Result<(), String>) {
+    let pki = TestPKI::generate().unwrap();
+
+    tls_handshake_ocsp(&pki, name, None, None)
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn tls_handshake_ocsp(
+    pki: &TestPKI,
+    name: &str,
+    server_ocsp: Option<PKIOCSPConfig>,
+    client_ocsp: Option<PKIOCSPConfig>
+) -> (Result<(), String>, Result<(), String>) {
+    tls_handshake_config(pki, name, server_ocsp, client_ocsp, None)
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn tls_handshake_config(
+    pki: &TestPKI,
+    name: &str,
+    server_ocsp: Option<PKIOCSPConfig>,
+    client_ocsp: Option<PKIOCSPConfig>,
+    client_constraints: Option<PKIPeerConstraints>
+) -> (Result<(), String>, Result<(), String>) {
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        server_ocsp,
+        None
+    );
+    let server_identity = pki.server_identity();
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        client_ocsp,
+        client_constraints
+    );
+    let client_identity = pki.client_identity();
+    let endpoint = IPEndpointAddr::name(String::from(name));
+    let acceptor = server_root.load_acceptor(&server_identity, None).unwrap();
+    let connector = client_root
+        .load_connector(Some(&client_identity), None, &endpoint)
+        .unwrap();
+    let config = client_root.connect_configuration(&connector).unwrap();
+
+    tls_handshake_with(acceptor, config, name)
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn tls_handshake_with(
+    acceptor: SslAcceptor,
+    config: ConnectConfiguration,
+    name: &str
+) -> (Result<(), String>, Result<(), String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+
+        acceptor
+            .accept(stream)
+            .map(|stream| {
+                assert_eq!(Some(SslVersion::TLS1_3), stream.ssl().version2());
+                assert!(stream.ssl().peer_certificate().is_some());
+            })
+            .map_err(|err| err.to_string())
+    });
+    let stream = TcpStream::connect(addr).unwrap();
+    let client = config
+        .connect(name, stream)
+        .map(|_| ())
+        .map_err(|err| err.to_string());
+
+    (client, server.join().unwrap())
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake() {
+    init();
+
+    let (client, server) = tls_handshake("test-server.nowhere.com");
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_wrong_host() {
+    init();
+
+    let (client, server) = tls_handshake("wrong-server.nowhere.com");
+
+    assert!(client.is_err());
+    assert!(server.is_err());
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn ocsp_test_config(
+    responder_url: Option<String>,
+    responses: &[PathBuf],
+    require_stapled: bool
+) -> PKIOCSPConfig {
+    PKIOCSPConfig::new(
+        responder_url,
+        true,
+        300,
+        None,
+        responses.to_vec(),
+        None,
+        require_stapled
+    )
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn ocsp_test_cert(path: &Path) -> X509 {
+    X509::from_pem(&read(path).unwrap()).unwrap()
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn spki_pin(path: &Path) -> CompoundHashID {
+    let cert = X509::from_pem(&read(path).unwrap()).unwrap();
+    let spki = cert.public_key().unwrap().public_key_to_der().unwrap();
+
+    CompoundHashAlgo::default().hash_bytes(&spki)
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_cert_extension() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert = ocsp_test_cert(&pki.path("server/ocsp/ocsp_cert.pem"));
+    let der = cert.to_der().unwrap();
+    let ekus = cert_extension(&der, &EKU_OID)
+        .and_then(ext_key_usages)
+        .unwrap();
+    let ocsp_signing = Asn1Object::from_str("1.3.6.1.5.5.7.3.9").unwrap();
+
+    assert_eq!(ekus, [ocsp_signing.as_slice()]);
+
+    // A non-minimal length anywhere on the path is rejected.
+    let mut bad = vec![0x30, 0x84];
+
+    bad.extend_from_slice(&(der.len() as u32 - 4).to_be_bytes());
+    bad.extend_from_slice(&der[4..]);
+
+    assert!(cert_extension(&bad, &EKU_OID).is_none());
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ext_key_usages_malformed() {
+    init();
+
+    assert_eq!(
+        ext_key_usages(&[0x30, 0x03, 0x06, 0x01, 0x2a]),
+        Some(vec![&[0x2a][..]])
+    );
+    assert_eq!(ext_key_usages(&[]), None);
+    assert_eq!(ext_key_usages(&[0x30, 0x81, 0x03, 0x06, 0x01, 0x2a]), None);
+    assert_eq!(ext_key_usages(&[0x30, 0x04, 0x06, 0x01, 0x2a]), None);
+    assert_eq!(ext_key_usages(&[0x30, 0x03, 0x04, 0x01, 0x2a]), None);
+    assert_eq!(ext_key_usages(&[0x30, 0x03, 0x06, 0x01, 0x2a, 0x00]), None);
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_pinned() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let pin = spki_pin(&pki.path("server/certs/test_server_cert.pem"));
+    let constraints = PKIPeerConstraints::new(
+        vec![pin],
+        vec![],
+        vec![String::from("test-server.*")],
+        vec![],
+        vec![],
+        vec![]
+    );
+    let (client, server) = tls_handshake_config(
+        &pki,
+        "test-server.nowhere.com",
+        None,
+        None,
+        Some(constraints)
+    );
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_pin_mismatch() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let pin = spki_pin(&pki.path("client/certs/test_client_cert.pem"));
+    let constraints = PKIPeerConstraints::new(
+        vec![pin],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        vec![]
+    );
+    let (client, server) = tls_handshake_config(
+        &pki,
+        "test-server.nowhere.com",
+        None,
+        None,
+        Some(constraints)
+    );
+
+    assert!(client.is_err());
+    assert!(server.is_err());
+}
@@ -0,0 +1,791 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Constraints on peer certificates beyond chaining to a trust root.
+//!
+//! This module provides [PKIPeerConstraints], which restricts the
+//! peers accepted by a [PKITrustRoot](super::PKITrustRoot) by SPKI
+//! pins, subject and alternative name patterns, extended key usages,
+//! and DNS name constraints.
+#[cfg(feature = "openssl")]
+use std::convert::TryFrom;
+#[cfg(feature = "openssl")]
+use std::fmt::Display;
+#[cfg(feature = "openssl")]
+use std::fmt::Formatter;
+#[cfg(feature = "openssl")]
+use std::net::IpAddr;
+
+#[cfg(feature = "openssl")]
+use log::trace;
+#[cfg(feature = "openssl")]
+use log::warn;
+#[cfg(feature = "openssl")]
+use openssl::asn1::Asn1Object;
+#[cfg(feature = "openssl")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "openssl")]
+use openssl::nid::Nid;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509Ref;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509StoreContextRef;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509VerifyResult;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use serde::Deserialize;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use serde::Serialize;
+
+#[cfg(feature = "openssl")]
+use crate::config::pki::cert_extension;
+#[cfg(feature = "openssl")]
+use crate::config::pki::ext_key_usages;
+#[cfg(feature = "openssl")]
+use crate::config::pki::EKU_OID;
+#[cfg(feature = "openssl")]
+use crate::error::ErrorScope;
+#[cfg(feature = "openssl")]
+use crate::error::ScopedError;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use crate::hashid::CompoundHashID;
+#[cfg(feature = "openssl")]
+use crate::hashid::HashAlgo;
+#[cfg(feature = "openssl")]
+use crate::sign::cert_subject;
+
+#[cfg(feature = "openssl")]
+/// Errors that can occur while checking a certificate chain against
+/// [PKIPeerConstraints].
+#[derive(Debug)]
+pub enum PKIPeerConstraintError {
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The certificate chain was empty.
+    NoCerts,
+    /// No public key in the chain matched any of the SPKI pins.
+    PinMismatch,
+    /// The subject name did not match any of the allowed patterns.
+    SubjectNotAllowed {
+        /// The subject name.
+        subject: String
+    },
+    /// No subject alternative name matched any of the allowed
+    /// patterns.
+    AltNameNotAllowed,
+    /// A configured extended key usage could not be recognized.
+    BadEKU {
+        /// The extended key usage name.
+        eku: String
+    },
+    /// The certificate lacked a required extended key usage.
+    MissingEKU {
+        /// The extended key usage name.
+        eku: String
+    },
+    /// A DNS name was not within any of the permitted subtrees.
+    NameNotPermitted {
+        /// The DNS name.
+        name: String
+    },
+    /// A DNS name was within one of the excluded subtrees.
+    NameExcluded {
+        /// The DNS name.
+        name: String
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+/// Additional constraints on peer certificates, beyond chaining to a
+/// trusted root.
+///
+/// These allow peers to be restricted to a specific set of
+/// certificates, even when they share a CA with other parties.  The
+/// constraints are checked after OpenSSL's own verification succeeds,
+/// and any constraint that is not given is not checked.
+///
+/// # YAML Format
+///
+/// The YAML format has six fields, all of which are optional:
+///
+/// - `spki-pins`: A list of hashes of DER-encoded SubjectPublicKeyInfo
+///   structures, in the text format of [CompoundHashID].  At least one
+///   certificate in the verified chain (including the leaf and the root) must
+///   have a public key matching one of these.
+///
+/// - `subjects`: A list of patterns for the leaf certificate's subject name, in
+///   the one-line form `C=US, O=Constellation, CN=node`.  The subject must
+///   match one of these.
+///
+/// - `alt-names`: A list of patterns for the leaf certificate's subject
+///   alternative names.  At least one DNS name, IP address, email address, or
+///   URI must match one of these.
+///
+/// - `required-ekus`: A list of extended key usages that the leaf certificate
+///   must have.  These can be given as OpenSSL short names (such as
+///   `serverAuth` or `clientAuth`), long names, or dotted OIDs.  The
+///   `anyExtendedKeyUsage` value does not satisfy other usages.
+///
+/// - `permitted-dns`: A list of DNS subtrees.  Every DNS name in the leaf
+///   certificate must be within one of these.
+///
+/// - `excluded-dns`: A list of DNS subtrees.  No DNS name in the leaf
+///   certificate may be within any of these.
+///
+/// Patterns are matched without regard to case, and may contain `*`,
+/// which matches any sequence of characters.  A DNS subtree such as
+/// `example.com` contains `example.com` itself and all of its
+/// subdomains; a leading `.`, as in `.example.com`, restricts it to
+/// subdomains only.  The DNS names checked against subtrees are those
+/// in the subject alternative names, or the subject's common name if
+/// there are none.
+///
+/// ## Examples
+///
+/// ```yaml
+/// spki-pins:
+///   - SHA256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+/// subjects:
+///   - "C=US, O=Constellation, *"
+/// alt-names:
+///   - "*.nodes.example.com"
+/// required-ekus:
+///   - serverAuth
+///   - clientAuth
+/// permitted-dns:
+///   - nodes.example.com
+/// excluded-dns:
+///   - .test.nodes.example.com
+/// ```
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Deserialize,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-peer-constraints")]
+pub struct PKIPeerConstraints {
+    /// Hashes of allowed SubjectPublicKeyInfos.
+    #[serde(default)]
+    spki_pins: Vec<CompoundHashID>,
+    /// Patterns for allowed subject names.
+    #[serde(default)]
+    subjects: Vec<String>,
+    /// Patterns for allowed subject alternative names.
+    #[serde(default)]
+    alt_names: Vec<String>,
+    /// Required extended key usages.
+    #[serde(default)]
+    required_ekus: Vec<String>,
+    /// Permitted DNS subtrees.
+    #[serde(default)]
+    permitted_dns: Vec<String>,
+    /// Excluded DNS subtrees.
+    #[serde(default)]
+    excluded_dns: Vec<String>
+}
+
+#[cfg(feature = "openssl")]
+/// Get the subject alternative names of `cert` in text form, along
+/// with whether each is a DNS name.
+fn cert_alt_names(cert: &X509Ref) -> Vec<(bool, String)> {
+    let mut out = Vec::new();
+
+    if let Some(names) = cert.subject_alt_names() {
+        for name in names.iter() {
+            if let Some(dns) = name.dnsname() {
+                out.push((true, dns.to_string()))
+            } else if let Some(ip) = name.ipaddress() {
+                let addr = match ip.len() {
+                    4 => <[u8; 4]>::try_from(ip)
+                        .ok()
+                        .map(|bytes| IpAddr::from(bytes).to_string()),
+                    16 => <[u8; 16]>::try_from(ip)
+                        .ok()
+                        .map(|bytes| IpAddr::from(bytes).to_string()),
+                    _ => None
+                };
+
+                out.extend(addr.map(|addr| (false, addr)))
+            } else if let Some(email) = name.email() {
+                out.push((false, email.to_string()))
+            } else if let Some(uri) = name.uri() {
+                out.push((false, uri.to_string()))
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(feature = "openssl")]
+/// Check whether `name` matches `pattern`, ignoring case.
+///
+/// The pattern may contain `*`, which matches any sequence of
+/// characters.
+fn pattern_matches(
+    pattern: &str,
+    name: &str
+) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last star, and the name position it matched up to.
+    let mut star = None;
+
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((sp, sn)) = star {
+            p = sp + 1;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(feature = "openssl")]
+/// Check whether the DNS `name` is within the subtree `domain`,
+/// ignoring case.
+///
+/// A leading `.` in `domain` excludes the domain itself.
+fn dns_in_subtree(
+    domain: &str,
+    name: &str
+) -> bool {
+    let domain = domain.to_lowercase();
+    let name = name.to_lowercase();
+
+    match domain.strip_prefix('.') {
+        Some(_) => name.ends_with(&domain),
+        None => {
+            name == domain ||
+                name.strip_suffix(&domain)
+                    .map(|prefix| prefix.ends_with('.'))
+                    .unwrap_or(false)
+        }
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl PKIPeerConstraints {
+    /// Create a new `PKIPeerConstraints` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        spki_pins: Vec<CompoundHashID>,
+        subjects: Vec<String>,
+        alt_names: Vec<String>,
+        required_ekus: Vec<String>,
+        permitted_dns: Vec<String>,
+        excluded_dns: Vec<String>
+    ) -> Self {
+        PKIPeerConstraints {
+            spki_pins: spki_pins,
+            subjects: subjects,
+            alt_names: alt_names,
+            required_ekus: required_ekus,
+            permitted_dns: permitted_dns,
+            excluded_dns: excluded_dns
+        }
+    }
+
+    /// Get the SPKI pins.
+    #[inline]
+    pub fn spki_pins(&self) -> &[CompoundHashID] {
+        &self.spki_pins
+    }
+
+    /// Get the patterns for allowed subject names.
+    #[inline]
+    pub fn subjects(&self) -> &[String] {
+        &self.subjects
+    }
+
+    /// Get the patterns for allowed subject alternative names.
+    #[inline]
+    pub fn alt_names(&self) -> &[String] {
+        &self.alt_names
+    }
+
+    /// Get the required extended key usages.
+    #[inline]
+    pub fn required_ekus(&self) -> &[String] {
+        &self.required_ekus
+    }
+
+    /// Get the permitted DNS subtrees.
+    #[inline]
+    pub fn permitted_dns(&self) -> &[String] {
+        &self.permitted_dns
+    }
+
+    /// Get the excluded DNS subtrees.
+    #[inline]
+    pub fn excluded_dns(&self) -> &[String] {
+        &self.excluded_dns
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check whether any certificate in `chain` matches one of the
+    /// SPKI pins.
+    fn check_pins<'a, I>(
+        &self,
+        chain: I
+    ) -> Result<(), PKIPeerConstraintError>
+    where
+        I: IntoIterator<Item = &'a X509Ref> {
+        if self.spki_pins.is_empty() {
+            return Ok(());
+        }
+
+        for cert in chain {
+            let spki = cert
+                .public_key()
+                .and_then(|key| key.public_key_to_der())
+                .map_err(|err| PKIPeerConstraintError::OpenSSL {
+                    error: err
+                })?;
+
+            for pin in &self.spki_pins {
+                if pin.algo().hash_bytes(&spki) == *pin {
+                    trace!(target: "pki-peer-constraints",
+                           "certificate matched SPKI pin {}",
+                           pin);
+
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(PKIPeerConstraintError::PinMismatch)
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check the subject and alternative names of `cert` against the
+    /// allowed patterns and DNS subtrees.
+    fn check_names(
+        &self,
+        cert: &X509Ref
+    ) -> Result<(), PKIPeerConstraintError> {
+        if !self.subjects.is_empty() {
+            let subject = cert_subject(cert).map_err(|err| {
+                PKIPeerConstraintError::OpenSSL { error: err }
+            })?;
+
+            if !self
+                .subjects
+                .iter()
+                .any(|pattern| pattern_matches(pattern, &subject))
+            {
+                return Err(PKIPeerConstraintError::SubjectNotAllowed {
+                    subject: subject
+                });
+            }
+        }
+
+        let alt_names = cert_alt_names(cert);
+
+        if !self.alt_names.is_empty() &&
+            !alt_names.iter().any(|(_, name)| {
+                self.alt_names
+                    .iter()
+                    .any(|pattern| pattern_matches(pattern, name))
+            })
+        {
+            return Err(PKIPeerConstraintError::AltNameNotAllowed);
+        }
+
+        if self.permitted_dns.is_empty() && self.excluded_dns.is_empty() {
+            return Ok(());
+        }
+
+        let mut dns_names: Vec<String> = alt_names
+            .into_iter()
+            .filter_map(|(dns, name)| if dns { Some(name) } else { None })
+            .collect();
+
+        if dns_names.is_empty() {
+            for entry in cert.subject_name().entries_by_nid(Nid::COMMONNAME) {
+                let name = entry.data().to_string().map_err(|err| {
+                    PKIPeerConstraintError::OpenSSL { error: err }
+                })?;
+
+                dns_names.push(name)
+            }
+        }
+
+        for name in dns_names {
+            if !self.permitted_dns.is_empty() &&
+                !self
+                    .permitted_dns
+                    .iter()
+                    .any(|domain| dns_in_subtree(domain, &name))
+            {
+                return Err(PKIPeerConstraintError::NameNotPermitted {
+                    name: name
+                });
+            }
+
+            if self
+                .excluded_dns
+                .iter()
+                .any(|domain| dns_in_subtree(domain, &name))
+            {
+                return Err(PKIPeerConstraintError::NameExcluded {
+                    name: name
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check that `cert` has all the required extended key usages.
+    fn check_ekus(
+        &self,
+        cert: &X509Ref
+    ) -> Result<(), PKIPeerConstraintError> {
+        if self.required_ekus.is_empty() {
+            return Ok(());
+        }
+
+        let der = cert
+            .to_der()
+            .map_err(|err| PKIPeerConstraintError::OpenSSL { error: err })?;
+        let ekus = cert_extension(&der, &EKU_OID)
+            .and_then(ext_key_usages)
+            .unwrap_or_default();
+
+        for eku in &self.required_ekus {
+            let oid = Asn1Object::from_str(eku).map_err(|_| {
+                PKIPeerConstraintError::BadEKU { eku: eku.clone() }
+            })?;
+
+            if !ekus.contains(&oid.as_slice()) {
+                return Err(PKIPeerConstraintError::MissingEKU {
+                    eku: eku.clone()
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check a verified certificate chain against these constraints.
+    ///
+    /// The first certificate in `chain` must be the leaf.  This does
+    /// not perform any other verification of the chain; it should
+    /// only be called on chains that have already been verified
+    /// against a trust store.
+    pub fn check<'a, I>(
+        &self,
+        chain: I
+    ) -> Result<(), PKIPeerConstraintError>
+    where
+        I: IntoIterator<Item = &'a X509Ref> {
+        let chain: Vec<&X509Ref> = chain.into_iter().collect();
+        let leaf = chain.first().ok_or(PKIPeerConstraintError::NoCerts)?;
+
+        self.check_ekus(leaf)?;
+        self.check_names(leaf)?;
+        self.check_pins(chain.iter().copied())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Verification callback for TLS contexts.
+    ///
+    /// This checks the constraints once OpenSSL has verified the
+    /// whole chain, which happens at depth 0.
+    pub(crate) fn verify_callback(
+        &self,
+        preverify: bool,
+        ctx: &mut X509StoreContextRef
+    ) -> bool {
+        if !preverify || ctx.error_depth() != 0 {
+            return preverify;
+        }
+
+        let result = match ctx.chain() {
+            Some(chain) => self.check(chain.iter()),
+            None => Err(PKIPeerConstraintError::NoCerts)
+        };
+
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(target: "pki-peer-constraints",
+                      "peer certificate rejected: {}",
+                      err);
+
+                ctx.set_error(X509VerifyResult::APPLICATION_VERIFICATION);
+
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl ScopedError for PKIPeerConstraintError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PKIPeerConstraintError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            PKIPeerConstraintError::NoCerts => ErrorScope::External,
+            PKIPeerConstraintError::PinMismatch => ErrorScope::External,
+            PKIPeerConstraintError::SubjectNotAllowed { .. } => {
+                ErrorScope::External
+            }
+            PKIPeerConstraintError::AltNameNotAllowed => ErrorScope::External,
+            PKIPeerConstraintError::BadEKU { .. } => ErrorScope::System,
+            PKIPeerConstraintError::MissingEKU { .. } => ErrorScope::External,
+            PKIPeerConstraintError::NameNotPermitted { .. } => {
+                ErrorScope::External
+            }
+            PKIPeerConstraintError::NameExcluded { .. } => ErrorScope::External
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKIPeerConstraintError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIPeerConstraintError::OpenSSL { error } => error.fmt(f),
+            PKIPeerConstraintError::NoCerts => {
+                write!(f, "empty certificate chain")
+            }
+            PKIPeerConstraintError::PinMismatch => {
+                write!(f, "no public key matches any SPKI pin")
+            }
+            PKIPeerConstraintError::SubjectNotAllowed { subject } => {
+                write!(f, "subject name {} is not allowed", subject)
+            }
+            PKIPeerConstraintError::AltNameNotAllowed => {
+                write!(f, "no subject alternative name is allowed")
+            }
+            PKIPeerConstraintError::BadEKU { eku } => {
+                write!(f, "unrecognized extended key usage {}", eku)
+            }
+            PKIPeerConstraintError::MissingEKU { eku } => {
+                write!(f, "missing required extended key usage {}", eku)
+            }
+            PKIPeerConstraintError::NameNotPermitted { name } => {
+                write!(f, "DNS name {} is not in a permitted subtree", name)
+            }
+            PKIPeerConstraintError::NameExcluded { name } => {
+                write!(f, "DNS name {} is in an excluded subtree", name)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::ocsp_test_cert;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::spki_pin;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::PKITrustRoot;
+#[cfg(all(test, feature = "openssl"))]
+use crate::init;
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_deserialize_peer_constraints() {
+    init();
+
+    let yaml = concat!(
+        "root-certs:\n",
+        "  - /usr/local/etc/test/test.cert\n",
+        "peer-constraints:\n",
+        "  spki-pins:\n",
+        "    - SHA256:9f86d081884c7d659a2feaa0c55ad015",
+        "a3bf4f1b2b0b822cd15d6c15b0f00a08\n",
+        "  alt-names:\n",
+        "    - \"*.nowhere.com\"\n",
+        "  required-ekus:\n",
+        "    - serverAuth\n",
+        "  excluded-dns:\n",
+        "    - .test.nowhere.com\n"
+    );
+    let actual: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let expected = PKIPeerConstraints::new(
+        vec![CompoundHashID::try_from(concat!(
+            "SHA256:9f86d081884c7d659a2feaa0c55ad015",
+            "a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        ))
+        .unwrap()],
+        vec![],
+        vec![String::from("*.nowhere.com")],
+        vec![String::from("serverAuth")],
+        vec![],
+        vec![String::from(".test.nowhere.com")]
+    );
+
+    assert_eq!(Some(&expected), actual.peer_constraints())
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_pattern_matches() {
+    assert!(pattern_matches("*.nowhere.com", "test-server.nowhere.com"));
+    assert!(pattern_matches(
+        "TEST-*.nowhere.com",
+        "test-server.Nowhere.com"
+    ));
+    assert!(pattern_matches("*", ""));
+    assert!(pattern_matches("a*b*c", "aXbYbZc"));
+    assert!(!pattern_matches("*.nowhere.com", "nowhere.com"));
+    assert!(!pattern_matches("a*b", "aXbY"));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_dns_in_subtree() {
+    assert!(dns_in_subtree("nowhere.com", "nowhere.com"));
+    assert!(dns_in_subtree("nowhere.com", "test-server.NOWHERE.com"));
+    assert!(!dns_in_subtree("nowhere.com", "test-server.elsewhere.com"));
+    assert!(!dns_in_subtree("where.com", "nowhere.com"));
+    assert!(!dns_in_subtree(".nowhere.com", "nowhere.com"));
+    assert!(dns_in_subtree(".nowhere.com", "test-server.nowhere.com"));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_peer_constraints_check() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let ca = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let ocsp = ocsp_test_cert(&pki.path("server/ocsp/ocsp_cert.pem"));
+    let chain = [cert.as_ref(), ca.as_ref()];
+    let strs = |vals: &[&str]| -> Vec<String> {
+        vals.iter().map(|val| String::from(*val)).collect()
+    };
+    let constraints = |pins, subjects, alt_names, ekus, permitted, excluded| {
+        PKIPeerConstraints::new(
+            pins,
+            strs(subjects),
+            strs(alt_names),
+            strs(ekus),
+            strs(permitted),
+            strs(excluded)
+        )
+    };
+
+    assert!(PKIPeerConstraints::default().check(chain).is_ok());
+    assert!(matches!(
+        PKIPeerConstraints::default().check([]),
+        Err(PKIPeerConstraintError::NoCerts)
+    ));
+
+    // Pins match any certificate in the chain.
+    let server_pin = spki_pin(&pki.path("server/certs/test_server_cert.pem"));
+    let ca_pin = spki_pin(&pki.path("server/ca_cert.pem"));
+    let client_pin = spki_pin(&pki.path("client/ca_cert.pem"));
+
+    assert!(constraints(vec![server_pin], &[], &[], &[], &[], &[])
+        .check(chain)
+        .is_ok());
+    assert!(constraints(vec![ca_pin], &[], &[], &[], &[], &[])
+        .check(chain)
+        .is_ok());
+    assert!(matches!(
+        constraints(vec![client_pin], &[], &[], &[], &[], &[]).check(chain),
+        Err(PKIPeerConstraintError::PinMismatch)
+    ));
+
+    // Subject and alternative name patterns.
+    assert!(
+        constraints(vec![], &["*CN=test-server.*"], &[], &[], &[], &[])
+            .check(chain)
+            .is_ok()
+    );
+    assert!(matches!(
+        constraints(vec![], &["*CN=other*"], &[], &[], &[], &[]).check(chain),
+        Err(PKIPeerConstraintError::SubjectNotAllowed { .. })
+    ));
+    assert!(constraints(vec![], &[], &["*.nowhere.com"], &[], &[], &[])
+        .check(chain)
+        .is_ok());
+    assert!(matches!(
+        constraints(vec![], &[], &["*.elsewhere.com"], &[], &[], &[])
+            .check(chain),
+        Err(PKIPeerConstraintError::AltNameNotAllowed)
+    ));
+
+    // Extended key usages.
+    assert!(matches!(
+        constraints(vec![], &[], &[], &["serverAuth"], &[], &[]).check(chain),
+        Err(PKIPeerConstraintError::MissingEKU { .. })
+    ));
+    assert!(matches!(
+        constraints(vec![], &[], &[], &["notAnEKU"], &[], &[]).check(chain),
+        Err(PKIPeerConstraintError::BadEKU { .. })
+    ));
+    assert!(constraints(vec![], &[], &[], &["OCSPSigning"], &[], &[])
+        .check([ocsp.as_ref()])
+        .is_ok());
+    assert!(
+        constraints(vec![], &[], &[], &["1.3.6.1.5.5.7.3.9"], &[], &[])
+            .check([ocsp.as_ref()])
+            .is_ok()
+    );
+
+    // DNS subtrees.
+    assert!(constraints(vec![], &[], &[], &[], &["nowhere.com"], &[])
+        .check(chain)
+        .is_ok());
+    assert!(matches!(
+        constraints(vec![], &[], &[], &[], &["elsewhere.com"], &[])
+            .check(chain),
+        Err(PKIPeerConstraintError::NameNotPermitted { .. })
+    ));
+    assert!(matches!(
+        constraints(vec![], &[], &[], &[], &[], &[".nowhere.com"]).check(chain),
+        Err(PKIPeerConstraintError::NameExcluded { .. })
+    ));
+}
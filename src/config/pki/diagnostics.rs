@@ -0,0 +1,941 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Offline diagnostics for trust roots.
+//!
+//! This module provides [diagnose](PKITrustRoot::diagnose), which
+//! checks the files referenced by a [PKITrustRoot] and reports
+//! problems with them in a [PKITrustRootReport], and
+//! [check_chain](PKITrustRoot::check_chain), which explains why a
+//! certificate chain is rejected.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::read;
+use std::fs::read_dir;
+use std::io::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use log::debug;
+use log::trace;
+use openssl::error::ErrorStack;
+use openssl::stack::Stack;
+use openssl::x509::X509PurposeId;
+use openssl::x509::X509StoreContext;
+use openssl::x509::X509VerifyResult;
+use openssl::x509::X509;
+use time::OffsetDateTime;
+
+use crate::config::pki::constraints::PKIPeerConstraintError;
+use crate::config::pki::ocsp::OCSPError;
+use crate::config::pki::PKICRLInfo;
+use crate::config::pki::PKICertInfo;
+use crate::config::pki::PKITrustRoot;
+use crate::config::pki::PKITrustRootLoadError;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::CompoundHashAlgo;
+use crate::net::IPEndpointAddr;
+use crate::sign::cert_subject;
+
+/// Errors that can occur while checking a certificate chain with
+/// [check_chain](PKITrustRoot::check_chain).
+#[derive(Debug)]
+pub enum PKIChainError {
+    /// An error occurred loading the trust root.
+    TrustRoot {
+        /// The trust root error.
+        error: PKITrustRootLoadError
+    },
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The certificate chain was empty.
+    NoCerts,
+    /// A certificate in the chain was rejected by verification.
+    Rejected {
+        /// Depth in the chain of the rejected certificate, where the
+        /// leaf is at depth 0.
+        depth: u32,
+        /// Subject name of the rejected certificate, if known.
+        subject: Option<String>,
+        /// The reason for rejection.
+        reason: X509VerifyResult
+    },
+    /// The chain was verified, but violated the peer constraints.
+    Constraint {
+        /// The constraint error.
+        error: PKIPeerConstraintError
+    },
+    /// The chain was verified, but the OCSP check of the first
+    /// certificate failed.
+    OCSP {
+        /// The OCSP error.
+        error: OCSPError
+    }
+}
+
+/// Problems found by [diagnose](PKITrustRoot::diagnose).
+#[derive(Debug)]
+pub enum PKIDiagnostic {
+    /// A file could not be read.
+    IO {
+        /// Path to the file.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    },
+    /// A file could not be parsed.
+    Parse {
+        /// Path to the file.
+        path: PathBuf,
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// A root certificate file contained no certificates.
+    NoCerts {
+        /// Path to the file.
+        path: PathBuf
+    },
+    /// A certificate has expired.
+    Expired {
+        /// Path to the file containing the certificate.
+        path: PathBuf,
+        /// Subject name of the certificate.
+        subject: String,
+        /// Expiry time of the certificate.
+        not_after: SystemTime
+    },
+    /// A certificate is not yet valid.
+    NotYetValid {
+        /// Path to the file containing the certificate.
+        path: PathBuf,
+        /// Subject name of the certificate.
+        subject: String,
+        /// Start of the certificate's validity period.
+        not_before: SystemTime
+    },
+    /// A certificate will expire soon.
+    ExpiresSoon {
+        /// Path to the file containing the certificate.
+        path: PathBuf,
+        /// Subject name of the certificate.
+        subject: String,
+        /// Expiry time of the certificate.
+        not_after: SystemTime
+    },
+    /// A CRL is past its next update time.
+    StaleCRL {
+        /// Path to the file containing the CRL.
+        path: PathBuf,
+        /// Issuer name of the CRL.
+        issuer: String,
+        /// Next update time of the CRL.
+        next_update: SystemTime
+    },
+    /// A CRL will reach its next update time soon.
+    CRLUpdateSoon {
+        /// Path to the file containing the CRL.
+        path: PathBuf,
+        /// Issuer name of the CRL.
+        issuer: String,
+        /// Next update time of the CRL.
+        next_update: SystemTime
+    },
+    /// The trust store could not be built.
+    Load {
+        /// The trust root error.
+        error: PKITrustRootLoadError
+    }
+}
+
+/// Results of an offline check of a [PKITrustRoot], produced by
+/// [diagnose](PKITrustRoot::diagnose).
+///
+/// The [Display] instance gives a human-readable summary, suitable
+/// for startup checks and operational tooling.
+#[derive(Debug)]
+pub struct PKITrustRootReport {
+    /// The root certificates that were found.
+    certs: Vec<PKICertInfo>,
+    /// The CRLs that were found.
+    crls: Vec<PKICRLInfo>,
+    /// Problems that were found.
+    diagnostics: Vec<PKIDiagnostic>
+}
+
+impl PKIDiagnostic {
+    /// Check whether this indicates an error, as opposed to a
+    /// warning about something that will become an error.
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            PKIDiagnostic::ExpiresSoon { .. } |
+                PKIDiagnostic::CRLUpdateSoon { .. }
+        )
+    }
+}
+
+impl PKITrustRootReport {
+    /// Get the root certificates that were found.
+    #[inline]
+    pub fn certs(&self) -> &[PKICertInfo] {
+        &self.certs
+    }
+
+    /// Get the CRLs that were found.
+    #[inline]
+    pub fn crls(&self) -> &[PKICRLInfo] {
+        &self.crls
+    }
+
+    /// Get the problems that were found.
+    #[inline]
+    pub fn diagnostics(&self) -> &[PKIDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Check whether no errors were found.
+    ///
+    /// Warnings do not cause this to fail.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        !self.diagnostics.iter().any(|diag| diag.is_error())
+    }
+}
+
+impl ScopedError for PKIChainError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PKIChainError::TrustRoot { error } => error.scope(),
+            PKIChainError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            PKIChainError::NoCerts => ErrorScope::External,
+            PKIChainError::Rejected { .. } => ErrorScope::External,
+            PKIChainError::Constraint { error } => error.scope(),
+            PKIChainError::OCSP { error } => error.scope()
+        }
+    }
+}
+
+impl Display for PKIChainError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIChainError::TrustRoot { error } => error.fmt(f),
+            PKIChainError::OpenSSL { error } => error.fmt(f),
+            PKIChainError::NoCerts => write!(f, "empty certificate chain"),
+            PKIChainError::Rejected {
+                depth,
+                subject: Some(subject),
+                reason
+            } => write!(
+                f,
+                "certificate {} at depth {} rejected: {}",
+                subject,
+                depth,
+                reason.error_string()
+            ),
+            PKIChainError::Rejected {
+                depth,
+                subject: None,
+                reason
+            } => write!(
+                f,
+                "certificate at depth {} rejected: {}",
+                depth,
+                reason.error_string()
+            ),
+            PKIChainError::Constraint { error } => error.fmt(f),
+            PKIChainError::OCSP { error } => error.fmt(f)
+        }
+    }
+}
+
+impl Display for PKIDiagnostic {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIDiagnostic::IO { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            PKIDiagnostic::Parse { path, error } => {
+                write!(f, "error parsing {}: {}", path.display(), error)
+            }
+            PKIDiagnostic::NoCerts { path } => {
+                write!(f, "no certificates in {}", path.display())
+            }
+            PKIDiagnostic::Expired {
+                path,
+                subject,
+                not_after
+            } => write!(
+                f,
+                "certificate {} in {} expired at {}",
+                subject,
+                path.display(),
+                OffsetDateTime::from(*not_after)
+            ),
+            PKIDiagnostic::NotYetValid {
+                path,
+                subject,
+                not_before
+            } => write!(
+                f,
+                "certificate {} in {} is not valid until {}",
+                subject,
+                path.display(),
+                OffsetDateTime::from(*not_before)
+            ),
+            PKIDiagnostic::ExpiresSoon {
+                path,
+                subject,
+                not_after
+            } => write!(
+                f,
+                "certificate {} in {} expires at {}",
+                subject,
+                path.display(),
+                OffsetDateTime::from(*not_after)
+            ),
+            PKIDiagnostic::StaleCRL {
+                path,
+                issuer,
+                next_update
+            } => write!(
+                f,
+                "CRL from {} in {} was due for update at {}",
+                issuer,
+                path.display(),
+                OffsetDateTime::from(*next_update)
+            ),
+            PKIDiagnostic::CRLUpdateSoon {
+                path,
+                issuer,
+                next_update
+            } => write!(
+                f,
+                "CRL from {} in {} is due for update at {}",
+                issuer,
+                path.display(),
+                OffsetDateTime::from(*next_update)
+            ),
+            PKIDiagnostic::Load { error } => {
+                write!(f, "error building trust store: {}", error)
+            }
+        }
+    }
+}
+
+impl Display for PKITrustRootReport {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        for cert in &self.certs {
+            writeln!(f, "root: {}", cert)?;
+        }
+
+        for crl in &self.crls {
+            writeln!(f, "CRL: {}", crl)?;
+        }
+
+        for diag in &self.diagnostics {
+            if diag.is_error() {
+                writeln!(f, "error: {}", diag)?;
+            } else {
+                writeln!(f, "warning: {}", diag)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl PKITrustRoot {
+    /// Check the validity period of the certificate described by
+    /// `info` at `now`.
+    fn diagnose_cert(
+        info: &PKICertInfo,
+        now: SystemTime,
+        warn_before: Duration,
+        diagnostics: &mut Vec<PKIDiagnostic>
+    ) {
+        if info.not_after <= now {
+            diagnostics.push(PKIDiagnostic::Expired {
+                path: info.path.clone(),
+                subject: info.subject.clone(),
+                not_after: info.not_after
+            })
+        } else if info.not_before > now {
+            diagnostics.push(PKIDiagnostic::NotYetValid {
+                path: info.path.clone(),
+                subject: info.subject.clone(),
+                not_before: info.not_before
+            })
+        } else if info.not_after <= now + warn_before {
+            diagnostics.push(PKIDiagnostic::ExpiresSoon {
+                path: info.path.clone(),
+                subject: info.subject.clone(),
+                not_after: info.not_after
+            })
+        }
+    }
+
+    /// Check the next update time of `info` at `now`.
+    fn diagnose_crl(
+        info: &PKICRLInfo,
+        now: SystemTime,
+        warn_before: Duration,
+        diagnostics: &mut Vec<PKIDiagnostic>
+    ) {
+        if let Some(next_update) = info.next_update {
+            if next_update <= now {
+                diagnostics.push(PKIDiagnostic::StaleCRL {
+                    path: info.path.clone(),
+                    issuer: info.issuer.clone(),
+                    next_update: next_update
+                })
+            } else if next_update <= now + warn_before {
+                diagnostics.push(PKIDiagnostic::CRLUpdateSoon {
+                    path: info.path.clone(),
+                    issuer: info.issuer.clone(),
+                    next_update: next_update
+                })
+            }
+        }
+    }
+
+    /// Check this configuration offline, without establishing any
+    /// connections.
+    ///
+    /// This reads every root certificate and CRL named in the
+    /// configuration, including those in CA directories, and reports
+    /// them together with any problems found.  Certificates that are
+    /// expired or not yet valid at `now`, or that expire within
+    /// `warn_before` of it, are flagged, as are CRLs whose next update
+    /// time has passed or falls within `warn_before`.  Certificate
+    /// fingerprints are computed with `algo`.
+    ///
+    /// This also attempts to build the trust store, and reports any
+    /// error that occurs.  Unlike [load](PKITrustRoot::load), this
+    /// does not stop at the first problem.
+    ///
+    /// Files in CA directories that are neither certificates nor CRLs
+    /// are ignored.
+    pub fn diagnose(
+        &self,
+        algo: &CompoundHashAlgo,
+        now: SystemTime,
+        warn_before: Duration
+    ) -> PKITrustRootReport {
+        debug!(target: "pki-trust-root",
+               "checking PKI trust root configuration");
+
+        let mut certs = Vec::new();
+        let mut crls = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        for path in &self.root_certs {
+            let pem = match read(path) {
+                Ok(pem) => pem,
+                Err(err) => {
+                    diagnostics.push(PKIDiagnostic::IO {
+                        path: path.clone(),
+                        error: err
+                    });
+
+                    continue;
+                }
+            };
+
+            match X509::stack_from_pem(&pem) {
+                Ok(stack) if stack.is_empty() => diagnostics
+                    .push(PKIDiagnostic::NoCerts { path: path.clone() }),
+                Ok(stack) => {
+                    for cert in stack {
+                        match PKICertInfo::inspect(path.clone(), &cert, algo) {
+                            Ok(info) => certs.push(info),
+                            Err(err) => diagnostics.push(PKIDiagnostic::Parse {
+                                path: path.clone(),
+                                error: err
+                            })
+                        }
+                    }
+                }
+                Err(err) => diagnostics.push(PKIDiagnostic::Parse {
+                    path: path.clone(),
+                    error: err
+                })
+            }
+        }
+
+        for path in &self.crls {
+            let result = read(path)
+                .map_err(|err| PKIDiagnostic::IO {
+                    path: path.clone(),
+                    error: err
+                })
+                .and_then(|pem| {
+                    PKICRLInfo::load(path.clone(), &pem).map_err(|err| {
+                        PKIDiagnostic::Parse {
+                            path: path.clone(),
+                            error: err
+                        }
+                    })
+                });
+
+            match result {
+                Ok(info) => crls.push(info),
+                Err(diag) => diagnostics.push(diag)
+            }
+        }
+
+        for dir in &self.dirs {
+            let entries = match read_dir(dir) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    diagnostics.push(PKIDiagnostic::IO {
+                        path: dir.clone(),
+                        error: err
+                    });
+
+                    continue;
+                }
+            };
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+
+            paths.sort();
+
+            for path in paths {
+                let pem = match read(&path) {
+                    Ok(pem) => pem,
+                    Err(err) => {
+                        diagnostics.push(PKIDiagnostic::IO {
+                            path: path,
+                            error: err
+                        });
+
+                        continue;
+                    }
+                };
+
+                match X509::stack_from_pem(&pem) {
+                    Ok(stack) if !stack.is_empty() => {
+                        for cert in stack {
+                            let info =
+                                PKICertInfo::inspect(path.clone(), &cert, algo);
+
+                            match info {
+                                Ok(info) => certs.push(info),
+                                Err(err) => {
+                                    diagnostics.push(PKIDiagnostic::Parse {
+                                        path: path.clone(),
+                                        error: err
+                                    })
+                                }
+                            }
+                        }
+                    }
+                    _ => match PKICRLInfo::load(path.clone(), &pem) {
+                        Ok(info) => crls.push(info),
+                        Err(_) => {
+                            trace!(target: "pki-trust-root",
+                                   "ignoring {} in CA directory",
+                                   path.to_string_lossy());
+                        }
+                    }
+                }
+            }
+        }
+
+        for info in &certs {
+            Self::diagnose_cert(info, now, warn_before, &mut diagnostics)
+        }
+
+        for info in &crls {
+            Self::diagnose_crl(info, now, warn_before, &mut diagnostics)
+        }
+
+        if let Err(err) = self.load(Some(now), None, X509PurposeId::ANY) {
+            diagnostics.push(PKIDiagnostic::Load { error: err })
+        }
+
+        PKITrustRootReport {
+            certs: certs,
+            crls: crls,
+            diagnostics: diagnostics
+        }
+    }
+
+    /// Check whether `chain` would be accepted by this configuration,
+    /// and explain why not if it would be rejected.
+    ///
+    /// The first certificate in `chain` is the one being checked, and
+    /// the rest are untrusted intermediates.  The remaining arguments
+    /// are the same as for [load](PKITrustRoot::load).  The chain is
+    /// verified against the trust store, and then checked against the
+    /// peer constraints, if any are configured.
+    ///
+    /// If `ocsp` is configured with pre-fetched responses, the status
+    /// of the first certificate is then checked against them, as with
+    /// [check_files](super::ocsp::PKIOCSPConfig::check_files).  The responder
+    /// is never queried.
+    pub fn check_chain(
+        &self,
+        chain: &[X509],
+        verify_time: Option<SystemTime>,
+        endpoint: Option<&IPEndpointAddr>,
+        purpose: X509PurposeId
+    ) -> Result<(), PKIChainError> {
+        let (cert, rest) = chain.split_first().ok_or(PKIChainError::NoCerts)?;
+        let store = self
+            .load(verify_time, endpoint, purpose)
+            .map_err(|err| PKIChainError::TrustRoot { error: err })?;
+        let untrusted = Stack::new()
+            .and_then(|stack| {
+                rest.iter().cloned().try_fold(stack, |mut stack, cert| {
+                    stack.push(cert).map(|_| stack)
+                })
+            })
+            .map_err(|err| PKIChainError::OpenSSL { error: err })?;
+        let mut ctx = X509StoreContext::new()
+            .map_err(|err| PKIChainError::OpenSSL { error: err })?;
+        let verified = ctx
+            .init(&store, cert, &untrusted, |ctx| {
+                if ctx.verify_cert()? {
+                    let chain: Vec<X509> = ctx
+                        .chain()
+                        .map(|chain| {
+                            chain.iter().map(|cert| cert.to_owned()).collect()
+                        })
+                        .unwrap_or_default();
+
+                    Ok(Ok(chain))
+                } else {
+                    let subject = ctx
+                        .current_cert()
+                        .and_then(|cert| cert_subject(cert).ok());
+
+                    Ok(Err(PKIChainError::Rejected {
+                        depth: ctx.error_depth(),
+                        subject: subject,
+                        reason: ctx.error()
+                    }))
+                }
+            })
+            .map_err(|err| PKIChainError::OpenSSL { error: err })??;
+
+        if let Some(constraints) = &self.peer_constraints {
+            constraints
+                .check(verified.iter().map(|cert| cert.as_ref()))
+                .map_err(|err| PKIChainError::Constraint { error: err })?;
+        }
+
+        match &self.ocsp {
+            Some(ocsp) if !ocsp.responses().is_empty() => {
+                let ocsp_store = self
+                    .load(verify_time, None, X509PurposeId::OCSP_HELPER)
+                    .map_err(|err| PKIChainError::TrustRoot { error: err })?;
+                let issuer = verified.get(1).ok_or(PKIChainError::OCSP {
+                    error: OCSPError::NoStatus
+                })?;
+
+                ocsp.check_files(&ocsp_store, cert, issuer)
+                    .map_err(|err| PKIChainError::OCSP { error: err })
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+use std::fs::copy;
+#[cfg(test)]
+use std::fs::write;
+#[cfg(test)]
+use std::path::Path;
+
+#[cfg(test)]
+use crate::config::pki::constraints::PKIPeerConstraints;
+#[cfg(test)]
+use crate::config::pki::ocsp_test_cert;
+#[cfg(test)]
+use crate::config::pki::ocsp_test_config;
+#[cfg(test)]
+use crate::config::pki::spki_pin;
+#[cfg(test)]
+use crate::config::pki::testing::TestCA;
+#[cfg(test)]
+use crate::config::pki::testing::TestPKI;
+#[cfg(test)]
+use crate::hashid::HashAlgo;
+#[cfg(test)]
+use crate::init;
+
+#[cfg(test)]
+fn write_test_crl(
+    ca: &TestCA,
+    path: &Path,
+    next_update: SystemTime
+) {
+    ca.write_crl(path, &[], next_update).unwrap()
+}
+
+#[test]
+fn test_diagnose_trust_root() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let ca_dir = pki.path("ca");
+    let stale = pki.path("stale_crl.pem");
+    let day = Duration::from_secs(86400);
+    let now = SystemTime::now();
+
+    std::fs::create_dir(&ca_dir).unwrap();
+    copy(pki.path("client/ca_cert.pem"), ca_dir.join("ca_cert.pem")).unwrap();
+    write(ca_dir.join("README"), "not a certificate").unwrap();
+    write_test_crl(pki.server_ca(), &ca_dir.join("crl.pem"), now + day * 30);
+    write_test_crl(pki.server_ca(), &stale, now - day);
+
+    let conf = PKITrustRoot::new(
+        vec![ca_dir.clone()],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![stale.clone()],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let algo = CompoundHashAlgo::default();
+    let report = conf.diagnose(&algo, now, day);
+
+    assert_eq!(2, report.certs().len());
+    assert_eq!(2, report.crls().len());
+    assert_eq!(1, report.diagnostics().len());
+    assert!(matches!(
+        &report.diagnostics()[0],
+        PKIDiagnostic::StaleCRL { path, .. } if *path == stale
+    ));
+    assert!(!report.is_ok());
+
+    let server_ca = &report.certs()[0];
+    let der = pki.server_ca().cert().to_der().unwrap();
+
+    assert_eq!(pki.path("server/ca_cert.pem"), *server_ca.path());
+    assert_eq!(server_ca.subject(), server_ca.issuer());
+    assert!(server_ca.subject().contains("CN=Test Server CA"));
+    assert!(server_ca.not_before() < now && now < server_ca.not_after());
+    assert_eq!(algo.hash_bytes(&der), *server_ca.fingerprint());
+
+    // Warnings for the CRL in the directory, but not errors.
+    let report = conf.diagnose(&algo, now, day * 60);
+
+    assert!(report
+        .diagnostics()
+        .iter()
+        .any(|diag| matches!(diag, PKIDiagnostic::CRLUpdateSoon { .. })));
+
+    // Everything has expired in the distant future.
+    let later = now + day * 365 * 200;
+    let report = conf.diagnose(&algo, later, day);
+    let expired = report
+        .diagnostics()
+        .iter()
+        .filter(|diag| matches!(diag, PKIDiagnostic::Expired { .. }))
+        .count();
+
+    assert_eq!(2, expired);
+    assert!(!report.to_string().is_empty());
+}
+
+#[test]
+fn test_diagnose_trust_root_missing() {
+    init();
+
+    let conf = PKITrustRoot::new(
+        vec![],
+        vec![PathBuf::from("test/data/nonexistent.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let algo = CompoundHashAlgo::default();
+    let report = conf.diagnose(&algo, SystemTime::now(), Duration::ZERO);
+
+    assert!(report.certs().is_empty());
+    assert!(matches!(report.diagnostics()[0], PKIDiagnostic::IO { .. }));
+    assert!(matches!(
+        report.diagnostics()[1],
+        PKIDiagnostic::Load { .. }
+    ));
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn test_check_chain() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let chain = [cert];
+    let endpoint =
+        IPEndpointAddr::name(String::from("test-server.nowhere.com"));
+    let wrong = IPEndpointAddr::name(String::from("wrong-server.nowhere.com"));
+    let conf = |root: &str, constraints| {
+        PKITrustRoot::new(
+            vec![],
+            vec![pki.path(root)],
+            vec![],
+            vec![],
+            vec![],
+            None,
+            None,
+            None,
+            constraints
+        )
+    };
+    let server = conf("server/ca_cert.pem", None);
+    let client = conf("client/ca_cert.pem", None);
+
+    server
+        .check_chain(&chain, None, Some(&endpoint), X509PurposeId::SSL_SERVER)
+        .expect("Expected success");
+
+    assert!(matches!(
+        server.check_chain(&[], None, None, X509PurposeId::SSL_SERVER),
+        Err(PKIChainError::NoCerts)
+    ));
+
+    match server.check_chain(
+        &chain,
+        None,
+        Some(&wrong),
+        X509PurposeId::SSL_SERVER
+    ) {
+        Err(PKIChainError::Rejected { depth, reason, .. }) => {
+            assert_eq!(0, depth);
+            // X509_V_ERR_HOSTNAME_MISMATCH
+            assert_eq!(62, reason.as_raw());
+        }
+        result => panic!("Expected rejection, got {:?}", result)
+    }
+
+    match client.check_chain(&chain, None, None, X509PurposeId::SSL_SERVER) {
+        Err(PKIChainError::Rejected {
+            depth,
+            subject: Some(subject),
+            reason
+        }) => {
+            assert_eq!(0, depth);
+            assert!(subject.contains("CN=test-server.nowhere.com"));
+            // X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY
+            assert_eq!(20, reason.as_raw());
+        }
+        result => panic!("Expected rejection, got {:?}", result)
+    }
+
+    let pin = spki_pin(&pki.path("client/ca_cert.pem"));
+    let constraints = PKIPeerConstraints::new(
+        vec![pin],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        vec![]
+    );
+    let pinned = conf("server/ca_cert.pem", Some(constraints));
+
+    assert!(matches!(
+        pinned.check_chain(&chain, None, None, X509PurposeId::SSL_SERVER),
+        Err(PKIChainError::Constraint {
+            error: PKIPeerConstraintError::PinMismatch
+        })
+    ));
+}
+
+#[test]
+fn test_check_chain_ocsp() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let responses = [
+        pki.path("server/ocsp/test_server_ocsp.der"),
+        pki.path("server/ocsp/test_revoked_ocsp.der")
+    ];
+    let conf = |responses: &[PathBuf]| {
+        PKITrustRoot::new(
+            vec![],
+            vec![pki.path("server/ca_cert.pem")],
+            vec![],
+            vec![],
+            vec![],
+            None,
+            None,
+            Some(ocsp_test_config(None, responses, false)),
+            None
+        )
+    };
+    let check = |conf: &PKITrustRoot, name: &str| {
+        let cert = ocsp_test_cert(
+            &pki.path(&format!("server/certs/test_{}_cert.pem", name))
+        );
+
+        conf.check_chain(&[cert], None, None, X509PurposeId::SSL_SERVER)
+    };
+    let with_responses = conf(&responses);
+
+    check(&with_responses, "server").expect("Expected success");
+
+    match check(&with_responses, "revoked") {
+        Err(
+            err @ PKIChainError::OCSP {
+                error: OCSPError::Revoked { .. }
+            }
+        ) => assert_eq!(ErrorScope::External, err.scope()),
+        result => panic!("Expected revocation, got {:?}", result)
+    }
+
+    // A response that does not cover the certificate is not enough.
+    assert!(matches!(
+        check(&conf(&responses[1..]), "server"),
+        Err(PKIChainError::OCSP {
+            error: OCSPError::NoStatus
+        })
+    ));
+
+    // Without pre-fetched responses, there is nothing to check.
+    check(&conf(&[]), "revoked").expect("Expected success");
+}
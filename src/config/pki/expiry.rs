@@ -0,0 +1,1431 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Expiry monitoring and renewal for PKI material.
+//!
+//! This module provides [PKIExpiryMonitor], which watches the
+//! certificates and CRLs in a [ReloadableTrustStore] and
+//! [ReloadableIdentity] and warns as they approach expiry, and
+//! [PKIRenewalHook], through which expiring material can be renewed
+//! automatically.
+#[cfg(feature = "openssl")]
+use std::collections::HashMap;
+#[cfg(feature = "openssl")]
+use std::fmt::Display;
+#[cfg(feature = "openssl")]
+use std::fmt::Formatter;
+#[cfg(feature = "openssl")]
+use std::io::Error;
+#[cfg(feature = "openssl")]
+use std::path::PathBuf;
+#[cfg(feature = "openssl")]
+use std::process::Command;
+#[cfg(feature = "openssl")]
+use std::process::ExitStatus;
+#[cfg(feature = "openssl")]
+use std::sync::Arc;
+#[cfg(feature = "openssl")]
+use std::sync::Mutex;
+#[cfg(feature = "openssl")]
+use std::thread::sleep;
+#[cfg(feature = "openssl")]
+use std::thread::Builder;
+#[cfg(feature = "openssl")]
+use std::thread::JoinHandle;
+#[cfg(feature = "openssl")]
+use std::time::Duration;
+#[cfg(feature = "openssl")]
+use std::time::Instant;
+#[cfg(feature = "openssl")]
+use std::time::SystemTime;
+
+#[cfg(feature = "openssl")]
+use log::info;
+#[cfg(feature = "openssl")]
+use log::trace;
+#[cfg(feature = "openssl")]
+use log::warn;
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "openssl")]
+use time::OffsetDateTime;
+
+#[cfg(feature = "openssl")]
+use crate::config::pki::reload::sleep_while_live;
+#[cfg(feature = "openssl")]
+use crate::config::pki::reload::ReloadableIdentity;
+#[cfg(feature = "openssl")]
+use crate::config::pki::reload::ReloadableTrustStore;
+#[cfg(feature = "openssl")]
+use crate::config::pki::PKICRLInfo;
+#[cfg(feature = "openssl")]
+use crate::config::pki::PKICertInfo;
+#[cfg(feature = "openssl")]
+use crate::config::pki::PKIIdentityLoadError;
+#[cfg(feature = "openssl")]
+use crate::config::pki::PKITrustRootLoadError;
+#[cfg(feature = "openssl")]
+use crate::error::ErrorScope;
+#[cfg(feature = "openssl")]
+use crate::error::MutexPoison;
+#[cfg(feature = "openssl")]
+use crate::error::ScopedError;
+#[cfg(feature = "openssl")]
+use crate::error::WithMutexPoison;
+#[cfg(feature = "openssl")]
+use crate::hashid::CompoundHashAlgo;
+#[cfg(feature = "openssl")]
+use crate::shutdown::ShutdownFlag;
+
+#[cfg(feature = "openssl")]
+/// Errors that can occur when renewing expiring certificates or CRLs.
+#[derive(Debug)]
+pub enum PKIRenewalError {
+    /// The renewal command could not be run.
+    Command {
+        /// The command.
+        command: String,
+        /// The IO error.
+        error: Error
+    },
+    /// The renewal command did not finish in time, and was killed.
+    TimedOut {
+        /// The command.
+        command: String,
+        /// The time it was allowed.
+        timeout: Duration
+    },
+    /// The renewal command failed.
+    Failed {
+        /// The command.
+        command: String,
+        /// Exit status of the command.
+        status: ExitStatus
+    },
+    /// A renewal hook reported an error.
+    Hook {
+        /// Description of the error.
+        msg: String
+    },
+    /// The trust store could not be reloaded.
+    TrustRoot {
+        /// The trust root error.
+        error: PKITrustRootLoadError
+    },
+    /// The identity could not be reloaded.
+    Identity {
+        /// The identity error.
+        error: PKIIdentityLoadError
+    }
+}
+
+/// Configuration for monitoring the expiry of certificates and CRLs.
+///
+/// This is used by [PKIExpiryMonitor] (with the `openssl` feature),
+/// which periodically checks our own certificate chain, the root
+/// certificates, and the next update times of CRLs, and warns as
+/// each threshold is crossed.
+///
+/// # YAML Format
+///
+/// The YAML format has three fields, all of which are optional:
+///
+/// - `thresholds`: A list of times in seconds before expiry at which to warn. A
+///   warning is issued once for each threshold as it is crossed, and again on
+///   expiry.  Defaults to 30 days, 7 days, and 1 day.
+///
+/// - `interval`: Time in seconds between checks.  Defaults to one hour.
+///
+/// - `renew-command`: A command to run for each warning, given as a program
+///   followed by its arguments.  This is intended to invoke a local ACME or EST
+///   client, which should write renewed files in place.  See [PKICommandHook]
+///   for the environment it is run with.
+///
+/// ## Examples
+///
+/// ```yaml
+/// thresholds:
+///   - 1209600
+///   - 172800
+/// interval: 600
+/// renew-command:
+///   - /usr/bin/certbot
+///   - renew
+///   - --quiet
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-expiry")]
+pub struct PKIExpiryConfig {
+    /// Times in seconds before expiry at which to warn.
+    #[serde(default = "PKIExpiryConfig::default_thresholds")]
+    thresholds: Vec<u32>,
+    /// Time in seconds between checks.
+    #[serde(default = "PKIExpiryConfig::default_interval")]
+    interval: u32,
+    /// Command to run to renew expiring material.
+    #[serde(default)]
+    renew_command: Vec<String>
+}
+
+#[cfg(feature = "openssl")]
+/// Something tracked by a [PKIExpiryMonitor] that is close to, or
+/// past, its expiry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PKIExpiryItem {
+    /// A certificate in our own certificate chain.
+    Identity(PKICertInfo),
+    /// A root certificate in the trust store.
+    RootCert(PKICertInfo),
+    /// A CRL in the trust store, which expires at its next update
+    /// time.
+    CRL(PKICRLInfo)
+}
+
+#[cfg(feature = "openssl")]
+/// Warning issued by a [PKIExpiryMonitor] when a threshold is
+/// crossed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PKIExpiryWarning {
+    /// The item that is expiring.
+    item: PKIExpiryItem,
+    /// The threshold that was crossed, or `None` if the item has
+    /// expired.
+    threshold: Option<Duration>
+}
+
+#[cfg(feature = "openssl")]
+/// Results of a single check by a [PKIExpiryMonitor].
+#[derive(Debug, Default)]
+pub struct PKIExpiryReport {
+    /// Warnings that were newly issued.
+    warnings: Vec<PKIExpiryWarning>,
+    /// Errors from renewal hooks and reloads.
+    errors: Vec<PKIRenewalError>
+}
+
+#[cfg(feature = "openssl")]
+/// Trait for renewal hooks, called by a [PKIExpiryMonitor] for each
+/// warning it issues.
+///
+/// A hook should obtain renewed certificates or CRLs, and write them
+/// in place of the files named by the configuration; after any hook
+/// succeeds, the monitor reloads its trust store and identity.  This
+/// is implemented for closures, and by [PKICommandHook].
+pub trait PKIRenewalHook {
+    /// Attempt to renew the item described by `warning`.
+    fn renew(
+        &mut self,
+        warning: &PKIExpiryWarning
+    ) -> Result<(), PKIRenewalError>;
+}
+
+#[cfg(feature = "openssl")]
+/// [PKIRenewalHook] that runs an external command.
+///
+/// The command is run for each warning, with the following
+/// environment variables describing it:
+///
+/// - `PKI_EXPIRY_KIND`: One of `identity`, `root-cert`, or `crl`.  Warnings are
+///   issued for every certificate in our own chain and every root certificate
+///   and CRL in the trust store, so the command is run for all three kinds; a
+///   command that can only renew some of them should check this and exit
+///   successfully for the others.
+///
+/// - `PKI_EXPIRY_PATH`: Path to the file containing the item.
+///
+/// - `PKI_EXPIRY_NAME`: Subject name of the certificate, or issuer name of the
+///   CRL.
+///
+/// - `PKI_EXPIRY_TIME`: Expiry time, in seconds since the Unix epoch.
+///
+/// A non-zero exit status is reported as an error.  A command that
+/// runs for longer than its timeout (five minutes by default) is
+/// killed, and also reported as an error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PKICommandHook {
+    /// The program and its arguments.
+    command: Vec<String>,
+    /// Time to allow the command to run.
+    timeout: Duration
+}
+
+#[cfg(feature = "openssl")]
+/// Monitor for the expiry of certificates and CRLs.
+///
+/// This tracks our own certificate chain from a [ReloadableIdentity]
+/// and the root certificates and CRLs from a [ReloadableTrustStore],
+/// and issues a [PKIExpiryWarning] whenever one of them crosses a
+/// threshold given in its [PKIExpiryConfig], or expires.  Each
+/// threshold produces only one warning for a given item; renewing
+/// the item resets this.
+///
+/// Checks are made with [check](PKIExpiryMonitor::check), or
+/// periodically in a background thread with
+/// [watch](PKIExpiryMonitor::watch).  Each check first picks up any
+/// files that have changed.  Warnings are logged, and passed to the
+/// [PKIRenewalHook], if there is one.  If the hook succeeds, the
+/// trust store and identity are reloaded.
+///
+/// Renewed material only takes effect in connections that use the
+/// reloaded trust store and identity, such as those accepted by
+/// [load_acceptor](ReloadableTrustStore::load_acceptor), or made
+/// with [set_verify_store](ReloadableTrustStore::set_verify_store)
+/// and [set_identity](ReloadableIdentity::set_identity).  Contexts
+/// loaded directly from a [PKITrustRoot](super::PKITrustRoot) or
+/// [PKIIdentity](super::PKIIdentity) keep the material they were
+/// created with.
+pub struct PKIExpiryMonitor {
+    /// Thresholds, in decreasing order.
+    thresholds: Vec<Duration>,
+    /// Time between checks.
+    interval: Duration,
+    /// The trust store to monitor.
+    trust_store: Option<Arc<ReloadableTrustStore>>,
+    /// The identity to monitor.
+    identity: Option<Arc<ReloadableIdentity>>,
+    /// Hook to call for each warning.
+    hook: Mutex<Option<Box<dyn PKIRenewalHook + Send>>>,
+    /// Number of thresholds crossed for each item that has been
+    /// warned about.
+    warned: Mutex<HashMap<(PathBuf, String, SystemTime), usize>>
+}
+
+#[cfg(feature = "openssl")]
+/// Default time to allow a renewal command to run.
+const RENEW_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[cfg(feature = "openssl")]
+/// Interval at which to check whether a renewal command has exited.
+const RENEW_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl PKIExpiryConfig {
+    /// Create a new `PKIExpiryConfig` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        thresholds: Vec<u32>,
+        interval: u32,
+        renew_command: Vec<String>
+    ) -> Self {
+        PKIExpiryConfig {
+            thresholds: thresholds,
+            interval: interval,
+            renew_command: renew_command
+        }
+    }
+
+    #[inline]
+    fn default_thresholds() -> Vec<u32> {
+        vec![30 * 86400, 7 * 86400, 86400]
+    }
+
+    #[inline]
+    fn default_interval() -> u32 {
+        3600
+    }
+
+    /// Get the times in seconds before expiry at which to warn.
+    #[inline]
+    pub fn thresholds(&self) -> &[u32] {
+        &self.thresholds
+    }
+
+    /// Get the time in seconds between checks.
+    #[inline]
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Get the command to run to renew expiring material.
+    #[inline]
+    pub fn renew_command(&self) -> &[String] {
+        &self.renew_command
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Get a [PKICommandHook] for `renew-command`, if one is given.
+    #[inline]
+    pub fn command_hook(&self) -> Option<PKICommandHook> {
+        if self.renew_command.is_empty() {
+            None
+        } else {
+            Some(PKICommandHook::new(self.renew_command.clone()))
+        }
+    }
+}
+
+impl Default for PKIExpiryConfig {
+    #[inline]
+    fn default() -> Self {
+        PKIExpiryConfig {
+            thresholds: PKIExpiryConfig::default_thresholds(),
+            interval: PKIExpiryConfig::default_interval(),
+            renew_command: vec![]
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIExpiryItem {
+    /// Get the path to the file containing the item.
+    #[inline]
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            PKIExpiryItem::Identity(info) => info.path(),
+            PKIExpiryItem::RootCert(info) => info.path(),
+            PKIExpiryItem::CRL(info) => info.path()
+        }
+    }
+
+    /// Get the subject name of a certificate, or the issuer name of
+    /// a CRL.
+    #[inline]
+    pub fn name(&self) -> &str {
+        match self {
+            PKIExpiryItem::Identity(info) => info.subject(),
+            PKIExpiryItem::RootCert(info) => info.subject(),
+            PKIExpiryItem::CRL(info) => info.issuer()
+        }
+    }
+
+    /// Get the expiry time, or `None` for a CRL with no next update
+    /// time.
+    #[inline]
+    pub fn expires(&self) -> Option<SystemTime> {
+        match self {
+            PKIExpiryItem::Identity(info) => Some(info.not_after()),
+            PKIExpiryItem::RootCert(info) => Some(info.not_after()),
+            PKIExpiryItem::CRL(info) => info.next_update()
+        }
+    }
+
+    /// Get a short name for the kind of item.
+    #[inline]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PKIExpiryItem::Identity(_) => "identity",
+            PKIExpiryItem::RootCert(_) => "root-cert",
+            PKIExpiryItem::CRL(_) => "crl"
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIExpiryWarning {
+    /// Get the item that is expiring.
+    #[inline]
+    pub fn item(&self) -> &PKIExpiryItem {
+        &self.item
+    }
+
+    /// Get the threshold that was crossed, or `None` if the item has
+    /// expired.
+    #[inline]
+    pub fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+
+    /// Check whether the item has expired.
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.threshold.is_none()
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIExpiryReport {
+    /// Get the warnings that were newly issued.
+    #[inline]
+    pub fn warnings(&self) -> &[PKIExpiryWarning] {
+        &self.warnings
+    }
+
+    /// Get the errors from renewal hooks and reloads.
+    #[inline]
+    pub fn errors(&self) -> &[PKIRenewalError] {
+        &self.errors
+    }
+
+    /// Check whether there are neither warnings nor errors.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty() && self.errors.is_empty()
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl<F> PKIRenewalHook for F
+where
+    F: FnMut(&PKIExpiryWarning) -> Result<(), PKIRenewalError>
+{
+    #[inline]
+    fn renew(
+        &mut self,
+        warning: &PKIExpiryWarning
+    ) -> Result<(), PKIRenewalError> {
+        self(warning)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKICommandHook {
+    /// Create a new `PKICommandHook` that runs `command`, which is a
+    /// program followed by its arguments, with the default timeout.
+    #[inline]
+    pub fn new(command: Vec<String>) -> Self {
+        PKICommandHook::with_timeout(command, RENEW_TIMEOUT)
+    }
+
+    /// Create a new `PKICommandHook` that runs `command`, killing it
+    /// if it has not exited after `timeout`.
+    #[inline]
+    pub fn with_timeout(
+        command: Vec<String>,
+        timeout: Duration
+    ) -> Self {
+        PKICommandHook {
+            command: command,
+            timeout: timeout
+        }
+    }
+
+    /// Get the program and its arguments.
+    #[inline]
+    pub fn command(&self) -> &[String] {
+        &self.command
+    }
+
+    /// Get the time the command is allowed to run.
+    #[inline]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIRenewalHook for PKICommandHook {
+    fn renew(
+        &mut self,
+        warning: &PKIExpiryWarning
+    ) -> Result<(), PKIRenewalError> {
+        let (program, args) = match self.command.split_first() {
+            Some(split) => split,
+            None => {
+                return Err(PKIRenewalError::Hook {
+                    msg: String::from("empty renewal command")
+                })
+            }
+        };
+        let item = warning.item();
+        let expires = item
+            .expires()
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+
+        info!(target: "pki-expiry",
+              "running renewal command {} for {}",
+              program, item);
+
+        let command_err = |err| PKIRenewalError::Command {
+            command: program.clone(),
+            error: err
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .env("PKI_EXPIRY_KIND", item.kind())
+            .env("PKI_EXPIRY_PATH", item.path())
+            .env("PKI_EXPIRY_NAME", item.name())
+            .env("PKI_EXPIRY_TIME", expires.to_string())
+            .spawn()
+            .map_err(command_err)?;
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(command_err)? {
+                break status;
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                warn!(target: "pki-expiry",
+                      "killing renewal command {} after {} seconds",
+                      program, self.timeout.as_secs());
+
+                // The child may have exited since it was last polled.
+                if let Err(err) = child.kill() {
+                    warn!(target: "pki-expiry",
+                          "error killing renewal command {}: {}",
+                          program, err);
+                }
+
+                child.wait().map_err(command_err)?;
+
+                return Err(PKIRenewalError::TimedOut {
+                    command: program.clone(),
+                    timeout: self.timeout
+                });
+            }
+
+            sleep(RENEW_POLL_INTERVAL.min(deadline - now))
+        };
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(PKIRenewalError::Failed {
+                command: program.clone(),
+                status: status
+            })
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIExpiryMonitor {
+    /// Create a new `PKIExpiryMonitor` from `config`.
+    ///
+    /// This monitors `trust_store` and `identity`, if they are given,
+    /// and calls `hook` for each warning.  If `hook` is `None`, the
+    /// hook created from `config` by
+    /// [command_hook](PKIExpiryConfig::command_hook) is used, if
+    /// there is one.
+    pub fn new(
+        config: &PKIExpiryConfig,
+        trust_store: Option<Arc<ReloadableTrustStore>>,
+        identity: Option<Arc<ReloadableIdentity>>,
+        hook: Option<Box<dyn PKIRenewalHook + Send>>
+    ) -> Self {
+        let mut thresholds: Vec<Duration> = config
+            .thresholds
+            .iter()
+            .map(|secs| Duration::from_secs(*secs as u64))
+            .collect();
+
+        thresholds.sort_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+
+        let hook = hook.or_else(|| {
+            config
+                .command_hook()
+                .map(|hook| Box::new(hook) as Box<dyn PKIRenewalHook + Send>)
+        });
+
+        PKIExpiryMonitor {
+            thresholds: thresholds,
+            interval: Duration::from_secs(config.interval as u64),
+            trust_store: trust_store,
+            identity: identity,
+            hook: Mutex::new(hook),
+            warned: Mutex::new(HashMap::new())
+        }
+    }
+
+    /// Get the trust store being monitored.
+    #[inline]
+    pub fn trust_store(&self) -> Option<&Arc<ReloadableTrustStore>> {
+        self.trust_store.as_ref()
+    }
+
+    /// Get the identity being monitored.
+    #[inline]
+    pub fn identity(&self) -> Option<&Arc<ReloadableIdentity>> {
+        self.identity.as_ref()
+    }
+
+    /// Get the number of thresholds that `expires` has crossed at
+    /// `now`, counting expiry as one more.
+    fn level(
+        &self,
+        expires: SystemTime,
+        now: SystemTime
+    ) -> usize {
+        match expires.duration_since(now) {
+            Ok(remaining) if !remaining.is_zero() => self
+                .thresholds
+                .iter()
+                .take_while(|threshold| remaining <= **threshold)
+                .count(),
+            _ => self.thresholds.len() + 1
+        }
+    }
+
+    /// Collect the certificates and CRLs currently in use.
+    fn items(&self) -> Result<Vec<PKIExpiryItem>, MutexPoison> {
+        let algo = CompoundHashAlgo::default();
+        let mut items = Vec::new();
+
+        if let Some(identity) = &self.identity {
+            let current = identity.current()?;
+            let path = identity.identity().cert_chain();
+
+            for cert in &current.1 {
+                match PKICertInfo::inspect(path.clone(), cert, &algo) {
+                    Ok(info) => items.push(PKIExpiryItem::Identity(info)),
+                    Err(err) => {
+                        warn!(target: "pki-expiry",
+                              "error inspecting certificate in {}: {}",
+                              path.display(), err)
+                    }
+                }
+            }
+        }
+
+        if let Some(store) = &self.trust_store {
+            // Problems with the files themselves are reported when
+            // the store is reloaded.
+            let report = store.trust_root().diagnose(
+                &algo,
+                SystemTime::now(),
+                Duration::ZERO
+            );
+
+            items.extend(
+                report.certs().iter().cloned().map(PKIExpiryItem::RootCert)
+            );
+            items.extend(report.crls().iter().cloned().map(PKIExpiryItem::CRL));
+        }
+
+        Ok(items)
+    }
+
+    /// Reload the trust store and identity, either unconditionally or
+    /// only if their files have changed.
+    fn reload(
+        &self,
+        force: bool,
+        errors: &mut Vec<PKIRenewalError>
+    ) -> Result<(), MutexPoison> {
+        if let Some(store) = &self.trust_store {
+            let result = if force {
+                store.reload()
+            } else {
+                store.poll().map(|_| ())
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(WithMutexPoison::Inner { error }) => {
+                    errors.push(PKIRenewalError::TrustRoot { error: error })
+                }
+                Err(WithMutexPoison::MutexPoison) => return Err(MutexPoison)
+            }
+        }
+
+        if let Some(identity) = &self.identity {
+            let result = if force {
+                identity.reload()
+            } else {
+                identity.poll().map(|_| ())
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(WithMutexPoison::Inner { error }) => {
+                    errors.push(PKIRenewalError::Identity { error: error })
+                }
+                Err(WithMutexPoison::MutexPoison) => return Err(MutexPoison)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check expiry times at `now`.
+    ///
+    /// This first reloads the trust store and identity if their files
+    /// have changed.  A warning is then issued for each item that has
+    /// crossed a threshold since it was last warned about, and the
+    /// renewal hook is called for each warning.  If the hook succeeds
+    /// for any of them, the trust store and identity are reloaded.
+    pub fn check(
+        &self,
+        now: SystemTime
+    ) -> Result<PKIExpiryReport, MutexPoison> {
+        trace!(target: "pki-expiry",
+               "checking certificate and CRL expiry");
+
+        let mut report = PKIExpiryReport::default();
+
+        self.reload(false, &mut report.errors)?;
+
+        let items = self.items()?;
+        let mut warned = self.warned.lock().map_err(|_| MutexPoison)?;
+        let mut seen = HashMap::with_capacity(items.len());
+
+        for item in items {
+            let expires = match item.expires() {
+                Some(expires) => expires,
+                None => continue
+            };
+            let level = self.level(expires, now);
+            let key = (item.path().clone(), item.name().to_string(), expires);
+            let prev = warned.get(&key).copied().unwrap_or(0);
+
+            seen.insert(key, level.max(prev));
+
+            if level > prev {
+                let warning = PKIExpiryWarning {
+                    item: item,
+                    threshold: self.thresholds.get(level - 1).copied()
+                };
+
+                warn!(target: "pki-expiry",
+                      "{}",
+                      warning);
+
+                report.warnings.push(warning)
+            }
+        }
+
+        // Forget items that are no longer in use.
+        *warned = seen;
+        drop(warned);
+
+        if !report.warnings.is_empty() {
+            let mut hook = self.hook.lock().map_err(|_| MutexPoison)?;
+
+            if let Some(hook) = hook.as_mut() {
+                let mut renewed = false;
+
+                for warning in &report.warnings {
+                    match hook.renew(warning) {
+                        Ok(()) => renewed = true,
+                        Err(err) => {
+                            warn!(target: "pki-expiry",
+                                  "failed to renew {}: {}",
+                                  warning.item(), err);
+
+                            report.errors.push(err)
+                        }
+                    }
+                }
+
+                if renewed {
+                    info!(target: "pki-expiry",
+                          "reloading renewed PKI material");
+
+                    self.reload(true, &mut report.errors)?;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Start a thread that calls [check](PKIExpiryMonitor::check) on
+    /// `monitor` immediately, then at the configured interval, until
+    /// `shutdown` is set.
+    ///
+    /// Reports containing warnings or errors are passed to `report`.
+    /// The thread exits if a mutex is poisoned.
+    pub fn watch<F>(
+        monitor: Arc<Self>,
+        shutdown: ShutdownFlag,
+        mut report: F
+    ) -> Result<JoinHandle<()>, Error>
+    where
+        F: 'static + FnMut(&PKIExpiryReport) + Send {
+        Builder::new()
+            .name(String::from("pki-expiry-watch"))
+            .spawn(move || {
+                while shutdown.is_live() {
+                    match monitor.check(SystemTime::now()) {
+                        Ok(result) => {
+                            if !result.is_empty() {
+                                report(&result)
+                            }
+                        }
+                        Err(_) => {
+                            warn!(target: "pki-expiry",
+                                  "mutex poisoned, stopping expiry watch");
+
+                            return;
+                        }
+                    }
+
+                    sleep_while_live(&shutdown, monitor.interval);
+                }
+            })
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl ScopedError for PKIRenewalError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PKIRenewalError::Command { error, .. } => error.scope(),
+            PKIRenewalError::TimedOut { .. } => ErrorScope::System,
+            PKIRenewalError::Failed { .. } => ErrorScope::System,
+            PKIRenewalError::Hook { .. } => ErrorScope::System,
+            PKIRenewalError::TrustRoot { error } => error.scope(),
+            PKIRenewalError::Identity { error } => error.scope()
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKIRenewalError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIRenewalError::Command { command, error } => {
+                write!(f, "error running {}: {}", command, error)
+            }
+            PKIRenewalError::TimedOut { command, timeout } => {
+                write!(
+                    f,
+                    "{} did not finish within {} seconds",
+                    command,
+                    timeout.as_secs()
+                )
+            }
+            PKIRenewalError::Failed { command, status } => {
+                write!(f, "{} failed: {}", command, status)
+            }
+            PKIRenewalError::Hook { msg } => write!(f, "{}", msg),
+            PKIRenewalError::TrustRoot { error } => {
+                write!(f, "error reloading trust store: {}", error)
+            }
+            PKIRenewalError::Identity { error } => {
+                write!(f, "error reloading identity: {}", error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKIExpiryItem {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIExpiryItem::Identity(info) => write!(
+                f,
+                "certificate {} in {}",
+                info.subject(),
+                info.path().display()
+            ),
+            PKIExpiryItem::RootCert(info) => write!(
+                f,
+                "root certificate {} in {}",
+                info.subject(),
+                info.path().display()
+            ),
+            PKIExpiryItem::CRL(info) => write!(
+                f,
+                "CRL from {} in {}",
+                info.issuer(),
+                info.path().display()
+            )
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for PKIExpiryWarning {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        let expires = self
+            .item
+            .expires()
+            .map(OffsetDateTime::from)
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        match (&self.item, self.threshold) {
+            (PKIExpiryItem::CRL(_), None) => {
+                write!(f, "{} was due for update at {}", self.item, expires)
+            }
+            (PKIExpiryItem::CRL(_), Some(_)) => {
+                write!(f, "{} is due for update at {}", self.item, expires)
+            }
+            (_, None) => write!(f, "{} expired at {}", self.item, expires),
+            (_, Some(_)) => write!(f, "{} expires at {}", self.item, expires)
+        }
+    }
+}
+
+#[cfg(all(test, unix, feature = "openssl"))]
+use std::fs::read_to_string;
+#[cfg(all(test, feature = "openssl"))]
+use std::sync::atomic::AtomicUsize;
+#[cfg(all(test, feature = "openssl"))]
+use std::sync::atomic::Ordering;
+#[cfg(all(test, feature = "openssl"))]
+use std::sync::mpsc::channel;
+
+#[cfg(all(test, feature = "openssl"))]
+use openssl::x509::X509PurposeId;
+
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestCA;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestCertSpec;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::PKIIdentity;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::PKITrustRoot;
+#[cfg(all(test, feature = "openssl"))]
+use crate::hashid::HashAlgo;
+#[cfg(test)]
+use crate::init;
+
+#[test]
+fn test_deserialize_expiry_config() {
+    init();
+
+    let yaml = concat!(
+        "thresholds:\n",
+        "  - 1209600\n",
+        "  - 172800\n",
+        "interval: 600\n",
+        "renew-command:\n",
+        "  - /usr/bin/certbot\n",
+        "  - renew\n"
+    );
+    let expected = PKIExpiryConfig::new(
+        vec![1209600, 172800],
+        600,
+        vec![String::from("/usr/bin/certbot"), String::from("renew")]
+    );
+    let actual = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(expected, actual);
+
+    let yaml = "interval: 600\n";
+    let actual: PKIExpiryConfig = serde_yaml::from_str(yaml).unwrap();
+
+    assert_eq!(&[2592000, 604800, 86400], actual.thresholds());
+    assert!(actual.renew_command().is_empty());
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn expiry_test_setup(
+    pki: &TestPKI,
+    lifetime: Duration,
+    crl_lifetime: Option<Duration>
+) -> (TestCA, PKIIdentity, PKITrustRoot) {
+    let ca = TestCA::root("Expiry Test CA").unwrap();
+    let ca_path = pki.path("expiry_ca_cert.pem");
+    let crl_path = pki.path("expiry_crl.pem");
+    let spec = TestCertSpec::valid("expiry-test", vec![], vec![], lifetime);
+    let identity = ca
+        .issue(&spec)
+        .unwrap()
+        .write(&pki.path("expiry_cert.pem"), &pki.path("expiry_key.pem"))
+        .unwrap();
+    let crls = match crl_lifetime {
+        Some(lifetime) => {
+            ca.write_crl(&crl_path, &[], SystemTime::now() + lifetime)
+                .unwrap();
+
+            vec![crl_path]
+        }
+        None => vec![]
+    };
+
+    ca.write(&ca_path, &pki.path("expiry_ca_key.pem")).unwrap();
+
+    let trust_root = PKITrustRoot::new(
+        vec![],
+        vec![ca_path],
+        crls,
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+
+    (ca, identity, trust_root)
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn expiry_test_monitor(
+    identity: PKIIdentity,
+    trust_root: PKITrustRoot,
+    hook: Option<Box<dyn PKIRenewalHook + Send>>
+) -> PKIExpiryMonitor {
+    expiry_test_monitor_with(identity, trust_root, 0, vec![], hook)
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn expiry_test_monitor_with(
+    identity: PKIIdentity,
+    trust_root: PKITrustRoot,
+    interval: u32,
+    renew_command: Vec<String>,
+    hook: Option<Box<dyn PKIRenewalHook + Send>>
+) -> PKIExpiryMonitor {
+    let day = 86400;
+    let config = PKIExpiryConfig::new(
+        vec![day, 30 * day, 7 * day],
+        interval,
+        renew_command
+    );
+    let store =
+        ReloadableTrustStore::new(trust_root, None, None, X509PurposeId::ANY)
+            .unwrap();
+    let identity = ReloadableIdentity::new(identity).unwrap();
+
+    PKIExpiryMonitor::new(
+        &config,
+        Some(Arc::new(store)),
+        Some(Arc::new(identity)),
+        hook
+    )
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_expiry_monitor_thresholds() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) =
+        expiry_test_setup(&pki, day * 10, Some(day * 2));
+    let monitor = expiry_test_monitor(identity, trust_root, None);
+    let now = SystemTime::now();
+    let report = monitor.check(now).unwrap();
+    let mut warnings: Vec<(&str, Option<Duration>)> = report
+        .warnings()
+        .iter()
+        .map(|warning| (warning.item().kind(), warning.threshold()))
+        .collect();
+
+    warnings.sort();
+
+    assert!(report.errors().is_empty());
+    assert_eq!(
+        vec![("crl", Some(day * 7)), ("identity", Some(day * 30))],
+        warnings
+    );
+
+    // Thresholds are only warned about once.
+    assert!(monitor.check(now).unwrap().is_empty());
+
+    let report = monitor.check(now + day * 9 + day / 2).unwrap();
+    let mut warnings: Vec<(&str, Option<Duration>)> = report
+        .warnings()
+        .iter()
+        .map(|warning| (warning.item().kind(), warning.threshold()))
+        .collect();
+
+    warnings.sort();
+
+    assert_eq!(vec![("crl", None), ("identity", Some(day))], warnings);
+
+    let report = monitor.check(now + day * 11).unwrap();
+
+    assert_eq!(1, report.warnings().len());
+    assert!(report.warnings()[0].is_expired());
+    assert!(report.warnings()[0].to_string().contains("CN=expiry-test"));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_expiry_monitor_renewal() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (ca, identity, trust_root) = expiry_test_setup(&pki, day * 3, None);
+    let cert_path = identity.cert_chain().clone();
+    let key_path = pki.path("expiry_key.pem");
+    let calls = Arc::new(AtomicUsize::new(0));
+    let hook_calls = calls.clone();
+    let hook = move |warning: &PKIExpiryWarning| {
+        hook_calls.fetch_add(1, Ordering::SeqCst);
+
+        match warning.item() {
+            PKIExpiryItem::Identity(_) => {
+                let spec = TestCertSpec::server("expiry-test");
+
+                ca.issue(&spec)
+                    .unwrap()
+                    .write(&cert_path, &key_path)
+                    .unwrap();
+
+                Ok(())
+            }
+            _ => Err(PKIRenewalError::Hook {
+                msg: String::from("unexpected warning")
+            })
+        }
+    };
+    let monitor =
+        expiry_test_monitor(identity, trust_root, Some(Box::new(hook)));
+    let old = monitor.identity().unwrap().current().unwrap();
+    let report = monitor.check(SystemTime::now()).unwrap();
+
+    assert_eq!(1, report.warnings().len());
+    assert!(report.errors().is_empty());
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+
+    let new = monitor.identity().unwrap().current().unwrap();
+
+    assert!(!Arc::ptr_eq(&old, &new));
+    assert!(new.1[0].not_after() > old.1[0].not_after());
+    // The renewed certificate does not need renewing.
+    assert!(monitor.check(SystemTime::now()).unwrap().is_empty());
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_expiry_monitor_renewal_failure() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) = expiry_test_setup(&pki, day * 3, None);
+    let hook = |_: &PKIExpiryWarning| {
+        Err(PKIRenewalError::Hook {
+            msg: String::from("renewal failed")
+        })
+    };
+    let monitor =
+        expiry_test_monitor(identity, trust_root, Some(Box::new(hook)));
+    let old = monitor.identity().unwrap().current().unwrap();
+    let report = monitor.check(SystemTime::now()).unwrap();
+
+    assert_eq!(1, report.warnings().len());
+    assert_eq!(1, report.errors().len());
+    assert!(Arc::ptr_eq(
+        &old,
+        &monitor.identity().unwrap().current().unwrap()
+    ));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_expiry_monitor_watch() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) = expiry_test_setup(&pki, day * 3, None);
+    let monitor = Arc::new(expiry_test_monitor(identity, trust_root, None));
+    let mut shutdown = ShutdownFlag::new();
+    let (send, recv) = channel();
+    let watch =
+        PKIExpiryMonitor::watch(monitor, shutdown.clone(), move |report| {
+            send.send(report.warnings().len()).unwrap()
+        })
+        .unwrap();
+
+    assert_eq!(1, recv.recv_timeout(Duration::from_secs(5)).unwrap());
+
+    shutdown.set();
+    watch.join().unwrap();
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_expiry_monitor_watch_shutdown() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) = expiry_test_setup(&pki, day * 3, None);
+    let monitor = Arc::new(expiry_test_monitor_with(
+        identity,
+        trust_root,
+        3600,
+        vec![],
+        None
+    ));
+    let mut shutdown = ShutdownFlag::new();
+    let (send, recv) = channel();
+    let watch =
+        PKIExpiryMonitor::watch(monitor, shutdown.clone(), move |report| {
+            send.send(report.warnings().len()).unwrap()
+        })
+        .unwrap();
+
+    // Wait for the first check, so that the thread is sleeping.
+    assert_eq!(1, recv.recv_timeout(Duration::from_secs(5)).unwrap());
+
+    let start = Instant::now();
+
+    // The thread notices shutdown without waiting out the interval.
+    shutdown.set();
+    watch.join().unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(60));
+}
+
+#[cfg(all(unix, feature = "openssl"))]
+#[test]
+fn test_expiry_monitor_command_hook() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) = expiry_test_setup(&pki, day * 3, None);
+    let marker = pki.path("renewed");
+    let command = vec![
+        String::from("sh"),
+        String::from("-c"),
+        format!("echo \"$PKI_EXPIRY_KIND\" > {}", marker.display()),
+    ];
+    // The hook from the configuration is used when none is given.
+    let monitor =
+        expiry_test_monitor_with(identity, trust_root, 0, command, None);
+    let report = monitor.check(SystemTime::now()).unwrap();
+
+    assert_eq!(1, report.warnings().len());
+    assert!(report.errors().is_empty());
+    assert_eq!("identity\n", read_to_string(&marker).unwrap());
+}
+
+#[cfg(all(unix, feature = "openssl"))]
+#[test]
+fn test_expiry_monitor_command_hook_kinds() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let day = Duration::from_secs(86400);
+    let (_, identity, trust_root) =
+        expiry_test_setup(&pki, day * 3, Some(day * 2));
+    let marker = pki.path("renewed");
+    // The command is run for every kind of item, and is expected to
+    // skip the ones it cannot renew.
+    let command = vec![
+        String::from("sh"),
+        String::from("-c"),
+        format!(
+            "case \"$PKI_EXPIRY_KIND\" in identity) echo renewed >> {0};; \
+             *) echo \"skipped $PKI_EXPIRY_KIND\" >> {0};; esac",
+            marker.display()
+        ),
+    ];
+    let monitor =
+        expiry_test_monitor_with(identity, trust_root, 0, command, None);
+    let report = monitor.check(SystemTime::now()).unwrap();
+    let contents = read_to_string(&marker).unwrap();
+    let mut lines: Vec<&str> = contents.lines().collect();
+
+    lines.sort();
+
+    assert_eq!(2, report.warnings().len());
+    assert!(report.errors().is_empty());
+    assert_eq!(vec!["renewed", "skipped crl"], lines);
+}
+
+#[cfg(all(unix, feature = "openssl"))]
+#[test]
+fn test_command_hook_timeout() {
+    init();
+
+    let now = SystemTime::now();
+    let crl = PKIExpiryWarning {
+        item: PKIExpiryItem::CRL(PKICRLInfo {
+            path: PathBuf::from("/etc/ssl/crl.pem"),
+            issuer: String::from("C=US, O=Constellation, CN=Test CA"),
+            last_update: now,
+            next_update: Some(now)
+        }),
+        threshold: None
+    };
+    let mut hook = PKICommandHook::with_timeout(
+        vec![String::from("sleep"), String::from("30")],
+        Duration::from_millis(200)
+    );
+    let start = Instant::now();
+
+    assert!(matches!(
+        hook.renew(&crl),
+        Err(PKIRenewalError::TimedOut { .. })
+    ));
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        Duration::from_secs(300),
+        PKICommandHook::new(vec![]).timeout()
+    );
+}
+
+#[cfg(all(unix, feature = "openssl"))]
+#[test]
+fn test_command_hook() {
+    init();
+
+    let now = SystemTime::now();
+    let crl = PKIExpiryWarning {
+        item: PKIExpiryItem::CRL(PKICRLInfo {
+            path: PathBuf::from("/etc/ssl/crl.pem"),
+            issuer: String::from("C=US, O=Constellation, CN=Test CA"),
+            last_update: now,
+            next_update: Some(now)
+        }),
+        threshold: None
+    };
+    let cert = PKIExpiryWarning {
+        item: PKIExpiryItem::RootCert(PKICertInfo {
+            path: PathBuf::from("/etc/ssl/ca.pem"),
+            subject: String::from("C=US, O=Constellation, CN=Test CA"),
+            issuer: String::from("C=US, O=Constellation, CN=Test CA"),
+            not_before: now,
+            not_after: now,
+            fingerprint: CompoundHashAlgo::default().hash_bytes(b"")
+        }),
+        threshold: Some(Duration::from_secs(86400))
+    };
+    let mut hook = PKICommandHook::new(vec![
+        String::from("sh"),
+        String::from("-c"),
+        String::from(concat!(
+            "test \"$PKI_EXPIRY_KIND\" = crl && ",
+            "test \"$PKI_EXPIRY_PATH\" = /etc/ssl/crl.pem && ",
+            "test \"$PKI_EXPIRY_NAME\" = \"C=US, O=Constellation, CN=Test CA\""
+        )),
+    ]);
+
+    hook.renew(&crl).unwrap();
+
+    assert!(matches!(
+        hook.renew(&cert),
+        Err(PKIRenewalError::Failed { .. })
+    ));
+
+    let mut hook = PKICommandHook::new(vec![String::from(
+        "/nonexistent/constellation-renew"
+    )]);
+
+    assert!(matches!(
+        hook.renew(&crl),
+        Err(PKIRenewalError::Command { .. })
+    ));
+    assert!(matches!(
+        PKICommandHook::new(vec![]).renew(&crl),
+        Err(PKIRenewalError::Hook { .. })
+    ));
+}
@@ -0,0 +1,909 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Private key providers.
+//!
+//! This module provides [PKIKey], which loads the private key of an
+//! identity either from a file ([PKIFileKey]) or from a hardware token
+//! through PKCS#11 ([PKIPKCS11Key]), along with the sources for the
+//! passphrases and PINs that protect them ([PKIPassphraseSource]).
+#[cfg(feature = "openssl")]
+use std::collections::HashMap;
+#[cfg(feature = "openssl")]
+use std::env;
+use std::env::VarError;
+#[cfg(feature = "openssl")]
+use std::ffi::CStr;
+#[cfg(feature = "openssl")]
+use std::ffi::CString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use std::fs::read;
+use std::io::Error;
+#[cfg(feature = "openssl")]
+use std::os::raw::c_char;
+#[cfg(feature = "openssl")]
+use std::os::raw::c_int;
+#[cfg(feature = "openssl")]
+use std::os::raw::c_void;
+use std::path::PathBuf;
+#[cfg(feature = "openssl")]
+use std::ptr::null;
+#[cfg(feature = "openssl")]
+use std::ptr::null_mut;
+#[cfg(feature = "openssl")]
+use std::sync::Mutex;
+#[cfg(feature = "openssl")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "openssl")]
+use foreign_types::ForeignType;
+#[cfg(feature = "openssl")]
+use log::debug;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use log::trace;
+#[cfg(feature = "openssl")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
+#[cfg(feature = "openssl")]
+use openssl::pkey::Private;
+#[cfg(feature = "openssl")]
+use openssl::provider::Provider;
+#[cfg(feature = "openssl")]
+use openssl_sys::EVP_PKEY;
+#[cfg(feature = "rustls")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "rustls")]
+use rustls::pki_types::PrivateKeyDer;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Errors that can occur while loading a private key from a
+/// [PKIKeyProvider].
+#[derive(Debug)]
+pub enum PKIKeyLoadError {
+    /// An IO error occurred reading a file.
+    IO {
+        /// Path to the file.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    },
+    /// An environment variable holding a passphrase could not be read.
+    Env {
+        /// Name of the environment variable.
+        var: String,
+        /// The error reading the variable.
+        error: VarError
+    },
+    #[cfg(feature = "openssl")]
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// A PKCS#11 URI was malformed.
+    BadURI {
+        /// The URI.
+        uri: String
+    },
+    /// No private key was found at a PKCS#11 URI.
+    NoKey {
+        /// The URI.
+        uri: String
+    },
+    #[cfg(feature = "rustls")]
+    /// The private key could not be parsed.
+    PEM {
+        /// Path to the private key.
+        path: PathBuf,
+        /// The PEM error.
+        error: rustls::pki_types::pem::Error
+    },
+    #[cfg(feature = "rustls")]
+    /// The key cannot be loaded by the rustls backend.
+    Unsupported {
+        /// Description of the key.
+        key: String
+    }
+}
+
+#[cfg(feature = "openssl")]
+/// Trait for sources of private keys.
+///
+/// This abstracts over where a private key is held, so that the same
+/// signing and TLS code can use keys held in files as well as keys
+/// held in hardware tokens.  For keys held in tokens, the resulting
+/// [PKey] is a handle that performs private-key operations on the
+/// token.
+pub trait PKIKeyProvider {
+    /// Load the private key.
+    fn load_key(&self) -> Result<PKey<Private>, PKIKeyLoadError>;
+}
+
+/// Source for a passphrase or PIN protecting a private key.
+///
+/// # YAML Format
+///
+/// The YAML format is a single-field object, with one of the
+/// following fields:
+///
+/// - `file`: Path to a file containing the passphrase.  Only the first line of
+///   the file is used.
+///
+/// - `env`: Name of an environment variable containing the passphrase.
+///
+/// ## Examples
+///
+/// ```yaml
+/// file: /etc/ssl/private/server-key.pass
+/// ```
+///
+/// ```yaml
+/// env: SERVER_KEY_PASSPHRASE
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-passphrase-source")]
+pub enum PKIPassphraseSource {
+    /// Read the passphrase from the first line of a file.
+    File(PathBuf),
+    /// Read the passphrase from an environment variable.
+    Env(String)
+}
+
+/// Configuration for a private key stored in a file.
+///
+/// The file may contain a PEM-encoded key in any format OpenSSL
+/// recognizes, or a DER-encoded PKCS#8 or traditional key.  Encrypted
+/// keys (including encrypted PKCS#8) are decrypted using the
+/// passphrase.
+///
+/// # YAML Format
+///
+/// The YAML format has the following fields:
+///
+/// - `path`: Path to the file containing the private key.
+///
+/// - `passphrase` (optional): A [PKIPassphraseSource] for the passphrase of an
+///   encrypted key.  If this is absent, the private key must not be encrypted.
+///
+/// ## Examples
+///
+/// ```yaml
+/// path: /etc/ssl/private/server-key.pem
+/// passphrase:
+///   file: /etc/ssl/private/server-key.pass
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-file-key")]
+pub struct PKIFileKey {
+    /// Path to the private key.
+    path: PathBuf,
+    /// Source for the passphrase of an encrypted key.
+    #[serde(default)]
+    passphrase: Option<PKIPassphraseSource>
+}
+
+/// Configuration for a private key held in a PKCS#11 token.
+///
+/// Keys are loaded through an OpenSSL provider that implements
+/// PKCS#11 URIs (by default, `pkcs11`, as implemented by
+/// [pkcs11-provider](https://github.com/latchset/pkcs11-provider)).
+/// The private key material never leaves the token; the resulting key
+/// refers to the token for all private-key operations.
+///
+/// The PKCS#11 module to use is configured through the provider
+/// itself.  For `pkcs11-provider`, this is done with the
+/// `pkcs11-module-path` directive in the OpenSSL configuration, or
+/// the `PKCS11_PROVIDER_MODULE` environment variable.  For example, to
+/// use SoftHSM:
+///
+/// ```sh
+/// export PKCS11_PROVIDER_MODULE=/usr/lib/softhsm/libsofthsm2.so
+/// ```
+///
+/// # YAML Format
+///
+/// The YAML format has the following fields:
+///
+/// - `uri`: The [RFC 7512](https://www.rfc-editor.org/rfc/rfc7512) PKCS#11 URI
+///   identifying the private key.
+///
+/// - `pin` (optional): A [PKIPassphraseSource] for the user PIN.  If present,
+///   this is supplied to the provider as the `pin-value` URI attribute.
+///
+/// - `provider` (optional): The name of the OpenSSL provider to load.  The
+///   default is `pkcs11`.
+///
+/// ## Examples
+///
+/// ```yaml
+/// uri: "pkcs11:token=constellation;object=server-key;type=private"
+/// pin:
+///   env: PKCS11_PIN
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-pkcs11-key")]
+pub struct PKIPKCS11Key {
+    /// The PKCS#11 URI for the key.
+    uri: String,
+    /// Source for the user PIN.
+    #[serde(default)]
+    pin: Option<PKIPassphraseSource>,
+    /// Name of the OpenSSL provider implementing PKCS#11.
+    #[serde(default = "PKIPKCS11Key::default_provider")]
+    provider: String
+}
+
+/// Configuration for the location of a private key.
+///
+/// # YAML Format
+///
+/// The YAML format is a single-field object, with one of the
+/// following fields:
+///
+/// - `file`: A [PKIFileKey] describing a key stored in a file.
+///
+/// - `pkcs11`: A [PKIPKCS11Key] describing a key held in a PKCS#11 token.
+///
+/// ## Examples
+///
+/// ```yaml
+/// file:
+///   path: /etc/ssl/private/server-key.pem
+/// ```
+///
+/// ```yaml
+/// pkcs11:
+///   uri: "pkcs11:token=constellation;object=server-key;type=private"
+///   pin:
+///     file: /etc/ssl/private/token.pin
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-key")]
+pub enum PKIKey {
+    /// A key stored in a file.
+    File(PKIFileKey),
+    /// A key held in a PKCS#11 token.
+    #[serde(rename = "pkcs11")]
+    PKCS11(PKIPKCS11Key)
+}
+
+#[cfg(feature = "openssl")]
+/// `OSSL_STORE_INFO` type for private keys.
+const OSSL_STORE_INFO_PKEY: c_int = 4;
+
+// The OpenSSL store API is not covered by the `openssl` crate.
+#[cfg(feature = "openssl")]
+extern "C" {
+    fn OSSL_STORE_open(
+        uri: *const c_char,
+        ui_method: *const c_void,
+        ui_data: *mut c_void,
+        post_process: *const c_void,
+        post_process_data: *mut c_void
+    ) -> *mut c_void;
+    fn OSSL_STORE_expect(
+        ctx: *mut c_void,
+        expected_type: c_int
+    ) -> c_int;
+    fn OSSL_STORE_load(ctx: *mut c_void) -> *mut c_void;
+    fn OSSL_STORE_eof(ctx: *mut c_void) -> c_int;
+    fn OSSL_STORE_error(ctx: *mut c_void) -> c_int;
+    fn OSSL_STORE_close(ctx: *mut c_void) -> c_int;
+    fn OSSL_STORE_INFO_get_type(info: *const c_void) -> c_int;
+    fn OSSL_STORE_INFO_get1_PKEY(info: *const c_void) -> *mut EVP_PKEY;
+    fn OSSL_STORE_INFO_free(info: *mut c_void);
+}
+
+#[cfg(feature = "openssl")]
+/// Get the OpenSSL providers loaded by [load_provider], by name.
+fn loaded_providers() -> &'static Mutex<HashMap<String, Provider>> {
+    static PROVIDERS: OnceLock<Mutex<HashMap<String, Provider>>> =
+        OnceLock::new();
+
+    PROVIDERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[cfg(feature = "openssl")]
+/// Load the OpenSSL provider `name`, if it has not been loaded
+/// already.
+///
+/// Providers remain loaded for the life of the process, as keys
+/// loaded from them depend on them.
+fn load_provider(name: &str) -> Result<(), ErrorStack> {
+    // The map is only ever added to, so it is still usable if a
+    // holder of the lock panicked.
+    let mut providers = loaded_providers()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+
+    if !providers.contains_key(name) {
+        debug!(target: "pki-key",
+               "loading OpenSSL provider {}",
+               name);
+
+        let provider = Provider::try_load(None, name, true)?;
+
+        providers.insert(name.to_string(), provider);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "openssl")]
+/// Load the first private key from the OpenSSL store at `uri`.
+fn store_load_key(uri: &CStr) -> Result<Option<PKey<Private>>, ErrorStack> {
+    // SAFETY: all pointers are checked before use, the store context
+    // is closed on every path, and each info object is freed once.
+    // `OSSL_STORE_INFO_get1_PKEY` returns a new reference, which is
+    // owned by the resulting `PKey`.
+    unsafe {
+        let ctx = OSSL_STORE_open(
+            uri.as_ptr(),
+            null(),
+            null_mut(),
+            null(),
+            null_mut()
+        );
+
+        if ctx.is_null() {
+            return Err(ErrorStack::get());
+        }
+
+        if OSSL_STORE_expect(ctx, OSSL_STORE_INFO_PKEY) <= 0 {
+            OSSL_STORE_close(ctx);
+
+            return Err(ErrorStack::get());
+        }
+
+        let mut key = None;
+
+        while key.is_none() && OSSL_STORE_eof(ctx) == 0 {
+            let info = OSSL_STORE_load(ctx);
+
+            if info.is_null() {
+                if OSSL_STORE_error(ctx) != 0 {
+                    break;
+                }
+
+                continue;
+            }
+
+            if OSSL_STORE_INFO_get_type(info) == OSSL_STORE_INFO_PKEY {
+                let pkey = OSSL_STORE_INFO_get1_PKEY(info);
+
+                if !pkey.is_null() {
+                    key = Some(PKey::from_ptr(pkey))
+                }
+            }
+
+            OSSL_STORE_INFO_free(info);
+        }
+
+        OSSL_STORE_close(ctx);
+
+        match key {
+            Some(key) => {
+                // Discard any errors from objects that were skipped.
+                ErrorStack::get();
+
+                Ok(Some(key))
+            }
+            None => {
+                let errs = ErrorStack::get();
+
+                if errs.errors().is_empty() {
+                    Ok(None)
+                } else {
+                    Err(errs)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+/// Add a `pin-value` query attribute to the PKCS#11 `uri`.
+fn pkcs11_uri_add_pin(
+    uri: &str,
+    pin: &[u8]
+) -> String {
+    let sep = if uri.contains('?') { '&' } else { '?' };
+    let mut out = format!("{}{}pin-value=", uri, sep);
+
+    // Percent-encode everything but RFC 3986 unreserved characters.
+    for byte in pin {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(byte) {
+            out.push(*byte as char)
+        } else {
+            out.push_str(&format!("%{:02X}", byte))
+        }
+    }
+
+    out
+}
+
+impl PKIPassphraseSource {
+    #[cfg(feature = "openssl")]
+    /// Load the passphrase.
+    ///
+    /// For files, only the first line is used, excluding the line
+    /// terminator.
+    pub fn load(&self) -> Result<Vec<u8>, PKIKeyLoadError> {
+        match self {
+            PKIPassphraseSource::File(path) => {
+                trace!(target: "pki-key",
+                       "loading passphrase from {}",
+                       path.display());
+
+                let contents =
+                    read(path).map_err(|err| PKIKeyLoadError::IO {
+                        path: path.clone(),
+                        error: err
+                    })?;
+                let line =
+                    contents.split(|b| *b == b'\n').next().unwrap_or(&[]);
+
+                Ok(line.strip_suffix(b"\r").unwrap_or(line).to_vec())
+            }
+            PKIPassphraseSource::Env(var) => {
+                trace!(target: "pki-key",
+                       "loading passphrase from ${}",
+                       var);
+
+                env::var(var).map(String::into_bytes).map_err(|err| {
+                    PKIKeyLoadError::Env {
+                        var: var.clone(),
+                        error: err
+                    }
+                })
+            }
+        }
+    }
+}
+
+impl PKIFileKey {
+    /// Create a new `PKIFileKey` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        path: PathBuf,
+        passphrase: Option<PKIPassphraseSource>
+    ) -> Self {
+        PKIFileKey {
+            path: path,
+            passphrase: passphrase
+        }
+    }
+
+    /// Get the path to the private key.
+    #[inline]
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Get the source for the passphrase, if there is one.
+    #[inline]
+    pub fn passphrase(&self) -> Option<&PKIPassphraseSource> {
+        self.passphrase.as_ref()
+    }
+
+    #[cfg(feature = "rustls")]
+    /// Load the private key for use with rustls.
+    ///
+    /// PEM-encoded PKCS#8, PKCS#1, and SEC1 keys are supported, as
+    /// are DER-encoded PKCS#8 keys.  Encrypted keys are not
+    /// supported, and will fail to load if a passphrase is given.
+    pub fn load_rustls_key(
+        &self
+    ) -> Result<PrivateKeyDer<'static>, PKIKeyLoadError> {
+        trace!(target: "pki-key",
+               "loading private key for rustls from {}",
+               self.path.display());
+
+        if self.passphrase.is_some() {
+            return Err(PKIKeyLoadError::Unsupported {
+                key: String::from("encrypted private keys")
+            });
+        }
+
+        let data = read(&self.path).map_err(|err| PKIKeyLoadError::IO {
+            path: self.path.clone(),
+            error: err
+        })?;
+
+        if data.windows(11).any(|window| window == b"-----BEGIN ") {
+            PrivateKeyDer::from_pem_slice(&data).map_err(|err| {
+                PKIKeyLoadError::PEM {
+                    path: self.path.clone(),
+                    error: err
+                }
+            })
+        } else {
+            Ok(PrivateKeyDer::Pkcs8(data.into()))
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIKeyProvider for PKIFileKey {
+    /// Load the private key.
+    ///
+    /// If a passphrase is configured, it will be used to decrypt the
+    /// key.  Otherwise, encrypted keys will fail to load (rather than
+    /// prompting for a password).
+    fn load_key(&self) -> Result<PKey<Private>, PKIKeyLoadError> {
+        trace!(target: "pki-key",
+               "loading private key from {}",
+               self.path.display());
+
+        let data = read(&self.path).map_err(|err| PKIKeyLoadError::IO {
+            path: self.path.clone(),
+            error: err
+        })?;
+        let passphrase = match &self.passphrase {
+            Some(passphrase) => Some(passphrase.load()?),
+            None => None
+        };
+        let pem = data.windows(11).any(|window| window == b"-----BEGIN ");
+
+        match (pem, passphrase) {
+            (true, Some(passphrase)) => {
+                PKey::private_key_from_pem_passphrase(&data, &passphrase)
+            }
+            (true, None) => {
+                PKey::private_key_from_pem_callback(&data, |_| Ok(0))
+            }
+            (false, Some(passphrase)) => {
+                PKey::private_key_from_pkcs8_passphrase(&data, &passphrase)
+            }
+            (false, None) => PKey::private_key_from_der(&data)
+        }
+        .map_err(|err| PKIKeyLoadError::OpenSSL { error: err })
+    }
+}
+
+impl PKIPKCS11Key {
+    /// Create a new `PKIPKCS11Key` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        uri: String,
+        pin: Option<PKIPassphraseSource>,
+        provider: Option<String>
+    ) -> Self {
+        PKIPKCS11Key {
+            uri: uri,
+            pin: pin,
+            provider: provider.unwrap_or_else(PKIPKCS11Key::default_provider)
+        }
+    }
+
+    #[inline]
+    fn default_provider() -> String {
+        String::from("pkcs11")
+    }
+
+    /// Get the PKCS#11 URI for the key.
+    #[inline]
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Get the source for the user PIN, if there is one.
+    #[inline]
+    pub fn pin(&self) -> Option<&PKIPassphraseSource> {
+        self.pin.as_ref()
+    }
+
+    /// Get the name of the OpenSSL provider implementing PKCS#11.
+    #[inline]
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIKeyProvider for PKIPKCS11Key {
+    /// Load the private key.
+    ///
+    /// The configured OpenSSL provider is loaded the first time it
+    /// is needed, and then remains loaded for the life of the process.
+    fn load_key(&self) -> Result<PKey<Private>, PKIKeyLoadError> {
+        trace!(target: "pki-key",
+               "loading private key from {}",
+               self.uri);
+
+        if !self.uri.starts_with("pkcs11:") {
+            return Err(PKIKeyLoadError::BadURI {
+                uri: self.uri.clone()
+            });
+        }
+
+        load_provider(&self.provider)
+            .map_err(|err| PKIKeyLoadError::OpenSSL { error: err })?;
+
+        let uri = match &self.pin {
+            Some(pin) => pkcs11_uri_add_pin(&self.uri, &pin.load()?),
+            None => self.uri.clone()
+        };
+        let uri = CString::new(uri).map_err(|_| PKIKeyLoadError::BadURI {
+            uri: self.uri.clone()
+        })?;
+
+        store_load_key(&uri)
+            .map_err(|err| PKIKeyLoadError::OpenSSL { error: err })?
+            .ok_or_else(|| PKIKeyLoadError::NoKey {
+                uri: self.uri.clone()
+            })
+    }
+}
+
+impl PKIKey {
+    #[cfg(feature = "rustls")]
+    /// Load the private key for use with rustls.
+    ///
+    /// Only unencrypted keys held in files can be loaded; see
+    /// [PKIFileKey::load_rustls_key].
+    pub fn load_rustls_key(
+        &self
+    ) -> Result<PrivateKeyDer<'static>, PKIKeyLoadError> {
+        match self {
+            PKIKey::File(key) => key.load_rustls_key(),
+            PKIKey::PKCS11(_) => Err(PKIKeyLoadError::Unsupported {
+                key: String::from("PKCS#11 keys")
+            })
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl PKIKeyProvider for PKIKey {
+    #[inline]
+    fn load_key(&self) -> Result<PKey<Private>, PKIKeyLoadError> {
+        match self {
+            PKIKey::File(key) => key.load_key(),
+            PKIKey::PKCS11(key) => key.load_key()
+        }
+    }
+}
+
+impl ScopedError for PKIKeyLoadError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PKIKeyLoadError::IO { error, .. } => error.scope(),
+            PKIKeyLoadError::Env { .. } => ErrorScope::System,
+            #[cfg(feature = "openssl")]
+            PKIKeyLoadError::OpenSSL { .. } => ErrorScope::System,
+            PKIKeyLoadError::BadURI { .. } => ErrorScope::System,
+            PKIKeyLoadError::NoKey { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKIKeyLoadError::PEM { .. } => ErrorScope::System,
+            #[cfg(feature = "rustls")]
+            PKIKeyLoadError::Unsupported { .. } => ErrorScope::System
+        }
+    }
+}
+
+impl Display for PKIKeyLoadError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PKIKeyLoadError::IO { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            PKIKeyLoadError::Env { var, error } => {
+                write!(f, "error reading ${}: {}", var, error)
+            }
+            #[cfg(feature = "openssl")]
+            PKIKeyLoadError::OpenSSL { error } => error.fmt(f),
+            PKIKeyLoadError::BadURI { uri } => {
+                write!(f, "malformed PKCS#11 URI {}", uri)
+            }
+            PKIKeyLoadError::NoKey { uri } => {
+                write!(f, "no private key found at {}", uri)
+            }
+            #[cfg(feature = "rustls")]
+            PKIKeyLoadError::PEM { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            #[cfg(feature = "rustls")]
+            PKIKeyLoadError::Unsupported { key } => {
+                write!(f, "{} are not supported with rustls", key)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+use std::fs::write;
+
+#[cfg(all(test, feature = "openssl"))]
+use openssl::hash::MessageDigest;
+#[cfg(all(test, feature = "openssl"))]
+use openssl::symm::Cipher;
+
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
+#[cfg(all(test, feature = "openssl"))]
+use crate::init;
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_load_file_key_der() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let key = pki.server_identity().load_key().unwrap();
+    let der_path = pki.path("key.der");
+    let encrypted_path = pki.path("key_encrypted.der");
+    let var = "CONSTELLATION_TEST_LOAD_FILE_KEY_DER";
+    let encrypted = key
+        .private_key_to_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secret")
+        .unwrap();
+
+    write(&der_path, key.private_key_to_pkcs8().unwrap()).unwrap();
+    write(&encrypted_path, encrypted).unwrap();
+    std::env::set_var(var, "secret");
+
+    let loaded = PKIFileKey::new(der_path, None).load_key().unwrap();
+
+    assert!(loaded.public_eq(&key));
+
+    let passphrase = PKIPassphraseSource::Env(String::from(var));
+    let loaded = PKIFileKey::new(encrypted_path.clone(), Some(passphrase))
+        .load_key()
+        .unwrap();
+
+    assert!(loaded.public_eq(&key));
+
+    let passphrase = PKIPassphraseSource::Env(String::from(
+        "CONSTELLATION_TEST_LOAD_FILE_KEY_DER_UNSET"
+    ));
+
+    assert!(matches!(
+        PKIFileKey::new(encrypted_path, Some(passphrase)).load_key(),
+        Err(PKIKeyLoadError::Env { .. })
+    ));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_pkcs11_uri_add_pin() {
+    assert_eq!(
+        "pkcs11:object=key?pin-value=1234",
+        pkcs11_uri_add_pin("pkcs11:object=key", b"1234")
+    );
+    assert_eq!(
+        "pkcs11:object=key?module-name=softhsm2&pin-value=a%20b%26c%25",
+        pkcs11_uri_add_pin("pkcs11:object=key?module-name=softhsm2", b"a b&c%")
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_store_load_key() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let key = pki.server_identity().load_key().unwrap();
+    let path = pki.path("server/private/test_server_key.pem");
+    let uri = CString::new(format!("file:{}", path.display())).unwrap();
+    let loaded = store_load_key(&uri).unwrap().expect("Expected key");
+
+    assert!(loaded.public_eq(&key));
+
+    // Certificates are not keys.
+    let path = pki.path("server/ca_cert.pem");
+    let uri = CString::new(format!("file:{}", path.display())).unwrap();
+
+    assert!(store_load_key(&uri).unwrap().is_none());
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_load_provider() {
+    init();
+
+    load_provider("default").unwrap();
+
+    let provider = loaded_providers().lock().unwrap()["default"].as_ptr();
+
+    // Loading again reuses the same provider.
+    load_provider("default").unwrap();
+
+    assert_eq!(
+        provider,
+        loaded_providers().lock().unwrap()["default"].as_ptr()
+    );
+    assert!(load_provider("no-such-provider").is_err());
+    assert!(!loaded_providers()
+        .lock()
+        .unwrap()
+        .contains_key("no-such-provider"));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_pkcs11_bad_uri() {
+    init();
+
+    let key = PKIPKCS11Key::new(String::from("file:/etc/key.pem"), None, None);
+
+    assert!(matches!(
+        key.load_key(),
+        Err(PKIKeyLoadError::BadURI { .. })
+    ));
+}
+
+/// Test loading and signing with a key held in a PKCS#11 token.
+///
+/// This requires `pkcs11-provider` and a token holding a key, which
+/// can be set up with SoftHSM:
+///
+/// ```sh
+/// softhsm2-util --init-token --free --label test --pin 1234 --so-pin 1234
+/// pkcs11-tool --module /usr/lib/softhsm/libsofthsm2.so --login \
+///     --pin 1234 --token-label test --keypairgen --key-type EC:secp384r1 \
+///     --label test-key
+/// export PKCS11_PROVIDER_MODULE=/usr/lib/softhsm/libsofthsm2.so
+/// export PKCS11_TEST_URI="pkcs11:token=test;object=test-key;type=private"
+/// export PKCS11_TEST_PIN=1234
+/// cargo test -- --ignored test_pkcs11_softhsm
+/// ```
+#[cfg(feature = "openssl")]
+#[test]
+#[ignore]
+fn test_pkcs11_softhsm() {
+    init();
+
+    let uri = std::env::var("PKCS11_TEST_URI").unwrap();
+    let pin = PKIPassphraseSource::Env(String::from("PKCS11_TEST_PIN"));
+    let key = PKIPKCS11Key::new(uri, Some(pin), None).load_key().unwrap();
+    let mut signer =
+        openssl::sign::Signer::new(MessageDigest::sha384(), &key).unwrap();
+    let signature = signer.sign_oneshot_to_vec(b"hello").unwrap();
+    let mut verifier =
+        openssl::sign::Verifier::new(MessageDigest::sha384(), &key).unwrap();
+
+    assert!(verifier.verify_oneshot(&signature, b"hello").unwrap());
+}
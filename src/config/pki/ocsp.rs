@@ -0,0 +1,1414 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! OCSP revocation checking.
+//!
+//! This module provides [PKIOCSPConfig], which checks the revocation
+//! status of certificates using OCSP responses, whether loaded from
+//! files, queried from a responder, or stapled to a TLS handshake.
+#[cfg(feature = "openssl")]
+use std::fmt::Display;
+#[cfg(feature = "openssl")]
+use std::fmt::Formatter;
+#[cfg(feature = "openssl")]
+use std::fs::read;
+#[cfg(feature = "openssl")]
+use std::io::Error;
+#[cfg(feature = "openssl")]
+use std::io::ErrorKind;
+#[cfg(feature = "openssl")]
+use std::io::Read;
+#[cfg(feature = "openssl")]
+use std::io::Write;
+#[cfg(feature = "openssl")]
+use std::net::TcpStream;
+#[cfg(feature = "openssl")]
+use std::net::ToSocketAddrs;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use std::path::Path;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use std::path::PathBuf;
+#[cfg(feature = "openssl")]
+use std::time::Duration;
+#[cfg(feature = "openssl")]
+use std::time::Instant;
+
+#[cfg(feature = "openssl")]
+use log::debug;
+#[cfg(feature = "openssl")]
+use log::trace;
+#[cfg(feature = "openssl")]
+use log::warn;
+#[cfg(feature = "openssl")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "openssl")]
+use openssl::hash::MessageDigest;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspCertId;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspCertStatus;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspFlag;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspRequest;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspResponse;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspResponseStatus;
+#[cfg(feature = "openssl")]
+use openssl::ocsp::OcspRevokedStatus;
+#[cfg(feature = "openssl")]
+use openssl::rand::rand_bytes;
+#[cfg(feature = "openssl")]
+use openssl::ssl::SslRef;
+#[cfg(feature = "openssl")]
+use openssl::stack::Stack;
+#[cfg(feature = "openssl")]
+use openssl::x509::store::X509StoreRef;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509Ref;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509VerifyResult;
+#[cfg(feature = "openssl")]
+use openssl::x509::X509;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use serde::Deserialize;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use serde::Serialize;
+
+#[cfg(feature = "openssl")]
+use crate::codec::der::encode_tlv;
+#[cfg(feature = "openssl")]
+use crate::codec::der::split_tlv;
+#[cfg(feature = "openssl")]
+use crate::config::pki::reload::identity_index;
+#[cfg(feature = "openssl")]
+use crate::config::pki::reload::ocsp_store_index;
+#[cfg(feature = "openssl")]
+use crate::error::ErrorScope;
+#[cfg(feature = "openssl")]
+use crate::error::ScopedError;
+
+#[cfg(feature = "openssl")]
+/// Errors that can occur while checking certificate status with
+/// OCSP.
+#[derive(Debug)]
+pub enum OCSPError {
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// Error reading a pre-fetched OCSP response file.
+    IO {
+        /// Path to the response file.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    },
+    /// No responder URL was configured, and the certificate does not
+    /// name one.
+    NoResponder,
+    /// The responder URL could not be used.
+    BadURL {
+        /// The responder URL.
+        url: String
+    },
+    /// Error communicating with the OCSP responder.
+    Fetch {
+        /// The responder URL.
+        url: String,
+        /// The IO error.
+        error: Error
+    },
+    /// The OCSP responder returned an HTTP error.
+    HTTPStatus {
+        /// The responder URL.
+        url: String,
+        /// The HTTP status line.
+        status: String
+    },
+    /// The OCSP response indicated an error.
+    ResponseStatus {
+        /// The response status.
+        status: OcspResponseStatus
+    },
+    /// The OCSP response signature could not be verified.
+    BadSignature {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The OCSP response did not contain the expected nonce.
+    BadNonce,
+    /// No OCSP response covers the certificate.
+    NoStatus,
+    /// The OCSP response is not fresh.
+    Stale {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The certificate has been revoked.
+    Revoked {
+        /// The revocation reason.
+        reason: OcspRevokedStatus
+    },
+    /// The OCSP responder does not know the certificate.
+    Unknown
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+/// Configuration for checking certificate revocation with OCSP.
+///
+/// OCSP responses can be obtained in three ways: stapled to a TLS
+/// handshake by the peer, from pre-fetched response files, or by
+/// querying an OCSP responder directly.  Responses are only accepted
+/// if they are signed by a CA in the trust store (or a responder
+/// certificate issued by one).
+///
+/// # YAML Format
+///
+/// The YAML format has seven fields, all of which are optional:
+///
+/// - `responder-url`: URL of the OCSP responder to query.  This overrides any
+///   responder named in the certificate.  Only `http` URLs are supported.
+///
+/// - `nonce`: Whether to include a nonce in OCSP queries, and require it to be
+///   echoed in the response.  Defaults to `true`.
+///
+/// - `freshness-tolerance`: Tolerance, in seconds, for clock skew when checking
+///   the validity period of responses.  Defaults to 300.
+///
+/// - `max-age`: Maximum age, in seconds, of responses that have no next update
+///   time.  If absent, the age is not checked.
+///
+/// - `responses`: A list of paths to files containing pre-fetched DER-encoded
+///   OCSP responses.  These are used to check certificates locally, and are
+///   stapled to TLS handshakes when acting as a server.  They are read again
+///   for each handshake, so they can be refreshed in place.
+///
+/// - `issuer`: Path to a PEM file with the certificate that issued our own
+///   certificate.  This is needed to find a response to staple if the chain in
+///   our identity does not include the issuer.  The trust store is never used
+///   for this, as it holds the issuers of peer certificates.
+///
+/// - `require-stapled`: Whether TLS clients will require the server to staple
+///   an OCSP response.  Defaults to `false`.
+///
+/// ## Examples
+///
+/// ```yaml
+/// responder-url: http://ocsp.example.com/
+/// nonce: true
+/// freshness-tolerance: 300
+/// max-age: 86400
+/// responses:
+///   - /etc/ssl/ocsp/server-cert.der
+/// issuer: /etc/ssl/certs/server-ca-cert.pem
+/// require-stapled: true
+/// ```
+#[derive(
+    Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "pki-ocsp")]
+pub struct PKIOCSPConfig {
+    /// URL of the OCSP responder, overriding the certificate.
+    #[serde(default)]
+    responder_url: Option<String>,
+    /// Whether to use a nonce in OCSP queries.
+    #[serde(default = "PKIOCSPConfig::default_nonce")]
+    nonce: bool,
+    /// Tolerance in seconds for response validity times.
+    #[serde(default = "PKIOCSPConfig::default_freshness_tolerance")]
+    freshness_tolerance: u32,
+    /// Maximum age in seconds for responses.
+    #[serde(default)]
+    max_age: Option<u32>,
+    /// Paths to pre-fetched DER-encoded OCSP responses.
+    #[serde(default)]
+    responses: Vec<PathBuf>,
+    /// Path to the issuer of our own certificate.
+    #[serde(default)]
+    issuer: Option<PathBuf>,
+    /// Whether to require stapled OCSP responses.
+    #[serde(default)]
+    require_stapled: bool
+}
+
+#[cfg(feature = "openssl")]
+/// Length of OCSP request nonces.
+const OCSP_NONCE_LEN: usize = 16;
+
+#[cfg(feature = "openssl")]
+/// Timeout for an entire exchange with an OCSP responder.
+const OCSP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "openssl")]
+/// Maximum size of an HTTP response from an OCSP responder, including
+/// the headers.
+const OCSP_MAX_RESPONSE: u64 = 64 * 1024;
+
+#[cfg(feature = "openssl")]
+/// DER encoding of the OCSP nonce extension OID (1.3.6.1.5.5.7.48.1.2).
+const OCSP_NONCE_OID: [u8; 11] = [
+    0x06, 0x09, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x02
+];
+
+#[cfg(feature = "openssl")]
+/// Add a nonce extension to the DER-encoded OCSP `request`.
+///
+/// The request must not already have extensions.
+fn ocsp_request_add_nonce(
+    request: &[u8],
+    nonce: &[u8]
+) -> Option<Vec<u8>> {
+    // OCSPRequest ::= SEQUENCE { tbsRequest TBSRequest, ... }
+    let (0x30, outer, []) = split_tlv(request).ok()? else {
+        return None;
+    };
+    let (0x30, tbs, rest) = split_tlv(outer).ok()? else {
+        return None;
+    };
+    let value = encode_tlv(0x04, &encode_tlv(0x04, nonce));
+    let ext = encode_tlv(0x30, &[&OCSP_NONCE_OID[..], &value].concat());
+    // requestExtensions [2] EXPLICIT Extensions
+    let exts = encode_tlv(0xa2, &encode_tlv(0x30, &ext));
+    let tbs = encode_tlv(0x30, &[tbs, &exts].concat());
+
+    Some(encode_tlv(0x30, &[&tbs, rest].concat()))
+}
+
+#[cfg(feature = "openssl")]
+/// Get the nonce from the DER-encoded OCSP `response`, if it has one.
+fn ocsp_response_nonce(response: &[u8]) -> Option<&[u8]> {
+    // OCSPResponse ::= SEQUENCE { status, [0] EXPLICIT ResponseBytes }
+    let (0x30, outer, _) = split_tlv(response).ok()? else {
+        return None;
+    };
+    let (0x0a, _, outer) = split_tlv(outer).ok()? else {
+        return None;
+    };
+    let (0xa0, bytes, _) = split_tlv(outer).ok()? else {
+        return None;
+    };
+    // ResponseBytes ::= SEQUENCE { responseType, response OCTET STRING }
+    let (0x30, bytes, _) = split_tlv(bytes).ok()? else {
+        return None;
+    };
+    let (0x06, _, bytes) = split_tlv(bytes).ok()? else {
+        return None;
+    };
+    let (0x04, basic, _) = split_tlv(bytes).ok()? else {
+        return None;
+    };
+    // BasicOCSPResponse ::= SEQUENCE { tbsResponseData, ... }
+    let (0x30, basic, _) = split_tlv(basic).ok()? else {
+        return None;
+    };
+    let (0x30, mut data, _) = split_tlv(basic).ok()? else {
+        return None;
+    };
+
+    // Find responseExtensions [1] EXPLICIT Extensions, which follows
+    // producedAt.  The responder ID may also be tagged [1].
+    let mut produced = false;
+
+    while !data.is_empty() {
+        let (tag, content, rest) = split_tlv(data).ok()?;
+
+        if tag == 0x18 {
+            produced = true;
+        } else if produced && tag == 0xa1 {
+            let (0x30, mut exts, _) = split_tlv(content).ok()? else {
+                return None;
+            };
+
+            while !exts.is_empty() {
+                let (_, ext, rest) = split_tlv(exts).ok()?;
+
+                if let Some(value) = ext.strip_prefix(&OCSP_NONCE_OID[..]) {
+                    // Skip the critical flag, if present.
+                    let value = match split_tlv(value).ok()? {
+                        (0x01, _, rest) => rest,
+                        _ => value
+                    };
+                    let (0x04, value, _) = split_tlv(value).ok()? else {
+                        return None;
+                    };
+
+                    // The nonce is usually wrapped in an OCTET STRING.
+                    return match split_tlv(value) {
+                        Ok((0x04, nonce, [])) => Some(nonce),
+                        _ => Some(value)
+                    };
+                }
+
+                exts = rest;
+            }
+
+            return None;
+        }
+
+        data = rest;
+    }
+
+    None
+}
+
+#[cfg(feature = "openssl")]
+/// Send the DER-encoded OCSP `request` to the responder at `url`
+/// using HTTP POST, and return the response body.
+fn ocsp_http_post(
+    url: &str,
+    request: &[u8]
+) -> Result<Vec<u8>, OCSPError> {
+    let bad_url = || OCSPError::BadURL {
+        url: url.to_string()
+    };
+    let fetch_err = |err| OCSPError::Fetch {
+        url: url.to_string(),
+        error: err
+    };
+    let rest = url.strip_prefix("http://").ok_or_else(bad_url)?;
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/")
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            (host, port.parse::<u16>().map_err(|_| bad_url())?)
+        }
+        None => (authority, 80)
+    };
+    let deadline = Instant::now() + OCSP_TIMEOUT;
+    let remaining = || {
+        deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| fetch_err(Error::from(ErrorKind::TimedOut)))
+    };
+    let mut last_err = Error::from(ErrorKind::AddrNotAvailable);
+    let mut stream = None;
+
+    for addr in (host, port).to_socket_addrs().map_err(fetch_err)? {
+        match TcpStream::connect_timeout(&addr, remaining()?) {
+            Ok(conn) => {
+                stream = Some(conn);
+
+                break;
+            }
+            Err(err) => last_err = err
+        }
+    }
+
+    let mut stream = stream.ok_or_else(|| fetch_err(last_err))?;
+    let header = format!(
+        concat!(
+            "POST {} HTTP/1.0\r\n",
+            "Host: {}\r\n",
+            "Content-Type: application/ocsp-request\r\n",
+            "Content-Length: {}\r\n",
+            "\r\n"
+        ),
+        path,
+        authority,
+        request.len()
+    );
+
+    stream
+        .set_write_timeout(Some(remaining()?))
+        .map_err(fetch_err)?;
+    stream.write_all(header.as_bytes()).map_err(fetch_err)?;
+    stream
+        .set_write_timeout(Some(remaining()?))
+        .map_err(fetch_err)?;
+    stream.write_all(request).map_err(fetch_err)?;
+
+    // Read one byte past the limit, to detect oversized responses.
+    let mut reader = (&stream).take(OCSP_MAX_RESPONSE + 1);
+    let mut response = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        stream
+            .set_read_timeout(Some(remaining()?))
+            .map_err(fetch_err)?;
+
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => response.extend_from_slice(&buf[..len]),
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(fetch_err(err))
+        }
+    }
+
+    if response.len() as u64 > OCSP_MAX_RESPONSE {
+        return Err(fetch_err(Error::new(
+            ErrorKind::InvalidData,
+            "HTTP response too large"
+        )));
+    }
+
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| {
+            fetch_err(Error::new(
+                ErrorKind::InvalidData,
+                "malformed HTTP response"
+            ))
+        })?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head.lines().next().unwrap_or_default();
+
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(OCSPError::HTTPStatus {
+            url: url.to_string(),
+            status: status.to_string()
+        });
+    }
+
+    Ok(response[split + 4..].to_vec())
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+impl PKIOCSPConfig {
+    #[inline]
+    fn default_nonce() -> bool {
+        true
+    }
+
+    #[inline]
+    fn default_freshness_tolerance() -> u32 {
+        300
+    }
+
+    /// Create a new `PKIOCSPConfig` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        responder_url: Option<String>,
+        nonce: bool,
+        freshness_tolerance: u32,
+        max_age: Option<u32>,
+        responses: Vec<PathBuf>,
+        issuer: Option<PathBuf>,
+        require_stapled: bool
+    ) -> Self {
+        PKIOCSPConfig {
+            responder_url: responder_url,
+            nonce: nonce,
+            freshness_tolerance: freshness_tolerance,
+            max_age: max_age,
+            responses: responses,
+            issuer: issuer,
+            require_stapled: require_stapled
+        }
+    }
+
+    /// Get the responder URL override.
+    #[inline]
+    pub fn responder_url(&self) -> Option<&str> {
+        self.responder_url.as_deref()
+    }
+
+    /// Get whether to use a nonce in OCSP queries.
+    #[inline]
+    pub fn nonce(&self) -> bool {
+        self.nonce
+    }
+
+    /// Get the tolerance in seconds for response validity times.
+    #[inline]
+    pub fn freshness_tolerance(&self) -> u32 {
+        self.freshness_tolerance
+    }
+
+    /// Get the maximum age in seconds for responses.
+    #[inline]
+    pub fn max_age(&self) -> Option<u32> {
+        self.max_age
+    }
+
+    /// Get the paths to pre-fetched OCSP responses.
+    #[inline]
+    pub fn responses(&self) -> &[PathBuf] {
+        &self.responses
+    }
+
+    /// Get the path to the issuer of our own certificate.
+    #[inline]
+    pub fn issuer(&self) -> Option<&Path> {
+        self.issuer.as_deref()
+    }
+
+    /// Get whether stapled OCSP responses are required.
+    #[inline]
+    pub fn require_stapled(&self) -> bool {
+        self.require_stapled
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Find the issuer of our own certificate `cert`, in `chain` or
+    /// else in the `issuer` file.
+    pub(crate) fn find_issuer(
+        &self,
+        cert: &X509Ref,
+        chain: &[X509]
+    ) -> Option<X509> {
+        let extra = match &self.issuer {
+            Some(path) => match read(path)
+                .map_err(|err| err.to_string())
+                .and_then(|pem| {
+                    X509::stack_from_pem(&pem).map_err(|err| err.to_string())
+                }) {
+                Ok(certs) => certs,
+                Err(err) => {
+                    warn!(target: "pki-ocsp",
+                          "could not load OCSP issuer from {}: {}",
+                          path.to_string_lossy(), err);
+
+                    vec![]
+                }
+            },
+            None => vec![]
+        };
+
+        chain.iter().cloned().chain(extra).find(|issuer| {
+            issuer.issued(cert) == X509VerifyResult::OK &&
+                issuer
+                    .public_key()
+                    .and_then(|key| cert.verify(&key))
+                    .unwrap_or(false)
+        })
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check the status of `cert`, issued by `issuer`, in the
+    /// DER-encoded OCSP `response`.
+    ///
+    /// The response must be signed by a CA in `store`, or by a
+    /// responder certificate issued by one, and must be fresh
+    /// according to the configured tolerances.  If `nonce` is given,
+    /// the response must contain the same nonce.
+    ///
+    /// The store should be loaded with the
+    /// [OCSP_HELPER](openssl::x509::X509PurposeId::OCSP_HELPER) purpose, as
+    /// responder certificates will not satisfy the TLS purposes.
+    ///
+    /// This succeeds only if the certificate's status is good.
+    pub fn check_response(
+        &self,
+        store: &X509StoreRef,
+        cert: &X509Ref,
+        issuer: &X509Ref,
+        response: &[u8],
+        nonce: Option<&[u8]>
+    ) -> Result<(), OCSPError> {
+        let response = OcspResponse::from_der(response)
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+        if response.status() != OcspResponseStatus::SUCCESSFUL {
+            return Err(OCSPError::ResponseStatus {
+                status: response.status()
+            });
+        }
+
+        let basic = response
+            .basic()
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+        let certs =
+            Stack::new().map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+        basic
+            .verify(&certs, store, OcspFlag::empty())
+            .map_err(|err| OCSPError::BadSignature { error: err })?;
+
+        if let Some(nonce) = nonce {
+            let der = response
+                .to_der()
+                .map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+            if ocsp_response_nonce(&der) != Some(nonce) {
+                return Err(OCSPError::BadNonce);
+            }
+        }
+
+        let id = OcspCertId::from_cert(MessageDigest::sha1(), cert, issuer)
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+        let status = basic.find_status(&id).ok_or(OCSPError::NoStatus)?;
+
+        status
+            .check_validity(self.freshness_tolerance, self.max_age)
+            .map_err(|err| OCSPError::Stale { error: err })?;
+
+        if status.status == OcspCertStatus::GOOD {
+            Ok(())
+        } else if status.status == OcspCertStatus::REVOKED {
+            Err(OCSPError::Revoked {
+                reason: status.reason
+            })
+        } else {
+            Err(OCSPError::Unknown)
+        }
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Check the status of `cert`, issued by `issuer`, against the
+    /// pre-fetched OCSP responses.
+    ///
+    /// The first response that covers `cert` determines the result.
+    /// Nonces are not checked.  See
+    /// [check_response](PKIOCSPConfig::check_response) for details.
+    pub fn check_files(
+        &self,
+        store: &X509StoreRef,
+        cert: &X509Ref,
+        issuer: &X509Ref
+    ) -> Result<(), OCSPError> {
+        for path in &self.responses {
+            trace!(target: "pki-ocsp",
+                   "checking OCSP response file {}",
+                   path.to_string_lossy());
+
+            let response = read(path).map_err(|err| OCSPError::IO {
+                path: path.clone(),
+                error: err
+            })?;
+
+            match self.check_response(store, cert, issuer, &response, None) {
+                Err(OCSPError::NoStatus) => {}
+                result => return result
+            }
+        }
+
+        Err(OCSPError::NoStatus)
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Find a pre-fetched OCSP response that covers `cert`, issued
+    /// by `issuer`.
+    ///
+    /// This does not check the response.
+    pub fn find_response(
+        &self,
+        cert: &X509Ref,
+        issuer: &X509Ref
+    ) -> Result<Option<Vec<u8>>, OCSPError> {
+        let id = OcspCertId::from_cert(MessageDigest::sha1(), cert, issuer)
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+        for path in &self.responses {
+            let response = read(path).map_err(|err| OCSPError::IO {
+                path: path.clone(),
+                error: err
+            })?;
+            let covers = OcspResponse::from_der(&response)
+                .and_then(|parsed| parsed.basic())
+                .map(|basic| basic.find_status(&id).is_some())
+                .unwrap_or(false);
+
+            if covers {
+                return Ok(Some(response));
+            }
+        }
+
+        Ok(None)
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Query an OCSP responder for the status of `cert`, issued by
+    /// `issuer`.
+    ///
+    /// The responder URL is taken from the configuration if present,
+    /// or else from `cert`.  A nonce is included if configured.  See
+    /// [check_response](PKIOCSPConfig::check_response) for details.
+    pub fn query(
+        &self,
+        store: &X509StoreRef,
+        cert: &X509Ref,
+        issuer: &X509Ref
+    ) -> Result<(), OCSPError> {
+        let url = match &self.responder_url {
+            Some(url) => url.clone(),
+            None => cert
+                .ocsp_responders()
+                .ok()
+                .and_then(|urls| urls.iter().next().map(|url| url.to_string()))
+                .ok_or(OCSPError::NoResponder)?
+        };
+        let id = OcspCertId::from_cert(MessageDigest::sha1(), cert, issuer)
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+        let mut request = OcspRequest::new()
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+        request
+            .add_id(id)
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+
+        let mut request = request
+            .to_der()
+            .map_err(|err| OCSPError::OpenSSL { error: err })?;
+        let nonce = if self.nonce {
+            let mut nonce = vec![0; OCSP_NONCE_LEN];
+
+            rand_bytes(&mut nonce)
+                .map_err(|err| OCSPError::OpenSSL { error: err })?;
+            request = ocsp_request_add_nonce(&request, &nonce)
+                .ok_or(OCSPError::BadNonce)?;
+
+            Some(nonce)
+        } else {
+            None
+        };
+
+        debug!(target: "pki-ocsp",
+               "querying OCSP responder at {}",
+               url);
+
+        let response = ocsp_http_post(&url, &request)?;
+
+        self.check_response(store, cert, issuer, &response, nonce.as_deref())
+    }
+
+    #[cfg(feature = "openssl")]
+    /// Status callback for TLS contexts.
+    ///
+    /// On the server side, this staples a pre-fetched response for
+    /// the certificate being presented.  This is the one set by
+    /// [ReloadableIdentity::set_identity] if there is one, and
+    /// otherwise the first in `chain`.  The response files are read
+    /// on each handshake, so that refreshed responses are picked up.
+    ///
+    /// On the client side, this checks the stapled response from the
+    /// server against the verified chain.  The response is verified
+    /// against the store set by
+    /// [ReloadableTrustStore::set_verify_store] if there is one, and
+    /// otherwise `ocsp_store`.
+    pub(crate) fn status_callback(
+        &self,
+        ssl: &mut SslRef,
+        ocsp_store: &X509StoreRef,
+        chain: &[X509]
+    ) -> Result<bool, ErrorStack> {
+        if ssl.is_server() {
+            let current = ssl.ex_data(identity_index()?).cloned();
+            let chain = match &current {
+                Some(current) => &current.1,
+                None => chain
+            };
+            let cert = match chain.first() {
+                Some(cert) => cert,
+                None => return Ok(false)
+            };
+            let staple = match self.find_issuer(cert, &chain[1..]) {
+                Some(issuer) => match self.find_response(cert, &issuer) {
+                    Ok(staple) => staple,
+                    Err(err) => {
+                        warn!(target: "pki-ocsp",
+                              "could not load OCSP response to staple: {}",
+                              err);
+
+                        None
+                    }
+                },
+                None => None
+            };
+
+            match staple {
+                Some(staple) => {
+                    ssl.set_ocsp_status(&staple)?;
+
+                    Ok(true)
+                }
+                None => Ok(false)
+            }
+        } else {
+            let store = ssl.ex_data(ocsp_store_index()?).cloned();
+            let store = match &store {
+                Some(store) => store,
+                None => ocsp_store
+            };
+            // The peer's certificate has already been verified, using
+            // the connection's trust store.
+            let verified = ssl.verified_chain().map(|chain| {
+                (
+                    chain.get(0).map(|cert| cert.to_owned()),
+                    chain.get(1).map(|cert| cert.to_owned())
+                )
+            });
+            let (cert, issuer) = match verified {
+                Some((Some(cert), Some(issuer))) => (cert, issuer),
+                _ => {
+                    warn!(target: "pki-ocsp",
+                          "no verified issuer for OCSP check");
+
+                    return Ok(false);
+                }
+            };
+            let (cert, issuer) = (&cert, &issuer);
+            let result = match ssl.ocsp_status() {
+                Some(response) => {
+                    self.check_response(store, cert, issuer, response, None)
+                }
+                None if self.require_stapled => {
+                    warn!(target: "pki-ocsp",
+                          "peer did not staple required OCSP response");
+
+                    return Ok(false);
+                }
+                None if self.responses.is_empty() => return Ok(true),
+                None => self.check_files(store, cert, issuer)
+            };
+
+            match result {
+                Ok(()) => Ok(true),
+                Err(err) => {
+                    warn!(target: "pki-ocsp",
+                          "OCSP check failed: {}",
+                          err);
+
+                    Ok(false)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl ScopedError for OCSPError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            OCSPError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            OCSPError::IO { error, .. } => error.scope(),
+            OCSPError::NoResponder => ErrorScope::System,
+            OCSPError::BadURL { .. } => ErrorScope::System,
+            OCSPError::Fetch { error, .. } => error.scope(),
+            OCSPError::HTTPStatus { .. } => ErrorScope::External,
+            OCSPError::ResponseStatus { status }
+                if *status == OcspResponseStatus::TRY_LATER =>
+            {
+                ErrorScope::Retryable
+            }
+            OCSPError::ResponseStatus { .. } => ErrorScope::External,
+            OCSPError::BadSignature { .. } => ErrorScope::External,
+            OCSPError::BadNonce => ErrorScope::External,
+            OCSPError::NoStatus => ErrorScope::External,
+            OCSPError::Stale { .. } => ErrorScope::External,
+            OCSPError::Revoked { .. } => ErrorScope::External,
+            OCSPError::Unknown => ErrorScope::External
+        }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Display for OCSPError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            OCSPError::OpenSSL { error } => error.fmt(f),
+            OCSPError::IO { path, error } => write!(
+                f,
+                "error reading OCSP response {}: {}",
+                path.to_string_lossy(),
+                error
+            ),
+            OCSPError::NoResponder => write!(f, "no OCSP responder URL"),
+            OCSPError::BadURL { url } => {
+                write!(f, "unsupported OCSP responder URL {}", url)
+            }
+            OCSPError::Fetch { url, error } => {
+                write!(f, "error querying OCSP responder {}: {}", url, error)
+            }
+            OCSPError::HTTPStatus { url, status } => {
+                write!(f, "OCSP responder {} returned error ({})", url, status)
+            }
+            OCSPError::ResponseStatus { status } => {
+                write!(f, "OCSP response status {}", status.as_raw())
+            }
+            OCSPError::BadSignature { error } => {
+                write!(f, "bad OCSP response signature: {}", error)
+            }
+            OCSPError::BadNonce => write!(f, "OCSP response nonce mismatch"),
+            OCSPError::NoStatus => {
+                write!(f, "no OCSP response for certificate")
+            }
+            OCSPError::Stale { error } => {
+                write!(f, "OCSP response is not fresh: {}", error)
+            }
+            OCSPError::Revoked { reason } => {
+                write!(f, "certificate revoked (reason {})", reason.as_raw())
+            }
+            OCSPError::Unknown => {
+                write!(f, "certificate status unknown to OCSP responder")
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+use std::net::TcpListener;
+#[cfg(all(test, feature = "openssl"))]
+use std::thread::spawn;
+#[cfg(all(test, feature = "openssl"))]
+use std::thread::JoinHandle;
+#[cfg(all(test, feature = "openssl"))]
+use std::time::SystemTime;
+
+#[cfg(all(test, feature = "openssl"))]
+use openssl::x509::store::X509Store;
+#[cfg(all(test, feature = "openssl"))]
+use openssl::x509::X509PurposeId;
+
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::ocsp_test_cert;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::ocsp_test_config;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestOCSPStatus;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::tls_handshake_ocsp;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::PKITrustRoot;
+#[cfg(all(test, feature = "openssl"))]
+use crate::init;
+
+#[cfg(all(test, feature = "openssl"))]
+fn ocsp_test_store(pki: &TestPKI) -> X509Store {
+    PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    )
+    .load(None, None, X509PurposeId::ANY)
+    .unwrap()
+}
+
+/// Read an HTTP request with a body from `stream`.
+#[cfg(all(test, feature = "openssl"))]
+fn ocsp_test_read_request(stream: &mut TcpStream) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        let len = stream.read(&mut buf).unwrap();
+
+        if len == 0 {
+            return request;
+        }
+
+        request.extend_from_slice(&buf[..len]);
+
+        if let Some(split) =
+            request.windows(4).position(|window| window == b"\r\n\r\n")
+        {
+            let head =
+                String::from_utf8_lossy(&request[..split]).to_lowercase();
+            let body_len = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|len| len.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+
+            if request.len() >= split + 4 + body_len {
+                return request;
+            }
+        }
+    }
+}
+
+/// Start an in-process OCSP responder serving `nrequests` requests
+/// for the server certificates generated by [TestPKI].
+///
+/// Responses are generated with [TestCA::ocsp_response], and so have
+/// no nonce.
+#[cfg(all(test, feature = "openssl"))]
+fn ocsp_test_responder(
+    pki: &TestPKI,
+    nrequests: usize
+) -> (JoinHandle<()>, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let next_update = SystemTime::now() + Duration::from_secs(3600);
+    let revoked_status = TestOCSPStatus::Revoked {
+        time: SystemTime::now() - Duration::from_secs(3600),
+        reason: OcspRevokedStatus::KEY_COMPROMISE
+    };
+    // Responses, keyed by the serial number that they cover.
+    let responses: Vec<(Vec<u8>, Vec<u8>)> = vec![
+        ("server", TestOCSPStatus::Good),
+        ("revoked", revoked_status),
+    ]
+    .into_iter()
+    .map(|(name, status)| {
+        let path = format!("server/certs/test_{}_cert.pem", name);
+        let cert = ocsp_test_cert(&pki.path(&path));
+        let serial = cert.serial_number().to_bn().unwrap().to_vec();
+        let response = pki
+            .server_ca()
+            .ocsp_response(pki.ocsp_responder(), &cert, status, next_update)
+            .unwrap();
+
+        (serial, response)
+    })
+    .collect();
+    let responder = spawn(move || {
+        for _ in 0..nrequests {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = ocsp_test_read_request(&mut stream);
+            let (_, body) = responses
+                .iter()
+                .find(|(serial, _)| {
+                    request.windows(serial.len()).any(|window| window == serial)
+                })
+                .expect("Expected request for a known certificate");
+            let header = format!(
+                concat!(
+                    "HTTP/1.0 200 OK\r\n",
+                    "Content-Type: application/ocsp-response\r\n",
+                    "Content-Length: {}\r\n",
+                    "\r\n"
+                ),
+                body.len()
+            );
+
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+
+    (responder, url)
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_deserialize_ocsp() {
+    init();
+
+    let yaml = concat!(
+        "root-certs:\n",
+        "  - /usr/local/etc/test/test.cert\n",
+        "ocsp:\n",
+        "  responder-url: http://ocsp.nowhere.com/\n",
+        "  responses:\n",
+        "    - /usr/local/etc/test/test.der\n"
+    );
+    let actual: PKITrustRoot = serde_yaml::from_str(yaml).unwrap();
+    let expected = PKIOCSPConfig::new(
+        Some(String::from("http://ocsp.nowhere.com/")),
+        true,
+        300,
+        None,
+        vec![PathBuf::from("/usr/local/etc/test/test.der")],
+        None,
+        false
+    );
+
+    assert_eq!(Some(&expected), actual.ocsp())
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_check_files() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let config = ocsp_test_config(
+        None,
+        &[
+            pki.path("server/ocsp/test_server_ocsp.der"),
+            pki.path("server/ocsp/test_revoked_ocsp.der")
+        ],
+        false
+    );
+    let store = ocsp_test_store(&pki);
+    let issuer = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let server = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let revoked =
+        ocsp_test_cert(&pki.path("server/certs/test_revoked_cert.pem"));
+    let ocsp = ocsp_test_cert(&pki.path("server/ocsp/ocsp_cert.pem"));
+
+    config.check_files(&store, &server, &issuer).unwrap();
+
+    match config.check_files(&store, &revoked, &issuer) {
+        Err(OCSPError::Revoked { reason }) => {
+            assert_eq!(OcspRevokedStatus::KEY_COMPROMISE, reason)
+        }
+        _ => panic!("Expected revoked")
+    }
+
+    match config.check_files(&store, &ocsp, &issuer) {
+        Err(OCSPError::NoStatus) => {}
+        _ => panic!("Expected no status")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_check_response_untrusted() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let config = ocsp_test_config(None, &[], false);
+    let store = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    )
+    .load(None, None, X509PurposeId::ANY)
+    .unwrap();
+    let issuer = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let server = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let response = read(pki.path("server/ocsp/test_server_ocsp.der")).unwrap();
+
+    match config.check_response(&store, &server, &issuer, &response, None) {
+        Err(OCSPError::BadSignature { .. }) => {}
+        _ => panic!("Expected bad signature")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_check_response_nonce() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let config = ocsp_test_config(None, &[], false);
+    let store = ocsp_test_store(&pki);
+    let issuer = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let server = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let response = read(pki.path("server/ocsp/test_server_ocsp.der")).unwrap();
+
+    // Pre-fetched responses have no nonce.
+    match config.check_response(
+        &store,
+        &server,
+        &issuer,
+        &response,
+        Some(b"nonce")
+    ) {
+        Err(OCSPError::BadNonce) => {}
+        _ => panic!("Expected bad nonce")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_request_add_nonce() {
+    init();
+
+    let request =
+        ocsp_request_add_nonce(&[0x30, 0x02, 0x30, 0x00], b"nonce").unwrap();
+    let (0x30, outer, []) = split_tlv(&request).unwrap() else {
+        panic!("Expected SEQUENCE")
+    };
+    let (0x30, tbs, []) = split_tlv(outer).unwrap() else {
+        panic!("Expected SEQUENCE")
+    };
+    let (0xa2, _, []) = split_tlv(tbs).unwrap() else {
+        panic!("Expected request extensions")
+    };
+
+    // Non-minimal lengths are rejected.
+    assert!(
+        ocsp_request_add_nonce(&[0x30, 0x81, 0x02, 0x30, 0x00], b"nonce")
+            .is_none()
+    );
+    assert!(
+        ocsp_request_add_nonce(&[0x30, 0x02, 0x30, 0x81, 0x00], b"nonce")
+            .is_none()
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_response_nonce_malformed() {
+    init();
+
+    assert!(ocsp_response_nonce(&[]).is_none());
+    assert!(ocsp_response_nonce(&[0x30, 0x05, 0x0a, 0x01, 0x00]).is_none());
+    assert!(
+        ocsp_response_nonce(&[0x30, 0x80, 0x0a, 0x01, 0x00, 0x00, 0x00])
+            .is_none()
+    );
+    assert!(
+        ocsp_response_nonce(&[0x30, 0x81, 0x03, 0x0a, 0x01, 0x00]).is_none()
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_query() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let (responder, url) = ocsp_test_responder(&pki, 2);
+    // The test responder does not echo nonces.
+    let config =
+        PKIOCSPConfig::new(Some(url), false, 300, None, vec![], None, false);
+    let store = ocsp_test_store(&pki);
+    let issuer = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let server = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+    let revoked =
+        ocsp_test_cert(&pki.path("server/certs/test_revoked_cert.pem"));
+    let result = config.query(&store, &server, &issuer);
+    let revoked_result = config.query(&store, &revoked, &issuer);
+
+    responder.join().unwrap();
+    result.unwrap();
+
+    match revoked_result {
+        Err(OCSPError::Revoked { .. }) => {}
+        Err(err) => panic!("Expected revoked, got {}", err),
+        Ok(()) => panic!("Expected revoked")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_query_no_responder() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let config = ocsp_test_config(None, &[], false);
+    let store = ocsp_test_store(&pki);
+    let issuer = ocsp_test_cert(&pki.path("server/ca_cert.pem"));
+    let server = ocsp_test_cert(&pki.path("server/certs/test_server_cert.pem"));
+
+    match config.query(&store, &server, &issuer) {
+        Err(OCSPError::NoResponder) => {}
+        _ => panic!("Expected no responder")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_ocsp_http_post_too_large() {
+    init();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let responder = spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let body = vec![0; 1024 * 1024];
+
+        // Leaving the request unread would make closing the connection
+        // reset it, discarding the response before the client sees it.
+        ocsp_test_read_request(&mut stream);
+
+        // The client may hang up partway through.
+        let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n");
+        let _ = stream.write_all(&body);
+    });
+
+    match ocsp_http_post(&url, b"request") {
+        Err(OCSPError::Fetch { error, .. }) => {
+            assert_eq!(ErrorKind::InvalidData, error.kind())
+        }
+        _ => panic!("Expected oversized response to be rejected")
+    }
+
+    responder.join().unwrap();
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_ocsp_stapled() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    // The server needs its own issuer to find a response to staple.
+    let server_ocsp = PKIOCSPConfig::new(
+        None,
+        true,
+        300,
+        None,
+        vec![pki.path("server/ocsp/test_server_ocsp.der")],
+        Some(pki.path("server/ca_cert.pem")),
+        false
+    );
+    let client_ocsp = ocsp_test_config(None, &[], true);
+    let (client, server) = tls_handshake_ocsp(
+        &pki,
+        "test-server.nowhere.com",
+        Some(server_ocsp),
+        Some(client_ocsp)
+    );
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_ocsp_no_issuer() {
+    init();
+
+    // Without its issuer, the server cannot find a response to staple.
+    let pki = TestPKI::generate().unwrap();
+    let server_ocsp = ocsp_test_config(
+        None,
+        &[pki.path("server/ocsp/test_server_ocsp.der")],
+        false
+    );
+    let client_ocsp = ocsp_test_config(None, &[], true);
+    let (client, _) = tls_handshake_ocsp(
+        &pki,
+        "test-server.nowhere.com",
+        Some(server_ocsp),
+        Some(client_ocsp)
+    );
+
+    assert!(client.is_err());
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_tls_handshake_ocsp_not_stapled() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let client_ocsp = ocsp_test_config(None, &[], true);
+    let (client, server) = tls_handshake_ocsp(
+        &pki,
+        "test-server.nowhere.com",
+        None,
+        Some(client_ocsp)
+    );
+
+    assert!(client.is_err());
+    assert!(server.is_err());
+}
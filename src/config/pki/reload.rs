@@ -0,0 +1,1216 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Trust stores and identities that can be reloaded at runtime.
+//!
+//! This module provides [ReloadableTrustStore] and
+//! [ReloadableIdentity], which are built from a [PKITrustRoot] and a
+//! [PKIIdentity], and which can be rebuilt when the files they
+//! reference change, so that CRLs, root certificates, and our own
+//! certificate can be updated without restarting.
+use std::io::Error;
+use std::os::raw::c_int;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::thread::sleep;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use foreign_types::ForeignType;
+use foreign_types::ForeignTypeRef;
+use log::debug;
+use log::warn;
+use openssl::error::ErrorStack;
+use openssl::ex_data::Index;
+use openssl::pkey::PKey;
+use openssl::pkey::Private;
+use openssl::ssl::ClientHelloResponse;
+use openssl::ssl::ConnectConfiguration;
+use openssl::ssl::Ssl;
+use openssl::ssl::SslAcceptor;
+use openssl::ssl::SslConnector;
+use openssl::ssl::SslRef;
+use openssl::stack::Stack;
+use openssl::x509::store::X509Store;
+use openssl::x509::X509PurposeId;
+use openssl::x509::X509;
+use openssl_sys::stack_st_X509;
+use openssl_sys::EVP_PKEY;
+use openssl_sys::SSL;
+use openssl_sys::X509_STORE;
+
+use crate::config::pki::PKIIdentity;
+use crate::config::pki::PKIIdentityLoadError;
+use crate::config::pki::PKITrustRoot;
+use crate::config::pki::PKITrustRootLoadError;
+use crate::config::pki::TLSContextLoadError;
+use crate::error::MutexPoison;
+use crate::error::WithMutexPoison;
+use crate::net::IPEndpointAddr;
+use crate::shutdown::ShutdownFlag;
+
+/// Trust store built from a [PKITrustRoot], which can be rebuilt when
+/// the files it references change.
+///
+/// This holds the most recently loaded [X509Store], which can be
+/// obtained with [store](ReloadableTrustStore::store).  The files and
+/// directories named in the [PKITrustRoot] can be checked for
+/// changes with [poll](ReloadableTrustStore::poll), or periodically
+/// in a background thread with
+/// [watch](ReloadableTrustStore::watch).  When a change is detected,
+/// a new [X509Store] is built and swapped in atomically; if this
+/// fails, the previous store remains in use.
+///
+/// This allows CRLs and root certificates to be updated without
+/// restarting.  TLS and DTLS contexts hold the store they were
+/// created with, so connections must be given the current store when
+/// they start.  This is done for acceptors created with
+/// [load_acceptor](ReloadableTrustStore::load_acceptor), for
+/// connections configured with
+/// [connect_configuration](ReloadableTrustStore::connect_configuration),
+/// and for sessions of a [DTLSSocket](crate::dtls::DTLSSocket) given
+/// this store with
+/// [set_trust_store](crate::dtls::DTLSSocket::set_trust_store).  Any
+/// other connection can be given it with
+/// [set_verify_store](ReloadableTrustStore::set_verify_store).
+pub struct ReloadableTrustStore {
+    /// Configuration for the trust store.
+    trust_root: PKITrustRoot,
+    /// Time used for certificate validity checks.
+    verify_time: Option<SystemTime>,
+    /// Endpoint for host name checks.
+    endpoint: Option<IPEndpointAddr>,
+    /// Purpose for verification.
+    purpose: X509PurposeId,
+    /// The current trust store.
+    store: RwLock<Arc<X509Store>>,
+    /// The current trust store for OCSP responses, if OCSP is
+    /// configured.
+    ocsp_store: RwLock<Option<Arc<X509Store>>>,
+    /// Modification times of the files that the store was built from.
+    stamps: Mutex<Vec<(PathBuf, Option<SystemTime>)>>
+}
+
+/// Private key and certificate chain loaded from a [PKIIdentity],
+/// which can be reloaded when the files it references change.
+///
+/// This is the counterpart of [ReloadableTrustStore] for our own
+/// identity.  The most recently loaded key and chain can be obtained
+/// with [current](ReloadableIdentity::current).  The certificate
+/// chain file, and the key file if the key is held in one, can be
+/// checked for changes with [poll](ReloadableIdentity::poll).  If
+/// reloading fails, for example because the certificate has been
+/// replaced but the key has not yet, the previous key and chain
+/// remain in use.
+pub struct ReloadableIdentity {
+    /// Configuration for the identity.
+    identity: PKIIdentity,
+    /// The current private key and certificate chain.
+    current: RwLock<Arc<(PKey<Private>, Vec<X509>)>>,
+    /// Modification times of the files that were loaded.
+    stamps: Mutex<Vec<(PathBuf, Option<SystemTime>)>>
+}
+
+/// Longest time that background threads sleep between checks of
+/// their [ShutdownFlag].
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Reference counting for trust stores and replacing the whole
+// certificate chain of a connection are not covered by the `openssl`
+// crate.
+extern "C" {
+    fn X509_STORE_up_ref(store: *mut X509_STORE) -> c_int;
+    fn SSL_use_cert_and_key(
+        ssl: *mut SSL,
+        x509: *mut openssl_sys::X509,
+        privatekey: *mut EVP_PKEY,
+        chain: *mut stack_st_X509,
+        override_: c_int
+    ) -> c_int;
+}
+
+/// Get the index of the trust store used to check OCSP responses for
+/// a connection, set by [ReloadableTrustStore::set_verify_store].
+pub(crate) fn ocsp_store_index(
+) -> Result<Index<Ssl, Arc<X509Store>>, ErrorStack> {
+    static INDEX: OnceLock<Index<Ssl, Arc<X509Store>>> = OnceLock::new();
+
+    match INDEX.get() {
+        Some(index) => Ok(*index),
+        None => {
+            let index = Ssl::new_ex_index()?;
+
+            Ok(*INDEX.get_or_init(|| index))
+        }
+    }
+}
+
+/// Get the index of the key and certificate chain presented on a
+/// connection, set by [ReloadableIdentity::set_identity].
+pub(crate) fn identity_index(
+) -> Result<Index<Ssl, Arc<(PKey<Private>, Vec<X509>)>>, ErrorStack> {
+    static INDEX: OnceLock<Index<Ssl, Arc<(PKey<Private>, Vec<X509>)>>> =
+        OnceLock::new();
+
+    match INDEX.get() {
+        Some(index) => Ok(*index),
+        None => {
+            let index = Ssl::new_ex_index()?;
+
+            Ok(*INDEX.get_or_init(|| index))
+        }
+    }
+}
+
+/// Sleep for `duration`, waking periodically to check `shutdown`.
+///
+/// Returns whether `shutdown` is still live.
+pub(crate) fn sleep_while_live(
+    shutdown: &ShutdownFlag,
+    duration: Duration
+) -> bool {
+    let deadline = Instant::now() + duration;
+
+    loop {
+        let now = Instant::now();
+
+        if !shutdown.is_live() {
+            return false;
+        } else if now >= deadline {
+            return true;
+        }
+
+        sleep((deadline - now).min(SHUTDOWN_CHECK_INTERVAL));
+    }
+}
+
+impl ReloadableTrustStore {
+    /// Create a new `ReloadableTrustStore` from `trust_root`.
+    ///
+    /// The remaining arguments are the same as those of
+    /// [load](PKITrustRoot::load), and will be used each time the
+    /// store is rebuilt.  This loads the initial store, and fails if
+    /// that cannot be done.
+    pub fn new(
+        trust_root: PKITrustRoot,
+        verify_time: Option<SystemTime>,
+        endpoint: Option<IPEndpointAddr>,
+        purpose: X509PurposeId
+    ) -> Result<Self, PKITrustRootLoadError> {
+        let stamps = trust_root.file_stamps();
+        let store = trust_root.load(verify_time, endpoint.as_ref(), purpose)?;
+        let ocsp_store = trust_root.load_ocsp_store(verify_time)?;
+
+        Ok(ReloadableTrustStore {
+            trust_root: trust_root,
+            verify_time: verify_time,
+            endpoint: endpoint,
+            purpose: purpose,
+            store: RwLock::new(Arc::new(store)),
+            ocsp_store: RwLock::new(ocsp_store.map(Arc::new)),
+            stamps: Mutex::new(stamps)
+        })
+    }
+
+    /// Get the configuration for the trust store.
+    #[inline]
+    pub fn trust_root(&self) -> &PKITrustRoot {
+        &self.trust_root
+    }
+
+    /// Get the current trust store.
+    ///
+    /// The returned store is not affected by subsequent reloads.
+    #[inline]
+    pub fn store(&self) -> Result<Arc<X509Store>, MutexPoison> {
+        let guard = self.store.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.clone())
+    }
+
+    /// Get the current trust store for OCSP responses, if OCSP is
+    /// configured.
+    ///
+    /// The returned store is not affected by subsequent reloads.
+    #[inline]
+    pub fn ocsp_store(&self) -> Result<Option<Arc<X509Store>>, MutexPoison> {
+        let guard = self.ocsp_store.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.clone())
+    }
+
+    /// Use the current trust store to verify the peer of `ssl`.
+    ///
+    /// This overrides the trust store of the context that `ssl` was
+    /// created from, and must be done before the handshake starts.
+    /// If OCSP is configured, stapled responses are also checked
+    /// against the current store.
+    pub fn set_verify_store(
+        &self,
+        ssl: &mut SslRef
+    ) -> Result<(), WithMutexPoison<TLSContextLoadError>> {
+        if let Some(ocsp_store) = self
+            .ocsp_store()
+            .map_err(|_| WithMutexPoison::MutexPoison)?
+        {
+            let index =
+                ocsp_store_index().map_err(|err| WithMutexPoison::Inner {
+                    error: TLSContextLoadError::OpenSSL { error: err }
+                })?;
+
+            ssl.set_ex_data(index, ocsp_store);
+        }
+
+        let store = self.store().map_err(|_| WithMutexPoison::MutexPoison)?;
+
+        // SAFETY: the store is kept alive by `store` during the call,
+        // and the new reference is owned by the resulting `X509Store`.
+        let store = unsafe {
+            X509_STORE_up_ref(store.as_ptr());
+
+            X509Store::from_ptr(store.as_ptr())
+        };
+
+        ssl.set_verify_cert_store(store)
+            .map_err(|err| WithMutexPoison::Inner {
+                error: TLSContextLoadError::OpenSSL { error: err }
+            })
+    }
+
+    /// Create a [ConnectConfiguration] from `connector`, which will
+    /// verify the server against the current trust store.
+    ///
+    /// The `connector` would typically be created by
+    /// [load_connector](PKITrustRoot::load_connector) on the
+    /// configuration of this store, which should have been created
+    /// with [X509PurposeId::SSL_SERVER].  See also
+    /// [connect_configuration](PKITrustRoot::connect_configuration).
+    pub fn connect_configuration(
+        &self,
+        connector: &SslConnector
+    ) -> Result<ConnectConfiguration, WithMutexPoison<TLSContextLoadError>>
+    {
+        let mut config = self
+            .trust_root
+            .connect_configuration(connector)
+            .map_err(|err| WithMutexPoison::Inner {
+                error: TLSContextLoadError::OpenSSL { error: err }
+            })?;
+
+        self.set_verify_store(&mut config)?;
+
+        Ok(config)
+    }
+
+    /// Generate an OpenSSL [SslAcceptor] which verifies each client
+    /// against the trust store, and presents the key and certificate
+    /// chain from `identity`, that are current when its handshake
+    /// starts.
+    ///
+    /// This is otherwise the same as
+    /// [load_acceptor](PKITrustRoot::load_acceptor).  The store
+    /// should have been created with [X509PurposeId::SSL_CLIENT].
+    /// If OCSP is configured, the acceptor staples a pre-fetched
+    /// response for the current certificate, which is looked up on
+    /// each handshake.
+    pub fn load_acceptor(
+        self: &Arc<Self>,
+        identity: &Arc<ReloadableIdentity>
+    ) -> Result<SslAcceptor, TLSContextLoadError> {
+        let mut builder = self
+            .trust_root
+            .acceptor_builder(identity.identity(), self.verify_time)?;
+        let store = self.clone();
+        let identity = identity.clone();
+
+        builder.set_client_hello_callback(move |ssl, _| {
+            store.set_verify_store(ssl).map_err(|err| {
+                warn!(target: "pki-trust-root",
+                      "could not set trust store for connection: {}",
+                      err);
+
+                ErrorStack::get()
+            })?;
+            identity.set_identity(ssl).map_err(|err| {
+                warn!(target: "pki-trust-root",
+                      "could not set identity for connection: {}",
+                      err);
+
+                ErrorStack::get()
+            })?;
+
+            Ok(ClientHelloResponse::SUCCESS)
+        });
+
+        Ok(builder.build())
+    }
+
+    /// Check whether any of the files or directories in the
+    /// configuration have changed since the store was last loaded.
+    pub fn changed(&self) -> Result<bool, MutexPoison> {
+        let guard = self.stamps.lock().map_err(|_| MutexPoison)?;
+
+        Ok(*guard != self.trust_root.file_stamps())
+    }
+
+    /// Rebuild the trust store unconditionally.
+    ///
+    /// If this fails, the previous store remains in use, and no
+    /// further reload will be attempted by
+    /// [poll](ReloadableTrustStore::poll) until the files change
+    /// again.
+    pub fn reload(&self) -> Result<(), WithMutexPoison<PKITrustRootLoadError>> {
+        let mut stamps = self
+            .stamps
+            .lock()
+            .map_err(|_| WithMutexPoison::MutexPoison)?;
+
+        // Take the stamps first, so that changes made during the
+        // load will be picked up by the next poll.
+        *stamps = self.trust_root.file_stamps();
+
+        debug!(target: "pki-trust-root",
+               "reloading PKI trust root");
+
+        match self
+            .trust_root
+            .load(self.verify_time, self.endpoint.as_ref(), self.purpose)
+            .and_then(|store| {
+                let ocsp_store =
+                    self.trust_root.load_ocsp_store(self.verify_time)?;
+
+                Ok((store, ocsp_store))
+            }) {
+            Ok((store, ocsp_store)) => {
+                let mut guard = self
+                    .store
+                    .write()
+                    .map_err(|_| WithMutexPoison::MutexPoison)?;
+                let mut ocsp_guard = self
+                    .ocsp_store
+                    .write()
+                    .map_err(|_| WithMutexPoison::MutexPoison)?;
+
+                *guard = Arc::new(store);
+                *ocsp_guard = ocsp_store.map(Arc::new);
+
+                Ok(())
+            }
+            Err(err) => {
+                warn!(target: "pki-trust-root",
+                      "failed to reload PKI trust root, keeping previous: {}",
+                      err);
+
+                Err(WithMutexPoison::Inner { error: err })
+            }
+        }
+    }
+
+    /// Rebuild the trust store if any of its files have changed.
+    ///
+    /// Returns whether the store was rebuilt.
+    pub fn poll(&self) -> Result<bool, WithMutexPoison<PKITrustRootLoadError>> {
+        if self.changed().map_err(|_| WithMutexPoison::MutexPoison)? {
+            self.reload()?;
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Start a thread that calls [poll](ReloadableTrustStore::poll)
+    /// on `store` every `interval`, until `shutdown` is set.
+    ///
+    /// Reload errors are logged, and passed to `report`.  The thread
+    /// exits if a mutex is poisoned.
+    pub fn watch<F>(
+        store: Arc<Self>,
+        interval: Duration,
+        shutdown: ShutdownFlag,
+        mut report: F
+    ) -> Result<JoinHandle<()>, Error>
+    where
+        F: 'static + FnMut(&PKITrustRootLoadError) + Send {
+        Builder::new()
+            .name(String::from("pki-trust-root-watch"))
+            .spawn(move || {
+                while sleep_while_live(&shutdown, interval) {
+                    match store.poll() {
+                        Ok(_) => {}
+                        Err(WithMutexPoison::Inner { error }) => report(&error),
+                        Err(WithMutexPoison::MutexPoison) => {
+                            warn!(target: "pki-trust-root",
+                                  "mutex poisoned, stopping trust root watch");
+
+                            return;
+                        }
+                    }
+                }
+            })
+    }
+}
+
+impl ReloadableIdentity {
+    /// Create a new `ReloadableIdentity` from `identity`.
+    ///
+    /// This loads the initial key and certificate chain, and fails if
+    /// that cannot be done.
+    pub fn new(identity: PKIIdentity) -> Result<Self, PKIIdentityLoadError> {
+        let stamps = identity.file_stamps();
+        let current = identity.load()?;
+
+        Ok(ReloadableIdentity {
+            identity: identity,
+            current: RwLock::new(Arc::new(current)),
+            stamps: Mutex::new(stamps)
+        })
+    }
+
+    /// Get the configuration for the identity.
+    #[inline]
+    pub fn identity(&self) -> &PKIIdentity {
+        &self.identity
+    }
+
+    /// Get the current private key and certificate chain.
+    ///
+    /// The result is not affected by subsequent reloads.
+    #[inline]
+    pub fn current(
+        &self
+    ) -> Result<Arc<(PKey<Private>, Vec<X509>)>, MutexPoison> {
+        let guard = self.current.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.clone())
+    }
+
+    /// Present the current key and certificate chain on `ssl`.
+    ///
+    /// This overrides the identity of the context that `ssl` was
+    /// created from, and must be done before the handshake starts.
+    pub fn set_identity(
+        &self,
+        ssl: &mut SslRef
+    ) -> Result<(), WithMutexPoison<TLSContextLoadError>> {
+        let current =
+            self.current().map_err(|_| WithMutexPoison::MutexPoison)?;
+        let index = identity_index().map_err(|err| WithMutexPoison::Inner {
+            error: TLSContextLoadError::OpenSSL { error: err }
+        })?;
+
+        // Record the identity, so that a matching OCSP response can be
+        // stapled.
+        ssl.set_ex_data(index, current.clone());
+
+        let (key, chain) = &*current;
+        let mut extra = Stack::new().map_err(|err| WithMutexPoison::Inner {
+            error: TLSContextLoadError::OpenSSL { error: err }
+        })?;
+
+        for cert in &chain[1..] {
+            extra
+                .push(cert.clone())
+                .map_err(|err| WithMutexPoison::Inner {
+                    error: TLSContextLoadError::OpenSSL { error: err }
+                })?;
+        }
+
+        // SAFETY: all arguments are kept alive during the call, and
+        // `SSL_use_cert_and_key` takes its own references to them.
+        let res = unsafe {
+            SSL_use_cert_and_key(
+                ssl.as_ptr(),
+                chain[0].as_ptr(),
+                key.as_ptr(),
+                extra.as_ptr(),
+                1
+            )
+        };
+
+        if res == 1 {
+            Ok(())
+        } else {
+            Err(WithMutexPoison::Inner {
+                error: TLSContextLoadError::OpenSSL {
+                    error: ErrorStack::get()
+                }
+            })
+        }
+    }
+
+    /// Check whether the certificate chain or key file have changed
+    /// since they were last loaded.
+    pub fn changed(&self) -> Result<bool, MutexPoison> {
+        let guard = self.stamps.lock().map_err(|_| MutexPoison)?;
+
+        Ok(*guard != self.identity.file_stamps())
+    }
+
+    /// Reload the key and certificate chain unconditionally.
+    ///
+    /// If this fails, the previous key and chain remain in use, and
+    /// no further reload will be attempted by
+    /// [poll](ReloadableIdentity::poll) until the files change again.
+    pub fn reload(&self) -> Result<(), WithMutexPoison<PKIIdentityLoadError>> {
+        let mut stamps = self
+            .stamps
+            .lock()
+            .map_err(|_| WithMutexPoison::MutexPoison)?;
+
+        *stamps = self.identity.file_stamps();
+
+        debug!(target: "pki-identity",
+               "reloading PKI identity");
+
+        match self.identity.load() {
+            Ok(current) => {
+                let mut guard = self
+                    .current
+                    .write()
+                    .map_err(|_| WithMutexPoison::MutexPoison)?;
+
+                *guard = Arc::new(current);
+
+                Ok(())
+            }
+            Err(err) => {
+                warn!(target: "pki-identity",
+                      "failed to reload PKI identity, keeping previous: {}",
+                      err);
+
+                Err(WithMutexPoison::Inner { error: err })
+            }
+        }
+    }
+
+    /// Reload the key and certificate chain if their files have
+    /// changed.
+    ///
+    /// Returns whether they were reloaded.
+    pub fn poll(&self) -> Result<bool, WithMutexPoison<PKIIdentityLoadError>> {
+        if self.changed().map_err(|_| WithMutexPoison::MutexPoison)? {
+            self.reload()?;
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+use std::fs::copy;
+#[cfg(test)]
+use std::fs::read;
+#[cfg(test)]
+use std::fs::write;
+#[cfg(test)]
+use std::fs::File;
+#[cfg(test)]
+use std::path::Path;
+#[cfg(test)]
+use std::sync::mpsc::channel;
+
+#[cfg(test)]
+use openssl::x509::store::X509StoreRef;
+#[cfg(test)]
+use openssl::x509::X509StoreContext;
+
+#[cfg(test)]
+use crate::config::pki::keys::PKIFileKey;
+#[cfg(test)]
+use crate::config::pki::keys::PKIKey;
+#[cfg(test)]
+use crate::config::pki::ocsp::PKIOCSPConfig;
+#[cfg(test)]
+use crate::config::pki::ocsp_test_cert;
+#[cfg(test)]
+use crate::config::pki::ocsp_test_config;
+#[cfg(test)]
+use crate::config::pki::testing::TestCert;
+#[cfg(test)]
+use crate::config::pki::testing::TestCertSpec;
+#[cfg(test)]
+use crate::config::pki::testing::TestOCSPStatus;
+#[cfg(test)]
+use crate::config::pki::testing::TestPKI;
+#[cfg(test)]
+use crate::config::pki::tls_handshake_with;
+#[cfg(test)]
+use crate::config::pki::X509VerifyFlag;
+#[cfg(test)]
+use crate::init;
+#[cfg(test)]
+use crate::sign::cert_subject;
+
+#[cfg(test)]
+fn store_verifies(
+    store: &X509StoreRef,
+    path: &Path
+) -> bool {
+    let cert = X509::from_pem(&read(path).unwrap()).unwrap();
+    let chain = Stack::new().unwrap();
+    let mut ctx = X509StoreContext::new().unwrap();
+
+    ctx.init(store, &cert, &chain, |ctx| ctx.verify_cert())
+        .unwrap()
+}
+
+#[cfg(test)]
+fn replace_file(
+    path: &Path,
+    contents: &[u8],
+    offset: u64
+) {
+    write(path, contents).unwrap();
+
+    // Filesystem timestamps can be coarse, so force a change.
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(offset))
+        .unwrap();
+}
+
+#[test]
+fn test_reloadable_trust_store() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let server_cert = pki.path("server/certs/test_server_cert.pem");
+    let client_cert = pki.path("client/certs/test_client_cert.pem");
+    let ca = pki.path("ca_cert.pem");
+
+    copy(pki.path("server/ca_cert.pem"), &ca).unwrap();
+
+    let conf = PKITrustRoot::new(
+        vec![],
+        vec![ca.clone()],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = ReloadableTrustStore::new(conf, None, None, X509PurposeId::ANY)
+        .unwrap();
+    let first = store.store().unwrap();
+
+    assert!(!store.changed().unwrap());
+    assert!(!store.poll().unwrap());
+    assert!(store_verifies(&first, &server_cert));
+    assert!(!store_verifies(&first, &client_cert));
+
+    replace_file(&ca, &read(pki.path("client/ca_cert.pem")).unwrap(), 10);
+
+    assert!(store.changed().unwrap());
+    assert!(store.poll().unwrap());
+
+    let second = store.store().unwrap();
+
+    assert!(store_verifies(&second, &client_cert));
+    assert!(!store_verifies(&second, &server_cert));
+    // Previously obtained stores are unaffected.
+    assert!(store_verifies(&first, &server_cert));
+
+    replace_file(&ca, b"not a certificate", 20);
+
+    match store.poll() {
+        Err(WithMutexPoison::Inner { .. }) => {}
+        _ => panic!("Expected reload failure")
+    }
+
+    assert!(Arc::ptr_eq(&second, &store.store().unwrap()));
+    // Failed reloads are not retried until the files change again.
+    assert!(!store.poll().unwrap());
+}
+
+#[test]
+fn test_reloadable_trust_store_acceptor() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let crl = pki.path("client/crl/crl.pem");
+    let next_update = SystemTime::now() + Duration::from_secs(3600);
+    let name = "test-server.nowhere.com";
+
+    pki.client_ca().write_crl(&crl, &[], next_update).unwrap();
+
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![crl.clone()],
+        vec![X509VerifyFlag::CRLCheck],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(
+            server_root,
+            None,
+            None,
+            X509PurposeId::SSL_CLIENT
+        )
+        .unwrap()
+    );
+    let identity =
+        Arc::new(ReloadableIdentity::new(pki.server_identity()).unwrap());
+    let acceptor = store.load_acceptor(&identity).unwrap();
+    let connector = client_root
+        .load_connector(
+            Some(&pki.client_identity()),
+            None,
+            &IPEndpointAddr::name(String::from(name))
+        )
+        .unwrap();
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, server) = tls_handshake_with(acceptor.clone(), config, name);
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+
+    // Revoke the client's certificate after the acceptor was created.
+    let client_cert =
+        ocsp_test_cert(&pki.path("client/certs/test_client_cert.pem"));
+    let revoked = pki.client_ca().crl(&[&client_cert], next_update).unwrap();
+
+    replace_file(&crl, &revoked.to_pem().unwrap(), 10);
+
+    assert!(store.poll().unwrap());
+
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (_, server) = tls_handshake_with(acceptor, config, name);
+
+    assert!(server.is_err());
+}
+
+#[test]
+fn test_reloadable_identity_acceptor() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert_path = pki.path("server/live_cert.pem");
+    let key_path = pki.path("server/live_key.pem");
+    let name = "test-server.nowhere.com";
+    let renewed_name = "renewed-server.nowhere.com";
+    let identity = pki
+        .server_ca()
+        .issue(&TestCertSpec::server(name))
+        .unwrap()
+        .write(&cert_path, &key_path)
+        .unwrap();
+    let identity = Arc::new(ReloadableIdentity::new(identity).unwrap());
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(
+            server_root,
+            None,
+            None,
+            X509PurposeId::SSL_CLIENT
+        )
+        .unwrap()
+    );
+    let acceptor = store.load_acceptor(&identity).unwrap();
+    let client_identity = pki.client_identity();
+    let connector = client_root
+        .load_connector(
+            Some(&client_identity),
+            None,
+            &IPEndpointAddr::name(String::from(renewed_name))
+        )
+        .unwrap();
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, _) =
+        tls_handshake_with(acceptor.clone(), config, renewed_name);
+
+    assert!(client.is_err());
+
+    // Renew the server's certificate after the acceptor was created.
+    pki.server_ca()
+        .issue(&TestCertSpec::server(renewed_name))
+        .unwrap()
+        .write(&cert_path, &key_path)
+        .unwrap();
+    identity.reload().unwrap();
+
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, server) = tls_handshake_with(acceptor, config, renewed_name);
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[test]
+fn test_reloadable_trust_store_watch() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let ca = pki.path("ca_cert.pem");
+
+    copy(pki.path("server/ca_cert.pem"), &ca).unwrap();
+
+    let conf = PKITrustRoot::new(
+        vec![],
+        vec![ca.clone()],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(conf, None, None, X509PurposeId::ANY)
+            .unwrap()
+    );
+    let mut shutdown = ShutdownFlag::new();
+    let (send, recv) = channel();
+    let watch = ReloadableTrustStore::watch(
+        store.clone(),
+        Duration::from_millis(10),
+        shutdown.clone(),
+        move |err| send.send(err.to_string()).unwrap()
+    )
+    .unwrap();
+
+    replace_file(&ca, b"not a certificate", 10);
+
+    assert!(recv.recv_timeout(Duration::from_secs(5)).is_ok());
+    assert!(store_verifies(
+        &store.store().unwrap(),
+        &pki.path("server/certs/test_server_cert.pem")
+    ));
+
+    shutdown.set();
+    watch.join().unwrap();
+}
+
+#[test]
+fn test_reloadable_trust_store_watch_shutdown() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let conf = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(conf, None, None, X509PurposeId::ANY)
+            .unwrap()
+    );
+    let mut shutdown = ShutdownFlag::new();
+    let watch = ReloadableTrustStore::watch(
+        store,
+        Duration::from_secs(3600),
+        shutdown.clone(),
+        |_| {}
+    )
+    .unwrap();
+    let start = Instant::now();
+
+    // The thread notices shutdown without waiting out the interval.
+    shutdown.set();
+    watch.join().unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+fn test_reloadable_ocsp_staple() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert_path = pki.path("server/live_cert.pem");
+    let key_path = pki.path("server/live_key.pem");
+    let response_path = pki.path("server/ocsp/live_ocsp.der");
+    let next_update = SystemTime::now() + Duration::from_secs(3600);
+    let name = "test-server.nowhere.com";
+    let ocsp_response = |cert: &TestCert| {
+        pki.server_ca()
+            .ocsp_response(
+                pki.ocsp_responder(),
+                cert.cert(),
+                TestOCSPStatus::Good,
+                next_update
+            )
+            .unwrap()
+    };
+    let cert = pki.server_ca().issue(&TestCertSpec::server(name)).unwrap();
+
+    write(&response_path, ocsp_response(&cert)).unwrap();
+
+    let identity = cert.write(&cert_path, &key_path).unwrap();
+    let identity = Arc::new(ReloadableIdentity::new(identity).unwrap());
+    let server_ocsp = PKIOCSPConfig::new(
+        None,
+        true,
+        300,
+        None,
+        vec![response_path.clone()],
+        Some(pki.path("server/ca_cert.pem")),
+        false
+    );
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        Some(server_ocsp),
+        None
+    );
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        Some(ocsp_test_config(None, &[], true)),
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(
+            server_root,
+            None,
+            None,
+            X509PurposeId::SSL_CLIENT
+        )
+        .unwrap()
+    );
+    let acceptor = store.load_acceptor(&identity).unwrap();
+    let connector = client_root
+        .load_connector(
+            Some(&pki.client_identity()),
+            None,
+            &IPEndpointAddr::name(String::from(name))
+        )
+        .unwrap();
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, _) = tls_handshake_with(acceptor.clone(), config, name);
+
+    client.expect("Expected client success");
+
+    // Renew the server's certificate.  The old response does not
+    // cover it, so nothing is stapled.
+    let renewed = pki.server_ca().issue(&TestCertSpec::server(name)).unwrap();
+
+    renewed.write(&cert_path, &key_path).unwrap();
+    identity.reload().unwrap();
+
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, _) = tls_handshake_with(acceptor.clone(), config, name);
+
+    assert!(client.is_err());
+
+    // Fetching a response for the renewed certificate fixes this,
+    // without recreating the acceptor.
+    write(&response_path, ocsp_response(&renewed)).unwrap();
+
+    let config = client_root.connect_configuration(&connector).unwrap();
+    let (client, server) = tls_handshake_with(acceptor, config, name);
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[test]
+fn test_reloadable_ocsp_store() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = pki.path("roots.pem");
+    let name = "test-server.nowhere.com";
+    let endpoint = IPEndpointAddr::name(String::from(name));
+
+    // Start out trusting the wrong CA.
+    copy(pki.path("client/ca_cert.pem"), &roots).unwrap();
+
+    let server_ocsp = PKIOCSPConfig::new(
+        None,
+        true,
+        300,
+        None,
+        vec![pki.path("server/ocsp/test_server_ocsp.der")],
+        Some(pki.path("server/ca_cert.pem")),
+        false
+    );
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        Some(server_ocsp),
+        None
+    );
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![roots.clone()],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        Some(ocsp_test_config(None, &[], true)),
+        None
+    );
+    let acceptor = server_root
+        .load_acceptor(&pki.server_identity(), None)
+        .unwrap();
+    let connector = client_root
+        .load_connector(Some(&pki.client_identity()), None, &endpoint)
+        .unwrap();
+    let store = ReloadableTrustStore::new(
+        client_root,
+        None,
+        Some(endpoint),
+        X509PurposeId::SSL_SERVER
+    )
+    .unwrap();
+    let config = store.connect_configuration(&connector).unwrap();
+    let (client, _) = tls_handshake_with(acceptor.clone(), config, name);
+
+    assert!(client.is_err());
+
+    // Both the server's certificate and its stapled response are
+    // checked against the reloaded store, rather than the connector's.
+    replace_file(&roots, &read(pki.path("server/ca_cert.pem")).unwrap(), 10);
+
+    assert!(store.poll().unwrap());
+    assert!(store.ocsp_store().unwrap().is_some());
+
+    let config = store.connect_configuration(&connector).unwrap();
+    let (client, server) = tls_handshake_with(acceptor, config, name);
+
+    client.expect("Expected client success");
+    server.expect("Expected server success");
+}
+
+#[test]
+fn test_reloadable_identity() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert_path = pki.path("cert.pem");
+    let key_path = pki.path("key.pem");
+
+    copy(pki.path("server/certs/test_server_cert.pem"), &cert_path).unwrap();
+    copy(pki.path("server/private/test_server_key.pem"), &key_path).unwrap();
+
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        PKIKey::File(PKIFileKey::new(key_path.clone(), None))
+    );
+    let reloadable = ReloadableIdentity::new(identity).unwrap();
+    let first = reloadable.current().unwrap();
+    let server = cert_subject(&first.1[0]).unwrap();
+
+    assert!(!reloadable.changed().unwrap());
+    assert!(!reloadable.poll().unwrap());
+    assert!(server.contains("CN=test-server.nowhere.com"));
+
+    // The certificate is replaced before the key.
+    replace_file(
+        &cert_path,
+        &read(pki.path("client/certs/test_client_cert.pem")).unwrap(),
+        10
+    );
+
+    assert!(matches!(
+        reloadable.poll(),
+        Err(WithMutexPoison::Inner {
+            error: PKIIdentityLoadError::KeyMismatch
+        })
+    ));
+    assert!(Arc::ptr_eq(&first, &reloadable.current().unwrap()));
+
+    replace_file(
+        &key_path,
+        &read(pki.path("client/private/test_client_key.pem")).unwrap(),
+        20
+    );
+
+    assert!(reloadable.poll().unwrap());
+
+    let second = reloadable.current().unwrap();
+
+    assert!(cert_subject(&second.1[0])
+        .unwrap()
+        .contains("CN=test-client"));
+    // Previously obtained identities are unaffected.
+    assert_eq!(server, cert_subject(&first.1[0]).unwrap());
+}
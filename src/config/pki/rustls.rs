@@ -0,0 +1,839 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Pure-Rust backend for trust roots and identities.
+//!
+//! This module provides the `rustls` feature's counterparts of the
+//! OpenSSL-based `load*` methods of [PKITrustRoot] and [PKIIdentity],
+//! which build rustls and webpki verifiers, TLS configurations, and
+//! certified keys from the same configuration.
+use std::fs::read;
+use std::fs::read_dir;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use log::debug;
+use log::trace;
+use log::warn;
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::CertificateRevocationListDer;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::sign::CertifiedKey;
+use rustls::version::TLS13;
+use rustls::ClientConfig;
+use rustls::InconsistentKeys;
+use rustls::RootCertStore;
+use rustls::ServerConfig;
+
+use crate::config::pki::PKIIdentity;
+use crate::config::pki::PKIIdentityLoadError;
+use crate::config::pki::PKITrustRoot;
+use crate::config::pki::PKITrustRootLoadError;
+use crate::config::pki::TLSContextLoadError;
+use crate::config::pki::X509HostFlag;
+use crate::config::pki::X509VerifyFlag;
+
+/// Highest `auth-level` that is always met by the rustls backend.
+///
+/// With the *ring* provider, rustls only accepts RSA keys of at least
+/// 2048 bits, ECDSA keys on P-256 or P-384, and Ed25519 keys, and
+/// only TLS 1.3 cipher suites are enabled.
+const RUSTLS_AUTH_LEVEL: u8 = 2;
+
+/// Lowest `verify-depth` that is always met by the rustls backend.
+///
+/// This is the maximum number of intermediate certificates that
+/// webpki allows in a chain.
+const RUSTLS_VERIFY_DEPTH: u8 = 6;
+
+/// Get the crypto provider used by the rustls backend.
+#[inline]
+fn rustls_provider() -> CryptoProvider {
+    rustls::crypto::ring::default_provider()
+}
+
+impl PKITrustRoot {
+    /// Check that every option in this configuration can be honored
+    /// by the rustls backend.
+    ///
+    /// Options that would only relax verification are ignored with a
+    /// warning, as webpki's own checks are stricter.  Options that
+    /// would make verification stricter than webpki supports are
+    /// rejected.
+    fn check_rustls_options(&self) -> Result<(), PKITrustRootLoadError> {
+        for flag in &self.verify_flags {
+            match flag {
+                X509VerifyFlag::CRLCheck | X509VerifyFlag::CRLCheckAll => {}
+                X509VerifyFlag::AllowProxyCerts |
+                X509VerifyFlag::NoCheckTime => {
+                    warn!(target: "pki-trust-root",
+                          "verify flag {} is ignored with rustls",
+                          flag.name())
+                }
+                _ => {
+                    return Err(PKITrustRootLoadError::Unsupported {
+                        option: format!("verify flag {}", flag.name())
+                    })
+                }
+            }
+        }
+
+        for flag in &self.host_flags {
+            match flag {
+                // webpki never matches partial wildcards or subject
+                // names.
+                X509HostFlag::NoPartialWildcards |
+                X509HostFlag::NeverCheckSubject => {}
+                X509HostFlag::NoWildcards => {
+                    return Err(PKITrustRootLoadError::Unsupported {
+                        option: format!("host flag {}", flag.name())
+                    })
+                }
+                _ => {
+                    warn!(target: "pki-trust-root",
+                          "host flag {} is ignored with rustls",
+                          flag.name())
+                }
+            }
+        }
+
+        match self.auth_level {
+            Some(level) if level > RUSTLS_AUTH_LEVEL => {
+                return Err(PKITrustRootLoadError::Unsupported {
+                    option: format!("auth-level {}", level)
+                })
+            }
+            _ => {}
+        }
+
+        match self.verify_depth {
+            Some(depth) if depth < RUSTLS_VERIFY_DEPTH => {
+                return Err(PKITrustRootLoadError::Unsupported {
+                    option: format!("verify-depth {}", depth)
+                })
+            }
+            _ => {}
+        }
+
+        if self.ocsp.is_some() {
+            return Err(PKITrustRootLoadError::Unsupported {
+                option: String::from("ocsp")
+            });
+        }
+
+        if self.peer_constraints.is_some() {
+            return Err(PKITrustRootLoadError::Unsupported {
+                option: String::from("peer-constraints")
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Load the root certificates and CRLs for the rustls backend.
+    ///
+    /// Every file in the CA directories is read, and any PEM-encoded
+    /// certificates and CRLs in them are used; other files are
+    /// ignored.
+    fn load_rustls_files(
+        &self
+    ) -> Result<
+        (RootCertStore, Vec<CertificateRevocationListDer<'static>>),
+        PKITrustRootLoadError
+    > {
+        if self.root_certs.is_empty() && self.dirs.is_empty() {
+            return Err(PKITrustRootLoadError::NoRootCerts);
+        }
+
+        let mut roots = RootCertStore::empty();
+        let mut crls = Vec::new();
+
+        for path in &self.root_certs {
+            trace!(target: "pki-trust-root",
+                   "loading trusted cert file {}",
+                   path.display());
+
+            let certs = CertificateDer::pem_file_iter(path)
+                .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| PKITrustRootLoadError::PEM {
+                    path: path.clone(),
+                    error: err
+                })?;
+
+            for cert in certs {
+                roots.add(cert).map_err(|err| {
+                    PKITrustRootLoadError::BadRootCert {
+                        path: path.clone(),
+                        error: err
+                    }
+                })?;
+            }
+        }
+
+        for path in &self.crls {
+            trace!(target: "pki-trust-root",
+                   "loading CRL file {}",
+                   path.display());
+
+            let file = CertificateRevocationListDer::pem_file_iter(path)
+                .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| PKITrustRootLoadError::PEM {
+                    path: path.clone(),
+                    error: err
+                })?;
+
+            crls.extend(file);
+        }
+
+        for dir in &self.dirs {
+            trace!(target: "pki-trust-root",
+                   "reading trust root directory {}",
+                   dir.display());
+
+            let entries =
+                read_dir(dir).map_err(|err| PKITrustRootLoadError::PEM {
+                    path: dir.clone(),
+                    error: rustls::pki_types::pem::Error::Io(err)
+                })?;
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+
+            paths.sort();
+
+            for path in paths {
+                let pem = match read(&path) {
+                    Ok(pem) => pem,
+                    Err(_) => continue
+                };
+
+                for cert in CertificateDer::pem_slice_iter(&pem).flatten() {
+                    roots.add(cert).map_err(|err| {
+                        PKITrustRootLoadError::BadRootCert {
+                            path: path.clone(),
+                            error: err
+                        }
+                    })?;
+                }
+
+                crls.extend(
+                    CertificateRevocationListDer::pem_slice_iter(&pem)
+                        .flatten()
+                );
+            }
+        }
+
+        if roots.is_empty() {
+            return Err(PKITrustRootLoadError::NoRootCerts);
+        }
+
+        Ok((roots, crls))
+    }
+
+    /// Get the CRLs to check, and whether to check only the leaf
+    /// certificate, from the `CRL_CHECK` and `CRL_CHECK_ALL` flags.
+    ///
+    /// As with OpenSSL, CRLs are only checked if one of these is set.
+    fn rustls_revocation(
+        &self,
+        crls: Vec<CertificateRevocationListDer<'static>>
+    ) -> Option<(Vec<CertificateRevocationListDer<'static>>, bool)> {
+        if self.verify_flags.contains(&X509VerifyFlag::CRLCheckAll) {
+            Some((crls, false))
+        } else if self.verify_flags.contains(&X509VerifyFlag::CRLCheck) {
+            Some((crls, true))
+        } else {
+            None
+        }
+    }
+
+    /// Generate a rustls [RootCertStore] from this configuration.
+    ///
+    /// This contains the root certificates, including those found in
+    /// the CA directories.  Unlike the verifiers created by
+    /// [load_rustls_server_verifier](PKITrustRoot::load_rustls_server_verifier)
+    /// and
+    /// [load_rustls_client_verifier](PKITrustRoot::load_rustls_client_verifier),
+    /// this does not include CRLs.
+    ///
+    /// This fails if the configuration contains options that cannot be
+    /// honored by rustls; see the documentation of [PKITrustRoot].
+    pub fn load_rustls(&self) -> Result<RootCertStore, PKITrustRootLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing rustls trust root from configuration");
+
+        self.check_rustls_options()?;
+
+        let (roots, _) = self.load_rustls_files()?;
+
+        Ok(roots)
+    }
+
+    /// Generate a rustls verifier for server certificates from this
+    /// configuration.
+    ///
+    /// This uses the root certificates and CRLs in this
+    /// configuration, with the `CRL_CHECK` and `CRL_CHECK_ALL` flags
+    /// controlling revocation checking as they do with OpenSSL.
+    /// Expired CRLs are rejected.  Host names are checked against the
+    /// server name given when the connection is made.
+    pub fn load_rustls_server_verifier(
+        &self
+    ) -> Result<Arc<WebPkiServerVerifier>, PKITrustRootLoadError> {
+        self.check_rustls_options()?;
+
+        let (roots, crls) = self.load_rustls_files()?;
+        let mut builder = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls_provider())
+        );
+
+        if let Some((crls, leaf_only)) = self.rustls_revocation(crls) {
+            builder = builder.with_crls(crls).enforce_revocation_expiration();
+
+            if leaf_only {
+                builder = builder.only_check_end_entity_revocation()
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|err| PKITrustRootLoadError::Verifier { error: err })
+    }
+
+    /// Generate a rustls verifier for client certificates from this
+    /// configuration.
+    ///
+    /// Clients are required to present certificates.  Revocation is
+    /// checked as described for
+    /// [load_rustls_server_verifier](PKITrustRoot::load_rustls_server_verifier).
+    pub fn load_rustls_client_verifier(
+        &self
+    ) -> Result<Arc<dyn ClientCertVerifier>, PKITrustRootLoadError> {
+        self.check_rustls_options()?;
+
+        let (roots, crls) = self.load_rustls_files()?;
+        let mut builder = WebPkiClientVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls_provider())
+        );
+
+        if let Some((crls, leaf_only)) = self.rustls_revocation(crls) {
+            builder = builder.with_crls(crls).enforce_revocation_expiration();
+
+            if leaf_only {
+                builder = builder.only_check_end_entity_revocation()
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|err| PKITrustRootLoadError::Verifier { error: err })
+    }
+
+    /// Generate a rustls [ClientConfig] from this configuration.
+    ///
+    /// Server certificates will be verified with the verifier
+    /// created by
+    /// [load_rustls_server_verifier](PKITrustRoot::load_rustls_server_verifier).
+    /// If `identity` is provided, it will be presented as the client
+    /// certificate.  As with
+    /// [load_connector](PKITrustRoot::load_connector), only TLS 1.3
+    /// is enabled.
+    pub fn load_rustls_client_config(
+        &self,
+        identity: Option<&PKIIdentity>
+    ) -> Result<ClientConfig, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing rustls client config from configuration");
+
+        let verifier = self
+            .load_rustls_server_verifier()
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls_provider()))
+                .with_protocol_versions(&[&TLS13])
+                .map_err(|err| TLSContextLoadError::Rustls { error: err })?
+                .with_webpki_verifier(verifier);
+
+        match identity {
+            Some(identity) => {
+                let (key, chain) = identity.load_rustls().map_err(|err| {
+                    TLSContextLoadError::Identity { error: err }
+                })?;
+
+                builder
+                    .with_client_auth_cert(chain, key)
+                    .map_err(|err| TLSContextLoadError::Rustls { error: err })
+            }
+            None => Ok(builder.with_no_client_auth())
+        }
+    }
+
+    /// Generate a rustls [ServerConfig] from this configuration.
+    ///
+    /// The server will present `identity`, and will require clients
+    /// to present certificates, which will be verified with the
+    /// verifier created by
+    /// [load_rustls_client_verifier](PKITrustRoot::load_rustls_client_verifier).
+    /// As with [load_acceptor](PKITrustRoot::load_acceptor), only TLS
+    /// 1.3 is enabled.
+    pub fn load_rustls_server_config(
+        &self,
+        identity: &PKIIdentity
+    ) -> Result<ServerConfig, TLSContextLoadError> {
+        debug!(target: "pki-trust-root",
+               "initializing rustls server config from configuration");
+
+        let verifier = self
+            .load_rustls_client_verifier()
+            .map_err(|err| TLSContextLoadError::TrustRoot { error: err })?;
+        let (key, chain) = identity
+            .load_rustls()
+            .map_err(|err| TLSContextLoadError::Identity { error: err })?;
+
+        ServerConfig::builder_with_provider(Arc::new(rustls_provider()))
+            .with_protocol_versions(&[&TLS13])
+            .map_err(|err| TLSContextLoadError::Rustls { error: err })?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(chain, key)
+            .map_err(|err| TLSContextLoadError::Rustls { error: err })
+    }
+}
+
+impl PKIIdentity {
+    /// Load the private key and certificate chain for use with
+    /// rustls.
+    ///
+    /// As with [load](PKIIdentity::load), this checks that the
+    /// private key matches the first certificate in the chain.  See
+    /// [PKIKey::load_rustls_key](super::keys::PKIKey::load_rustls_key)
+    /// for the keys that can be loaded.
+    pub fn load_rustls(
+        &self
+    ) -> Result<
+        (PrivateKeyDer<'static>, Vec<CertificateDer<'static>>),
+        PKIIdentityLoadError
+    > {
+        debug!(target: "pki-identity",
+               "loading PKI identity for rustls from configuration");
+
+        let key = self
+            .key
+            .load_rustls_key()
+            .map_err(|err| PKIIdentityLoadError::Key { error: err })?;
+        let chain = CertificateDer::pem_file_iter(&self.cert_chain)
+            .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| PKIIdentityLoadError::PEM {
+                path: self.cert_chain.clone(),
+                error: err
+            })?;
+
+        if chain.is_empty() {
+            return Err(PKIIdentityLoadError::NoCerts {
+                path: self.cert_chain.clone()
+            });
+        }
+
+        match CertifiedKey::from_der(
+            chain.clone(),
+            key.clone_key(),
+            &rustls_provider()
+        ) {
+            Ok(_) => Ok((key, chain)),
+            Err(rustls::Error::InconsistentKeys(
+                InconsistentKeys::KeyMismatch
+            )) => Err(PKIIdentityLoadError::KeyMismatch),
+            Err(err) => Err(PKIIdentityLoadError::Rustls { error: err })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "openssl"))]
+use std::convert::TryFrom;
+#[cfg(all(test, feature = "openssl"))]
+use std::fs::write;
+
+#[cfg(all(test, feature = "openssl"))]
+use openssl::symm::Cipher;
+#[cfg(all(test, feature = "openssl"))]
+use rustls::pki_types::ServerName;
+#[cfg(all(test, feature = "openssl"))]
+use rustls::ClientConnection;
+#[cfg(all(test, feature = "openssl"))]
+use rustls::ServerConnection;
+
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::keys::PKIFileKey;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::keys::PKIKey;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::keys::PKIKeyLoadError;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::keys::PKIPKCS11Key;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::keys::PKIPassphraseSource;
+#[cfg(all(test, feature = "openssl"))]
+use crate::config::pki::testing::TestPKI;
+#[cfg(test)]
+use crate::init;
+
+#[cfg(all(test, feature = "openssl"))]
+fn rustls_handshake(
+    client: ClientConfig,
+    server: ServerConfig,
+    name: &str
+) -> Result<(), rustls::Error> {
+    let name = ServerName::try_from(String::from(name)).unwrap();
+    let mut client = ClientConnection::new(Arc::new(client), name)?;
+    let mut server = ServerConnection::new(Arc::new(server))?;
+    let mut buf = Vec::new();
+
+    while client.is_handshaking() || server.is_handshaking() {
+        buf.clear();
+        client.write_tls(&mut buf).unwrap();
+        server.read_tls(&mut buf.as_slice()).unwrap();
+        server.process_new_packets()?;
+        buf.clear();
+        server.write_tls(&mut buf).unwrap();
+        client.read_tls(&mut buf.as_slice()).unwrap();
+        client.process_new_packets()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "openssl"))]
+fn rustls_test_roots(
+    pki: &TestPKI,
+    verify_flags: Vec<X509VerifyFlag>
+) -> (PKITrustRoot, PKITrustRoot) {
+    let server_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("client/ca_cert.pem")],
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let client_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![pki.path("server/crl/crl.pem")],
+        verify_flags,
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+
+    (server_root, client_root)
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_rustls_handshake() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let (server_root, client_root) = rustls_test_roots(&pki, vec![]);
+    let client = client_root
+        .load_rustls_client_config(Some(&pki.client_identity()))
+        .unwrap();
+    let server = server_root
+        .load_rustls_server_config(&pki.server_identity())
+        .unwrap();
+
+    rustls_handshake(client, server, "test-server.nowhere.com")
+        .expect("Expected success");
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_rustls_handshake_wrong_host() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let (server_root, client_root) = rustls_test_roots(&pki, vec![]);
+    let client = client_root
+        .load_rustls_client_config(Some(&pki.client_identity()))
+        .unwrap();
+    let server = server_root
+        .load_rustls_server_config(&pki.server_identity())
+        .unwrap();
+
+    assert!(
+        rustls_handshake(client, server, "wrong-server.nowhere.com").is_err()
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_rustls_handshake_no_client_cert() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let (server_root, client_root) = rustls_test_roots(&pki, vec![]);
+    let client = client_root.load_rustls_client_config(None).unwrap();
+    let server = server_root
+        .load_rustls_server_config(&pki.server_identity())
+        .unwrap();
+
+    assert!(
+        rustls_handshake(client, server, "test-server.nowhere.com").is_err()
+    );
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_rustls_handshake_revoked() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+
+    // CRLs are ignored without the CRL flags.
+    let (server_root, client_root) = rustls_test_roots(&pki, vec![]);
+    let client = client_root
+        .load_rustls_client_config(Some(&pki.client_identity()))
+        .unwrap();
+    let server = server_root
+        .load_rustls_server_config(&pki.revoked_identity())
+        .unwrap();
+
+    rustls_handshake(client, server, "test-revoked.nowhere.com")
+        .expect("Expected success");
+
+    for flag in [X509VerifyFlag::CRLCheck, X509VerifyFlag::CRLCheckAll] {
+        let (server_root, client_root) = rustls_test_roots(&pki, vec![flag]);
+        let client = client_root
+            .load_rustls_client_config(Some(&pki.client_identity()))
+            .unwrap();
+        let server = server_root
+            .load_rustls_server_config(&pki.revoked_identity())
+            .unwrap();
+
+        assert!(rustls_handshake(client, server, "test-revoked.nowhere.com")
+            .is_err());
+
+        let client = client_root
+            .load_rustls_client_config(Some(&pki.client_identity()))
+            .unwrap();
+        let server = server_root
+            .load_rustls_server_config(&pki.server_identity())
+            .unwrap();
+
+        rustls_handshake(client, server, "test-server.nowhere.com")
+            .expect("Expected success");
+    }
+}
+
+#[test]
+fn test_rustls_options() {
+    init();
+
+    let unsupported = [
+        "verify-flags:\n  - POLICY_CHECK\n",
+        "verify-flags:\n  - CHECK_SS_SIGNATURE\n",
+        "host-flags:\n  - NO_WILDCARDS\n",
+        "auth-level: 3\n",
+        "verify-depth: 4\n"
+    ];
+
+    for yaml in unsupported {
+        let root: PKITrustRoot = serde_yaml::from_str(&format!(
+            "root-certs:\n  - /nonexistent/ca_cert.pem\n{}",
+            yaml
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            root.load_rustls(),
+            Err(PKITrustRootLoadError::Unsupported { .. })
+        ));
+    }
+
+    let ignored = [
+        "verify-flags:\n  - CRL_CHECK_ALL\n  - NO_CHECK_TIME\n",
+        "host-flags:\n  - ALWAYS_CHECK_SUBJECT\n  - NO_PARTIAL_WILDCARDS\n",
+        "auth-level: 2\n",
+        "verify-depth: 16\n"
+    ];
+
+    for yaml in ignored {
+        let root: PKITrustRoot = serde_yaml::from_str(&format!(
+            "root-certs:\n  - /nonexistent/ca_cert.pem\n{}",
+            yaml
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            root.load_rustls(),
+            Err(PKITrustRootLoadError::PEM { .. })
+        ));
+    }
+
+    assert!(matches!(
+        PKITrustRoot::default().load_rustls(),
+        Err(PKITrustRootLoadError::NoRootCerts)
+    ));
+}
+
+#[test]
+fn test_rustls_ocsp_peer_constraints() {
+    init();
+
+    let root: PKITrustRoot = serde_yaml::from_str(concat!(
+        "root-certs:\n",
+        "  - /nonexistent/ca_cert.pem\n",
+        "ocsp:\n",
+        "  responder-url: http://ocsp.example.com/\n"
+    ))
+    .unwrap();
+
+    // The option is kept, rather than silently dropped.
+    assert!(root.ocsp().is_some());
+
+    match root.load_rustls() {
+        Err(PKITrustRootLoadError::Unsupported { option }) => {
+            assert_eq!("ocsp", option)
+        }
+        _ => panic!("Expected unsupported option")
+    }
+
+    let root: PKITrustRoot = serde_yaml::from_str(concat!(
+        "root-certs:\n",
+        "  - /nonexistent/ca_cert.pem\n",
+        "peer-constraints:\n",
+        "  subjects:\n",
+        "    - \"CN=node\"\n"
+    ))
+    .unwrap();
+
+    assert!(root.peer_constraints().is_some());
+
+    match root.load_rustls() {
+        Err(PKITrustRootLoadError::Unsupported { option }) => {
+            assert_eq!("peer-constraints", option)
+        }
+        _ => panic!("Expected unsupported option")
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_rustls_trust_root_dir() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let root: PKITrustRoot = serde_yaml::from_str(&format!(
+        "dirs:\n  - {}\n",
+        pki.path("server").display()
+    ))
+    .unwrap();
+    let roots = root.load_rustls().unwrap();
+
+    assert_eq!(roots.len(), 1);
+
+    let empty: PKITrustRoot = serde_yaml::from_str(&format!(
+        "dirs:\n  - {}\n",
+        pki.path("server/private").display()
+    ))
+    .unwrap();
+
+    assert!(matches!(
+        empty.load_rustls(),
+        Err(PKITrustRootLoadError::NoRootCerts)
+    ));
+}
+
+#[cfg(feature = "openssl")]
+#[test]
+fn test_load_rustls_identity() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let cert_path = pki.path("server/certs/test_server_cert.pem");
+    let key = pki.server_identity().load_key().unwrap();
+    let der_path = pki.path("key.der");
+    let encrypted_path = pki.path("key_encrypted.pem");
+    let encrypted = key
+        .private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secret")
+        .unwrap();
+
+    write(&der_path, key.private_key_to_pkcs8().unwrap()).unwrap();
+    write(&encrypted_path, encrypted).unwrap();
+
+    let (_, chain) = pki.server_identity().load_rustls().unwrap();
+
+    assert!(!chain.is_empty());
+
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        PKIKey::File(PKIFileKey::new(der_path, None))
+    );
+
+    identity.load_rustls().expect("Expected success");
+
+    let passphrase = PKIPassphraseSource::Env(String::from("UNUSED"));
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        PKIKey::File(PKIFileKey::new(encrypted_path, Some(passphrase)))
+    );
+
+    assert!(matches!(
+        identity.load_rustls(),
+        Err(PKIIdentityLoadError::Key {
+            error: PKIKeyLoadError::Unsupported { .. }
+        })
+    ));
+
+    let identity = PKIIdentity::new(
+        cert_path.clone(),
+        PKIKey::PKCS11(PKIPKCS11Key::new(
+            String::from("pkcs11:object=test-key"),
+            None,
+            None
+        ))
+    );
+
+    assert!(matches!(
+        identity.load_rustls(),
+        Err(PKIIdentityLoadError::Key {
+            error: PKIKeyLoadError::Unsupported { .. }
+        })
+    ));
+
+    let identity =
+        PKIIdentity::new(cert_path, pki.client_identity().key().clone());
+
+    assert!(matches!(
+        identity.load_rustls(),
+        Err(PKIIdentityLoadError::KeyMismatch)
+    ));
+}
@@ -0,0 +1,1244 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Generator for test public key infrastructure.
+//!
+//! This module generates CA hierarchies, leaf certificates, CRLs, and
+//! OCSP responses on demand, so that tests involving
+//! [PKITrustRoot](super::PKITrustRoot) and [PKIIdentity] do not depend
+//! on externally-generated files.  The following are provided:
+//!
+//! * [TestCertSpec] describes a certificate to be generated: its common name,
+//!   subject alternative names, extended key usages, and validity period.
+//!   Convenience constructors exist for currently valid, expired, and
+//!   not-yet-valid certificates.
+//!
+//! * [TestCA] is an in-memory CA, which can issue intermediate CAs, leaf
+//!   certificates ([TestCert]), CRLs, and OCSP responses.
+//!
+//! * [TestPKI] generates a standard server/client hierarchy in a temporary
+//!   directory, which is removed when it is dropped.
+//!
+//! All keys are generated on the NIST P-384 curve, and everything is
+//! signed with SHA-384.
+//!
+//! This module is only available with the `test-pki` feature, and is
+//! intended to be used by downstream packages in their own tests:
+//!
+//! ```
+//! use constellation_common::config::pki::testing::TestPKI;
+//! use constellation_common::config::pki::PKITrustRoot;
+//! use constellation_common::net::IPEndpointAddr;
+//!
+//! let pki = TestPKI::generate().unwrap();
+//! let yaml = format!(
+//!     "root-certs:\n  - {}\n",
+//!     pki.path("server/ca_cert.pem").display()
+//! );
+//! let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
+//! let name = String::from("test-server.nowhere.com");
+//! let endpoint = IPEndpointAddr::name(name);
+//!
+//! root.load_connector(Some(&pki.client_identity()), None, &endpoint)
+//!     .unwrap();
+//! ```
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::create_dir_all;
+use std::fs::write;
+use std::io::Error;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::bn::MsbOption;
+use openssl::ec::EcGroup;
+use openssl::ec::EcKey;
+use openssl::error::ErrorStack;
+use openssl::hash::hash;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::ocsp::OcspRevokedStatus;
+use openssl::pkey::PKey;
+use openssl::pkey::Private;
+use openssl::sign::Signer;
+use openssl::x509::extension::AuthorityKeyIdentifier;
+use openssl::x509::extension::BasicConstraints;
+use openssl::x509::extension::CrlNumber;
+use openssl::x509::extension::ExtendedKeyUsage;
+use openssl::x509::extension::KeyUsage;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::extension::SubjectKeyIdentifier;
+use openssl::x509::X509Builder;
+use openssl::x509::X509Crl;
+use openssl::x509::X509CrlBuilder;
+use openssl::x509::X509Name;
+use openssl::x509::X509NameBuilder;
+use openssl::x509::X509Ref;
+use openssl::x509::X509RevokedBuilder;
+use openssl::x509::X509;
+use tempfile::TempDir;
+
+use crate::codec::der::encode_generalized_time;
+use crate::codec::der::encode_tlv;
+use crate::codec::der::encode_unsigned_integer;
+use crate::codec::der::split_tlv;
+use crate::config::pki::keys::PKIFileKey;
+use crate::config::pki::keys::PKIKey;
+use crate::config::pki::PKIIdentity;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::net::IPEndpointAddr;
+
+/// Validity period of CA certificates (100 years).
+pub const CA_VALIDITY: Duration = Duration::from_secs(36500 * 86400);
+
+/// Validity period of leaf certificates (50 years).
+pub const CERT_VALIDITY: Duration = Duration::from_secs(18250 * 86400);
+
+/// How far in the past generated objects become valid.
+///
+/// This allows for some clock skew between the generator and the
+/// code under test.
+const BACKDATE: Duration = Duration::from_secs(3600);
+
+/// Subject name prefix for all generated certificates.
+const SUBJECT_PREFIX: [(Nid, &str); 3] = [
+    (Nid::COUNTRYNAME, "US"),
+    (Nid::ORGANIZATIONNAME, "Constellation"),
+    (Nid::ORGANIZATIONALUNITNAME, "Tests")
+];
+
+/// Errors that can occur when generating test PKI.
+#[derive(Debug)]
+pub enum TestPKIError {
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// An IO error occurred writing a file.
+    IO {
+        /// Path to the file.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    }
+}
+
+/// Description of a certificate to be generated.
+///
+/// All certificates are issued with a subject of the form
+/// `/C=US/O=Constellation/OU=Tests/CN=<common name>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestCertSpec {
+    /// The subject common name.
+    common_name: String,
+    /// Subject alternative names.
+    alt_names: Vec<IPEndpointAddr>,
+    /// Extended key usages, as OpenSSL names or dotted OIDs.
+    ekus: Vec<String>,
+    /// Start of the validity period.
+    not_before: SystemTime,
+    /// End of the validity period.
+    not_after: SystemTime
+}
+
+/// Status to report in a generated OCSP response.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TestOCSPStatus {
+    /// The certificate is good.
+    Good,
+    /// The certificate has been revoked.
+    Revoked {
+        /// Time of revocation.
+        time: SystemTime,
+        /// Reason for revocation.
+        ///
+        /// [NO_STATUS](OcspRevokedStatus::NO_STATUS) omits the reason.
+        reason: OcspRevokedStatus
+    },
+    /// The responder does not know about the certificate.
+    Unknown
+}
+
+/// An in-memory certificate authority.
+pub struct TestCA {
+    /// The CA's private key.
+    key: PKey<Private>,
+    /// The CA's certificate, followed by its issuers, ending with the
+    /// root.
+    chain: Vec<X509>
+}
+
+/// An in-memory leaf certificate and its private key.
+pub struct TestCert {
+    /// The private key.
+    key: PKey<Private>,
+    /// The leaf certificate, followed by any intermediate CAs, but not
+    /// the root.
+    chain: Vec<X509>
+}
+
+/// Standard test PKI, generated in a temporary directory.
+///
+/// This consists of two independent CAs with the following files,
+/// relative to [dir](TestPKI::dir):
+///
+/// * `server/ca_cert.pem`, `server/ca_key.pem`: the server CA, with common name
+///   `Test Server CA`.
+///
+/// * `server/certs/test_server_cert.pem`, `server/private/test_server_key.pem`:
+///   a server certificate for `test-server.nowhere.com`.
+///
+/// * `server/certs/test_revoked_cert.pem`,
+///   `server/private/test_revoked_key.pem`: a server certificate for
+///   `test-revoked.nowhere.com`, which is revoked.
+///
+/// * `server/crl/crl.pem`: a CRL for the server CA, listing the revoked
+///   certificate.
+///
+/// * `server/ocsp/ocsp_cert.pem`, `server/ocsp/ocsp_key.pem`: an OCSP responder
+///   certificate issued by the server CA.
+///
+/// * `server/ocsp/test_server_ocsp.der`, `server/ocsp/test_revoked_ocsp.der`:
+///   OCSP responses for the two server certificates.
+///
+/// * `client/ca_cert.pem`, `client/ca_key.pem`: the client CA, with common name
+///   `Test Client CA`.
+///
+/// * `client/certs/test_client_cert.pem`, `client/private/test_client_key.pem`:
+///   a client certificate for `test-client.nowhere.com`.
+pub struct TestPKI {
+    /// The temporary directory holding the files.
+    dir: TempDir,
+    /// The server CA.
+    server_ca: TestCA,
+    /// The client CA.
+    client_ca: TestCA,
+    /// The OCSP responder for the server CA.
+    ocsp_responder: TestCert
+}
+
+impl ScopedError for TestPKIError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            TestPKIError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            TestPKIError::IO { error, .. } => error.scope()
+        }
+    }
+}
+
+impl Display for TestPKIError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            TestPKIError::OpenSSL { error } => error.fmt(f),
+            TestPKIError::IO { path, error } => {
+                write!(f, "{} writing {}", error, path.display())
+            }
+        }
+    }
+}
+
+impl From<ErrorStack> for TestPKIError {
+    #[inline]
+    fn from(error: ErrorStack) -> TestPKIError {
+        TestPKIError::OpenSSL { error: error }
+    }
+}
+
+impl TestCertSpec {
+    /// Create a new `TestCertSpec` from its components.
+    #[inline]
+    pub fn new(
+        common_name: &str,
+        alt_names: Vec<IPEndpointAddr>,
+        ekus: Vec<String>,
+        not_before: SystemTime,
+        not_after: SystemTime
+    ) -> Self {
+        TestCertSpec {
+            common_name: String::from(common_name),
+            alt_names: alt_names,
+            ekus: ekus,
+            not_before: not_before,
+            not_after: not_after
+        }
+    }
+
+    /// Create a `TestCertSpec` valid from now for `lifetime`.
+    #[inline]
+    pub fn valid(
+        common_name: &str,
+        alt_names: Vec<IPEndpointAddr>,
+        ekus: Vec<String>,
+        lifetime: Duration
+    ) -> Self {
+        let now = SystemTime::now();
+
+        TestCertSpec::new(
+            common_name,
+            alt_names,
+            ekus,
+            now - BACKDATE,
+            now + lifetime
+        )
+    }
+
+    /// Create a `TestCertSpec` for a leaf certificate that expired a
+    /// day ago.
+    #[inline]
+    pub fn expired(
+        common_name: &str,
+        alt_names: Vec<IPEndpointAddr>,
+        ekus: Vec<String>
+    ) -> Self {
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86400);
+
+        TestCertSpec::new(
+            common_name,
+            alt_names,
+            ekus,
+            now - day * 2,
+            now - day
+        )
+    }
+
+    /// Create a `TestCertSpec` for a leaf certificate that only
+    /// becomes valid a day from now.
+    #[inline]
+    pub fn not_yet_valid(
+        common_name: &str,
+        alt_names: Vec<IPEndpointAddr>,
+        ekus: Vec<String>
+    ) -> Self {
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86400);
+
+        TestCertSpec::new(
+            common_name,
+            alt_names,
+            ekus,
+            now + day,
+            now + day * 2
+        )
+    }
+
+    /// Create a `TestCertSpec` for a server certificate for `name`,
+    /// valid for [CERT_VALIDITY].
+    ///
+    /// The name is used both as the common name and the only DNS
+    /// alternative name.
+    #[inline]
+    pub fn server(name: &str) -> Self {
+        TestCertSpec::valid(
+            name,
+            vec![IPEndpointAddr::name(String::from(name))],
+            vec![],
+            CERT_VALIDITY
+        )
+    }
+
+    /// Get the subject common name.
+    #[inline]
+    pub fn common_name(&self) -> &str {
+        &self.common_name
+    }
+
+    /// Get the subject alternative names.
+    #[inline]
+    pub fn alt_names(&self) -> &[IPEndpointAddr] {
+        &self.alt_names
+    }
+
+    /// Get the extended key usages.
+    #[inline]
+    pub fn ekus(&self) -> &[String] {
+        &self.ekus
+    }
+
+    /// Get the start of the validity period.
+    #[inline]
+    pub fn not_before(&self) -> SystemTime {
+        self.not_before
+    }
+
+    /// Get the end of the validity period.
+    #[inline]
+    pub fn not_after(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Get the subject name.
+    fn subject(&self) -> Result<X509Name, ErrorStack> {
+        let mut builder = X509NameBuilder::new()?;
+
+        for (nid, val) in SUBJECT_PREFIX {
+            builder.append_entry_by_nid(nid, val)?;
+        }
+
+        builder.append_entry_by_nid(Nid::COMMONNAME, &self.common_name)?;
+
+        Ok(builder.build())
+    }
+
+    /// Generate a certificate for `key`, signed by `issuer`, or
+    /// self-signed if `issuer` is `None`.
+    fn build(
+        &self,
+        key: &PKey<Private>,
+        issuer: Option<(&X509Ref, &PKey<Private>)>,
+        ca: bool
+    ) -> Result<X509, ErrorStack> {
+        let mut builder = X509Builder::new()?;
+        let subject = self.subject()?;
+        let mut serial = BigNum::new()?;
+
+        serial.rand(127, MsbOption::ONE, false)?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&*serial.to_asn1_integer()?)?;
+        builder.set_subject_name(&subject)?;
+
+        match issuer {
+            Some((cert, _)) => builder.set_issuer_name(cert.subject_name())?,
+            None => builder.set_issuer_name(&subject)?
+        }
+
+        builder.set_pubkey(key)?;
+        builder.set_not_before(&*asn1_time(self.not_before)?)?;
+        builder.set_not_after(&*asn1_time(self.not_after)?)?;
+
+        let issuer_cert = issuer.map(|(cert, _)| cert);
+        let mut exts = Vec::new();
+
+        if ca {
+            exts.push(BasicConstraints::new().critical().ca().build()?);
+            exts.push(
+                KeyUsage::new()
+                    .critical()
+                    .digital_signature()
+                    .key_cert_sign()
+                    .crl_sign()
+                    .build()?
+            );
+        } else {
+            exts.push(BasicConstraints::new().build()?);
+            exts.push(KeyUsage::new().digital_signature().build()?);
+        }
+
+        exts.push(
+            SubjectKeyIdentifier::new()
+                .build(&builder.x509v3_context(issuer_cert, None))?
+        );
+
+        if issuer_cert.is_some() {
+            exts.push(
+                AuthorityKeyIdentifier::new()
+                    .keyid(false)
+                    .build(&builder.x509v3_context(issuer_cert, None))?
+            );
+        }
+
+        if !self.alt_names.is_empty() {
+            let mut alt_names = SubjectAlternativeName::new();
+
+            for alt_name in &self.alt_names {
+                match alt_name {
+                    IPEndpointAddr::Addr(addr) => {
+                        alt_names.ip(&addr.to_string());
+                    }
+                    IPEndpointAddr::Name(name) => {
+                        alt_names.dns(name);
+                    }
+                }
+            }
+
+            exts.push(
+                alt_names.build(&builder.x509v3_context(issuer_cert, None))?
+            );
+        }
+
+        if !self.ekus.is_empty() {
+            let mut ekus = ExtendedKeyUsage::new();
+
+            for eku in &self.ekus {
+                ekus.other(eku);
+            }
+
+            exts.push(ekus.build()?);
+        }
+
+        for ext in exts {
+            builder.append_extension(ext)?;
+        }
+
+        match issuer {
+            Some((_, issuer_key)) => {
+                builder.sign(issuer_key, MessageDigest::sha384())?
+            }
+            None => builder.sign(key, MessageDigest::sha384())?
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl TestCA {
+    /// Create a self-signed root CA from `spec`.
+    pub fn new(spec: &TestCertSpec) -> Result<Self, ErrorStack> {
+        let key = gen_key()?;
+        let cert = spec.build(&key, None, true)?;
+
+        Ok(TestCA {
+            key: key,
+            chain: vec![cert]
+        })
+    }
+
+    /// Create a self-signed root CA with common name `common_name`,
+    /// valid for [CA_VALIDITY].
+    #[inline]
+    pub fn root(common_name: &str) -> Result<Self, ErrorStack> {
+        TestCA::new(&TestCertSpec::valid(
+            common_name,
+            vec![],
+            vec![],
+            CA_VALIDITY
+        ))
+    }
+
+    /// Issue an intermediate CA from `spec`.
+    pub fn intermediate(
+        &self,
+        spec: &TestCertSpec
+    ) -> Result<TestCA, ErrorStack> {
+        let key = gen_key()?;
+        let cert = spec.build(&key, Some((self.cert(), &self.key)), true)?;
+        let mut chain = vec![cert];
+
+        chain.extend(self.chain.iter().cloned());
+
+        Ok(TestCA {
+            key: key,
+            chain: chain
+        })
+    }
+
+    /// Issue a leaf certificate from `spec`.
+    pub fn issue(
+        &self,
+        spec: &TestCertSpec
+    ) -> Result<TestCert, ErrorStack> {
+        let key = gen_key()?;
+        let cert = spec.build(&key, Some((self.cert(), &self.key)), false)?;
+        let mut chain = vec![cert];
+
+        // Include any intermediates, but not the root.
+        chain.extend(self.chain[..self.chain.len() - 1].iter().cloned());
+
+        Ok(TestCert {
+            key: key,
+            chain: chain
+        })
+    }
+
+    /// Get the CA certificate.
+    #[inline]
+    pub fn cert(&self) -> &X509 {
+        &self.chain[0]
+    }
+
+    /// Get the CA's private key.
+    #[inline]
+    pub fn key(&self) -> &PKey<Private> {
+        &self.key
+    }
+
+    /// Get the CA certificate followed by its issuers, ending with the
+    /// root.
+    #[inline]
+    pub fn chain(&self) -> &[X509] {
+        &self.chain
+    }
+
+    /// Get the root certificate of this CA's hierarchy.
+    #[inline]
+    pub fn root_cert(&self) -> &X509 {
+        &self.chain[self.chain.len() - 1]
+    }
+
+    /// Generate a CRL revoking `revoked`, with a next update time of
+    /// `next_update`.
+    pub fn crl(
+        &self,
+        revoked: &[&X509Ref],
+        next_update: SystemTime
+    ) -> Result<X509Crl, ErrorStack> {
+        let now = SystemTime::now();
+        // The AKI extension needs a certificate context to be built.
+        let cert_builder = X509Builder::new()?;
+        let aki = AuthorityKeyIdentifier::new()
+            .keyid(true)
+            .build(&cert_builder.x509v3_context(Some(self.cert()), None))?;
+        let number = CrlNumber::new(BigNum::from_u32(1)?)?;
+        let mut builder = X509CrlBuilder::new()?;
+
+        builder.set_issuer_name(self.cert().subject_name())?;
+        builder.append_extension(aki)?;
+        builder.append_extension(number.build()?)?;
+        builder.set_last_update(&*asn1_time(now - BACKDATE)?)?;
+        builder.set_next_update(&*asn1_time(next_update)?)?;
+
+        for cert in revoked {
+            let mut entry = X509RevokedBuilder::new()?;
+
+            entry.set_serial_number(cert.serial_number())?;
+            entry.set_revocation_date(&*asn1_time(now - BACKDATE)?)?;
+            builder.add_revoked(entry.build())?;
+        }
+
+        builder.sign(&self.key, MessageDigest::sha384())?;
+
+        builder.build()
+    }
+
+    /// Generate a DER-encoded OCSP response reporting `status` for
+    /// `cert`, signed by `responder`.
+    ///
+    /// The responder should have been issued by this CA with the
+    /// `OCSPSigning` extended key usage, and is included in the
+    /// response.  The response has no nonce.
+    pub fn ocsp_response(
+        &self,
+        responder: &TestCert,
+        cert: &X509Ref,
+        status: TestOCSPStatus,
+        next_update: SystemTime
+    ) -> Result<Vec<u8>, ErrorStack> {
+        // CertID ::= SEQUENCE { hashAlgorithm, issuerNameHash,
+        //                       issuerKeyHash, serialNumber }
+        let name_hash =
+            hash(MessageDigest::sha1(), &self.cert().subject_name().to_der()?)?;
+        let spki = self.cert().public_key()?.public_key_to_der()?;
+        let key_hash = hash(MessageDigest::sha1(), &spki_key_bits(&spki))?;
+        let serial =
+            encode_unsigned_integer(&cert.serial_number().to_bn()?.to_vec());
+        let cert_id = encode_tlv(
+            0x30,
+            &[
+                SHA1_ALGORITHM,
+                &encode_tlv(0x04, &name_hash),
+                &encode_tlv(0x04, &key_hash),
+                &serial
+            ]
+            .concat()
+        );
+        let cert_status = match status {
+            TestOCSPStatus::Good => vec![0x80, 0x00],
+            TestOCSPStatus::Revoked { time, reason } => {
+                let mut info = encode_generalized_time(time);
+
+                if reason != OcspRevokedStatus::NO_STATUS {
+                    let reason = encode_tlv(0x0a, &[reason.as_raw() as u8]);
+
+                    info.extend(encode_tlv(0xa0, &reason));
+                }
+
+                encode_tlv(0xa1, &info)
+            }
+            TestOCSPStatus::Unknown => vec![0x82, 0x00]
+        };
+        let now = SystemTime::now();
+        let single = encode_tlv(
+            0x30,
+            &[
+                cert_id,
+                cert_status,
+                encode_generalized_time(now - BACKDATE),
+                encode_tlv(0xa0, &encode_generalized_time(next_update))
+            ]
+            .concat()
+        );
+        // ResponseData ::= SEQUENCE { responderID, producedAt, responses }
+        let responder_id =
+            encode_tlv(0xa1, &responder.cert().subject_name().to_der()?);
+        let data = encode_tlv(
+            0x30,
+            &[
+                responder_id,
+                encode_generalized_time(now),
+                encode_tlv(0x30, &single)
+            ]
+            .concat()
+        );
+        let mut signer = Signer::new(MessageDigest::sha384(), &responder.key)?;
+        let mut signature = vec![0];
+
+        signature.extend(signer.sign_oneshot_to_vec(&data)?);
+
+        // BasicOCSPResponse ::= SEQUENCE { tbsResponseData,
+        //                                  signatureAlgorithm,
+        //                                  signature, [0] certs }
+        let basic = encode_tlv(
+            0x30,
+            &[
+                data,
+                ECDSA_SHA384_ALGORITHM.to_vec(),
+                encode_tlv(0x03, &signature),
+                encode_tlv(
+                    0xa0,
+                    &encode_tlv(0x30, &responder.cert().to_der()?)
+                )
+            ]
+            .concat()
+        );
+        let response_bytes = encode_tlv(
+            0x30,
+            &[OCSP_BASIC_OID, &encode_tlv(0x04, &basic)].concat()
+        );
+
+        // OCSPResponse ::= SEQUENCE { responseStatus, [0] responseBytes }
+        Ok(encode_tlv(
+            0x30,
+            &[&[0x0a, 0x01, 0x00][..], &encode_tlv(0xa0, &response_bytes)]
+                .concat()
+        ))
+    }
+
+    /// Write the CA certificate and private key in PEM format to
+    /// `cert_path` and `key_path`.
+    pub fn write(
+        &self,
+        cert_path: &Path,
+        key_path: &Path
+    ) -> Result<(), TestPKIError> {
+        write_file(cert_path, &self.cert().to_pem()?)?;
+        write_file(key_path, &self.key.private_key_to_pem_pkcs8()?)
+    }
+
+    /// Write a CRL generated by [crl](TestCA::crl) in PEM format to
+    /// `path`.
+    pub fn write_crl(
+        &self,
+        path: &Path,
+        revoked: &[&X509Ref],
+        next_update: SystemTime
+    ) -> Result<(), TestPKIError> {
+        write_file(path, &self.crl(revoked, next_update)?.to_pem()?)
+    }
+}
+
+impl TestCert {
+    /// Get the leaf certificate.
+    #[inline]
+    pub fn cert(&self) -> &X509 {
+        &self.chain[0]
+    }
+
+    /// Get the private key.
+    #[inline]
+    pub fn key(&self) -> &PKey<Private> {
+        &self.key
+    }
+
+    /// Get the leaf certificate, followed by any intermediate CAs.
+    #[inline]
+    pub fn chain(&self) -> &[X509] {
+        &self.chain
+    }
+
+    /// Write the certificate chain and private key in PEM format to
+    /// `cert_path` and `key_path`, returning a [PKIIdentity] that
+    /// refers to them.
+    pub fn write(
+        &self,
+        cert_path: &Path,
+        key_path: &Path
+    ) -> Result<PKIIdentity, TestPKIError> {
+        let mut pem = Vec::new();
+
+        for cert in &self.chain {
+            pem.extend(cert.to_pem()?);
+        }
+
+        write_file(cert_path, &pem)?;
+        write_file(key_path, &self.key.private_key_to_pem_pkcs8()?)?;
+
+        Ok(PKIIdentity::new(
+            cert_path.to_path_buf(),
+            PKIKey::File(PKIFileKey::new(key_path.to_path_buf(), None))
+        ))
+    }
+}
+
+impl TestPKI {
+    /// Generate the standard test PKI in a new temporary directory.
+    pub fn generate() -> Result<Self, TestPKIError> {
+        let dir = TempDir::new().map_err(|err| TestPKIError::IO {
+            path: std::env::temp_dir(),
+            error: err
+        })?;
+        let root = dir.path();
+        let server_ca = TestCA::root("Test Server CA")?;
+        let client_ca = TestCA::root("Test Client CA")?;
+
+        for sub in [
+            "server/certs",
+            "server/crl",
+            "server/ocsp",
+            "server/private",
+            "client/certs",
+            "client/crl",
+            "client/private"
+        ] {
+            let path = root.join(sub);
+
+            create_dir_all(&path).map_err(|err| TestPKIError::IO {
+                path: path,
+                error: err
+            })?;
+        }
+
+        server_ca.write(
+            &root.join("server/ca_cert.pem"),
+            &root.join("server/ca_key.pem")
+        )?;
+        client_ca.write(
+            &root.join("client/ca_cert.pem"),
+            &root.join("client/ca_key.pem")
+        )?;
+
+        let server = server_ca
+            .issue(&TestCertSpec::server("test-server.nowhere.com"))?;
+        let revoked = server_ca
+            .issue(&TestCertSpec::server("test-revoked.nowhere.com"))?;
+        let client = client_ca
+            .issue(&TestCertSpec::server("test-client.nowhere.com"))?;
+        let ocsp = server_ca.issue(&TestCertSpec::valid(
+            "Test Server OCSP Responder",
+            vec![],
+            vec![String::from("OCSPSigning")],
+            CERT_VALIDITY
+        ))?;
+
+        for (name, cert, side) in [
+            ("server", &server, "server"),
+            ("revoked", &revoked, "server"),
+            ("client", &client, "client")
+        ] {
+            cert.write(
+                &root.join(format!("{}/certs/test_{}_cert.pem", side, name)),
+                &root.join(format!("{}/private/test_{}_key.pem", side, name))
+            )?;
+        }
+
+        ocsp.write(
+            &root.join("server/ocsp/ocsp_cert.pem"),
+            &root.join("server/ocsp/ocsp_key.pem")
+        )?;
+
+        let next_update = SystemTime::now() + CERT_VALIDITY;
+
+        server_ca.write_crl(
+            &root.join("server/crl/crl.pem"),
+            &[revoked.cert()],
+            next_update
+        )?;
+
+        // Pre-fetched responses.
+        let revoked_status = TestOCSPStatus::Revoked {
+            time: SystemTime::now() - BACKDATE,
+            reason: OcspRevokedStatus::KEY_COMPROMISE
+        };
+
+        for (name, cert, status) in [
+            ("server", &server, TestOCSPStatus::Good),
+            ("revoked", &revoked, revoked_status)
+        ] {
+            let response = server_ca.ocsp_response(
+                &ocsp,
+                cert.cert(),
+                status,
+                next_update
+            )?;
+
+            write_file(
+                &root.join(format!("server/ocsp/test_{}_ocsp.der", name)),
+                &response
+            )?;
+        }
+
+        Ok(TestPKI {
+            dir: dir,
+            server_ca: server_ca,
+            client_ca: client_ca,
+            ocsp_responder: ocsp
+        })
+    }
+
+    /// Get the directory holding the generated files.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Get the path to `rel`, relative to [dir](TestPKI::dir).
+    #[inline]
+    pub fn path(
+        &self,
+        rel: &str
+    ) -> PathBuf {
+        self.dir.path().join(rel)
+    }
+
+    /// Get the server CA.
+    #[inline]
+    pub fn server_ca(&self) -> &TestCA {
+        &self.server_ca
+    }
+
+    /// Get the client CA.
+    #[inline]
+    pub fn client_ca(&self) -> &TestCA {
+        &self.client_ca
+    }
+
+    /// Get the OCSP responder for the server CA, for use with
+    /// [ocsp_response](TestCA::ocsp_response).
+    #[inline]
+    pub fn ocsp_responder(&self) -> &TestCert {
+        &self.ocsp_responder
+    }
+
+    /// Get the identity for `test-server.nowhere.com`.
+    #[inline]
+    pub fn server_identity(&self) -> PKIIdentity {
+        self.identity("server", "server")
+    }
+
+    /// Get the identity for `test-revoked.nowhere.com`.
+    #[inline]
+    pub fn revoked_identity(&self) -> PKIIdentity {
+        self.identity("server", "revoked")
+    }
+
+    /// Get the identity for `test-client.nowhere.com`.
+    #[inline]
+    pub fn client_identity(&self) -> PKIIdentity {
+        self.identity("client", "client")
+    }
+
+    fn identity(
+        &self,
+        side: &str,
+        name: &str
+    ) -> PKIIdentity {
+        PKIIdentity::new(
+            self.path(&format!("{}/certs/test_{}_cert.pem", side, name)),
+            PKIKey::File(PKIFileKey::new(
+                self.path(&format!("{}/private/test_{}_key.pem", side, name)),
+                None
+            ))
+        )
+    }
+}
+
+/// DER encoding of the SHA-1 `AlgorithmIdentifier`.
+const SHA1_ALGORITHM: &[u8] = &[
+    0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00
+];
+
+/// DER encoding of the ECDSA with SHA-384 `AlgorithmIdentifier`.
+const ECDSA_SHA384_ALGORITHM: &[u8] = &[
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03
+];
+
+/// DER encoding of the `id-pkix-ocsp-basic` OID.
+const OCSP_BASIC_OID: &[u8] = &[
+    0x06, 0x09, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01
+];
+
+/// Generate a new private key.
+fn gen_key() -> Result<PKey<Private>, ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::SECP384R1)?;
+
+    PKey::from_ec_key(EcKey::generate(&group)?)
+}
+
+/// Convert a [SystemTime] to an [Asn1Time].
+fn asn1_time(time: SystemTime) -> Result<Asn1Time, ErrorStack> {
+    let secs = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(diff) => diff.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64)
+    };
+
+    Asn1Time::from_unix(secs)
+}
+
+/// Get the public key bits from a DER-encoded `SubjectPublicKeyInfo`.
+fn spki_key_bits(spki: &[u8]) -> Vec<u8> {
+    // SubjectPublicKeyInfo ::= SEQUENCE { algorithm, subjectPublicKey }
+    split_tlv(spki)
+        .and_then(|(_, content, _)| split_tlv(content))
+        .and_then(|(_, _, rest)| split_tlv(rest))
+        .ok()
+        .and_then(|(_, bits, _)| bits.get(1..))
+        .map(|bits| bits.to_vec())
+        .unwrap_or_default()
+}
+
+/// Write `contents` to `path`.
+fn write_file(
+    path: &Path,
+    contents: &[u8]
+) -> Result<(), TestPKIError> {
+    write(path, contents).map_err(|err| TestPKIError::IO {
+        path: path.to_path_buf(),
+        error: err
+    })
+}
+
+#[cfg(test)]
+use openssl::x509::X509PurposeId;
+
+#[cfg(test)]
+use crate::config::pki::constraints::PKIPeerConstraints;
+#[cfg(test)]
+use crate::config::pki::diagnostics::PKIChainError;
+#[cfg(test)]
+use crate::config::pki::PKITrustRoot;
+#[cfg(test)]
+use crate::init;
+
+#[cfg(test)]
+fn test_trust_root(
+    dir: &Path,
+    ca: &TestCA,
+    crls: Vec<PathBuf>
+) -> PKITrustRoot {
+    let path = dir.join("ca_cert.pem");
+
+    write_file(&path, &ca.root_cert().to_pem().unwrap()).unwrap();
+
+    PKITrustRoot::new(
+        vec![],
+        vec![path],
+        crls,
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    )
+}
+
+#[cfg(test)]
+fn test_rejection(result: Result<(), PKIChainError>) -> i32 {
+    match result {
+        Err(PKIChainError::Rejected { depth, reason, .. }) => {
+            assert_eq!(0, depth);
+
+            reason.as_raw()
+        }
+        result => panic!("Expected rejection, got {:?}", result)
+    }
+}
+
+#[test]
+fn test_generate_pki() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let server = pki.server_identity().load_cert_chain().unwrap();
+    let client = pki.client_identity().load_cert_chain().unwrap();
+    let endpoint =
+        IPEndpointAddr::name(String::from("test-server.nowhere.com"));
+    let root = |path: &str| {
+        PKITrustRoot::new(
+            vec![],
+            vec![pki.path(path)],
+            vec![],
+            vec![],
+            vec![],
+            None,
+            None,
+            None,
+            None
+        )
+    };
+
+    pki.server_identity().load().expect("Expected success");
+    pki.client_identity().load().expect("Expected success");
+    root("server/ca_cert.pem")
+        .check_chain(&server, None, Some(&endpoint), X509PurposeId::SSL_SERVER)
+        .expect("Expected success");
+    root("client/ca_cert.pem")
+        .check_chain(&client, None, None, X509PurposeId::SSL_CLIENT)
+        .expect("Expected success");
+    assert!(root("client/ca_cert.pem")
+        .check_chain(&server, None, None, X509PurposeId::SSL_SERVER)
+        .is_err());
+
+    for path in [
+        "server/ca_key.pem",
+        "server/crl/crl.pem",
+        "server/ocsp/ocsp_cert.pem",
+        "server/ocsp/test_revoked_ocsp.der",
+        "server/ocsp/test_server_ocsp.der",
+        "client/ca_key.pem"
+    ] {
+        assert!(pki.path(path).is_file(), "missing {}", path);
+    }
+
+    // The revoked certificate is rejected once the CRL is configured.
+    let revoked = pki.revoked_identity().load_cert_chain().unwrap();
+    let crl_root = PKITrustRoot::new(
+        vec![],
+        vec![pki.path("server/ca_cert.pem")],
+        vec![pki.path("server/crl/crl.pem")],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+
+    crl_root
+        .check_chain(&server, None, None, X509PurposeId::SSL_SERVER)
+        .expect("Expected success");
+    // X509_V_ERR_CERT_REVOKED
+    assert_eq!(
+        23,
+        test_rejection(crl_root.check_chain(
+            &revoked,
+            None,
+            None,
+            X509PurposeId::SSL_SERVER
+        ))
+    );
+}
+
+#[test]
+fn test_validity() {
+    init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let ca = TestCA::root("Test Validity CA").unwrap();
+    let root = test_trust_root(dir.path(), &ca, vec![]);
+    let name = || vec![IPEndpointAddr::name(String::from("test.nowhere.com"))];
+    let expired = ca
+        .issue(&TestCertSpec::expired("test.nowhere.com", name(), vec![]))
+        .unwrap();
+    let not_yet_valid = ca
+        .issue(&TestCertSpec::not_yet_valid(
+            "test.nowhere.com",
+            name(),
+            vec![]
+        ))
+        .unwrap();
+
+    assert!(expired.cert().not_after() < Asn1Time::days_from_now(0).unwrap());
+    // X509_V_ERR_CERT_HAS_EXPIRED
+    assert_eq!(
+        10,
+        test_rejection(root.check_chain(
+            expired.chain(),
+            None,
+            None,
+            X509PurposeId::ANY
+        ))
+    );
+    // X509_V_ERR_CERT_NOT_YET_VALID
+    assert_eq!(
+        9,
+        test_rejection(root.check_chain(
+            not_yet_valid.chain(),
+            None,
+            None,
+            X509PurposeId::ANY
+        ))
+    );
+}
+
+#[test]
+fn test_intermediate_alt_names_ekus() {
+    init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let ca = TestCA::root("Test Root CA").unwrap();
+    let intermediate = ca
+        .intermediate(&TestCertSpec::valid(
+            "Test Intermediate CA",
+            vec![],
+            vec![],
+            CA_VALIDITY
+        ))
+        .unwrap();
+    let addr = "127.0.0.1".parse().unwrap();
+    let leaf = intermediate
+        .issue(&TestCertSpec::valid(
+            "test.nowhere.com",
+            vec![
+                IPEndpointAddr::name(String::from("test.nowhere.com")),
+                IPEndpointAddr::ip(addr),
+            ],
+            vec![String::from("serverAuth")],
+            CERT_VALIDITY
+        ))
+        .unwrap();
+    let root = test_trust_root(dir.path(), &intermediate, vec![]);
+
+    assert_eq!(2, intermediate.chain().len());
+    assert_eq!(2, leaf.chain().len());
+    assert_eq!(
+        ca.cert().to_der().unwrap(),
+        intermediate.root_cert().to_der().unwrap()
+    );
+    root.check_chain(
+        leaf.chain(),
+        None,
+        Some(&IPEndpointAddr::ip(addr)),
+        X509PurposeId::SSL_SERVER
+    )
+    .expect("Expected success");
+
+    let constraints = |ekus: &[&str]| {
+        PKIPeerConstraints::new(
+            vec![],
+            vec![],
+            vec![String::from("*.nowhere.com")],
+            ekus.iter().map(|eku| String::from(*eku)).collect(),
+            vec![],
+            vec![]
+        )
+    };
+
+    let chain = || leaf.chain().iter().map(|cert| cert.as_ref());
+
+    assert!(constraints(&["serverAuth"]).check(chain()).is_ok());
+    assert!(constraints(&["clientAuth"]).check(chain()).is_err());
+}
@@ -16,24 +16,245 @@
 // License along with this program.  If not, see
 // <https://www.gnu.org/licenses/>.
 
+//! Configuration for OpenPGP signature verification.
+//!
+//! This provides [PGPTrustRoot], which describes a keyring and the
+//! keys within it that are ultimately trusted.  Loading a
+//! [PGPTrustRoot] produces a [PGPVerifier], which can verify
+//! detached OpenPGP signatures.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::read;
+use std::io::Error;
+use std::path::Path;
 use std::path::PathBuf;
 
+use log::debug;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::config::signing::pgp::PGPKeyring;
+use crate::config::signing::pgp::PGPKeyringError;
+use crate::config::signing::pgp::PGPVerifier;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+pub mod pgp;
+
+/// Validity levels for OpenPGP keys.
+///
+/// These are ordered from least to most trusted.  See the
+/// [pgp] module for how validity is computed.
+///
+/// # YAML Format
+///
+/// The YAML format is one of the strings `marginal`, `full`, or
+/// `ultimate`.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PGPTrustLevel {
+    /// Key is certified by a fully-valid key.
+    Marginal,
+    /// Key is certified by an ultimately-trusted key.
+    Full,
+    /// Key is ultimately trusted.
+    Ultimate
+}
+
+/// Configuration for an OpenPGP trust root.
+///
+/// This identifies a public keyring, the keys in it that are
+/// ultimately trusted, and the minimum validity a key must have for
+/// its signatures to be accepted.
+///
+/// # YAML Format
+///
+/// The YAML format has four fields:
+///
+/// - `path`: The path to the keyring, which may be binary or ASCII-armored (as
+///   produced by `gpg --export`).  GnuPG keybox files (`pubring.kbx`) are not
+///   supported.
+///
+/// - `trust-keys`: Fingerprints or (long) key IDs of keys that are ultimately
+///   trusted.
+///
+/// - `signing-key`: Fingerprint or (long) key ID of the key used to sign
+///   locally-produced data.  This must be present in the keyring.
+///
+/// - `min-trust`: The minimum [PGPTrustLevel] a key must have for its
+///   signatures to be accepted.
+///
+/// ## Examples
+///
+/// The following is an example of a YAML configuration:
+/// ```yaml
+/// path: /usr/local/etc/test/keyring.asc
+/// trust-keys:
+///   - 8AF996F0B8E59D78463C2F8373311A2CB3B58601
+/// signing-key: E5B86F1434CAB12BE8ADFFF6646908A82BA74F1B
+/// min-trust: full
+/// ```
 #[derive(
     Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
 )]
 #[serde(rename_all = "kebab-case")]
 #[serde(rename = "pgp-trust-root")]
 pub struct PGPTrustRoot {
-    /// Path to the PGP key wallet.
-    pub(crate) path: PathBuf,
-    pub(crate) trust_keys: Vec<String>,
-    pub(crate) signing_key: String,
-    pub(crate) min_trust: String
+    /// Path to the PGP keyring.
+    path: PathBuf,
+    /// Ultimately-trusted keys.
+    trust_keys: Vec<String>,
+    /// Key used for signing.
+    signing_key: String,
+    /// Minimum validity for signing keys.
+    min_trust: PGPTrustLevel
 }
 
+/// Errors that can occur when loading a [PGPTrustRoot].
+#[derive(Debug)]
+pub enum PGPTrustRootLoadError {
+    /// Error reading the keyring.
+    IO {
+        /// Path to the keyring.
+        path: PathBuf,
+        /// The IO error.
+        error: Error
+    },
+    /// Error loading the keyring.
+    Keyring {
+        /// Path to the keyring.
+        path: PathBuf,
+        /// The keyring error.
+        error: PGPKeyringError
+    }
+}
+
+impl ScopedError for PGPTrustRootLoadError {
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PGPTrustRootLoadError::IO { error, .. } => error.scope(),
+            PGPTrustRootLoadError::Keyring { .. } => ErrorScope::System
+        }
+    }
+}
+
+impl Display for PGPTrustRootLoadError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PGPTrustRootLoadError::IO { path, error } => {
+                write!(f, "error reading {}: {}", path.display(), error)
+            }
+            PGPTrustRootLoadError::Keyring { path, error } => {
+                write!(f, "error loading {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl Display for PGPTrustLevel {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PGPTrustLevel::Marginal => write!(f, "marginal"),
+            PGPTrustLevel::Full => write!(f, "full"),
+            PGPTrustLevel::Ultimate => write!(f, "ultimate")
+        }
+    }
+}
+
+impl PGPTrustRoot {
+    /// Create a new `PGPTrustRoot` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        path: PathBuf,
+        trust_keys: Vec<String>,
+        signing_key: String,
+        min_trust: PGPTrustLevel
+    ) -> Self {
+        PGPTrustRoot {
+            path: path,
+            trust_keys: trust_keys,
+            signing_key: signing_key,
+            min_trust: min_trust
+        }
+    }
+
+    /// Get the path to the keyring.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the ultimately-trusted keys.
+    #[inline]
+    pub fn trust_keys(&self) -> &[String] {
+        &self.trust_keys
+    }
+
+    /// Get the key used for signing.
+    #[inline]
+    pub fn signing_key(&self) -> &str {
+        &self.signing_key
+    }
+
+    /// Get the minimum validity for signing keys.
+    #[inline]
+    pub fn min_trust(&self) -> PGPTrustLevel {
+        self.min_trust
+    }
+
+    /// Load the keyring and create a [PGPVerifier] from this
+    /// configuration.
+    ///
+    /// This will fail if any of the trusted keys or the signing key
+    /// are not present in the keyring.
+    pub fn load(&self) -> Result<PGPVerifier, PGPTrustRootLoadError> {
+        debug!(target: "pgp-trust-root",
+               "loading PGP keyring from {}",
+               self.path.display());
+
+        let buf =
+            read(&self.path).map_err(|err| PGPTrustRootLoadError::IO {
+                path: self.path.clone(),
+                error: err
+            })?;
+        let keyring = PGPKeyring::parse(&buf, &self.trust_keys)
+            .and_then(|keyring| {
+                keyring.find(&self.signing_key)?;
+
+                Ok(keyring)
+            })
+            .map_err(|err| PGPTrustRootLoadError::Keyring {
+                path: self.path.clone(),
+                error: err
+            })?;
+
+        Ok(PGPVerifier::new(keyring, self.min_trust))
+    }
+}
+
+#[cfg(test)]
+use std::time::SystemTime;
+
 #[test]
 fn test_deserialize_tls_cfg_certs_dir() {
     let yaml = concat!(
@@ -51,9 +272,87 @@ fn test_deserialize_tls_cfg_certs_dir() {
             String::from("0987654321FEDCBA"),
         ],
         signing_key: String::from("ABCDEF1234567890"),
-        min_trust: String::from("ultimate")
+        min_trust: PGPTrustLevel::Ultimate
     };
     let actual = serde_yaml::from_str(yaml).unwrap();
 
     assert_eq!(expected, actual)
 }
+
+#[test]
+fn test_deserialize_bad_trust_level() {
+    let yaml = concat!(
+        "path: /usr/local/etc/test/keystore.pgp\n",
+        "trust-keys: []\n",
+        "signing-key: ABCDEF1234567890\n",
+        "min-trust: somewhat\n"
+    );
+
+    assert!(serde_yaml::from_str::<PGPTrustRoot>(yaml).is_err())
+}
+
+#[test]
+fn test_trust_level_order() {
+    assert!(PGPTrustLevel::Marginal < PGPTrustLevel::Full);
+    assert!(PGPTrustLevel::Full < PGPTrustLevel::Ultimate);
+}
+
+#[test]
+fn test_load_verify() {
+    let root = PGPTrustRoot::new(
+        PathBuf::from("test/data/pgp/keyring.asc"),
+        vec![String::from("8AF996F0B8E59D78463C2F8373311A2CB3B58601")],
+        String::from("E5B86F1434CAB12BE8ADFFF6646908A82BA74F1B"),
+        PGPTrustLevel::Full
+    );
+    let verifier = root.load().unwrap();
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read("test/data/pgp/sig_signer.asc").unwrap();
+    let signer = verifier
+        .verify_detached_at(&msg, &sig, SystemTime::now())
+        .unwrap();
+
+    assert_eq!(
+        "E5B86F1434CAB12BE8ADFFF6646908A82BA74F1B",
+        signer.fingerprint()
+    );
+    assert_eq!(Some(PGPTrustLevel::Full), signer.validity());
+
+    let sig = read("test/data/pgp/sig_other.asc").unwrap();
+    match verifier.verify_detached(&msg, &sig) {
+        Err(err) => assert_eq!(ErrorScope::Msg, err.scope()),
+        Ok(_) => panic!("expected untrusted error")
+    }
+}
+
+#[test]
+fn test_load_missing_signing_key() {
+    let root = PGPTrustRoot::new(
+        PathBuf::from("test/data/pgp/keyring.asc"),
+        vec![String::from("8AF996F0B8E59D78463C2F8373311A2CB3B58601")],
+        String::from("0123456789ABCDEF"),
+        PGPTrustLevel::Full
+    );
+
+    match root.load() {
+        Err(
+            err @ PGPTrustRootLoadError::Keyring {
+                error: PGPKeyringError::MissingKey { .. },
+                ..
+            }
+        ) => assert_eq!(ErrorScope::System, err.scope()),
+        _ => panic!("expected missing key error")
+    }
+}
+
+#[test]
+fn test_load_missing_file() {
+    let root = PGPTrustRoot::new(
+        PathBuf::from("test/data/pgp/nonexistent.asc"),
+        vec![],
+        String::from("0123456789ABCDEF"),
+        PGPTrustLevel::Full
+    );
+
+    assert!(matches!(root.load(), Err(PGPTrustRootLoadError::IO { .. })))
+}
@@ -0,0 +1,2163 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! OpenPGP keyrings and detached signature verification.
+//!
+//! This implements the subset of OpenPGP (RFC 4880) needed to load
+//! public keyrings (as produced by `gpg --export`, either binary or
+//! ASCII-armored) and verify detached signatures against them.  Only
+//! version 4 keys and signatures are supported, with RSA, ECDSA over
+//! the NIST curves, and EdDSA over Ed25519.  Signatures must use a
+//! SHA-2 digest; MD5 and SHA-1 signatures are rejected.
+//!
+//! # Validity
+//!
+//! The validity of each key is computed from a fixed set of
+//! ultimately-trusted keys:
+//!
+//! - Ultimately-trusted keys have [Ultimate](PGPTrustLevel::Ultimate) validity.
+//!
+//! - Keys with a user ID certified by a key with
+//!   [Ultimate](PGPTrustLevel::Ultimate) validity have
+//!   [Full](PGPTrustLevel::Full) validity.
+//!
+//! - Keys with a user ID certified by a key with [Full](PGPTrustLevel::Full)
+//!   validity have [Marginal](PGPTrustLevel::Marginal) validity.
+//!
+//! All other keys have no validity.  This is a deliberately
+//! simplified form of the GnuPG web of trust: owner trust,
+//! trust signatures, and third-party certification revocations are
+//! not considered.
+//!
+//! Certification signatures are checked once, when the keyring is
+//! loaded.  Validity itself is computed at the time given for each
+//! verification, so keys and certifications that expire after the
+//! keyring is loaded stop conferring validity.
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use openssl::base64::decode_block;
+use openssl::bn::BigNum;
+use openssl::bn::BigNumContext;
+use openssl::ec::EcGroup;
+use openssl::ec::EcKey;
+use openssl::ec::EcPoint;
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::Hasher;
+use openssl::hash::MessageDigest;
+use openssl::md::Md;
+use openssl::nid::Nid;
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use openssl::pkey::Public;
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::Padding;
+use openssl::rsa::Rsa;
+use openssl::sha::Sha1;
+use openssl::sign::Verifier;
+
+use crate::config::signing::PGPTrustLevel;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+const ARMOR_BEGIN: &str = "-----BEGIN PGP ";
+const ARMOR_END: &str = "-----END PGP ";
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_TRUST: u8 = 12;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTR: u8 = 17;
+
+const SIG_BINARY: u8 = 0x00;
+const SIG_TEXT: u8 = 0x01;
+const SIG_CERT_GENERIC: u8 = 0x10;
+const SIG_CERT_POSITIVE: u8 = 0x13;
+const SIG_SUBKEY_BINDING: u8 = 0x18;
+const SIG_PRIMARY_BINDING: u8 = 0x19;
+const SIG_DIRECT_KEY: u8 = 0x1f;
+const SIG_KEY_REVOCATION: u8 = 0x20;
+const SIG_SUBKEY_REVOCATION: u8 = 0x28;
+const SIG_CERT_REVOCATION: u8 = 0x30;
+
+const ALGO_RSA: u8 = 1;
+const ALGO_RSA_SIGN: u8 = 3;
+const ALGO_ECDSA: u8 = 19;
+const ALGO_EDDSA: u8 = 22;
+
+const SUBPACKET_CREATED: u8 = 2;
+const SUBPACKET_SIG_EXPIRES: u8 = 3;
+const SUBPACKET_KEY_EXPIRES: u8 = 9;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const SUBPACKET_EMBEDDED_SIG: u8 = 32;
+const SUBPACKET_ISSUER_FPR: u8 = 33;
+
+/// Subpackets that are informational, and may safely be ignored
+/// even when marked critical.
+const SUBPACKET_IGNORABLE: [u8; 8] = [4, 11, 21, 22, 23, 25, 30, 34];
+
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+const OID_NIST_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_NIST_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_NIST_P521: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+const OID_ED25519: &[u8] =
+    &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+/// Errors that can occur when parsing OpenPGP data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PGPParseError {
+    /// Malformed ASCII armor.
+    BadArmor,
+    /// ASCII armor checksum did not match.
+    BadChecksum,
+    /// Data ended unexpectedly.
+    Truncated,
+    /// Malformed packet header.
+    BadHeader,
+    /// Partial body lengths were used in a key or signature packet.
+    PartialLength,
+    /// Unsupported packet version.
+    BadVersion {
+        /// Packet tag.
+        tag: u8,
+        /// Packet version.
+        version: u8
+    },
+    /// Unexpected packet.
+    UnexpectedPacket {
+        /// Packet tag.
+        tag: u8
+    },
+    /// Malformed public key material.
+    BadKey,
+    /// Signature has no creation time.
+    NoCreationTime
+}
+
+/// Errors that can occur when loading a [PGPKeyring].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PGPKeyringError {
+    /// Error parsing the keyring data.
+    Parse {
+        /// The parse error.
+        error: PGPParseError
+    },
+    /// A key was identified by something other than a key ID or
+    /// fingerprint.
+    BadKeySpec {
+        /// The key specifier.
+        key: String
+    },
+    /// A key was not present in the keyring.
+    MissingKey {
+        /// The key specifier.
+        key: String
+    }
+}
+
+/// Errors that can occur when verifying a detached signature.
+#[derive(Debug)]
+pub enum PGPVerifyError {
+    /// Error parsing the signature.
+    Parse {
+        /// The parse error.
+        error: PGPParseError
+    },
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// No signature packets were present.
+    NoSignature,
+    /// Signature is not a document signature.
+    NotDocument {
+        /// Signature type.
+        sig_type: u8
+    },
+    /// Signature has an unrecognized critical subpacket.
+    CriticalSubpacket,
+    /// Unsupported public-key algorithm.
+    UnsupportedAlgo {
+        /// Algorithm identifier.
+        algo: u8
+    },
+    /// Unsupported hash algorithm.
+    UnsupportedHash {
+        /// Algorithm identifier.
+        algo: u8
+    },
+    /// Signing key is not in the keyring.
+    UnknownKey {
+        /// Issuer key ID or fingerprint, if the signature has one.
+        issuer: Option<String>
+    },
+    /// Signing key is not usable for signatures.
+    NotSigningKey {
+        /// Fingerprint of the key.
+        fingerprint: String
+    },
+    /// Signing key has been revoked.
+    Revoked {
+        /// Fingerprint of the key.
+        fingerprint: String
+    },
+    /// Signing key has expired.
+    KeyExpired {
+        /// Fingerprint of the key.
+        fingerprint: String
+    },
+    /// Signature has expired.
+    SignatureExpired,
+    /// Signature did not verify.
+    BadSignature,
+    /// Signing key does not have sufficient validity.
+    Untrusted {
+        /// Fingerprint of the key.
+        fingerprint: String,
+        /// Validity of the key.
+        validity: Option<PGPTrustLevel>
+    }
+}
+
+/// A version 4 OpenPGP public key or subkey.
+#[derive(Clone)]
+pub struct PGPPublicKey {
+    body: Vec<u8>,
+    created: u32,
+    fingerprint: [u8; 20],
+    key: KeyMaterial
+}
+
+/// A primary key in a [PGPKeyring], with its valid user IDs and
+/// signing subkeys.
+#[derive(Clone)]
+pub struct PGPKey {
+    primary: PGPPublicKey,
+    user_ids: Vec<String>,
+    subkeys: Vec<PGPSubkey>,
+    certs: Vec<Certification>,
+    can_sign: bool,
+    expires: Option<u32>,
+    revoked: bool
+}
+
+/// A set of OpenPGP keys, with validity computed from a set of
+/// ultimately-trusted keys.
+///
+/// See the [module documentation](self) for how validity is
+/// computed.
+#[derive(Clone)]
+pub struct PGPKeyring {
+    keys: Vec<PGPKey>,
+    /// Indexes of the ultimately-trusted keys.
+    trusted: Vec<usize>,
+    /// Verified certifications between keys.
+    edges: Vec<Edge>
+}
+
+/// Verifier for detached OpenPGP signatures.
+///
+/// This combines a [PGPKeyring] with the minimum validity that a
+/// signing key must have.  This is obtained by loading a
+/// [PGPTrustRoot](crate::config::signing::PGPTrustRoot).
+#[derive(Clone)]
+pub struct PGPVerifier {
+    keyring: PGPKeyring,
+    min_trust: PGPTrustLevel
+}
+
+/// Information about the signer of a verified detached signature.
+pub struct PGPSigner<'a> {
+    key: &'a PGPKey,
+    signing_key: &'a PGPPublicKey,
+    validity: Option<PGPTrustLevel>,
+    created: u32
+}
+
+#[derive(Clone)]
+struct PGPSubkey {
+    key: PGPPublicKey,
+    expires: Option<u32>
+}
+
+#[derive(Clone)]
+struct Certification {
+    uid: Vec<u8>,
+    sig: Signature
+}
+
+/// A certification of one key's user ID by another, whose signature
+/// has been verified.
+#[derive(Clone)]
+struct Edge {
+    issuer: usize,
+    target: usize,
+    /// Index of the certification in the target's `certs`.
+    cert: usize
+}
+
+#[derive(Clone)]
+enum KeyMaterial {
+    Rsa { pkey: PKey<Public> },
+    Ecdsa { pkey: PKey<Public> },
+    Eddsa { pkey: PKey<Public> },
+    Unsupported { algo: u8 }
+}
+
+#[derive(Clone)]
+struct Signature {
+    sig_type: u8,
+    pub_algo: u8,
+    hash_algo: u8,
+    hashed: Vec<u8>,
+    left16: [u8; 2],
+    mpis: Vec<Vec<u8>>,
+    created: Option<u32>,
+    expires: Option<u32>,
+    key_expires: Option<u32>,
+    key_flags: Option<u8>,
+    issuer_id: Option<[u8; 8]>,
+    issuer_fpr: Option<[u8; 20]>,
+    embedded: Option<Box<Signature>>,
+    critical_unknown: bool
+}
+
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8]
+}
+
+struct Reader<'a> {
+    buf: &'a [u8]
+}
+
+enum Component {
+    Primary,
+    UserID,
+    Attribute,
+    Subkey
+}
+
+struct RawKey {
+    primary: PGPPublicKey,
+    direct: Vec<Signature>,
+    uids: Vec<(Vec<u8>, Vec<Signature>)>,
+    subkeys: Vec<(PGPPublicKey, Vec<Signature>)>
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf: buf }
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn bytes(
+        &mut self,
+        len: usize
+    ) -> Result<&'a [u8], PGPParseError> {
+        if len <= self.buf.len() {
+            let (out, rest) = self.buf.split_at(len);
+
+            self.buf = rest;
+
+            Ok(out)
+        } else {
+            Err(PGPParseError::Truncated)
+        }
+    }
+
+    #[inline]
+    fn u8(&mut self) -> Result<u8, PGPParseError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    #[inline]
+    fn u16(&mut self) -> Result<u16, PGPParseError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    #[inline]
+    fn u32(&mut self) -> Result<u32, PGPParseError> {
+        let bytes = self.bytes(4)?;
+
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    #[inline]
+    fn mpi(&mut self) -> Result<&'a [u8], PGPParseError> {
+        let bits = self.u16()? as usize;
+
+        self.bytes(bits.div_ceil(8))
+    }
+
+    #[inline]
+    fn oid(&mut self) -> Result<&'a [u8], PGPParseError> {
+        let len = self.u8()? as usize;
+
+        self.bytes(len)
+    }
+}
+
+impl PGPPublicKey {
+    fn parse(
+        tag: u8,
+        body: &[u8]
+    ) -> Result<Self, PGPParseError> {
+        if body.len() > u16::MAX as usize {
+            return Err(PGPParseError::BadHeader);
+        }
+
+        let mut reader = Reader::new(body);
+        let version = reader.u8()?;
+
+        if version != 4 {
+            return Err(PGPParseError::BadVersion {
+                tag: tag,
+                version: version
+            });
+        }
+
+        let created = reader.u32()?;
+        let algo = reader.u8()?;
+        let key = KeyMaterial::parse(algo, &mut reader)?;
+        let mut sha1 = Sha1::new();
+
+        sha1.update(&key_header(body));
+        sha1.update(body);
+
+        Ok(PGPPublicKey {
+            body: body.to_vec(),
+            created: created,
+            fingerprint: sha1.finish(),
+            key: key
+        })
+    }
+
+    /// Get the fingerprint of this key, as uppercase hex.
+    #[inline]
+    pub fn fingerprint(&self) -> String {
+        hex(&self.fingerprint)
+    }
+
+    /// Get the (long) key ID of this key, as uppercase hex.
+    #[inline]
+    pub fn key_id(&self) -> String {
+        hex(&self.fingerprint[12..])
+    }
+
+    /// Get the creation time of this key.
+    #[inline]
+    pub fn created(&self) -> SystemTime {
+        timestamp(self.created)
+    }
+
+    #[inline]
+    fn matches(
+        &self,
+        spec: &[u8]
+    ) -> bool {
+        spec == self.fingerprint || spec == &self.fingerprint[12..]
+    }
+
+    fn issued(
+        &self,
+        sig: &Signature
+    ) -> bool {
+        match (&sig.issuer_fpr, &sig.issuer_id) {
+            (Some(fpr), _) => fpr == &self.fingerprint,
+            (None, Some(id)) => id[..] == self.fingerprint[12..],
+            (None, None) => false
+        }
+    }
+
+    #[inline]
+    fn header(&self) -> [u8; 3] {
+        key_header(&self.body)
+    }
+
+    fn expired(
+        &self,
+        expires: Option<u32>,
+        now: SystemTime
+    ) -> bool {
+        match expires {
+            Some(0) | None => false,
+            Some(secs) => {
+                timestamp(self.created) + Duration::from_secs(secs as u64) <=
+                    now
+            }
+        }
+    }
+
+    fn verify(
+        &self,
+        sig: &Signature,
+        parts: &[&[u8]]
+    ) -> Result<(), PGPVerifyError> {
+        let (md, md_ref) = match sig.hash_algo {
+            8 => (MessageDigest::sha256(), Md::sha256()),
+            9 => (MessageDigest::sha384(), Md::sha384()),
+            10 => (MessageDigest::sha512(), Md::sha512()),
+            11 => (MessageDigest::sha224(), Md::sha224()),
+            algo => return Err(PGPVerifyError::UnsupportedHash { algo: algo })
+        };
+        let mut hasher = Hasher::new(md)
+            .map_err(|err| PGPVerifyError::OpenSSL { error: err })?;
+
+        for part in parts {
+            hasher
+                .update(part)
+                .map_err(|err| PGPVerifyError::OpenSSL { error: err })?;
+        }
+
+        let trailer = (sig.hashed.len() as u32).to_be_bytes();
+
+        hasher
+            .update(&sig.hashed)
+            .and_then(|_| hasher.update(&[0x04, 0xff]))
+            .and_then(|_| hasher.update(&trailer))
+            .map_err(|err| PGPVerifyError::OpenSSL { error: err })?;
+
+        let digest = hasher
+            .finish()
+            .map_err(|err| PGPVerifyError::OpenSSL { error: err })?;
+
+        if digest[..2] != sig.left16 {
+            return Err(PGPVerifyError::BadSignature);
+        }
+
+        // Verification failures from OpenSSL indicate bad signatures,
+        // not library errors.
+        let valid = match (&self.key, sig.pub_algo) {
+            (KeyMaterial::Rsa { pkey }, ALGO_RSA | ALGO_RSA_SIGN) => {
+                let size = pkey.size();
+                let s = &sig.mpis[0];
+
+                if s.len() > size {
+                    return Err(PGPVerifyError::BadSignature);
+                }
+
+                let mut padded = vec![0; size - s.len()];
+
+                padded.extend_from_slice(s);
+
+                PkeyCtx::new(pkey)
+                    .and_then(|mut ctx| {
+                        ctx.verify_init()?;
+                        ctx.set_rsa_padding(Padding::PKCS1)?;
+                        ctx.set_signature_md(md_ref)?;
+                        ctx.verify(&digest, &padded)
+                    })
+                    .unwrap_or(false)
+            }
+            (KeyMaterial::Ecdsa { pkey }, ALGO_ECDSA) => {
+                let der = BigNum::from_slice(&sig.mpis[0])
+                    .and_then(|r| {
+                        let s = BigNum::from_slice(&sig.mpis[1])?;
+
+                        EcdsaSig::from_private_components(r, s)?.to_der()
+                    })
+                    .map_err(|err| PGPVerifyError::OpenSSL { error: err })?;
+
+                PkeyCtx::new(pkey)
+                    .and_then(|mut ctx| {
+                        ctx.verify_init()?;
+                        ctx.verify(&digest, &der)
+                    })
+                    .unwrap_or(false)
+            }
+            (KeyMaterial::Eddsa { pkey }, ALGO_EDDSA) => {
+                let (r, s) = (&sig.mpis[0], &sig.mpis[1]);
+
+                if r.len() > 32 || s.len() > 32 {
+                    return Err(PGPVerifyError::BadSignature);
+                }
+
+                let mut raw = [0; 64];
+
+                raw[32 - r.len()..32].copy_from_slice(r);
+                raw[64 - s.len()..].copy_from_slice(s);
+
+                // OpenPGP EdDSA signs the digest, not the data.
+                Verifier::new_without_digest(pkey)
+                    .and_then(|mut verifier| {
+                        verifier.verify_oneshot(&raw, &digest)
+                    })
+                    .unwrap_or(false)
+            }
+            (KeyMaterial::Unsupported { algo }, _) => {
+                return Err(PGPVerifyError::UnsupportedAlgo { algo: *algo })
+            }
+            _ => false
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(PGPVerifyError::BadSignature)
+        }
+    }
+}
+
+impl KeyMaterial {
+    fn parse(
+        algo: u8,
+        reader: &mut Reader
+    ) -> Result<Self, PGPParseError> {
+        match algo {
+            ALGO_RSA | ALGO_RSA_SIGN => {
+                let n = reader.mpi()?;
+                let e = reader.mpi()?;
+                let pkey = BigNum::from_slice(n)
+                    .and_then(|n| {
+                        let e = BigNum::from_slice(e)?;
+
+                        PKey::from_rsa(Rsa::from_public_components(n, e)?)
+                    })
+                    .map_err(|_| PGPParseError::BadKey)?;
+
+                Ok(KeyMaterial::Rsa { pkey: pkey })
+            }
+            ALGO_ECDSA => {
+                let nid = match reader.oid()? {
+                    OID_NIST_P256 => Nid::X9_62_PRIME256V1,
+                    OID_NIST_P384 => Nid::SECP384R1,
+                    OID_NIST_P521 => Nid::SECP521R1,
+                    _ => return Ok(KeyMaterial::Unsupported { algo: algo })
+                };
+                let point = reader.mpi()?;
+                let pkey = EcGroup::from_curve_name(nid)
+                    .and_then(|group| {
+                        let mut ctx = BigNumContext::new()?;
+                        let point =
+                            EcPoint::from_bytes(&group, point, &mut ctx)?;
+
+                        PKey::from_ec_key(EcKey::from_public_key(
+                            &group, &point
+                        )?)
+                    })
+                    .map_err(|_| PGPParseError::BadKey)?;
+
+                Ok(KeyMaterial::Ecdsa { pkey: pkey })
+            }
+            ALGO_EDDSA => {
+                if reader.oid()? != OID_ED25519 {
+                    return Ok(KeyMaterial::Unsupported { algo: algo });
+                }
+
+                // Points are prefixed with 0x40.
+                let point = reader.mpi()?;
+
+                if point.len() != 33 || point[0] != 0x40 {
+                    return Err(PGPParseError::BadKey);
+                }
+
+                let pkey =
+                    PKey::public_key_from_raw_bytes(&point[1..], Id::ED25519)
+                        .map_err(|_| PGPParseError::BadKey)?;
+
+                Ok(KeyMaterial::Eddsa { pkey: pkey })
+            }
+            _ => Ok(KeyMaterial::Unsupported { algo: algo })
+        }
+    }
+}
+
+impl Signature {
+    fn parse(body: &[u8]) -> Result<Self, PGPParseError> {
+        let mut reader = Reader::new(body);
+        let version = reader.u8()?;
+
+        if version != 4 {
+            return Err(PGPParseError::BadVersion {
+                tag: TAG_SIGNATURE,
+                version: version
+            });
+        }
+
+        let sig_type = reader.u8()?;
+        let pub_algo = reader.u8()?;
+        let hash_algo = reader.u8()?;
+        let hashed_len = reader.u16()? as usize;
+        let hashed_subpackets = reader.bytes(hashed_len)?;
+        let unhashed_len = reader.u16()? as usize;
+        let unhashed_subpackets = reader.bytes(unhashed_len)?;
+        let left16 = reader.bytes(2)?;
+        let nmpis = match pub_algo {
+            ALGO_RSA | ALGO_RSA_SIGN => 1,
+            ALGO_ECDSA | ALGO_EDDSA => 2,
+            _ => 0
+        };
+        let mut mpis = Vec::with_capacity(nmpis);
+
+        for _ in 0..nmpis {
+            mpis.push(reader.mpi()?.to_vec());
+        }
+
+        let mut sig = Signature {
+            sig_type: sig_type,
+            pub_algo: pub_algo,
+            hash_algo: hash_algo,
+            hashed: body[..6 + hashed_len].to_vec(),
+            left16: [left16[0], left16[1]],
+            mpis: mpis,
+            created: None,
+            expires: None,
+            key_expires: None,
+            key_flags: None,
+            issuer_id: None,
+            issuer_fpr: None,
+            embedded: None,
+            critical_unknown: false
+        };
+
+        sig.subpackets(hashed_subpackets, true)?;
+        sig.subpackets(unhashed_subpackets, false)?;
+
+        Ok(sig)
+    }
+
+    fn subpackets(
+        &mut self,
+        buf: &[u8],
+        hashed: bool
+    ) -> Result<(), PGPParseError> {
+        let mut reader = Reader::new(buf);
+
+        while !reader.is_empty() {
+            let first = reader.u8()? as usize;
+            let len = match first {
+                0..=191 => first,
+                192..=254 => ((first - 192) << 8) + reader.u8()? as usize + 192,
+                _ => reader.u32()? as usize
+            };
+            let mut data = Reader::new(reader.bytes(len)?);
+            let kind = data.u8()?;
+            let critical = kind & 0x80 != 0;
+
+            // Only the issuer and embedded signatures are trusted
+            // from the unhashed area; both are verified separately.
+            match (kind & 0x7f, hashed) {
+                (SUBPACKET_CREATED, true) => self.created = Some(data.u32()?),
+                (SUBPACKET_SIG_EXPIRES, true) => {
+                    self.expires = Some(data.u32()?)
+                }
+                (SUBPACKET_KEY_EXPIRES, true) => {
+                    self.key_expires = Some(data.u32()?)
+                }
+                (SUBPACKET_KEY_FLAGS, true) => {
+                    self.key_flags = Some(data.bytes(1)?[0])
+                }
+                (SUBPACKET_ISSUER, _) => {
+                    let id = data.bytes(8)?;
+                    let mut buf = [0; 8];
+
+                    buf.copy_from_slice(id);
+                    self.issuer_id = self.issuer_id.or(Some(buf));
+                }
+                // Only version 4 fingerprints are understood.
+                (SUBPACKET_ISSUER_FPR, _) if data.buf.first() == Some(&4) => {
+                    let fpr = data.bytes(21)?;
+                    let mut buf = [0; 20];
+
+                    buf.copy_from_slice(&fpr[1..]);
+                    self.issuer_fpr = self.issuer_fpr.or(Some(buf));
+                }
+                (SUBPACKET_EMBEDDED_SIG, _) => {
+                    let sig = Signature::parse(data.bytes(len - 1)?)?;
+
+                    self.embedded = Some(Box::new(sig))
+                }
+                (kind, true)
+                    if critical && !SUBPACKET_IGNORABLE.contains(&kind) =>
+                {
+                    self.critical_unknown = true
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn is_certification(&self) -> bool {
+        (SIG_CERT_GENERIC..=SIG_CERT_POSITIVE).contains(&self.sig_type)
+    }
+
+    #[inline]
+    fn created(&self) -> u32 {
+        self.created.unwrap_or(0)
+    }
+
+    fn expired(
+        &self,
+        now: SystemTime
+    ) -> bool {
+        match self.expires {
+            Some(0) | None => false,
+            Some(secs) => {
+                timestamp(self.created()) + Duration::from_secs(secs as u64) <=
+                    now
+            }
+        }
+    }
+
+    fn issuer(&self) -> Option<String> {
+        match (&self.issuer_fpr, &self.issuer_id) {
+            (Some(fpr), _) => Some(hex(fpr)),
+            (None, Some(id)) => Some(hex(id)),
+            (None, None) => None
+        }
+    }
+}
+
+impl RawKey {
+    fn new(primary: PGPPublicKey) -> Self {
+        RawKey {
+            primary: primary,
+            direct: vec![],
+            uids: vec![],
+            subkeys: vec![]
+        }
+    }
+
+    fn check_uid(
+        &self,
+        uid: &[u8],
+        signer: &PGPPublicKey,
+        sig: &Signature
+    ) -> bool {
+        let hdr = self.primary.header();
+        let uid_hdr = uid_header(uid);
+
+        !sig.critical_unknown &&
+            signer
+                .verify(sig, &[&hdr, &self.primary.body, &uid_hdr, uid])
+                .is_ok()
+    }
+
+    fn check_subkey(
+        &self,
+        subkey: &PGPPublicKey,
+        signer: &PGPPublicKey,
+        sig: &Signature
+    ) -> bool {
+        let hdr = self.primary.header();
+        let sub_hdr = subkey.header();
+        let parts: [&[u8]; 4] =
+            [&hdr, &self.primary.body, &sub_hdr, &subkey.body];
+
+        !sig.critical_unknown && signer.verify(sig, &parts).is_ok()
+    }
+
+    fn check_direct(
+        &self,
+        sig: &Signature
+    ) -> bool {
+        let hdr = self.primary.header();
+
+        self.primary.issued(sig) &&
+            !sig.critical_unknown &&
+            self.primary
+                .verify(sig, &[&hdr, &self.primary.body])
+                .is_ok()
+    }
+
+    /// Check all self-signatures, keeping only valid user IDs and
+    /// signing subkeys.
+    fn validate(self) -> PGPKey {
+        let revoked = self.direct.iter().any(|sig| {
+            sig.sig_type == SIG_KEY_REVOCATION && self.check_direct(sig)
+        });
+        let mut latest: Option<&Signature> = self.direct.iter().find(|sig| {
+            sig.sig_type == SIG_DIRECT_KEY && self.check_direct(sig)
+        });
+        let mut user_ids = vec![];
+        let mut certs = vec![];
+
+        for (uid, sigs) in &self.uids {
+            let selfsig = sigs
+                .iter()
+                .filter(|sig| {
+                    sig.is_certification() &&
+                        self.primary.issued(sig) &&
+                        self.check_uid(uid, &self.primary, sig)
+                })
+                .max_by_key(|sig| sig.created());
+            let uid_revoked = sigs.iter().any(|sig| {
+                sig.sig_type == SIG_CERT_REVOCATION &&
+                    self.primary.issued(sig) &&
+                    self.check_uid(uid, &self.primary, sig)
+            });
+
+            if let (Some(selfsig), false) = (selfsig, uid_revoked) {
+                if latest.is_none_or(|sig| sig.created() < selfsig.created()) {
+                    latest = Some(selfsig)
+                }
+
+                user_ids.push(String::from_utf8_lossy(uid).into_owned());
+
+                for sig in sigs {
+                    if sig.is_certification() && !self.primary.issued(sig) {
+                        certs.push(Certification {
+                            uid: uid.clone(),
+                            sig: sig.clone()
+                        })
+                    }
+                }
+            }
+        }
+
+        let mut subkeys = vec![];
+
+        for (subkey, sigs) in &self.subkeys {
+            let binding = sigs
+                .iter()
+                .filter(|sig| {
+                    sig.sig_type == SIG_SUBKEY_BINDING &&
+                        self.primary.issued(sig) &&
+                        self.check_subkey(subkey, &self.primary, sig)
+                })
+                .max_by_key(|sig| sig.created());
+            let sub_revoked = sigs.iter().any(|sig| {
+                sig.sig_type == SIG_SUBKEY_REVOCATION &&
+                    self.primary.issued(sig) &&
+                    self.check_subkey(subkey, &self.primary, sig)
+            });
+
+            if let (Some(binding), false) = (binding, sub_revoked) {
+                // Signing subkeys must cross-certify the primary key.
+                let signs = binding
+                    .key_flags
+                    .is_some_and(|flags| flags & KEY_FLAG_SIGN != 0);
+                let backsig = binding.embedded.as_ref().is_some_and(|sig| {
+                    sig.sig_type == SIG_PRIMARY_BINDING &&
+                        self.check_subkey(subkey, subkey, sig)
+                });
+
+                if signs && backsig {
+                    subkeys.push(PGPSubkey {
+                        key: subkey.clone(),
+                        expires: binding.key_expires
+                    })
+                }
+            }
+        }
+
+        // As with subkeys, only the signing flag allows data
+        // signatures; a certify-only primary key must sign using a
+        // subkey.
+        let can_sign = latest
+            .and_then(|sig| sig.key_flags)
+            .is_none_or(|flags| flags & KEY_FLAG_SIGN != 0);
+        let expires = latest.and_then(|sig| sig.key_expires);
+
+        PGPKey {
+            primary: self.primary,
+            user_ids: user_ids,
+            subkeys: subkeys,
+            certs: certs,
+            can_sign: can_sign,
+            expires: expires,
+            revoked: revoked
+        }
+    }
+}
+
+impl PGPKey {
+    /// Get the primary public key.
+    #[inline]
+    pub fn primary(&self) -> &PGPPublicKey {
+        &self.primary
+    }
+
+    /// Get the fingerprint of the primary key, as uppercase hex.
+    #[inline]
+    pub fn fingerprint(&self) -> String {
+        self.primary.fingerprint()
+    }
+
+    /// Get the (long) key ID of the primary key, as uppercase hex.
+    #[inline]
+    pub fn key_id(&self) -> String {
+        self.primary.key_id()
+    }
+
+    /// Get the valid (self-certified and not revoked) user IDs.
+    #[inline]
+    pub fn user_ids(&self) -> &[String] {
+        &self.user_ids
+    }
+
+    /// Get the expiration time of the key, if it has one.
+    #[inline]
+    pub fn expires(&self) -> Option<SystemTime> {
+        match self.expires {
+            Some(0) | None => None,
+            Some(secs) => {
+                Some(self.primary.created() + Duration::from_secs(secs as u64))
+            }
+        }
+    }
+
+    /// Check whether the key has been revoked.
+    #[inline]
+    pub fn is_revoked(&self) -> bool {
+        self.revoked
+    }
+
+    /// Check whether the key is usable at time `now`.
+    #[inline]
+    fn usable(
+        &self,
+        now: SystemTime
+    ) -> bool {
+        !self.revoked &&
+            !self.user_ids.is_empty() &&
+            !self.primary.expired(self.expires, now)
+    }
+
+    /// Find the key (primary or subkey) that issued `sig`.
+    fn signing_key(
+        &self,
+        sig: &Signature
+    ) -> Option<(&PGPPublicKey, Option<u32>)> {
+        if self.primary.issued(sig) {
+            Some((&self.primary, self.expires))
+        } else {
+            self.subkeys
+                .iter()
+                .find(|subkey| subkey.key.issued(sig))
+                .map(|subkey| (&subkey.key, subkey.expires))
+        }
+    }
+}
+
+impl PGPKeyring {
+    /// Parse a keyring from `buf`, which may be binary or
+    /// ASCII-armored.
+    ///
+    /// The keys identified by `trust_keys` will be ultimately
+    /// trusted.  These may be given either as fingerprints or as
+    /// (long) key IDs, in hex.
+    pub fn parse(
+        buf: &[u8],
+        trust_keys: &[String]
+    ) -> Result<Self, PGPKeyringError> {
+        let data = dearmor(buf)
+            .map_err(|err| PGPKeyringError::Parse { error: err })?;
+        let packets = packets(&data)
+            .map_err(|err| PGPKeyringError::Parse { error: err })?;
+        let raw = raw_keys(&packets)
+            .map_err(|err| PGPKeyringError::Parse { error: err })?;
+        let mut keyring = PGPKeyring {
+            keys: raw.into_iter().map(RawKey::validate).collect(),
+            trusted: Vec::with_capacity(trust_keys.len()),
+            edges: vec![]
+        };
+
+        for key in trust_keys {
+            let idx = keyring.index(key)?;
+
+            keyring.trusted.push(idx)
+        }
+
+        keyring.edges = keyring.certifications();
+
+        Ok(keyring)
+    }
+
+    /// Get all keys in the keyring.
+    #[inline]
+    pub fn keys(&self) -> &[PGPKey] {
+        &self.keys
+    }
+
+    /// Find the key identified by `key`, given as a fingerprint or
+    /// (long) key ID in hex.
+    #[inline]
+    pub fn find(
+        &self,
+        key: &str
+    ) -> Result<&PGPKey, PGPKeyringError> {
+        self.index(key).map(|idx| &self.keys[idx])
+    }
+
+    /// Get the validity at time `now` of the key identified by `key`,
+    /// given as a fingerprint or (long) key ID in hex.
+    #[inline]
+    pub fn validity_at(
+        &self,
+        key: &str,
+        now: SystemTime
+    ) -> Result<Option<PGPTrustLevel>, PGPKeyringError> {
+        let idx = self.index(key)?;
+
+        Ok(self.validities(now)[idx])
+    }
+
+    fn index(
+        &self,
+        key: &str
+    ) -> Result<usize, PGPKeyringError> {
+        let spec =
+            parse_key_spec(key).ok_or_else(|| PGPKeyringError::BadKeySpec {
+                key: key.to_string()
+            })?;
+
+        self.keys
+            .iter()
+            .position(|k| k.primary.matches(&spec))
+            .ok_or_else(|| PGPKeyringError::MissingKey {
+                key: key.to_string()
+            })
+    }
+
+    /// Find all certifications of one key by another whose
+    /// signatures verify.
+    ///
+    /// Expiry is not considered here, as it depends on the time at
+    /// which validity is computed.
+    fn certifications(&self) -> Vec<Edge> {
+        let mut edges = vec![];
+
+        for (target, key) in self.keys.iter().enumerate() {
+            for (idx, cert) in key.certs.iter().enumerate() {
+                for (issuer, signer) in self.keys.iter().enumerate() {
+                    let hdr = key.primary.header();
+                    let uid_hdr = uid_header(&cert.uid);
+                    let parts: [&[u8]; 4] =
+                        [&hdr, &key.primary.body, &uid_hdr, &cert.uid];
+
+                    if issuer != target &&
+                        signer.primary.issued(&cert.sig) &&
+                        !cert.sig.critical_unknown &&
+                        signer.primary.verify(&cert.sig, &parts).is_ok()
+                    {
+                        edges.push(Edge {
+                            issuer: issuer,
+                            target: target,
+                            cert: idx
+                        })
+                    }
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Compute the validity of every key at time `now`, in the same
+    /// order as [keys](PGPKeyring::keys).
+    fn validities(
+        &self,
+        now: SystemTime
+    ) -> Vec<Option<PGPTrustLevel>> {
+        let mut validity = vec![None; self.keys.len()];
+
+        for idx in &self.trusted {
+            if self.keys[*idx].usable(now) {
+                validity[*idx] = Some(PGPTrustLevel::Ultimate)
+            }
+        }
+
+        for (from, to) in [
+            (PGPTrustLevel::Ultimate, PGPTrustLevel::Full),
+            (PGPTrustLevel::Full, PGPTrustLevel::Marginal)
+        ] {
+            let certified: Vec<usize> = self
+                .edges
+                .iter()
+                .filter(|edge| {
+                    let issuer = &self.keys[edge.issuer];
+                    let target = &self.keys[edge.target];
+
+                    validity[edge.issuer] == Some(from) &&
+                        validity[edge.target].is_none() &&
+                        issuer.usable(now) &&
+                        target.usable(now) &&
+                        !target.certs[edge.cert].sig.expired(now)
+                })
+                .map(|edge| edge.target)
+                .collect();
+
+            for idx in certified {
+                validity[idx] = Some(to)
+            }
+        }
+
+        validity
+    }
+
+    /// Verify the detached signature `sig` over `data` at time
+    /// `now`, requiring the signing key to have at least `min_trust`
+    /// validity.
+    ///
+    /// `sig` may be binary or ASCII-armored.  If it contains
+    /// multiple signatures, the first that verifies will be used.
+    pub fn verify_detached_at(
+        &self,
+        data: &[u8],
+        sig: &[u8],
+        min_trust: PGPTrustLevel,
+        now: SystemTime
+    ) -> Result<PGPSigner<'_>, PGPVerifyError> {
+        let sigdata =
+            dearmor(sig).map_err(|err| PGPVerifyError::Parse { error: err })?;
+        let packets = packets(&sigdata)
+            .map_err(|err| PGPVerifyError::Parse { error: err })?;
+        let validity = self.validities(now);
+        let mut result = Err(PGPVerifyError::NoSignature);
+
+        for packet in packets {
+            if packet.tag == TAG_SIGNATURE {
+                result = Signature::parse(packet.body)
+                    .map_err(|err| PGPVerifyError::Parse { error: err })
+                    .and_then(|sig| {
+                        self.verify_sig(data, &sig, &validity, min_trust, now)
+                    });
+
+                if result.is_ok() {
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn verify_sig(
+        &self,
+        data: &[u8],
+        sig: &Signature,
+        validity: &[Option<PGPTrustLevel>],
+        min_trust: PGPTrustLevel,
+        now: SystemTime
+    ) -> Result<PGPSigner<'_>, PGPVerifyError> {
+        let data = match sig.sig_type {
+            SIG_BINARY => Cow::Borrowed(data),
+            SIG_TEXT => Cow::Owned(canonicalize_text(data)),
+            sig_type => {
+                return Err(PGPVerifyError::NotDocument { sig_type: sig_type })
+            }
+        };
+        let created = sig.created.ok_or(PGPVerifyError::Parse {
+            error: PGPParseError::NoCreationTime
+        })?;
+
+        if sig.critical_unknown {
+            return Err(PGPVerifyError::CriticalSubpacket);
+        }
+
+        if sig.expired(now) {
+            return Err(PGPVerifyError::SignatureExpired);
+        }
+
+        let mut result = Err(PGPVerifyError::UnknownKey {
+            issuer: sig.issuer()
+        });
+
+        // Key IDs may collide, so try every candidate.
+        for (key, validity) in self.keys.iter().zip(validity) {
+            if let Some((signing_key, expires)) = key.signing_key(sig) {
+                result = Self::check_signer(
+                    key,
+                    signing_key,
+                    expires,
+                    *validity,
+                    &data,
+                    sig,
+                    min_trust,
+                    now
+                )
+                .map(|_| PGPSigner {
+                    key: key,
+                    signing_key: signing_key,
+                    validity: *validity,
+                    created: created
+                });
+
+                if result.is_ok() {
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn check_signer(
+        key: &PGPKey,
+        signing_key: &PGPPublicKey,
+        expires: Option<u32>,
+        validity: Option<PGPTrustLevel>,
+        data: &[u8],
+        sig: &Signature,
+        min_trust: PGPTrustLevel,
+        now: SystemTime
+    ) -> Result<(), PGPVerifyError> {
+        if key.revoked {
+            return Err(PGPVerifyError::Revoked {
+                fingerprint: key.fingerprint()
+            });
+        }
+
+        if key.primary.expired(key.expires, now) ||
+            signing_key.expired(expires, now)
+        {
+            return Err(PGPVerifyError::KeyExpired {
+                fingerprint: signing_key.fingerprint()
+            });
+        }
+
+        if std::ptr::eq(signing_key, &key.primary) && !key.can_sign {
+            return Err(PGPVerifyError::NotSigningKey {
+                fingerprint: signing_key.fingerprint()
+            });
+        }
+
+        signing_key.verify(sig, &[data])?;
+
+        if validity < Some(min_trust) {
+            return Err(PGPVerifyError::Untrusted {
+                fingerprint: key.fingerprint(),
+                validity: validity
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl PGPVerifier {
+    /// Create a new `PGPVerifier` from its components.
+    #[inline]
+    pub fn new(
+        keyring: PGPKeyring,
+        min_trust: PGPTrustLevel
+    ) -> Self {
+        PGPVerifier {
+            keyring: keyring,
+            min_trust: min_trust
+        }
+    }
+
+    /// Get the keyring.
+    #[inline]
+    pub fn keyring(&self) -> &PGPKeyring {
+        &self.keyring
+    }
+
+    /// Get the minimum validity required of signing keys.
+    #[inline]
+    pub fn min_trust(&self) -> PGPTrustLevel {
+        self.min_trust
+    }
+
+    /// Verify the detached signature `sig` over `data`.
+    ///
+    /// `sig` may be binary or ASCII-armored.
+    #[inline]
+    pub fn verify_detached(
+        &self,
+        data: &[u8],
+        sig: &[u8]
+    ) -> Result<PGPSigner<'_>, PGPVerifyError> {
+        self.verify_detached_at(data, sig, SystemTime::now())
+    }
+
+    /// Verify the detached signature `sig` over `data` at time
+    /// `now`.
+    ///
+    /// `sig` may be binary or ASCII-armored.
+    #[inline]
+    pub fn verify_detached_at(
+        &self,
+        data: &[u8],
+        sig: &[u8],
+        now: SystemTime
+    ) -> Result<PGPSigner<'_>, PGPVerifyError> {
+        self.keyring
+            .verify_detached_at(data, sig, self.min_trust, now)
+    }
+}
+
+impl PGPSigner<'_> {
+    /// Get the key that made the signature.
+    #[inline]
+    pub fn key(&self) -> &PGPKey {
+        self.key
+    }
+
+    /// Get the fingerprint of the primary key that made the
+    /// signature.
+    #[inline]
+    pub fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+
+    /// Get the fingerprint of the (primary or sub-) key that made
+    /// the signature.
+    #[inline]
+    pub fn signing_fingerprint(&self) -> String {
+        self.signing_key.fingerprint()
+    }
+
+    /// Get the validity of the signing key at the time the
+    /// signature was verified.
+    #[inline]
+    pub fn validity(&self) -> Option<PGPTrustLevel> {
+        self.validity
+    }
+
+    /// Get the creation time of the signature.
+    #[inline]
+    pub fn created(&self) -> SystemTime {
+        timestamp(self.created)
+    }
+}
+
+#[inline]
+fn timestamp(secs: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs as u64)
+}
+
+#[inline]
+fn key_header(body: &[u8]) -> [u8; 3] {
+    let len = (body.len() as u16).to_be_bytes();
+
+    [0x99, len[0], len[1]]
+}
+
+#[inline]
+fn uid_header(uid: &[u8]) -> [u8; 5] {
+    let len = (uid.len() as u32).to_be_bytes();
+
+    [0xb4, len[0], len[1], len[2], len[3]]
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn parse_key_spec(key: &str) -> Option<Vec<u8>> {
+    let digits: String = key.chars().filter(|c| !c.is_whitespace()).collect();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(&digits);
+
+    if (digits.len() == 16 || digits.len() == 40) && digits.is_ascii() {
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+            .collect()
+    } else {
+        None
+    }
+}
+
+/// Convert line endings to CRLF, for text signatures.
+fn canonicalize_text(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+
+    for (i, b) in data.iter().enumerate() {
+        if *b == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+
+        out.push(*b);
+    }
+
+    out
+}
+
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xb704ce;
+
+    for b in data {
+        crc ^= (*b as u32) << 16;
+
+        for _ in 0..8 {
+            crc <<= 1;
+
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864cfb;
+            }
+        }
+    }
+
+    crc & 0xffffff
+}
+
+/// Strip ASCII armor, if present.
+///
+/// Multiple armored blocks are concatenated.
+fn dearmor(buf: &[u8]) -> Result<Cow<'_, [u8]>, PGPParseError> {
+    if !buf.trim_ascii_start().starts_with(ARMOR_BEGIN.as_bytes()) {
+        return Ok(Cow::Borrowed(buf));
+    }
+
+    let text = std::str::from_utf8(buf).map_err(|_| PGPParseError::BadArmor)?;
+    let mut lines = text.lines().map(str::trim);
+    let mut out = Vec::new();
+
+    while let Some(line) = lines.next() {
+        if !line.starts_with(ARMOR_BEGIN) {
+            continue;
+        }
+
+        // Skip armor headers.
+        let mut b64 = String::new();
+        let mut checksum = None;
+
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            } else if !line.contains(':') {
+                b64.push_str(line);
+                break;
+            }
+        }
+
+        loop {
+            match lines.next() {
+                Some(line) if line.starts_with(ARMOR_END) => break,
+                Some(line) if line.starts_with('=') => {
+                    checksum = Some(
+                        decode_block(&line[1..])
+                            .map_err(|_| PGPParseError::BadArmor)?
+                    )
+                }
+                Some(line) => b64.push_str(line),
+                None => return Err(PGPParseError::BadArmor)
+            }
+        }
+
+        let data = decode_block(&b64).map_err(|_| PGPParseError::BadArmor)?;
+
+        if let Some(checksum) = checksum {
+            let crc = crc24(&data).to_be_bytes();
+
+            if checksum[..] != crc[1..] {
+                return Err(PGPParseError::BadChecksum);
+            }
+        }
+
+        out.extend_from_slice(&data);
+    }
+
+    Ok(Cow::Owned(out))
+}
+
+fn packets(mut buf: &[u8]) -> Result<Vec<Packet<'_>>, PGPParseError> {
+    let mut out = Vec::new();
+
+    while !buf.is_empty() {
+        let mut reader = Reader::new(buf);
+        let ctb = reader.u8()?;
+
+        if ctb & 0x80 == 0 {
+            return Err(PGPParseError::BadHeader);
+        }
+
+        let (tag, len) = if ctb & 0x40 != 0 {
+            let first = reader.u8()? as usize;
+            let len = match first {
+                0..=191 => first,
+                192..=223 => ((first - 192) << 8) + reader.u8()? as usize + 192,
+                255 => reader.u32()? as usize,
+                _ => return Err(PGPParseError::PartialLength)
+            };
+
+            (ctb & 0x3f, len)
+        } else {
+            let len = match ctb & 0x03 {
+                0 => reader.u8()? as usize,
+                1 => reader.u16()? as usize,
+                2 => reader.u32()? as usize,
+                _ => reader.buf.len()
+            };
+
+            ((ctb >> 2) & 0x0f, len)
+        };
+        let body = reader.bytes(len)?;
+
+        out.push(Packet {
+            tag: tag,
+            body: body
+        });
+        buf = reader.buf;
+    }
+
+    Ok(out)
+}
+
+/// Group packets into transferable public keys.
+///
+/// Signatures that cannot be parsed are skipped, as they cannot
+/// contribute to validity anyway.
+fn raw_keys(packets: &[Packet]) -> Result<Vec<RawKey>, PGPParseError> {
+    let mut keys: Vec<RawKey> = Vec::new();
+    let mut component = Component::Primary;
+
+    for packet in packets {
+        match (packet.tag, keys.last_mut()) {
+            (TAG_PUBLIC_KEY, _) => {
+                let key = PGPPublicKey::parse(packet.tag, packet.body)?;
+
+                keys.push(RawKey::new(key));
+                component = Component::Primary;
+            }
+            (TAG_USER_ID, Some(key)) => {
+                key.uids.push((packet.body.to_vec(), vec![]));
+                component = Component::UserID;
+            }
+            (TAG_USER_ATTR, Some(_)) => component = Component::Attribute,
+            (TAG_PUBLIC_SUBKEY, Some(key)) => {
+                let subkey = PGPPublicKey::parse(packet.tag, packet.body)?;
+
+                key.subkeys.push((subkey, vec![]));
+                component = Component::Subkey;
+            }
+            (TAG_SIGNATURE, Some(key)) => {
+                if let Ok(sig) = Signature::parse(packet.body) {
+                    match component {
+                        Component::Primary => key.direct.push(sig),
+                        Component::UserID => {
+                            if let Some((_, sigs)) = key.uids.last_mut() {
+                                sigs.push(sig)
+                            }
+                        }
+                        Component::Attribute => {}
+                        Component::Subkey => {
+                            if let Some((_, sigs)) = key.subkeys.last_mut() {
+                                sigs.push(sig)
+                            }
+                        }
+                    }
+                }
+            }
+            (TAG_TRUST, Some(_)) => {}
+            (tag, _) => {
+                return Err(PGPParseError::UnexpectedPacket { tag: tag })
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+impl ScopedError for PGPParseError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl ScopedError for PGPKeyringError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::System
+    }
+}
+
+impl ScopedError for PGPVerifyError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PGPVerifyError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            _ => ErrorScope::Msg
+        }
+    }
+}
+
+impl Display for PGPParseError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PGPParseError::BadArmor => write!(f, "malformed ASCII armor"),
+            PGPParseError::BadChecksum => {
+                write!(f, "ASCII armor checksum mismatch")
+            }
+            PGPParseError::Truncated => write!(f, "truncated data"),
+            PGPParseError::BadHeader => write!(f, "malformed packet header"),
+            PGPParseError::PartialLength => {
+                write!(f, "unexpected partial body length")
+            }
+            PGPParseError::BadVersion { tag, version } => write!(
+                f,
+                "unsupported version {} for packet tag {}",
+                version, tag
+            ),
+            PGPParseError::UnexpectedPacket { tag } => {
+                write!(f, "unexpected packet tag {}", tag)
+            }
+            PGPParseError::BadKey => write!(f, "malformed public key"),
+            PGPParseError::NoCreationTime => {
+                write!(f, "signature has no creation time")
+            }
+        }
+    }
+}
+
+impl Display for PGPKeyringError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PGPKeyringError::Parse { error } => error.fmt(f),
+            PGPKeyringError::BadKeySpec { key } => {
+                write!(f, "bad key fingerprint or ID {}", key)
+            }
+            PGPKeyringError::MissingKey { key } => {
+                write!(f, "key {} not found in keyring", key)
+            }
+        }
+    }
+}
+
+impl Display for PGPVerifyError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PGPVerifyError::Parse { error } => error.fmt(f),
+            PGPVerifyError::OpenSSL { error } => error.fmt(f),
+            PGPVerifyError::NoSignature => write!(f, "no signature present"),
+            PGPVerifyError::NotDocument { sig_type } => {
+                write!(f, "not a document signature (type {:02x})", sig_type)
+            }
+            PGPVerifyError::CriticalSubpacket => {
+                write!(f, "unrecognized critical signature subpacket")
+            }
+            PGPVerifyError::UnsupportedAlgo { algo } => {
+                write!(f, "unsupported public-key algorithm {}", algo)
+            }
+            PGPVerifyError::UnsupportedHash { algo } => {
+                write!(f, "unsupported hash algorithm {}", algo)
+            }
+            PGPVerifyError::UnknownKey {
+                issuer: Some(issuer)
+            } => {
+                write!(f, "unknown signing key {}", issuer)
+            }
+            PGPVerifyError::UnknownKey { issuer: None } => {
+                write!(f, "signature has no issuer")
+            }
+            PGPVerifyError::NotSigningKey { fingerprint } => {
+                write!(f, "key {} is not a signing key", fingerprint)
+            }
+            PGPVerifyError::Revoked { fingerprint } => {
+                write!(f, "key {} has been revoked", fingerprint)
+            }
+            PGPVerifyError::KeyExpired { fingerprint } => {
+                write!(f, "key {} has expired", fingerprint)
+            }
+            PGPVerifyError::SignatureExpired => {
+                write!(f, "signature has expired")
+            }
+            PGPVerifyError::BadSignature => write!(f, "bad signature"),
+            PGPVerifyError::Untrusted {
+                fingerprint,
+                validity: Some(validity)
+            } => write!(
+                f,
+                "key {} has insufficient validity ({})",
+                fingerprint, validity
+            ),
+            PGPVerifyError::Untrusted {
+                fingerprint,
+                validity: None
+            } => write!(f, "key {} has no validity", fingerprint)
+        }
+    }
+}
+
+#[cfg(test)]
+use std::fs::read;
+
+#[cfg(test)]
+const ROOT_FPR: &str = "8AF996F0B8E59D78463C2F8373311A2CB3B58601";
+#[cfg(test)]
+const SIGNER_FPR: &str = "E5B86F1434CAB12BE8ADFFF6646908A82BA74F1B";
+#[cfg(test)]
+const SIGNER_SUBKEY_FPR: &str = "D5DD269F49145008F4BE6C65DB133C33F4E04E09";
+#[cfg(test)]
+const OTHER_FPR: &str = "F36A4346CBDD50C9628F4B4BF37CC0CB9151C42D";
+
+#[cfg(test)]
+fn test_keyring(file: &str) -> PGPKeyring {
+    let buf = read(format!("test/data/pgp/{}", file)).unwrap();
+
+    PGPKeyring::parse(&buf, &[String::from("73311A2CB3B58601")]).unwrap()
+}
+
+#[cfg(test)]
+fn test_verify(
+    sig: &str,
+    min_trust: PGPTrustLevel
+) -> Result<String, PGPVerifyError> {
+    let keyring = test_keyring("keyring.asc");
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read(format!("test/data/pgp/{}", sig)).unwrap();
+
+    keyring
+        .verify_detached_at(&msg, &sig, min_trust, SystemTime::now())
+        .map(|signer| signer.signing_fingerprint())
+}
+
+#[test]
+fn test_keyring_parse() {
+    let keyring = test_keyring("keyring.asc");
+    let keys = keyring.keys();
+
+    assert_eq!(3, keys.len());
+    assert_eq!(ROOT_FPR, keys[0].fingerprint());
+    assert_eq!("73311A2CB3B58601", keys[0].key_id());
+    assert_eq!(&["Test Root <root@nowhere.com>"], keys[0].user_ids());
+    assert_eq!(SIGNER_FPR, keys[1].fingerprint());
+    assert_eq!(1, keys[1].subkeys.len());
+    assert_eq!(SIGNER_SUBKEY_FPR, keys[1].subkeys[0].key.fingerprint());
+    assert_eq!(OTHER_FPR, keys[2].fingerprint());
+    assert!(keys.iter().all(|key| !key.is_revoked()));
+    assert!(keys.iter().all(|key| key.expires().is_none()));
+}
+
+#[test]
+fn test_keyring_binary() {
+    let armored = test_keyring("keyring.asc");
+    let binary = test_keyring("keyring.gpg");
+
+    assert_eq!(
+        armored
+            .keys()
+            .iter()
+            .map(PGPKey::fingerprint)
+            .collect::<Vec<_>>(),
+        binary
+            .keys()
+            .iter()
+            .map(PGPKey::fingerprint)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_keyring_validity() {
+    let keyring = test_keyring("keyring.asc");
+    let now = SystemTime::now();
+
+    assert_eq!(
+        Some(PGPTrustLevel::Ultimate),
+        keyring.validity_at(ROOT_FPR, now).unwrap()
+    );
+    assert_eq!(
+        Some(PGPTrustLevel::Full),
+        keyring.validity_at(SIGNER_FPR, now).unwrap()
+    );
+    assert_eq!(None, keyring.validity_at(OTHER_FPR, now).unwrap());
+}
+
+#[test]
+fn test_keyring_find() {
+    let keyring = test_keyring("keyring.asc");
+
+    assert_eq!(
+        SIGNER_FPR,
+        keyring.find("0x646908a82ba74f1b").unwrap().fingerprint()
+    );
+    assert_eq!(
+        SIGNER_FPR,
+        keyring
+            .find("E5B8 6F14 34CA B12B E8AD  FFF6 6469 08A8 2BA7 4F1B")
+            .unwrap()
+            .fingerprint()
+    );
+    assert_eq!(
+        Err(PGPKeyringError::BadKeySpec {
+            key: String::from("nonsense")
+        }),
+        keyring.find("nonsense").map(|_| ())
+    );
+    assert_eq!(
+        Err(PGPKeyringError::MissingKey {
+            key: String::from("0123456789ABCDEF")
+        }),
+        keyring.find("0123456789ABCDEF").map(|_| ())
+    );
+}
+
+#[test]
+fn test_keyring_bad_checksum() {
+    let buf = read("test/data/pgp/keyring.asc").unwrap();
+    let text = String::from_utf8(buf).unwrap().replace("=tFAO", "=AAAA");
+
+    assert!(PGPKeyring::parse(text.as_bytes(), &[]).is_err());
+}
+
+#[test]
+fn test_verify_root() {
+    assert_eq!(
+        ROOT_FPR,
+        test_verify("sig_root.asc", PGPTrustLevel::Ultimate).unwrap()
+    );
+}
+
+#[test]
+fn test_verify_signer() {
+    assert_eq!(
+        SIGNER_FPR,
+        test_verify("sig_signer.asc", PGPTrustLevel::Full).unwrap()
+    );
+}
+
+#[test]
+fn test_verify_subkey() {
+    assert_eq!(
+        SIGNER_SUBKEY_FPR,
+        test_verify("sig_subkey.asc", PGPTrustLevel::Marginal).unwrap()
+    );
+}
+
+#[test]
+fn test_verify_signer_untrusted() {
+    match test_verify("sig_signer.asc", PGPTrustLevel::Ultimate) {
+        Err(PGPVerifyError::Untrusted {
+            fingerprint,
+            validity
+        }) => {
+            assert_eq!(SIGNER_FPR, fingerprint);
+            assert_eq!(Some(PGPTrustLevel::Full), validity);
+        }
+        _ => panic!("expected untrusted error")
+    }
+}
+
+#[test]
+fn test_verify_other_untrusted() {
+    match test_verify("sig_other.asc", PGPTrustLevel::Marginal) {
+        Err(err @ PGPVerifyError::Untrusted { validity: None, .. }) => {
+            assert_eq!(ErrorScope::Msg, err.scope())
+        }
+        _ => panic!("expected untrusted error")
+    }
+}
+
+#[test]
+fn test_verify_certify_only() {
+    // The primary key signed the message, but its latest self-signature
+    // only allows it to certify.
+    let buf = read("test/data/pgp/keyring_certify.asc").unwrap();
+    let keyring =
+        PGPKeyring::parse(&buf, &[String::from("A37E5C7DD816FDC8")]).unwrap();
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read("test/data/pgp/sig_certify.asc").unwrap();
+
+    match keyring.verify_detached_at(
+        &msg,
+        &sig,
+        PGPTrustLevel::Marginal,
+        SystemTime::now()
+    ) {
+        Err(PGPVerifyError::NotSigningKey { fingerprint }) => {
+            assert_eq!("57A8AA009ABC22A329B6FE6FA37E5C7DD816FDC8", fingerprint)
+        }
+        _ => panic!("expected not signing key error")
+    }
+}
+
+#[test]
+fn test_verify_tampered() {
+    let keyring = test_keyring("keyring.asc");
+    let sig = read("test/data/pgp/sig_signer.asc").unwrap();
+
+    assert!(matches!(
+        keyring.verify_detached_at(
+            b"constellation test massage\n",
+            &sig,
+            PGPTrustLevel::Marginal,
+            SystemTime::now()
+        ),
+        Err(PGPVerifyError::BadSignature)
+    ));
+}
+
+#[test]
+fn test_verify_unknown_key() {
+    let buf = read("test/data/pgp/keyring.asc").unwrap();
+    let keyring = PGPKeyring::parse(&buf, &[]).unwrap();
+    let keyring = PGPKeyring {
+        keys: keyring.keys[..1].to_vec(),
+        trusted: vec![],
+        edges: vec![]
+    };
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read("test/data/pgp/sig_other.asc").unwrap();
+
+    assert!(matches!(
+        keyring.verify_detached_at(
+            &msg,
+            &sig,
+            PGPTrustLevel::Marginal,
+            SystemTime::now()
+        ),
+        Err(PGPVerifyError::UnknownKey { issuer: Some(_) })
+    ));
+}
+
+#[test]
+fn test_verify_validity_at() {
+    let buf = read("test/data/pgp/keyring_expiring.asc").unwrap();
+    let keyring = PGPKeyring::parse(
+        &buf,
+        &[String::from("B0CF5BED57A2202EBD9E8384FABFB252B10F197E")]
+    )
+    .unwrap();
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read("test/data/pgp/sig_expiring.asc").unwrap();
+    let signer = "56A6BEB9C956AE50AE29A21B62E4B9EB1A3CC8E0";
+    let now = SystemTime::now();
+    // The root key expires at the end of 2099; the signer never does.
+    let later = timestamp(4102444800 + 86400 * 180);
+
+    assert_eq!(
+        Some(PGPTrustLevel::Full),
+        keyring
+            .verify_detached_at(&msg, &sig, PGPTrustLevel::Full, now)
+            .unwrap()
+            .validity()
+    );
+    assert_eq!(None, keyring.validity_at(signer, later).unwrap());
+
+    match keyring.verify_detached_at(&msg, &sig, PGPTrustLevel::Marginal, later)
+    {
+        Err(PGPVerifyError::Untrusted {
+            fingerprint,
+            validity: None
+        }) => assert_eq!(signer, fingerprint),
+        _ => panic!("expected untrusted error")
+    }
+}
+
+#[test]
+fn test_keyring_malformed() {
+    let mut eddsa_bad_point = vec![0xc6, 0x13, 0x04, 0, 0, 0, 0, ALGO_EDDSA];
+
+    eddsa_bad_point.push(OID_ED25519.len() as u8);
+    eddsa_bad_point.extend_from_slice(OID_ED25519);
+    eddsa_bad_point.extend_from_slice(&[0x00, 0x07, 0x40]);
+
+    let cases: [(&[u8], PGPParseError); 8] = [
+        (&[0x00], PGPParseError::BadHeader),
+        (&[0xc6], PGPParseError::Truncated),
+        (&[0xc6, 0xe0], PGPParseError::PartialLength),
+        (
+            &[0xc6, 0xff, 0xff, 0xff, 0xff, 0xff],
+            PGPParseError::Truncated
+        ),
+        (&[0xc6, 0x02, 0x04, 0x00], PGPParseError::Truncated),
+        (
+            &[0xc6, 0x01, 0x03],
+            PGPParseError::BadVersion { tag: 6, version: 3 }
+        ),
+        (&[0xcd, 0x00], PGPParseError::UnexpectedPacket { tag: 13 }),
+        (&eddsa_bad_point, PGPParseError::BadKey)
+    ];
+
+    for (buf, error) in cases {
+        assert_eq!(
+            Err(PGPKeyringError::Parse { error: error }),
+            PGPKeyring::parse(buf, &[]).map(|_| ())
+        );
+    }
+}
+
+#[test]
+fn test_keyring_truncated() {
+    let buf = read("test/data/pgp/keyring.gpg").unwrap();
+
+    // Every proper prefix either fails to parse or ends on a packet
+    // boundary, and yields fewer keys.
+    for len in 0..buf.len() {
+        if let Ok(keyring) = PGPKeyring::parse(&buf[..len], &[]) {
+            assert!(keyring.keys().len() <= 3)
+        }
+    }
+}
+
+#[test]
+fn test_verify_malformed() {
+    let keyring = test_keyring("keyring.asc");
+    let msg = read("test/data/pgp/msg.txt").unwrap();
+    let sig = read("test/data/pgp/sig_signer.asc").unwrap();
+    let sig = dearmor(&sig).unwrap();
+    let now = SystemTime::now();
+    let cases: [(&[u8], PGPVerifyError); 3] = [
+        (&[], PGPVerifyError::NoSignature),
+        (
+            &[0xc2, 0x01, 0x03],
+            PGPVerifyError::Parse {
+                error: PGPParseError::BadVersion { tag: 2, version: 3 }
+            }
+        ),
+        (
+            &[0xc2, 0x03, 0x04, 0x00, 0x16],
+            PGPVerifyError::Parse {
+                error: PGPParseError::Truncated
+            }
+        )
+    ];
+
+    for (sig, error) in cases {
+        let result =
+            keyring.verify_detached_at(&msg, sig, PGPTrustLevel::Marginal, now);
+
+        assert_eq!(
+            Some(error.to_string()),
+            result.err().map(|err| err.to_string())
+        );
+    }
+
+    for len in 0..sig.len() {
+        assert!(keyring
+            .verify_detached_at(&msg, &sig[..len], PGPTrustLevel::Marginal, now)
+            .is_err());
+    }
+}
@@ -0,0 +1,1816 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! DTLS security for datagram sockets.
+//!
+//! This module provides [DTLSSocket], which wraps any socket
+//! implementing both [Sender] and [Receiver], and secures the traffic
+//! to and from each peer address with a separate DTLS session.
+//! Sessions are established on demand: sending to a new peer starts a
+//! handshake as the client, and receiving from a new peer starts one
+//! as the server.  Messages sent before a handshake completes are
+//! queued and sent once it does.
+//!
+//! Both sides of a session authenticate with certificates, which are
+//! verified against a [PKITrustRoot].  The identity from the peer's
+//! certificate is provided as the [MsgCred](Receiver::MsgCred) for
+//! every received message.
+//!
+//! Handshake failures are reported as [std::io::Error]s wrapping a
+//! [DTLSHandshakeError], which carries an [ErrorScope] and the
+//! number of queued messages that were discarded.
+//!
+//! Servers perform a cookie exchange before keeping any state for a
+//! new peer, so a spoofed ClientHello cannot create a session.  A
+//! peer that restarts its session replaces the old one only once the
+//! new handshake completes, and the number of sessions is limited
+//! (see [set_max_sessions](DTLSSocket::set_max_sessions)).  The
+//! server context must have cookie callbacks installed; contexts
+//! from [load_dtls_server](PKITrustRoot::load_dtls_server) do.
+//!
+//! Lost handshake messages are retransmitted by
+//! [tick](DTLSSocket::tick), which is called every time the socket
+//! receives.  If the underlying socket can block indefinitely, it
+//! should be given a read timeout of no more than a second, so that
+//! retransmissions still happen while waiting for the peer.
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::hash::Hash;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::IoSlice;
+use std::io::IoSliceMut;
+use std::io::Read;
+use std::io::Write;
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+use std::ptr::null_mut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use foreign_types::ForeignTypeRef;
+use log::debug;
+use log::trace;
+use log::warn;
+use openssl::error::ErrorStack;
+use openssl::ex_data::Index;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::pkey::Private;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use openssl::ssl::ErrorCode;
+use openssl::ssl::Ssl;
+use openssl::ssl::SslContext;
+use openssl::ssl::SslContextBuilder;
+use openssl::ssl::SslOptions;
+use openssl::ssl::SslRef;
+use openssl::ssl::SslStream;
+use openssl::x509::X509;
+use openssl_sys::SSL_ctrl;
+use openssl_sys::SSL;
+
+use crate::config::pki::reload::ReloadableIdentity;
+use crate::config::pki::reload::ReloadableTrustStore;
+use crate::config::pki::PKIIdentity;
+use crate::config::pki::PKITrustRoot;
+use crate::config::pki::TLSContextLoadError;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::net::Receiver;
+use crate::net::Sender;
+use crate::net::Socket;
+use crate::sign::cert_dns_names;
+use crate::sign::cert_subject;
+
+/// MTU to use if the underlying socket does not report one.
+const DEFAULT_MTU: usize = 1400;
+
+/// Space to reserve for DTLS record overhead in each datagram.
+///
+/// This covers the record header, explicit nonce, and AEAD tag.
+const DTLS_OVERHEAD: usize = 64;
+
+/// Maximum size of a datagram.
+const MAX_DATAGRAM: usize = 65536;
+
+/// Default limit on the number of sessions.
+const DEFAULT_MAX_SESSIONS: usize = 4096;
+
+/// Size of the secret used to generate cookies.
+const COOKIE_SECRET_LEN: usize = 32;
+
+/// `SSL_ctrl` command for `DTLSv1_handle_timeout`.
+const DTLS_CTRL_HANDLE_TIMEOUT: c_int = 74;
+
+// The DTLS listen API is not covered by the `openssl` crate.
+extern "C" {
+    fn DTLSv1_listen(
+        ssl: *mut SSL,
+        peer: *mut c_void
+    ) -> c_int;
+    fn BIO_ADDR_new() -> *mut c_void;
+    fn BIO_ADDR_free(addr: *mut c_void);
+}
+
+/// Identity of a DTLS peer.
+///
+/// This is derived from the peer's certificate, which has been
+/// verified against a trust store during the handshake.
+#[derive(Clone, Debug)]
+pub struct DTLSPeerIdentity {
+    /// Subject name, in one-line form.
+    subject: String,
+    /// DNS names from the subject alternative names.
+    dns_names: Vec<String>,
+    /// The peer's certificate.
+    cert: X509
+}
+
+/// Error indicating that a DTLS handshake failed.
+///
+/// This is wrapped inside the [std::io::Error]s returned by
+/// [DTLSSocket], and can be recovered with
+/// [get_ref](std::io::Error::get_ref).
+#[derive(Debug)]
+pub struct DTLSHandshakeError {
+    /// Kind of the underlying error, which determines the scope.
+    kind: ErrorKind,
+    /// Description of the underlying error.
+    desc: String,
+    /// Number of queued messages that were discarded.
+    dropped: usize
+}
+
+/// DTLS layer over a datagram socket.
+///
+/// See the [module documentation](self) for details.
+pub struct DTLSSocket<Inner: Sender + Receiver> {
+    inner: Inner,
+    client: SslContext,
+    server: SslContext,
+    trust_store: Option<Arc<ReloadableTrustStore>>,
+    identity: Option<Arc<ReloadableIdentity>>,
+    max_sessions: usize,
+    sessions: Mutex<HashMap<Inner::Addr, DTLSSession>>,
+    received: Mutex<VecDeque<(Vec<u8>, Inner::Addr, DTLSPeerIdentity)>>
+}
+
+#[derive(Default)]
+struct DTLSSession {
+    state: Option<DTLSState>,
+    identity: Option<DTLSPeerIdentity>,
+    pending: Vec<Vec<u8>>,
+    /// New handshake from a peer that restarted, which replaces this
+    /// session once it completes.
+    restart: Option<Box<DTLSSession>>
+}
+
+enum DTLSState {
+    Handshake(SslStream<DatagramPipe>),
+    Established(SslStream<DatagramPipe>)
+}
+
+/// In-memory transport for OpenSSL, exchanging whole datagrams.
+#[derive(Debug, Default)]
+struct DatagramPipe {
+    incoming: VecDeque<Vec<u8>>,
+    outgoing: Vec<Vec<u8>>
+}
+
+impl Read for DatagramPipe {
+    fn read(
+        &mut self,
+        buf: &mut [u8]
+    ) -> Result<usize, Error> {
+        match self.incoming.pop_front() {
+            Some(datagram) => {
+                let len = datagram.len().min(buf.len());
+
+                buf[..len].copy_from_slice(&datagram[..len]);
+
+                Ok(len)
+            }
+            None => Err(Error::from(ErrorKind::WouldBlock))
+        }
+    }
+}
+
+impl Write for DatagramPipe {
+    #[inline]
+    fn write(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<usize, Error> {
+        self.outgoing.push(buf.to_vec());
+
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl DTLSPeerIdentity {
+    fn new(cert: X509) -> Result<Self, Error> {
+        let subject = cert_subject(&cert)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+        Ok(DTLSPeerIdentity {
+            subject: subject,
+            dns_names: cert_dns_names(&cert),
+            cert: cert
+        })
+    }
+
+    /// Get the subject name of the peer's certificate, in one-line
+    /// form (for example, `C=US, O=Example, CN=host.example.com`).
+    #[inline]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Get the DNS names in the subject alternative names of the
+    /// peer's certificate.
+    #[inline]
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// Get the peer's certificate.
+    #[inline]
+    pub fn cert(&self) -> &X509 {
+        &self.cert
+    }
+}
+
+impl DTLSHandshakeError {
+    fn new(
+        error: &openssl::ssl::Error,
+        dropped: usize
+    ) -> Self {
+        DTLSHandshakeError {
+            kind: handshake_kind(error.code(), error.io_error()),
+            desc: error.to_string(),
+            dropped: dropped
+        }
+    }
+
+    /// Get the number of queued messages that were discarded because
+    /// the handshake failed.
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl From<DTLSHandshakeError> for Error {
+    fn from(val: DTLSHandshakeError) -> Error {
+        Error::new(val.kind, val)
+    }
+}
+
+impl DTLSSession {
+    #[inline]
+    fn is_established(&self) -> bool {
+        matches!(self.state, Some(DTLSState::Established(_)))
+    }
+
+    /// Get the streams of all handshakes in progress.
+    fn handshakes_mut(&mut self) -> Vec<&mut SslStream<DatagramPipe>> {
+        let mut streams = vec![];
+
+        if let Some(DTLSState::Handshake(stream)) = &mut self.state {
+            streams.push(stream);
+        }
+
+        if let Some(restart) = &mut self.restart {
+            streams.extend(restart.handshakes_mut());
+        }
+
+        streams
+    }
+}
+
+impl<Inner> DTLSSocket<Inner>
+where
+    Inner: Sender + Receiver,
+    Inner::Addr: Hash + Send
+{
+    /// Create a new `DTLSSocket` over `inner`.
+    ///
+    /// The `client` and `server` contexts are used for sessions
+    /// initiated locally and remotely, respectively.  These would
+    /// typically be created with
+    /// [load_dtls_client](PKITrustRoot::load_dtls_client) and
+    /// [load_dtls_server](PKITrustRoot::load_dtls_server).  The
+    /// `server` context must have cookie callbacks installed, as with
+    /// [set_cookie_callbacks].
+    #[inline]
+    pub fn new(
+        inner: Inner,
+        client: SslContext,
+        server: SslContext
+    ) -> Self {
+        DTLSSocket {
+            inner: inner,
+            client: client,
+            server: server,
+            trust_store: None,
+            identity: None,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            sessions: Mutex::new(HashMap::new()),
+            received: Mutex::new(VecDeque::new())
+        }
+    }
+
+    /// Create a new `DTLSSocket` over `inner`, using `trust_root` to
+    /// verify peers and presenting `identity` to them.
+    ///
+    /// The `verify_time` parameter optionally sets the time that will
+    /// be checked against certificate validity and expiry times.
+    pub fn create(
+        inner: Inner,
+        trust_root: &PKITrustRoot,
+        identity: &PKIIdentity,
+        verify_time: Option<SystemTime>
+    ) -> Result<Self, TLSContextLoadError> {
+        let client = trust_root.load_dtls_client(identity, verify_time)?;
+        let server = trust_root.load_dtls_server(identity, verify_time)?;
+
+        Ok(DTLSSocket::new(inner, client, server))
+    }
+
+    /// Get the underlying socket.
+    #[inline]
+    pub fn inner(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Verify peers in new sessions against the current store in
+    /// `trust_store`, instead of the stores in the contexts.
+    ///
+    /// This allows CRLs and root certificates to be updated without
+    /// recreating the socket.  As the same store is used for both
+    /// sides of sessions, it should have been created with
+    /// [X509PurposeId::ANY](openssl::x509::X509PurposeId::ANY).
+    #[inline]
+    pub fn set_trust_store(
+        &mut self,
+        trust_store: Arc<ReloadableTrustStore>
+    ) {
+        self.trust_store = Some(trust_store)
+    }
+
+    /// Present the current key and certificate chain in `identity`
+    /// in new sessions, instead of the ones in the contexts.
+    ///
+    /// This allows certificates to be renewed without recreating the
+    /// socket.
+    #[inline]
+    pub fn set_identity(
+        &mut self,
+        identity: Arc<ReloadableIdentity>
+    ) {
+        self.identity = Some(identity)
+    }
+
+    /// Set the maximum number of sessions, including those still
+    /// handshaking.
+    ///
+    /// Handshakes from new peers are ignored once this is reached,
+    /// and new sessions cannot be started locally.
+    #[inline]
+    pub fn set_max_sessions(
+        &mut self,
+        max: usize
+    ) {
+        self.max_sessions = max
+    }
+
+    /// Retransmit handshake messages whose timers have expired.
+    ///
+    /// This is called every time the socket receives; it only needs
+    /// to be called otherwise if receiving might block for longer
+    /// than the retransmission timeout (initially one second).
+    /// Handshakes that time out too many times are discarded.
+    pub fn tick(&self) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().map_err(|_| poisoned())?;
+        let mut failed = vec![];
+
+        for (addr, session) in sessions.iter_mut() {
+            for stream in session.handshakes_mut() {
+                // SAFETY: the pointer comes from a live `SslStream`,
+                // and `DTLSv1_handle_timeout` takes no argument.
+                let result = unsafe {
+                    SSL_ctrl(
+                        stream.ssl().as_ptr(),
+                        DTLS_CTRL_HANDLE_TIMEOUT,
+                        0,
+                        null_mut()
+                    )
+                };
+
+                if result < 0 {
+                    // Clear the error queue.
+                    let _ = ErrorStack::get();
+
+                    failed.push(addr.clone());
+                } else {
+                    if result > 0 {
+                        trace!(target: "dtls",
+                               "retransmitting handshake to {}",
+                               addr);
+                    }
+
+                    self.transmit(addr, stream.get_mut())?;
+                }
+            }
+        }
+
+        for addr in failed {
+            if let Some(session) = sessions.get_mut(&addr) {
+                if session.is_established() {
+                    // Only the restarted handshake failed.
+                    session.restart = None;
+                } else {
+                    warn!(target: "dtls",
+                          concat!("DTLS handshake with {} timed out, ",
+                                  "discarding {} queued messages"),
+                          addr, session.pending.len());
+
+                    sessions.remove(&addr);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check whether a session with `addr` has been established.
+    pub fn is_established(
+        &self,
+        addr: &Inner::Addr
+    ) -> Result<bool, Error> {
+        let sessions = self.sessions.lock().map_err(|_| poisoned())?;
+
+        Ok(sessions
+            .get(addr)
+            .is_some_and(|session| session.is_established()))
+    }
+
+    /// Get the identity of the peer at `addr`, if a session has been
+    /// established.
+    pub fn peer_identity(
+        &self,
+        addr: &Inner::Addr
+    ) -> Result<Option<DTLSPeerIdentity>, Error> {
+        let sessions = self.sessions.lock().map_err(|_| poisoned())?;
+
+        Ok(sessions
+            .get(addr)
+            .and_then(|session| session.identity.clone()))
+    }
+
+    /// Start a handshake with `addr`, if there is no session with it.
+    pub fn connect(
+        &self,
+        addr: &Inner::Addr
+    ) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().map_err(|_| poisoned())?;
+
+        if !sessions.contains_key(addr) {
+            let session = self.start(addr, sessions.len())?;
+
+            sessions.insert(addr.clone(), session);
+        }
+
+        Ok(())
+    }
+
+    /// Close the session with `addr`, if there is one.
+    ///
+    /// Established sessions will be shut down cleanly.  Returns
+    /// whether a session existed.
+    pub fn close(
+        &self,
+        addr: &Inner::Addr
+    ) -> Result<bool, Error> {
+        let mut sessions = self.sessions.lock().map_err(|_| poisoned())?;
+
+        match sessions.remove(addr) {
+            Some(DTLSSession {
+                state: Some(DTLSState::Established(mut stream)),
+                ..
+            }) => {
+                debug!(target: "dtls",
+                       "closing DTLS session with {}",
+                       addr);
+
+                // The peer may already be gone; failure here is harmless.
+                if stream.shutdown().is_ok() {
+                    self.transmit(addr, stream.get_mut())?;
+                }
+
+                Ok(true)
+            }
+            Some(session) => {
+                if !session.pending.is_empty() {
+                    warn!(target: "dtls",
+                          concat!("closing DTLS handshake with {}, ",
+                                  "discarding {} queued messages"),
+                          addr, session.pending.len());
+                }
+
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+
+    #[inline]
+    fn dtls_mtu(&self) -> usize {
+        self.inner.mtu().unwrap_or(DEFAULT_MTU)
+    }
+
+    /// Send all outgoing datagrams in `pipe` to `addr`.
+    fn transmit(
+        &self,
+        addr: &Inner::Addr,
+        pipe: &mut DatagramPipe
+    ) -> Result<(), Error> {
+        for datagram in pipe.outgoing.drain(..) {
+            self.inner.send_to(addr, &datagram)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a new SSL object from `ctx`.
+    fn new_ssl(
+        &self,
+        ctx: &SslContext
+    ) -> Result<Ssl, Error> {
+        let mut ssl = Ssl::new(ctx).map_err(Error::other)?;
+
+        ssl.set_mtu(self.dtls_mtu() as u32).map_err(Error::other)?;
+
+        if let Some(trust_store) = &self.trust_store {
+            trust_store
+                .set_verify_store(&mut ssl)
+                .map_err(|err| Error::other(err.to_string()))?;
+        }
+
+        if let Some(identity) = &self.identity {
+            identity
+                .set_identity(&mut ssl)
+                .map_err(|err| Error::other(err.to_string()))?;
+        }
+
+        Ok(ssl)
+    }
+
+    /// Start a new client session with `addr`, given the number of
+    /// existing sessions.
+    fn start(
+        &self,
+        addr: &Inner::Addr,
+        count: usize
+    ) -> Result<DTLSSession, Error> {
+        if count >= self.max_sessions {
+            return Err(Error::new(
+                ErrorKind::OutOfMemory,
+                "too many DTLS sessions"
+            ));
+        }
+
+        let mut ssl = self.new_ssl(&self.client)?;
+
+        ssl.set_connect_state();
+
+        let stream = SslStream::new(ssl, DatagramPipe::default())
+            .map_err(Error::other)?;
+        let mut session = DTLSSession::default();
+
+        debug!(target: "dtls",
+               "starting DTLS client handshake with {}",
+               addr);
+
+        self.advance(addr, &mut session, stream)?;
+
+        Ok(session)
+    }
+
+    /// Check the cookie in a datagram from `addr` that should hold a
+    /// ClientHello, without keeping any state.
+    ///
+    /// If the cookie is valid, this returns a server stream that will
+    /// continue the handshake.  Otherwise, a HelloVerifyRequest with
+    /// a fresh cookie is sent, or the datagram is ignored.
+    fn listen(
+        &self,
+        addr: &Inner::Addr,
+        datagram: &[u8]
+    ) -> Result<Option<SslStream<DatagramPipe>>, Error> {
+        let mut ssl = self.new_ssl(&self.server)?;
+        let mut pipe = DatagramPipe::default();
+
+        ssl.set_accept_state();
+        ssl.set_ex_data(
+            cookie_addr_index().map_err(Error::other)?,
+            addr.to_string().into_bytes()
+        );
+        pipe.incoming.push_back(datagram.to_vec());
+
+        let mut stream = SslStream::new(ssl, pipe).map_err(Error::other)?;
+
+        // SAFETY: the pointer comes from a live `SslStream`, and the
+        // peer address is allocated and freed here.
+        let result = unsafe {
+            let peer = BIO_ADDR_new();
+
+            if peer.is_null() {
+                return Err(Error::other(ErrorStack::get()));
+            }
+
+            let result = DTLSv1_listen(stream.ssl().as_ptr(), peer);
+
+            BIO_ADDR_free(peer);
+
+            result
+        };
+
+        match result {
+            1 => {
+                debug!(target: "dtls",
+                       "starting DTLS server handshake with {}",
+                       addr);
+
+                // The ClientHello was consumed, but the handshake
+                // needs to see it again.
+                stream.get_mut().incoming.push_front(datagram.to_vec());
+
+                Ok(Some(stream))
+            }
+            0 => {
+                trace!(target: "dtls",
+                       "sending DTLS cookie to {}",
+                       addr);
+
+                self.transmit(addr, stream.get_mut())?;
+
+                Ok(None)
+            }
+            _ => {
+                debug!(target: "dtls",
+                       "ignoring invalid DTLS datagram from {}: {}",
+                       addr, ErrorStack::get());
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Take a handshake step on `stream`, and update `session` with
+    /// the result.
+    fn advance(
+        &self,
+        addr: &Inner::Addr,
+        session: &mut DTLSSession,
+        mut stream: SslStream<DatagramPipe>
+    ) -> Result<(), Error> {
+        match stream.do_handshake() {
+            Ok(()) => {
+                let cert =
+                    stream.ssl().peer_certificate().ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::PermissionDenied,
+                            "no peer certificate"
+                        )
+                    })?;
+                let identity = DTLSPeerIdentity::new(cert)?;
+
+                debug!(target: "dtls",
+                       "DTLS session established with {} ({})",
+                       addr, identity.subject());
+
+                for msg in session.pending.drain(..) {
+                    stream.ssl_write(&msg).map_err(|err| {
+                        Error::new(ErrorKind::ConnectionReset, err)
+                    })?;
+                }
+
+                self.transmit(addr, stream.get_mut())?;
+                session.identity = Some(identity);
+                session.state = Some(DTLSState::Established(stream));
+
+                Ok(())
+            }
+            Err(err)
+                if err.code() == ErrorCode::WANT_READ ||
+                    err.code() == ErrorCode::WANT_WRITE =>
+            {
+                self.transmit(addr, stream.get_mut())?;
+                session.state = Some(DTLSState::Handshake(stream));
+
+                Ok(())
+            }
+            Err(err) => {
+                warn!(target: "dtls",
+                      "DTLS handshake with {} failed: {}",
+                      addr, err);
+
+                // Send any alerts generated by the failure.
+                let _ = self.transmit(addr, stream.get_mut());
+
+                if !session.pending.is_empty() {
+                    warn!(target: "dtls",
+                          "discarding {} queued messages for {}",
+                          session.pending.len(), addr);
+                }
+
+                Err(DTLSHandshakeError::new(&err, session.pending.len()).into())
+            }
+        }
+    }
+
+    /// Feed `datagram` to a session that is still handshaking.
+    fn handshake(
+        &self,
+        addr: &Inner::Addr,
+        session: &mut DTLSSession,
+        datagram: Vec<u8>
+    ) -> Result<(), Error> {
+        match session.state.take() {
+            Some(DTLSState::Handshake(mut stream)) => {
+                stream.get_mut().incoming.push_back(datagram);
+
+                self.advance(addr, session, stream)
+            }
+            state => {
+                session.state = state;
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Read all available application data from an established
+    /// session.
+    ///
+    /// Returns whether the session remains open.
+    fn read_available(
+        &self,
+        addr: &Inner::Addr,
+        session: &mut DTLSSession
+    ) -> Result<bool, Error> {
+        let (stream, identity) = match (&mut session.state, &session.identity) {
+            (Some(DTLSState::Established(stream)), Some(identity)) => {
+                (stream, identity)
+            }
+            _ => return Ok(true)
+        };
+        let mut received = self.received.lock().map_err(|_| poisoned())?;
+        let mut buf = vec![0; MAX_DATAGRAM];
+
+        let result = loop {
+            match stream.ssl_read(&mut buf) {
+                Ok(len) => {
+                    trace!(target: "dtls",
+                           "received {} bytes from {}",
+                           len, addr);
+
+                    received.push_back((
+                        buf[..len].to_vec(),
+                        addr.clone(),
+                        identity.clone()
+                    ))
+                }
+                Err(err) if err.code() == ErrorCode::WANT_READ => {
+                    break Ok(true)
+                }
+                Err(err) if err.code() == ErrorCode::ZERO_RETURN => {
+                    debug!(target: "dtls",
+                           "DTLS session with {} closed by peer",
+                           addr);
+
+                    break Ok(false);
+                }
+                Err(err) => {
+                    break Err(Error::new(ErrorKind::ConnectionReset, err))
+                }
+            }
+        };
+
+        self.transmit(addr, stream.get_mut())?;
+
+        result
+    }
+
+    /// Process a datagram received from `addr`.
+    fn process(
+        &self,
+        addr: Inner::Addr,
+        datagram: Vec<u8>
+    ) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().map_err(|_| poisoned())?;
+        let mut session = match sessions.remove(&addr) {
+            Some(session) => session,
+            None if sessions.len() >= self.max_sessions => {
+                warn!(target: "dtls",
+                      "ignoring datagram from {}: too many DTLS sessions",
+                      addr);
+
+                return Ok(());
+            }
+            None => match self.listen(&addr, &datagram)? {
+                Some(stream) => {
+                    let mut session = DTLSSession::default();
+
+                    self.advance(&addr, &mut session, stream)?;
+                    sessions.insert(addr, session);
+
+                    return Ok(());
+                }
+                None => return Ok(())
+            }
+        };
+
+        if !session.is_established() {
+            self.handshake(&addr, &mut session, datagram)?;
+        } else if let Some(mut restart) = session.restart.take() {
+            // Records for the old session are discarded by the new
+            // handshake, and vice versa.
+            if let Err(err) =
+                self.handshake(&addr, &mut restart, datagram.clone())
+            {
+                // The existing session is unaffected.
+                sessions.insert(addr, session);
+
+                return Err(err);
+            }
+
+            if restart.is_established() {
+                debug!(target: "dtls",
+                       "peer {} restarted DTLS session",
+                       addr);
+
+                session = *restart;
+            } else {
+                session.restart = Some(restart);
+
+                if let Some(DTLSState::Established(stream)) = &mut session.state
+                {
+                    stream.get_mut().incoming.push_back(datagram);
+                }
+            }
+        } else if is_client_hello(&datagram) {
+            // The peer may have restarted, but the session is only
+            // replaced once the new handshake completes.
+            match self.listen(&addr, &datagram) {
+                Ok(Some(stream)) => {
+                    let mut restart = DTLSSession::default();
+                    let result = self.advance(&addr, &mut restart, stream);
+
+                    if result.is_ok() {
+                        session.restart = Some(Box::new(restart));
+                    }
+
+                    sessions.insert(addr, session);
+
+                    return result;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    sessions.insert(addr, session);
+
+                    return Err(err);
+                }
+            }
+        } else if let Some(DTLSState::Established(stream)) = &mut session.state
+        {
+            stream.get_mut().incoming.push_back(datagram);
+        }
+
+        if self.read_available(&addr, &mut session)? {
+            sessions.insert(addr, session);
+        }
+
+        Ok(())
+    }
+
+    /// Get the next received message, receiving and processing
+    /// datagrams from the underlying socket as necessary.
+    fn next_msg(
+        &self,
+        peek: bool
+    ) -> Result<(Vec<u8>, Inner::Addr, DTLSPeerIdentity), Error> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+
+        loop {
+            {
+                let mut received =
+                    self.received.lock().map_err(|_| poisoned())?;
+
+                if peek {
+                    if let Some(msg) = received.front() {
+                        return Ok(msg.clone());
+                    }
+                } else if let Some(msg) = received.pop_front() {
+                    return Ok(msg);
+                }
+            }
+
+            self.tick()?;
+
+            let (len, addr, _) = self.inner.recv_from(&mut buf)?;
+
+            self.process(addr, buf[..len].to_vec())?;
+        }
+    }
+}
+
+impl<Inner> Socket for DTLSSocket<Inner>
+where
+    Inner: Sender + Receiver,
+    Inner::Addr: Hash + Send
+{
+    type Addr = Inner::Addr;
+
+    #[inline]
+    fn local_addr(&self) -> Result<Self::Addr, Error> {
+        self.inner.local_addr()
+    }
+}
+
+impl<Inner> Sender for DTLSSocket<Inner>
+where
+    Inner: Sender + Receiver,
+    Inner::Addr: Hash + Send
+{
+    #[inline]
+    fn mtu(&self) -> Option<usize> {
+        self.inner
+            .mtu()
+            .map(|mtu| mtu.saturating_sub(DTLS_OVERHEAD))
+    }
+
+    /// Send the data in `buf` to the counterparty at `Addr`.
+    ///
+    /// If no session is established with `addr`, this will queue the
+    /// message, starting a handshake if necessary.  The message will
+    /// be sent when the handshake completes, which requires receiving
+    /// on this socket.
+    fn send_to(
+        &self,
+        addr: &Self::Addr,
+        buf: &[u8]
+    ) -> Result<usize, Error> {
+        let mut sessions = self.sessions.lock().map_err(|_| poisoned())?;
+
+        if !sessions.contains_key(addr) {
+            let session = self.start(addr, sessions.len())?;
+
+            sessions.insert(addr.clone(), session);
+        }
+
+        let session = sessions.get_mut(addr).ok_or_else(poisoned)?;
+
+        match &mut session.state {
+            Some(DTLSState::Established(stream)) => {
+                let result = stream.ssl_write(buf);
+
+                self.transmit(addr, stream.get_mut())?;
+
+                result.map_err(|err| {
+                    sessions.remove(addr);
+
+                    Error::new(ErrorKind::ConnectionReset, err)
+                })
+            }
+            _ => {
+                trace!(target: "dtls",
+                       "queueing {} bytes for {} until handshake completes",
+                       buf.len(), addr);
+
+                session.pending.push(buf.to_vec());
+
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn send_to_vectored(
+        &self,
+        addr: &Self::Addr,
+        bufs: &[IoSlice<'_>]
+    ) -> Result<usize, Error> {
+        // Each send is a single DTLS record, so gather the buffers.
+        let buf: Vec<u8> =
+            bufs.iter().flat_map(|buf| buf.iter().copied()).collect();
+
+        self.send_to(addr, &buf)
+    }
+
+    #[inline]
+    fn flush(&self) -> Result<(), Error> {
+        self.inner.flush()
+    }
+}
+
+impl<Inner> Receiver for DTLSSocket<Inner>
+where
+    Inner: Sender + Receiver,
+    Inner::Addr: Hash + Send
+{
+    type MsgCred = DTLSPeerIdentity;
+
+    fn recv_from(
+        &self,
+        buf: &mut [u8]
+    ) -> Result<(usize, Self::Addr, Option<Self::MsgCred>), Error> {
+        let (msg, addr, identity) = self.next_msg(false)?;
+        let len = msg.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&msg[..len]);
+
+        Ok((len, addr, Some(identity)))
+    }
+
+    fn recv_from_vectored(
+        &self,
+        bufs: &mut [IoSliceMut<'_>]
+    ) -> Result<(usize, Self::Addr, Option<Self::MsgCred>), Error> {
+        let (msg, addr, identity) = self.next_msg(false)?;
+        let mut len = 0;
+
+        for buf in bufs {
+            let n = (msg.len() - len).min(buf.len());
+
+            buf[..n].copy_from_slice(&msg[len..len + n]);
+            len += n;
+        }
+
+        Ok((len, addr, Some(identity)))
+    }
+
+    fn peek_from(
+        &self,
+        buf: &mut [u8]
+    ) -> Result<(usize, Self::Addr), Error> {
+        let (msg, addr, _) = self.next_msg(true)?;
+        let len = msg.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&msg[..len]);
+
+        Ok((len, addr))
+    }
+}
+
+/// Install the cookie callbacks needed by [DTLSSocket] on a server
+/// context.
+///
+/// Cookies are an HMAC of the peer's address, keyed with a random
+/// secret that is generated here.
+pub fn set_cookie_callbacks(
+    builder: &mut SslContextBuilder
+) -> Result<(), ErrorStack> {
+    let mut secret = [0; COOKIE_SECRET_LEN];
+
+    rand_bytes(&mut secret)?;
+
+    let key = PKey::hmac(&secret)?;
+    let verify_key = key.clone();
+
+    builder.set_options(SslOptions::COOKIE_EXCHANGE);
+    builder.set_cookie_generate_cb(move |ssl, buf| {
+        let cookie = cookie(&key, ssl)?;
+        let len = cookie.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&cookie[..len]);
+
+        Ok(len)
+    });
+    builder.set_cookie_verify_cb(move |ssl, buf| {
+        match cookie(&verify_key, ssl) {
+            Ok(cookie) => {
+                cookie.len() >= buf.len() &&
+                    !buf.is_empty() &&
+                    memcmp::eq(&cookie[..buf.len()], buf)
+            }
+            Err(_) => false
+        }
+    });
+
+    Ok(())
+}
+
+/// Get the index of the peer address used to generate cookies.
+fn cookie_addr_index() -> Result<Index<Ssl, Vec<u8>>, ErrorStack> {
+    static INDEX: OnceLock<Index<Ssl, Vec<u8>>> = OnceLock::new();
+
+    match INDEX.get() {
+        Some(index) => Ok(*index),
+        None => {
+            let index = Ssl::new_ex_index()?;
+
+            Ok(*INDEX.get_or_init(|| index))
+        }
+    }
+}
+
+/// Generate the cookie for the peer of `ssl`.
+fn cookie(
+    key: &PKey<Private>,
+    ssl: &SslRef
+) -> Result<Vec<u8>, ErrorStack> {
+    let addr = ssl
+        .ex_data(cookie_addr_index()?)
+        .ok_or_else(ErrorStack::get)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+
+    signer.update(addr)?;
+    signer.sign_to_vec()
+}
+
+/// Check whether `datagram` starts with a DTLS ClientHello in epoch
+/// 0.
+fn is_client_hello(datagram: &[u8]) -> bool {
+    // Record header: type (1), version (2), epoch (2), seq (6), len (2);
+    // followed by the handshake message type.
+    datagram.len() > 13 &&
+        datagram[0] == 22 &&
+        datagram[3..5] == [0, 0] &&
+        datagram[13] == 1
+}
+
+/// Get the [ErrorKind] for a handshake failure with OpenSSL error
+/// code `code`, and underlying IO error `io`.
+///
+/// This follows the [ScopedError] implementation for
+/// [HandshakeError](openssl::ssl::HandshakeError): IO errors keep
+/// their own kind, and everything else is a failure of the exchange
+/// with the peer.
+fn handshake_kind(
+    code: ErrorCode,
+    io: Option<&Error>
+) -> ErrorKind {
+    match (code, io) {
+        (ErrorCode::WANT_READ, _) |
+        (ErrorCode::WANT_WRITE, _) |
+        (ErrorCode::WANT_CLIENT_HELLO_CB, _) => ErrorKind::WouldBlock,
+        (ErrorCode::SYSCALL, Some(io)) => io.kind(),
+        _ => ErrorKind::ConnectionRefused
+    }
+}
+
+#[inline]
+fn poisoned() -> Error {
+    Error::other("mutex poisoned")
+}
+
+impl ScopedError for DTLSHandshakeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        Error::from(self.kind).scope()
+    }
+}
+
+impl Display for DTLSHandshakeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter
+    ) -> Result<(), std::fmt::Error> {
+        write!(f, "DTLS handshake failed: {}", self.desc)?;
+
+        if self.dropped > 0 {
+            write!(f, " ({} queued messages discarded)", self.dropped)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for DTLSHandshakeError {}
+
+#[cfg(test)]
+use std::net::SocketAddr;
+#[cfg(test)]
+use std::net::UdpSocket;
+#[cfg(test)]
+use std::sync::atomic::AtomicUsize;
+#[cfg(test)]
+use std::sync::atomic::Ordering;
+#[cfg(test)]
+use std::thread::spawn;
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use openssl::x509::X509PurposeId;
+
+#[cfg(test)]
+use crate::config::pki::testing::TestCertSpec;
+#[cfg(test)]
+use crate::config::pki::testing::TestPKI;
+#[cfg(test)]
+use crate::config::pki::X509VerifyFlag;
+#[cfg(test)]
+use crate::init;
+
+/// UDP socket that can be made to lose outgoing datagrams.
+#[cfg(test)]
+struct TestUdpSocket(UdpSocket, AtomicUsize);
+
+#[cfg(test)]
+impl TestUdpSocket {
+    /// Lose the next `count` outgoing datagrams.
+    fn lose(
+        &self,
+        count: usize
+    ) {
+        self.1.store(count, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+impl Socket for TestUdpSocket {
+    type Addr = SocketAddr;
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.0.local_addr()
+    }
+}
+
+#[cfg(test)]
+impl Sender for TestUdpSocket {
+    fn send_to(
+        &self,
+        addr: &SocketAddr,
+        buf: &[u8]
+    ) -> Result<usize, Error> {
+        let lost = self
+            .1
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok();
+
+        if lost {
+            Ok(buf.len())
+        } else {
+            self.0.send_to(buf, addr)
+        }
+    }
+
+    fn send_to_vectored(
+        &self,
+        addr: &SocketAddr,
+        bufs: &[IoSlice<'_>]
+    ) -> Result<usize, Error> {
+        let buf: Vec<u8> =
+            bufs.iter().flat_map(|buf| buf.iter().copied()).collect();
+
+        self.send_to(addr, &buf)
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Receiver for TestUdpSocket {
+    type MsgCred = ();
+
+    fn recv_from(
+        &self,
+        buf: &mut [u8]
+    ) -> Result<(usize, SocketAddr, Option<()>), Error> {
+        self.0.recv_from(buf).map(|(len, addr)| (len, addr, None))
+    }
+
+    fn recv_from_vectored(
+        &self,
+        _bufs: &mut [IoSliceMut<'_>]
+    ) -> Result<(usize, SocketAddr, Option<()>), Error> {
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+
+    fn peek_from(
+        &self,
+        buf: &mut [u8]
+    ) -> Result<(usize, SocketAddr), Error> {
+        self.0.peek_from(buf)
+    }
+}
+
+#[cfg(test)]
+fn test_socket(
+    pki: &TestPKI,
+    roots: &[&str],
+    side: &str
+) -> DTLSSocket<TestUdpSocket> {
+    test_socket_at(pki, roots, side, "127.0.0.1:0".parse().unwrap())
+}
+
+#[cfg(test)]
+fn test_socket_at(
+    pki: &TestPKI,
+    roots: &[&str],
+    side: &str,
+    addr: SocketAddr
+) -> DTLSSocket<TestUdpSocket> {
+    let socket = UdpSocket::bind(addr).unwrap();
+
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let trust_root = PKITrustRoot::new(
+        vec![],
+        roots.iter().map(|root| pki.path(root)).collect(),
+        vec![],
+        vec![],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let identity = match side {
+        "server" => pki.server_identity(),
+        _ => pki.client_identity()
+    };
+
+    DTLSSocket::create(
+        TestUdpSocket(socket, AtomicUsize::new(0)),
+        &trust_root,
+        &identity,
+        None
+    )
+    .unwrap()
+}
+
+/// Get the first ClientHello that `client` sends.
+#[cfg(test)]
+fn test_client_hello(client: &DTLSSocket<TestUdpSocket>) -> Vec<u8> {
+    let sniffer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut buf = vec![0; MAX_DATAGRAM];
+
+    sniffer
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client.connect(&sniffer.local_addr().unwrap()).unwrap();
+
+    let (len, _) = sniffer.recv_from(&mut buf).unwrap();
+
+    buf.truncate(len);
+
+    assert!(is_client_hello(&buf));
+
+    buf
+}
+
+/// Establish a session between `client` and `server`.
+#[cfg(test)]
+fn test_establish(
+    client: &DTLSSocket<TestUdpSocket>,
+    server: DTLSSocket<TestUdpSocket>
+) -> DTLSSocket<TestUdpSocket> {
+    let server_addr = server.local_addr().unwrap();
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, _) = server.recv_from(&mut buf).unwrap();
+
+        server.send_to(&addr, &buf[..len]).unwrap();
+        server
+    });
+    let mut buf = [0; 64];
+
+    client.send_to(&server_addr, b"hello").unwrap();
+
+    let (len, _, _) = client.recv_from(&mut buf).unwrap();
+
+    assert_eq!(b"hello", &buf[..len]);
+
+    peer.join().unwrap()
+}
+
+#[test]
+fn test_dtls_exchange() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let server = test_socket(&pki, &roots, "server");
+    let client_addr = client.local_addr().unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, cred) = server.recv_from(&mut buf).unwrap();
+        let cred = cred.unwrap();
+
+        assert_eq!(b"hello", &buf[..len]);
+        assert_eq!(client_addr, addr);
+        assert_eq!(&["test-client.nowhere.com"], cred.dns_names());
+        assert!(server.is_established(&addr).unwrap());
+
+        server.send_to(&addr, b"world").unwrap();
+        server
+    });
+
+    assert_eq!(5, client.send_to(&server_addr, b"hello").unwrap());
+    assert!(!client.is_established(&server_addr).unwrap());
+
+    let mut buf = [0; 64];
+    let (len, addr) = client.peek_from(&mut buf).unwrap();
+
+    assert_eq!(b"world", &buf[..len]);
+    assert_eq!(server_addr, addr);
+
+    let (len, addr, cred) = client.recv_from(&mut buf).unwrap();
+    let cred = cred.unwrap();
+
+    assert_eq!(b"world", &buf[..len]);
+    assert_eq!(server_addr, addr);
+    assert_eq!(&["test-server.nowhere.com"], cred.dns_names());
+    assert!(cred.subject().contains("CN=test-server.nowhere.com"));
+    assert!(client.peer_identity(&server_addr).unwrap().is_some());
+
+    let _server = peer.join().unwrap();
+
+    assert!(client.close(&server_addr).unwrap());
+    assert!(!client.close(&server_addr).unwrap());
+}
+
+#[test]
+fn test_dtls_untrusted_peer() {
+    init();
+
+    // The client does not trust the server's CA.
+    let pki = TestPKI::generate().unwrap();
+    let client = test_socket(&pki, &["client/ca_cert.pem"], "client");
+    let server = test_socket(
+        &pki,
+        &["client/ca_cert.pem", "server/ca_cert.pem"],
+        "server"
+    );
+    let server_addr = server.local_addr().unwrap();
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+
+        server.recv_from(&mut buf).is_err()
+    });
+
+    client.send_to(&server_addr, b"hello").unwrap();
+
+    let mut buf = [0; 64];
+    let err = client.recv_from(&mut buf).unwrap_err();
+    let handshake = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<DTLSHandshakeError>())
+        .expect("expected handshake error");
+
+    assert_eq!(ErrorScope::External, handshake.scope());
+    assert_eq!(ErrorScope::External, err.scope());
+    assert!(peer.join().unwrap());
+}
+
+#[test]
+fn test_dtls_handshake_scope() {
+    init();
+
+    let io = Error::from(ErrorKind::PermissionDenied);
+    let scope = |code, io| {
+        DTLSHandshakeError {
+            kind: handshake_kind(code, io),
+            desc: String::new(),
+            dropped: 0
+        }
+        .scope()
+    };
+
+    assert_eq!(ErrorScope::Retryable, scope(ErrorCode::WANT_READ, None));
+    assert_eq!(ErrorScope::Retryable, scope(ErrorCode::WANT_WRITE, None));
+    assert_eq!(ErrorScope::System, scope(ErrorCode::SYSCALL, Some(&io)));
+    assert_eq!(ErrorScope::External, scope(ErrorCode::SYSCALL, None));
+    assert_eq!(ErrorScope::External, scope(ErrorCode::SSL, None));
+    assert_eq!(ErrorScope::External, scope(ErrorCode::ZERO_RETURN, None));
+
+    // The scope is preserved when wrapped in an IO error.
+    let err = Error::from(DTLSHandshakeError {
+        kind: handshake_kind(ErrorCode::SYSCALL, Some(&io)),
+        desc: io.to_string(),
+        dropped: 0
+    });
+
+    assert_eq!(ErrorKind::PermissionDenied, err.kind());
+    assert_eq!(ErrorScope::System, err.scope());
+}
+
+#[test]
+fn test_dtls_cookie_exchange() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let server = test_socket(&pki, &roots, "server");
+    let client_addr = client.local_addr().unwrap();
+    let hello = test_client_hello(&client);
+
+    // A ClientHello without a cookie only gets a HelloVerifyRequest.
+    server.process(client_addr, hello).unwrap();
+
+    assert!(server.sessions.lock().unwrap().is_empty());
+
+    let mut buf = vec![0; MAX_DATAGRAM];
+    let (len, _) = client.inner().0.recv_from(&mut buf).unwrap();
+
+    assert!(len > 13);
+    assert_eq!(22, buf[0]);
+    assert_eq!(3, buf[13]);
+}
+
+#[test]
+fn test_dtls_max_sessions() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let mut server = test_socket(&pki, &roots, "server");
+    let client_addr = client.local_addr().unwrap();
+    let hello = test_client_hello(&client);
+
+    server.set_max_sessions(0);
+    server.process(client_addr, hello).unwrap();
+
+    assert!(server.sessions.lock().unwrap().is_empty());
+
+    client
+        .inner()
+        .0
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+
+    let mut buf = vec![0; MAX_DATAGRAM];
+
+    assert!(client.inner().0.recv_from(&mut buf).is_err());
+
+    let err = server.send_to(&client_addr, b"hello").unwrap_err();
+
+    assert_eq!(ErrorKind::OutOfMemory, err.kind());
+}
+
+#[test]
+fn test_dtls_spoofed_client_hello() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let server = test_socket(&pki, &roots, "server");
+    let other = test_socket(&pki, &roots, "client");
+    let client_addr = client.local_addr().unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server = test_establish(&client, server);
+    let hello = test_client_hello(&other);
+
+    // A ClientHello claiming to be from the client does not disturb
+    // the established session.
+    server.process(client_addr, hello).unwrap();
+
+    assert!(server.is_established(&client_addr).unwrap());
+    assert!(server.sessions.lock().unwrap()[&client_addr]
+        .restart
+        .is_none());
+
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, _) = server.recv_from(&mut buf).unwrap();
+
+        assert_eq!(b"again", &buf[..len]);
+        assert_eq!(client_addr, addr);
+    });
+
+    client.send_to(&server_addr, b"again").unwrap();
+    peer.join().unwrap();
+}
+
+#[test]
+fn test_dtls_peer_restart() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let server = test_socket(&pki, &roots, "server");
+    let client_addr = client.local_addr().unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server = test_establish(&client, server);
+
+    // Restart the client on the same address, without closing.
+    drop(client);
+
+    let client = test_socket_at(&pki, &roots, "client", client_addr);
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, cred) = server.recv_from(&mut buf).unwrap();
+
+        assert_eq!(b"again", &buf[..len]);
+        assert_eq!(client_addr, addr);
+        assert_eq!(&["test-client.nowhere.com"], cred.unwrap().dns_names());
+
+        server.send_to(&addr, b"welcome back").unwrap();
+    });
+
+    client.send_to(&server_addr, b"again").unwrap();
+
+    let mut buf = [0; 64];
+    let (len, _, _) = client.recv_from(&mut buf).unwrap();
+
+    assert_eq!(b"welcome back", &buf[..len]);
+    peer.join().unwrap();
+}
+
+#[test]
+fn test_dtls_retransmit() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let client = test_socket(&pki, &roots, "client");
+    let server = test_socket(&pki, &roots, "server");
+    let server_addr = server.local_addr().unwrap();
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, _) = server.recv_from(&mut buf).unwrap();
+
+        server.send_to(&addr, &buf[..len]).unwrap();
+    });
+
+    // Lose the first ClientHello.
+    client.inner().lose(1);
+    client
+        .inner()
+        .0
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    client.send_to(&server_addr, b"hello").unwrap();
+
+    let mut buf = [0; 64];
+    let mut result = client.recv_from(&mut buf);
+
+    for _ in 0..25 {
+        match &result {
+            Err(err)
+                if err.kind() == ErrorKind::WouldBlock ||
+                    err.kind() == ErrorKind::TimedOut =>
+            {
+                result = client.recv_from(&mut buf)
+            }
+            _ => break
+        }
+    }
+
+    let (len, _, _) = result.unwrap();
+
+    assert_eq!(b"hello", &buf[..len]);
+    peer.join().unwrap();
+}
+
+#[test]
+fn test_dtls_reloaded_crl() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let crl = pki.path("client/crl/crl.pem");
+    let next_update = SystemTime::now() + Duration::from_secs(3600);
+
+    pki.client_ca().write_crl(&crl, &[], next_update).unwrap();
+
+    let trust_root = PKITrustRoot::new(
+        vec![],
+        roots.iter().map(|root| pki.path(root)).collect(),
+        vec![crl.clone()],
+        vec![X509VerifyFlag::CRLCheck],
+        vec![],
+        None,
+        None,
+        None,
+        None
+    );
+    let store = Arc::new(
+        ReloadableTrustStore::new(trust_root, None, None, X509PurposeId::ANY)
+            .unwrap()
+    );
+    let client = test_socket(&pki, &roots, "client");
+    let mut server = test_socket(&pki, &roots, "server");
+    let server_addr = server.local_addr().unwrap();
+
+    server.set_trust_store(store.clone());
+
+    let server = test_establish(&client, server);
+
+    // Revoke the client's certificate after the socket was created.
+    let cert = X509::from_pem(
+        &std::fs::read(pki.path("client/certs/test_client_cert.pem")).unwrap()
+    )
+    .unwrap();
+
+    pki.client_ca()
+        .write_crl(&crl, &[&cert], next_update)
+        .unwrap();
+    store.reload().unwrap();
+
+    let other = test_socket(&pki, &roots, "client");
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+
+        server.recv_from(&mut buf).is_err()
+    });
+
+    other.send_to(&server_addr, b"hello").unwrap();
+
+    let mut buf = [0; 64];
+
+    assert!(other.recv_from(&mut buf).is_err());
+    assert!(peer.join().unwrap());
+}
+
+#[test]
+fn test_dtls_reloaded_identity() {
+    init();
+
+    let pki = TestPKI::generate().unwrap();
+    let roots = ["client/ca_cert.pem", "server/ca_cert.pem"];
+    let cert_path = pki.path("server/live_cert.pem");
+    let key_path = pki.path("server/live_key.pem");
+    let identity = pki
+        .server_ca()
+        .issue(&TestCertSpec::server("test-server.nowhere.com"))
+        .unwrap()
+        .write(&cert_path, &key_path)
+        .unwrap();
+    let identity = Arc::new(ReloadableIdentity::new(identity).unwrap());
+    let client = test_socket(&pki, &roots, "client");
+    let mut server = test_socket(&pki, &roots, "server");
+    let server_addr = server.local_addr().unwrap();
+
+    server.set_identity(identity.clone());
+
+    let server = test_establish(&client, server);
+
+    // Renew the server's certificate after the socket was created.
+    pki.server_ca()
+        .issue(&TestCertSpec::server("renewed-server.nowhere.com"))
+        .unwrap()
+        .write(&cert_path, &key_path)
+        .unwrap();
+    identity.reload().unwrap();
+
+    let other = test_socket(&pki, &roots, "client");
+    let peer = spawn(move || {
+        let mut buf = [0; 64];
+        let (len, addr, _) = server.recv_from(&mut buf).unwrap();
+
+        server.send_to(&addr, &buf[..len]).unwrap();
+    });
+
+    other.send_to(&server_addr, b"hello").unwrap();
+
+    let mut buf = [0; 64];
+    let (_, _, cred) = other.recv_from(&mut buf).unwrap();
+
+    assert!(cred
+        .unwrap()
+        .subject()
+        .contains("CN=renewed-server.nowhere.com"));
+    peer.join().unwrap();
+}
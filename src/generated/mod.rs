@@ -16,4 +16,5 @@
 // License along with this program.  If not, see
 // <https://www.gnu.org/licenses/>.
 
+pub mod hash_id;
 pub mod version;
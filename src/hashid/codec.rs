@@ -0,0 +1,317 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Compact binary encoding for [CompoundHashID]s.
+//!
+//! This module provides [CompoundHashIDCodec], which encodes a
+//! [CompoundHashID] as a single tag byte identifying the algorithm,
+//! followed by the raw hash value.  The length of the hash value is
+//! determined by the algorithm, so no length is encoded.
+//!
+//! The tags are as follows:
+//!
+//! | Tag    | Algorithm  |
+//! |--------|------------|
+//! | `0x01` | Blake2b    |
+//! | `0x02` | RipeMD-160 |
+//! | `0x03` | SHA3-512   |
+//! | `0x04` | SHA384     |
+//! | `0x05` | Skein-512  |
+//! | `0x06` | Whirlpool  |
+//! | `0x07` | Blake2s    |
+//! | `0x08` | BLAKE3     |
+//! | `0x09` | SHA256     |
+//! | `0x0a` | SHA512-256 |
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::BLAKE3Algo;
+use crate::hashid::Blake2bAlgo;
+use crate::hashid::Blake2bID;
+use crate::hashid::Blake2sAlgo;
+use crate::hashid::Blake2sID;
+use crate::hashid::CompoundHashAlgo;
+use crate::hashid::CompoundHashID;
+use crate::hashid::HashAlgo;
+use crate::hashid::HashID;
+use crate::hashid::RipeMD160Algo;
+use crate::hashid::RipeMD160ID;
+use crate::hashid::SHA256Algo;
+use crate::hashid::SHA384Algo;
+use crate::hashid::SHA3Algo;
+use crate::hashid::SHA512_256Algo;
+use crate::hashid::SkeinAlgo;
+use crate::hashid::SkeinID;
+use crate::hashid::WhirlpoolAlgo;
+use crate::hashid::WhirlpoolID;
+use crate::hashid::BLAKE3ID;
+use crate::hashid::SHA256ID;
+use crate::hashid::SHA384ID;
+use crate::hashid::SHA3ID;
+use crate::hashid::SHA512_256ID;
+
+const BLAKE2B_TAG: u8 = 0x01;
+const RIPEMD160_TAG: u8 = 0x02;
+const SHA3_TAG: u8 = 0x03;
+const SHA384_TAG: u8 = 0x04;
+const SKEIN_TAG: u8 = 0x05;
+const WHIRLPOOL_TAG: u8 = 0x06;
+const BLAKE2S_TAG: u8 = 0x07;
+const BLAKE3_TAG: u8 = 0x08;
+const SHA256_TAG: u8 = 0x09;
+const SHA512_256_TAG: u8 = 0x0a;
+
+/// Size of the largest hash value.
+const MAX_HASH_LEN: usize = 64;
+
+/// Codec for the compact binary encoding of [CompoundHashID]s.
+///
+/// See the [module documentation](crate::hashid::codec) for details.
+#[derive(Clone, Debug, Default)]
+pub struct CompoundHashIDCodec;
+
+/// Errors that can occur when decoding a [CompoundHashID].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HashIDDecodeError {
+    /// The input was too short.
+    Truncated,
+    /// The algorithm tag was not recognized.
+    UnknownTag {
+        /// The tag.
+        tag: u8
+    }
+}
+
+impl CompoundHashIDCodec {
+    /// Get the tag for `id`.
+    fn tag(id: &CompoundHashID) -> u8 {
+        match id {
+            CompoundHashID::Blake2b { .. } => BLAKE2B_TAG,
+            CompoundHashID::RipeMD160 { .. } => RIPEMD160_TAG,
+            CompoundHashID::SHA3 { .. } => SHA3_TAG,
+            CompoundHashID::SHA384 { .. } => SHA384_TAG,
+            CompoundHashID::Skein { .. } => SKEIN_TAG,
+            CompoundHashID::Whirlpool { .. } => WHIRLPOOL_TAG,
+            CompoundHashID::Blake2s { .. } => BLAKE2S_TAG,
+            CompoundHashID::BLAKE3 { .. } => BLAKE3_TAG,
+            CompoundHashID::SHA256 { .. } => SHA256_TAG,
+            CompoundHashID::SHA512_256 { .. } => SHA512_256_TAG
+        }
+    }
+
+    /// Get the algorithm and hash length for `tag`.
+    fn algo(tag: u8) -> Result<(CompoundHashAlgo, usize), HashIDDecodeError> {
+        match tag {
+            BLAKE2B_TAG => Ok((
+                CompoundHashAlgo::Blake2b {
+                    blake2b: Blake2bAlgo
+                },
+                Blake2bID::HASH_LEN
+            )),
+            RIPEMD160_TAG => Ok((
+                CompoundHashAlgo::RipeMD160 {
+                    ripemd160: RipeMD160Algo
+                },
+                RipeMD160ID::HASH_LEN
+            )),
+            SHA3_TAG => Ok((
+                CompoundHashAlgo::SHA3 { sha3: SHA3Algo },
+                SHA3ID::HASH_LEN
+            )),
+            SHA384_TAG => Ok((
+                CompoundHashAlgo::SHA384 { sha384: SHA384Algo },
+                SHA384ID::HASH_LEN
+            )),
+            SKEIN_TAG => Ok((
+                CompoundHashAlgo::Skein { skein: SkeinAlgo },
+                SkeinID::HASH_LEN
+            )),
+            WHIRLPOOL_TAG => Ok((
+                CompoundHashAlgo::Whirlpool {
+                    whirlpool: WhirlpoolAlgo
+                },
+                WhirlpoolID::HASH_LEN
+            )),
+            BLAKE2S_TAG => Ok((
+                CompoundHashAlgo::Blake2s {
+                    blake2s: Blake2sAlgo
+                },
+                Blake2sID::HASH_LEN
+            )),
+            BLAKE3_TAG => Ok((
+                CompoundHashAlgo::BLAKE3 { blake3: BLAKE3Algo },
+                BLAKE3ID::HASH_LEN
+            )),
+            SHA256_TAG => Ok((
+                CompoundHashAlgo::SHA256 { sha256: SHA256Algo },
+                SHA256ID::HASH_LEN
+            )),
+            SHA512_256_TAG => Ok((
+                CompoundHashAlgo::SHA512_256 {
+                    sha512_256: SHA512_256Algo
+                },
+                SHA512_256ID::HASH_LEN
+            )),
+            tag => Err(HashIDDecodeError::UnknownTag { tag: tag })
+        }
+    }
+}
+
+impl DatagramCodec<CompoundHashID> for CompoundHashIDCodec {
+    type CreateError = Infallible;
+    type DecodeError = HashIDDecodeError;
+    type EncodeError = Infallible;
+    type Param = ();
+
+    const MAX_BYTES: usize = 1 + MAX_HASH_LEN;
+
+    #[inline]
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(CompoundHashIDCodec)
+    }
+
+    fn encode(
+        &mut self,
+        val: &CompoundHashID,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let bytes = val.bytes();
+
+        buf[0] = Self::tag(val);
+        buf[1..bytes.len() + 1].copy_from_slice(bytes);
+
+        Ok(bytes.len() + 1)
+    }
+
+    #[inline]
+    fn encode_to_vec(
+        &mut self,
+        val: &CompoundHashID
+    ) -> Result<Vec<u8>, Self::EncodeError> {
+        let bytes = val.bytes();
+        let mut out = Vec::with_capacity(bytes.len() + 1);
+
+        out.push(Self::tag(val));
+        out.extend_from_slice(bytes);
+
+        Ok(out)
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(CompoundHashID, usize), Self::DecodeError> {
+        let tag = *buf.first().ok_or(HashIDDecodeError::Truncated)?;
+        let (algo, len) = Self::algo(tag)?;
+        let bytes = buf.get(1..len + 1).ok_or(HashIDDecodeError::Truncated)?;
+        let id = algo
+            .wrap_hashed_bytes(bytes)
+            .map_err(|_| HashIDDecodeError::Truncated)?;
+
+        Ok((id, len + 1))
+    }
+}
+
+impl ScopedError for HashIDDecodeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl Display for HashIDDecodeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            HashIDDecodeError::Truncated => write!(f, "hash ID truncated"),
+            HashIDDecodeError::UnknownTag { tag } => {
+                write!(f, "unknown hash algorithm tag {:02x}", tag)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::convert::TryFrom;
+
+#[test]
+fn test_hashid_codec_round_trip() {
+    let mut codec = CompoundHashIDCodec::create(()).unwrap();
+
+    for name in [
+        "Blake2b",
+        "RipeMD-160",
+        "SHA3-512",
+        "SHA384",
+        "Skein",
+        "Whirlpool",
+        "Blake2s",
+        "BLAKE3",
+        "SHA256",
+        "SHA512-256"
+    ] {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let id = algo.hash_bytes(b"test data");
+        let mut buf = [0; CompoundHashIDCodec::MAX_BYTES];
+        let len = codec.encode(&id, &mut buf).unwrap();
+        let vec = codec.encode_to_vec(&id).unwrap();
+        let (decoded, nbytes) = codec.decode(&buf).unwrap();
+
+        assert_eq!(&buf[..len], &vec[..]);
+        assert_eq!(id.bytes().len() + 1, len);
+        assert_eq!(len, nbytes);
+        assert_eq!(id, decoded);
+    }
+}
+
+#[test]
+fn test_hashid_codec_encode() {
+    let mut codec = CompoundHashIDCodec::create(()).unwrap();
+    let id = RipeMD160Algo.null_hash();
+    let id = CompoundHashID::RipeMD160 { ripemd160: id };
+    let expected = [
+        0x02, 0x9c, 0x11, 0x85, 0xa5, 0xc5, 0xe9, 0xfc, 0x54, 0x61, 0x28, 0x08,
+        0x97, 0x7e, 0xe8, 0xf5, 0x48, 0xb2, 0x25, 0x8d, 0x31
+    ];
+
+    assert_eq!(&expected[..], &codec.encode_to_vec(&id).unwrap()[..]);
+}
+
+#[test]
+fn test_hashid_codec_bad() {
+    let mut codec = CompoundHashIDCodec::create(()).unwrap();
+    let encoded = codec
+        .encode_to_vec(&CompoundHashAlgo::default().null_hash())
+        .unwrap();
+
+    assert_eq!(Err(HashIDDecodeError::Truncated), codec.decode(&[]));
+    assert_eq!(
+        Err(HashIDDecodeError::Truncated),
+        codec.decode(&encoded[..encoded.len() - 1])
+    );
+    assert_eq!(
+        Err(HashIDDecodeError::UnknownTag { tag: 0x00 }),
+        codec.decode(&[0x00; 65])
+    );
+}
@@ -0,0 +1,882 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Keyed hash algorithms, for generating authenticated IDs.
+//!
+//! This module provides the [KeyedHashAlgo] trait, which parallels
+//! [HashAlgo] for keyed hash functions (message authentication
+//! codes).  A [KeyedHashAlgo] combined with a key using
+//! [with_key](KeyedHashAlgo::with_key) produces a [KeyedHash], which
+//! implements [HashAlgo], and can be used anywhere an unkeyed hash
+//! can.
+//!
+//! The IDs produced by keyed hash algorithms are compared in constant
+//! time, both by their [PartialEq] instances, and by
+//! [ConstantTimeEq].
+use std::array::TryFromSliceError;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::hash::Hash;
+
+use blake2::Blake2bMac512;
+use digest::InvalidLength;
+use digest::Mac;
+use hmac::Hmac;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::Serializer;
+use sha2::Sha384;
+use sha3::Sha3_512;
+use subtle::Choice;
+use subtle::ConstantTimeEq;
+
+use crate::hashid::HashAlgo;
+use crate::hashid::HashID;
+use crate::hashid::Hasher;
+
+/// Trait for specific keyed cryptographic hash algorithms.
+///
+/// This parallels [HashAlgo], except that hashing requires a key.
+/// Use [with_key](KeyedHashAlgo::with_key) to obtain a [HashAlgo]
+/// instance that uses a specific key.
+pub trait KeyedHashAlgo: Clone {
+    type HashID: HashID + ConstantTimeEq;
+    type Hasher: Hasher<HashID = Self::HashID> + Clone;
+
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError>;
+
+    /// Start an incremental hash using `key`.
+    ///
+    /// This will fail if `key` is not a valid length for this
+    /// algorithm.
+    fn start(
+        &self,
+        key: &[u8]
+    ) -> Result<Self::Hasher, InvalidLength>;
+
+    /// Get a [HashAlgo] instance that hashes using `key`.
+    ///
+    /// This will fail if `key` is not a valid length for this
+    /// algorithm.
+    #[inline]
+    fn with_key(
+        &self,
+        key: &[u8]
+    ) -> Result<KeyedHash<Self>, InvalidLength> {
+        Ok(KeyedHash {
+            init: self.start(key)?,
+            algo: self.clone()
+        })
+    }
+
+    /// Hash `bytes` using `key`.
+    #[inline]
+    fn hash_bytes(
+        &self,
+        key: &[u8],
+        bytes: &[u8]
+    ) -> Result<Self::HashID, InvalidLength> {
+        let mut hasher = self.start(key)?;
+
+        hasher.update(bytes);
+
+        Ok(hasher.finish())
+    }
+
+    /// Check whether `id` is the keyed hash of `bytes` under `key`.
+    ///
+    /// The comparison is done in constant time.
+    #[inline]
+    fn verify(
+        &self,
+        key: &[u8],
+        bytes: &[u8],
+        id: &Self::HashID
+    ) -> Result<bool, InvalidLength> {
+        Ok(self.hash_bytes(key, bytes)?.ct_eq(id).into())
+    }
+}
+
+/// A [KeyedHashAlgo] combined with a specific key.
+///
+/// This implements [HashAlgo], and can be used wherever an unkeyed
+/// algorithm can be used.
+#[derive(Clone)]
+pub struct KeyedHash<Algo: KeyedHashAlgo> {
+    algo: Algo,
+    /// Hasher that has been initialized with the key.
+    init: Algo::Hasher
+}
+
+/// [KeyedHashAlgo] using the Blake2b algorithm in keyed mode.
+///
+/// Keys may be at most 64 bytes.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Blake2bMacAlgo;
+
+/// [HashID] using the Blake2b algorithm in keyed mode.
+#[derive(Clone, Debug, Eq)]
+pub struct Blake2bMacID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the Blake2b algorithm in keyed mode.
+#[derive(Clone)]
+pub struct Blake2bMacHasher {
+    mac: Blake2bMac512
+}
+
+/// [KeyedHashAlgo] using HMAC with the SHA384 (SHA2-384) algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HMACSHA384Algo;
+
+/// [HashID] using HMAC with the SHA384 (SHA2-384) algorithm.
+#[derive(Clone, Debug, Eq)]
+pub struct HMACSHA384ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using HMAC with the SHA384 (SHA2-384) algorithm.
+#[derive(Clone)]
+pub struct HMACSHA384Hasher {
+    mac: Hmac<Sha384>
+}
+
+/// [KeyedHashAlgo] using HMAC with the SHA3-512 algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HMACSHA3Algo;
+
+/// [HashID] using HMAC with the SHA3-512 algorithm.
+#[derive(Clone, Debug, Eq)]
+pub struct HMACSHA3ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using HMAC with the SHA3-512 algorithm.
+#[derive(Clone)]
+pub struct HMACSHA3Hasher {
+    mac: Hmac<Sha3_512>
+}
+
+/// [KeyedHashAlgo] instance capable of using a dynamically-configured
+/// keyed hash function.
+///
+/// This also can serve as a configuration object, and can be
+/// deserialized.
+///
+/// # YAML Format
+///
+/// This is represented as a string naming the algorithm, which is
+/// one of `Blake2b-MAC`, `HMAC-SHA384`, or `HMAC-SHA3-512`:
+///
+/// ```yaml
+/// HMAC-SHA3-512
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub enum CompoundKeyedHashAlgo {
+    /// The Blake2b algorithm in keyed mode.
+    Blake2bMac { blake2b: Blake2bMacAlgo },
+    /// HMAC with the SHA384 algorithm.
+    HMACSHA384 { sha384: HMACSHA384Algo },
+    /// HMAC with the SHA3-512 algorithm.
+    HMACSHA3 { sha3: HMACSHA3Algo }
+}
+
+/// [HashID] instance representing an ID generated from a
+/// dynamically-configured keyed hash algorithm.
+#[derive(Clone, Debug, Eq)]
+pub enum CompoundKeyedHashID {
+    /// ID generated from the Blake2b algorithm in keyed mode.
+    Blake2bMac { blake2b: Blake2bMacID },
+    /// ID generated from HMAC with the SHA384 algorithm.
+    HMACSHA384 { sha384: HMACSHA384ID },
+    /// ID generated from HMAC with the SHA3-512 algorithm.
+    HMACSHA3 { sha3: HMACSHA3ID }
+}
+
+/// [Hasher] instance for a dynamically-configured keyed hash
+/// algorithm.
+#[derive(Clone)]
+pub enum CompoundKeyedHasher {
+    /// Hasher for the Blake2b algorithm in keyed mode.
+    Blake2bMac { blake2b: Blake2bMacHasher },
+    /// Hasher for HMAC with the SHA384 algorithm.
+    HMACSHA384 { sha384: HMACSHA384Hasher },
+    /// Hasher for HMAC with the SHA3-512 algorithm.
+    HMACSHA3 { sha3: HMACSHA3Hasher }
+}
+
+impl<Algo> KeyedHash<Algo>
+where
+    Algo: KeyedHashAlgo
+{
+    /// Get the underlying [KeyedHashAlgo].
+    #[inline]
+    pub fn algo(&self) -> &Algo {
+        &self.algo
+    }
+
+    /// Check whether `id` is the keyed hash of `bytes`.
+    ///
+    /// The comparison is done in constant time.
+    #[inline]
+    pub fn verify(
+        &self,
+        bytes: &[u8],
+        id: &Algo::HashID
+    ) -> bool {
+        self.hash_bytes(bytes).ct_eq(id).into()
+    }
+}
+
+impl<Algo> HashAlgo for KeyedHash<Algo>
+where
+    Algo: KeyedHashAlgo
+{
+    type HashID = Algo::HashID;
+    type Hasher = Algo::Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        self.algo.wrap_hashed_bytes(bytes)
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        self.init.clone()
+    }
+}
+
+impl KeyedHashAlgo for Blake2bMacAlgo {
+    type HashID = Blake2bMacID;
+    type Hasher = Blake2bMacHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(Blake2bMacID { id: id })
+    }
+
+    #[inline]
+    fn start(
+        &self,
+        key: &[u8]
+    ) -> Result<Self::Hasher, InvalidLength> {
+        Ok(Blake2bMacHasher {
+            mac: Blake2bMac512::new_from_slice(key)?
+        })
+    }
+}
+
+impl Hasher for Blake2bMacHasher {
+    type HashID = Blake2bMacID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.mac.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.mac.finalize().into_bytes();
+        let mut id = [0; Blake2bMacID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        Blake2bMacID { id: id }
+    }
+}
+
+impl Blake2bMacID {
+    const HASH_LEN: usize = 512 / 8;
+}
+
+impl HashID for Blake2bMacID {
+    #[inline]
+    fn name(&self) -> &str {
+        "Blake2b-MAC"
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl ConstantTimeEq for Blake2bMacID {
+    #[inline]
+    fn ct_eq(
+        &self,
+        other: &Self
+    ) -> Choice {
+        self.id.ct_eq(&other.id)
+    }
+}
+
+impl PartialEq for Blake2bMacID {
+    #[inline]
+    fn eq(
+        &self,
+        other: &Self
+    ) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Hash for Blake2bMacID {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(
+        &self,
+        state: &mut H
+    ) {
+        self.id.hash(state)
+    }
+}
+
+impl Display for Blake2bMacID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl KeyedHashAlgo for HMACSHA384Algo {
+    type HashID = HMACSHA384ID;
+    type Hasher = HMACSHA384Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(HMACSHA384ID { id: id })
+    }
+
+    #[inline]
+    fn start(
+        &self,
+        key: &[u8]
+    ) -> Result<Self::Hasher, InvalidLength> {
+        Ok(HMACSHA384Hasher {
+            mac: Hmac::new_from_slice(key)?
+        })
+    }
+}
+
+impl Hasher for HMACSHA384Hasher {
+    type HashID = HMACSHA384ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.mac.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.mac.finalize().into_bytes();
+        let mut id = [0; HMACSHA384ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        HMACSHA384ID { id: id }
+    }
+}
+
+impl HMACSHA384ID {
+    const HASH_LEN: usize = 384 / 8;
+}
+
+impl HashID for HMACSHA384ID {
+    #[inline]
+    fn name(&self) -> &str {
+        "HMAC-SHA384"
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl ConstantTimeEq for HMACSHA384ID {
+    #[inline]
+    fn ct_eq(
+        &self,
+        other: &Self
+    ) -> Choice {
+        self.id.ct_eq(&other.id)
+    }
+}
+
+impl PartialEq for HMACSHA384ID {
+    #[inline]
+    fn eq(
+        &self,
+        other: &Self
+    ) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Hash for HMACSHA384ID {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(
+        &self,
+        state: &mut H
+    ) {
+        self.id.hash(state)
+    }
+}
+
+impl Display for HMACSHA384ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl KeyedHashAlgo for HMACSHA3Algo {
+    type HashID = HMACSHA3ID;
+    type Hasher = HMACSHA3Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(HMACSHA3ID { id: id })
+    }
+
+    #[inline]
+    fn start(
+        &self,
+        key: &[u8]
+    ) -> Result<Self::Hasher, InvalidLength> {
+        Ok(HMACSHA3Hasher {
+            mac: Hmac::new_from_slice(key)?
+        })
+    }
+}
+
+impl Hasher for HMACSHA3Hasher {
+    type HashID = HMACSHA3ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.mac.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.mac.finalize().into_bytes();
+        let mut id = [0; HMACSHA3ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        HMACSHA3ID { id: id }
+    }
+}
+
+impl HMACSHA3ID {
+    const HASH_LEN: usize = 512 / 8;
+}
+
+impl HashID for HMACSHA3ID {
+    #[inline]
+    fn name(&self) -> &str {
+        "HMAC-SHA3-512"
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl ConstantTimeEq for HMACSHA3ID {
+    #[inline]
+    fn ct_eq(
+        &self,
+        other: &Self
+    ) -> Choice {
+        self.id.ct_eq(&other.id)
+    }
+}
+
+impl PartialEq for HMACSHA3ID {
+    #[inline]
+    fn eq(
+        &self,
+        other: &Self
+    ) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Hash for HMACSHA3ID {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(
+        &self,
+        state: &mut H
+    ) {
+        self.id.hash(state)
+    }
+}
+
+impl Display for HMACSHA3ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CompoundKeyedHashAlgo {
+    #[inline]
+    fn default() -> Self {
+        CompoundKeyedHashAlgo::HMACSHA3 { sha3: HMACSHA3Algo }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for CompoundKeyedHashAlgo {
+    type Error = &'a str;
+
+    fn try_from(name: &'a str) -> Result<CompoundKeyedHashAlgo, &'a str> {
+        match name {
+            "Blake2b-MAC" => Ok(CompoundKeyedHashAlgo::Blake2bMac {
+                blake2b: Blake2bMacAlgo
+            }),
+            "HMAC-SHA384" => Ok(CompoundKeyedHashAlgo::HMACSHA384 {
+                sha384: HMACSHA384Algo
+            }),
+            "HMAC-SHA3-512" => {
+                Ok(CompoundKeyedHashAlgo::HMACSHA3 { sha3: HMACSHA3Algo })
+            }
+            err => Err(err)
+        }
+    }
+}
+
+impl Serialize for CompoundKeyedHashAlgo {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        match self {
+            CompoundKeyedHashAlgo::Blake2bMac { .. } => {
+                serializer.serialize_str("Blake2b-MAC")
+            }
+            CompoundKeyedHashAlgo::HMACSHA384 { .. } => {
+                serializer.serialize_str("HMAC-SHA384")
+            }
+            CompoundKeyedHashAlgo::HMACSHA3 { .. } => {
+                serializer.serialize_str("HMAC-SHA3-512")
+            }
+        }
+    }
+}
+
+impl KeyedHashAlgo for CompoundKeyedHashAlgo {
+    type HashID = CompoundKeyedHashID;
+    type Hasher = CompoundKeyedHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        match self {
+            CompoundKeyedHashAlgo::Blake2bMac { blake2b } => blake2b
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundKeyedHashID::Blake2bMac { blake2b: out }),
+            CompoundKeyedHashAlgo::HMACSHA384 { sha384 } => sha384
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundKeyedHashID::HMACSHA384 { sha384: out }),
+            CompoundKeyedHashAlgo::HMACSHA3 { sha3 } => sha3
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundKeyedHashID::HMACSHA3 { sha3: out })
+        }
+    }
+
+    fn start(
+        &self,
+        key: &[u8]
+    ) -> Result<Self::Hasher, InvalidLength> {
+        match self {
+            CompoundKeyedHashAlgo::Blake2bMac { blake2b } => {
+                Ok(CompoundKeyedHasher::Blake2bMac {
+                    blake2b: blake2b.start(key)?
+                })
+            }
+            CompoundKeyedHashAlgo::HMACSHA384 { sha384 } => {
+                Ok(CompoundKeyedHasher::HMACSHA384 {
+                    sha384: sha384.start(key)?
+                })
+            }
+            CompoundKeyedHashAlgo::HMACSHA3 { sha3 } => {
+                Ok(CompoundKeyedHasher::HMACSHA3 {
+                    sha3: sha3.start(key)?
+                })
+            }
+        }
+    }
+}
+
+impl Hasher for CompoundKeyedHasher {
+    type HashID = CompoundKeyedHashID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        match self {
+            CompoundKeyedHasher::Blake2bMac { blake2b } => {
+                blake2b.update(bytes)
+            }
+            CompoundKeyedHasher::HMACSHA384 { sha384 } => sha384.update(bytes),
+            CompoundKeyedHasher::HMACSHA3 { sha3 } => sha3.update(bytes)
+        }
+    }
+
+    fn finish(self) -> Self::HashID {
+        match self {
+            CompoundKeyedHasher::Blake2bMac { blake2b } => {
+                CompoundKeyedHashID::Blake2bMac {
+                    blake2b: blake2b.finish()
+                }
+            }
+            CompoundKeyedHasher::HMACSHA384 { sha384 } => {
+                CompoundKeyedHashID::HMACSHA384 {
+                    sha384: sha384.finish()
+                }
+            }
+            CompoundKeyedHasher::HMACSHA3 { sha3 } => {
+                CompoundKeyedHashID::HMACSHA3 {
+                    sha3: sha3.finish()
+                }
+            }
+        }
+    }
+}
+
+impl HashID for CompoundKeyedHashID {
+    #[inline]
+    fn name(&self) -> &str {
+        match self {
+            CompoundKeyedHashID::Blake2bMac { blake2b } => blake2b.name(),
+            CompoundKeyedHashID::HMACSHA384 { sha384 } => sha384.name(),
+            CompoundKeyedHashID::HMACSHA3 { sha3 } => sha3.name()
+        }
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        match self {
+            CompoundKeyedHashID::Blake2bMac { blake2b } => blake2b.bytes(),
+            CompoundKeyedHashID::HMACSHA384 { sha384 } => sha384.bytes(),
+            CompoundKeyedHashID::HMACSHA3 { sha3 } => sha3.bytes()
+        }
+    }
+}
+
+impl ConstantTimeEq for CompoundKeyedHashID {
+    /// Compare two IDs in constant time.
+    ///
+    /// Only the comparison of the hash values themselves is done in
+    /// constant time; IDs from different algorithms are always
+    /// unequal.
+    fn ct_eq(
+        &self,
+        other: &Self
+    ) -> Choice {
+        match (self, other) {
+            (
+                CompoundKeyedHashID::Blake2bMac { blake2b: a },
+                CompoundKeyedHashID::Blake2bMac { blake2b: b }
+            ) => a.ct_eq(b),
+            (
+                CompoundKeyedHashID::HMACSHA384 { sha384: a },
+                CompoundKeyedHashID::HMACSHA384 { sha384: b }
+            ) => a.ct_eq(b),
+            (
+                CompoundKeyedHashID::HMACSHA3 { sha3: a },
+                CompoundKeyedHashID::HMACSHA3 { sha3: b }
+            ) => a.ct_eq(b),
+            _ => Choice::from(0)
+        }
+    }
+}
+
+impl PartialEq for CompoundKeyedHashID {
+    #[inline]
+    fn eq(
+        &self,
+        other: &Self
+    ) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Hash for CompoundKeyedHashID {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(
+        &self,
+        state: &mut H
+    ) {
+        self.name().hash(state);
+        self.bytes().hash(state)
+    }
+}
+
+impl Display for CompoundKeyedHashID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        match self {
+            CompoundKeyedHashID::Blake2bMac { blake2b } => blake2b.fmt(f),
+            CompoundKeyedHashID::HMACSHA384 { sha384 } => sha384.fmt(f),
+            CompoundKeyedHashID::HMACSHA3 { sha3 } => sha3.fmt(f)
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_ALGOS: [&str; 3] = ["Blake2b-MAC", "HMAC-SHA384", "HMAC-SHA3-512"];
+
+#[test]
+fn test_keyed_hash_round_trip() {
+    let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+    for name in TEST_ALGOS {
+        let algo = CompoundKeyedHashAlgo::try_from(name).unwrap();
+        let keyed = algo.with_key(b"key one").unwrap();
+        let expected = algo.hash_bytes(b"key one", &data).unwrap();
+        let mut hasher = keyed.start();
+
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+
+        let actual = hasher.finish();
+
+        assert_eq!(expected, actual);
+        assert_eq!(expected, keyed.hash_bytes(&data));
+        assert!(keyed.verify(&data, &expected));
+        assert!(algo.verify(b"key one", &data, &expected).unwrap());
+        assert!(!algo.verify(b"key two", &data, &expected).unwrap());
+        assert!(!keyed.verify(&data[1..], &expected));
+    }
+}
+
+#[test]
+fn test_keyed_hash_different_algos() {
+    let a = CompoundKeyedHashAlgo::try_from("HMAC-SHA3-512").unwrap();
+    let b = CompoundKeyedHashAlgo::try_from("HMAC-SHA384").unwrap();
+    let a = a.hash_bytes(b"key", b"data").unwrap();
+    let b = b.hash_bytes(b"key", b"data").unwrap();
+
+    assert_ne!(a, b);
+    assert!(!bool::from(a.ct_eq(&b)));
+}
+
+#[test]
+fn test_hmac_sha384_rfc4231() {
+    let algo = HMACSHA384Algo;
+    let id = algo.hash_bytes(&[0x0b; 20], b"Hi There").unwrap();
+    let expected = [
+        0xaf, 0xd0, 0x39, 0x44, 0xd8, 0x48, 0x95, 0x62, 0x6b, 0x08, 0x25, 0xf4,
+        0xab, 0x46, 0x90, 0x7f, 0x15, 0xf9, 0xda, 0xdb, 0xe4, 0x10, 0x1e, 0xc6,
+        0x82, 0xaa, 0x03, 0x4c, 0x7c, 0xeb, 0xc5, 0x9c, 0xfa, 0xea, 0x9e, 0xa9,
+        0x07, 0x6e, 0xde, 0x7f, 0x4a, 0xf1, 0x52, 0xe8, 0xb2, 0xfa, 0x9c, 0xb6
+    ];
+
+    assert_eq!(id.bytes(), &expected);
+}
+
+#[test]
+fn test_blake2b_mac_bad_key() {
+    assert!(Blake2bMacAlgo.start(&[0; 65]).is_err());
+    assert!(Blake2bMacAlgo.start(&[0; 64]).is_ok());
+}
+
+#[test]
+fn test_keyed_hash_algo_serde() {
+    for name in TEST_ALGOS {
+        let algo: CompoundKeyedHashAlgo = serde_yaml::from_str(name).unwrap();
+        let yaml = serde_yaml::to_string(&algo).unwrap();
+
+        assert_eq!(format!("{}\n", name), yaml);
+    }
+
+    assert!(serde_yaml::from_str::<CompoundKeyedHashAlgo>("MD5").is_err());
+    assert!(
+        serde_yaml::from_str::<CompoundKeyedHashAlgo>("Skein-MAC-512").is_err()
+    );
+}
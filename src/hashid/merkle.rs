@@ -0,0 +1,1053 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Merkle trees over any [HashAlgo].
+//!
+//! This module provides [MerkleTree], which allows membership of
+//! items in large sets to be proven compactly.  The tree structure,
+//! hashing, and proof formats follow the Merkle tree construction used
+//! by Certificate Transparency (RFC 9162, section 2.1), generalized to
+//! any [HashAlgo]:
+//!
+//! * Leaf hashes are computed as `H(0x00 || data)`, and internal node hashes
+//!   are computed as `H(0x01 || left || right)`.  This domain separation
+//!   prevents a leaf from being passed off as an internal node, or vice versa.
+//!
+//! * The hash of an empty tree is the hash of the empty string.
+//!
+//! * [InclusionProof]s show that a leaf is present in a tree of a given size.
+//!
+//! * [ConsistencyProof]s show that a tree of a given size is a prefix of a
+//!   larger tree (that is, that the larger tree was obtained only by appending
+//!   leaves).
+//!
+//! Proofs can be sent over the wire using [MerkleProofCodec].
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::HashAlgo;
+use crate::hashid::HashID;
+use crate::hashid::Hasher;
+
+/// Prefix byte for leaf hashes.
+const LEAF_PREFIX: u8 = 0x00;
+
+/// Prefix byte for internal node hashes.
+const NODE_PREFIX: u8 = 0x01;
+
+/// Maximum number of hashes in any proof.
+///
+/// Tree sizes are 64-bit, so no proof will have more than 64 hashes
+/// in its path.
+const MAX_PATH_LEN: usize = 64;
+
+/// Maximum size of an encoded 64-bit integer.
+const MAX_VARINT_BYTES: usize = 10;
+
+/// Merkle tree over a [HashAlgo].
+///
+/// This stores the hashes of all the leaves, and computes roots and
+/// proofs for any prefix of the tree on demand.  See the [module
+/// documentation](crate::hashid::merkle) for details.
+#[derive(Clone)]
+pub struct MerkleTree<Algo: HashAlgo> {
+    algo: Algo,
+    leaves: Vec<Algo::HashID>
+}
+
+/// Proof that a leaf is included in a tree of a given size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InclusionProof<ID> {
+    /// Index of the leaf.
+    index: u64,
+    /// Size of the tree.
+    size: u64,
+    /// Hashes on the path from the leaf to the root.
+    path: Vec<ID>
+}
+
+/// Proof that a tree of a given size is a prefix of a larger tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConsistencyProof<ID> {
+    /// Size of the smaller tree.
+    old_size: u64,
+    /// Size of the larger tree.
+    new_size: u64,
+    /// Hashes needed to reconstruct both roots.
+    path: Vec<ID>
+}
+
+/// Codec for [InclusionProof]s and [ConsistencyProof]s.
+///
+/// Proofs are encoded as the two sizes (or index and size) as
+/// variable-length integers, followed by the number of hashes in the
+/// path as a single byte, followed by the raw hash values.  The
+/// [HashAlgo] is fixed by the codec's parameter, so it is not
+/// encoded.
+///
+/// `MAX_BYTES` gives the maximum size of an encoded proof.  Proofs
+/// over trees with up to `2^k` leaves have at most `k` hashes (`2k`
+/// for consistency proofs), which can be used to size this.
+pub struct MerkleProofCodec<Algo: HashAlgo, const MAX_BYTES: usize> {
+    algo: Algo,
+    /// Length of the hash values.
+    hash_len: usize
+}
+
+/// Errors that can occur when encoding a proof with
+/// [MerkleProofCodec].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MerkleEncodeError {
+    /// The encoded proof would exceed the maximum size.
+    TooLong {
+        /// Length of the encoded proof.
+        len: usize,
+        /// Maximum length.
+        max: usize
+    },
+    /// A hash in the proof had the wrong length.
+    BadHashLen {
+        /// Expected length.
+        expected: usize,
+        /// Actual length.
+        actual: usize
+    }
+}
+
+/// Errors that can occur when decoding a proof with
+/// [MerkleProofCodec].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MerkleDecodeError {
+    /// The input was too short.
+    Truncated,
+    /// An integer was malformed or out of range.
+    BadInt,
+    /// The path was longer than any valid proof.
+    PathTooLong {
+        /// Length of the path.
+        len: usize
+    }
+}
+
+/// Compute the hash of a leaf containing `data`.
+#[inline]
+pub fn leaf_hash<Algo>(
+    algo: &Algo,
+    data: &[u8]
+) -> Algo::HashID
+where
+    Algo: HashAlgo {
+    let mut hasher = algo.start();
+
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(data);
+
+    hasher.finish()
+}
+
+/// Compute the hash of an internal node with children `left` and
+/// `right`.
+#[inline]
+pub fn node_hash<Algo>(
+    algo: &Algo,
+    left: &Algo::HashID,
+    right: &Algo::HashID
+) -> Algo::HashID
+where
+    Algo: HashAlgo {
+    let mut hasher = algo.start();
+
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.bytes());
+    hasher.update(right.bytes());
+
+    hasher.finish()
+}
+
+/// Get the largest power of two strictly less than `n`.
+///
+/// `n` must be at least 2.
+#[inline]
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+impl<Algo> MerkleTree<Algo>
+where
+    Algo: HashAlgo,
+    Algo::HashID: Clone
+{
+    /// Create a new, empty `MerkleTree` using `algo`.
+    #[inline]
+    pub fn new(algo: Algo) -> Self {
+        MerkleTree {
+            algo: algo,
+            leaves: Vec::new()
+        }
+    }
+
+    /// Get the [HashAlgo] used by this tree.
+    #[inline]
+    pub fn algo(&self) -> &Algo {
+        &self.algo
+    }
+
+    /// Get the number of leaves in the tree.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Check whether the tree is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Get the hashes of all the leaves.
+    #[inline]
+    pub fn leaves(&self) -> &[Algo::HashID] {
+        &self.leaves
+    }
+
+    /// Add a leaf containing `data`, returning its index.
+    #[inline]
+    pub fn push(
+        &mut self,
+        data: &[u8]
+    ) -> usize {
+        let hash = leaf_hash(&self.algo, data);
+
+        self.push_leaf_hash(hash)
+    }
+
+    /// Add a leaf containing the encoding of `val` by `codec`,
+    /// returning its index.
+    ///
+    /// This is the Merkle tree counterpart to [HashAlgo::hashid].
+    #[inline]
+    pub fn push_encoded<T, Codec>(
+        &mut self,
+        codec: &mut Codec,
+        val: &T
+    ) -> Result<usize, Codec::EncodeError>
+    where
+        Codec: DatagramCodec<T> {
+        let encoded = codec.encode_to_vec(val)?;
+
+        Ok(self.push(&encoded))
+    }
+
+    /// Add a leaf with an already-computed leaf hash, returning its
+    /// index.
+    ///
+    /// `hash` must have been computed with [leaf_hash].
+    #[inline]
+    pub fn push_leaf_hash(
+        &mut self,
+        hash: Algo::HashID
+    ) -> usize {
+        self.leaves.push(hash);
+
+        self.leaves.len() - 1
+    }
+
+    /// Get the root hash of the whole tree.
+    #[inline]
+    pub fn root(&self) -> Algo::HashID {
+        self.subtree_root(&self.leaves)
+    }
+
+    /// Get the root hash of the tree consisting of the first `size`
+    /// leaves.
+    ///
+    /// Returns `None` if `size` is larger than the tree.
+    #[inline]
+    pub fn root_at(
+        &self,
+        size: usize
+    ) -> Option<Algo::HashID> {
+        self.leaves
+            .get(..size)
+            .map(|leaves| self.subtree_root(leaves))
+    }
+
+    /// Get an [InclusionProof] for the leaf at `index`, in the tree
+    /// consisting of the first `size` leaves.
+    ///
+    /// Returns `None` if `index` is not less than `size`, or `size`
+    /// is larger than the tree.
+    pub fn inclusion_proof(
+        &self,
+        index: usize,
+        size: usize
+    ) -> Option<InclusionProof<Algo::HashID>> {
+        if index < size && size <= self.leaves.len() {
+            let mut path = Vec::new();
+
+            self.inclusion_path(index, &self.leaves[..size], &mut path);
+
+            Some(InclusionProof {
+                index: index as u64,
+                size: size as u64,
+                path: path
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Get a [ConsistencyProof] showing that the tree consisting of
+    /// the first `old_size` leaves is a prefix of the tree consisting
+    /// of the first `new_size` leaves.
+    ///
+    /// Returns `None` if `old_size` is larger than `new_size`, or
+    /// `new_size` is larger than the tree.
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize
+    ) -> Option<ConsistencyProof<Algo::HashID>> {
+        if old_size <= new_size && new_size <= self.leaves.len() {
+            let mut path = Vec::new();
+
+            if old_size != 0 {
+                self.consistency_path(
+                    old_size,
+                    &self.leaves[..new_size],
+                    true,
+                    &mut path
+                );
+            }
+
+            Some(ConsistencyProof {
+                old_size: old_size as u64,
+                new_size: new_size as u64,
+                path: path
+            })
+        } else {
+            None
+        }
+    }
+
+    fn subtree_root(
+        &self,
+        leaves: &[Algo::HashID]
+    ) -> Algo::HashID {
+        match leaves.len() {
+            0 => self.algo.null_hash(),
+            1 => leaves[0].clone(),
+            n => {
+                let k = split_point(n);
+                let left = self.subtree_root(&leaves[..k]);
+                let right = self.subtree_root(&leaves[k..]);
+
+                node_hash(&self.algo, &left, &right)
+            }
+        }
+    }
+
+    fn inclusion_path(
+        &self,
+        index: usize,
+        leaves: &[Algo::HashID],
+        path: &mut Vec<Algo::HashID>
+    ) {
+        let n = leaves.len();
+
+        if n > 1 {
+            let k = split_point(n);
+
+            if index < k {
+                self.inclusion_path(index, &leaves[..k], path);
+                path.push(self.subtree_root(&leaves[k..]));
+            } else {
+                self.inclusion_path(index - k, &leaves[k..], path);
+                path.push(self.subtree_root(&leaves[..k]));
+            }
+        }
+    }
+
+    fn consistency_path(
+        &self,
+        old_size: usize,
+        leaves: &[Algo::HashID],
+        complete: bool,
+        path: &mut Vec<Algo::HashID>
+    ) {
+        let n = leaves.len();
+
+        if old_size == n {
+            if !complete {
+                path.push(self.subtree_root(leaves));
+            }
+        } else {
+            let k = split_point(n);
+
+            if old_size <= k {
+                self.consistency_path(old_size, &leaves[..k], complete, path);
+                path.push(self.subtree_root(&leaves[k..]));
+            } else {
+                self.consistency_path(old_size - k, &leaves[k..], false, path);
+                path.push(self.subtree_root(&leaves[..k]));
+            }
+        }
+    }
+}
+
+impl<ID> InclusionProof<ID>
+where
+    ID: HashID + Clone + PartialEq
+{
+    /// Create a new `InclusionProof` from its components.
+    #[inline]
+    pub fn new(
+        index: u64,
+        size: u64,
+        path: Vec<ID>
+    ) -> Self {
+        InclusionProof {
+            index: index,
+            size: size,
+            path: path
+        }
+    }
+
+    /// Get the index of the leaf.
+    #[inline]
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Get the size of the tree.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the hashes on the path from the leaf to the root.
+    #[inline]
+    pub fn path(&self) -> &[ID] {
+        &self.path
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (u64, u64, Vec<ID>) {
+        (self.index, self.size, self.path)
+    }
+
+    /// Verify that the leaf containing `data` is included in the tree
+    /// with root hash `root`.
+    #[inline]
+    pub fn verify<Algo>(
+        &self,
+        algo: &Algo,
+        data: &[u8],
+        root: &ID
+    ) -> bool
+    where
+        Algo: HashAlgo<HashID = ID> {
+        self.verify_leaf_hash(algo, &leaf_hash(algo, data), root)
+    }
+
+    /// Verify that the leaf with hash `leaf` is included in the tree
+    /// with root hash `root`.
+    pub fn verify_leaf_hash<Algo>(
+        &self,
+        algo: &Algo,
+        leaf: &ID,
+        root: &ID
+    ) -> bool
+    where
+        Algo: HashAlgo<HashID = ID> {
+        if self.index >= self.size {
+            return false;
+        }
+
+        let mut fnode = self.index;
+        let mut snode = self.size - 1;
+        let mut hash = leaf.clone();
+
+        for elem in &self.path {
+            if snode == 0 {
+                return false;
+            }
+
+            if fnode & 1 == 1 || fnode == snode {
+                hash = node_hash(algo, elem, &hash);
+
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                hash = node_hash(algo, &hash, elem);
+            }
+
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && &hash == root
+    }
+}
+
+impl<ID> ConsistencyProof<ID>
+where
+    ID: HashID + Clone + PartialEq
+{
+    /// Create a new `ConsistencyProof` from its components.
+    #[inline]
+    pub fn new(
+        old_size: u64,
+        new_size: u64,
+        path: Vec<ID>
+    ) -> Self {
+        ConsistencyProof {
+            old_size: old_size,
+            new_size: new_size,
+            path: path
+        }
+    }
+
+    /// Get the size of the smaller tree.
+    #[inline]
+    pub fn old_size(&self) -> u64 {
+        self.old_size
+    }
+
+    /// Get the size of the larger tree.
+    #[inline]
+    pub fn new_size(&self) -> u64 {
+        self.new_size
+    }
+
+    /// Get the hashes in the proof.
+    #[inline]
+    pub fn path(&self) -> &[ID] {
+        &self.path
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (u64, u64, Vec<ID>) {
+        (self.old_size, self.new_size, self.path)
+    }
+
+    /// Verify that the tree with root hash `old_root` is a prefix of
+    /// the tree with root hash `new_root`.
+    pub fn verify<Algo>(
+        &self,
+        algo: &Algo,
+        old_root: &ID,
+        new_root: &ID
+    ) -> bool
+    where
+        Algo: HashAlgo<HashID = ID> {
+        if self.old_size > self.new_size {
+            return false;
+        }
+
+        // The empty tree is a prefix of every tree, and every tree is
+        // a prefix of itself.
+        if self.old_size == 0 || self.old_size == self.new_size {
+            return self.path.is_empty() &&
+                (self.old_size == 0 || old_root == new_root);
+        }
+
+        let mut path = self.path.iter();
+        let first = if self.old_size.is_power_of_two() {
+            old_root
+        } else {
+            match path.next() {
+                Some(first) => first,
+                None => return false
+            }
+        };
+        let mut fnode = self.old_size - 1;
+        let mut snode = self.new_size - 1;
+
+        while fnode & 1 == 1 {
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        let mut fhash = first.clone();
+        let mut shash = first.clone();
+
+        for elem in path {
+            if snode == 0 {
+                return false;
+            }
+
+            if fnode & 1 == 1 || fnode == snode {
+                fhash = node_hash(algo, elem, &fhash);
+                shash = node_hash(algo, elem, &shash);
+
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                shash = node_hash(algo, &shash, elem);
+            }
+
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && &fhash == old_root && &shash == new_root
+    }
+}
+
+/// Encode `val` as a variable-length integer.
+fn encode_varint(
+    mut val: u64,
+    out: &mut Vec<u8>
+) {
+    while val >= 0x80 {
+        out.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+
+    out.push(val as u8)
+}
+
+/// Decode a variable-length integer, returning it and the number of
+/// bytes consumed.
+fn decode_varint(buf: &[u8]) -> Result<(u64, usize), MerkleDecodeError> {
+    let mut val: u64 = 0;
+
+    for (i, byte) in buf.iter().take(MAX_VARINT_BYTES).enumerate() {
+        let bits = (*byte & 0x7f) as u64;
+
+        // The last byte can contribute only one bit.
+        if i == MAX_VARINT_BYTES - 1 && bits > 1 {
+            return Err(MerkleDecodeError::BadInt);
+        }
+
+        val |= bits << (7 * i);
+
+        if byte & 0x80 == 0 {
+            // Reject non-minimal encodings.
+            if i != 0 && bits == 0 {
+                return Err(MerkleDecodeError::BadInt);
+            }
+
+            return Ok((val, i + 1));
+        }
+    }
+
+    if buf.len() < MAX_VARINT_BYTES {
+        Err(MerkleDecodeError::Truncated)
+    } else {
+        Err(MerkleDecodeError::BadInt)
+    }
+}
+
+impl<Algo, const MAX_BYTES: usize> MerkleProofCodec<Algo, MAX_BYTES>
+where
+    Algo: HashAlgo
+{
+    /// Create a new `MerkleProofCodec` for proofs using `algo`.
+    ///
+    /// This is equivalent to [DatagramCodec::create].
+    #[inline]
+    pub fn new(algo: Algo) -> Self {
+        let hash_len = algo.null_hash().bytes().len();
+
+        MerkleProofCodec {
+            algo: algo,
+            hash_len: hash_len
+        }
+    }
+
+    /// Get the [HashAlgo] for proofs.
+    #[inline]
+    pub fn algo(&self) -> &Algo {
+        &self.algo
+    }
+
+    fn encode_proof<ID>(
+        &self,
+        first: u64,
+        second: u64,
+        path: &[ID],
+        buf: &mut [u8]
+    ) -> Result<usize, MerkleEncodeError>
+    where
+        ID: HashID {
+        let mut header = Vec::with_capacity(MAX_VARINT_BYTES * 2 + 1);
+
+        encode_varint(first, &mut header);
+        encode_varint(second, &mut header);
+        header.push(path.len() as u8);
+
+        let len = header.len() + (path.len() * self.hash_len);
+
+        if path.len() > MAX_PATH_LEN || len > MAX_BYTES {
+            return Err(MerkleEncodeError::TooLong {
+                len: len,
+                max: MAX_BYTES
+            });
+        }
+
+        buf[..header.len()].copy_from_slice(&header);
+
+        let mut offset = header.len();
+
+        for hash in path {
+            let bytes = hash.bytes();
+
+            if bytes.len() != self.hash_len {
+                return Err(MerkleEncodeError::BadHashLen {
+                    expected: self.hash_len,
+                    actual: bytes.len()
+                });
+            }
+
+            buf[offset..offset + self.hash_len].copy_from_slice(bytes);
+            offset += self.hash_len;
+        }
+
+        Ok(offset)
+    }
+
+    fn decode_proof(
+        &self,
+        buf: &[u8]
+    ) -> Result<(u64, u64, Vec<Algo::HashID>, usize), MerkleDecodeError> {
+        let (first, first_len) = decode_varint(buf)?;
+        let (second, second_len) = decode_varint(&buf[first_len..])?;
+        let mut offset = first_len + second_len;
+        let count =
+            *buf.get(offset).ok_or(MerkleDecodeError::Truncated)? as usize;
+
+        if count > MAX_PATH_LEN {
+            return Err(MerkleDecodeError::PathTooLong { len: count });
+        }
+
+        offset += 1;
+
+        let mut path = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let bytes = buf
+                .get(offset..offset + self.hash_len)
+                .ok_or(MerkleDecodeError::Truncated)?;
+            let hash = self
+                .algo
+                .wrap_hashed_bytes(bytes)
+                .map_err(|_| MerkleDecodeError::Truncated)?;
+
+            path.push(hash);
+            offset += self.hash_len;
+        }
+
+        Ok((first, second, path, offset))
+    }
+}
+
+impl<Algo, const MAX_BYTES: usize> DatagramCodec<InclusionProof<Algo::HashID>>
+    for MerkleProofCodec<Algo, MAX_BYTES>
+where
+    Algo: HashAlgo,
+    Algo::HashID: Clone + PartialEq
+{
+    type CreateError = Infallible;
+    type DecodeError = MerkleDecodeError;
+    type EncodeError = MerkleEncodeError;
+    type Param = Algo;
+
+    const MAX_BYTES: usize = MAX_BYTES;
+
+    #[inline]
+    fn create(algo: Algo) -> Result<Self, Infallible> {
+        Ok(MerkleProofCodec::new(algo))
+    }
+
+    #[inline]
+    fn encode(
+        &mut self,
+        val: &InclusionProof<Algo::HashID>,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        self.encode_proof(val.index, val.size, &val.path, buf)
+    }
+
+    #[inline]
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(InclusionProof<Algo::HashID>, usize), Self::DecodeError> {
+        let (index, size, path, nbytes) = self.decode_proof(buf)?;
+
+        Ok((InclusionProof::new(index, size, path), nbytes))
+    }
+}
+
+impl<Algo, const MAX_BYTES: usize> DatagramCodec<ConsistencyProof<Algo::HashID>>
+    for MerkleProofCodec<Algo, MAX_BYTES>
+where
+    Algo: HashAlgo,
+    Algo::HashID: Clone + PartialEq
+{
+    type CreateError = Infallible;
+    type DecodeError = MerkleDecodeError;
+    type EncodeError = MerkleEncodeError;
+    type Param = Algo;
+
+    const MAX_BYTES: usize = MAX_BYTES;
+
+    #[inline]
+    fn create(algo: Algo) -> Result<Self, Infallible> {
+        Ok(MerkleProofCodec::new(algo))
+    }
+
+    #[inline]
+    fn encode(
+        &mut self,
+        val: &ConsistencyProof<Algo::HashID>,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        self.encode_proof(val.old_size, val.new_size, &val.path, buf)
+    }
+
+    #[inline]
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(ConsistencyProof<Algo::HashID>, usize), Self::DecodeError>
+    {
+        let (old_size, new_size, path, nbytes) = self.decode_proof(buf)?;
+
+        Ok((ConsistencyProof::new(old_size, new_size, path), nbytes))
+    }
+}
+
+impl ScopedError for MerkleEncodeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl ScopedError for MerkleDecodeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl Display for MerkleEncodeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            MerkleEncodeError::TooLong { len, max } => {
+                write!(f, "proof is {} bytes (maximum {})", len, max)
+            }
+            MerkleEncodeError::BadHashLen { expected, actual } => write!(
+                f,
+                "hash in proof is {} bytes (expected {})",
+                actual, expected
+            )
+        }
+    }
+}
+
+impl Display for MerkleDecodeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            MerkleDecodeError::Truncated => write!(f, "proof truncated"),
+            MerkleDecodeError::BadInt => write!(f, "malformed integer"),
+            MerkleDecodeError::PathTooLong { len } => {
+                write!(f, "proof path has {} hashes", len)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::hashid::SHA256Algo;
+
+#[cfg(test)]
+fn test_tree(size: usize) -> MerkleTree<SHA256Algo> {
+    let mut tree = MerkleTree::new(SHA256Algo);
+
+    for i in 0..size {
+        tree.push(format!("leaf {}", i).as_bytes());
+    }
+
+    tree
+}
+
+#[test]
+fn test_merkle_known_answers() {
+    // Test vectors from RFC 6962 reference implementation.
+    let leaves: [&[u8]; 8] = [
+        &[],
+        &[0x00],
+        &[0x10],
+        &[0x20, 0x21],
+        &[0x30, 0x31],
+        &[0x40, 0x41, 0x42, 0x43],
+        &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57],
+        &[
+            0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a,
+            0x6b, 0x6c, 0x6d, 0x6e, 0x6f
+        ]
+    ];
+    let mut tree = MerkleTree::new(SHA256Algo);
+
+    assert_eq!(
+        "SHA256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        tree.root().to_string()
+    );
+
+    for leaf in leaves {
+        tree.push(leaf);
+    }
+
+    assert_eq!(
+        "SHA256:6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        tree.root_at(1).unwrap().to_string()
+    );
+    assert_eq!(
+        "SHA256:5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        tree.root().to_string()
+    );
+}
+
+#[test]
+fn test_merkle_inclusion() {
+    let tree = test_tree(20);
+
+    for size in 1..=20 {
+        let root = tree.root_at(size).unwrap();
+
+        for index in 0..size {
+            let proof = tree.inclusion_proof(index, size).unwrap();
+            let data = format!("leaf {}", index);
+
+            assert!(proof.verify(&SHA256Algo, data.as_bytes(), &root));
+            assert!(!proof.verify(&SHA256Algo, b"not a leaf", &root));
+
+            if size > 1 {
+                let (_, _, path) = proof.take();
+                let wrong_index = ((index + 1) % size) as u64;
+                let wrong = InclusionProof::new(wrong_index, size as u64, path);
+
+                assert!(!wrong.verify(&SHA256Algo, data.as_bytes(), &root));
+            }
+        }
+    }
+
+    assert!(tree.inclusion_proof(5, 5).is_none());
+    assert!(tree.inclusion_proof(0, 21).is_none());
+}
+
+#[test]
+fn test_merkle_consistency() {
+    let tree = test_tree(20);
+
+    for new_size in 0..=20 {
+        let new_root = tree.root_at(new_size).unwrap();
+
+        for old_size in 0..=new_size {
+            let old_root = tree.root_at(old_size).unwrap();
+            let proof = tree.consistency_proof(old_size, new_size).unwrap();
+
+            assert!(proof.verify(&SHA256Algo, &old_root, &new_root));
+
+            if old_size != 0 && old_size != new_size {
+                let bad_root = tree.root_at(old_size - 1).unwrap();
+
+                assert!(!proof.verify(&SHA256Algo, &bad_root, &new_root));
+                assert!(!proof.verify(&SHA256Algo, &old_root, &old_root));
+            }
+        }
+    }
+
+    assert!(tree.consistency_proof(5, 4).is_none());
+    assert!(tree.consistency_proof(5, 21).is_none());
+}
+
+#[test]
+fn test_merkle_proof_codec() {
+    let tree = test_tree(300);
+    let mut codec = MerkleProofCodec::<SHA256Algo, 1024>::new(SHA256Algo);
+    let proof = tree.inclusion_proof(200, 300).unwrap();
+    let encoded = codec.encode_to_vec(&proof).unwrap();
+    let (decoded, nbytes): (InclusionProof<_>, _) =
+        codec.decode(&encoded).unwrap();
+
+    // 2 + 2 bytes of sizes, 1 byte of count, and 9 hashes.
+    assert_eq!(2 + 2 + 1 + 9 * 32, encoded.len());
+    assert_eq!(encoded.len(), nbytes);
+    assert_eq!(proof, decoded);
+
+    let proof = tree.consistency_proof(100, 300).unwrap();
+    let encoded = codec.encode_to_vec(&proof).unwrap();
+    let (decoded, nbytes): (ConsistencyProof<_>, _) =
+        codec.decode(&encoded).unwrap();
+
+    assert_eq!(encoded.len(), nbytes);
+    assert_eq!(proof, decoded);
+
+    let res: Result<(ConsistencyProof<_>, _), _> =
+        codec.decode(&encoded[..encoded.len() - 1]);
+
+    assert_eq!(Err(MerkleDecodeError::Truncated), res);
+}
+
+#[test]
+fn test_merkle_proof_codec_too_long() {
+    let tree = test_tree(300);
+    let mut codec = MerkleProofCodec::<SHA256Algo, 64>::new(SHA256Algo);
+    let proof = tree.inclusion_proof(200, 300).unwrap();
+
+    assert!(matches!(
+        codec.encode_to_vec(&proof),
+        Err(MerkleEncodeError::TooLong { .. })
+    ));
+}
+
+#[test]
+fn test_varint() {
+    for val in [0, 1, 127, 128, 300, 1 << 32, u64::MAX] {
+        let mut buf = Vec::new();
+
+        encode_varint(val, &mut buf);
+
+        assert_eq!(Ok((val, buf.len())), decode_varint(&buf));
+    }
+
+    assert_eq!(Err(MerkleDecodeError::BadInt), decode_varint(&[0x80, 0x00]));
+    assert_eq!(Err(MerkleDecodeError::Truncated), decode_varint(&[0x80]));
+    assert_eq!(Err(MerkleDecodeError::BadInt), decode_varint(&[0xff; 10]));
+}
@@ -0,0 +1,2356 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Wrapper types for cryptographic hashes and IDs generated from them.
+use std::array::TryFromSliceError;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
+use std::io::copy;
+use std::io::Read;
+use std::io::Write;
+use std::str::FromStr;
+
+use blake2::Blake2b512;
+use blake2::Blake2s256;
+use digest::Digest;
+use ripemd::Ripemd160;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::Serializer;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512_256;
+use sha3::Sha3_512;
+use skein::consts::U64;
+use skein::Skein512;
+use whirlpool::Whirlpool;
+
+use crate::codec::per::PERCodec;
+use crate::codec::DatagramCodec;
+pub use crate::generated::hash_id::HashIdentifier;
+
+pub mod codec;
+pub mod keyed;
+pub mod merkle;
+pub mod policy;
+
+/// [PERCodec] for [HashIdentifier]s.
+///
+/// This allows [CompoundHashID]s to be sent over the wire, by
+/// converting them to and from [HashIdentifier]s.
+pub type HashIdentifierPERCodec = PERCodec<HashIdentifier, 517>;
+
+/// Trait for IDs generated from hashing a more complex type.
+pub trait HashID: Sized {
+    /// Get the name of the hash function used for this ID.
+    fn name(&self) -> &str;
+
+    /// Get the bytes of the hashed value.
+    fn bytes(&self) -> &[u8];
+}
+
+/// Trait for incremental hashers, created by [HashAlgo::start].
+///
+/// This allows a hash to be computed over input that is provided in
+/// pieces, rather than all at once.  See [HashWriter] for an adapter
+/// allowing a `Hasher` to be used as a [Write].
+pub trait Hasher {
+    type HashID: HashID;
+
+    /// Add `bytes` to the input being hashed.
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    );
+
+    /// Complete the hash, and produce the [HashID].
+    fn finish(self) -> Self::HashID;
+}
+
+/// Trait for specific cryptographic hash algorithms.
+pub trait HashAlgo {
+    type HashID: HashID;
+    type Hasher: Hasher<HashID = Self::HashID>;
+
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError>;
+
+    /// Start an incremental hash.
+    fn start(&self) -> Self::Hasher;
+
+    #[inline]
+    fn hash_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Self::HashID {
+        let mut hasher = self.start();
+
+        hasher.update(bytes);
+
+        hasher.finish()
+    }
+
+    /// Hash all the data that can be read from `reader`.
+    ///
+    /// This reads and hashes the data in pieces, and does not
+    /// buffer the entire input in memory.
+    fn hash_reader<R>(
+        &self,
+        reader: &mut R
+    ) -> Result<Self::HashID, std::io::Error>
+    where
+        R: Read {
+        let mut writer = HashWriter::new(self.start());
+
+        copy(reader, &mut writer)?;
+
+        Ok(writer.finish())
+    }
+
+    #[inline]
+    fn null_hash(&self) -> Self::HashID {
+        self.hash_bytes(&[])
+    }
+
+    fn hashid<T, Codec>(
+        &self,
+        codec: &mut Codec,
+        val: &T
+    ) -> Result<Self::HashID, Codec::EncodeError>
+    where
+        Codec: DatagramCodec<T> {
+        let encoded = codec.encode_to_vec(val)?;
+
+        Ok(self.hash_bytes(&encoded))
+    }
+}
+
+/// Adapter allowing a [Hasher] to be used as a [Write].
+///
+/// All data written to this will be added to the hash.  This allows
+/// large objects and files to be hashed (for example, with
+/// [copy](std::io::copy)) without buffering them in memory.
+#[derive(Clone)]
+pub struct HashWriter<H: Hasher> {
+    hasher: H
+}
+
+/// [HashAlgo] using the Blake2b algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Blake2bAlgo;
+
+/// [HashID] using the Blake2b algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct Blake2bID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the Blake2b algorithm.
+#[derive(Clone)]
+pub struct Blake2bHasher {
+    hasher: Blake2b512
+}
+
+/// [HashAlgo] using the Blake2s algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Blake2sAlgo;
+
+/// [HashID] using the Blake2s algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct Blake2sID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the Blake2s algorithm.
+#[derive(Clone)]
+pub struct Blake2sHasher {
+    hasher: Blake2s256
+}
+
+/// [HashAlgo] using the BLAKE3 algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BLAKE3Algo;
+
+/// [HashID] using the BLAKE3 algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct BLAKE3ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the BLAKE3 algorithm.
+#[derive(Clone)]
+pub struct BLAKE3Hasher {
+    /// Boxed, as the BLAKE3 state is much larger than the others.
+    hasher: Box<blake3::Hasher>
+}
+
+/// [HashAlgo] using the RipeMD-160 algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RipeMD160Algo;
+
+/// [HashID] using the RipeMD-160 algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct RipeMD160ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the RipeMD-160 algorithm.
+#[derive(Clone)]
+pub struct RipeMD160Hasher {
+    hasher: Ripemd160
+}
+
+/// [HashAlgo] using the SHA256 (SHA2-256) algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SHA256Algo;
+
+/// [HashID] using the SHA256 (SHA2-256) algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct SHA256ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the SHA256 (SHA2-256) algorithm.
+#[derive(Clone)]
+pub struct SHA256Hasher {
+    hasher: Sha256
+}
+
+/// [HashAlgo] using the SHA3-512 algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SHA3Algo;
+
+/// [HashID] using the SHA3-512 algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct SHA3ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the SHA3-512 algorithm.
+#[derive(Clone)]
+pub struct SHA3Hasher {
+    hasher: Sha3_512
+}
+
+/// [HashAlgo] using the SHA384 (SHA2-384) algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SHA384Algo;
+
+/// [HashID] using the SHA384 (SHA2-384) algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct SHA384ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the SHA384 (SHA2-384) algorithm.
+#[derive(Clone)]
+pub struct SHA384Hasher {
+    hasher: Sha384
+}
+
+/// [HashAlgo] using the SHA512/256 (SHA2-512/256) algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SHA512_256Algo;
+
+/// [HashID] using the SHA512/256 (SHA2-512/256) algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct SHA512_256ID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the SHA512/256 (SHA2-512/256) algorithm.
+#[derive(Clone)]
+pub struct SHA512_256Hasher {
+    hasher: Sha512_256
+}
+
+/// [HashAlgo] using the Skein algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SkeinAlgo;
+
+/// [HashID] using the Skein algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct SkeinID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the Skein algorithm.
+#[derive(Clone)]
+pub struct SkeinHasher {
+    hasher: Skein512<U64>
+}
+
+/// [HashAlgo] using the Whirlpool algorithm.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WhirlpoolAlgo;
+
+/// [HashID] using the Whirlpool algorithm.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub struct WhirlpoolID {
+    id: [u8; Self::HASH_LEN]
+}
+
+/// [Hasher] using the Whirlpool algorithm.
+#[derive(Clone)]
+pub struct WhirlpoolHasher {
+    hasher: Whirlpool
+}
+
+/// [HashAlgo] instance capable of using a dynamically-configured hash
+/// function.
+///
+/// This also can serve as a configuration object, and can be deserialized.
+///
+/// # YAML Format
+///
+/// This is represented as a string naming the algorithm, which is
+/// one of `Blake2b`, `Blake2s`, `BLAKE3`, `RipeMD-160`, `SHA256`,
+/// `SHA3-512`, `SHA384`, `SHA512-256`, `Skein`, or `Whirlpool`:
+///
+/// ```yaml
+/// SHA3-512
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub enum CompoundHashAlgo {
+    /// The Blake2b hash algorithm.
+    Blake2b { blake2b: Blake2bAlgo },
+    /// The Blake2s hash algorithm.
+    Blake2s { blake2s: Blake2sAlgo },
+    /// The BLAKE3 hash algorithm.
+    BLAKE3 { blake3: BLAKE3Algo },
+    /// The RipeMD-160 hash algorithm.
+    RipeMD160 { ripemd160: RipeMD160Algo },
+    /// The SHA256 hash algorithm.
+    SHA256 { sha256: SHA256Algo },
+    /// The SHA3-512 hash algorithm.
+    SHA3 { sha3: SHA3Algo },
+    /// The SHA384 hash algorithm.
+    SHA384 { sha384: SHA384Algo },
+    /// The SHA512/256 hash algorithm.
+    SHA512_256 { sha512_256: SHA512_256Algo },
+    /// The Skein-512 hash algorithm.
+    Skein { skein: SkeinAlgo },
+    /// The Whirlpool hash algorithm.
+    Whirlpool { whirlpool: WhirlpoolAlgo }
+}
+
+/// [HashID] instance representing an ID generated from a
+/// dynamically-configured hash algorithm.
+///
+/// # Text Format
+///
+/// This is represented in text as the name of the algorithm, followed
+/// by a colon, followed by the hash value in lowercase hexadecimal.
+/// This is the same as the output of [Display], and can be parsed
+/// with [FromStr].  This format is also used for serialization:
+///
+/// ```yaml
+/// RipeMD-160:9c1185a5c5e9fc54612808977ee8f548b2258d31
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "&'_ str")]
+pub enum CompoundHashID {
+    /// ID generated from the Blake2b algorithm.
+    Blake2b { blake2b: Blake2bID },
+    /// ID generated from the Blake2s algorithm.
+    Blake2s { blake2s: Blake2sID },
+    /// ID generated from the BLAKE3 algorithm.
+    BLAKE3 { blake3: BLAKE3ID },
+    /// ID generated from the RipeMD-160 algorithm.
+    RipeMD160 { ripemd160: RipeMD160ID },
+    /// ID generated from the SHA256 algorithm.
+    SHA256 { sha256: SHA256ID },
+    /// ID generated from the SHA3-512 algorithm.
+    SHA3 { sha3: SHA3ID },
+    /// ID generated from the SHA384 algorithm.
+    SHA384 { sha384: SHA384ID },
+    /// ID generated from the SHA512/256 algorithm.
+    SHA512_256 { sha512_256: SHA512_256ID },
+    /// ID generated from the Skein-512 algorithm.
+    Skein { skein: SkeinID },
+    /// ID generated from the Whirlpool algorithm.
+    Whirlpool { whirlpool: WhirlpoolID }
+}
+
+/// [Hasher] instance for a dynamically-configured hash algorithm.
+#[derive(Clone)]
+pub enum CompoundHasher {
+    /// Hasher for the Blake2b algorithm.
+    Blake2b { blake2b: Blake2bHasher },
+    /// Hasher for the Blake2s algorithm.
+    Blake2s { blake2s: Blake2sHasher },
+    /// Hasher for the BLAKE3 algorithm.
+    BLAKE3 { blake3: BLAKE3Hasher },
+    /// Hasher for the RipeMD-160 algorithm.
+    RipeMD160 { ripemd160: RipeMD160Hasher },
+    /// Hasher for the SHA256 algorithm.
+    SHA256 { sha256: SHA256Hasher },
+    /// Hasher for the SHA3-512 algorithm.
+    SHA3 { sha3: SHA3Hasher },
+    /// Hasher for the SHA384 algorithm.
+    SHA384 { sha384: SHA384Hasher },
+    /// Hasher for the SHA512/256 algorithm.
+    SHA512_256 { sha512_256: SHA512_256Hasher },
+    /// Hasher for the Skein-512 algorithm.
+    Skein { skein: SkeinHasher },
+    /// Hasher for the Whirlpool algorithm.
+    Whirlpool { whirlpool: WhirlpoolHasher }
+}
+
+/// Errors that can occur when parsing a [HashID] from its text form.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HashIDParseError {
+    /// The separator between the algorithm name and hash was missing.
+    NoSeparator,
+    /// The algorithm name was not recognized.
+    UnknownAlgo {
+        /// The algorithm name.
+        name: String
+    },
+    /// The algorithm name did not match the expected algorithm.
+    WrongAlgo {
+        /// The expected algorithm name.
+        expected: &'static str,
+        /// The actual algorithm name.
+        actual: String
+    },
+    /// The hash value contained a non-hexadecimal character.
+    BadHex,
+    /// The hash value had the wrong length.
+    BadLen {
+        /// The expected length, in bytes.
+        expected: usize,
+        /// The actual length, in hex digits.
+        actual: usize
+    }
+}
+
+impl<H> HashWriter<H>
+where
+    H: Hasher
+{
+    /// Create a new `HashWriter` around `hasher`.
+    #[inline]
+    pub fn new(hasher: H) -> Self {
+        HashWriter { hasher: hasher }
+    }
+
+    /// Complete the hash, and produce the [HashID].
+    #[inline]
+    pub fn finish(self) -> H::HashID {
+        self.hasher.finish()
+    }
+}
+
+impl<H> Write for HashWriter<H>
+where
+    H: Hasher
+{
+    #[inline]
+    fn write(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<usize, std::io::Error> {
+        self.hasher.update(buf);
+
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn write_all(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(), std::io::Error> {
+        self.hasher.update(buf);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// Parse the text form of a hash ID, which must use the algorithm
+/// `name`.
+fn parse_id<const LEN: usize>(
+    name: &'static str,
+    s: &str
+) -> Result<[u8; LEN], HashIDParseError> {
+    let (actual, hex) =
+        s.split_once(':').ok_or(HashIDParseError::NoSeparator)?;
+
+    if actual != name {
+        return Err(HashIDParseError::WrongAlgo {
+            expected: name,
+            actual: actual.to_string()
+        });
+    }
+
+    if hex.len() != LEN * 2 {
+        return Err(HashIDParseError::BadLen {
+            expected: LEN,
+            actual: hex.len()
+        });
+    }
+
+    let mut out = [0; LEN];
+
+    for (i, chunk) in hex.as_bytes().chunks(2).enumerate() {
+        let hi = (chunk[0] as char)
+            .to_digit(16)
+            .ok_or(HashIDParseError::BadHex)?;
+        let lo = (chunk[1] as char)
+            .to_digit(16)
+            .ok_or(HashIDParseError::BadHex)?;
+
+        out[i] = ((hi << 4) | lo) as u8;
+    }
+
+    Ok(out)
+}
+
+impl HashAlgo for RipeMD160Algo {
+    type HashID = RipeMD160ID;
+    type Hasher = RipeMD160Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(RipeMD160ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        RipeMD160Hasher {
+            hasher: Ripemd160::default()
+        }
+    }
+}
+
+impl Hasher for RipeMD160Hasher {
+    type HashID = RipeMD160ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; RipeMD160ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        RipeMD160ID { id: id }
+    }
+}
+
+impl RipeMD160ID {
+    const HASH_LEN: usize = 160 / 8;
+    const NAME: &'static str = "RipeMD-160";
+}
+
+impl HashID for RipeMD160ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for RipeMD160ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for RipeMD160ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(RipeMD160ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for RipeMD160ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for RipeMD160ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for Blake2bAlgo {
+    type HashID = Blake2bID;
+    type Hasher = Blake2bHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(Blake2bID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        Blake2bHasher {
+            hasher: Blake2b512::default()
+        }
+    }
+}
+
+impl Hasher for Blake2bHasher {
+    type HashID = Blake2bID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; Blake2bID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        Blake2bID { id: id }
+    }
+}
+
+impl Blake2bID {
+    const HASH_LEN: usize = 512 / 8;
+    const NAME: &'static str = "Blake2b";
+}
+
+impl HashID for Blake2bID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for Blake2bID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Blake2bID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(Blake2bID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Blake2bID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for Blake2bID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for SHA3Algo {
+    type HashID = SHA3ID;
+    type Hasher = SHA3Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(SHA3ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        SHA3Hasher {
+            hasher: Sha3_512::default()
+        }
+    }
+}
+
+impl Hasher for SHA3Hasher {
+    type HashID = SHA3ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; SHA3ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        SHA3ID { id: id }
+    }
+}
+
+impl SHA3ID {
+    const HASH_LEN: usize = 512 / 8;
+    const NAME: &'static str = "SHA3-512";
+}
+
+impl HashID for SHA3ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for SHA3ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SHA3ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(SHA3ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SHA3ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for SHA3ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for SHA384Algo {
+    type HashID = SHA384ID;
+    type Hasher = SHA384Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(SHA384ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        SHA384Hasher {
+            hasher: Sha384::default()
+        }
+    }
+}
+
+impl Hasher for SHA384Hasher {
+    type HashID = SHA384ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; SHA384ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        SHA384ID { id: id }
+    }
+}
+
+impl SHA384ID {
+    const HASH_LEN: usize = 384 / 8;
+    const NAME: &'static str = "SHA384";
+}
+
+impl HashID for SHA384ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for SHA384ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SHA384ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(SHA384ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SHA384ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for SHA384ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for Blake2sAlgo {
+    type HashID = Blake2sID;
+    type Hasher = Blake2sHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(Blake2sID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        Blake2sHasher {
+            hasher: Blake2s256::default()
+        }
+    }
+}
+
+impl Hasher for Blake2sHasher {
+    type HashID = Blake2sID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; Blake2sID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        Blake2sID { id: id }
+    }
+}
+
+impl Blake2sID {
+    const HASH_LEN: usize = 256 / 8;
+    const NAME: &'static str = "Blake2s";
+}
+
+impl HashID for Blake2sID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for Blake2sID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Blake2sID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(Blake2sID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Blake2sID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for Blake2sID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for BLAKE3Algo {
+    type HashID = BLAKE3ID;
+    type Hasher = BLAKE3Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(BLAKE3ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        BLAKE3Hasher {
+            hasher: Box::new(blake3::Hasher::new())
+        }
+    }
+}
+
+impl Hasher for BLAKE3Hasher {
+    type HashID = BLAKE3ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes);
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+
+        BLAKE3ID {
+            id: *hashed.as_bytes()
+        }
+    }
+}
+
+impl BLAKE3ID {
+    const HASH_LEN: usize = 256 / 8;
+    const NAME: &'static str = "BLAKE3";
+}
+
+impl HashID for BLAKE3ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for BLAKE3ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for BLAKE3ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(BLAKE3ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for BLAKE3ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for BLAKE3ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for SHA256Algo {
+    type HashID = SHA256ID;
+    type Hasher = SHA256Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(SHA256ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        SHA256Hasher {
+            hasher: Sha256::default()
+        }
+    }
+}
+
+impl Hasher for SHA256Hasher {
+    type HashID = SHA256ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; SHA256ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        SHA256ID { id: id }
+    }
+}
+
+impl SHA256ID {
+    const HASH_LEN: usize = 256 / 8;
+    const NAME: &'static str = "SHA256";
+}
+
+impl HashID for SHA256ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for SHA256ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SHA256ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(SHA256ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SHA256ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for SHA256ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for SHA512_256Algo {
+    type HashID = SHA512_256ID;
+    type Hasher = SHA512_256Hasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(SHA512_256ID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        SHA512_256Hasher {
+            hasher: Sha512_256::default()
+        }
+    }
+}
+
+impl Hasher for SHA512_256Hasher {
+    type HashID = SHA512_256ID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; SHA512_256ID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        SHA512_256ID { id: id }
+    }
+}
+
+impl SHA512_256ID {
+    const HASH_LEN: usize = 256 / 8;
+    const NAME: &'static str = "SHA512-256";
+}
+
+impl HashID for SHA512_256ID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for SHA512_256ID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SHA512_256ID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(SHA512_256ID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SHA512_256ID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for SHA512_256ID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for SkeinAlgo {
+    type HashID = SkeinID;
+    type Hasher = SkeinHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(SkeinID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        SkeinHasher {
+            hasher: Skein512::<U64>::new()
+        }
+    }
+}
+
+impl Hasher for SkeinHasher {
+    type HashID = SkeinID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; SkeinID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        SkeinID { id: id }
+    }
+}
+
+impl SkeinID {
+    const HASH_LEN: usize = 512 / 8;
+    const NAME: &'static str = "Skein-512";
+}
+
+impl HashID for SkeinID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for SkeinID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SkeinID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(SkeinID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SkeinID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for SkeinID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for WhirlpoolAlgo {
+    type HashID = WhirlpoolID;
+    type Hasher = WhirlpoolHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        let id = bytes.try_into()?;
+
+        Ok(WhirlpoolID { id: id })
+    }
+
+    #[inline]
+    fn start(&self) -> Self::Hasher {
+        WhirlpoolHasher {
+            hasher: Whirlpool::default()
+        }
+    }
+}
+
+impl Hasher for WhirlpoolHasher {
+    type HashID = WhirlpoolID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        self.hasher.update(bytes)
+    }
+
+    fn finish(self) -> Self::HashID {
+        let hashed = self.hasher.finalize();
+        let mut id = [0; WhirlpoolID::HASH_LEN];
+
+        id.copy_from_slice(hashed.as_slice());
+
+        WhirlpoolID { id: id }
+    }
+}
+
+impl WhirlpoolID {
+    const HASH_LEN: usize = 512 / 8;
+    const NAME: &'static str = "Whirlpool";
+}
+
+impl HashID for WhirlpoolID {
+    #[inline]
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.id
+    }
+}
+
+impl Display for WhirlpoolID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        write!(f, "{}:", self.name())?;
+
+        for i in 0..Self::HASH_LEN {
+            write!(f, "{:02x}", self.id[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for WhirlpoolID {
+    type Err = HashIDParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        Ok(WhirlpoolID {
+            id: parse_id(Self::NAME, s)?
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for WhirlpoolID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for WhirlpoolID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl Default for CompoundHashAlgo {
+    #[inline]
+    fn default() -> Self {
+        CompoundHashAlgo::SHA3 { sha3: SHA3Algo }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for CompoundHashAlgo {
+    type Error = &'a str;
+
+    fn try_from(name: &'a str) -> Result<CompoundHashAlgo, &'a str> {
+        match name {
+            "Blake2b" => Ok(CompoundHashAlgo::Blake2b {
+                blake2b: Blake2bAlgo
+            }),
+            "Blake2s" => Ok(CompoundHashAlgo::Blake2s {
+                blake2s: Blake2sAlgo
+            }),
+            "BLAKE3" => Ok(CompoundHashAlgo::BLAKE3 { blake3: BLAKE3Algo }),
+            "RipeMD-160" => Ok(CompoundHashAlgo::RipeMD160 {
+                ripemd160: RipeMD160Algo
+            }),
+            "SHA256" => Ok(CompoundHashAlgo::SHA256 { sha256: SHA256Algo }),
+            "SHA3-512" => Ok(CompoundHashAlgo::SHA3 { sha3: SHA3Algo }),
+            "SHA384" => Ok(CompoundHashAlgo::SHA384 { sha384: SHA384Algo }),
+            "SHA512-256" => Ok(CompoundHashAlgo::SHA512_256 {
+                sha512_256: SHA512_256Algo
+            }),
+            "Skein" => Ok(CompoundHashAlgo::Skein { skein: SkeinAlgo }),
+            "Whirlpool" => Ok(CompoundHashAlgo::Whirlpool {
+                whirlpool: WhirlpoolAlgo
+            }),
+            err => Err(err)
+        }
+    }
+}
+
+impl Display for CompoundHashAlgo {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        match self {
+            CompoundHashAlgo::Blake2b { .. } => write!(f, "Blake2b"),
+            CompoundHashAlgo::Blake2s { .. } => write!(f, "Blake2s"),
+            CompoundHashAlgo::BLAKE3 { .. } => write!(f, "BLAKE3"),
+            CompoundHashAlgo::RipeMD160 { .. } => write!(f, "RipeMD-160"),
+            CompoundHashAlgo::SHA256 { .. } => write!(f, "SHA256"),
+            CompoundHashAlgo::SHA3 { .. } => write!(f, "SHA3-512"),
+            CompoundHashAlgo::SHA384 { .. } => write!(f, "SHA384"),
+            CompoundHashAlgo::SHA512_256 { .. } => write!(f, "SHA512-256"),
+            CompoundHashAlgo::Skein { .. } => write!(f, "Skein"),
+            CompoundHashAlgo::Whirlpool { .. } => write!(f, "Whirlpool")
+        }
+    }
+}
+
+impl Serialize for CompoundHashAlgo {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl HashAlgo for CompoundHashAlgo {
+    type HashID = CompoundHashID;
+    type Hasher = CompoundHasher;
+
+    #[inline]
+    fn wrap_hashed_bytes(
+        &self,
+        bytes: &[u8]
+    ) -> Result<Self::HashID, TryFromSliceError> {
+        match self {
+            CompoundHashAlgo::Blake2b { blake2b } => blake2b
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::Blake2b { blake2b: out }),
+            CompoundHashAlgo::Blake2s { blake2s } => blake2s
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::Blake2s { blake2s: out }),
+            CompoundHashAlgo::BLAKE3 { blake3 } => blake3
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::BLAKE3 { blake3: out }),
+            CompoundHashAlgo::RipeMD160 { ripemd160 } => ripemd160
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::RipeMD160 { ripemd160: out }),
+            CompoundHashAlgo::SHA256 { sha256 } => sha256
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::SHA256 { sha256: out }),
+            CompoundHashAlgo::SHA3 { sha3 } => sha3
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::SHA3 { sha3: out }),
+            CompoundHashAlgo::SHA384 { sha384 } => sha384
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::SHA384 { sha384: out }),
+            CompoundHashAlgo::SHA512_256 { sha512_256 } => sha512_256
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::SHA512_256 { sha512_256: out }),
+            CompoundHashAlgo::Skein { skein } => skein
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::Skein { skein: out }),
+            CompoundHashAlgo::Whirlpool { whirlpool } => whirlpool
+                .wrap_hashed_bytes(bytes)
+                .map(|out| CompoundHashID::Whirlpool { whirlpool: out })
+        }
+    }
+
+    fn start(&self) -> Self::Hasher {
+        match self {
+            CompoundHashAlgo::Blake2b { blake2b } => CompoundHasher::Blake2b {
+                blake2b: blake2b.start()
+            },
+            CompoundHashAlgo::Blake2s { blake2s } => CompoundHasher::Blake2s {
+                blake2s: blake2s.start()
+            },
+            CompoundHashAlgo::BLAKE3 { blake3 } => CompoundHasher::BLAKE3 {
+                blake3: blake3.start()
+            },
+            CompoundHashAlgo::RipeMD160 { ripemd160 } => {
+                CompoundHasher::RipeMD160 {
+                    ripemd160: ripemd160.start()
+                }
+            }
+            CompoundHashAlgo::SHA256 { sha256 } => CompoundHasher::SHA256 {
+                sha256: sha256.start()
+            },
+            CompoundHashAlgo::SHA3 { sha3 } => {
+                CompoundHasher::SHA3 { sha3: sha3.start() }
+            }
+            CompoundHashAlgo::SHA384 { sha384 } => CompoundHasher::SHA384 {
+                sha384: sha384.start()
+            },
+            CompoundHashAlgo::SHA512_256 { sha512_256 } => {
+                CompoundHasher::SHA512_256 {
+                    sha512_256: sha512_256.start()
+                }
+            }
+            CompoundHashAlgo::Skein { skein } => CompoundHasher::Skein {
+                skein: skein.start()
+            },
+            CompoundHashAlgo::Whirlpool { whirlpool } => {
+                CompoundHasher::Whirlpool {
+                    whirlpool: whirlpool.start()
+                }
+            }
+        }
+    }
+}
+
+impl Hasher for CompoundHasher {
+    type HashID = CompoundHashID;
+
+    #[inline]
+    fn update(
+        &mut self,
+        bytes: &[u8]
+    ) {
+        match self {
+            CompoundHasher::Blake2b { blake2b } => blake2b.update(bytes),
+            CompoundHasher::Blake2s { blake2s } => blake2s.update(bytes),
+            CompoundHasher::BLAKE3 { blake3 } => blake3.update(bytes),
+            CompoundHasher::RipeMD160 { ripemd160 } => ripemd160.update(bytes),
+            CompoundHasher::SHA256 { sha256 } => sha256.update(bytes),
+            CompoundHasher::SHA3 { sha3 } => sha3.update(bytes),
+            CompoundHasher::SHA384 { sha384 } => sha384.update(bytes),
+            CompoundHasher::SHA512_256 { sha512_256 } => {
+                sha512_256.update(bytes)
+            }
+            CompoundHasher::Skein { skein } => skein.update(bytes),
+            CompoundHasher::Whirlpool { whirlpool } => whirlpool.update(bytes)
+        }
+    }
+
+    fn finish(self) -> Self::HashID {
+        match self {
+            CompoundHasher::Blake2b { blake2b } => CompoundHashID::Blake2b {
+                blake2b: blake2b.finish()
+            },
+            CompoundHasher::Blake2s { blake2s } => CompoundHashID::Blake2s {
+                blake2s: blake2s.finish()
+            },
+            CompoundHasher::BLAKE3 { blake3 } => CompoundHashID::BLAKE3 {
+                blake3: blake3.finish()
+            },
+            CompoundHasher::RipeMD160 { ripemd160 } => {
+                CompoundHashID::RipeMD160 {
+                    ripemd160: ripemd160.finish()
+                }
+            }
+            CompoundHasher::SHA256 { sha256 } => CompoundHashID::SHA256 {
+                sha256: sha256.finish()
+            },
+            CompoundHasher::SHA3 { sha3 } => CompoundHashID::SHA3 {
+                sha3: sha3.finish()
+            },
+            CompoundHasher::SHA384 { sha384 } => CompoundHashID::SHA384 {
+                sha384: sha384.finish()
+            },
+            CompoundHasher::SHA512_256 { sha512_256 } => {
+                CompoundHashID::SHA512_256 {
+                    sha512_256: sha512_256.finish()
+                }
+            }
+            CompoundHasher::Skein { skein } => CompoundHashID::Skein {
+                skein: skein.finish()
+            },
+            CompoundHasher::Whirlpool { whirlpool } => {
+                CompoundHashID::Whirlpool {
+                    whirlpool: whirlpool.finish()
+                }
+            }
+        }
+    }
+}
+
+impl CompoundHashID {
+    /// Get the [CompoundHashAlgo] that produced this ID.
+    #[inline]
+    pub fn algo(&self) -> CompoundHashAlgo {
+        match self {
+            CompoundHashID::Blake2b { .. } => CompoundHashAlgo::Blake2b {
+                blake2b: Blake2bAlgo
+            },
+            CompoundHashID::Blake2s { .. } => CompoundHashAlgo::Blake2s {
+                blake2s: Blake2sAlgo
+            },
+            CompoundHashID::BLAKE3 { .. } => {
+                CompoundHashAlgo::BLAKE3 { blake3: BLAKE3Algo }
+            }
+            CompoundHashID::RipeMD160 { .. } => CompoundHashAlgo::RipeMD160 {
+                ripemd160: RipeMD160Algo
+            },
+            CompoundHashID::SHA256 { .. } => {
+                CompoundHashAlgo::SHA256 { sha256: SHA256Algo }
+            }
+            CompoundHashID::SHA3 { .. } => {
+                CompoundHashAlgo::SHA3 { sha3: SHA3Algo }
+            }
+            CompoundHashID::SHA384 { .. } => {
+                CompoundHashAlgo::SHA384 { sha384: SHA384Algo }
+            }
+            CompoundHashID::SHA512_256 { .. } => CompoundHashAlgo::SHA512_256 {
+                sha512_256: SHA512_256Algo
+            },
+            CompoundHashID::Skein { .. } => {
+                CompoundHashAlgo::Skein { skein: SkeinAlgo }
+            }
+            CompoundHashID::Whirlpool { .. } => CompoundHashAlgo::Whirlpool {
+                whirlpool: WhirlpoolAlgo
+            }
+        }
+    }
+}
+
+impl HashID for CompoundHashID {
+    #[inline]
+    fn name(&self) -> &str {
+        match self {
+            CompoundHashID::Blake2b { blake2b } => blake2b.name(),
+            CompoundHashID::Blake2s { blake2s } => blake2s.name(),
+            CompoundHashID::BLAKE3 { blake3 } => blake3.name(),
+            CompoundHashID::RipeMD160 { ripemd160 } => ripemd160.name(),
+            CompoundHashID::SHA256 { sha256 } => sha256.name(),
+            CompoundHashID::SHA3 { sha3 } => sha3.name(),
+            CompoundHashID::SHA384 { sha384 } => sha384.name(),
+            CompoundHashID::SHA512_256 { sha512_256 } => sha512_256.name(),
+            CompoundHashID::Skein { skein } => skein.name(),
+            CompoundHashID::Whirlpool { whirlpool } => whirlpool.name()
+        }
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        match self {
+            CompoundHashID::Blake2b { blake2b } => blake2b.bytes(),
+            CompoundHashID::Blake2s { blake2s } => blake2s.bytes(),
+            CompoundHashID::BLAKE3 { blake3 } => blake3.bytes(),
+            CompoundHashID::RipeMD160 { ripemd160 } => ripemd160.bytes(),
+            CompoundHashID::SHA256 { sha256 } => sha256.bytes(),
+            CompoundHashID::SHA3 { sha3 } => sha3.bytes(),
+            CompoundHashID::SHA384 { sha384 } => sha384.bytes(),
+            CompoundHashID::SHA512_256 { sha512_256 } => sha512_256.bytes(),
+            CompoundHashID::Skein { skein } => skein.bytes(),
+            CompoundHashID::Whirlpool { whirlpool } => whirlpool.bytes()
+        }
+    }
+}
+
+impl Display for CompoundHashID {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        match self {
+            CompoundHashID::Blake2b { blake2b } => blake2b.fmt(f),
+            CompoundHashID::Blake2s { blake2s } => blake2s.fmt(f),
+            CompoundHashID::BLAKE3 { blake3 } => blake3.fmt(f),
+            CompoundHashID::RipeMD160 { ripemd160 } => ripemd160.fmt(f),
+            CompoundHashID::SHA256 { sha256 } => sha256.fmt(f),
+            CompoundHashID::SHA3 { sha3 } => sha3.fmt(f),
+            CompoundHashID::SHA384 { sha384 } => sha384.fmt(f),
+            CompoundHashID::SHA512_256 { sha512_256 } => sha512_256.fmt(f),
+            CompoundHashID::Skein { skein } => skein.fmt(f),
+            CompoundHashID::Whirlpool { whirlpool } => whirlpool.fmt(f)
+        }
+    }
+}
+
+impl From<&CompoundHashID> for HashIdentifier {
+    #[inline]
+    fn from(val: &CompoundHashID) -> HashIdentifier {
+        match val {
+            CompoundHashID::Blake2b { blake2b } => {
+                HashIdentifier::Blake2b(blake2b.id.to_vec())
+            }
+            CompoundHashID::Blake2s { blake2s } => {
+                HashIdentifier::Blake2s(blake2s.id.to_vec())
+            }
+            CompoundHashID::BLAKE3 { blake3 } => {
+                HashIdentifier::Blake3(blake3.id.to_vec())
+            }
+            CompoundHashID::RipeMD160 { ripemd160 } => {
+                HashIdentifier::Ripemd160(ripemd160.id.to_vec())
+            }
+            CompoundHashID::SHA256 { sha256 } => {
+                HashIdentifier::Sha256(sha256.id.to_vec())
+            }
+            CompoundHashID::SHA3 { sha3 } => {
+                HashIdentifier::Sha3(sha3.id.to_vec())
+            }
+            CompoundHashID::SHA384 { sha384 } => {
+                HashIdentifier::Sha384(sha384.id.to_vec())
+            }
+            CompoundHashID::SHA512_256 { sha512_256 } => {
+                HashIdentifier::Sha512256(sha512_256.id.to_vec())
+            }
+            CompoundHashID::Skein { skein } => {
+                HashIdentifier::Skein(skein.id.to_vec())
+            }
+            CompoundHashID::Whirlpool { whirlpool } => {
+                HashIdentifier::Whirlpool(whirlpool.id.to_vec())
+            }
+        }
+    }
+}
+
+impl From<CompoundHashID> for HashIdentifier {
+    #[inline]
+    fn from(val: CompoundHashID) -> HashIdentifier {
+        HashIdentifier::from(&val)
+    }
+}
+
+impl TryFrom<&HashIdentifier> for CompoundHashID {
+    type Error = TryFromSliceError;
+
+    fn try_from(val: &HashIdentifier) -> Result<Self, TryFromSliceError> {
+        match val {
+            HashIdentifier::Blake2b(bytes) => Ok(CompoundHashID::Blake2b {
+                blake2b: Blake2bAlgo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Blake2s(bytes) => Ok(CompoundHashID::Blake2s {
+                blake2s: Blake2sAlgo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Blake3(bytes) => Ok(CompoundHashID::BLAKE3 {
+                blake3: BLAKE3Algo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Ripemd160(bytes) => Ok(CompoundHashID::RipeMD160 {
+                ripemd160: RipeMD160Algo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Sha256(bytes) => Ok(CompoundHashID::SHA256 {
+                sha256: SHA256Algo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Sha3(bytes) => Ok(CompoundHashID::SHA3 {
+                sha3: SHA3Algo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Sha384(bytes) => Ok(CompoundHashID::SHA384 {
+                sha384: SHA384Algo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Sha512256(bytes) => {
+                Ok(CompoundHashID::SHA512_256 {
+                    sha512_256: SHA512_256Algo.wrap_hashed_bytes(bytes)?
+                })
+            }
+            HashIdentifier::Skein(bytes) => Ok(CompoundHashID::Skein {
+                skein: SkeinAlgo.wrap_hashed_bytes(bytes)?
+            }),
+            HashIdentifier::Whirlpool(bytes) => Ok(CompoundHashID::Whirlpool {
+                whirlpool: WhirlpoolAlgo.wrap_hashed_bytes(bytes)?
+            })
+        }
+    }
+}
+
+impl TryFrom<HashIdentifier> for CompoundHashID {
+    type Error = TryFromSliceError;
+
+    #[inline]
+    fn try_from(val: HashIdentifier) -> Result<Self, TryFromSliceError> {
+        CompoundHashID::try_from(&val)
+    }
+}
+
+impl FromStr for CompoundHashID {
+    type Err = HashIDParseError;
+
+    fn from_str(s: &str) -> Result<Self, HashIDParseError> {
+        let (name, _) =
+            s.split_once(':').ok_or(HashIDParseError::NoSeparator)?;
+
+        match name {
+            Blake2bID::NAME => Ok(CompoundHashID::Blake2b {
+                blake2b: Blake2bID::from_str(s)?
+            }),
+            Blake2sID::NAME => Ok(CompoundHashID::Blake2s {
+                blake2s: Blake2sID::from_str(s)?
+            }),
+            BLAKE3ID::NAME => Ok(CompoundHashID::BLAKE3 {
+                blake3: BLAKE3ID::from_str(s)?
+            }),
+            RipeMD160ID::NAME => Ok(CompoundHashID::RipeMD160 {
+                ripemd160: RipeMD160ID::from_str(s)?
+            }),
+            SHA256ID::NAME => Ok(CompoundHashID::SHA256 {
+                sha256: SHA256ID::from_str(s)?
+            }),
+            SHA3ID::NAME => Ok(CompoundHashID::SHA3 {
+                sha3: SHA3ID::from_str(s)?
+            }),
+            SHA384ID::NAME => Ok(CompoundHashID::SHA384 {
+                sha384: SHA384ID::from_str(s)?
+            }),
+            SHA512_256ID::NAME => Ok(CompoundHashID::SHA512_256 {
+                sha512_256: SHA512_256ID::from_str(s)?
+            }),
+            SkeinID::NAME => Ok(CompoundHashID::Skein {
+                skein: SkeinID::from_str(s)?
+            }),
+            WhirlpoolID::NAME => Ok(CompoundHashID::Whirlpool {
+                whirlpool: WhirlpoolID::from_str(s)?
+            }),
+            name => Err(HashIDParseError::UnknownAlgo {
+                name: name.to_string()
+            })
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for CompoundHashID {
+    type Error = HashIDParseError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, HashIDParseError> {
+        Self::from_str(s)
+    }
+}
+
+impl Serialize for CompoundHashID {
+    #[inline]
+    fn serialize<S>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for HashIDParseError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), Error> {
+        match self {
+            HashIDParseError::NoSeparator => {
+                write!(f, "missing separator in hash ID")
+            }
+            HashIDParseError::UnknownAlgo { name } => {
+                write!(f, "unknown hash algorithm {}", name)
+            }
+            HashIDParseError::WrongAlgo { expected, actual } => write!(
+                f,
+                "wrong hash algorithm {} (expected {})",
+                actual, expected
+            ),
+            HashIDParseError::BadHex => {
+                write!(f, "bad hexadecimal digit in hash ID")
+            }
+            HashIDParseError::BadLen { expected, actual } => write!(
+                f,
+                "hash ID has {} hex digits (expected {})",
+                actual,
+                expected * 2
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_ALGOS: [&str; 10] = [
+    "Blake2b",
+    "Blake2s",
+    "BLAKE3",
+    "RipeMD-160",
+    "SHA256",
+    "SHA3-512",
+    "SHA384",
+    "SHA512-256",
+    "Skein",
+    "Whirlpool"
+];
+
+#[test]
+fn test_incremental_hash() {
+    let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let expected = algo.hash_bytes(&data);
+        let mut hasher = algo.start();
+
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+
+        let actual = hasher.finish();
+
+        assert_eq!(expected, actual);
+    }
+}
+
+#[test]
+fn test_hash_writer() {
+    let data: Vec<u8> = (0..100000).map(|i| (i * 7) as u8).collect();
+
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let expected = algo.hash_bytes(&data);
+        let mut writer = HashWriter::new(algo.start());
+
+        writer.write_all(&data[..12345]).unwrap();
+        writer.write_all(&data[12345..]).unwrap();
+
+        assert_eq!(expected, writer.finish());
+        assert_eq!(expected, algo.hash_reader(&mut &data[..]).unwrap());
+    }
+}
+
+#[test]
+fn test_hashid_text_round_trip() {
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let id = algo.hash_bytes(b"test data");
+        let text = id.to_string();
+        let parsed = CompoundHashID::from_str(&text).unwrap();
+
+        assert_eq!(id, parsed);
+    }
+}
+
+#[test]
+fn test_hashid_text_parse() {
+    let text = "RipeMD-160:9c1185a5c5e9fc54612808977ee8f548b2258d31";
+    let id = RipeMD160ID::from_str(text).unwrap();
+
+    assert_eq!(RipeMD160Algo.null_hash(), id);
+    assert_eq!(
+        Err(HashIDParseError::NoSeparator),
+        CompoundHashID::from_str("RipeMD-160")
+    );
+    assert_eq!(
+        Err(HashIDParseError::UnknownAlgo {
+            name: String::from("MD5")
+        }),
+        CompoundHashID::from_str("MD5:d41d8cd98f00b204e9800998ecf8427e")
+    );
+    assert_eq!(
+        Err(HashIDParseError::WrongAlgo {
+            expected: "SHA384",
+            actual: String::from("RipeMD-160")
+        }),
+        SHA384ID::from_str(text)
+    );
+    assert_eq!(
+        Err(HashIDParseError::BadLen {
+            expected: 20,
+            actual: 38
+        }),
+        RipeMD160ID::from_str(&text[..text.len() - 2])
+    );
+    assert_eq!(
+        Err(HashIDParseError::BadHex),
+        RipeMD160ID::from_str(
+            "RipeMD-160:+c1185a5c5e9fc54612808977ee8f548b2258d31"
+        )
+    );
+}
+
+#[test]
+fn test_hashid_serde() {
+    let id = CompoundHashAlgo::default().hash_bytes(b"test data");
+    let yaml = serde_yaml::to_string(&id).unwrap();
+    let parsed: CompoundHashID = serde_yaml::from_str(&yaml).unwrap();
+
+    assert_eq!(format!("{}\n", id), yaml);
+    assert_eq!(id, parsed);
+}
+
+#[test]
+fn test_hash_identifier_per_round_trip() {
+    let mut codec = HashIdentifierPERCodec::create(()).unwrap();
+
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let id = algo.hash_bytes(b"test data");
+        let ident = HashIdentifier::from(&id);
+        let encoded = codec.encode_to_vec(&ident).unwrap();
+        let (decoded, nbytes) = codec.decode(&encoded).unwrap();
+
+        assert_eq!(encoded.len(), nbytes);
+        assert_eq!((id.bytes().len() * 8 + 5).div_ceil(8), nbytes);
+        assert_eq!(ident, decoded);
+        assert_eq!(id, CompoundHashID::try_from(decoded).unwrap());
+    }
+}
+
+#[test]
+fn test_hash_identifier_per_encode() {
+    let mut codec = HashIdentifierPERCodec::create(()).unwrap();
+    let id = CompoundHashID::RipeMD160 {
+        ripemd160: RipeMD160Algo.null_hash()
+    };
+    let encoded = codec.encode_to_vec(&HashIdentifier::from(id)).unwrap();
+
+    // Extension bit clear, then choice index 1 in 4 bits, followed
+    // by the hash.
+    assert_eq!(21, encoded.len());
+    assert_eq!(0x0c, encoded[0]);
+}
+
+#[test]
+fn test_hash_identifier_bad_len() {
+    let ident = HashIdentifier::Sha384(vec![0; 64]);
+
+    assert!(CompoundHashID::try_from(ident).is_err());
+}
+
+#[test]
+fn test_hash_known_answers() {
+    // Published test vectors for the message "abc", except for
+    // Skein-512, which uses the single byte 0xff from the Skein
+    // specification.
+    let vectors = [
+        (
+            "Blake2b",
+            &b"abc"[..],
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        ),
+        (
+            "Blake2s",
+            &b"abc"[..],
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+        ),
+        (
+            "BLAKE3",
+            &b"abc"[..],
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        ),
+        (
+            "RipeMD-160",
+            &b"abc"[..],
+            "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc"
+        ),
+        (
+            "SHA256",
+            &b"abc"[..],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        ),
+        (
+            "SHA3-512",
+            &b"abc"[..],
+            "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e\
+             10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"
+        ),
+        (
+            "SHA384",
+            &b"abc"[..],
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+             8086072ba1e7cc2358baeca134c825a7"
+        ),
+        (
+            "SHA512-256",
+            &b"abc"[..],
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23"
+        ),
+        (
+            "Skein",
+            &[0xff][..],
+            "71b7bce6fe6452227b9ced6014249e5bf9a9754c3ad618ccc4e0aae16b316cc8\
+             ca698d864307ed3e80b6ef1570812ac5272dc409b5a012df2a579102f340617a"
+        ),
+        (
+            "Whirlpool",
+            &b"abc"[..],
+            "4e2448a4c6f486bb16b6562c73b4020bf3043e3a731bce721ae1b303d97e6d4c\
+             7181eebdb6c57e277d0e34957114cbd6c797fc9d95d8b582d225292076d4eef5"
+        )
+    ];
+
+    for (name, msg, expected) in vectors {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+        let id = algo.hash_bytes(msg);
+        let text = id.to_string();
+        let (_, hex) = text.split_once(':').unwrap();
+
+        assert_eq!(expected, hex, "{}", name);
+    }
+}
+
+#[test]
+fn test_compound_hashid_algo() {
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+
+        assert_eq!(algo, algo.null_hash().algo());
+    }
+}
+
+#[test]
+fn test_compound_hash_algo_display() {
+    for name in TEST_ALGOS {
+        let algo = CompoundHashAlgo::try_from(name).unwrap();
+
+        assert_eq!(name, algo.to_string());
+    }
+}
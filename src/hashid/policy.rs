@@ -0,0 +1,633 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Hash algorithm policies.
+//!
+//! This module provides [HashPolicy], which describes the hash
+//! algorithms that are acceptable to a node, which of them it prefers,
+//! and when any of them are to be retired.  This allows a deployment
+//! to migrate from one algorithm to another without a flag day:
+//! a new algorithm can be accepted everywhere first, then preferred,
+//! and the old algorithm can then be deprecated and eventually
+//! rejected.
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::time::SystemTime;
+
+use serde::de::Error;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use time::Date;
+use time::Month;
+use time::OffsetDateTime;
+
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::CompoundHashAlgo;
+use crate::hashid::CompoundHashID;
+
+/// Policy for selecting and accepting hash algorithms.
+///
+/// # YAML Format
+///
+/// The YAML format has three fields:
+///
+/// - `preferred`: A list of [CompoundHashAlgo]s, in order of preference.  The
+///   first of these that has not been deprecated will be used to generate new
+///   IDs.  Defaults to a list containing only the default [CompoundHashAlgo].
+///
+/// - `accepted`: A list of additional [CompoundHashAlgo]s that are accepted
+///   from peers, but will not be used to generate new IDs unless a peer
+///   supports nothing else.
+///
+/// - `deprecated`: A list of deprecations, each of which has an `algo` field
+///   naming a [CompoundHashAlgo], and an `until` field giving a date in the
+///   form `YYYY-MM-DD`.  The algorithm continues to be accepted up to, but not
+///   including, the given date, and is rejected on and after it.
+///
+/// Algorithms that appear in neither `preferred` nor `accepted` are
+/// always rejected.
+///
+/// ## Examples
+///
+/// The following is an example of a YAML configuration with all
+/// fields represented:
+///
+/// ```yaml
+/// preferred:
+///   - SHA3-512
+///   - BLAKE3
+/// accepted:
+///   - Whirlpool
+///   - RipeMD-160
+/// deprecated:
+///   - algo: RipeMD-160
+///     until: 2026-01-01
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "hash-policy")]
+pub struct HashPolicy {
+    /// Algorithms to use, in order of preference.
+    #[serde(default = "HashPolicy::default_preferred")]
+    preferred: Vec<CompoundHashAlgo>,
+    /// Additional algorithms to accept.
+    #[serde(default)]
+    accepted: Vec<CompoundHashAlgo>,
+    /// Scheduled deprecations.
+    #[serde(default)]
+    deprecated: Vec<HashDeprecation>
+}
+
+/// Scheduled deprecation of a hash algorithm in a [HashPolicy].
+///
+/// See the [HashPolicy] documentation for the YAML format.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(rename = "hash-deprecation")]
+pub struct HashDeprecation {
+    /// The algorithm being deprecated.
+    algo: CompoundHashAlgo,
+    /// The date on which the algorithm will be rejected.
+    #[serde(serialize_with = "serialize_date")]
+    #[serde(deserialize_with = "deserialize_date")]
+    until: Date
+}
+
+/// Status of an accepted algorithm under a [HashPolicy].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HashAlgoStatus {
+    /// The algorithm is preferred.
+    Preferred,
+    /// The algorithm is accepted, but not preferred.
+    Accepted,
+    /// The algorithm is accepted, but will be rejected after a date.
+    Deprecated {
+        /// Date on which the algorithm will be rejected.
+        until: Date
+    }
+}
+
+/// Errors indicating that a hash algorithm is rejected by a
+/// [HashPolicy].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HashPolicyError {
+    /// The algorithm is not accepted by the policy.
+    NotAccepted {
+        /// The algorithm.
+        algo: CompoundHashAlgo
+    },
+    /// The algorithm was deprecated, and is now rejected.
+    Expired {
+        /// The algorithm.
+        algo: CompoundHashAlgo,
+        /// Date on which the algorithm was rejected.
+        until: Date
+    }
+}
+
+/// Parse a date of the form `YYYY-MM-DD`.
+fn parse_date(s: &str) -> Option<Date> {
+    let bytes = s.as_bytes();
+
+    if bytes.len() != 10 ||
+        bytes[4] != b'-' ||
+        bytes[7] != b'-' ||
+        !bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 4 || i == 7 || b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let year = s[0..4].parse::<i32>().ok()?;
+    let month = Month::try_from(s[5..7].parse::<u8>().ok()?).ok()?;
+    let day = s[8..10].parse::<u8>().ok()?;
+
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+fn serialize_date<S>(
+    date: &Date,
+    serializer: S
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer {
+    serializer.collect_str(date)
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Date, D::Error>
+where
+    D: Deserializer<'de> {
+    let s = <&str>::deserialize(deserializer)?;
+
+    parse_date(s).ok_or_else(|| D::Error::custom(format!("bad date {}", s)))
+}
+
+impl HashPolicy {
+    #[inline]
+    fn default_preferred() -> Vec<CompoundHashAlgo> {
+        vec![CompoundHashAlgo::default()]
+    }
+
+    /// Create a new `HashPolicy` from its components.
+    ///
+    /// The arguments of this function correspond to similarly-named
+    /// fields in the YAML format.  See documentation for details.
+    #[inline]
+    pub fn new(
+        preferred: Vec<CompoundHashAlgo>,
+        accepted: Vec<CompoundHashAlgo>,
+        deprecated: Vec<HashDeprecation>
+    ) -> Self {
+        HashPolicy {
+            preferred: preferred,
+            accepted: accepted,
+            deprecated: deprecated
+        }
+    }
+
+    /// Get the preferred algorithms, in order of preference.
+    #[inline]
+    pub fn preferred(&self) -> &[CompoundHashAlgo] {
+        &self.preferred
+    }
+
+    /// Get the additional accepted algorithms.
+    #[inline]
+    pub fn accepted(&self) -> &[CompoundHashAlgo] {
+        &self.accepted
+    }
+
+    /// Get the scheduled deprecations.
+    #[inline]
+    pub fn deprecated(&self) -> &[HashDeprecation] {
+        &self.deprecated
+    }
+
+    /// Get the status of `algo` at time `when`.
+    ///
+    /// This returns an error if `algo` is rejected.
+    pub fn status_at(
+        &self,
+        algo: &CompoundHashAlgo,
+        when: SystemTime
+    ) -> Result<HashAlgoStatus, HashPolicyError> {
+        let status = if self.preferred.contains(algo) {
+            HashAlgoStatus::Preferred
+        } else if self.accepted.contains(algo) {
+            HashAlgoStatus::Accepted
+        } else {
+            return Err(HashPolicyError::NotAccepted { algo: algo.clone() });
+        };
+        let date = OffsetDateTime::from(when).date();
+
+        // Use the earliest deprecation, if there is more than one.
+        match self
+            .deprecated
+            .iter()
+            .filter(|deprecation| &deprecation.algo == algo)
+            .map(|deprecation| deprecation.until)
+            .min()
+        {
+            Some(until) if date >= until => Err(HashPolicyError::Expired {
+                algo: algo.clone(),
+                until: until
+            }),
+            Some(until) => Ok(HashAlgoStatus::Deprecated { until: until }),
+            None => Ok(status)
+        }
+    }
+
+    /// Get the status of `algo` at the current time.
+    ///
+    /// This returns an error if `algo` is rejected.
+    #[inline]
+    pub fn status(
+        &self,
+        algo: &CompoundHashAlgo
+    ) -> Result<HashAlgoStatus, HashPolicyError> {
+        self.status_at(algo, SystemTime::now())
+    }
+
+    /// Check `id` against the policy at time `when`.
+    ///
+    /// This returns an error if the algorithm that produced `id` is
+    /// rejected.
+    #[inline]
+    pub fn check_id_at(
+        &self,
+        id: &CompoundHashID,
+        when: SystemTime
+    ) -> Result<HashAlgoStatus, HashPolicyError> {
+        self.status_at(&id.algo(), when)
+    }
+
+    /// Check `id` against the policy at the current time.
+    ///
+    /// This returns an error if the algorithm that produced `id` is
+    /// rejected.
+    #[inline]
+    pub fn check_id(
+        &self,
+        id: &CompoundHashID
+    ) -> Result<HashAlgoStatus, HashPolicyError> {
+        self.check_id_at(id, SystemTime::now())
+    }
+
+    /// Get all algorithms that are not rejected at time `when`, in
+    /// order of preference.
+    ///
+    /// Preferred algorithms come first, followed by other accepted
+    /// algorithms, followed by deprecated algorithms.  This is
+    /// suitable for sending to a peer for use with
+    /// [negotiate](HashPolicy::negotiate).
+    pub fn offer_at(
+        &self,
+        when: SystemTime
+    ) -> Vec<CompoundHashAlgo> {
+        let mut current = Vec::new();
+        let mut deprecated = Vec::new();
+
+        for algo in self.preferred.iter().chain(self.accepted.iter()) {
+            if !current.contains(algo) && !deprecated.contains(algo) {
+                match self.status_at(algo, when) {
+                    Ok(HashAlgoStatus::Deprecated { .. }) => {
+                        deprecated.push(algo.clone())
+                    }
+                    Ok(_) => current.push(algo.clone()),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        current.extend(deprecated);
+
+        current
+    }
+
+    /// Get all algorithms that are not rejected at the current time,
+    /// in order of preference.
+    #[inline]
+    pub fn offer(&self) -> Vec<CompoundHashAlgo> {
+        self.offer_at(SystemTime::now())
+    }
+
+    /// Get the algorithm to use for generating new IDs at time
+    /// `when`.
+    ///
+    /// This is the first preferred algorithm that has not been
+    /// deprecated, if there is one, and otherwise the first preferred
+    /// algorithm that is not yet rejected.
+    #[inline]
+    pub fn algo_at(
+        &self,
+        when: SystemTime
+    ) -> Option<CompoundHashAlgo> {
+        self.offer_at(when)
+            .into_iter()
+            .find(|algo| self.preferred.contains(algo))
+    }
+
+    /// Get the algorithm to use for generating new IDs at the current
+    /// time.
+    #[inline]
+    pub fn algo(&self) -> Option<CompoundHashAlgo> {
+        self.algo_at(SystemTime::now())
+    }
+
+    /// Choose an algorithm from those offered by a peer at time
+    /// `when`.
+    ///
+    /// `peer` gives the algorithms acceptable to the peer, typically
+    /// obtained from its [offer](HashPolicy::offer).  The result is
+    /// the most preferred algorithm under this policy that is also
+    /// in `peer`, or `None` if there is no such algorithm.  Because
+    /// the order of preference is taken from this policy, only one
+    /// side of a session should negotiate, and then inform the other
+    /// of the result, which the other can check with
+    /// [status](HashPolicy::status).
+    #[inline]
+    pub fn negotiate_at(
+        &self,
+        peer: &[CompoundHashAlgo],
+        when: SystemTime
+    ) -> Option<CompoundHashAlgo> {
+        self.offer_at(when)
+            .into_iter()
+            .find(|algo| peer.contains(algo))
+    }
+
+    /// Choose an algorithm from those offered by a peer at the
+    /// current time.
+    #[inline]
+    pub fn negotiate(
+        &self,
+        peer: &[CompoundHashAlgo]
+    ) -> Option<CompoundHashAlgo> {
+        self.negotiate_at(peer, SystemTime::now())
+    }
+}
+
+impl HashDeprecation {
+    /// Create a new `HashDeprecation` from its components.
+    #[inline]
+    pub fn new(
+        algo: CompoundHashAlgo,
+        until: Date
+    ) -> Self {
+        HashDeprecation {
+            algo: algo,
+            until: until
+        }
+    }
+
+    /// Get the algorithm being deprecated.
+    #[inline]
+    pub fn algo(&self) -> &CompoundHashAlgo {
+        &self.algo
+    }
+
+    /// Get the date on which the algorithm will be rejected.
+    #[inline]
+    pub fn until(&self) -> Date {
+        self.until
+    }
+}
+
+impl Default for HashPolicy {
+    #[inline]
+    fn default() -> Self {
+        HashPolicy {
+            preferred: HashPolicy::default_preferred(),
+            accepted: vec![],
+            deprecated: vec![]
+        }
+    }
+}
+
+impl ScopedError for HashPolicyError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl Display for HashPolicyError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            HashPolicyError::NotAccepted { algo } => {
+                write!(f, "hash algorithm {} is not accepted", algo)
+            }
+            HashPolicyError::Expired { algo, until } => write!(
+                f,
+                "hash algorithm {} has been rejected since {}",
+                algo, until
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use crate::hashid::HashAlgo;
+
+#[cfg(test)]
+fn test_policy() -> HashPolicy {
+    let yaml = concat!(
+        "preferred:\n",
+        "  - SHA3-512\n",
+        "  - BLAKE3\n",
+        "accepted:\n",
+        "  - Whirlpool\n",
+        "  - RipeMD-160\n",
+        "deprecated:\n",
+        "  - algo: RipeMD-160\n",
+        "    until: 2026-01-01\n",
+        "  - algo: SHA3-512\n",
+        "    until: 2030-06-15\n"
+    );
+
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[cfg(test)]
+fn algo(name: &str) -> CompoundHashAlgo {
+    CompoundHashAlgo::try_from(name).unwrap()
+}
+
+#[cfg(test)]
+fn at(date: &str) -> SystemTime {
+    let date = parse_date(date).unwrap();
+    let secs = date.midnight().assume_utc().unix_timestamp();
+
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64)
+}
+
+#[test]
+fn test_parse_policy() {
+    let expected = HashPolicy::new(
+        vec![algo("SHA3-512"), algo("BLAKE3")],
+        vec![algo("Whirlpool"), algo("RipeMD-160")],
+        vec![
+            HashDeprecation::new(
+                algo("RipeMD-160"),
+                Date::from_calendar_date(2026, Month::January, 1).unwrap()
+            ),
+            HashDeprecation::new(
+                algo("SHA3-512"),
+                Date::from_calendar_date(2030, Month::June, 15).unwrap()
+            ),
+        ]
+    );
+    let actual = test_policy();
+
+    assert_eq!(expected, actual);
+
+    let yaml = serde_yaml::to_string(&actual).unwrap();
+
+    assert_eq!(actual, serde_yaml::from_str(&yaml).unwrap());
+}
+
+#[test]
+fn test_parse_policy_default() {
+    let actual: HashPolicy = serde_yaml::from_str("{}").unwrap();
+
+    assert_eq!(HashPolicy::default(), actual);
+    assert_eq!(Some(CompoundHashAlgo::default()), actual.algo());
+}
+
+#[test]
+fn test_parse_policy_bad_date() {
+    let yaml = concat!(
+        "deprecated:\n",
+        "  - algo: RipeMD-160\n",
+        "    until: 2026-02-30\n"
+    );
+
+    assert!(serde_yaml::from_str::<HashPolicy>(yaml).is_err());
+    assert_eq!(None, parse_date("+202-01-01"));
+    assert_eq!(None, parse_date("2026-1-01"));
+}
+
+#[test]
+fn test_policy_status() {
+    let policy = test_policy();
+    let before = at("2025-12-31");
+    let after = at("2026-01-01");
+
+    assert_eq!(
+        Ok(HashAlgoStatus::Preferred),
+        policy.status_at(&algo("BLAKE3"), after)
+    );
+    assert_eq!(
+        Ok(HashAlgoStatus::Accepted),
+        policy.status_at(&algo("Whirlpool"), after)
+    );
+    assert_eq!(
+        Ok(HashAlgoStatus::Deprecated {
+            until: parse_date("2026-01-01").unwrap()
+        }),
+        policy.status_at(&algo("RipeMD-160"), before)
+    );
+    assert!(matches!(
+        policy.status_at(&algo("RipeMD-160"), after),
+        Err(HashPolicyError::Expired { .. })
+    ));
+    assert_eq!(
+        Err(HashPolicyError::NotAccepted {
+            algo: algo("SHA256")
+        }),
+        policy.status_at(&algo("SHA256"), before)
+    );
+
+    let id = algo("RipeMD-160").hash_bytes(b"test data");
+
+    assert!(policy.check_id_at(&id, before).is_ok());
+    assert!(policy.check_id_at(&id, after).is_err());
+}
+
+#[test]
+fn test_policy_algo() {
+    let policy = test_policy();
+
+    assert_eq!(Some(algo("BLAKE3")), policy.algo_at(at("2026-01-01")));
+    assert_eq!(Some(algo("BLAKE3")), policy.algo_at(at("2031-01-01")));
+
+    let policy = HashPolicy::new(
+        vec![algo("SHA3-512")],
+        vec![],
+        vec![HashDeprecation::new(
+            algo("SHA3-512"),
+            parse_date("2030-01-01").unwrap()
+        )]
+    );
+
+    assert_eq!(Some(algo("SHA3-512")), policy.algo_at(at("2029-12-31")));
+    assert_eq!(None, policy.algo_at(at("2030-01-01")));
+}
+
+#[test]
+fn test_policy_negotiate() {
+    let policy = test_policy();
+    let before = at("2025-06-01");
+    let after = at("2026-06-01");
+
+    assert_eq!(
+        vec![
+            algo("BLAKE3"),
+            algo("Whirlpool"),
+            algo("SHA3-512"),
+            algo("RipeMD-160")
+        ],
+        policy.offer_at(before)
+    );
+    assert_eq!(
+        vec![algo("BLAKE3"), algo("Whirlpool"), algo("SHA3-512")],
+        policy.offer_at(after)
+    );
+
+    let peer = HashPolicy::new(
+        vec![algo("RipeMD-160")],
+        vec![algo("SHA3-512"), algo("Whirlpool")],
+        vec![]
+    );
+
+    assert_eq!(
+        Some(algo("Whirlpool")),
+        policy.negotiate_at(&peer.offer_at(after), after)
+    );
+    assert_eq!(
+        Some(algo("RipeMD-160")),
+        peer.negotiate_at(&policy.offer_at(before), before)
+    );
+    assert_eq!(
+        Some(algo("SHA3-512")),
+        peer.negotiate_at(&policy.offer_at(after), after)
+    );
+    assert_eq!(None, policy.negotiate_at(&[algo("SHA256")], after));
+}
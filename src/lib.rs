@@ -42,6 +42,8 @@ mod generated;
 
 pub mod codec;
 pub mod config;
+#[cfg(feature = "openssl")]
+pub mod dtls;
 pub mod error;
 pub mod hashid;
 pub mod net;
@@ -49,6 +51,9 @@ pub mod nonblock;
 pub mod retry;
 pub mod sched;
 pub mod shutdown;
+#[cfg(feature = "openssl")]
+pub mod sign;
+pub mod store;
 pub mod sync;
 pub mod version;
 
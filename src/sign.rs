@@ -0,0 +1,1145 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Digital signatures over encoded values.
+//!
+//! This module provides object-level authenticity that does not
+//! depend on the transport.  A [Signer] holds a private key and its
+//! certificate chain (typically loaded from a [PKIIdentity]), and
+//! signs the encoding of a value produced by a [DatagramCodec],
+//! producing a [SignedEnvelope].  A [Verifier] holds an [X509Store]
+//! (typically obtained from [PKITrustRoot::load]), and checks both the
+//! certificate chain and the signature on a [SignedEnvelope] before
+//! decoding the value, producing a [Verified] value that carries the
+//! [SignerIdentity].
+//!
+//! [SignedEnvelope]s can be sent over the wire using
+//! [SignedEnvelopeCodec].
+//!
+//! A [Verifier] only accepts signatures from certificates that are
+//! meant for signing objects: the signer's certificate must have a key
+//! usage extension permitting `digitalSignature`, and an extended key
+//! usage extension containing the purpose given to the [Verifier].
+//! This defaults to `codeSigning`, and can be set to a private OID
+//! with [Verifier::with_eku].  This keeps certificates issued for
+//! TLS from being used to sign objects.
+//!
+//! Signatures are computed over a fixed context string followed by
+//! the encoded value, which prevents them from being confused with
+//! signatures made for other purposes with the same key.  EdDSA keys
+//! (Ed25519 and Ed448) sign the data directly; all other keys use
+//! SHA-384 as the message digest.
+//!
+//! [PKITrustRoot::load]: crate::config::pki::PKITrustRoot::load
+use std::convert::Infallible;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use foreign_types::ForeignTypeRef;
+use openssl::asn1::Asn1Object;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::HasPublic;
+use openssl::pkey::Id;
+use openssl::pkey::PKey;
+use openssl::pkey::PKeyRef;
+use openssl::pkey::Private;
+use openssl::stack::Stack;
+use openssl::x509::store::X509Store;
+use openssl::x509::X509NameRef;
+use openssl::x509::X509Ref;
+use openssl::x509::X509StoreContext;
+use openssl::x509::X509;
+
+use crate::codec::DatagramCodec;
+use crate::config::pki::cert_extension;
+use crate::config::pki::ext_key_usages;
+use crate::config::pki::PKIIdentity;
+use crate::config::pki::PKIIdentityLoadError;
+use crate::config::pki::EKU_OID;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+
+/// Context string prefixed to all signed data.
+const SIGNATURE_CONTEXT: &[u8] = b"constellation signed envelope\0";
+
+/// Maximum number of certificates in a [SignedEnvelope].
+pub const MAX_CHAIN_LEN: usize = 8;
+
+/// Extended key usage required by [Verifier::new].
+pub const DEFAULT_SIGNER_EKU: &str = "codeSigning";
+
+/// DER contents of the `codeSigning` OID (1.3.6.1.5.5.7.3.3).
+const CODE_SIGNING_OID: [u8; 8] =
+    [0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03];
+
+/// Size of the length fields in the [SignedEnvelopeCodec] encoding.
+const LEN_BYTES: usize = 2;
+
+/// Signs encoded values with a private key.
+///
+/// See the [module documentation](crate::sign) for details.
+pub struct Signer {
+    key: PKey<Private>,
+    /// DER encodings of the certificate chain.
+    chain: Vec<Vec<u8>>
+}
+
+/// Verifies [SignedEnvelope]s against a trust store.
+///
+/// See the [module documentation](crate::sign) for details.
+pub struct Verifier {
+    store: X509Store,
+    /// Extended key usage required of signers, as given.
+    eku: String,
+    /// DER contents of the OID of `eku`.
+    eku_oid: Vec<u8>
+}
+
+/// A signed, encoded value, together with the signer's certificate
+/// chain.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SignedEnvelope {
+    /// DER encodings of the certificate chain, leaf first.
+    chain: Vec<Vec<u8>>,
+    /// Encoded value.
+    payload: Vec<u8>,
+    /// Signature over the context and payload.
+    signature: Vec<u8>
+}
+
+/// Identity of the signer of a [Verified] value.
+///
+/// This is derived from the signer's certificate, which has been
+/// verified against a trust store.
+#[derive(Clone)]
+pub struct SignerIdentity {
+    /// Subject name, in one-line form.
+    subject: String,
+    /// DNS names from the subject alternative names.
+    dns_names: Vec<String>,
+    /// The signer's certificate.
+    cert: X509
+}
+
+/// A value whose signature has been verified by a [Verifier].
+pub struct Verified<T> {
+    val: T,
+    signer: SignerIdentity
+}
+
+/// Codec for [SignedEnvelope]s.
+///
+/// The encoding consists of the number of certificates as a single
+/// byte, followed by each certificate, then the payload, then the
+/// signature.  Each of these is encoded as a 16-bit big-endian length
+/// followed by the raw bytes.
+pub struct SignedEnvelopeCodec<const MAX_BYTES: usize>;
+
+/// Errors that can occur when signing a value.
+#[derive(Debug)]
+pub enum SignError<Encode> {
+    /// Error encoding the value.
+    Encode {
+        /// The encoding error.
+        error: Encode
+    },
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    }
+}
+
+/// Errors that can occur when verifying a [SignedEnvelope].
+#[derive(Debug)]
+pub enum VerifyError<Decode> {
+    /// Error decoding the value.
+    Decode {
+        /// The decoding error.
+        error: Decode
+    },
+    /// An error occurred in the OpenSSL library.
+    OpenSSL {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// A certificate in the envelope could not be parsed.
+    BadCert {
+        /// The OpenSSL error stack.
+        error: ErrorStack
+    },
+    /// The certificate chain could not be verified.
+    Untrusted {
+        /// Description of the verification failure.
+        reason: String
+    },
+    /// The signer's certificate does not permit digital signatures.
+    BadKeyUsage,
+    /// The signer's certificate lacks the required extended key usage.
+    MissingEKU {
+        /// The required extended key usage.
+        eku: String
+    },
+    /// The signature did not match.
+    BadSignature
+}
+
+/// Errors that can occur when encoding a [SignedEnvelope].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignedEnvelopeEncodeError {
+    /// The encoded envelope would exceed the maximum size.
+    TooLong {
+        /// Length of the encoded envelope.
+        len: usize,
+        /// Maximum length.
+        max: usize
+    },
+    /// The certificate chain was empty, or too long.
+    BadChainLen {
+        /// Length of the certificate chain.
+        len: usize
+    }
+}
+
+/// Errors that can occur when decoding a [SignedEnvelope].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignedEnvelopeDecodeError {
+    /// The input was too short.
+    Truncated,
+    /// The certificate chain was empty, or too long.
+    BadChainLen {
+        /// Length of the certificate chain.
+        len: usize
+    }
+}
+
+/// Get the message digest to use with `key`.
+fn digest<T>(key: &PKeyRef<T>) -> Option<MessageDigest>
+where
+    T: HasPublic {
+    match key.id() {
+        Id::ED25519 | Id::ED448 => None,
+        _ => Some(MessageDigest::sha384())
+    }
+}
+
+/// Get the data to be signed for `payload`.
+fn signed_data(payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(SIGNATURE_CONTEXT.len() + payload.len());
+
+    data.extend_from_slice(SIGNATURE_CONTEXT);
+    data.extend_from_slice(payload);
+
+    data
+}
+
+impl Signer {
+    /// Create a new `Signer` from a private key and its certificate
+    /// chain.
+    ///
+    /// The first certificate in `chain` must be the one for `key`.
+    pub fn new(
+        key: PKey<Private>,
+        chain: &[X509]
+    ) -> Result<Self, ErrorStack> {
+        let chain = chain
+            .iter()
+            .map(|cert| cert.to_der())
+            .collect::<Result<Vec<Vec<u8>>, ErrorStack>>()?;
+
+        Ok(Signer {
+            key: key,
+            chain: chain
+        })
+    }
+
+    /// Create a new `Signer` by loading a [PKIIdentity].
+    pub fn from_identity(
+        identity: &PKIIdentity
+    ) -> Result<Self, PKIIdentityLoadError> {
+        let (key, chain) = identity.load()?;
+
+        Signer::new(key, &chain)
+            .map_err(|err| PKIIdentityLoadError::OpenSSL { error: err })
+    }
+
+    /// Sign `payload`, which is already encoded.
+    pub fn sign_bytes(
+        &self,
+        payload: &[u8]
+    ) -> Result<SignedEnvelope, ErrorStack> {
+        let mut signer = match digest(&self.key) {
+            Some(digest) => openssl::sign::Signer::new(digest, &self.key)?,
+            None => openssl::sign::Signer::new_without_digest(&self.key)?
+        };
+        let signature = signer.sign_oneshot_to_vec(&signed_data(payload))?;
+
+        Ok(SignedEnvelope {
+            chain: self.chain.clone(),
+            payload: payload.to_vec(),
+            signature: signature
+        })
+    }
+
+    /// Sign the encoding of `val` by `codec`.
+    #[inline]
+    pub fn sign<T, Codec>(
+        &self,
+        codec: &mut Codec,
+        val: &T
+    ) -> Result<SignedEnvelope, SignError<Codec::EncodeError>>
+    where
+        Codec: DatagramCodec<T> {
+        let payload = codec
+            .encode_to_vec(val)
+            .map_err(|err| SignError::Encode { error: err })?;
+
+        self.sign_bytes(&payload)
+            .map_err(|err| SignError::OpenSSL { error: err })
+    }
+}
+
+impl Verifier {
+    /// Create a new `Verifier` that checks certificate chains against
+    /// `store`, and requires the [DEFAULT_SIGNER_EKU] extended key
+    /// usage.
+    #[inline]
+    pub fn new(store: X509Store) -> Self {
+        Verifier {
+            store: store,
+            eku: String::from(DEFAULT_SIGNER_EKU),
+            eku_oid: CODE_SIGNING_OID.to_vec()
+        }
+    }
+
+    /// Create a new `Verifier` that checks certificate chains against
+    /// `store`, and requires the extended key usage `eku`.
+    ///
+    /// The extended key usage is given as an OpenSSL name (such as
+    /// `codeSigning`) or a dotted OID.
+    pub fn with_eku(
+        store: X509Store,
+        eku: &str
+    ) -> Result<Self, ErrorStack> {
+        let oid = Asn1Object::from_str(eku)?;
+
+        Ok(Verifier {
+            store: store,
+            eku: String::from(eku),
+            eku_oid: oid.as_slice().to_vec()
+        })
+    }
+
+    /// Get the extended key usage required of signers.
+    #[inline]
+    pub fn eku(&self) -> &str {
+        &self.eku
+    }
+
+    /// Check that `cert` is meant for signing objects.
+    fn check_purpose<Decode>(
+        &self,
+        cert: &X509Ref
+    ) -> Result<(), VerifyError<Decode>> {
+        // SAFETY: cert is a valid pointer.  This only updates the
+        // cached extension information.
+        let (flags, usage) = unsafe {
+            (
+                openssl_sys::X509_get_extension_flags(cert.as_ptr()),
+                openssl_sys::X509_get_key_usage(cert.as_ptr())
+            )
+        };
+
+        if flags & openssl_sys::EXFLAG_KUSAGE == 0 ||
+            usage & openssl_sys::X509v3_KU_DIGITAL_SIGNATURE == 0
+        {
+            return Err(VerifyError::BadKeyUsage);
+        }
+
+        let der = cert
+            .to_der()
+            .map_err(|err| VerifyError::BadCert { error: err })?;
+        let ekus = cert_extension(&der, &EKU_OID)
+            .and_then(ext_key_usages)
+            .unwrap_or_default();
+
+        if ekus.contains(&self.eku_oid.as_slice()) {
+            Ok(())
+        } else {
+            Err(VerifyError::MissingEKU {
+                eku: self.eku.clone()
+            })
+        }
+    }
+
+    /// Get the trust store.
+    #[inline]
+    pub fn store(&self) -> &X509Store {
+        &self.store
+    }
+
+    /// Verify `envelope`, returning the [SignerIdentity] and the
+    /// still-encoded payload.
+    pub fn verify_bytes<'a>(
+        &self,
+        envelope: &'a SignedEnvelope
+    ) -> Result<(SignerIdentity, &'a [u8]), VerifyError<Infallible>> {
+        let mut certs = envelope
+            .chain
+            .iter()
+            .map(|der| X509::from_der(der))
+            .collect::<Result<Vec<X509>, ErrorStack>>()
+            .map_err(|err| VerifyError::BadCert { error: err })?;
+
+        if certs.is_empty() {
+            return Err(VerifyError::Untrusted {
+                reason: String::from("no certificates")
+            });
+        }
+
+        let leaf = certs.remove(0);
+        let mut untrusted =
+            Stack::new().map_err(|err| VerifyError::OpenSSL { error: err })?;
+
+        for cert in certs {
+            untrusted
+                .push(cert)
+                .map_err(|err| VerifyError::OpenSSL { error: err })?;
+        }
+
+        let mut ctx = X509StoreContext::new()
+            .map_err(|err| VerifyError::OpenSSL { error: err })?;
+        let failure = ctx
+            .init(&self.store, &leaf, &untrusted, |ctx| {
+                if ctx.verify_cert()? {
+                    Ok(None)
+                } else {
+                    Ok(Some(ctx.error().error_string().to_string()))
+                }
+            })
+            .map_err(|err| VerifyError::OpenSSL { error: err })?;
+
+        if let Some(reason) = failure {
+            return Err(VerifyError::Untrusted { reason: reason });
+        }
+
+        self.check_purpose(&leaf)?;
+
+        let public = leaf
+            .public_key()
+            .map_err(|err| VerifyError::BadCert { error: err })?;
+        let mut verifier = match digest(&public) {
+            Some(digest) => openssl::sign::Verifier::new(digest, &public),
+            None => openssl::sign::Verifier::new_without_digest(&public)
+        }
+        .map_err(|err| VerifyError::OpenSSL { error: err })?;
+
+        // Malformed signatures show up as errors, rather than false.
+        match verifier.verify_oneshot(
+            &envelope.signature,
+            &signed_data(&envelope.payload)
+        ) {
+            Ok(true) => {
+                let signer = SignerIdentity::new(leaf)
+                    .map_err(|err| VerifyError::BadCert { error: err })?;
+
+                Ok((signer, &envelope.payload))
+            }
+            _ => Err(VerifyError::BadSignature)
+        }
+    }
+
+    /// Verify `envelope`, and decode the payload with `codec`.
+    pub fn verify<T, Codec>(
+        &self,
+        codec: &mut Codec,
+        envelope: &SignedEnvelope
+    ) -> Result<Verified<T>, VerifyError<Codec::DecodeError>>
+    where
+        Codec: DatagramCodec<T> {
+        let (signer, payload) =
+            self.verify_bytes(envelope).map_err(|err| match err {
+                VerifyError::Decode { error } => match error {},
+                VerifyError::OpenSSL { error } => {
+                    VerifyError::OpenSSL { error: error }
+                }
+                VerifyError::BadCert { error } => {
+                    VerifyError::BadCert { error: error }
+                }
+                VerifyError::Untrusted { reason } => {
+                    VerifyError::Untrusted { reason: reason }
+                }
+                VerifyError::BadKeyUsage => VerifyError::BadKeyUsage,
+                VerifyError::MissingEKU { eku } => {
+                    VerifyError::MissingEKU { eku: eku }
+                }
+                VerifyError::BadSignature => VerifyError::BadSignature
+            })?;
+        let (val, _) = codec
+            .decode(payload)
+            .map_err(|err| VerifyError::Decode { error: err })?;
+
+        Ok(Verified {
+            val: val,
+            signer: signer
+        })
+    }
+}
+
+impl SignedEnvelope {
+    /// Create a new `SignedEnvelope` from its components.
+    #[inline]
+    pub fn new(
+        chain: Vec<Vec<u8>>,
+        payload: Vec<u8>,
+        signature: Vec<u8>
+    ) -> Self {
+        SignedEnvelope {
+            chain: chain,
+            payload: payload,
+            signature: signature
+        }
+    }
+
+    /// Get the DER encodings of the certificate chain, leaf first.
+    #[inline]
+    pub fn chain(&self) -> &[Vec<u8>] {
+        &self.chain
+    }
+
+    /// Get the encoded value.
+    ///
+    /// This has not been verified.
+    #[inline]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Get the signature.
+    #[inline]
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Decompose this into its components.
+    #[inline]
+    pub fn take(self) -> (Vec<Vec<u8>>, Vec<u8>, Vec<u8>) {
+        (self.chain, self.payload, self.signature)
+    }
+}
+
+impl SignerIdentity {
+    fn new(cert: X509) -> Result<Self, ErrorStack> {
+        Ok(SignerIdentity {
+            subject: cert_subject(&cert)?,
+            dns_names: cert_dns_names(&cert),
+            cert: cert
+        })
+    }
+
+    /// Get the subject name of the signer's certificate, in one-line
+    /// form (for example, `C=US, O=Example, CN=host.example.com`).
+    #[inline]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Get the DNS names in the subject alternative names of the
+    /// signer's certificate.
+    #[inline]
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// Get the signer's certificate.
+    #[inline]
+    pub fn cert(&self) -> &X509 {
+        &self.cert
+    }
+}
+
+/// Get the subject name of `cert` in one-line form.
+#[inline]
+pub(crate) fn cert_subject(cert: &X509Ref) -> Result<String, ErrorStack> {
+    name_oneline(cert.subject_name())
+}
+
+/// Get `name` in one-line form.
+pub(crate) fn name_oneline(name: &X509NameRef) -> Result<String, ErrorStack> {
+    let mut parts = Vec::new();
+
+    for entry in name.entries() {
+        let name = entry.object().nid().short_name()?;
+        let data = entry.data().to_string()?;
+
+        parts.push(format!("{}={}", name, data));
+    }
+
+    Ok(parts.join(", "))
+}
+
+/// Get the DNS names in the subject alternative names of `cert`.
+pub(crate) fn cert_dns_names(cert: &X509Ref) -> Vec<String> {
+    cert.subject_alt_names()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| name.dnsname().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl<T> Verified<T> {
+    /// Get the verified value.
+    #[inline]
+    pub fn val(&self) -> &T {
+        &self.val
+    }
+
+    /// Get the identity of the signer.
+    #[inline]
+    pub fn signer(&self) -> &SignerIdentity {
+        &self.signer
+    }
+
+    /// Decompose this into the value and the signer identity.
+    #[inline]
+    pub fn take(self) -> (T, SignerIdentity) {
+        (self.val, self.signer)
+    }
+}
+
+impl<const MAX_BYTES: usize> SignedEnvelopeCodec<MAX_BYTES> {
+    /// Append a length-prefixed field.
+    fn push_field(
+        out: &mut Vec<u8>,
+        field: &[u8]
+    ) -> Result<(), SignedEnvelopeEncodeError> {
+        let len = u16::try_from(field.len()).map_err(|_| {
+            SignedEnvelopeEncodeError::TooLong {
+                len: field.len(),
+                max: u16::MAX as usize
+            }
+        })?;
+
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(field);
+
+        Ok(())
+    }
+
+    /// Read a length-prefixed field at `offset`.
+    fn read_field(
+        buf: &[u8],
+        offset: &mut usize
+    ) -> Result<Vec<u8>, SignedEnvelopeDecodeError> {
+        let len = buf
+            .get(*offset..*offset + LEN_BYTES)
+            .ok_or(SignedEnvelopeDecodeError::Truncated)?;
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+        let start = *offset + LEN_BYTES;
+        let field = buf
+            .get(start..start + len)
+            .ok_or(SignedEnvelopeDecodeError::Truncated)?;
+
+        *offset = start + len;
+
+        Ok(field.to_vec())
+    }
+}
+
+impl<const MAX_BYTES: usize> DatagramCodec<SignedEnvelope>
+    for SignedEnvelopeCodec<MAX_BYTES>
+{
+    type CreateError = Infallible;
+    type DecodeError = SignedEnvelopeDecodeError;
+    type EncodeError = SignedEnvelopeEncodeError;
+    type Param = ();
+
+    const MAX_BYTES: usize = MAX_BYTES;
+
+    #[inline]
+    fn create(_param: ()) -> Result<Self, Infallible> {
+        Ok(SignedEnvelopeCodec)
+    }
+
+    fn encode(
+        &mut self,
+        val: &SignedEnvelope,
+        buf: &mut [u8]
+    ) -> Result<usize, Self::EncodeError> {
+        let encoded = self.encode_to_vec(val)?;
+
+        buf[..encoded.len()].copy_from_slice(&encoded);
+
+        Ok(encoded.len())
+    }
+
+    fn encode_to_vec(
+        &mut self,
+        val: &SignedEnvelope
+    ) -> Result<Vec<u8>, Self::EncodeError> {
+        let nchain = val.chain.len();
+
+        if nchain == 0 || nchain > MAX_CHAIN_LEN {
+            return Err(SignedEnvelopeEncodeError::BadChainLen { len: nchain });
+        }
+
+        let mut out = vec![nchain as u8];
+
+        for cert in &val.chain {
+            Self::push_field(&mut out, cert)?;
+        }
+
+        Self::push_field(&mut out, &val.payload)?;
+        Self::push_field(&mut out, &val.signature)?;
+
+        if out.len() > MAX_BYTES {
+            return Err(SignedEnvelopeEncodeError::TooLong {
+                len: out.len(),
+                max: MAX_BYTES
+            });
+        }
+
+        Ok(out)
+    }
+
+    fn decode(
+        &mut self,
+        buf: &[u8]
+    ) -> Result<(SignedEnvelope, usize), Self::DecodeError> {
+        let nchain =
+            *buf.first().ok_or(SignedEnvelopeDecodeError::Truncated)? as usize;
+
+        if nchain == 0 || nchain > MAX_CHAIN_LEN {
+            return Err(SignedEnvelopeDecodeError::BadChainLen { len: nchain });
+        }
+
+        let mut offset = 1;
+        let mut chain = Vec::with_capacity(nchain);
+
+        for _ in 0..nchain {
+            chain.push(Self::read_field(buf, &mut offset)?);
+        }
+
+        let payload = Self::read_field(buf, &mut offset)?;
+        let signature = Self::read_field(buf, &mut offset)?;
+
+        Ok((SignedEnvelope::new(chain, payload, signature), offset))
+    }
+}
+
+impl<Encode> ScopedError for SignError<Encode>
+where
+    Encode: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            SignError::Encode { error } => error.scope(),
+            SignError::OpenSSL { .. } => ErrorScope::Unrecoverable
+        }
+    }
+}
+
+impl<Decode> ScopedError for VerifyError<Decode>
+where
+    Decode: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            VerifyError::Decode { error } => error.scope(),
+            VerifyError::OpenSSL { .. } => ErrorScope::Unrecoverable,
+            VerifyError::BadCert { .. } => ErrorScope::Msg,
+            VerifyError::Untrusted { .. } => ErrorScope::Msg,
+            VerifyError::BadKeyUsage => ErrorScope::Msg,
+            VerifyError::MissingEKU { .. } => ErrorScope::Msg,
+            VerifyError::BadSignature => ErrorScope::Msg
+        }
+    }
+}
+
+impl ScopedError for SignedEnvelopeEncodeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl ScopedError for SignedEnvelopeDecodeError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        ErrorScope::Msg
+    }
+}
+
+impl<Encode> Display for SignError<Encode>
+where
+    Encode: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            SignError::Encode { error } => error.fmt(f),
+            SignError::OpenSSL { error } => error.fmt(f)
+        }
+    }
+}
+
+impl<Decode> Display for VerifyError<Decode>
+where
+    Decode: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            VerifyError::Decode { error } => error.fmt(f),
+            VerifyError::OpenSSL { error } => error.fmt(f),
+            VerifyError::BadCert { error } => {
+                write!(f, "bad certificate in signed envelope: {}", error)
+            }
+            VerifyError::Untrusted { reason } => {
+                write!(f, "signer certificate not trusted: {}", reason)
+            }
+            VerifyError::BadKeyUsage => write!(
+                f,
+                "signer certificate does not permit digital signatures"
+            ),
+            VerifyError::MissingEKU { eku } => {
+                write!(f, "signer certificate lacks extended key usage {}", eku)
+            }
+            VerifyError::BadSignature => write!(f, "signature mismatch")
+        }
+    }
+}
+
+impl Display for SignedEnvelopeEncodeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            SignedEnvelopeEncodeError::TooLong { len, max } => {
+                write!(
+                    f,
+                    "signed envelope field is {} bytes (maximum {})",
+                    len, max
+                )
+            }
+            SignedEnvelopeEncodeError::BadChainLen { len } => {
+                write!(f, "certificate chain has {} certificates", len)
+            }
+        }
+    }
+}
+
+impl Display for SignedEnvelopeDecodeError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            SignedEnvelopeDecodeError::Truncated => {
+                write!(f, "signed envelope truncated")
+            }
+            SignedEnvelopeDecodeError::BadChainLen { len } => {
+                write!(f, "certificate chain has {} certificates", len)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::path::Path;
+#[cfg(test)]
+use std::time::SystemTime;
+
+#[cfg(test)]
+use openssl::asn1::Asn1Time;
+#[cfg(test)]
+use openssl::bn::BigNum;
+#[cfg(test)]
+use openssl::x509::extension::AuthorityKeyIdentifier;
+#[cfg(test)]
+use openssl::x509::extension::ExtendedKeyUsage;
+#[cfg(test)]
+use openssl::x509::extension::KeyUsage;
+#[cfg(test)]
+use openssl::x509::X509Builder;
+#[cfg(test)]
+use openssl::x509::X509NameBuilder;
+#[cfg(test)]
+use openssl::x509::X509PurposeId;
+
+#[cfg(test)]
+use crate::config::pki::keys::PKIFileKey;
+#[cfg(test)]
+use crate::config::pki::keys::PKIKey;
+#[cfg(test)]
+use crate::config::pki::testing::TestCertSpec;
+#[cfg(test)]
+use crate::config::pki::testing::TestPKI;
+#[cfg(test)]
+use crate::config::pki::testing::CERT_VALIDITY;
+#[cfg(test)]
+use crate::config::pki::PKITrustRoot;
+#[cfg(test)]
+use crate::net::IPEndpointAddr;
+#[cfg(test)]
+use crate::version::Version;
+#[cfg(test)]
+use crate::version::VersionPERCodec;
+
+#[cfg(test)]
+fn test_signer_with_ekus(
+    pki: &TestPKI,
+    ekus: &[&str]
+) -> Signer {
+    let name = "test-signer.nowhere.com";
+    let cert = pki
+        .server_ca()
+        .issue(&TestCertSpec::valid(
+            name,
+            vec![IPEndpointAddr::name(String::from(name))],
+            ekus.iter().map(|eku| String::from(*eku)).collect(),
+            CERT_VALIDITY
+        ))
+        .unwrap();
+
+    Signer::new(cert.key().clone(), cert.chain()).unwrap()
+}
+
+#[cfg(test)]
+fn test_signer(pki: &TestPKI) -> Signer {
+    test_signer_with_ekus(pki, &[DEFAULT_SIGNER_EKU])
+}
+
+#[cfg(test)]
+fn test_store(ca: &Path) -> X509Store {
+    let yaml = format!("root-certs:\n  - {}\n", ca.display());
+    let root: PKITrustRoot = serde_yaml::from_str(&yaml).unwrap();
+
+    root.load(None, None, X509PurposeId::ANY).unwrap()
+}
+
+#[cfg(test)]
+fn test_verifier(ca: &Path) -> Verifier {
+    Verifier::new(test_store(ca))
+}
+
+#[test]
+fn test_sign_verify() {
+    let pki = TestPKI::generate().unwrap();
+    let signer = test_signer(&pki);
+    let verifier = test_verifier(&pki.path("server/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let version = Version::new(1, 2, 3);
+    let envelope = signer.sign(&mut codec, &version).unwrap();
+    let verified = verifier.verify(&mut codec, &envelope).unwrap();
+
+    assert_eq!(&version, verified.val());
+    assert_eq!(
+        "C=US, O=Constellation, OU=Tests, CN=test-signer.nowhere.com",
+        verified.signer().subject()
+    );
+    assert_eq!(
+        &[String::from("test-signer.nowhere.com")],
+        verified.signer().dns_names()
+    );
+}
+
+#[test]
+fn test_sign_codec_round_trip() {
+    let pki = TestPKI::generate().unwrap();
+    let signer = test_signer(&pki);
+    let verifier = test_verifier(&pki.path("server/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let mut envelope_codec = SignedEnvelopeCodec::<4096>::create(()).unwrap();
+    let version = Version::new(4, 5, 6);
+    let envelope = signer.sign(&mut codec, &version).unwrap();
+    let encoded = envelope_codec.encode_to_vec(&envelope).unwrap();
+    let (decoded, nbytes) = envelope_codec.decode(&encoded).unwrap();
+
+    assert_eq!(encoded.len(), nbytes);
+    assert_eq!(envelope, decoded);
+
+    let verified = verifier.verify(&mut codec, &decoded).unwrap();
+
+    assert_eq!(&version, verified.val());
+    assert_eq!(
+        Err(SignedEnvelopeDecodeError::Truncated),
+        envelope_codec.decode(&encoded[..encoded.len() - 1])
+    );
+}
+
+#[test]
+fn test_verify_untrusted() {
+    let pki = TestPKI::generate().unwrap();
+    let signer = test_signer(&pki);
+    let verifier = test_verifier(&pki.path("client/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+    let err = verifier.verify_bytes(&envelope).err().unwrap();
+
+    assert!(matches!(err, VerifyError::Untrusted { .. }));
+    assert_eq!(ErrorScope::Msg, err.scope());
+}
+
+#[test]
+fn test_verify_tampered() {
+    let pki = TestPKI::generate().unwrap();
+    let signer = test_signer(&pki);
+    let verifier = test_verifier(&pki.path("server/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+    let (chain, _, signature) = envelope.take();
+    let payload = codec.encode_to_vec(&Version::new(1, 2, 4)).unwrap();
+    let tampered = SignedEnvelope::new(chain, payload, signature);
+    let err = verifier.verify(&mut codec, &tampered).err().unwrap();
+
+    assert!(matches!(err, VerifyError::BadSignature));
+}
+
+#[test]
+fn test_verify_tls_cert() {
+    let pki = TestPKI::generate().unwrap();
+    let verifier = test_verifier(&pki.path("server/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+
+    // The server certificate has no extended key usages.
+    let signer = Signer::from_identity(&pki.server_identity()).unwrap();
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+    let err = verifier.verify_bytes(&envelope).err().unwrap();
+
+    assert!(matches!(err, VerifyError::MissingEKU { .. }));
+    assert_eq!(ErrorScope::Msg, err.scope());
+
+    // A certificate only for TLS servers.
+    let signer = test_signer_with_ekus(&pki, &["serverAuth"]);
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+
+    assert!(matches!(
+        verifier.verify_bytes(&envelope),
+        Err(VerifyError::MissingEKU { .. })
+    ));
+}
+
+#[test]
+fn test_verify_custom_eku() {
+    let pki = TestPKI::generate().unwrap();
+    let store = test_store(&pki.path("server/ca_cert.pem"));
+    let verifier = Verifier::with_eku(store, "1.3.6.1.4.1.99999.1").unwrap();
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let signer = test_signer_with_ekus(&pki, &["1.3.6.1.4.1.99999.1"]);
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+
+    assert!(verifier.verify_bytes(&envelope).is_ok());
+
+    // A code signing certificate is not enough.
+    let envelope = test_signer(&pki)
+        .sign(&mut codec, &Version::new(1, 2, 3))
+        .unwrap();
+
+    assert!(matches!(
+        verifier.verify_bytes(&envelope),
+        Err(VerifyError::MissingEKU { .. })
+    ));
+
+    let store = test_store(&pki.path("server/ca_cert.pem"));
+
+    assert!(Verifier::with_eku(store, "notAnEKU").is_err());
+}
+
+#[test]
+fn test_verify_bad_key_usage() {
+    let pki = TestPKI::generate().unwrap();
+    let verifier = test_verifier(&pki.path("server/ca_cert.pem"));
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let ca = pki.server_ca();
+    let key = pki.ocsp_responder().key().clone();
+    let mut name = X509NameBuilder::new().unwrap();
+    let mut builder = X509Builder::new().unwrap();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    builder.set_version(2).unwrap();
+    builder
+        .set_serial_number(
+            &BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()
+        )
+        .unwrap();
+    name.append_entry_by_text("CN", "test-signer.nowhere.com")
+        .unwrap();
+    builder.set_subject_name(&name.build()).unwrap();
+    builder.set_issuer_name(ca.cert().subject_name()).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::from_unix(now - 3600).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::from_unix(now + 3600).unwrap())
+        .unwrap();
+    let aki = AuthorityKeyIdentifier::new()
+        .keyid(false)
+        .build(&builder.x509v3_context(Some(ca.cert()), None))
+        .unwrap();
+
+    builder.append_extension(aki).unwrap();
+    builder
+        .append_extension(KeyUsage::new().key_agreement().build().unwrap())
+        .unwrap();
+    builder
+        .append_extension(
+            ExtendedKeyUsage::new().code_signing().build().unwrap()
+        )
+        .unwrap();
+    builder.sign(ca.key(), MessageDigest::sha384()).unwrap();
+
+    let signer = Signer::new(key, &[builder.build()]).unwrap();
+    let envelope = signer.sign(&mut codec, &Version::new(1, 2, 3)).unwrap();
+    let err = verifier.verify_bytes(&envelope).err().unwrap();
+
+    assert!(matches!(err, VerifyError::BadKeyUsage));
+    assert_eq!(ErrorScope::Msg, err.scope());
+}
+
+#[test]
+fn test_identity_key_mismatch() {
+    let pki = TestPKI::generate().unwrap();
+    let key =
+        PKIFileKey::new(pki.path("client/private/test_client_key.pem"), None);
+    let identity = PKIIdentity::new(
+        pki.path("server/certs/test_server_cert.pem"),
+        PKIKey::File(key)
+    );
+
+    assert!(matches!(
+        Signer::from_identity(&identity),
+        Err(PKIIdentityLoadError::KeyMismatch)
+    ));
+}
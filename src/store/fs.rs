@@ -0,0 +1,498 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Filesystem-backed [ObjectStore] implementation.
+//!
+//! Objects are stored in a directory tree laid out as follows:
+//!
+//! ```text
+//! <dir>/<algorithm>/<first byte>/<remaining bytes>
+//! <dir>/<algorithm>/tmp/
+//! ```
+//!
+//! The algorithm is given by [HashID::name], and the bytes of the
+//! hash are written in lowercase hex.  Spreading objects over
+//! directories named by the first byte of their hash avoids very
+//! large directories.
+//!
+//! Objects are first written to a temporary file in the `tmp`
+//! directory, then renamed into place.  This ensures that a partially
+//! written object is never visible, even if the process exits
+//! during a write.  Both the file and its directory are synced, so
+//! that a completed write survives a crash.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write as FmtWrite;
+use std::fs::create_dir_all;
+use std::fs::metadata;
+use std::fs::read;
+use std::fs::read_dir;
+use std::fs::remove_file;
+use std::fs::rename;
+#[cfg(unix)]
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::HashAlgo;
+use crate::hashid::HashID;
+use crate::store::ObjectInfo;
+use crate::store::ObjectStore;
+
+/// Name of the directory holding partially-written objects.
+const TMP_DIR: &str = "tmp";
+
+/// [ObjectStore] that holds objects in a directory.
+///
+/// See the [module documentation](crate::store::fs) for details on
+/// the layout.  Every object is hashed when it is read, and objects
+/// whose contents do not match their IDs are removed and reported as
+/// [Corrupt](FileStoreError::Corrupt).
+#[derive(Clone)]
+pub struct FileObjectStore<Algo: HashAlgo> {
+    algo: Algo,
+    /// Directory for objects using `algo`.
+    dir: PathBuf,
+    /// Length of hash values in bytes.
+    hash_len: usize
+}
+
+/// Errors that can occur in a [FileObjectStore].
+#[derive(Debug)]
+pub enum FileStoreError {
+    /// An IO error occurred.
+    IO {
+        /// The IO error.
+        error: Error
+    },
+    /// An object did not match its ID.
+    Corrupt {
+        /// Path to the object.
+        path: PathBuf
+    }
+}
+
+/// Write `bytes` as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        // Writing to a String cannot fail.
+        let _ = write!(out, "{:02x}", byte);
+    }
+
+    out
+}
+
+/// Parse lowercase hex, returning `None` if it is malformed.
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(s.len() / 2);
+    let mut chars = s.chars();
+
+    while let (Some(hi), Some(lo)) = (chars.next(), chars.next()) {
+        if hi.is_ascii_uppercase() || lo.is_ascii_uppercase() {
+            return None;
+        }
+
+        out.push(((hi.to_digit(16)? << 4) | lo.to_digit(16)?) as u8);
+    }
+
+    Some(out)
+}
+
+impl<Algo> FileObjectStore<Algo>
+where
+    Algo: HashAlgo
+{
+    /// Create a new `FileObjectStore` using `algo`, holding objects
+    /// under `dir`.
+    ///
+    /// This will create any needed directories.  Any objects already
+    /// present under `dir` will be available from the store.
+    pub fn new<P>(
+        algo: Algo,
+        dir: P
+    ) -> Result<Self, FileStoreError>
+    where
+        P: AsRef<Path> {
+        let null = algo.null_hash();
+        let dir = dir.as_ref().join(null.name());
+        let hash_len = null.bytes().len();
+
+        create_dir_all(dir.join(TMP_DIR))
+            .map_err(|err| FileStoreError::IO { error: err })?;
+
+        Ok(FileObjectStore {
+            algo: algo,
+            dir: dir,
+            hash_len: hash_len
+        })
+    }
+
+    /// Get the directory holding objects for this store's algorithm.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the path at which the object with ID `id` is stored.
+    #[inline]
+    pub fn path(
+        &self,
+        id: &Algo::HashID
+    ) -> PathBuf {
+        let bytes = id.bytes();
+
+        self.dir.join(hex(&bytes[..1])).join(hex(&bytes[1..]))
+    }
+
+    /// Remove temporary files older than `max_age`.
+    ///
+    /// Temporary files are left behind only if the process exits
+    /// during a write, so this should be called periodically, or at
+    /// startup.  Returns the number of files removed.
+    pub fn remove_stale_tmp(
+        &self,
+        max_age: Duration
+    ) -> Result<usize, FileStoreError> {
+        let now = SystemTime::now();
+        let mut count = 0;
+
+        for entry in read_dir(self.dir.join(TMP_DIR))
+            .map_err(|err| FileStoreError::IO { error: err })?
+        {
+            let entry =
+                entry.map_err(|err| FileStoreError::IO { error: err })?;
+            let modified = entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .map_err(|err| FileStoreError::IO { error: err })?;
+
+            if now.duration_since(modified).is_ok_and(|age| age >= max_age) {
+                match remove_file(entry.path()) {
+                    Ok(()) => count += 1,
+                    Err(err) if err.kind() == ErrorKind::NotFound => {}
+                    Err(err) => return Err(FileStoreError::IO { error: err })
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Atomically write `data` to `path`.
+    fn write_atomic(
+        &self,
+        path: &Path,
+        data: &[u8]
+    ) -> Result<(), Error> {
+        let tmp = self
+            .dir
+            .join(TMP_DIR)
+            .join(format!("{:016x}", rand::random::<u64>()));
+        let mut file =
+            OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+
+        let res =
+            file.write_all(data)
+                .and_then(|_| file.sync_all())
+                .and_then(|_| {
+                    if let Some(parent) = path.parent() {
+                        create_dir_all(parent)?;
+                    }
+
+                    rename(&tmp, path)?;
+
+                    // The new directory entry is only durable once
+                    // the directory itself is synced.
+                    #[cfg(unix)]
+                    if let Some(parent) = path.parent() {
+                        File::open(parent)?.sync_all()?;
+                    }
+
+                    Ok(())
+                });
+
+        if res.is_err() {
+            let _ = remove_file(&tmp);
+        }
+
+        res
+    }
+}
+
+impl<Algo> ObjectStore for FileObjectStore<Algo>
+where
+    Algo: HashAlgo,
+    Algo::HashID: PartialEq
+{
+    type Algo = Algo;
+    type Error = FileStoreError;
+
+    #[inline]
+    fn algo(&self) -> &Algo {
+        &self.algo
+    }
+
+    fn put(
+        &self,
+        data: &[u8]
+    ) -> Result<Algo::HashID, FileStoreError> {
+        let id = self.algo.hash_bytes(data);
+        let path = self.path(&id);
+
+        if !path.exists() {
+            self.write_atomic(&path, data)
+                .map_err(|err| FileStoreError::IO { error: err })?;
+        }
+
+        Ok(id)
+    }
+
+    fn get(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<Option<Vec<u8>>, FileStoreError> {
+        let path = self.path(id);
+
+        match read(&path) {
+            Ok(data) => {
+                if &self.algo.hash_bytes(&data) == id {
+                    Ok(Some(data))
+                } else {
+                    // Remove it, so that it can be stored again.
+                    let _ = remove_file(&path);
+
+                    Err(FileStoreError::Corrupt { path: path })
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(FileStoreError::IO { error: err })
+        }
+    }
+
+    #[inline]
+    fn contains(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<bool, FileStoreError> {
+        Ok(self.info(id)?.is_some())
+    }
+
+    fn info(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<Option<ObjectInfo>, FileStoreError> {
+        match metadata(self.path(id)) {
+            Ok(meta) => {
+                let modified = meta
+                    .modified()
+                    .map_err(|err| FileStoreError::IO { error: err })?;
+
+                Ok(Some(ObjectInfo::new(meta.len(), modified)))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(FileStoreError::IO { error: err })
+        }
+    }
+
+    fn remove(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<bool, FileStoreError> {
+        match remove_file(self.path(id)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(FileStoreError::IO { error: err })
+        }
+    }
+
+    fn ids(&self) -> Result<Vec<Algo::HashID>, FileStoreError> {
+        let mut out = Vec::new();
+
+        for entry in read_dir(&self.dir)
+            .map_err(|err| FileStoreError::IO { error: err })?
+        {
+            let entry =
+                entry.map_err(|err| FileStoreError::IO { error: err })?;
+            let prefix = match entry.file_name().to_str().and_then(unhex) {
+                Some(prefix) if prefix.len() == 1 => prefix,
+                // Skip the temp directory and anything else unexpected.
+                _ => continue
+            };
+
+            for entry in read_dir(entry.path())
+                .map_err(|err| FileStoreError::IO { error: err })?
+            {
+                let entry =
+                    entry.map_err(|err| FileStoreError::IO { error: err })?;
+
+                if let Some(rest) = entry.file_name().to_str().and_then(unhex) {
+                    if rest.len() + 1 == self.hash_len {
+                        let mut bytes = prefix.clone();
+
+                        bytes.extend_from_slice(&rest);
+
+                        if let Ok(id) = self.algo.wrap_hashed_bytes(&bytes) {
+                            out.push(id)
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+impl ScopedError for FileStoreError {
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            FileStoreError::IO { error } => error.scope(),
+            FileStoreError::Corrupt { .. } => ErrorScope::Msg
+        }
+    }
+}
+
+impl Display for FileStoreError {
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            FileStoreError::IO { error } => error.fmt(f),
+            FileStoreError::Corrupt { path } => {
+                write!(f, "object {} does not match its ID", path.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use std::fs::write;
+
+#[cfg(test)]
+use crate::hashid::CompoundHashAlgo;
+#[cfg(test)]
+use crate::store::check_store;
+#[cfg(test)]
+use crate::store::check_store_codec;
+
+#[test]
+fn test_file_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+
+    check_store(&store);
+}
+
+#[test]
+fn test_file_store_codec() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+
+    check_store_codec(&store);
+}
+
+#[test]
+fn test_file_store_layout() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+    let id = store.put(b"test object").unwrap();
+    let hex = hex(id.bytes());
+    let expected = dir.path().join(id.name()).join(&hex[..2]).join(&hex[2..]);
+
+    assert_eq!(expected, store.path(&id));
+    assert!(expected.is_file());
+
+    // A second store over the same directory sees the object.
+    let other =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+
+    assert_eq!(vec![id.clone()], other.ids().unwrap());
+    assert_eq!(
+        Some(&b"test object"[..]),
+        other.get(&id).unwrap().as_deref()
+    );
+}
+
+#[test]
+fn test_file_store_corrupt() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+    let id = store.put(b"test object").unwrap();
+
+    write(store.path(&id), b"corrupted").unwrap();
+
+    assert!(matches!(
+        store.get(&id),
+        Err(FileStoreError::Corrupt { .. })
+    ));
+    assert!(!store.contains(&id).unwrap());
+
+    // Storing it again repairs it.
+    store.put(b"test object").unwrap();
+
+    assert_eq!(
+        Some(&b"test object"[..]),
+        store.get(&id).unwrap().as_deref()
+    );
+}
+
+#[test]
+fn test_file_store_stale_tmp() {
+    let dir = tempfile::tempdir().unwrap();
+    let store =
+        FileObjectStore::new(CompoundHashAlgo::default(), dir.path()).unwrap();
+
+    write(store.dir().join(TMP_DIR).join("partial"), b"partial").unwrap();
+
+    assert_eq!(
+        0,
+        store.remove_stale_tmp(Duration::from_secs(3600)).unwrap()
+    );
+    assert_eq!(1, store.remove_stale_tmp(Duration::ZERO).unwrap());
+    assert!(store.ids().unwrap().is_empty());
+}
+
+#[test]
+fn test_hex() {
+    assert_eq!("00ff1a", hex(&[0x00, 0xff, 0x1a]));
+    assert_eq!(Some(vec![0x00, 0xff, 0x1a]), unhex("00ff1a"));
+    assert_eq!(None, unhex("00FF1A"));
+    assert_eq!(None, unhex("0"));
+    assert_eq!(None, unhex("+1"));
+    assert_eq!(None, unhex(TMP_DIR));
+}
@@ -0,0 +1,215 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! In-memory [ObjectStore] implementation.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::error::MutexPoison;
+use crate::hashid::HashAlgo;
+use crate::store::ObjectInfo;
+use crate::store::ObjectStore;
+
+/// [ObjectStore] that holds objects in memory.
+///
+/// Objects are hashed when they are stored, and cannot be modified
+/// afterward, so reads always match their IDs.  Clones of a
+/// `MemObjectStore` share the same objects.
+pub struct MemObjectStore<Algo: HashAlgo> {
+    algo: Algo,
+    objects: Arc<RwLock<HashMap<Algo::HashID, MemObject>>>
+}
+
+struct MemObject {
+    data: Arc<[u8]>,
+    stored: SystemTime
+}
+
+impl<Algo> MemObjectStore<Algo>
+where
+    Algo: HashAlgo,
+    Algo::HashID: Clone + Eq + Hash
+{
+    /// Create a new, empty `MemObjectStore` using `algo`.
+    #[inline]
+    pub fn new(algo: Algo) -> Self {
+        MemObjectStore {
+            algo: algo,
+            objects: Arc::new(RwLock::new(HashMap::new()))
+        }
+    }
+
+    /// Get the number of objects in the store.
+    #[inline]
+    pub fn len(&self) -> Result<usize, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.len())
+    }
+
+    /// Check whether the store is empty.
+    #[inline]
+    pub fn is_empty(&self) -> Result<bool, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.is_empty())
+    }
+
+    /// Get the object with ID `id` as a shared slice, if it is
+    /// present.
+    ///
+    /// This avoids copying the object, unlike [ObjectStore::get].
+    #[inline]
+    pub fn get_shared(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<Option<Arc<[u8]>>, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.get(id).map(|obj| obj.data.clone()))
+    }
+}
+
+impl<Algo> Clone for MemObjectStore<Algo>
+where
+    Algo: HashAlgo + Clone
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        MemObjectStore {
+            algo: self.algo.clone(),
+            objects: self.objects.clone()
+        }
+    }
+}
+
+impl<Algo> ObjectStore for MemObjectStore<Algo>
+where
+    Algo: HashAlgo,
+    Algo::HashID: Clone + Eq + Hash
+{
+    type Algo = Algo;
+    type Error = MutexPoison;
+
+    #[inline]
+    fn algo(&self) -> &Algo {
+        &self.algo
+    }
+
+    fn put(
+        &self,
+        data: &[u8]
+    ) -> Result<Algo::HashID, MutexPoison> {
+        let id = self.algo.hash_bytes(data);
+        let mut guard = self.objects.write().map_err(|_| MutexPoison)?;
+
+        guard.entry(id.clone()).or_insert_with(|| MemObject {
+            data: Arc::from(data),
+            stored: SystemTime::now()
+        });
+
+        Ok(id)
+    }
+
+    #[inline]
+    fn get(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<Option<Vec<u8>>, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.get(id).map(|obj| obj.data.to_vec()))
+    }
+
+    #[inline]
+    fn contains(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<bool, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.contains_key(id))
+    }
+
+    #[inline]
+    fn info(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<Option<ObjectInfo>, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard
+            .get(id)
+            .map(|obj| ObjectInfo::new(obj.data.len() as u64, obj.stored)))
+    }
+
+    #[inline]
+    fn remove(
+        &self,
+        id: &Algo::HashID
+    ) -> Result<bool, MutexPoison> {
+        let mut guard = self.objects.write().map_err(|_| MutexPoison)?;
+
+        Ok(guard.remove(id).is_some())
+    }
+
+    #[inline]
+    fn ids(&self) -> Result<Vec<Algo::HashID>, MutexPoison> {
+        let guard = self.objects.read().map_err(|_| MutexPoison)?;
+
+        Ok(guard.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+use crate::hashid::CompoundHashAlgo;
+#[cfg(test)]
+use crate::store::check_store;
+#[cfg(test)]
+use crate::store::check_store_codec;
+
+#[test]
+fn test_mem_store() {
+    let store = MemObjectStore::new(CompoundHashAlgo::default());
+
+    check_store(&store);
+    assert!(store.is_empty().unwrap());
+}
+
+#[test]
+fn test_mem_store_codec() {
+    let store = MemObjectStore::new(CompoundHashAlgo::default());
+
+    check_store_codec(&store);
+}
+
+#[test]
+fn test_mem_store_shared() {
+    let store = MemObjectStore::new(CompoundHashAlgo::default());
+    let clone = store.clone();
+    let id = store.put(b"shared object").unwrap();
+
+    assert_eq!(1, clone.len().unwrap());
+    assert_eq!(
+        Some(&b"shared object"[..]),
+        clone.get_shared(&id).unwrap().as_deref()
+    );
+}
@@ -0,0 +1,502 @@
+// Copyright © 2024-25 The Johns Hopkins Applied Physics Laboratory LLC.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License,
+// version 3, as published by the Free Software Foundation.  If you
+// would like to purchase a commercial license for this software, please
+// contact APL’s Tech Transfer at 240-592-0817 or
+// techtransfer@jhuapl.edu.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Content-addressed object stores.
+//!
+//! This module provides the [ObjectStore] trait, which stores objects
+//! keyed by their [HashID], as computed by a [HashAlgo].  Because
+//! objects are named by their contents, storing the same object
+//! twice results in a single copy, and any object can be checked
+//! against its name when it is read back.  This allows objects that
+//! are received over the network to be cached and deduplicated.
+//!
+//! Two implementations are provided:
+//!
+//! * [MemObjectStore](crate::store::mem::MemObjectStore), which holds objects
+//!   in memory.
+//!
+//! * [FileObjectStore](crate::store::fs::FileObjectStore), which holds objects
+//!   in a directory on the filesystem.
+//!
+//! Objects are never removed implicitly.  Instead, [ObjectStore::gc]
+//! consults a [Retain] instance to decide which objects to keep.
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::hash::Hash;
+use std::time::SystemTime;
+
+use crate::codec::DatagramCodec;
+use crate::error::ErrorScope;
+use crate::error::ScopedError;
+use crate::hashid::HashAlgo;
+
+pub mod fs;
+pub mod mem;
+
+/// Information about an object in an [ObjectStore].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ObjectInfo {
+    /// Size of the object in bytes.
+    size: u64,
+    /// Time at which the object was stored.
+    stored: SystemTime
+}
+
+/// Statistics from a garbage collection pass.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GCStats {
+    /// Number of objects that were kept.
+    kept: usize,
+    /// Number of objects that were removed.
+    removed: usize,
+    /// Total size of the objects that were removed.
+    removed_bytes: u64
+}
+
+/// Errors that can occur when storing an object under an expected ID.
+#[derive(Debug)]
+pub enum PutVerifiedError<Inner> {
+    /// Error in the underlying [ObjectStore].
+    Inner {
+        /// The underlying error.
+        error: Inner
+    },
+    /// The object did not match the expected ID.
+    Mismatch
+}
+
+/// Errors that can occur when storing or loading an object through a
+/// [DatagramCodec].
+#[derive(Debug)]
+pub enum ObjectCodecError<Codec, Store> {
+    /// Error in the [DatagramCodec].
+    Codec {
+        /// The codec error.
+        error: Codec
+    },
+    /// Error in the underlying [ObjectStore].
+    Store {
+        /// The store error.
+        error: Store
+    }
+}
+
+/// Hooks for garbage collection in an [ObjectStore].
+///
+/// This is consulted by [ObjectStore::gc] for each object in the
+/// store, to decide whether or not it should be kept.  This trait is
+/// implemented for closures, as well as for [HashSet]s of IDs (which
+/// keep exactly the objects in the set).
+pub trait Retain<ID> {
+    /// Decide whether to keep the object with `id`.
+    fn retain(
+        &mut self,
+        id: &ID,
+        info: &ObjectInfo
+    ) -> bool;
+
+    /// Notification that the object with `id` was removed.
+    ///
+    /// This is called after [retain](Retain::retain) returns `false`
+    /// and the object has been removed.  The default implementation
+    /// does nothing.
+    #[inline]
+    fn removed(
+        &mut self,
+        _id: &ID,
+        _info: &ObjectInfo
+    ) {
+    }
+}
+
+/// Trait for content-addressed object stores.
+///
+/// Objects are stored and retrieved using the [HashID] computed by
+/// [algo](ObjectStore::algo).  Implementations must verify that the
+/// contents of an object match its ID when it is read, and must never
+/// return an object that does not match.  All operations take `&self`,
+/// and implementations may be shared between threads.
+///
+/// [HashID]: crate::hashid::HashID
+pub trait ObjectStore {
+    /// The [HashAlgo] used to compute IDs.
+    type Algo: HashAlgo;
+    /// Errors that can occur in store operations.
+    type Error: Display + ScopedError;
+
+    /// Get the [HashAlgo] used to compute IDs.
+    fn algo(&self) -> &Self::Algo;
+
+    /// Store the object `data`, returning its ID.
+    ///
+    /// If the object is already present, this has no effect.
+    fn put(
+        &self,
+        data: &[u8]
+    ) -> Result<<Self::Algo as HashAlgo>::HashID, Self::Error>;
+
+    /// Get the object with ID `id`, if it is present.
+    fn get(
+        &self,
+        id: &<Self::Algo as HashAlgo>::HashID
+    ) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Check whether the object with ID `id` is present.
+    fn contains(
+        &self,
+        id: &<Self::Algo as HashAlgo>::HashID
+    ) -> Result<bool, Self::Error>;
+
+    /// Get the [ObjectInfo] for the object with ID `id`, if it is
+    /// present.
+    fn info(
+        &self,
+        id: &<Self::Algo as HashAlgo>::HashID
+    ) -> Result<Option<ObjectInfo>, Self::Error>;
+
+    /// Remove the object with ID `id`, returning whether it was
+    /// present.
+    fn remove(
+        &self,
+        id: &<Self::Algo as HashAlgo>::HashID
+    ) -> Result<bool, Self::Error>;
+
+    /// Get the IDs of all objects in the store.
+    fn ids(&self)
+        -> Result<Vec<<Self::Algo as HashAlgo>::HashID>, Self::Error>;
+
+    /// Store the object `data`, which is expected to have ID `id`.
+    ///
+    /// This is intended for objects received from elsewhere under a
+    /// claimed ID.  The object will not be stored if it does not match
+    /// the ID.
+    fn put_verified(
+        &self,
+        id: &<Self::Algo as HashAlgo>::HashID,
+        data: &[u8]
+    ) -> Result<(), PutVerifiedError<Self::Error>>
+    where
+        <Self::Algo as HashAlgo>::HashID: PartialEq {
+        if &self.algo().hash_bytes(data) == id {
+            self.put(data)
+                .map_err(|err| PutVerifiedError::Inner { error: err })?;
+
+            Ok(())
+        } else {
+            Err(PutVerifiedError::Mismatch)
+        }
+    }
+
+    /// Store `val` as encoded by `codec`, returning its ID.
+    ///
+    /// The resulting ID will be the same as that produced by
+    /// [HashAlgo::hashid].
+    fn put_encoded<T, Codec>(
+        &self,
+        codec: &mut Codec,
+        val: &T
+    ) -> Result<
+        <Self::Algo as HashAlgo>::HashID,
+        ObjectCodecError<Codec::EncodeError, Self::Error>
+    >
+    where
+        Codec: DatagramCodec<T> {
+        let encoded = codec
+            .encode_to_vec(val)
+            .map_err(|err| ObjectCodecError::Codec { error: err })?;
+
+        self.put(&encoded)
+            .map_err(|err| ObjectCodecError::Store { error: err })
+    }
+
+    /// Get the object with ID `id`, and decode it with `codec`.
+    fn get_decoded<T, Codec>(
+        &self,
+        codec: &mut Codec,
+        id: &<Self::Algo as HashAlgo>::HashID
+    ) -> Result<Option<T>, ObjectCodecError<Codec::DecodeError, Self::Error>>
+    where
+        Codec: DatagramCodec<T> {
+        match self
+            .get(id)
+            .map_err(|err| ObjectCodecError::Store { error: err })?
+        {
+            Some(data) => {
+                let (val, _) = codec
+                    .decode(&data)
+                    .map_err(|err| ObjectCodecError::Codec { error: err })?;
+
+                Ok(Some(val))
+            }
+            None => Ok(None)
+        }
+    }
+
+    /// Run a garbage collection pass, using `retain` to decide which
+    /// objects to keep.
+    fn gc<R>(
+        &self,
+        retain: &mut R
+    ) -> Result<GCStats, Self::Error>
+    where
+        R: Retain<<Self::Algo as HashAlgo>::HashID> {
+        let mut stats = GCStats::default();
+
+        for id in self.ids()? {
+            // Objects may disappear while the pass is running.
+            if let Some(info) = self.info(&id)? {
+                if retain.retain(&id, &info) {
+                    stats.kept += 1;
+                } else if self.remove(&id)? {
+                    stats.removed += 1;
+                    stats.removed_bytes += info.size;
+                    retain.removed(&id, &info);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+impl ObjectInfo {
+    /// Create a new `ObjectInfo` from its components.
+    #[inline]
+    pub fn new(
+        size: u64,
+        stored: SystemTime
+    ) -> Self {
+        ObjectInfo {
+            size: size,
+            stored: stored
+        }
+    }
+
+    /// Get the size of the object in bytes.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the time at which the object was stored.
+    #[inline]
+    pub fn stored(&self) -> SystemTime {
+        self.stored
+    }
+}
+
+impl GCStats {
+    /// Get the number of objects that were kept.
+    #[inline]
+    pub fn kept(&self) -> usize {
+        self.kept
+    }
+
+    /// Get the number of objects that were removed.
+    #[inline]
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Get the total size of the objects that were removed.
+    #[inline]
+    pub fn removed_bytes(&self) -> u64 {
+        self.removed_bytes
+    }
+}
+
+impl<ID, F> Retain<ID> for F
+where
+    F: FnMut(&ID, &ObjectInfo) -> bool
+{
+    #[inline]
+    fn retain(
+        &mut self,
+        id: &ID,
+        info: &ObjectInfo
+    ) -> bool {
+        self(id, info)
+    }
+}
+
+impl<ID> Retain<ID> for HashSet<ID>
+where
+    ID: Eq + Hash
+{
+    #[inline]
+    fn retain(
+        &mut self,
+        id: &ID,
+        _info: &ObjectInfo
+    ) -> bool {
+        self.contains(id)
+    }
+}
+
+impl<Inner> ScopedError for PutVerifiedError<Inner>
+where
+    Inner: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            PutVerifiedError::Inner { error } => error.scope(),
+            PutVerifiedError::Mismatch => ErrorScope::Msg
+        }
+    }
+}
+
+impl<Codec, Store> ScopedError for ObjectCodecError<Codec, Store>
+where
+    Codec: ScopedError,
+    Store: ScopedError
+{
+    #[inline]
+    fn scope(&self) -> ErrorScope {
+        match self {
+            ObjectCodecError::Codec { error } => error.scope(),
+            ObjectCodecError::Store { error } => error.scope()
+        }
+    }
+}
+
+impl<Inner> Display for PutVerifiedError<Inner>
+where
+    Inner: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            PutVerifiedError::Inner { error } => error.fmt(f),
+            PutVerifiedError::Mismatch => {
+                write!(f, "object does not match expected ID")
+            }
+        }
+    }
+}
+
+impl<Codec, Store> Display for ObjectCodecError<Codec, Store>
+where
+    Codec: Display,
+    Store: Display
+{
+    fn fmt(
+        &self,
+        f: &mut Formatter<'_>
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            ObjectCodecError::Codec { error } => error.fmt(f),
+            ObjectCodecError::Store { error } => error.fmt(f)
+        }
+    }
+}
+
+/// Exercise the common behavior of an [ObjectStore].
+#[cfg(test)]
+fn check_store<Store>(store: &Store)
+where
+    Store: ObjectStore,
+    Store::Error: std::fmt::Debug,
+    <Store::Algo as HashAlgo>::HashID:
+        Clone + std::fmt::Debug + Eq + Hash + PartialEq {
+    let first = store.put(b"first object").unwrap();
+    let second = store.put(b"second object").unwrap();
+    let again = store.put(b"first object").unwrap();
+    let missing = store.algo().hash_bytes(b"missing object");
+
+    assert_eq!(first, again);
+    assert_eq!(first, store.algo().hash_bytes(b"first object"));
+    assert_eq!(
+        Some(&b"first object"[..]),
+        store.get(&first).unwrap().as_deref()
+    );
+    assert!(store.contains(&second).unwrap());
+    assert!(!store.contains(&missing).unwrap());
+    assert_eq!(None, store.get(&missing).unwrap());
+    assert_eq!(13, store.info(&second).unwrap().unwrap().size());
+
+    let mut ids = store.ids().unwrap();
+
+    ids.sort_by(|a, b| {
+        use crate::hashid::HashID;
+
+        a.bytes().cmp(b.bytes())
+    });
+
+    let mut expected = vec![first.clone(), second.clone()];
+
+    expected.sort_by(|a, b| {
+        use crate::hashid::HashID;
+
+        a.bytes().cmp(b.bytes())
+    });
+
+    assert_eq!(expected, ids);
+
+    assert!(store.put_verified(&missing, b"first object").is_err());
+    store.put_verified(&missing, b"missing object").unwrap();
+    assert!(store.contains(&missing).unwrap());
+
+    // Keep only the first object.
+    let mut live = HashSet::new();
+
+    live.insert(first.clone());
+
+    let stats = store.gc(&mut live).unwrap();
+
+    assert_eq!(1, stats.kept());
+    assert_eq!(2, stats.removed());
+    assert_eq!(27, stats.removed_bytes());
+    assert!(store.contains(&first).unwrap());
+    assert!(!store.contains(&second).unwrap());
+
+    // Closures can also be used.
+    let mut removed = Vec::new();
+    let stats = store
+        .gc(&mut |_: &_, info: &ObjectInfo| {
+            removed.push(info.size());
+
+            false
+        })
+        .unwrap();
+
+    assert_eq!(vec![12], removed);
+    assert_eq!(1, stats.removed());
+    assert!(!store.remove(&first).unwrap());
+    assert!(store.ids().unwrap().is_empty());
+}
+
+#[cfg(test)]
+fn check_store_codec<Store>(store: &Store)
+where
+    Store: ObjectStore,
+    Store::Error: std::fmt::Debug,
+    <Store::Algo as HashAlgo>::HashID: std::fmt::Debug + PartialEq {
+    use crate::version::Version;
+    use crate::version::VersionPERCodec;
+
+    let mut codec = VersionPERCodec::create(()).unwrap();
+    let val = Version::new(1, 4, 2);
+    let id = store.put_encoded(&mut codec, &val).unwrap();
+
+    assert_eq!(id, store.algo().hashid(&mut codec, &val).unwrap());
+    assert_eq!(Some(val), store.get_decoded(&mut codec, &id).unwrap());
+}
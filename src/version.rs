@@ -249,6 +249,22 @@ impl VersionRangeElem {
     }
 }
 
+impl VersionRange {
+    /// Check whether `version` falls within this range.
+    ///
+    /// Both bounds are inclusive, and are compared only to the
+    /// precision of the bound, so an upper bound of `2` will admit
+    /// `2.7.1`.  A missing bound is unlimited.
+    #[inline]
+    pub fn contains(
+        &self,
+        version: &Version
+    ) -> bool {
+        self.lower.as_ref().is_none_or(|lower| version >= lower) &&
+            self.upper.as_ref().is_none_or(|upper| version <= upper)
+    }
+}
+
 impl Display for FullVersion {
     fn fmt(
         &self,
@@ -1426,3 +1442,31 @@ fn test_version_range_elem_sub_eq() {
         assert_eq!(&lhs.eq(&rhs), expected)
     }
 }
+
+#[test]
+fn test_version_range_contains() {
+    let range = VersionRange {
+        lower: Some(VersionRangeElem::minor(1, 2)),
+        upper: Some(VersionRangeElem::major(2))
+    };
+    let tests = [
+        ((1, 1, 9), false),
+        ((1, 2, 0), true),
+        ((1, 9, 0), true),
+        ((2, 7, 1), true),
+        ((3, 0, 0), false)
+    ];
+
+    for (version, expected) in &tests {
+        let version = Version::new(version.0, version.1, version.2);
+
+        assert_eq!(&range.contains(&version), expected)
+    }
+
+    let unbounded = VersionRange {
+        lower: None,
+        upper: None
+    };
+
+    assert!(unbounded.contains(&Version::new(0, 0, 0)));
+}